@@ -78,8 +78,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             format: source_encoding,
             track_id: None,
             source: TrackApiSource::Local,
+            start_offset: None,
+            end_offset: None,
         },
         output_encoding,
+        Default::default(),
         None,
         None,
         None,
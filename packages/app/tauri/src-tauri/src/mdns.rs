@@ -12,6 +12,7 @@ pub struct MoosicBox {
     pub name: String,
     pub host: String,
     pub dns: String,
+    pub signing_key: Option<String>,
 }
 
 impl From<moosicbox_mdns::scanner::MoosicBox> for MoosicBox {
@@ -21,6 +22,7 @@ impl From<moosicbox_mdns::scanner::MoosicBox> for MoosicBox {
             name: value.name,
             host: format!("http://{}", value.host),
             dns: value.dns,
+            signing_key: value.signing_key,
         }
     }
 }
@@ -5,7 +5,7 @@
 use std::fs::File;
 use std::path::Path;
 
-use symphonia::core::audio::{AudioBuffer, SignalSpec};
+use symphonia::core::audio::{AudioBuffer, Signal, SignalSpec};
 use symphonia::core::codecs::{DecoderOptions, FinalizeResult, CODEC_TYPE_NULL};
 use symphonia::core::errors::Error;
 use symphonia::core::formats::{FormatOptions, FormatReader, Packet, SeekMode, SeekTo, Track};
@@ -77,6 +77,22 @@ impl AudioDecodeHandler {
         self
     }
 
+    /// Limits the decoded audio to the `start` to `end` seconds of the stream, e.g. for a virtual
+    /// track within a single-file album rip. Audio before `start` is dropped and the decode ends
+    /// once `end` is reached.
+    ///
+    /// The range is applied before any other filters. The decode needs to be seeked to at or
+    /// before `start`, see [`time_range_seek`].
+    pub fn with_time_range(mut self, start: f64, end: Option<f64>) -> Self {
+        self.filters.insert(
+            0,
+            Box::new(move |decoded, packet, track| {
+                trim_to_time_range(decoded, packet, track, start, end)
+            }),
+        );
+        self
+    }
+
     pub fn with_output(mut self, open_output: OpenAudioDecodeHandler) -> Self {
         self.open_decode_handlers.push(open_output);
         self
@@ -108,6 +124,10 @@ impl AudioDecodeHandler {
     ) -> Result<(), AudioDecodeError> {
         self.run_filters(&mut decoded, packet, track)?;
 
+        if decoded.frames() == 0 {
+            return Ok(());
+        }
+
         let len = self.outputs.len();
 
         for (i, output) in self.outputs.iter_mut().enumerate() {
@@ -142,6 +162,54 @@ impl AudioDecodeHandler {
     }
 }
 
+/// How far before the start of a time range to seek the decode to. Seeking drops whole packets, so
+/// this leaves room for the packet containing the start of the range to be trimmed instead.
+const TIME_RANGE_SEEK_PREROLL: f64 = 1.0;
+
+/// The position to seek a decode trimmed with [`AudioDecodeHandler::with_time_range`] to, for it to
+/// include the audio from `time` seconds on.
+#[must_use]
+pub fn time_range_seek(time: f64) -> Option<f64> {
+    Some(time - TIME_RANGE_SEEK_PREROLL).filter(|x| *x > 0.0)
+}
+
+/// The time in seconds of the start of the packet.
+fn packet_time(packet: &Packet, track: &Track, rate: u32) -> f64 {
+    track.codec_params.time_base.map_or_else(
+        || packet.ts() as f64 / f64::from(rate),
+        |time_base| {
+            let time = time_base.calc_time(packet.ts());
+            time.seconds as f64 + time.frac
+        },
+    )
+}
+
+fn trim_to_time_range(
+    decoded: &mut AudioBuffer<f32>,
+    packet: &Packet,
+    track: &Track,
+    start: f64,
+    end: Option<f64>,
+) -> Result<(), AudioDecodeError> {
+    let rate = decoded.spec().rate;
+    let packet_start = packet_time(packet, track, rate);
+
+    if end.is_some_and(|end| packet_start >= end) {
+        return Err(AudioDecodeError::StreamEnd);
+    }
+
+    let frames = decoded.frames();
+    let to_frame = |time: f64| {
+        (((time - packet_start) * f64::from(rate)).round().max(0.0) as usize).min(frames)
+    };
+    let first = to_frame(start);
+    let last = end.map_or(frames, to_frame).max(first);
+
+    decoded.trim(first, frames - last);
+
+    Ok(())
+}
+
 impl Default for AudioDecodeHandler {
     fn default() -> Self {
         Self::new()
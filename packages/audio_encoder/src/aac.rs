@@ -1,7 +1,8 @@
-use fdk_aac::enc::{BitRate, ChannelMode, Encoder, EncoderParams, Transport};
+pub use fdk_aac::enc::BitRate;
+use fdk_aac::enc::{ChannelMode, Encoder, EncoderParams, Transport};
 use thiserror::Error;
 
-use crate::EncodeInfo;
+use crate::{EncodeInfo, EncoderSettings, SettingsError};
 
 #[derive(Debug, Error)]
 pub enum EncoderError {
@@ -25,9 +26,15 @@ impl From<fdk_aac::enc::EncoderError> for EncoderError {
 }
 
 pub fn encoder_aac() -> Result<Encoder, EncoderError> {
+    encoder_aac_with_bit_rate(BitRate::VbrVeryHigh)
+}
+
+/// Creates a 44.1kHz stereo ADTS encoder with the given bit rate, e.g.
+/// `BitRate::Cbr(128_000)`.
+pub fn encoder_aac_with_bit_rate(bit_rate: BitRate) -> Result<Encoder, EncoderError> {
     let encoder = Encoder::new(EncoderParams {
         audio_object_type: fdk_aac::enc::AudioObjectType::Mpeg4LowComplexity,
-        bit_rate: BitRate::VbrVeryHigh,
+        bit_rate,
         sample_rate: 44_100,
         transport: Transport::Adts,
         channels: ChannelMode::Stereo,
@@ -35,6 +42,52 @@ pub fn encoder_aac() -> Result<Encoder, EncoderError> {
     Ok(encoder)
 }
 
+/// Checks that the settings are supported by the AAC encoder:
+///
+/// * `bitrate`: 8 to 320 kbps
+/// * `vbr_quality`: the fdk-aac VBR modes, from 1 (lowest) to 5 (highest)
+/// * `sample_rate`: the MPEG sample rates from 8kHz to 48kHz
+/// * `channels`: 1 or 2
+///
+/// # Errors
+///
+/// * If a setting isn't supported
+pub fn validate_settings(settings: &EncoderSettings) -> Result<(), SettingsError> {
+    crate::validate_settings(
+        settings,
+        |x| (8..=320).contains(&x),
+        |x| (1..=5).contains(&x),
+        |x| crate::MPEG_SAMPLE_RATES.contains(&x),
+        |x| x == 1 || x == 2,
+    )
+}
+
+/// Creates an ADTS encoder with the settings, defaulting to 44.1kHz stereo at
+/// the highest VBR quality.
+pub fn encoder_aac_with_settings(settings: &EncoderSettings) -> Result<Encoder, EncoderError> {
+    let bit_rate = match (settings.bitrate, settings.vbr_quality) {
+        (Some(kbps), _) => BitRate::Cbr(kbps * 1000),
+        (None, Some(1)) => BitRate::VbrVeryLow,
+        (None, Some(2)) => BitRate::VbrLow,
+        (None, Some(3)) => BitRate::VbrMedium,
+        (None, Some(4)) => BitRate::VbrHigh,
+        (None, _) => BitRate::VbrVeryHigh,
+    };
+
+    let encoder = Encoder::new(EncoderParams {
+        audio_object_type: fdk_aac::enc::AudioObjectType::Mpeg4LowComplexity,
+        bit_rate,
+        sample_rate: settings.sample_rate.unwrap_or(44_100),
+        transport: Transport::Adts,
+        channels: if settings.channels == Some(1) {
+            ChannelMode::Mono
+        } else {
+            ChannelMode::Stereo
+        },
+    })?;
+    Ok(encoder)
+}
+
 pub fn encode_aac(
     encoder: &Encoder,
     input: &[i16],
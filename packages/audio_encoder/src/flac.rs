@@ -6,7 +6,7 @@ use flacenc::{
 };
 use thiserror::Error;
 
-use crate::EncodeInfo;
+use crate::{EncodeInfo, EncoderSettings, SettingsError};
 
 pub struct Encoder {
     sink: ByteSink,
@@ -76,3 +76,13 @@ pub fn encode_flac(
         input_consumed: input.len(),
     })
 }
+
+/// Checks that the settings are supported by the FLAC encoder. FLAC is
+/// lossless, so none of the settings are supported.
+///
+/// # Errors
+///
+/// * If any setting is set
+pub fn validate_settings(settings: &EncoderSettings) -> Result<(), SettingsError> {
+    crate::validate_settings(settings, |_| false, |_| false, |_| false, |_| false)
+}
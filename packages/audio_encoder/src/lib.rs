@@ -1,5 +1,7 @@
 #![cfg_attr(feature = "fail-on-warnings", deny(warnings))]
 
+use thiserror::Error;
+
 #[cfg(feature = "aac")]
 pub mod aac;
 
@@ -16,3 +18,74 @@ pub struct EncodeInfo {
     pub output_size: usize,
     pub input_consumed: usize,
 }
+
+/// Overrides of an encoder's default output parameters. The supported
+/// values depend on the encoder, see the `validate_settings` function of
+/// each encoder module.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EncoderSettings {
+    /// The constant bit rate in kbps
+    pub bitrate: Option<u32>,
+    /// The encoder specific variable bit rate quality
+    pub vbr_quality: Option<u8>,
+    /// The output sample rate in Hz
+    pub sample_rate: Option<u32>,
+    /// The number of output channels
+    pub channels: Option<u8>,
+}
+
+impl EncoderSettings {
+    #[must_use]
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum SettingsError {
+    #[error("Unsupported bitrate: {0} kbps")]
+    Bitrate(u32),
+    #[error("Unsupported VBR quality: {0}")]
+    VbrQuality(u8),
+    #[error("Unsupported sample rate: {0} Hz")]
+    SampleRate(u32),
+    #[error("Unsupported number of channels: {0}")]
+    Channels(u8),
+    #[error("A bitrate and a VBR quality can't both be set")]
+    BitrateAndVbrQuality,
+    #[error("The format doesn't support encoder settings")]
+    Unsupported,
+}
+
+/// Checks each setting against the values the encoder supports.
+#[allow(unused)]
+fn validate_settings(
+    settings: &EncoderSettings,
+    bitrate: impl Fn(u32) -> bool,
+    vbr_quality: impl Fn(u8) -> bool,
+    sample_rate: impl Fn(u32) -> bool,
+    channels: impl Fn(u8) -> bool,
+) -> Result<(), SettingsError> {
+    if settings.bitrate.is_some() && settings.vbr_quality.is_some() {
+        return Err(SettingsError::BitrateAndVbrQuality);
+    }
+    if let Some(x) = settings.bitrate.filter(|x| !bitrate(*x)) {
+        return Err(SettingsError::Bitrate(x));
+    }
+    if let Some(x) = settings.vbr_quality.filter(|x| !vbr_quality(*x)) {
+        return Err(SettingsError::VbrQuality(x));
+    }
+    if let Some(x) = settings.sample_rate.filter(|x| !sample_rate(*x)) {
+        return Err(SettingsError::SampleRate(x));
+    }
+    if let Some(x) = settings.channels.filter(|x| !channels(*x)) {
+        return Err(SettingsError::Channels(x));
+    }
+    Ok(())
+}
+
+/// The sample rates supported by the MPEG audio encoders, in Hz.
+#[allow(unused)]
+const MPEG_SAMPLE_RATES: &[u32] = &[
+    8_000, 11_025, 12_000, 16_000, 22_050, 24_000, 32_000, 44_100, 48_000,
+];
@@ -1,6 +1,6 @@
 use thiserror::Error;
 
-use crate::EncodeInfo;
+use crate::{EncodeInfo, EncoderSettings, SettingsError};
 
 #[derive(Debug, Error)]
 pub enum EncoderError {
@@ -56,6 +56,121 @@ pub fn encoder_mp3() -> Result<mp3lame_encoder::Encoder, EncoderError> {
     Ok(mp3_encoder)
 }
 
+/// The supported constant bit rates, in kbps.
+const BITRATES: &[(u32, mp3lame_encoder::Bitrate)] = &[
+    (8, mp3lame_encoder::Bitrate::Kbps8),
+    (16, mp3lame_encoder::Bitrate::Kbps16),
+    (24, mp3lame_encoder::Bitrate::Kbps24),
+    (32, mp3lame_encoder::Bitrate::Kbps32),
+    (40, mp3lame_encoder::Bitrate::Kbps40),
+    (48, mp3lame_encoder::Bitrate::Kbps48),
+    (64, mp3lame_encoder::Bitrate::Kbps64),
+    (80, mp3lame_encoder::Bitrate::Kbps80),
+    (96, mp3lame_encoder::Bitrate::Kbps96),
+    (112, mp3lame_encoder::Bitrate::Kbps112),
+    (128, mp3lame_encoder::Bitrate::Kbps128),
+    (160, mp3lame_encoder::Bitrate::Kbps160),
+    (192, mp3lame_encoder::Bitrate::Kbps192),
+    (224, mp3lame_encoder::Bitrate::Kbps224),
+    (256, mp3lame_encoder::Bitrate::Kbps256),
+    (320, mp3lame_encoder::Bitrate::Kbps320),
+];
+
+/// Creates a 44.1kHz stereo constant bit rate encoder with the highest
+/// supported bit rate that isn't above `kbps`.
+///
+/// Unlike [`encoder_mp3`], the output has no ID3 or Xing/Info tag and the bit
+/// reservoir is disabled, so it only consists of audio frames that don't
+/// reference each other's data and can be split into independent segments.
+pub fn encoder_mp3_with_bitrate(kbps: u32) -> Result<mp3lame_encoder::Encoder, EncoderError> {
+    use mp3lame_encoder::Builder;
+
+    let bitrate = BITRATES
+        .iter()
+        .rev()
+        .find(|(x, _)| *x <= kbps)
+        .unwrap_or(&BITRATES[0])
+        .1;
+
+    let mut mp3_encoder = Builder::new().expect("Create LAME builder");
+    mp3_encoder.set_num_channels(2)?;
+    mp3_encoder.set_sample_rate(44_100)?;
+    mp3_encoder.set_brate(bitrate)?;
+    mp3_encoder.set_quality(mp3lame_encoder::Quality::Best)?;
+    mp3_encoder.set_to_write_vbr_tag(false)?;
+    // SAFETY: the pointer is only used while the builder is alive
+    let result =
+        unsafe { mp3lame_encoder::ffi::lame_set_disable_reservoir(mp3_encoder.as_ptr(), 1) };
+    if result != 0 {
+        return Err(mp3lame_encoder::BuildError::Generic.into());
+    }
+    Ok(mp3_encoder.build()?)
+}
+
+/// Checks that the settings are supported by the MP3 encoder:
+///
+/// * `bitrate`: one of the MPEG audio bit rates from 8 to 320 kbps
+/// * `vbr_quality`: the LAME VBR presets, from 0 (highest) to 9 (lowest)
+/// * `sample_rate`: the MPEG sample rates from 8kHz to 48kHz
+/// * `channels`: 1 or 2
+///
+/// # Errors
+///
+/// * If a setting isn't supported
+pub fn validate_settings(settings: &EncoderSettings) -> Result<(), SettingsError> {
+    crate::validate_settings(
+        settings,
+        |x| BITRATES.iter().any(|(kbps, _)| *kbps == x),
+        |x| x <= 9,
+        |x| crate::MPEG_SAMPLE_RATES.contains(&x),
+        |x| x == 1 || x == 2,
+    )
+}
+
+/// Creates an encoder with the settings, defaulting to a 44.1kHz stereo
+/// 320 kbps constant bit rate.
+pub fn encoder_mp3_with_settings(
+    settings: &EncoderSettings,
+) -> Result<mp3lame_encoder::Encoder, EncoderError> {
+    use mp3lame_encoder::{Builder, Mode, Quality, VbrMode};
+
+    let mut mp3_encoder = Builder::new().expect("Create LAME builder");
+    let channels = settings.channels.unwrap_or(2);
+    mp3_encoder.set_num_channels(channels)?;
+    if channels == 1 {
+        mp3_encoder.set_mode(Mode::Mono)?;
+    }
+    mp3_encoder.set_sample_rate(settings.sample_rate.unwrap_or(44_100))?;
+    mp3_encoder.set_quality(Quality::Best)?;
+
+    if let Some(vbr_quality) = settings.vbr_quality {
+        mp3_encoder.set_vbr_mode(VbrMode::Mtrh)?;
+        mp3_encoder.set_vbr_quality(match vbr_quality {
+            0 => Quality::Best,
+            1 => Quality::SecondBest,
+            2 => Quality::NearBest,
+            3 => Quality::VeryNice,
+            4 => Quality::Nice,
+            5 => Quality::Good,
+            6 => Quality::Decent,
+            7 => Quality::Ok,
+            8 => Quality::SecondWorst,
+            _ => Quality::Worst,
+        })?;
+    } else {
+        let kbps = settings.bitrate.unwrap_or(320);
+        let bitrate = BITRATES
+            .iter()
+            .rev()
+            .find(|(x, _)| *x <= kbps)
+            .unwrap_or(&BITRATES[0])
+            .1;
+        mp3_encoder.set_brate(bitrate)?;
+    }
+
+    Ok(mp3_encoder.build()?)
+}
+
 pub fn encode_mp3(
     encoder: &mut mp3lame_encoder::Encoder,
     input: &[i16],
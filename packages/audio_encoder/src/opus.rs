@@ -3,7 +3,7 @@ use std::fs::File;
 use ogg::{PacketReader, PacketWriteEndInfo, PacketWriter};
 use thiserror::Error;
 
-use crate::EncodeInfo;
+use crate::{EncodeInfo, EncoderSettings, SettingsError};
 
 #[derive(Debug, Error)]
 pub enum EncoderError {
@@ -87,6 +87,42 @@ pub fn encoder_opus() -> Result<::opus::Encoder, EncoderError> {
     Ok(encoder)
 }
 
+/// Checks that the settings are supported by the Opus encoder:
+///
+/// * `bitrate`: 6 to 510 kbps
+/// * `vbr_quality`: unsupported, Opus always uses the bit rate as its target
+/// * `sample_rate`: 48kHz
+/// * `channels`: 2
+///
+/// # Errors
+///
+/// * If a setting isn't supported
+pub fn validate_settings(settings: &EncoderSettings) -> Result<(), SettingsError> {
+    crate::validate_settings(
+        settings,
+        |x| (6..=510).contains(&x),
+        |_| false,
+        |x| x == 48_000,
+        |x| x == 2,
+    )
+}
+
+/// Creates a 48kHz stereo encoder with the settings' bit rate, defaulting to
+/// the encoder's automatic bit rate.
+pub fn encoder_opus_with_settings(
+    settings: &EncoderSettings,
+) -> Result<::opus::Encoder, EncoderError> {
+    let mut encoder = encoder_opus()?;
+
+    if let Some(kbps) = settings.bitrate {
+        encoder.set_bitrate(::opus::Bitrate::Bits(
+            i32::try_from(kbps * 1000).unwrap_or(i32::MAX),
+        ))?;
+    }
+
+    Ok(encoder)
+}
+
 pub fn encode_opus_float(
     encoder: &mut ::opus::Encoder,
     input: &[f32],
@@ -4,13 +4,16 @@ use bytes::Bytes;
 use moosicbox_audio_decoder::{
     decode_file_path_str, AudioDecode, AudioDecodeError, AudioDecodeHandler,
 };
-use moosicbox_audio_encoder::mp3::encoder_mp3;
+use moosicbox_audio_encoder::{
+    mp3::{encoder_mp3, encoder_mp3_with_settings, EncoderError},
+    EncoderSettings,
+};
 use moosicbox_stream_utils::{ByteStream, ByteWriter};
 use symphonia::core::formats::Track;
 use symphonia::core::units::Duration;
 use symphonia::core::{audio::*, formats::Packet};
 
-use crate::{to_samples, AudioOutputError, AudioWrite};
+use crate::{remix_channels, to_samples, AudioOutputError, AudioWrite};
 use moosicbox_resampler::Resampler;
 
 use super::AudioEncoder;
@@ -20,6 +23,7 @@ pub struct Mp3Encoder {
     input_rate: Option<u32>,
     resample_rate: Option<u32>,
     output_rate: usize,
+    output_channels: usize,
     duration: Option<Duration>,
     writer: Option<Box<dyn std::io::Write + Send + Sync>>,
     encoder: mp3lame_encoder::Encoder,
@@ -32,6 +36,7 @@ impl Mp3Encoder {
             input_rate: None,
             resample_rate: None,
             output_rate: 44100,
+            output_channels: 2,
             duration: None,
             writer: None,
             encoder: encoder_mp3().unwrap(),
@@ -44,12 +49,30 @@ impl Mp3Encoder {
             input_rate: None,
             resample_rate: None,
             output_rate: 44100,
+            output_channels: 2,
             duration: None,
             writer: Some(Box::new(writer)),
             encoder: encoder_mp3().unwrap(),
         }
     }
 
+    /// Replaces the encoder with one using the settings. The audio is resampled
+    /// and remixed to the settings' sample rate and channels. Default settings
+    /// keep the default encoder.
+    ///
+    /// # Errors
+    ///
+    /// * If the encoder failed to be created with the settings
+    pub fn with_settings(mut self, settings: &EncoderSettings) -> Result<Self, EncoderError> {
+        if settings.is_default() {
+            return Ok(self);
+        }
+        self.encoder = encoder_mp3_with_settings(settings)?;
+        self.output_rate = settings.sample_rate.map_or(44100, |x| x as usize);
+        self.output_channels = settings.channels.map_or(2, usize::from);
+        Ok(self)
+    }
+
     pub fn init_resampler(&mut self, spec: &SignalSpec, duration: Duration) -> &Self {
         if !self.resample_rate.is_some_and(|r| r == spec.rate)
             && self.output_rate != spec.rate as usize
@@ -151,7 +174,9 @@ impl AudioEncoder for Mp3Encoder {
     fn encode(&mut self, decoded: AudioBuffer<f32>) -> Result<Bytes, AudioOutputError> {
         log::debug!("Mp3Encoder encode {} frames", decoded.frames());
 
+        let channels = decoded.spec().channels.count();
         let decoded = self.resample_if_needed(decoded)?;
+        let decoded = remix_channels(decoded, channels, self.output_channels);
 
         Ok(self.encode_output(&decoded))
     }
@@ -159,7 +184,11 @@ impl AudioEncoder for Mp3Encoder {
     fn spec(&self) -> SignalSpec {
         SignalSpec {
             rate: self.output_rate as u32,
-            channels: Channels::FRONT_LEFT | Channels::FRONT_RIGHT,
+            channels: if self.output_channels == 1 {
+                Channels::FRONT_LEFT
+            } else {
+                Channels::FRONT_LEFT | Channels::FRONT_RIGHT
+            },
         }
     }
 }
@@ -4,8 +4,12 @@ use bytes::Bytes;
 use moosicbox_audio_decoder::{
     decode_file_path_str, AudioDecode, AudioDecodeError, AudioDecodeHandler,
 };
-use moosicbox_audio_encoder::opus::{
-    encoder_opus, OPUS_STREAM_COMMENTS_HEADER, OPUS_STREAM_IDENTIFICATION_HEADER,
+use moosicbox_audio_encoder::{
+    opus::{
+        encoder_opus, encoder_opus_with_settings, EncoderError, OPUS_STREAM_COMMENTS_HEADER,
+        OPUS_STREAM_IDENTIFICATION_HEADER,
+    },
+    EncoderSettings,
 };
 use moosicbox_stream_utils::{ByteStream, ByteWriter};
 use ogg::{PacketWriteEndInfo, PacketWriter};
@@ -67,6 +71,20 @@ impl OpusEncoder<'_> {
         x
     }
 
+    /// Replaces the encoder with one using the settings. Default settings keep
+    /// the default encoder.
+    ///
+    /// # Errors
+    ///
+    /// * If the encoder failed to be created with the settings
+    pub fn with_settings(self, settings: &EncoderSettings) -> Result<Self, EncoderError> {
+        if settings.is_default() {
+            return Ok(self);
+        }
+        *self.encoder.lock().unwrap() = encoder_opus_with_settings(settings)?;
+        Ok(self)
+    }
+
     pub fn init_resampler(&mut self, spec: &SignalSpec, duration: Duration) -> &Self {
         if !self.resample_rate.is_some_and(|r| r == spec.rate)
             && self.output_rate != spec.rate as usize
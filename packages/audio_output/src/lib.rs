@@ -280,6 +280,37 @@ fn to_samples<S: FromSample<f32> + Default + Clone>(decoded: AudioBuffer<f32>) -
     buf
 }
 
+/// Converts interleaved samples to another number of channels by averaging
+/// the channels down to mono or by duplicating mono samples.
+#[allow(unused)]
+fn remix_channels(samples: Vec<i16>, from: usize, to: usize) -> Vec<i16> {
+    if from == to || from == 0 || to == 0 {
+        return samples;
+    }
+
+    if to == 1 {
+        samples
+            .chunks(from)
+            .map(|frame| {
+                let sum = frame.iter().map(|x| i32::from(*x)).sum::<i32>();
+                #[allow(clippy::cast_possible_truncation)]
+                let sample = (sum / frame.len() as i32) as i16;
+                sample
+            })
+            .collect()
+    } else if from == 1 {
+        samples
+            .into_iter()
+            .flat_map(|x| std::iter::repeat_n(x, to))
+            .collect()
+    } else {
+        samples
+            .chunks(from)
+            .flat_map(|frame| (0..to).map(|ch| frame.get(ch).copied().unwrap_or_default()))
+            .collect()
+    }
+}
+
 static AUDIO_OUTPUT_SCANNER: LazyLock<Arc<Mutex<AudioOutputScanner>>> =
     LazyLock::new(|| Arc::new(Mutex::new(AudioOutputScanner::new())));
 
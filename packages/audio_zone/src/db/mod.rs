@@ -1,7 +1,10 @@
 use moosicbox_database::{
     boxed, config::ConfigDatabase, profiles::LibraryDatabase, query::*, DatabaseValue,
 };
-use moosicbox_json_utils::{database::DatabaseFetchError, ToValueType};
+use moosicbox_json_utils::{
+    database::{DatabaseFetchError, ToDatabaseValues as _},
+    ToValueType,
+};
 
 use crate::models::{CreateAudioZone, UpdateAudioZone};
 
@@ -113,7 +116,7 @@ pub async fn create_audio_zone(
 ) -> Result<AudioZoneModel, DatabaseFetchError> {
     Ok(db
         .insert("audio_zones")
-        .value("name", zone.name.clone())
+        .values(zone.to_database_values())
         .execute(db)
         .await?
         .to_value_type()?)
@@ -161,3 +164,100 @@ pub async fn get_players(
         .await?
         .to_value_type()?)
 }
+
+#[cfg(test)]
+mod test {
+    use moosicbox_schema::fixtures;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test_log::test(tokio::test)]
+    async fn creates_and_deletes_audio_zones() {
+        let db = fixtures::config_db().await;
+
+        let kitchen = create_audio_zone(
+            &db,
+            &CreateAudioZone {
+                name: "Kitchen".to_string(),
+            },
+        )
+        .await
+        .unwrap();
+        let office = create_audio_zone(
+            &db,
+            &CreateAudioZone {
+                name: "Office".to_string(),
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            get_zones(&db)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|x| x.id)
+                .collect::<Vec<_>>(),
+            vec![kitchen.id, office.id]
+        );
+
+        delete_audio_zone(&db, kitchen.id).await.unwrap();
+
+        assert_eq!(
+            get_zones(&db)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|x| x.name)
+                .collect::<Vec<_>>(),
+            vec!["Office".to_string()]
+        );
+        assert!(get_zone(&db, kitchen.id).await.unwrap().is_none());
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn get_zone_with_sessions_joins_library_sessions() {
+        let config_db = fixtures::config_db().await;
+        let library_db = fixtures::library_db().await;
+
+        let zone = create_audio_zone(
+            &config_db,
+            &CreateAudioZone {
+                name: "Living Room".to_string(),
+            },
+        )
+        .await
+        .unwrap();
+        let playlist_id = library_db
+            .insert("session_playlists")
+            .execute(&*library_db)
+            .await
+            .unwrap()
+            .id()
+            .unwrap();
+        let session_id: u64 = library_db
+            .insert("sessions")
+            .value("session_playlist_id", playlist_id)
+            .value("name", "Session")
+            .value("audio_zone_id", zone.id)
+            .execute(&*library_db)
+            .await
+            .unwrap()
+            .id()
+            .unwrap()
+            .try_into()
+            .unwrap();
+
+        assert_eq!(
+            get_zone_with_sessions(&config_db, &library_db)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|x| (x.id, x.session_id, x.name))
+                .collect::<Vec<_>>(),
+            vec![(zone.id, session_id, "Living Room".to_string())]
+        );
+    }
+}
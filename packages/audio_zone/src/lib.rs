@@ -7,9 +7,6 @@ use moosicbox_json_utils::database::DatabaseFetchError;
 #[cfg(feature = "api")]
 pub mod api;
 
-#[cfg(feature = "events")]
-pub mod events;
-
 pub mod db;
 pub mod models;
 
@@ -46,15 +43,6 @@ pub async fn create_audio_zone(
         .try_into_db(db.into())
         .await?;
 
-    #[cfg(feature = "events")]
-    {
-        moosicbox_task::spawn("create_audio_zone updated_events", async move {
-            if let Err(e) = crate::events::trigger_audio_zones_updated_event().await {
-                moosicbox_assert::die_or_error!("Failed to trigger event: {e:?}");
-            }
-        });
-    }
-
     Ok(resp)
 }
 
@@ -67,15 +55,6 @@ pub async fn update_audio_zone(
         .try_into_db(db.into())
         .await?;
 
-    #[cfg(feature = "events")]
-    {
-        moosicbox_task::spawn("create_audio_zone updated_events", async move {
-            if let Err(e) = crate::events::trigger_audio_zones_updated_event().await {
-                moosicbox_assert::die_or_error!("Failed to trigger event: {e:?}");
-            }
-        });
-    }
-
     Ok(resp)
 }
 
@@ -91,14 +70,5 @@ pub async fn delete_audio_zone(
         None
     };
 
-    #[cfg(feature = "events")]
-    {
-        moosicbox_task::spawn("create_audio_zone updated_events", async move {
-            if let Err(e) = crate::events::trigger_audio_zones_updated_event().await {
-                moosicbox_assert::die_or_error!("Failed to trigger event: {e:?}");
-            }
-        });
-    }
-
     Ok(resp)
 }
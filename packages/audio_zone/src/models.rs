@@ -4,8 +4,8 @@ use async_trait::async_trait;
 use moosicbox_core::sqlite::models::ToApi;
 use moosicbox_database::{AsId, Database, DatabaseValue, TryFromDb};
 use moosicbox_json_utils::{
-    database::{DatabaseFetchError, ToValue as _},
-    MissingValue, ParseError, ToValueType,
+    database::{DatabaseFetchError, FromRow, ToDatabaseValues},
+    MissingValue,
 };
 use serde::{Deserialize, Serialize};
 
@@ -136,7 +136,7 @@ impl From<AudioZoneWithSession> for ApiAudioZoneWithSession {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default, FromRow)]
 #[serde(rename_all = "camelCase")]
 pub struct Player {
     pub id: u64,
@@ -161,18 +161,6 @@ impl From<ApiPlayer> for Player {
 }
 
 impl MissingValue<Player> for &moosicbox_database::Row {}
-impl ToValueType<Player> for &moosicbox_database::Row {
-    fn to_value_type(self) -> Result<Player, ParseError> {
-        Ok(Player {
-            id: self.to_value("id")?,
-            audio_output_id: self.to_value("audio_output_id")?,
-            name: self.to_value("name")?,
-            playing: self.to_value("playing")?,
-            created: self.to_value("created")?,
-            updated: self.to_value("updated")?,
-        })
-    }
-}
 
 impl AsId for Player {
     fn as_id(&self) -> DatabaseValue {
@@ -201,7 +189,7 @@ impl ToApi<ApiPlayer> for Player {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default, ToDatabaseValues)]
 #[serde(rename_all = "camelCase")]
 pub struct CreateAudioZone {
     pub name: String,
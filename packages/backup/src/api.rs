@@ -0,0 +1,103 @@
+use actix_web::{
+    dev::{ServiceFactory, ServiceRequest},
+    error::{ErrorBadRequest, ErrorInternalServerError},
+    route,
+    web::{Json, JsonConfig},
+    Result, Scope,
+};
+use moosicbox_auth::NonTunnelRequestAuthorized;
+use moosicbox_database::{config::ConfigDatabase, profiles::LibraryDatabase};
+
+use crate::{
+    models::{ImportReport, LibraryArchive},
+    ImportError,
+};
+
+/// The largest archive accepted by the import endpoint. Archives of large
+/// libraries are well over the default JSON payload limit.
+pub const MAX_IMPORT_SIZE: usize = 256 * 1024 * 1024;
+
+pub fn bind_services<
+    T: ServiceFactory<ServiceRequest, Config = (), Error = actix_web::Error, InitError = ()>,
+>(
+    scope: Scope<T>,
+) -> Scope<T> {
+    scope
+        .app_data(JsonConfig::default().limit(MAX_IMPORT_SIZE))
+        .service(export_endpoint)
+        .service(import_endpoint)
+}
+
+#[cfg(feature = "openapi")]
+#[derive(utoipa::OpenApi)]
+#[openapi(
+    tags((name = "Backup")),
+    paths(export_endpoint, import_endpoint),
+    components(schemas(
+        ImportReport,
+        crate::models::UnresolvedItem,
+        crate::models::UnresolvedItemType,
+    ))
+)]
+pub struct Api;
+
+#[cfg_attr(
+    feature = "openapi", utoipa::path(
+        tags = ["Backup"],
+        get,
+        path = "/export",
+        description = "Export the library and config data into a versioned archive",
+        responses(
+            (
+                status = 200,
+                description = "The library archive",
+                body = Value,
+            )
+        )
+    )
+)]
+#[route("/export", method = "GET")]
+pub async fn export_endpoint(
+    config_db: ConfigDatabase,
+    library_db: LibraryDatabase,
+    _: NonTunnelRequestAuthorized,
+) -> Result<Json<LibraryArchive>> {
+    Ok(Json(
+        crate::export(&config_db, &library_db)
+            .await
+            .map_err(ErrorInternalServerError)?,
+    ))
+}
+
+#[cfg_attr(
+    feature = "openapi", utoipa::path(
+        tags = ["Backup"],
+        post,
+        path = "/import",
+        description = "Import an archive created by the export endpoint. Items that can't be mapped to the current library are listed in the response",
+        request_body = Value,
+        responses(
+            (
+                status = 200,
+                description = "A report of the imported items",
+                body = ImportReport,
+            )
+        )
+    )
+)]
+#[route("/import", method = "POST")]
+pub async fn import_endpoint(
+    archive: Json<LibraryArchive>,
+    config_db: ConfigDatabase,
+    library_db: LibraryDatabase,
+    _: NonTunnelRequestAuthorized,
+) -> Result<Json<ImportReport>> {
+    Ok(Json(
+        crate::import(&config_db, &library_db, &archive)
+            .await
+            .map_err(|e| match e {
+                ImportError::UnsupportedVersion(_) => ErrorBadRequest(e),
+                _ => ErrorInternalServerError(e),
+            })?,
+    ))
+}
@@ -0,0 +1,175 @@
+use moosicbox_core::sqlite::db::DbError;
+use moosicbox_database::{profiles::LibraryDatabase, query::FilterableQuery as _, DatabaseValue};
+use moosicbox_json_utils::{database::ToValue as _, ToValueType};
+use moosicbox_library::models::LibraryAlbum;
+use moosicbox_session::models::{PlaybackTarget, SessionPlaylistTrack};
+
+use crate::models::{ArchiveScanLocation, ArchiveSession};
+
+pub async fn get_scan_locations(db: &LibraryDatabase) -> Result<Vec<ArchiveScanLocation>, DbError> {
+    db.select("scan_locations")
+        .columns(&["origin", "path"])
+        .execute(db)
+        .await?
+        .iter()
+        .map(|row| {
+            Ok(ArchiveScanLocation {
+                origin: row.to_value("origin")?,
+                path: row.to_value("path")?,
+            })
+        })
+        .collect()
+}
+
+pub async fn add_scan_location(
+    db: &LibraryDatabase,
+    location: &ArchiveScanLocation,
+) -> Result<(), DbError> {
+    db.insert("scan_locations")
+        .value("origin", location.origin.as_str())
+        .value("path", location.path.clone())
+        .execute(db)
+        .await?;
+
+    Ok(())
+}
+
+/// All the albums in the library, regardless of whether they have any
+/// tracks, along with their ratings.
+pub async fn get_albums(db: &LibraryDatabase) -> Result<Vec<LibraryAlbum>, DbError> {
+    Ok(db
+        .select("albums")
+        .columns(&[
+            "albums.*",
+            "artists.title as artist",
+            "artists.tidal_id as tidal_artist_id",
+            "artists.qobuz_id as qobuz_artist_id",
+            "album_ratings.rating",
+            "album_ratings.loved",
+            "album_ratings.banned",
+        ])
+        .join("artists", "artists.id=albums.artist_id")
+        .left_join("album_ratings", "album_ratings.album_id=albums.id")
+        .execute(db)
+        .await?
+        .to_value_type()?)
+}
+
+/// Inserts the session and its playlist. The session's playback target must
+/// already be mapped to the audio zone ids of the target database.
+pub async fn insert_session(
+    db: &LibraryDatabase,
+    session: &ArchiveSession,
+    tracks: &[SessionPlaylistTrack],
+) -> Result<u64, DbError> {
+    let playlist_id: u64 = db
+        .insert("session_playlists")
+        .execute(db)
+        .await?
+        .to_value("id")?;
+
+    for track in tracks {
+        db.insert("session_playlist_tracks")
+            .value("session_playlist_id", playlist_id)
+            .value("track_id", track.id.as_str())
+            .value("type", track.r#type.as_ref())
+            .value("data", track.data.clone())
+            .execute(db)
+            .await?;
+    }
+
+    let mut values = session_values(session);
+    values.push(("session_playlist_id", DatabaseValue::UNumber(playlist_id)));
+
+    let session_id: u64 = db
+        .insert("sessions")
+        .values(values)
+        .execute(db)
+        .await?
+        .to_value("id")?;
+
+    insert_audio_zone_session(db, session_id, session).await?;
+
+    Ok(session_id)
+}
+
+/// Updates the state and playback target of an existing session with the
+/// same playlist as the archived session.
+pub async fn update_session(
+    db: &LibraryDatabase,
+    session_id: u64,
+    session: &ArchiveSession,
+) -> Result<(), DbError> {
+    db.update("sessions")
+        .where_eq("id", session_id)
+        .values(session_values(session))
+        .execute(db)
+        .await?;
+
+    db.delete("audio_zone_sessions")
+        .where_eq("session_id", session_id)
+        .execute(db)
+        .await?;
+
+    insert_audio_zone_session(db, session_id, session).await?;
+
+    Ok(())
+}
+
+fn session_values(session: &ArchiveSession) -> Vec<(&'static str, DatabaseValue)> {
+    let (audio_zone_id, connection_id, output_id) = match &session.playback_target {
+        Some(PlaybackTarget::AudioZone { audio_zone_id }) => (Some(*audio_zone_id), None, None),
+        Some(PlaybackTarget::ConnectionOutput {
+            connection_id,
+            output_id,
+        }) => (
+            None,
+            Some(connection_id.to_owned()),
+            Some(output_id.to_owned()),
+        ),
+        None => (None, None, None),
+    };
+
+    vec![
+        ("name", DatabaseValue::String(session.name.clone())),
+        ("active", DatabaseValue::Bool(session.active)),
+        ("playing", DatabaseValue::Bool(session.playing)),
+        (
+            "position",
+            DatabaseValue::NumberOpt(session.position.map(i64::from)),
+        ),
+        (
+            "seek",
+            DatabaseValue::NumberOpt(session.seek.map(|x| x as i64)),
+        ),
+        ("volume", DatabaseValue::Real(session.volume.unwrap_or(1.0))),
+        (
+            "playback_target",
+            DatabaseValue::StringOpt(
+                session
+                    .playback_target
+                    .as_ref()
+                    .map(|x| x.as_ref().to_string()),
+            ),
+        ),
+        ("audio_zone_id", DatabaseValue::UNumberOpt(audio_zone_id)),
+        ("connection_id", DatabaseValue::StringOpt(connection_id)),
+        ("output_id", DatabaseValue::StringOpt(output_id)),
+    ]
+}
+
+async fn insert_audio_zone_session(
+    db: &LibraryDatabase,
+    session_id: u64,
+    session: &ArchiveSession,
+) -> Result<(), DbError> {
+    if let Some(PlaybackTarget::AudioZone { audio_zone_id }) = &session.playback_target {
+        db.insert("audio_zone_sessions")
+            .value("session_id", session_id)
+            .value("audio_zone_id", *audio_zone_id)
+            .execute(db)
+            .await?;
+    }
+
+    Ok(())
+}
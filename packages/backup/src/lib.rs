@@ -0,0 +1,817 @@
+#![cfg_attr(feature = "fail-on-warnings", deny(warnings))]
+
+use std::collections::{HashMap, HashSet};
+
+use models::{
+    AlbumIdentity, ArchiveAudioZone, ArchiveConnection, ArchiveFavoriteAlbum, ArchiveFavoriteTrack,
+    ArchivePlayer, ArchivePlayerRef, ArchiveRating, ArchiveRatings, ArchiveSession,
+    ArchiveSessionTrack, ArtistIdentity, ConfigArchive, ImportReport, LibraryArchive,
+    LibraryDataArchive, TrackIdentity, UnresolvedItem, UnresolvedItemType, LIBRARY_ARCHIVE_VERSION,
+};
+use moosicbox_audio_zone::models::{CreateAudioZone, UpdateAudioZone};
+use moosicbox_core::sqlite::{
+    db::DbError,
+    models::{ApiSource, TrackApiSource},
+};
+use moosicbox_database::{config::ConfigDatabase, profiles::LibraryDatabase, DatabaseValue};
+use moosicbox_json_utils::database::DatabaseFetchError;
+use moosicbox_library::{
+    db::{InsertTrack, SetRating},
+    models::{LibraryAlbum, LibraryArtist, LibraryTrack},
+};
+use moosicbox_session::models::{
+    PlaybackTarget, RegisterConnection, RegisterPlayer, SessionPlaylistTrack,
+};
+use resolve::{AlbumResolver, ArtistResolver, TrackResolver};
+use thiserror::Error;
+
+#[cfg(feature = "api")]
+pub mod api;
+
+pub mod db;
+pub mod models;
+pub mod resolve;
+
+fn artist_identity(artist: &LibraryArtist) -> ArtistIdentity {
+    ArtistIdentity {
+        title: artist.title.clone(),
+        tidal_id: artist.tidal_id,
+        qobuz_id: artist.qobuz_id,
+        yt_id: artist.yt_id,
+    }
+}
+
+fn album_identity(album: &LibraryAlbum) -> AlbumIdentity {
+    AlbumIdentity {
+        title: album.title.clone(),
+        artist: album.artist.clone(),
+        directory: album.directory.clone(),
+        tidal_id: album.tidal_id,
+        qobuz_id: album.qobuz_id.clone(),
+        yt_id: album.yt_id,
+    }
+}
+
+fn track_identity(track: &LibraryTrack) -> TrackIdentity {
+    TrackIdentity {
+        title: track.title.clone(),
+        album: track.album.clone(),
+        artist: track.artist.clone(),
+        number: track.number,
+        duration: track.duration,
+        path: track.file.clone(),
+        tidal_id: track.tidal_id,
+        qobuz_id: track.qobuz_id,
+        yt_id: track.yt_id,
+    }
+}
+
+fn is_rated(rating: Option<u8>, loved: bool, banned: bool) -> bool {
+    rating.is_some() || loved || banned
+}
+
+#[derive(Debug, Error)]
+pub enum ExportError {
+    #[error(transparent)]
+    Db(#[from] DbError),
+    #[error(transparent)]
+    DatabaseFetch(#[from] DatabaseFetchError),
+}
+
+/// Exports the profiles, connections and audio zones from the config
+/// database along with the scan locations, favorites, ratings and sessions of
+/// the library database into a [`LibraryArchive`].
+///
+/// Items are referenced by identities (file paths, source ids and tags)
+/// rather than database ids so the archive can be imported into a fresh
+/// database, regardless of its backend.
+pub async fn export(
+    config_db: &ConfigDatabase,
+    library_db: &LibraryDatabase,
+) -> Result<LibraryArchive, ExportError> {
+    Ok(LibraryArchive {
+        version: LIBRARY_ARCHIVE_VERSION,
+        exported_at: chrono::Utc::now().to_rfc3339(),
+        config: export_config(config_db).await?,
+        library: export_library(library_db).await?,
+    })
+}
+
+async fn export_config(config_db: &ConfigDatabase) -> Result<ConfigArchive, ExportError> {
+    let profiles = moosicbox_config::get_profiles(config_db)
+        .await?
+        .into_iter()
+        .map(|x| x.name)
+        .collect();
+
+    let connections = moosicbox_session::get_connections(config_db).await?;
+
+    let players = connections
+        .iter()
+        .flat_map(|connection| {
+            connection
+                .players
+                .iter()
+                .map(|player| (player.id, (connection.id.clone(), player)))
+        })
+        .collect::<HashMap<_, _>>();
+
+    let audio_zones = moosicbox_audio_zone::zones(config_db)
+        .await?
+        .into_iter()
+        .map(|zone| ArchiveAudioZone {
+            id: zone.id,
+            players: zone
+                .players
+                .iter()
+                .filter_map(|player| {
+                    let Some((connection_id, player)) = players.get(&player.id) else {
+                        log::warn!(
+                            "Player {} in audio zone '{}' has no connection",
+                            player.id,
+                            zone.name
+                        );
+                        return None;
+                    };
+
+                    Some(ArchivePlayerRef {
+                        connection_id: connection_id.clone(),
+                        audio_output_id: player.audio_output_id.clone(),
+                        name: player.name.clone(),
+                    })
+                })
+                .collect(),
+            name: zone.name,
+        })
+        .collect();
+
+    let connections = connections
+        .into_iter()
+        .map(|connection| ArchiveConnection {
+            id: connection.id,
+            name: connection.name,
+            players: connection
+                .players
+                .into_iter()
+                .map(|player| ArchivePlayer {
+                    audio_output_id: player.audio_output_id,
+                    name: player.name,
+                })
+                .collect(),
+        })
+        .collect();
+
+    Ok(ConfigArchive {
+        profiles,
+        connections,
+        audio_zones,
+    })
+}
+
+async fn export_library(library_db: &LibraryDatabase) -> Result<LibraryDataArchive, ExportError> {
+    let scan_locations = db::get_scan_locations(library_db).await?;
+    let artists = moosicbox_library::db::get_artists(library_db).await?;
+    let albums = db::get_albums(library_db).await?;
+
+    let mut track_ids = HashSet::new();
+    let tracks = moosicbox_library::db::get_tracks(library_db, None)
+        .await?
+        .into_iter()
+        .filter(|x| track_ids.insert(x.id))
+        .collect::<Vec<_>>();
+
+    let artists_by_id = artists.iter().map(|x| (x.id, x)).collect::<HashMap<_, _>>();
+    let tracks_by_id = tracks.iter().map(|x| (x.id, x)).collect::<HashMap<_, _>>();
+
+    let favorites = albums
+        .iter()
+        .filter(|album| {
+            album.directory.is_none()
+                && (album.tidal_id.is_some() || album.qobuz_id.is_some() || album.yt_id.is_some())
+        })
+        .map(|album| ArchiveFavoriteAlbum {
+            artist: artists_by_id.get(&album.artist_id).map_or_else(
+                || ArtistIdentity {
+                    title: album.artist.clone(),
+                    tidal_id: album.tidal_artist_id,
+                    qobuz_id: album.qobuz_artist_id,
+                    yt_id: album.yt_artist_id,
+                },
+                |x| artist_identity(x),
+            ),
+            title: album.title.clone(),
+            date_released: album.date_released.clone(),
+            tidal_id: album.tidal_id,
+            qobuz_id: album.qobuz_id.clone(),
+            yt_id: album.yt_id,
+            tracks: tracks
+                .iter()
+                .filter(|x| x.album_id == album.id && x.source != TrackApiSource::Local)
+                .map(|x| ArchiveFavoriteTrack {
+                    number: x.number,
+                    title: x.title.clone(),
+                    duration: x.duration,
+                    format: x.format,
+                    source: x.source,
+                    tidal_id: x.tidal_id,
+                    qobuz_id: x.qobuz_id,
+                })
+                .collect(),
+        })
+        .collect();
+
+    let ratings = ArchiveRatings {
+        artists: artists
+            .iter()
+            .filter(|x| is_rated(x.rating, x.loved, x.banned))
+            .map(|x| ArchiveRating {
+                item: artist_identity(x),
+                rating: x.rating,
+                loved: x.loved,
+                banned: x.banned,
+            })
+            .collect(),
+        albums: albums
+            .iter()
+            .filter(|x| is_rated(x.rating, x.loved, x.banned))
+            .map(|x| ArchiveRating {
+                item: album_identity(x),
+                rating: x.rating,
+                loved: x.loved,
+                banned: x.banned,
+            })
+            .collect(),
+        tracks: tracks
+            .iter()
+            .filter(|x| is_rated(x.rating, x.loved, x.banned))
+            .map(|x| ArchiveRating {
+                item: track_identity(x),
+                rating: x.rating,
+                loved: x.loved,
+                banned: x.banned,
+            })
+            .collect(),
+    };
+
+    let mut sessions = vec![];
+
+    for session in moosicbox_session::get_sessions(library_db).await? {
+        let playlist_tracks =
+            moosicbox_session::get_session_playlist_tracks(library_db, session.playlist.id).await?;
+
+        let tracks = playlist_tracks
+            .into_iter()
+            .filter_map(|track| match track.r#type {
+                ApiSource::Library => {
+                    let library_track = track
+                        .id
+                        .parse::<u64>()
+                        .ok()
+                        .and_then(|id| tracks_by_id.get(&id));
+
+                    if library_track.is_none() {
+                        log::warn!(
+                            "Session '{}' references missing library track {}",
+                            session.name,
+                            track.id
+                        );
+                    }
+
+                    library_track.map(|x| ArchiveSessionTrack::Library {
+                        track: track_identity(x),
+                    })
+                }
+                source => Some(ArchiveSessionTrack::Remote {
+                    source,
+                    id: track.id,
+                    data: track.data,
+                }),
+            })
+            .collect();
+
+        sessions.push(ArchiveSession {
+            name: session.name,
+            active: session.active,
+            playing: session.playing,
+            position: session.position,
+            seek: session.seek,
+            volume: session.volume,
+            playback_target: session.playback_target,
+            tracks,
+        });
+    }
+
+    Ok(LibraryDataArchive {
+        scan_locations,
+        favorites,
+        ratings,
+        sessions,
+    })
+}
+
+#[derive(Debug, Error)]
+pub enum ImportError {
+    #[error(transparent)]
+    Db(#[from] DbError),
+    #[error(transparent)]
+    DatabaseFetch(#[from] DatabaseFetchError),
+    #[error("Unsupported archive version: {0}")]
+    UnsupportedVersion(u32),
+}
+
+fn unresolved(r#type: UnresolvedItemType, item: String, context: &str) -> UnresolvedItem {
+    log::debug!("Failed to resolve {type:?} '{item}' for {context}");
+
+    UnresolvedItem {
+        r#type,
+        item,
+        context: context.to_string(),
+    }
+}
+
+fn describe_track(track: &TrackIdentity) -> String {
+    track.path.clone().unwrap_or_else(|| {
+        format!(
+            "{} - {} - {}. {}",
+            track.artist, track.album, track.number, track.title
+        )
+    })
+}
+
+fn describe_album(album: &AlbumIdentity) -> String {
+    format!("{} - {}", album.artist, album.title)
+}
+
+/// Imports a [`LibraryArchive`] created by [`export`].
+///
+/// Existing data is kept: profiles, connections, audio zones and scan
+/// locations are matched by name, and rated items and session tracks are
+/// mapped to the tracks already in the library by their path, source ids or
+/// tags. Anything that can't be mapped is listed in the returned
+/// [`ImportReport`] instead of failing the import. Local tracks are expected
+/// to have been scanned before importing, otherwise their ratings and
+/// session entries will be reported as unresolved.
+pub async fn import(
+    config_db: &ConfigDatabase,
+    library_db: &LibraryDatabase,
+    archive: &LibraryArchive,
+) -> Result<ImportReport, ImportError> {
+    if archive.version > LIBRARY_ARCHIVE_VERSION {
+        return Err(ImportError::UnsupportedVersion(archive.version));
+    }
+
+    let mut report = ImportReport::default();
+
+    let audio_zone_ids = import_config(config_db, &archive.config, &mut report).await?;
+    import_library(library_db, &archive.library, &audio_zone_ids, &mut report).await?;
+
+    Ok(report)
+}
+
+/// Returns the ids of the imported audio zones keyed by their exported id.
+async fn import_config(
+    config_db: &ConfigDatabase,
+    config: &ConfigArchive,
+    report: &mut ImportReport,
+) -> Result<HashMap<u64, u64>, ImportError> {
+    for profile in &config.profiles {
+        moosicbox_config::upsert_profile(config_db, profile).await?;
+        report.profiles += 1;
+    }
+
+    for connection in &config.connections {
+        moosicbox_session::register_connection(
+            config_db,
+            &RegisterConnection {
+                connection_id: connection.id.clone(),
+                name: connection.name.clone(),
+                players: connection
+                    .players
+                    .iter()
+                    .map(|x| RegisterPlayer {
+                        audio_output_id: x.audio_output_id.clone(),
+                        name: x.name.clone(),
+                    })
+                    .collect(),
+            },
+        )
+        .await?;
+        report.connections += 1;
+    }
+
+    let player_ids = moosicbox_session::get_connections(config_db)
+        .await?
+        .into_iter()
+        .flat_map(|connection| {
+            connection.players.into_iter().map(move |player| {
+                (
+                    (connection.id.clone(), player.audio_output_id, player.name),
+                    player.id,
+                )
+            })
+        })
+        .collect::<HashMap<_, _>>();
+
+    let existing_zones = moosicbox_audio_zone::zones(config_db).await?;
+    let mut audio_zone_ids = HashMap::new();
+
+    for zone in &config.audio_zones {
+        let id = if let Some(existing) = existing_zones.iter().find(|x| x.name == zone.name) {
+            existing.id
+        } else {
+            moosicbox_audio_zone::create_audio_zone(
+                config_db,
+                &CreateAudioZone {
+                    name: zone.name.clone(),
+                },
+            )
+            .await?
+            .id
+        };
+
+        let players = zone
+            .players
+            .iter()
+            .filter_map(|player| {
+                let id = player_ids
+                    .get(&(
+                        player.connection_id.clone(),
+                        player.audio_output_id.clone(),
+                        player.name.clone(),
+                    ))
+                    .copied();
+
+                if id.is_none() {
+                    report.unresolved.push(unresolved(
+                        UnresolvedItemType::Player,
+                        format!("{} ({})", player.name, player.connection_id),
+                        &format!("audio zone '{}'", zone.name),
+                    ));
+                }
+
+                id
+            })
+            .collect();
+
+        moosicbox_audio_zone::update_audio_zone(
+            config_db,
+            UpdateAudioZone {
+                id,
+                name: None,
+                players: Some(players),
+            },
+        )
+        .await?;
+
+        audio_zone_ids.insert(zone.id, id);
+        report.audio_zones += 1;
+    }
+
+    Ok(audio_zone_ids)
+}
+
+async fn import_library(
+    library_db: &LibraryDatabase,
+    library: &LibraryDataArchive,
+    audio_zone_ids: &HashMap<u64, u64>,
+    report: &mut ImportReport,
+) -> Result<(), ImportError> {
+    let existing_locations = db::get_scan_locations(library_db).await?;
+
+    for location in &library.scan_locations {
+        if !existing_locations.contains(location) {
+            db::add_scan_location(library_db, location).await?;
+        }
+        report.scan_locations += 1;
+    }
+
+    for favorite in &library.favorites {
+        import_favorite(library_db, favorite).await?;
+        report.favorite_albums += 1;
+        report.favorite_tracks += favorite.tracks.len();
+    }
+
+    let artists = ArtistResolver::new(&moosicbox_library::db::get_artists(library_db).await?);
+    let albums = AlbumResolver::new(&db::get_albums(library_db).await?);
+    let tracks = TrackResolver::new(&moosicbox_library::db::get_tracks(library_db, None).await?);
+
+    for rating in &library.ratings.artists {
+        let Some(id) = artists.resolve(&rating.item) else {
+            report.unresolved.push(unresolved(
+                UnresolvedItemType::Artist,
+                rating.item.title.clone(),
+                "rating",
+            ));
+            continue;
+        };
+
+        moosicbox_library::db::set_artist_rating(library_db, id, to_set_rating(rating)).await?;
+        report.ratings += 1;
+    }
+
+    for rating in &library.ratings.albums {
+        let Some(id) = albums.resolve(&rating.item) else {
+            report.unresolved.push(unresolved(
+                UnresolvedItemType::Album,
+                describe_album(&rating.item),
+                "rating",
+            ));
+            continue;
+        };
+
+        moosicbox_library::db::set_album_rating(library_db, id, to_set_rating(rating)).await?;
+        report.ratings += 1;
+    }
+
+    for rating in &library.ratings.tracks {
+        let Some(id) = tracks.resolve(&rating.item) else {
+            report.unresolved.push(unresolved(
+                UnresolvedItemType::Track,
+                describe_track(&rating.item),
+                "rating",
+            ));
+            continue;
+        };
+
+        moosicbox_library::db::set_track_rating(library_db, id, to_set_rating(rating)).await?;
+        report.ratings += 1;
+    }
+
+    // Sessions imported before are matched by their name and playlist, so
+    // importing the same archive again updates them instead of duplicating
+    let mut existing_sessions = vec![];
+
+    for session in moosicbox_session::get_sessions(library_db).await? {
+        let tracks =
+            moosicbox_session::get_session_playlist_tracks(library_db, session.playlist.id).await?;
+        existing_sessions.push((session.id, session.name, tracks));
+    }
+
+    for session in &library.sessions {
+        let context = format!("session '{}'", session.name);
+
+        let playlist_tracks = session
+            .tracks
+            .iter()
+            .filter_map(|track| match track {
+                ArchiveSessionTrack::Library { track } => {
+                    let id = tracks.resolve(track);
+
+                    if id.is_none() {
+                        report.unresolved.push(unresolved(
+                            UnresolvedItemType::Track,
+                            describe_track(track),
+                            &context,
+                        ));
+                    }
+
+                    id.map(|id| SessionPlaylistTrack {
+                        id: id.to_string(),
+                        r#type: ApiSource::Library,
+                        data: None,
+                    })
+                }
+                ArchiveSessionTrack::Remote { source, id, data } => Some(SessionPlaylistTrack {
+                    id: id.clone(),
+                    r#type: *source,
+                    data: data.clone(),
+                }),
+            })
+            .collect::<Vec<_>>();
+
+        let playback_target = match &session.playback_target {
+            Some(PlaybackTarget::AudioZone { audio_zone_id }) => {
+                let id = audio_zone_ids.get(audio_zone_id).copied();
+
+                if id.is_none() {
+                    log::warn!("Audio zone {audio_zone_id} for {context} wasn't imported");
+                }
+
+                id.map(|audio_zone_id| PlaybackTarget::AudioZone { audio_zone_id })
+            }
+            target => target.clone(),
+        };
+
+        let session = ArchiveSession {
+            playback_target,
+            ..session.clone()
+        };
+
+        let existing = existing_sessions
+            .iter()
+            .position(|(_, name, tracks)| *name == session.name && *tracks == playlist_tracks);
+
+        if let Some(index) = existing {
+            let (session_id, ..) = existing_sessions.remove(index);
+            db::update_session(library_db, session_id, &session).await?;
+        } else {
+            db::insert_session(library_db, &session, &playlist_tracks).await?;
+        }
+        report.sessions += 1;
+    }
+
+    Ok(())
+}
+
+fn to_set_rating<T>(rating: &ArchiveRating<T>) -> SetRating {
+    SetRating {
+        rating: Some(rating.rating),
+        loved: Some(rating.loved),
+        banned: Some(rating.banned),
+    }
+}
+
+async fn import_favorite(
+    library_db: &LibraryDatabase,
+    favorite: &ArchiveFavoriteAlbum,
+) -> Result<(), ImportError> {
+    let mut artist = HashMap::from([(
+        "title",
+        DatabaseValue::String(favorite.artist.title.clone()),
+    )]);
+    if let Some(id) = favorite.artist.tidal_id {
+        artist.insert("tidal_id", DatabaseValue::UNumber(id));
+    }
+    if let Some(id) = favorite.artist.qobuz_id {
+        artist.insert("qobuz_id", DatabaseValue::UNumber(id));
+    }
+    if let Some(id) = favorite.artist.yt_id {
+        artist.insert("yt_id", DatabaseValue::UNumber(id));
+    }
+    let artist = moosicbox_library::db::add_artist_map_and_get_artist(library_db, artist).await?;
+
+    let mut album = HashMap::from([
+        ("artist_id", DatabaseValue::UNumber(artist.id)),
+        ("title", DatabaseValue::String(favorite.title.clone())),
+        (
+            "date_released",
+            DatabaseValue::StringOpt(favorite.date_released.clone()),
+        ),
+    ]);
+    if let Some(id) = favorite.tidal_id {
+        album.insert("tidal_id", DatabaseValue::UNumber(id));
+    }
+    if let Some(id) = &favorite.qobuz_id {
+        album.insert("qobuz_id", DatabaseValue::String(id.clone()));
+    }
+    if let Some(id) = favorite.yt_id {
+        album.insert("yt_id", DatabaseValue::UNumber(id));
+    }
+    let album = moosicbox_library::db::add_album_map_and_get_album(library_db, album).await?;
+
+    if favorite.tracks.is_empty() {
+        return Ok(());
+    }
+
+    moosicbox_library::db::add_tracks(
+        library_db,
+        favorite
+            .tracks
+            .iter()
+            .map(|track| InsertTrack {
+                track: LibraryTrack {
+                    number: track.number,
+                    title: track.title.clone(),
+                    duration: track.duration,
+                    format: track.format,
+                    source: track.source,
+                    ..Default::default()
+                },
+                album_id: album.id,
+                file: None,
+                qobuz_id: track.qobuz_id,
+                tidal_id: track.tidal_id,
+            })
+            .collect(),
+    )
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use moosicbox_library::db::{set_album_rating, set_track_rating};
+    use moosicbox_schema::fixtures;
+    use moosicbox_session::models::{CreateSession, CreateSessionPlaylist};
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    async fn seeded_dbs() -> (ConfigDatabase, LibraryDatabase, fixtures::SeededLibrary) {
+        let config_db = fixtures::config_db().await;
+        let library_db = fixtures::library_db().await;
+        let library = fixtures::seed_library(&**library_db.database)
+            .await
+            .unwrap();
+
+        (config_db, library_db, library)
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn export_round_trips_through_import() {
+        let (config_db, library_db, library) = seeded_dbs().await;
+
+        moosicbox_config::upsert_profile(&config_db, "fixture")
+            .await
+            .unwrap();
+        moosicbox_session::register_connection(
+            &config_db,
+            &RegisterConnection {
+                connection_id: "connection".to_string(),
+                name: "Living room".to_string(),
+                players: vec![RegisterPlayer {
+                    audio_output_id: "output".to_string(),
+                    name: "Speakers".to_string(),
+                }],
+            },
+        )
+        .await
+        .unwrap();
+        set_track_rating(
+            &library_db,
+            library.tracks[1],
+            SetRating {
+                rating: Some(Some(4)),
+                loved: Some(true),
+                ..SetRating::default()
+            },
+        )
+        .await
+        .unwrap();
+        set_album_rating(
+            &library_db,
+            library.albums[0],
+            SetRating {
+                banned: Some(true),
+                ..SetRating::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let archive = export(&config_db, &library_db).await.unwrap();
+        let archive: LibraryArchive =
+            serde_json::from_str(&serde_json::to_string(&archive).unwrap()).unwrap();
+
+        let (imported_config_db, imported_library_db, _) = seeded_dbs().await;
+        let report = import(&imported_config_db, &imported_library_db, &archive)
+            .await
+            .unwrap();
+
+        assert_eq!(report.profiles, 1);
+        assert_eq!(report.connections, 1);
+        assert_eq!(report.ratings, 2);
+        assert_eq!(report.unresolved, vec![]);
+
+        let imported = export(&imported_config_db, &imported_library_db)
+            .await
+            .unwrap();
+
+        assert_eq!(imported.config, archive.config);
+        assert_eq!(imported.library, archive.library);
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn importing_the_same_archive_twice_does_not_duplicate_sessions() {
+        let (config_db, library_db, library) = seeded_dbs().await;
+
+        moosicbox_session::create_session(
+            &library_db,
+            &CreateSession {
+                name: "Kitchen".to_string(),
+                audio_zone_id: None,
+                playlist: CreateSessionPlaylist {
+                    tracks: vec![library.tracks[0], library.tracks[1]],
+                },
+            },
+        )
+        .await
+        .unwrap();
+
+        let archive = export(&config_db, &library_db).await.unwrap();
+
+        let (imported_config_db, imported_library_db, _) = seeded_dbs().await;
+
+        for _ in 0..2 {
+            let report = import(&imported_config_db, &imported_library_db, &archive)
+                .await
+                .unwrap();
+
+            assert_eq!(report.sessions, 1);
+            assert_eq!(
+                moosicbox_session::get_sessions(&imported_library_db)
+                    .await
+                    .unwrap()
+                    .len(),
+                1
+            );
+        }
+
+        let imported = export(&imported_config_db, &imported_library_db)
+            .await
+            .unwrap();
+
+        assert_eq!(imported.library.sessions, archive.library.sessions);
+    }
+}
@@ -0,0 +1,238 @@
+use moosicbox_core::{
+    sqlite::models::{ApiSource, TrackApiSource},
+    types::AudioFormat,
+};
+use moosicbox_session::models::PlaybackTarget;
+use serde::{Deserialize, Serialize};
+
+/// The version of the archive format written by [`crate::export`]. Bumped
+/// whenever the format changes in a way older importers can't handle.
+pub const LIBRARY_ARCHIVE_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryArchive {
+    pub version: u32,
+    pub exported_at: String,
+    #[serde(default)]
+    pub config: ConfigArchive,
+    #[serde(default)]
+    pub library: LibraryDataArchive,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigArchive {
+    #[serde(default)]
+    pub profiles: Vec<String>,
+    #[serde(default)]
+    pub connections: Vec<ArchiveConnection>,
+    #[serde(default)]
+    pub audio_zones: Vec<ArchiveAudioZone>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveConnection {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub players: Vec<ArchivePlayer>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchivePlayer {
+    pub audio_output_id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveAudioZone {
+    /// The id of the audio zone in the exported database. Only used to map
+    /// the sessions' playback targets to the imported audio zones.
+    pub id: u64,
+    pub name: String,
+    #[serde(default)]
+    pub players: Vec<ArchivePlayerRef>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchivePlayerRef {
+    pub connection_id: String,
+    pub audio_output_id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryDataArchive {
+    #[serde(default)]
+    pub scan_locations: Vec<ArchiveScanLocation>,
+    #[serde(default)]
+    pub favorites: Vec<ArchiveFavoriteAlbum>,
+    #[serde(default)]
+    pub ratings: ArchiveRatings,
+    #[serde(default)]
+    pub sessions: Vec<ArchiveSession>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveScanLocation {
+    pub origin: String,
+    pub path: Option<String>,
+}
+
+/// Identifies an artist independently of its database id.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ArtistIdentity {
+    pub title: String,
+    pub tidal_id: Option<u64>,
+    pub qobuz_id: Option<u64>,
+    pub yt_id: Option<u64>,
+}
+
+/// Identifies an album independently of its database id.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct AlbumIdentity {
+    pub title: String,
+    pub artist: String,
+    pub directory: Option<String>,
+    pub tidal_id: Option<u64>,
+    pub qobuz_id: Option<String>,
+    pub yt_id: Option<u64>,
+}
+
+/// Identifies a track independently of its database id. Tracks are
+/// resolved by their file path first, then by their source ids and finally
+/// by their tags.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct TrackIdentity {
+    pub title: String,
+    pub album: String,
+    pub artist: String,
+    pub number: u32,
+    pub duration: f64,
+    pub path: Option<String>,
+    pub tidal_id: Option<u64>,
+    pub qobuz_id: Option<u64>,
+    pub yt_id: Option<u64>,
+}
+
+/// An album from a streaming source (Tidal, Qobuz, YouTube Music) that was
+/// added to the library. Local albums aren't exported since they are
+/// rebuilt by scanning the scan locations.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveFavoriteAlbum {
+    pub artist: ArtistIdentity,
+    pub title: String,
+    pub date_released: Option<String>,
+    pub tidal_id: Option<u64>,
+    pub qobuz_id: Option<String>,
+    pub yt_id: Option<u64>,
+    #[serde(default)]
+    pub tracks: Vec<ArchiveFavoriteTrack>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveFavoriteTrack {
+    pub number: u32,
+    pub title: String,
+    pub duration: f64,
+    pub format: Option<AudioFormat>,
+    pub source: TrackApiSource,
+    pub tidal_id: Option<u64>,
+    pub qobuz_id: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveRatings {
+    #[serde(default)]
+    pub artists: Vec<ArchiveRating<ArtistIdentity>>,
+    #[serde(default)]
+    pub albums: Vec<ArchiveRating<AlbumIdentity>>,
+    #[serde(default)]
+    pub tracks: Vec<ArchiveRating<TrackIdentity>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveRating<T> {
+    pub item: T,
+    pub rating: Option<u8>,
+    pub loved: bool,
+    pub banned: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveSession {
+    pub name: String,
+    pub active: bool,
+    pub playing: bool,
+    pub position: Option<u16>,
+    pub seek: Option<u64>,
+    pub volume: Option<f64>,
+    pub playback_target: Option<PlaybackTarget>,
+    #[serde(default)]
+    pub tracks: Vec<ArchiveSessionTrack>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+#[serde(tag = "type")]
+pub enum ArchiveSessionTrack {
+    #[serde(rename_all = "camelCase")]
+    Library { track: TrackIdentity },
+    #[serde(rename_all = "camelCase")]
+    Remote {
+        source: ApiSource,
+        id: String,
+        data: Option<String>,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum UnresolvedItemType {
+    Artist,
+    Album,
+    Track,
+    Player,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UnresolvedItem {
+    pub r#type: UnresolvedItemType,
+    /// A human readable description of the item that couldn't be resolved
+    pub item: String,
+    /// What the item was being imported for, e.g. a rating or a session
+    pub context: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ImportReport {
+    pub profiles: usize,
+    pub connections: usize,
+    pub audio_zones: usize,
+    pub scan_locations: usize,
+    pub favorite_albums: usize,
+    pub favorite_tracks: usize,
+    pub ratings: usize,
+    pub sessions: usize,
+    pub unresolved: Vec<UnresolvedItem>,
+}
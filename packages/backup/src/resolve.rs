@@ -0,0 +1,266 @@
+use std::collections::HashMap;
+
+use moosicbox_library::models::{LibraryAlbum, LibraryArtist, LibraryTrack};
+
+use crate::models::{AlbumIdentity, ArtistIdentity, TrackIdentity};
+
+fn normalize(value: &str) -> String {
+    value.trim().to_lowercase()
+}
+
+/// Maps exported [`ArtistIdentity`]s to the artist ids in the target
+/// database by their source ids, falling back to the artist name.
+#[derive(Default)]
+pub struct ArtistResolver {
+    by_tidal_id: HashMap<u64, u64>,
+    by_qobuz_id: HashMap<u64, u64>,
+    by_yt_id: HashMap<u64, u64>,
+    by_title: HashMap<String, u64>,
+}
+
+impl ArtistResolver {
+    pub fn new(artists: &[LibraryArtist]) -> Self {
+        let mut resolver = Self::default();
+
+        for artist in artists {
+            if let Some(id) = artist.tidal_id {
+                resolver.by_tidal_id.insert(id, artist.id);
+            }
+            if let Some(id) = artist.qobuz_id {
+                resolver.by_qobuz_id.insert(id, artist.id);
+            }
+            if let Some(id) = artist.yt_id {
+                resolver.by_yt_id.insert(id, artist.id);
+            }
+            resolver
+                .by_title
+                .entry(normalize(&artist.title))
+                .or_insert(artist.id);
+        }
+
+        resolver
+    }
+
+    pub fn resolve(&self, identity: &ArtistIdentity) -> Option<u64> {
+        identity
+            .tidal_id
+            .and_then(|id| self.by_tidal_id.get(&id))
+            .or_else(|| identity.qobuz_id.and_then(|id| self.by_qobuz_id.get(&id)))
+            .or_else(|| identity.yt_id.and_then(|id| self.by_yt_id.get(&id)))
+            .or_else(|| self.by_title.get(&normalize(&identity.title)))
+            .copied()
+    }
+}
+
+/// Maps exported [`AlbumIdentity`]s to the album ids in the target database
+/// by their directory, then their source ids and finally their title and
+/// album artist.
+#[derive(Default)]
+pub struct AlbumResolver {
+    by_directory: HashMap<String, u64>,
+    by_tidal_id: HashMap<u64, u64>,
+    by_qobuz_id: HashMap<String, u64>,
+    by_yt_id: HashMap<u64, u64>,
+    by_tags: HashMap<(String, String), u64>,
+}
+
+impl AlbumResolver {
+    pub fn new(albums: &[LibraryAlbum]) -> Self {
+        let mut resolver = Self::default();
+
+        for album in albums {
+            if let Some(directory) = &album.directory {
+                resolver.by_directory.insert(directory.clone(), album.id);
+            }
+            if let Some(id) = album.tidal_id {
+                resolver.by_tidal_id.insert(id, album.id);
+            }
+            if let Some(id) = &album.qobuz_id {
+                resolver.by_qobuz_id.insert(id.clone(), album.id);
+            }
+            if let Some(id) = album.yt_id {
+                resolver.by_yt_id.insert(id, album.id);
+            }
+            resolver
+                .by_tags
+                .entry((normalize(&album.title), normalize(&album.artist)))
+                .or_insert(album.id);
+        }
+
+        resolver
+    }
+
+    pub fn resolve(&self, identity: &AlbumIdentity) -> Option<u64> {
+        identity
+            .directory
+            .as_ref()
+            .and_then(|x| self.by_directory.get(x))
+            .or_else(|| identity.tidal_id.and_then(|id| self.by_tidal_id.get(&id)))
+            .or_else(|| {
+                identity
+                    .qobuz_id
+                    .as_ref()
+                    .and_then(|id| self.by_qobuz_id.get(id))
+            })
+            .or_else(|| identity.yt_id.and_then(|id| self.by_yt_id.get(&id)))
+            .or_else(|| {
+                self.by_tags
+                    .get(&(normalize(&identity.title), normalize(&identity.artist)))
+            })
+            .copied()
+    }
+}
+
+/// Maps exported [`TrackIdentity`]s to the track ids in the target database
+/// by their file path, then their source ids and finally their title, album
+/// and album artist tags.
+#[derive(Default)]
+pub struct TrackResolver {
+    by_path: HashMap<String, u64>,
+    by_tidal_id: HashMap<u64, u64>,
+    by_qobuz_id: HashMap<u64, u64>,
+    by_yt_id: HashMap<u64, u64>,
+    by_tags: HashMap<(String, String, String), Vec<(u32, u64)>>,
+}
+
+impl TrackResolver {
+    pub fn new(tracks: &[LibraryTrack]) -> Self {
+        let mut resolver = Self::default();
+
+        for track in tracks {
+            if let Some(file) = &track.file {
+                resolver.by_path.insert(file.clone(), track.id);
+            }
+            if let Some(id) = track.tidal_id {
+                resolver.by_tidal_id.insert(id, track.id);
+            }
+            if let Some(id) = track.qobuz_id {
+                resolver.by_qobuz_id.insert(id, track.id);
+            }
+            if let Some(id) = track.yt_id {
+                resolver.by_yt_id.insert(id, track.id);
+            }
+            resolver
+                .by_tags
+                .entry((
+                    normalize(&track.title),
+                    normalize(&track.album),
+                    normalize(&track.artist),
+                ))
+                .or_default()
+                .push((track.number, track.id));
+        }
+
+        resolver
+    }
+
+    pub fn resolve(&self, identity: &TrackIdentity) -> Option<u64> {
+        identity
+            .path
+            .as_ref()
+            .and_then(|x| self.by_path.get(x))
+            .or_else(|| identity.tidal_id.and_then(|id| self.by_tidal_id.get(&id)))
+            .or_else(|| identity.qobuz_id.and_then(|id| self.by_qobuz_id.get(&id)))
+            .or_else(|| identity.yt_id.and_then(|id| self.by_yt_id.get(&id)))
+            .copied()
+            .or_else(|| self.resolve_by_tags(identity))
+    }
+
+    fn resolve_by_tags(&self, identity: &TrackIdentity) -> Option<u64> {
+        let candidates = self.by_tags.get(&(
+            normalize(&identity.title),
+            normalize(&identity.album),
+            normalize(&identity.artist),
+        ))?;
+
+        candidates
+            .iter()
+            .find(|(number, _)| *number == identity.number)
+            .or_else(|| (candidates.len() == 1).then(|| &candidates[0]))
+            .map(|(_, id)| *id)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use moosicbox_core::sqlite::models::TrackApiSource;
+
+    use super::*;
+
+    fn track(id: u64, number: u32, title: &str, file: Option<&str>) -> LibraryTrack {
+        LibraryTrack {
+            id,
+            number,
+            title: title.to_string(),
+            album: "Album".to_string(),
+            artist: "Artist".to_string(),
+            file: file.map(ToString::to_string),
+            source: TrackApiSource::Local,
+            ..Default::default()
+        }
+    }
+
+    fn identity(number: u32, title: &str, path: Option<&str>) -> TrackIdentity {
+        TrackIdentity {
+            title: title.to_string(),
+            album: "Album".to_string(),
+            artist: "Artist".to_string(),
+            number,
+            path: path.map(ToString::to_string),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn track_resolver_prefers_the_file_path() {
+        let resolver = TrackResolver::new(&[
+            track(1, 1, "Song", Some("/music/a.flac")),
+            track(2, 1, "Song", Some("/music/b.flac")),
+        ]);
+
+        assert_eq!(
+            resolver.resolve(&identity(1, "Song", Some("/music/b.flac"))),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn track_resolver_falls_back_to_source_ids() {
+        let resolver = TrackResolver::new(&[LibraryTrack {
+            tidal_id: Some(1234),
+            source: TrackApiSource::Tidal,
+            ..track(3, 1, "Remote", None)
+        }]);
+
+        assert_eq!(
+            resolver.resolve(&TrackIdentity {
+                tidal_id: Some(1234),
+                ..identity(1, "Renamed", None)
+            }),
+            Some(3)
+        );
+    }
+
+    #[test]
+    fn track_resolver_falls_back_to_tags() {
+        let resolver = TrackResolver::new(&[
+            track(4, 1, "Song", Some("/new/a.flac")),
+            track(5, 2, "song ", Some("/new/b.flac")),
+        ]);
+
+        assert_eq!(
+            resolver.resolve(&identity(2, "Song", Some("/old/b.flac"))),
+            Some(5)
+        );
+    }
+
+    #[test]
+    fn track_resolver_doesnt_guess_between_ambiguous_tracks() {
+        let resolver = TrackResolver::new(&[
+            track(6, 1, "Song", Some("/new/a.flac")),
+            track(7, 2, "Song", Some("/new/b.flac")),
+        ]);
+
+        assert_eq!(resolver.resolve(&identity(3, "Song", None)), None);
+    }
+}
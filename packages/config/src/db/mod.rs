@@ -44,6 +44,34 @@ pub(crate) async fn get_or_init_server_identity(
     }
 }
 
+pub(crate) async fn get_server_signing_key(
+    db: &ConfigDatabase,
+) -> Result<Option<String>, DatabaseError> {
+    Ok(db
+        .select("identity")
+        .columns(&["signing_key"])
+        .execute_first(db)
+        .await?
+        .and_then(|x| {
+            x.get("signing_key")
+                .and_then(|x| x.as_str().map(std::string::ToString::to_string))
+        }))
+}
+
+pub(crate) async fn set_server_signing_key(
+    db: &ConfigDatabase,
+    signing_key: &str,
+) -> Result<(), GetOrInitServerIdentityError> {
+    get_or_init_server_identity(db).await?;
+
+    db.update("identity")
+        .value("signing_key", signing_key)
+        .execute(db)
+        .await?;
+
+    Ok(())
+}
+
 #[allow(unused)]
 pub(crate) async fn upsert_profile(
     db: &ConfigDatabase,
@@ -106,6 +106,21 @@ mod db_impl {
         crate::db::get_or_init_server_identity(db).await
     }
 
+    pub async fn get_server_signing_key(
+        db: &ConfigDatabase,
+    ) -> Result<Option<String>, DatabaseError> {
+        crate::db::get_server_signing_key(db).await
+    }
+
+    /// Stores the server's long-term signing key, creating the server
+    /// identity first if it doesn't exist yet.
+    pub async fn set_server_signing_key(
+        db: &ConfigDatabase,
+        signing_key: &str,
+    ) -> Result<(), GetOrInitServerIdentityError> {
+        crate::db::set_server_signing_key(db, signing_key).await
+    }
+
     pub async fn upsert_profile(
         db: &ConfigDatabase,
         name: &str,
@@ -218,6 +218,8 @@ pub struct Track {
     pub artist: String,
     pub artist_id: Id,
     pub file: Option<String>,
+    pub start_offset: Option<f64>,
+    pub end_offset: Option<f64>,
     pub artwork: Option<String>,
     pub blur: bool,
     pub bytes: u64,
@@ -246,6 +248,8 @@ struct TrackInner {
     artist: String,
     artist_id: Id,
     file: Option<String>,
+    start_offset: Option<f64>,
+    end_offset: Option<f64>,
     artwork: Option<String>,
     blur: bool,
     bytes: u64,
@@ -284,6 +288,8 @@ impl From<TrackInner> for Track {
             artist: value.artist,
             artist_id: value.artist_id,
             file: value.file,
+            start_offset: value.start_offset,
+            end_offset: value.end_offset,
             artwork: value.artwork,
             blur: value.blur,
             bytes: value.bytes,
@@ -528,6 +534,8 @@ pub enum AlbumSort {
     ReleaseDateDesc,
     DateAddedAsc,
     DateAddedDesc,
+    RatingAsc,
+    RatingDesc,
 }
 
 impl FromStr for AlbumSort {
@@ -543,6 +551,8 @@ impl FromStr for AlbumSort {
             "release-date-desc" => Ok(AlbumSort::ReleaseDateDesc),
             "date-added-asc" | "date-added" => Ok(AlbumSort::DateAddedAsc),
             "date-added-desc" => Ok(AlbumSort::DateAddedDesc),
+            "rating-asc" => Ok(AlbumSort::RatingAsc),
+            "rating-desc" | "rating" => Ok(AlbumSort::RatingDesc),
             _ => Err(()),
         }
     }
@@ -0,0 +1,173 @@
+//! Measures browse query latency while a scan is writing to the database, with
+//! every statement on a single connection and with a pool of read
+//! connections.
+//!
+//! `cargo bench -p moosicbox_database --bench rusqlite_read_pool`
+
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use moosicbox_database::{
+    query::{FilterableQuery as _, SortDirection},
+    rusqlite::{RusqliteDatabase, RusqlitePoolConfig},
+    Database,
+};
+
+const ALBUMS: u64 = 500;
+const TRACKS_PER_ALBUM: u64 = 12;
+const SCAN_BATCHES: u64 = 200;
+const SCAN_BATCH_SIZE: u64 = 250;
+const BROWSERS: usize = 8;
+
+fn db_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("moosicbox_bench_{name}_{}.db", std::process::id()))
+}
+
+fn remove_db(path: &Path) {
+    for suffix in ["", "-wal", "-shm"] {
+        let mut path = path.as_os_str().to_owned();
+        path.push(suffix);
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+async fn seed(db: &dyn Database) {
+    db.exec_raw(
+        "CREATE TABLE tracks (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            album_id INTEGER NOT NULL,
+            number INTEGER NOT NULL,
+            title TEXT NOT NULL,
+            duration REAL NOT NULL
+        );
+        CREATE INDEX tracks_album_id ON tracks(album_id);",
+    )
+    .await
+    .unwrap();
+
+    let tx = db.begin_transaction().await.unwrap();
+    for album_id in 0..ALBUMS {
+        for number in 1..=TRACKS_PER_ALBUM {
+            tx.insert("tracks")
+                .value("album_id", album_id)
+                .value("number", number)
+                .value("title", format!("Track {number}"))
+                .value("duration", 200.0)
+                .execute(&*tx)
+                .await
+                .unwrap();
+        }
+    }
+    tx.commit().await.unwrap();
+}
+
+async fn scan(db: Arc<Box<dyn Database>>) {
+    for batch in 0..SCAN_BATCHES {
+        let tx = db.begin_transaction().await.unwrap();
+        for i in 0..SCAN_BATCH_SIZE {
+            tx.insert("tracks")
+                .value("album_id", ALBUMS + batch)
+                .value("number", i)
+                .value("title", format!("Scanned {i}"))
+                .value("duration", 180.0)
+                .execute(&*tx)
+                .await
+                .unwrap();
+        }
+        tx.commit().await.unwrap();
+    }
+}
+
+async fn browse(
+    db: Arc<Box<dyn Database>>,
+    scanning: Arc<AtomicBool>,
+    offset: u64,
+) -> Vec<Duration> {
+    let mut latencies = vec![];
+    let mut album_id = offset;
+
+    while scanning.load(Ordering::SeqCst) {
+        let start = Instant::now();
+        db.select("tracks")
+            .where_eq("album_id", album_id % ALBUMS)
+            .sort("number", SortDirection::Asc)
+            .execute(&**db)
+            .await
+            .unwrap();
+        latencies.push(start.elapsed());
+        album_id += 7;
+        tokio::task::yield_now().await;
+    }
+
+    latencies
+}
+
+async fn run(name: &str, config: RusqlitePoolConfig) {
+    let path = db_path(name);
+    remove_db(&path);
+
+    let db: Arc<Box<dyn Database>> =
+        Arc::new(Box::new(RusqliteDatabase::open(&path, &config).unwrap()));
+    seed(&**db).await;
+
+    let scanning = Arc::new(AtomicBool::new(true));
+    let browsers = (0..BROWSERS)
+        .map(|i| tokio::spawn(browse(db.clone(), scanning.clone(), i as u64)))
+        .collect::<Vec<_>>();
+
+    let start = Instant::now();
+    tokio::spawn(scan(db.clone())).await.unwrap();
+    let scan_duration = start.elapsed();
+    scanning.store(false, Ordering::SeqCst);
+
+    let mut latencies = vec![];
+    for browser in browsers {
+        latencies.extend(browser.await.unwrap());
+    }
+    latencies.sort();
+
+    let percentile = |p: usize| {
+        latencies
+            .get((latencies.len() * p / 100).min(latencies.len().saturating_sub(1)))
+            .copied()
+            .unwrap_or_default()
+    };
+
+    println!(
+        "{name:>8}: scan={scan_duration:?} queries={} p50={:?} p95={:?} p99={:?} max={:?}",
+        latencies.len(),
+        percentile(50),
+        percentile(95),
+        percentile(99),
+        latencies.last().copied().unwrap_or_default(),
+    );
+
+    drop(db);
+    remove_db(&path);
+}
+
+fn main() {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(BROWSERS + 2)
+        .enable_all()
+        .build()
+        .unwrap();
+
+    runtime.block_on(async {
+        run(
+            "single",
+            RusqlitePoolConfig {
+                read_connections: 0,
+                ..Default::default()
+            },
+        )
+        .await;
+        run("pooled", RusqlitePoolConfig::default()).await;
+    });
+}
@@ -0,0 +1,512 @@
+use std::sync::{LazyLock, Mutex};
+
+use async_trait::async_trait;
+use tokio::sync::broadcast;
+
+use crate::{
+    query::{
+        DeleteStatement, InsertStatement, SelectQuery, UpdateStatement, UpsertMultiStatement,
+        UpsertStatement,
+    },
+    Database, DatabaseError, DatabaseTransaction, DatabaseValue, Row,
+};
+
+pub const DEFAULT_CHANGE_FEED_CAPACITY: usize = 1024;
+
+/// The feed every [`ChangeFeedDatabase`] that isn't given its own
+/// [`ChangeFeed`] publishes to.
+pub static DATABASE_CHANGES: LazyLock<ChangeFeed> =
+    LazyLock::new(|| ChangeFeed::new(DEFAULT_CHANGE_FEED_CAPACITY));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "api", derive(serde::Serialize))]
+#[cfg_attr(feature = "api", serde(rename_all = "SCREAMING_SNAKE_CASE"))]
+pub enum ChangeOperation {
+    Insert,
+    Update,
+    Upsert,
+    Delete,
+}
+
+/// A successful insert, update, upsert or delete of one or more rows in a
+/// table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DatabaseChange {
+    /// The library profile of the database the change was made in. `None`
+    /// for databases that aren't scoped to a profile, e.g. the config
+    /// database.
+    pub profile: Option<String>,
+    pub table: String,
+    pub operation: ChangeOperation,
+    /// The `id` of every row the statement affected. Empty when the table has
+    /// no `id` column.
+    pub keys: Vec<DatabaseValue>,
+}
+
+impl DatabaseChange {
+    fn new<'a>(
+        table: &str,
+        operation: ChangeOperation,
+        rows: impl Iterator<Item = &'a Row>,
+    ) -> Self {
+        Self {
+            profile: None,
+            table: table.to_string(),
+            operation,
+            keys: rows.filter_map(Row::id).collect(),
+        }
+    }
+}
+
+/// A broadcast channel of [`DatabaseChange`]s.
+///
+/// Subscribers that fall more than the feed's capacity behind miss the oldest
+/// changes and get a [`broadcast::error::RecvError::Lagged`].
+#[derive(Debug, Clone)]
+pub struct ChangeFeed {
+    sender: broadcast::Sender<DatabaseChange>,
+}
+
+impl ChangeFeed {
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            sender: broadcast::channel(capacity).0,
+        }
+    }
+
+    #[must_use]
+    pub fn subscribe(&self) -> broadcast::Receiver<DatabaseChange> {
+        self.sender.subscribe()
+    }
+
+    fn publish(&self, change: DatabaseChange) {
+        log::trace!("publish: {change:?}");
+        // Only fails when nobody is subscribed
+        let _ = self.sender.send(change);
+    }
+}
+
+/// A [`Database`] that publishes a [`DatabaseChange`] to its [`ChangeFeed`]
+/// after every successful insert, update, upsert and delete that affects at
+/// least one row.
+///
+/// Changes made in a transaction are published once it's committed, and
+/// dropped if it's rolled back. Raw statements aren't inspected, so they
+/// don't publish anything.
+#[derive(Debug)]
+pub struct ChangeFeedDatabase {
+    inner: Box<dyn Database>,
+    feed: ChangeFeed,
+    profile: Option<String>,
+}
+
+impl ChangeFeedDatabase {
+    #[must_use]
+    pub fn new(inner: Box<dyn Database>) -> Self {
+        Self {
+            inner,
+            feed: DATABASE_CHANGES.clone(),
+            profile: None,
+        }
+    }
+
+    #[must_use]
+    pub fn with_feed(mut self, feed: ChangeFeed) -> Self {
+        self.feed = feed;
+        self
+    }
+
+    /// Tags the published changes with the library profile the database
+    /// belongs to.
+    #[must_use]
+    pub fn with_profile(mut self, profile: impl Into<String>) -> Self {
+        self.profile = Some(profile.into());
+        self
+    }
+
+    fn emit(&self, change: DatabaseChange) {
+        self.feed.publish(DatabaseChange {
+            profile: self.profile.clone(),
+            ..change
+        });
+    }
+}
+
+/// A transaction started on a [`ChangeFeedDatabase`]
+#[derive(Debug)]
+pub struct ChangeFeedTransaction {
+    inner: Box<dyn DatabaseTransaction>,
+    feed: ChangeFeed,
+    profile: Option<String>,
+    pending: Mutex<Vec<DatabaseChange>>,
+    savepoints: Mutex<Vec<(String, usize)>>,
+}
+
+impl ChangeFeedTransaction {
+    fn emit(&self, change: DatabaseChange) {
+        self.pending.lock().unwrap().push(DatabaseChange {
+            profile: self.profile.clone(),
+            ..change
+        });
+    }
+}
+
+macro_rules! impl_change_feed_database {
+    ($type:ty) => {
+        #[async_trait]
+        impl Database for $type {
+            async fn query(&self, query: &SelectQuery<'_>) -> Result<Vec<Row>, DatabaseError> {
+                self.inner.query(query).await
+            }
+
+            async fn query_first(
+                &self,
+                query: &SelectQuery<'_>,
+            ) -> Result<Option<Row>, DatabaseError> {
+                self.inner.query_first(query).await
+            }
+
+            async fn exec_update(
+                &self,
+                statement: &UpdateStatement<'_>,
+            ) -> Result<Vec<Row>, DatabaseError> {
+                let rows = self.inner.exec_update(statement).await?;
+                if !rows.is_empty() {
+                    self.emit(DatabaseChange::new(
+                        statement.table_name,
+                        ChangeOperation::Update,
+                        rows.iter(),
+                    ));
+                }
+                Ok(rows)
+            }
+
+            async fn exec_update_first(
+                &self,
+                statement: &UpdateStatement<'_>,
+            ) -> Result<Option<Row>, DatabaseError> {
+                let row = self.inner.exec_update_first(statement).await?;
+                if row.is_some() {
+                    self.emit(DatabaseChange::new(
+                        statement.table_name,
+                        ChangeOperation::Update,
+                        row.iter(),
+                    ));
+                }
+                Ok(row)
+            }
+
+            async fn exec_insert(
+                &self,
+                statement: &InsertStatement<'_>,
+            ) -> Result<Row, DatabaseError> {
+                let row = self.inner.exec_insert(statement).await?;
+                self.emit(DatabaseChange::new(
+                    statement.table_name,
+                    ChangeOperation::Insert,
+                    std::iter::once(&row),
+                ));
+                Ok(row)
+            }
+
+            async fn exec_upsert(
+                &self,
+                statement: &UpsertStatement<'_>,
+            ) -> Result<Vec<Row>, DatabaseError> {
+                let rows = self.inner.exec_upsert(statement).await?;
+                if !rows.is_empty() {
+                    self.emit(DatabaseChange::new(
+                        statement.table_name,
+                        ChangeOperation::Upsert,
+                        rows.iter(),
+                    ));
+                }
+                Ok(rows)
+            }
+
+            async fn exec_upsert_first(
+                &self,
+                statement: &UpsertStatement<'_>,
+            ) -> Result<Row, DatabaseError> {
+                let row = self.inner.exec_upsert_first(statement).await?;
+                self.emit(DatabaseChange::new(
+                    statement.table_name,
+                    ChangeOperation::Upsert,
+                    std::iter::once(&row),
+                ));
+                Ok(row)
+            }
+
+            async fn exec_upsert_multi(
+                &self,
+                statement: &UpsertMultiStatement<'_>,
+            ) -> Result<Vec<Row>, DatabaseError> {
+                let rows = self.inner.exec_upsert_multi(statement).await?;
+                if !rows.is_empty() {
+                    self.emit(DatabaseChange::new(
+                        statement.table_name,
+                        ChangeOperation::Upsert,
+                        rows.iter(),
+                    ));
+                }
+                Ok(rows)
+            }
+
+            async fn exec_delete(
+                &self,
+                statement: &DeleteStatement<'_>,
+            ) -> Result<Vec<Row>, DatabaseError> {
+                let rows = self.inner.exec_delete(statement).await?;
+                if !rows.is_empty() {
+                    self.emit(DatabaseChange::new(
+                        statement.table_name,
+                        ChangeOperation::Delete,
+                        rows.iter(),
+                    ));
+                }
+                Ok(rows)
+            }
+
+            async fn exec_delete_first(
+                &self,
+                statement: &DeleteStatement<'_>,
+            ) -> Result<Option<Row>, DatabaseError> {
+                let row = self.inner.exec_delete_first(statement).await?;
+                if row.is_some() {
+                    self.emit(DatabaseChange::new(
+                        statement.table_name,
+                        ChangeOperation::Delete,
+                        row.iter(),
+                    ));
+                }
+                Ok(row)
+            }
+
+            async fn exec_raw(&self, statement: &str) -> Result<(), DatabaseError> {
+                self.inner.exec_raw(statement).await
+            }
+
+            fn trigger_close(&self) -> Result<(), DatabaseError> {
+                self.inner.trigger_close()
+            }
+
+            async fn close(&self) -> Result<(), DatabaseError> {
+                self.inner.close().await
+            }
+
+            async fn begin_transaction(
+                &self,
+            ) -> Result<Box<dyn DatabaseTransaction>, DatabaseError> {
+                Ok(Box::new(ChangeFeedTransaction {
+                    inner: self.inner.begin_transaction().await?,
+                    feed: self.feed.clone(),
+                    profile: self.profile.clone(),
+                    pending: Mutex::new(vec![]),
+                    savepoints: Mutex::new(vec![]),
+                }))
+            }
+        }
+    };
+}
+
+impl_change_feed_database!(ChangeFeedDatabase);
+impl_change_feed_database!(ChangeFeedTransaction);
+
+#[async_trait]
+impl DatabaseTransaction for ChangeFeedTransaction {
+    async fn commit(self: Box<Self>) -> Result<(), DatabaseError> {
+        let Self {
+            inner,
+            feed,
+            pending,
+            ..
+        } = *self;
+
+        inner.commit().await?;
+
+        for change in pending.into_inner().unwrap() {
+            feed.publish(change);
+        }
+
+        Ok(())
+    }
+
+    async fn rollback(self: Box<Self>) -> Result<(), DatabaseError> {
+        self.inner.rollback().await
+    }
+
+    async fn savepoint(&self, name: &str) -> Result<(), DatabaseError> {
+        self.inner.savepoint(name).await?;
+        let position = self.pending.lock().unwrap().len();
+        self.savepoints
+            .lock()
+            .unwrap()
+            .push((name.to_string(), position));
+        Ok(())
+    }
+
+    async fn release_savepoint(&self, name: &str) -> Result<(), DatabaseError> {
+        self.inner.release_savepoint(name).await?;
+        let mut savepoints = self.savepoints.lock().unwrap();
+        if let Some(index) = savepoints.iter().rposition(|(x, _)| x == name) {
+            savepoints.truncate(index);
+        }
+        drop(savepoints);
+        Ok(())
+    }
+
+    async fn rollback_to_savepoint(&self, name: &str) -> Result<(), DatabaseError> {
+        self.inner.rollback_to_savepoint(name).await?;
+        let savepoints = self.savepoints.lock().unwrap();
+        let position = savepoints
+            .iter()
+            .rfind(|(x, _)| x == name)
+            .map(|(_, position)| *position);
+        drop(savepoints);
+        if let Some(position) = position {
+            self.pending.lock().unwrap().truncate(position);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "sqlite-rusqlite"))]
+mod test {
+    use std::sync::Arc;
+
+    use pretty_assertions::assert_eq;
+    use tokio::sync::broadcast::error::TryRecvError;
+
+    use crate::{query::FilterableQuery as _, rusqlite::RusqliteDatabase, Database, DatabaseValue};
+
+    use super::*;
+
+    async fn setup() -> (Box<dyn Database>, broadcast::Receiver<DatabaseChange>) {
+        let connection = ::rusqlite::Connection::open_in_memory().unwrap();
+        let db = RusqliteDatabase::new(Arc::new(tokio::sync::Mutex::new(connection)));
+        db.exec_raw(
+            "CREATE TABLE sessions (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT NOT NULL);
+            CREATE TABLE audio_zone_players (audio_zone_id INTEGER NOT NULL, player_id INTEGER NOT NULL);",
+        )
+        .await
+        .unwrap();
+
+        let feed = ChangeFeed::new(16);
+        let receiver = feed.subscribe();
+
+        (
+            Box::new(
+                ChangeFeedDatabase::new(Box::new(db))
+                    .with_feed(feed)
+                    .with_profile("master"),
+            ),
+            receiver,
+        )
+    }
+
+    fn changes(receiver: &mut broadcast::Receiver<DatabaseChange>) -> Vec<DatabaseChange> {
+        let mut changes = vec![];
+        loop {
+            match receiver.try_recv() {
+                Ok(change) => changes.push(change),
+                Err(TryRecvError::Empty) => break changes,
+                Err(e) => panic!("Failed to receive change: {e:?}"),
+            }
+        }
+    }
+
+    fn change(table: &str, operation: ChangeOperation, keys: &[i64]) -> DatabaseChange {
+        DatabaseChange {
+            profile: Some("master".to_string()),
+            table: table.to_string(),
+            operation,
+            keys: keys.iter().copied().map(DatabaseValue::Number).collect(),
+        }
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn publishes_changes_with_affected_keys() {
+        let (db, mut receiver) = setup().await;
+
+        for name in ["a", "b", "c"] {
+            db.insert("sessions")
+                .value("name", name)
+                .execute(&*db)
+                .await
+                .unwrap();
+        }
+        db.update("sessions")
+            .value("name", "z")
+            .where_gt("id", 1)
+            .execute(&*db)
+            .await
+            .unwrap();
+        db.delete("sessions")
+            .where_eq("id", 1)
+            .execute(&*db)
+            .await
+            .unwrap();
+        db.delete("sessions")
+            .where_eq("id", 10)
+            .execute(&*db)
+            .await
+            .unwrap();
+        db.insert("audio_zone_players")
+            .value("audio_zone_id", 1)
+            .value("player_id", 2)
+            .execute(&*db)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            changes(&mut receiver),
+            vec![
+                change("sessions", ChangeOperation::Insert, &[1]),
+                change("sessions", ChangeOperation::Insert, &[2]),
+                change("sessions", ChangeOperation::Insert, &[3]),
+                change("sessions", ChangeOperation::Update, &[2, 3]),
+                change("sessions", ChangeOperation::Delete, &[1]),
+                change("audio_zone_players", ChangeOperation::Insert, &[]),
+            ]
+        );
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn publishes_transaction_changes_on_commit() {
+        let (db, mut receiver) = setup().await;
+
+        let tx = db.begin_transaction().await.unwrap();
+        tx.insert("sessions")
+            .value("name", "a")
+            .execute(&*tx)
+            .await
+            .unwrap();
+        tx.savepoint("discarded").await.unwrap();
+        tx.insert("sessions")
+            .value("name", "b")
+            .execute(&*tx)
+            .await
+            .unwrap();
+        tx.rollback_to_savepoint("discarded").await.unwrap();
+
+        assert_eq!(changes(&mut receiver), vec![]);
+
+        tx.commit().await.unwrap();
+
+        assert_eq!(
+            changes(&mut receiver),
+            vec![change("sessions", ChangeOperation::Insert, &[1])]
+        );
+
+        let tx = db.begin_transaction().await.unwrap();
+        tx.insert("sessions")
+            .value("name", "c")
+            .execute(&*tx)
+            .await
+            .unwrap();
+        tx.rollback().await.unwrap();
+
+        assert_eq!(changes(&mut receiver), vec![]);
+    }
+}
@@ -1,6 +1,7 @@
 #![cfg_attr(feature = "fail-on-warnings", deny(warnings))]
 #![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
 
+pub mod changes;
 pub mod config;
 #[cfg(feature = "postgres-raw")]
 pub mod postgres;
@@ -11,6 +12,7 @@ pub mod rusqlite;
 pub mod sqlx;
 
 pub mod query;
+pub mod trace;
 
 use std::{num::TryFromIntError, sync::Arc};
 
@@ -213,6 +215,14 @@ pub enum DatabaseError {
     PostgresSqlx(sqlx::postgres::SqlxDatabaseError),
     #[error("No row")]
     NoRow,
+    #[error("Database is busy")]
+    Busy,
+    #[error("Transactions are not supported")]
+    TransactionsNotSupported,
+    #[error("Already in a transaction")]
+    AlreadyInTransaction,
+    #[error("Invalid savepoint name: '{0}'")]
+    InvalidSavepointName(String),
 }
 
 impl DatabaseError {
@@ -315,6 +325,14 @@ pub trait Database: Send + Sync + std::fmt::Debug {
         statement: &DeleteStatement<'_>,
     ) -> Result<Option<Row>, DatabaseError>;
 
+    /// Executes raw SQL, which may contain multiple statements. Intended for
+    /// schema changes that the query builder can't express.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if any of the statements failed to execute.
+    async fn exec_raw(&self, statement: &str) -> Result<(), DatabaseError>;
+
     /// # Errors
     ///
     /// Will return `Err` if the close failed to trigger.
@@ -325,6 +343,75 @@ pub trait Database: Send + Sync + std::fmt::Debug {
     async fn close(&self) -> Result<(), DatabaseError> {
         self.trigger_close()
     }
+
+    /// Begins a transaction. The statements executed on the returned handle
+    /// are only persisted once it is committed, and are rolled back if it is
+    /// dropped without being committed.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the transaction failed to begin or if the
+    /// database doesn't support transactions.
+    async fn begin_transaction(&self) -> Result<Box<dyn DatabaseTransaction>, DatabaseError> {
+        Err(DatabaseError::TransactionsNotSupported)
+    }
+}
+
+#[async_trait]
+pub trait DatabaseTransaction: Database {
+    /// # Errors
+    ///
+    /// Will return `Err` if the commit failed.
+    async fn commit(self: Box<Self>) -> Result<(), DatabaseError>;
+
+    /// # Errors
+    ///
+    /// Will return `Err` if the rollback failed.
+    async fn rollback(self: Box<Self>) -> Result<(), DatabaseError>;
+
+    /// # Errors
+    ///
+    /// Will return `Err` if the savepoint name is invalid or the savepoint
+    /// failed to be created.
+    async fn savepoint(&self, name: &str) -> Result<(), DatabaseError>;
+
+    /// # Errors
+    ///
+    /// Will return `Err` if the savepoint name is invalid or the savepoint
+    /// failed to be released.
+    async fn release_savepoint(&self, name: &str) -> Result<(), DatabaseError>;
+
+    /// Rolls back the statements executed since the savepoint was created,
+    /// keeping the savepoint and the rest of the transaction.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the savepoint name is invalid or the rollback
+    /// failed.
+    async fn rollback_to_savepoint(&self, name: &str) -> Result<(), DatabaseError>;
+}
+
+impl<'a> From<&'a dyn DatabaseTransaction> for &'a dyn Database {
+    fn from(value: &'a dyn DatabaseTransaction) -> Self {
+        value
+    }
+}
+
+/// Savepoint names are interpolated into the statements, so they are
+/// restricted to identifiers.
+#[allow(unused)]
+pub(crate) fn validate_savepoint_name(name: &str) -> Result<(), DatabaseError> {
+    let mut chars = name.chars();
+
+    if chars
+        .next()
+        .is_some_and(|x| x.is_ascii_alphabetic() || x == '_')
+        && chars.all(|x| x.is_ascii_alphanumeric() || x == '_')
+    {
+        Ok(())
+    } else {
+        Err(DatabaseError::InvalidSavepointName(name.to_string()))
+    }
 }
 
 #[async_trait]
@@ -391,3 +478,215 @@ where
         U::try_from_db(self, db).await
     }
 }
+
+#[cfg(test)]
+pub(crate) mod test {
+    use pretty_assertions::assert_eq;
+
+    use crate::{
+        query::{count_all, count_distinct, max, sum, FilterableQuery as _, SortDirection},
+        validate_savepoint_name, Database, DatabaseError, DatabaseValue, Row,
+    };
+
+    async fn names(db: &dyn Database) -> Vec<String> {
+        db.select("transaction_test")
+            .sort("id", SortDirection::Asc)
+            .execute(db)
+            .await
+            .unwrap()
+            .into_iter()
+            .filter_map(|row| {
+                row.get("name")
+                    .and_then(|x| x.as_str().map(ToString::to_string))
+            })
+            .collect()
+    }
+
+    async fn insert(db: &dyn Database, name: &str) {
+        db.insert("transaction_test")
+            .value("name", name)
+            .execute(db)
+            .await
+            .unwrap();
+    }
+
+    /// Runs the transaction scenarios against an empty `transaction_test`
+    /// table with an auto incrementing `id` and a `name` column.
+    pub async fn test_transactions(db: &dyn Database) {
+        let tx = db.begin_transaction().await.unwrap();
+        insert(&*tx, "rolled back").await;
+        assert_eq!(names(&*tx).await, vec!["rolled back"]);
+        tx.rollback().await.unwrap();
+        assert_eq!(names(db).await, Vec::<String>::new());
+
+        let tx = db.begin_transaction().await.unwrap();
+        insert(&*tx, "committed").await;
+        tx.commit().await.unwrap();
+        assert_eq!(names(db).await, vec!["committed"]);
+
+        let tx = db.begin_transaction().await.unwrap();
+        insert(&*tx, "dropped").await;
+        drop(tx);
+        assert_eq!(names(db).await, vec!["committed"]);
+
+        let tx = db.begin_transaction().await.unwrap();
+        insert(&*tx, "before savepoint").await;
+        tx.savepoint("test_savepoint").await.unwrap();
+        insert(&*tx, "after savepoint").await;
+        tx.rollback_to_savepoint("test_savepoint").await.unwrap();
+        tx.release_savepoint("test_savepoint").await.unwrap();
+        assert!(matches!(
+            tx.begin_transaction().await,
+            Err(DatabaseError::AlreadyInTransaction)
+        ));
+        tx.commit().await.unwrap();
+        assert_eq!(names(db).await, vec!["committed", "before savepoint"]);
+    }
+
+    fn integer(row: &Row, column: &str) -> i64 {
+        match row.get(column) {
+            Some(DatabaseValue::Number(x) | DatabaseValue::NumberOpt(Some(x))) => x,
+            Some(DatabaseValue::UNumber(x) | DatabaseValue::UNumberOpt(Some(x))) => {
+                i64::try_from(x).unwrap()
+            }
+            value => panic!("Expected integer for {column}, got {value:?}"),
+        }
+    }
+
+    fn string(row: &Row, column: &str) -> String {
+        row.get(column).unwrap().as_str().unwrap().to_string()
+    }
+
+    /// Runs the aggregate, grouping, paging and pattern matching scenarios
+    /// against an empty `query_test` table with an auto incrementing `id`,
+    /// an `artist` text column and a `duration` integer column.
+    #[allow(clippy::too_many_lines)]
+    pub async fn test_query_features(db: &dyn Database) {
+        for (artist, duration) in [
+            ("Bonobo", 300),
+            ("Bonobo", 200),
+            ("bonobo remixed", 100),
+            ("Tycho", 250),
+            ("Tycho", 150),
+            ("Tycho", 350),
+            ("Sigur Rós", 50),
+        ] {
+            db.insert("query_test")
+                .value("artist", artist)
+                .value("duration", duration)
+                .execute(db)
+                .await
+                .unwrap();
+        }
+
+        let totals = db
+            .select("query_test")
+            .aggregate("tracks", count_all())
+            .aggregate("artists", count_distinct("artist"))
+            .aggregate("longest", max("duration"))
+            .execute_first(db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(integer(&totals, "tracks"), 7);
+        assert_eq!(integer(&totals, "artists"), 4);
+        assert_eq!(integer(&totals, "longest"), 350);
+
+        let grouped = db
+            .select("query_test")
+            .columns(&["artist"])
+            .aggregate("tracks", count_all())
+            .aggregate("total", sum("duration"))
+            .group_by("artist")
+            .having(Box::new(crate::query::where_gt(count_all(), 1)))
+            .sort("artist", SortDirection::Asc)
+            .execute(db)
+            .await
+            .unwrap();
+        assert_eq!(
+            grouped
+                .iter()
+                .map(|row| (string(row, "artist"), integer(row, "tracks")))
+                .collect::<Vec<_>>(),
+            vec![("Bonobo".to_string(), 2), ("Tycho".to_string(), 3)]
+        );
+        assert_eq!(
+            grouped
+                .iter()
+                .map(|row| row.get("total"))
+                .collect::<Vec<_>>(),
+            vec![
+                Some(DatabaseValue::Real(500.0)),
+                Some(DatabaseValue::Real(750.0))
+            ]
+        );
+
+        let page = db
+            .select("query_test")
+            .sort("id", SortDirection::Asc)
+            .limit(2)
+            .offset(2)
+            .execute(db)
+            .await
+            .unwrap();
+        assert_eq!(
+            page.iter()
+                .map(|row| integer(row, "duration"))
+                .collect::<Vec<_>>(),
+            vec![100, 250]
+        );
+
+        let rest = db
+            .select("query_test")
+            .sort("id", SortDirection::Asc)
+            .offset(4)
+            .execute(db)
+            .await
+            .unwrap();
+        assert_eq!(
+            rest.iter()
+                .map(|row| integer(row, "duration"))
+                .collect::<Vec<_>>(),
+            vec![150, 350, 50]
+        );
+
+        let pattern = db
+            .select("query_test")
+            .aggregate("tracks", count_all())
+            .where_like("artist", "%remix%")
+            .execute_first(db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(integer(&pattern, "tracks"), 1);
+
+        let insensitive = db
+            .select("query_test")
+            .aggregate("tracks", count_all())
+            .where_ilike("artist", "BONOBO%")
+            .execute_first(db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(integer(&insensitive, "tracks"), 3);
+
+        let non_ascii = db
+            .select("query_test")
+            .aggregate("tracks", count_all())
+            .where_ilike("artist", "SIGUR Rós")
+            .execute_first(db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(integer(&non_ascii, "tracks"), 1);
+    }
+
+    #[test]
+    fn validate_savepoint_name_only_allows_identifiers() {
+        assert!(validate_savepoint_name("scan_1").is_ok());
+        assert!(validate_savepoint_name("_scan").is_ok());
+        assert!(validate_savepoint_name("").is_err());
+        assert!(validate_savepoint_name("1scan").is_err());
+        assert!(validate_savepoint_name("scan; DROP TABLE tracks").is_err());
+    }
+}
@@ -1,6 +1,6 @@
 use std::{
     ops::Deref,
-    sync::{atomic::AtomicU16, LazyLock},
+    sync::{atomic::AtomicU16, Arc, LazyLock},
 };
 
 use async_trait::async_trait;
@@ -11,14 +11,19 @@ use thiserror::Error;
 use tokio::{
     io::{AsyncRead, AsyncWrite},
     pin,
+    sync::{OwnedRwLockWriteGuard, RwLock},
     task::JoinHandle,
 };
 use tokio_postgres::{types::IsNull, Client, Row, RowStream};
 
 use crate::{
-    query::{BooleanExpression, Expression, ExpressionType, Join, Sort, SortDirection},
-    Database, DatabaseError, DatabaseValue, DeleteStatement, InsertStatement, SelectQuery,
-    UpdateStatement, UpsertMultiStatement, UpsertStatement,
+    query::{
+        Aggregate, AggregateFunction, BooleanExpression, Expression, ExpressionType, Join, Sort,
+        SortDirection,
+    },
+    validate_savepoint_name, Database, DatabaseError, DatabaseTransaction, DatabaseValue,
+    DeleteStatement, InsertStatement, SelectQuery, UpdateStatement, UpsertMultiStatement,
+    UpsertStatement,
 };
 
 trait ToSql {
@@ -165,67 +170,38 @@ impl<T: Expression + ?Sized> ToSql for T {
                     .collect::<Vec<_>>()
                     .join(",")
             ),
-            ExpressionType::Literal(value) => value.value.to_string(),
-            ExpressionType::Identifier(value) => format_identifier(&value.value),
-            ExpressionType::SelectQuery(value) => {
-                let joins = value.joins.as_ref().map_or_else(String::new, |joins| {
-                    joins
-                        .iter()
-                        .map(|x| x.to_sql(index))
-                        .collect::<Vec<_>>()
-                        .join(" ")
-                });
-
-                let where_clause = value.filters.as_ref().map_or_else(String::new, |filters| {
-                    if filters.is_empty() {
-                        String::new()
-                    } else {
-                        format!(
-                            "WHERE {}",
-                            filters
-                                .iter()
-                                .map(|x| format!("({})", x.to_sql(index)))
-                                .collect::<Vec<_>>()
-                                .join(" AND ")
-                        )
+            ExpressionType::Like(value) => format!(
+                "({} {} {})",
+                value.left.to_sql(index),
+                if value.case_insensitive {
+                    "ILIKE"
+                } else {
+                    "LIKE"
+                },
+                value.pattern.to_sql(index)
+            ),
+            ExpressionType::Aggregate(value) => {
+                let column = value
+                    .column
+                    .as_ref()
+                    .map_or_else(|| "*".to_string(), |x| x.to_sql(index));
+                let distinct = if value.distinct { "DISTINCT " } else { "" };
+
+                match value.function {
+                    AggregateFunction::Count => format!("COUNT({distinct}{column})"),
+                    AggregateFunction::Sum => {
+                        format!("CAST(SUM({distinct}{column}) AS DOUBLE PRECISION)")
                     }
-                });
-
-                let sort_clause = value.sorts.as_ref().map_or_else(String::new, |sorts| {
-                    if sorts.is_empty() {
-                        String::new()
-                    } else {
-                        format!(
-                            "ORDER BY {}",
-                            sorts
-                                .iter()
-                                .map(|x| x.to_sql(index))
-                                .collect::<Vec<_>>()
-                                .join(", ")
-                        )
+                    AggregateFunction::Avg => {
+                        format!("CAST(AVG({distinct}{column}) AS DOUBLE PRECISION)")
                     }
-                });
-
-                let limit = value
-                    .limit
-                    .map_or_else(String::new, |limit| format!("LIMIT {limit}"));
-
-                format!(
-                    "SELECT {} {} FROM {} {} {} {} {}",
-                    if value.distinct { "DISTINCT" } else { "" },
-                    value
-                        .columns
-                        .iter()
-                        .map(|x| format_identifier(x))
-                        .collect::<Vec<_>>()
-                        .join(", "),
-                    value.table_name,
-                    joins,
-                    where_clause,
-                    sort_clause,
-                    limit
-                )
+                    AggregateFunction::Min => format!("MIN({distinct}{column})"),
+                    AggregateFunction::Max => format!("MAX({distinct}{column})"),
+                }
             }
+            ExpressionType::Literal(value) => value.value.to_string(),
+            ExpressionType::Identifier(value) => format_identifier(&value.value),
+            ExpressionType::SelectQuery(value) => build_select_query(value, value.limit, index),
             ExpressionType::DatabaseValue(value) => match value {
                 DatabaseValue::Null
                 | DatabaseValue::BoolOpt(None)
@@ -246,7 +222,7 @@ impl<T: Expression + ?Sized> ToSql for T {
 
 #[allow(clippy::module_name_repetitions)]
 pub struct PostgresDatabase {
-    client: Client,
+    client: Arc<RwLock<Client>>,
     handle: JoinHandle<std::result::Result<(), tokio_postgres::Error>>,
 }
 
@@ -258,7 +234,36 @@ impl PostgresDatabase {
     ) -> Self {
         let handle = moosicbox_task::spawn("Postgres database connection", connection);
 
-        Self { client, handle }
+        Self {
+            client: Arc::new(RwLock::new(client)),
+            handle,
+        }
+    }
+}
+
+/// A transaction holding the database's client until it is committed or
+/// rolled back. Statements executed on the database in the meantime wait for
+/// the transaction to finish.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug)]
+pub struct PostgresTransaction {
+    /// Taken once the transaction is finished
+    client: Option<OwnedRwLockWriteGuard<Client>>,
+}
+
+impl PostgresTransaction {
+    fn client(&self) -> &Client {
+        self.client
+            .as_deref()
+            .expect("Transaction has already been finished")
+    }
+
+    async fn execute(&self, statement: &str) -> Result<(), DatabaseError> {
+        self.client()
+            .batch_execute(statement)
+            .await
+            .map_err(PostgresDatabaseError::Postgres)?;
+        Ok(())
     }
 }
 
@@ -304,33 +309,17 @@ impl From<PostgresDatabaseError> for DatabaseError {
 #[async_trait]
 impl Database for PostgresDatabase {
     async fn query(&self, query: &SelectQuery<'_>) -> Result<Vec<crate::Row>, DatabaseError> {
-        Ok(select(
-            &self.client,
-            query.table_name,
-            query.distinct,
-            query.columns,
-            query.filters.as_deref(),
-            query.joins.as_deref(),
-            query.sorts.as_deref(),
-            query.limit,
-        )
-        .await?)
+        Ok(select(&*self.client.read().await, query, query.limit).await?)
     }
 
     async fn query_first(
         &self,
         query: &SelectQuery<'_>,
     ) -> Result<Option<crate::Row>, DatabaseError> {
-        Ok(find_row(
-            &self.client,
-            query.table_name,
-            query.distinct,
-            query.columns,
-            query.filters.as_deref(),
-            query.joins.as_deref(),
-            query.sorts.as_deref(),
-        )
-        .await?)
+        Ok(select(&*self.client.read().await, query, Some(1))
+            .await?
+            .into_iter()
+            .next())
     }
 
     async fn exec_delete(
@@ -338,7 +327,7 @@ impl Database for PostgresDatabase {
         statement: &DeleteStatement<'_>,
     ) -> Result<Vec<crate::Row>, DatabaseError> {
         Ok(delete(
-            &self.client,
+            &*self.client.read().await,
             statement.table_name,
             statement.filters.as_deref(),
             statement.limit,
@@ -351,7 +340,7 @@ impl Database for PostgresDatabase {
         statement: &DeleteStatement<'_>,
     ) -> Result<Option<crate::Row>, DatabaseError> {
         Ok(delete(
-            &self.client,
+            &*self.client.read().await,
             statement.table_name,
             statement.filters.as_deref(),
             Some(1),
@@ -365,7 +354,12 @@ impl Database for PostgresDatabase {
         &self,
         statement: &InsertStatement<'_>,
     ) -> Result<crate::Row, DatabaseError> {
-        Ok(insert_and_get_row(&self.client, statement.table_name, &statement.values).await?)
+        Ok(insert_and_get_row(
+            &*self.client.read().await,
+            statement.table_name,
+            &statement.values,
+        )
+        .await?)
     }
 
     async fn exec_update(
@@ -373,7 +367,7 @@ impl Database for PostgresDatabase {
         statement: &UpdateStatement<'_>,
     ) -> Result<Vec<crate::Row>, DatabaseError> {
         Ok(update_and_get_rows(
-            &self.client,
+            &*self.client.read().await,
             statement.table_name,
             &statement.values,
             statement.filters.as_deref(),
@@ -387,7 +381,7 @@ impl Database for PostgresDatabase {
         statement: &UpdateStatement<'_>,
     ) -> Result<Option<crate::Row>, DatabaseError> {
         Ok(update_and_get_row(
-            &self.client,
+            &*self.client.read().await,
             statement.table_name,
             &statement.values,
             statement.filters.as_deref(),
@@ -401,7 +395,7 @@ impl Database for PostgresDatabase {
         statement: &UpsertStatement<'_>,
     ) -> Result<Vec<crate::Row>, DatabaseError> {
         Ok(upsert(
-            &self.client,
+            &*self.client.read().await,
             statement.table_name,
             &statement.values,
             statement.filters.as_deref(),
@@ -415,7 +409,7 @@ impl Database for PostgresDatabase {
         statement: &UpsertStatement<'_>,
     ) -> Result<crate::Row, DatabaseError> {
         Ok(upsert_and_get_row(
-            &self.client,
+            &*self.client.read().await,
             statement.table_name,
             &statement.values,
             statement.filters.as_deref(),
@@ -430,7 +424,7 @@ impl Database for PostgresDatabase {
     ) -> Result<Vec<crate::Row>, DatabaseError> {
         let rows = {
             upsert_multi(
-                &self.client,
+                &*self.client.read().await,
                 statement.table_name,
                 statement
                     .unique
@@ -447,6 +441,214 @@ impl Database for PostgresDatabase {
         self.handle.abort();
         Ok(())
     }
+    async fn exec_raw(&self, statement: &str) -> Result<(), DatabaseError> {
+        self.client
+            .read()
+            .await
+            .batch_execute(statement)
+            .await
+            .map_err(PostgresDatabaseError::Postgres)?;
+        Ok(())
+    }
+
+    #[allow(clippy::significant_drop_tightening)]
+    async fn begin_transaction(&self) -> Result<Box<dyn DatabaseTransaction>, DatabaseError> {
+        let client = self.client.clone().write_owned().await;
+        client
+            .batch_execute("BEGIN")
+            .await
+            .map_err(PostgresDatabaseError::Postgres)?;
+
+        Ok(Box::new(PostgresTransaction {
+            client: Some(client),
+        }))
+    }
+}
+
+#[async_trait]
+impl Database for PostgresTransaction {
+    async fn query(&self, query: &SelectQuery<'_>) -> Result<Vec<crate::Row>, DatabaseError> {
+        Ok(select(self.client(), query, query.limit).await?)
+    }
+
+    async fn query_first(
+        &self,
+        query: &SelectQuery<'_>,
+    ) -> Result<Option<crate::Row>, DatabaseError> {
+        Ok(select(self.client(), query, Some(1))
+            .await?
+            .into_iter()
+            .next())
+    }
+
+    async fn exec_delete(
+        &self,
+        statement: &DeleteStatement<'_>,
+    ) -> Result<Vec<crate::Row>, DatabaseError> {
+        Ok(delete(
+            self.client(),
+            statement.table_name,
+            statement.filters.as_deref(),
+            statement.limit,
+        )
+        .await?)
+    }
+
+    async fn exec_delete_first(
+        &self,
+        statement: &DeleteStatement<'_>,
+    ) -> Result<Option<crate::Row>, DatabaseError> {
+        Ok(delete(
+            self.client(),
+            statement.table_name,
+            statement.filters.as_deref(),
+            Some(1),
+        )
+        .await?
+        .into_iter()
+        .next())
+    }
+
+    async fn exec_insert(
+        &self,
+        statement: &InsertStatement<'_>,
+    ) -> Result<crate::Row, DatabaseError> {
+        Ok(insert_and_get_row(self.client(), statement.table_name, &statement.values).await?)
+    }
+
+    async fn exec_update(
+        &self,
+        statement: &UpdateStatement<'_>,
+    ) -> Result<Vec<crate::Row>, DatabaseError> {
+        Ok(update_and_get_rows(
+            self.client(),
+            statement.table_name,
+            &statement.values,
+            statement.filters.as_deref(),
+            statement.limit,
+        )
+        .await?)
+    }
+
+    async fn exec_update_first(
+        &self,
+        statement: &UpdateStatement<'_>,
+    ) -> Result<Option<crate::Row>, DatabaseError> {
+        Ok(update_and_get_row(
+            self.client(),
+            statement.table_name,
+            &statement.values,
+            statement.filters.as_deref(),
+            statement.limit,
+        )
+        .await?)
+    }
+
+    async fn exec_upsert(
+        &self,
+        statement: &UpsertStatement<'_>,
+    ) -> Result<Vec<crate::Row>, DatabaseError> {
+        Ok(upsert(
+            self.client(),
+            statement.table_name,
+            &statement.values,
+            statement.filters.as_deref(),
+            statement.limit,
+        )
+        .await?)
+    }
+
+    async fn exec_upsert_first(
+        &self,
+        statement: &UpsertStatement<'_>,
+    ) -> Result<crate::Row, DatabaseError> {
+        Ok(upsert_and_get_row(
+            self.client(),
+            statement.table_name,
+            &statement.values,
+            statement.filters.as_deref(),
+            statement.limit,
+        )
+        .await?)
+    }
+
+    async fn exec_upsert_multi(
+        &self,
+        statement: &UpsertMultiStatement<'_>,
+    ) -> Result<Vec<crate::Row>, DatabaseError> {
+        let rows = {
+            upsert_multi(
+                self.client(),
+                statement.table_name,
+                statement
+                    .unique
+                    .as_ref()
+                    .ok_or(PostgresDatabaseError::MissingUnique)?,
+                &statement.values,
+            )
+            .await?
+        };
+        Ok(rows)
+    }
+
+    async fn exec_raw(&self, statement: &str) -> Result<(), DatabaseError> {
+        self.execute(statement).await
+    }
+
+    async fn begin_transaction(&self) -> Result<Box<dyn DatabaseTransaction>, DatabaseError> {
+        Err(DatabaseError::AlreadyInTransaction)
+    }
+}
+
+#[async_trait]
+impl DatabaseTransaction for PostgresTransaction {
+    async fn commit(mut self: Box<Self>) -> Result<(), DatabaseError> {
+        if let Some(client) = self.client.take() {
+            client
+                .batch_execute("COMMIT")
+                .await
+                .map_err(PostgresDatabaseError::Postgres)?;
+        }
+        Ok(())
+    }
+
+    async fn rollback(mut self: Box<Self>) -> Result<(), DatabaseError> {
+        if let Some(client) = self.client.take() {
+            client
+                .batch_execute("ROLLBACK")
+                .await
+                .map_err(PostgresDatabaseError::Postgres)?;
+        }
+        Ok(())
+    }
+
+    async fn savepoint(&self, name: &str) -> Result<(), DatabaseError> {
+        validate_savepoint_name(name)?;
+        self.execute(&format!("SAVEPOINT {name}")).await
+    }
+
+    async fn release_savepoint(&self, name: &str) -> Result<(), DatabaseError> {
+        validate_savepoint_name(name)?;
+        self.execute(&format!("RELEASE SAVEPOINT {name}")).await
+    }
+
+    async fn rollback_to_savepoint(&self, name: &str) -> Result<(), DatabaseError> {
+        validate_savepoint_name(name)?;
+        self.execute(&format!("ROLLBACK TO SAVEPOINT {name}")).await
+    }
+}
+
+impl Drop for PostgresTransaction {
+    fn drop(&mut self) {
+        if let Some(client) = self.client.take() {
+            log::debug!("Rolling back unfinished transaction");
+            moosicbox_task::spawn("Postgres transaction rollback", async move {
+                if let Err(e) = client.batch_execute("ROLLBACK").await {
+                    log::error!("Failed to roll back transaction: {e:?}");
+                }
+            });
+        }
+    }
 }
 
 fn column_value(row: &Row, index: &str) -> Result<DatabaseValue, PostgresDatabaseError> {
@@ -681,6 +883,81 @@ fn build_sort_props(sorts: &[Sort], index: &AtomicU16) -> Vec<String> {
     sorts.iter().map(|sort| sort.to_sql(index)).collect()
 }
 
+fn build_columns(
+    columns: &[&str],
+    aggregates: Option<&[(&str, Aggregate)]>,
+    index: &AtomicU16,
+) -> String {
+    columns
+        .iter()
+        .map(|x| format_identifier(x))
+        .chain(
+            aggregates
+                .unwrap_or_default()
+                .iter()
+                .map(|(alias, aggregate)| {
+                    format!(
+                        "{} AS {}",
+                        aggregate.to_sql(index),
+                        format_identifier(alias)
+                    )
+                }),
+        )
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn build_group_by_clause(group_by: Option<&[Box<dyn Expression>]>, index: &AtomicU16) -> String {
+    group_by.map_or_else(String::new, |group_by| {
+        if group_by.is_empty() {
+            String::new()
+        } else {
+            format!(
+                "GROUP BY {}",
+                group_by
+                    .iter()
+                    .map(|x| x.to_sql(index))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        }
+    })
+}
+
+fn build_having_clause(having: Option<&[Box<dyn BooleanExpression>]>, index: &AtomicU16) -> String {
+    having.map_or_else(String::new, |having| {
+        if having.is_empty() {
+            String::new()
+        } else {
+            format!("HAVING {}", build_where_props(having, index).join(" AND "))
+        }
+    })
+}
+
+fn build_limit_clause(limit: Option<usize>, offset: Option<usize>) -> String {
+    match (limit, offset) {
+        (Some(limit), Some(offset)) => format!("LIMIT {limit} OFFSET {offset}"),
+        (Some(limit), None) => format!("LIMIT {limit}"),
+        (None, Some(offset)) => format!("OFFSET {offset}"),
+        (None, None) => String::new(),
+    }
+}
+
+fn build_select_query(query: &SelectQuery<'_>, limit: Option<usize>, index: &AtomicU16) -> String {
+    format!(
+        "SELECT {} {} FROM {} {} {} {} {} {} {}",
+        if query.distinct { "DISTINCT" } else { "" },
+        build_columns(query.columns, query.aggregates.as_deref(), index),
+        query.table_name,
+        build_join_clauses(query.joins.as_deref()),
+        build_where_clause(query.filters.as_deref(), index),
+        build_group_by_clause(query.group_by.as_deref(), index),
+        build_having_clause(query.having.as_deref(), index),
+        build_sort_clause(query.sorts.as_deref(), index),
+        build_limit_clause(limit, query.offset),
+    )
+}
+
 fn build_update_where_clause(
     filters: Option<&[Box<dyn BooleanExpression>]>,
     limit: Option<usize>,
@@ -820,37 +1097,30 @@ fn bexprs_to_params_opt(values: Option<&[Box<dyn BooleanExpression>]>) -> Vec<Pg
     values.map(bexprs_to_params).unwrap_or_default()
 }
 
-#[allow(clippy::too_many_arguments)]
 async fn select(
     client: &Client,
-    table_name: &str,
-    distinct: bool,
-    columns: &[&str],
-    filters: Option<&[Box<dyn BooleanExpression>]>,
-    joins: Option<&[Join<'_>]>,
-    sort: Option<&[Sort]>,
+    query: &SelectQuery<'_>,
     limit: Option<usize>,
 ) -> Result<Vec<crate::Row>, PostgresDatabaseError> {
     let index = AtomicU16::new(0);
-    let query = format!(
-        "SELECT {} {} FROM {table_name} {} {} {} {}",
-        if distinct { "DISTINCT" } else { "" },
-        columns
-            .iter()
-            .map(|x| format_identifier(x))
-            .collect::<Vec<_>>()
-            .join(", "),
-        build_join_clauses(joins),
-        build_where_clause(filters, &index),
-        build_sort_clause(sort, &index),
-        limit.map_or_else(String::new, |limit| format!("LIMIT {limit}"))
-    );
-
-    log::trace!(
-        "Running select query: {query} with params: {:?}",
-        filters.map(|f| f.iter().filter_map(|x| x.params()).collect::<Vec<_>>())
-    );
-
+    let statement_query = build_select_query(query, limit, &index);
+    let values = [
+        query
+            .filters
+            .as_deref()
+            .map(bexprs_to_params)
+            .unwrap_or_default(),
+        query
+            .having
+            .as_deref()
+            .map(bexprs_to_params)
+            .unwrap_or_default(),
+    ]
+    .concat();
+
+    log::trace!("Running select query: {statement_query} with params: {values:?}");
+
+    let query = statement_query;
     let statement = client.prepare(&query).await?;
     let column_names = statement
         .columns()
@@ -858,8 +1128,7 @@ async fn select(
         .map(|x| x.name().to_string())
         .collect::<Vec<_>>();
 
-    let filters = bexprs_to_params_opt(filters);
-    let rows = client.query_raw(&statement, filters).await?;
+    let rows = client.query_raw(&statement, &values).await?;
 
     to_rows(&column_names, rows).await
 }
@@ -1479,3 +1748,50 @@ impl tokio_postgres::types::ToSql for PgDatabaseValue {
         self.to_sql_checked(ty, out)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_log::test(tokio::test)]
+    #[ignore = "requires a postgres server at TEST_POSTGRES_URL"]
+    async fn transactions_roll_back_and_commit() {
+        let url = std::env::var("TEST_POSTGRES_URL").expect("Missing TEST_POSTGRES_URL");
+        let (client, connection) = tokio_postgres::connect(&url, tokio_postgres::NoTls)
+            .await
+            .unwrap();
+        let db = PostgresDatabase::new(client, connection);
+        db.client
+            .read()
+            .await
+            .batch_execute(
+                "DROP TABLE IF EXISTS transaction_test; \
+                 CREATE TABLE transaction_test (id BIGSERIAL PRIMARY KEY, name TEXT)",
+            )
+            .await
+            .unwrap();
+
+        crate::test::test_transactions(&db).await;
+    }
+
+    #[test_log::test(tokio::test)]
+    #[ignore = "requires a postgres server at TEST_POSTGRES_URL"]
+    async fn query_features() {
+        let url = std::env::var("TEST_POSTGRES_URL").expect("Missing TEST_POSTGRES_URL");
+        let (client, connection) = tokio_postgres::connect(&url, tokio_postgres::NoTls)
+            .await
+            .unwrap();
+        let db = PostgresDatabase::new(client, connection);
+        db.client
+            .read()
+            .await
+            .batch_execute(
+                "DROP TABLE IF EXISTS query_test; \
+                 CREATE TABLE query_test (id BIGSERIAL PRIMARY KEY, artist TEXT, duration BIGINT)",
+            )
+            .await
+            .unwrap();
+
+        crate::test::test_query_features(&db).await;
+    }
+}
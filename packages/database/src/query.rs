@@ -43,12 +43,14 @@ pub enum ExpressionType<'a> {
     Gte(&'a Gte),
     Lte(&'a Lte),
     Join(&'a Join<'a>),
+    Like(&'a Like),
     Sort(&'a Sort),
     NotIn(&'a NotIn<'a>),
     NotEq(&'a NotEq),
     InList(&'a InList),
     Literal(&'a Literal),
     Coalesce(&'a Coalesce),
+    Aggregate(&'a Aggregate),
     Identifier(&'a Identifier),
     SelectQuery(&'a SelectQuery<'a>),
     DatabaseValue(&'a DatabaseValue),
@@ -161,6 +163,101 @@ pub fn identifier(value: &str) -> Identifier {
     }
 }
 
+/// The left-hand side of a comparison or a sort: a column name or an
+/// aggregate of a column.
+pub trait IntoColumn {
+    fn into_column(self) -> Box<dyn Expression>;
+}
+
+impl<T: Into<Identifier>> IntoColumn for T {
+    fn into_column(self) -> Box<dyn Expression> {
+        Box::new(self.into())
+    }
+}
+
+impl IntoColumn for Aggregate {
+    fn into_column(self) -> Box<dyn Expression> {
+        Box::new(self)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AggregateFunction {
+    Count,
+    Sum,
+    Min,
+    Max,
+    Avg,
+}
+
+/// An aggregate function over a column, e.g. `COUNT(*)` or `SUM(duration)`.
+///
+/// Sums and averages are always returned as reals so that the result type is
+/// the same on every backend.
+#[derive(Debug)]
+pub struct Aggregate {
+    pub function: AggregateFunction,
+    /// The aggregated column, or `None` for `COUNT(*)`
+    pub column: Option<Identifier>,
+    pub distinct: bool,
+}
+
+impl Expression for Aggregate {
+    fn expression_type(&self) -> ExpressionType {
+        ExpressionType::Aggregate(self)
+    }
+}
+
+impl From<Aggregate> for Box<dyn Expression> {
+    fn from(val: Aggregate) -> Self {
+        Box::new(val)
+    }
+}
+
+fn aggregate<T: Into<Identifier>>(function: AggregateFunction, column: T) -> Aggregate {
+    Aggregate {
+        function,
+        column: Some(column.into()),
+        distinct: false,
+    }
+}
+
+#[must_use]
+pub const fn count_all() -> Aggregate {
+    Aggregate {
+        function: AggregateFunction::Count,
+        column: None,
+        distinct: false,
+    }
+}
+
+pub fn count<T: Into<Identifier>>(column: T) -> Aggregate {
+    aggregate(AggregateFunction::Count, column)
+}
+
+pub fn count_distinct<T: Into<Identifier>>(column: T) -> Aggregate {
+    Aggregate {
+        distinct: true,
+        ..count(column)
+    }
+}
+
+pub fn sum<T: Into<Identifier>>(column: T) -> Aggregate {
+    aggregate(AggregateFunction::Sum, column)
+}
+
+pub fn min<T: Into<Identifier>>(column: T) -> Aggregate {
+    aggregate(AggregateFunction::Min, column)
+}
+
+pub fn max<T: Into<Identifier>>(column: T) -> Aggregate {
+    aggregate(AggregateFunction::Max, column)
+}
+
+pub fn avg<T: Into<Identifier>>(column: T) -> Aggregate {
+    aggregate(AggregateFunction::Avg, column)
+}
+
 impl Expression for DatabaseValue {
     fn expression_type(&self) -> ExpressionType {
         ExpressionType::DatabaseValue(self)
@@ -247,7 +344,7 @@ impl Expression for Or {
 
 #[derive(Debug)]
 pub struct NotEq {
-    pub left: Identifier,
+    pub left: Box<dyn Expression>,
     pub right: Box<dyn Expression>,
 }
 
@@ -264,7 +361,7 @@ impl Expression for NotEq {
 
 #[derive(Debug)]
 pub struct Eq {
-    pub left: Identifier,
+    pub left: Box<dyn Expression>,
     pub right: Box<dyn Expression>,
 }
 
@@ -281,7 +378,7 @@ impl Expression for Eq {
 
 #[derive(Debug)]
 pub struct Gt {
-    pub left: Identifier,
+    pub left: Box<dyn Expression>,
     pub right: Box<dyn Expression>,
 }
 
@@ -298,7 +395,7 @@ impl Expression for Gt {
 
 #[derive(Debug)]
 pub struct Gte {
-    pub left: Identifier,
+    pub left: Box<dyn Expression>,
     pub right: Box<dyn Expression>,
 }
 
@@ -315,7 +412,7 @@ impl Expression for Gte {
 
 #[derive(Debug)]
 pub struct Lt {
-    pub left: Identifier,
+    pub left: Box<dyn Expression>,
     pub right: Box<dyn Expression>,
 }
 
@@ -332,7 +429,7 @@ impl Expression for Lt {
 
 #[derive(Debug)]
 pub struct Lte {
-    pub left: Identifier,
+    pub left: Box<dyn Expression>,
     pub right: Box<dyn Expression>,
 }
 
@@ -347,9 +444,35 @@ impl Expression for Lte {
     }
 }
 
+/// A `LIKE` pattern match. `%` matches any sequence of characters and `_`
+/// any single character.
+///
+/// Case sensitive matches follow the backend's default collation (`LIKE` is
+/// case insensitive for ASCII characters on sqlite and mysql), while case
+/// insensitive matches ignore case on every backend. Sqlite only folds the
+/// case of ASCII characters, so on sqlite non-ASCII characters still have to
+/// match exactly, even in case insensitive matches.
+#[derive(Debug)]
+pub struct Like {
+    pub left: Box<dyn Expression>,
+    pub pattern: Box<dyn Expression>,
+    pub case_insensitive: bool,
+}
+
+impl BooleanExpression for Like {}
+impl Expression for Like {
+    fn expression_type(&self) -> ExpressionType {
+        ExpressionType::Like(self)
+    }
+
+    fn values(&self) -> Option<Vec<&DatabaseValue>> {
+        self.pattern.values()
+    }
+}
+
 #[derive(Debug)]
 pub struct In<'a> {
-    pub left: Identifier,
+    pub left: Box<dyn Expression>,
     pub values: Box<dyn List + 'a>,
 }
 
@@ -376,7 +499,7 @@ impl Expression for In<'_> {
 
 #[derive(Debug)]
 pub struct NotIn<'a> {
-    pub left: Identifier,
+    pub left: Box<dyn Expression>,
     pub values: Box<dyn List + 'a>,
 }
 
@@ -413,70 +536,94 @@ where
 
 pub fn where_eq<L, R>(left: L, right: R) -> Eq
 where
-    L: Into<Identifier>,
+    L: IntoColumn,
     R: Into<Box<dyn Expression>>,
 {
     Eq {
-        left: left.into(),
+        left: left.into_column(),
         right: right.into(),
     }
 }
 
 pub fn where_not_eq<L, R>(left: L, right: R) -> NotEq
 where
-    L: Into<Identifier>,
+    L: IntoColumn,
     R: Into<Box<dyn Expression>>,
 {
     NotEq {
-        left: left.into(),
+        left: left.into_column(),
         right: right.into(),
     }
 }
 
 pub fn where_gt<L, R>(left: L, right: R) -> Gt
 where
-    L: Into<Identifier>,
+    L: IntoColumn,
     R: Into<Box<dyn Expression>>,
 {
     Gt {
-        left: left.into(),
+        left: left.into_column(),
         right: right.into(),
     }
 }
 
 pub fn where_gte<L, R>(left: L, right: R) -> Gte
 where
-    L: Into<Identifier>,
+    L: IntoColumn,
     R: Into<Box<dyn Expression>>,
 {
     Gte {
-        left: left.into(),
+        left: left.into_column(),
         right: right.into(),
     }
 }
 
 pub fn where_lt<L, R>(left: L, right: R) -> Lt
 where
-    L: Into<Identifier>,
+    L: IntoColumn,
     R: Into<Box<dyn Expression>>,
 {
     Lt {
-        left: left.into(),
+        left: left.into_column(),
         right: right.into(),
     }
 }
 
 pub fn where_lte<L, R>(left: L, right: R) -> Lte
 where
-    L: Into<Identifier>,
+    L: IntoColumn,
     R: Into<Box<dyn Expression>>,
 {
     Lte {
-        left: left.into(),
+        left: left.into_column(),
         right: right.into(),
     }
 }
 
+pub fn where_like<L, R>(left: L, pattern: R) -> Like
+where
+    L: IntoColumn,
+    R: Into<Box<dyn Expression>>,
+{
+    Like {
+        left: left.into_column(),
+        pattern: pattern.into(),
+        case_insensitive: false,
+    }
+}
+
+pub fn where_ilike<L, R>(left: L, pattern: R) -> Like
+where
+    L: IntoColumn,
+    R: Into<Box<dyn Expression>>,
+{
+    Like {
+        left: left.into_column(),
+        pattern: pattern.into(),
+        case_insensitive: true,
+    }
+}
+
 #[must_use]
 pub fn where_and(conditions: Vec<Box<dyn BooleanExpression>>) -> And {
     And { conditions }
@@ -577,22 +724,22 @@ where
 
 pub fn where_in<'a, L, V>(left: L, values: V) -> In<'a>
 where
-    L: Into<Identifier>,
+    L: IntoColumn,
     V: Into<Box<dyn List + 'a>>,
 {
     In {
-        left: left.into(),
+        left: left.into_column(),
         values: values.into(),
     }
 }
 
 pub fn where_not_in<'a, L, V>(left: L, values: V) -> NotIn<'a>
 where
-    L: Into<Identifier>,
+    L: IntoColumn,
     V: Into<Box<dyn List + 'a>>,
 {
     NotIn {
-        left: left.into(),
+        left: left.into_column(),
         values: values.into(),
     }
 }
@@ -636,7 +783,7 @@ where
     #[must_use]
     fn where_in<L, V>(self, left: L, values: V) -> Self
     where
-        L: Into<Identifier>,
+        L: IntoColumn,
         V: Into<Box<dyn List>>,
     {
         self.filter(Box::new(where_in(left, values)))
@@ -645,7 +792,7 @@ where
     #[must_use]
     fn where_not_in<L, V>(self, left: L, values: V) -> Self
     where
-        L: Into<Identifier>,
+        L: IntoColumn,
         V: Into<Box<dyn List>>,
     {
         self.filter(Box::new(where_not_in(left, values)))
@@ -664,7 +811,7 @@ where
     #[must_use]
     fn where_eq<L, R>(self, left: L, right: R) -> Self
     where
-        L: Into<Identifier>,
+        L: IntoColumn,
         R: Into<Box<dyn Expression>>,
     {
         self.filter(Box::new(where_eq(left, right)))
@@ -673,7 +820,7 @@ where
     #[must_use]
     fn where_not_eq<L, R>(self, left: L, right: R) -> Self
     where
-        L: Into<Identifier>,
+        L: IntoColumn,
         R: Into<Box<dyn Expression>>,
     {
         self.filter(Box::new(where_not_eq(left, right)))
@@ -682,7 +829,7 @@ where
     #[must_use]
     fn where_gt<L, R>(self, left: L, right: R) -> Self
     where
-        L: Into<Identifier>,
+        L: IntoColumn,
         R: Into<Box<dyn Expression>>,
     {
         self.filter(Box::new(where_gt(left, right)))
@@ -691,7 +838,7 @@ where
     #[must_use]
     fn where_gte<L, R>(self, left: L, right: R) -> Self
     where
-        L: Into<Identifier>,
+        L: IntoColumn,
         R: Into<Box<dyn Expression>>,
     {
         self.filter(Box::new(where_gte(left, right)))
@@ -700,7 +847,7 @@ where
     #[must_use]
     fn where_lt<L, R>(self, left: L, right: R) -> Self
     where
-        L: Into<Identifier>,
+        L: IntoColumn,
         R: Into<Box<dyn Expression>>,
     {
         self.filter(Box::new(where_lt(left, right)))
@@ -709,11 +856,29 @@ where
     #[must_use]
     fn where_lte<L, R>(self, left: L, right: R) -> Self
     where
-        L: Into<Identifier>,
+        L: IntoColumn,
         R: Into<Box<dyn Expression>>,
     {
         self.filter(Box::new(where_lte(left, right)))
     }
+
+    #[must_use]
+    fn where_like<L, R>(self, left: L, pattern: R) -> Self
+    where
+        L: IntoColumn,
+        R: Into<Box<dyn Expression>>,
+    {
+        self.filter(Box::new(where_like(left, pattern)))
+    }
+
+    #[must_use]
+    fn where_ilike<L, R>(self, left: L, pattern: R) -> Self
+    where
+        L: IntoColumn,
+        R: Into<Box<dyn Expression>>,
+    {
+        self.filter(Box::new(where_ilike(left, pattern)))
+    }
 }
 
 impl<'a> From<SelectQuery<'a>> for Box<dyn List + 'a> {
@@ -728,10 +893,15 @@ pub struct SelectQuery<'a> {
     pub table_name: &'a str,
     pub distinct: bool,
     pub columns: &'a [&'a str],
+    /// The aggregates selected after the columns, with their aliases
+    pub aggregates: Option<Vec<(&'a str, Aggregate)>>,
     pub filters: Option<Vec<Box<dyn BooleanExpression>>>,
     pub joins: Option<Vec<Join<'a>>>,
+    pub group_by: Option<Vec<Box<dyn Expression>>>,
+    pub having: Option<Vec<Box<dyn BooleanExpression>>>,
     pub sorts: Option<Vec<Sort>>,
     pub limit: Option<usize>,
+    pub offset: Option<usize>,
 }
 
 impl List for SelectQuery<'_> {}
@@ -759,6 +929,15 @@ impl Expression for SelectQuery<'_> {
                     .collect::<Vec<_>>()
             })
             .unwrap_or_default();
+        let having_values = self
+            .having
+            .as_ref()
+            .map(|x| {
+                x.iter()
+                    .flat_map(|j| j.values().unwrap_or_default())
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
         let sorts_values = self
             .sorts
             .as_ref()
@@ -769,7 +948,7 @@ impl Expression for SelectQuery<'_> {
             })
             .unwrap_or_default();
 
-        let values: Vec<_> = [joins_values, filters_values, sorts_values].concat();
+        let values: Vec<_> = [joins_values, filters_values, having_values, sorts_values].concat();
 
         if values.is_empty() {
             None
@@ -785,10 +964,14 @@ pub fn select(table_name: &str) -> SelectQuery<'_> {
         table_name,
         distinct: false,
         columns: &["*"],
+        aggregates: None,
         filters: None,
         joins: None,
+        group_by: None,
+        having: None,
         sorts: None,
         limit: None,
+        offset: None,
     }
 }
 
@@ -875,12 +1058,13 @@ impl<'a> SelectQuery<'a> {
     #[must_use]
     pub fn sort<T>(mut self, expression: T, direction: SortDirection) -> Self
     where
-        T: Into<Identifier>,
+        T: IntoColumn,
     {
         if let Some(ref mut sorts) = self.sorts {
-            sorts.push(sort(expression.into(), direction));
+            sorts.push(sort(expression.into_column(), direction));
         } else {
-            self.sorts.replace(vec![sort(expression.into(), direction)]);
+            self.sorts
+                .replace(vec![sort(expression.into_column(), direction)]);
         }
         self
     }
@@ -891,6 +1075,51 @@ impl<'a> SelectQuery<'a> {
         self
     }
 
+    #[must_use]
+    pub fn offset(mut self, offset: usize) -> Self {
+        self.offset.replace(offset);
+        self
+    }
+
+    /// Selects the aggregate as `alias`. The default `*` column is dropped so
+    /// that only the aggregates and the explicitly selected (grouped) columns
+    /// are returned.
+    #[must_use]
+    pub fn aggregate(mut self, alias: &'a str, aggregate: Aggregate) -> Self {
+        if self.columns == ["*"] {
+            self.columns = &[];
+        }
+        if let Some(ref mut aggregates) = self.aggregates {
+            aggregates.push((alias, aggregate));
+        } else {
+            self.aggregates.replace(vec![(alias, aggregate)]);
+        }
+        self
+    }
+
+    #[must_use]
+    pub fn group_by<T>(mut self, column: T) -> Self
+    where
+        T: IntoColumn,
+    {
+        if let Some(ref mut group_by) = self.group_by {
+            group_by.push(column.into_column());
+        } else {
+            self.group_by.replace(vec![column.into_column()]);
+        }
+        self
+    }
+
+    #[must_use]
+    pub fn having(mut self, filter: Box<dyn BooleanExpression>) -> Self {
+        if let Some(ref mut having) = self.having {
+            having.push(filter);
+        } else {
+            self.having.replace(vec![filter]);
+        }
+        self
+    }
+
     /// # Errors
     ///
     /// Will return `Err` if the select query execution failed.
@@ -1191,10 +1420,14 @@ impl<'a> From<UpsertStatement<'a>> for SelectQuery<'a> {
             table_name: value.table_name,
             distinct: false,
             columns: &["*"],
+            aggregates: None,
             filters: value.filters,
             joins: None,
+            group_by: None,
+            having: None,
             sorts: None,
             limit: value.limit,
+            offset: None,
         }
     }
 }
@@ -1,28 +1,181 @@
-use std::{ops::Deref, sync::Arc};
+use std::{ops::Deref, path::Path, sync::Arc, time::Duration};
 
 use async_trait::async_trait;
-use rusqlite::{types::Value, Connection, Row, Rows, Statement};
+use rusqlite::{types::Value, Connection, ErrorCode, OpenFlags, Row, Rows, Statement};
 use thiserror::Error;
-use tokio::sync::Mutex;
+use tokio::sync::{Mutex, OwnedMutexGuard, Semaphore, SemaphorePermit};
 
 use crate::{
-    query::{BooleanExpression, Expression, ExpressionType, Join, Sort, SortDirection},
-    Database, DatabaseError, DatabaseValue, DeleteStatement, InsertStatement, SelectQuery,
-    UpdateStatement, UpsertMultiStatement, UpsertStatement,
+    query::{
+        Aggregate, AggregateFunction, BooleanExpression, Expression, ExpressionType, Join, Sort,
+        SortDirection,
+    },
+    validate_savepoint_name, Database, DatabaseError, DatabaseTransaction, DatabaseValue,
+    DeleteStatement, InsertStatement, SelectQuery, UpdateStatement, UpsertMultiStatement,
+    UpsertStatement,
 };
 
+/// A sqlite database.
+///
+/// Every statement is executed on the writer connection, except for queries
+/// when the database was opened with a pool of read connections, which then
+/// don't have to wait for writes to finish.
 #[allow(clippy::module_name_repetitions)]
 #[derive(Debug)]
 pub struct RusqliteDatabase {
     connection: Arc<Mutex<Connection>>,
+    readers: Option<ReadPool>,
+}
+
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, Clone, Copy)]
+pub struct RusqlitePoolConfig {
+    /// The number of read-only connections. With `0`, queries are executed on
+    /// the writer connection.
+    pub read_connections: usize,
+    /// How long a statement waits on a lock held by another connection before
+    /// failing with [`DatabaseError::Busy`].
+    pub busy_timeout: Duration,
+}
+
+impl Default for RusqlitePoolConfig {
+    fn default() -> Self {
+        Self {
+            read_connections: 4,
+            busy_timeout: Duration::from_secs(5),
+        }
+    }
 }
 
 impl RusqliteDatabase {
     pub const fn new(connection: Arc<Mutex<Connection>>) -> Self {
-        Self { connection }
+        Self {
+            connection,
+            readers: None,
+        }
+    }
+
+    /// Opens the database at `path` in WAL mode with a dedicated writer
+    /// connection and `config.read_connections` read-only connections.
+    ///
+    /// In WAL mode readers see the last committed state of the database while
+    /// a write is in progress, so queries no longer queue up behind long
+    /// writes such as a library scan.
+    ///
+    /// # Errors
+    ///
+    /// * If any of the connections failed to open
+    /// * If the database failed to be switched to WAL mode
+    pub fn open(path: &Path, config: &RusqlitePoolConfig) -> Result<Self, RusqliteDatabaseError> {
+        let writer = Connection::open(path)?;
+        writer.busy_timeout(config.busy_timeout)?;
+        let journal_mode: String =
+            writer.query_row("PRAGMA journal_mode=WAL", [], |row| row.get(0))?;
+        log::debug!("open: journal_mode={journal_mode}");
+
+        let readers = if config.read_connections == 0 {
+            None
+        } else {
+            let connections = (0..config.read_connections)
+                .map(|_| {
+                    let reader = Connection::open_with_flags(
+                        path,
+                        OpenFlags::SQLITE_OPEN_READ_ONLY
+                            | OpenFlags::SQLITE_OPEN_URI
+                            | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+                    )?;
+                    reader.busy_timeout(config.busy_timeout)?;
+                    Ok(reader)
+                })
+                .collect::<Result<Vec<_>, rusqlite::Error>>()?;
+
+            Some(ReadPool {
+                available: Semaphore::new(connections.len()),
+                connections: std::sync::Mutex::new(connections),
+            })
+        };
+
+        Ok(Self {
+            connection: Arc::new(Mutex::new(writer)),
+            readers,
+        })
+    }
+
+    async fn select(
+        &self,
+        query: &SelectQuery<'_>,
+        limit: Option<usize>,
+    ) -> Result<Vec<crate::Row>, RusqliteDatabaseError> {
+        if let Some(readers) = &self.readers {
+            select(&*readers.get().await, query, limit)
+        } else {
+            select(&*self.connection.lock().await, query, limit)
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ReadPool {
+    connections: std::sync::Mutex<Vec<Connection>>,
+    available: Semaphore,
+}
+
+impl ReadPool {
+    async fn get(&self) -> PooledConnection<'_> {
+        let permit = self
+            .available
+            .acquire()
+            .await
+            .expect("Read pool semaphore was closed");
+        let connection = self
+            .connections
+            .lock()
+            .unwrap()
+            .pop()
+            .expect("No read connection available for permit");
+
+        PooledConnection {
+            pool: self,
+            connection: Some(connection),
+            _permit: permit,
+        }
+    }
+}
+
+/// A read connection checked out of the [`ReadPool`]. It is returned to the
+/// pool, before the permit is released, when dropped.
+struct PooledConnection<'a> {
+    pool: &'a ReadPool,
+    connection: Option<Connection>,
+    _permit: SemaphorePermit<'a>,
+}
+
+impl Deref for PooledConnection<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Self::Target {
+        self.connection.as_ref().unwrap()
     }
 }
 
+impl Drop for PooledConnection<'_> {
+    fn drop(&mut self) {
+        if let Some(connection) = self.connection.take() {
+            self.pool.connections.lock().unwrap().push(connection);
+        }
+    }
+}
+
+/// A transaction holding the database's connection until it is committed or
+/// rolled back. Statements executed on the database in the meantime wait for
+/// the transaction to finish.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug)]
+pub struct RusqliteTransaction {
+    connection: Mutex<OwnedMutexGuard<Connection>>,
+    finished: bool,
+}
+
 trait ToSql {
     fn to_sql(&self) -> String;
 }
@@ -126,58 +279,29 @@ impl<T: Expression + ?Sized> ToSql for T {
                     .collect::<Vec<_>>()
                     .join(",")
             ),
+            // sqlite's `LIKE` already ignores the case of ASCII characters,
+            // and `LOWER` doesn't fold non-ASCII characters either
+            ExpressionType::Like(value) => {
+                format!("({} LIKE {})", value.left.to_sql(), value.pattern.to_sql())
+            }
+            ExpressionType::Aggregate(value) => {
+                let column = value
+                    .column
+                    .as_ref()
+                    .map_or_else(|| "*".to_string(), ToSql::to_sql);
+                let distinct = if value.distinct { "DISTINCT " } else { "" };
+
+                match value.function {
+                    AggregateFunction::Count => format!("COUNT({distinct}{column})"),
+                    AggregateFunction::Sum => format!("CAST(SUM({distinct}{column}) AS REAL)"),
+                    AggregateFunction::Avg => format!("CAST(AVG({distinct}{column}) AS REAL)"),
+                    AggregateFunction::Min => format!("MIN({distinct}{column})"),
+                    AggregateFunction::Max => format!("MAX({distinct}{column})"),
+                }
+            }
             ExpressionType::Literal(value) => value.value.to_string(),
             ExpressionType::Identifier(value) => value.value.clone(),
-            ExpressionType::SelectQuery(value) => {
-                let joins = value.joins.as_ref().map_or_else(String::new, |joins| {
-                    joins.iter().map(Join::to_sql).collect::<Vec<_>>().join(" ")
-                });
-
-                let where_clause = value.filters.as_ref().map_or_else(String::new, |filters| {
-                    if filters.is_empty() {
-                        String::new()
-                    } else {
-                        format!(
-                            "WHERE {}",
-                            filters
-                                .iter()
-                                .map(|x| format!("({})", x.to_sql()))
-                                .collect::<Vec<_>>()
-                                .join(" AND ")
-                        )
-                    }
-                });
-
-                let sort_clause = value.sorts.as_ref().map_or_else(String::new, |sorts| {
-                    if sorts.is_empty() {
-                        String::new()
-                    } else {
-                        format!(
-                            "ORDER BY {}",
-                            sorts
-                                .iter()
-                                .map(Sort::to_sql)
-                                .collect::<Vec<_>>()
-                                .join(", ")
-                        )
-                    }
-                });
-
-                let limit = value
-                    .limit
-                    .map_or_else(String::new, |limit| format!("LIMIT {limit}"));
-
-                format!(
-                    "SELECT {} {} FROM {} {} {} {} {}",
-                    if value.distinct { "DISTINCT" } else { "" },
-                    value.columns.join(", "),
-                    value.table_name,
-                    joins,
-                    where_clause,
-                    sort_clause,
-                    limit
-                )
-            }
+            ExpressionType::SelectQuery(value) => build_select_query(value, value.limit),
             ExpressionType::DatabaseValue(value) => match value {
                 DatabaseValue::Null
                 | DatabaseValue::BoolOpt(None)
@@ -212,40 +336,174 @@ pub enum RusqliteDatabaseError {
 
 impl From<RusqliteDatabaseError> for DatabaseError {
     fn from(value: RusqliteDatabaseError) -> Self {
-        Self::Rusqlite(value)
+        match value {
+            RusqliteDatabaseError::Rusqlite(rusqlite::Error::SqliteFailure(
+                rusqlite::ffi::Error {
+                    code: ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked,
+                    ..
+                },
+                _,
+            )) => Self::Busy,
+            value => Self::Rusqlite(value),
+        }
     }
 }
 
 #[async_trait]
 impl Database for RusqliteDatabase {
     async fn query(&self, query: &SelectQuery<'_>) -> Result<Vec<crate::Row>, DatabaseError> {
-        Ok(select(
-            &*self.connection.lock().await,
-            query.table_name,
-            query.distinct,
-            query.columns,
-            query.filters.as_deref(),
-            query.joins.as_deref(),
-            query.sorts.as_deref(),
-            query.limit,
-        )?)
+        Ok(self.select(query, query.limit).await?)
     }
 
     async fn query_first(
         &self,
         query: &SelectQuery<'_>,
     ) -> Result<Option<crate::Row>, DatabaseError> {
-        Ok(find_row(
+        Ok(self.select(query, Some(1)).await?.into_iter().next())
+    }
+
+    async fn exec_delete(
+        &self,
+        statement: &DeleteStatement<'_>,
+    ) -> Result<Vec<crate::Row>, DatabaseError> {
+        Ok(delete(
             &*self.connection.lock().await,
-            query.table_name,
-            query.distinct,
-            query.columns,
-            query.filters.as_deref(),
-            query.joins.as_deref(),
-            query.sorts.as_deref(),
+            statement.table_name,
+            statement.filters.as_deref(),
+            statement.limit,
+        )?)
+    }
+
+    async fn exec_delete_first(
+        &self,
+        statement: &DeleteStatement<'_>,
+    ) -> Result<Option<crate::Row>, DatabaseError> {
+        Ok(delete(
+            &*self.connection.lock().await,
+            statement.table_name,
+            statement.filters.as_deref(),
+            Some(1),
+        )?
+        .into_iter()
+        .next())
+    }
+
+    async fn exec_insert(
+        &self,
+        statement: &InsertStatement<'_>,
+    ) -> Result<crate::Row, DatabaseError> {
+        Ok(insert_and_get_row(
+            &*self.connection.lock().await,
+            statement.table_name,
+            &statement.values,
         )?)
     }
 
+    async fn exec_update(
+        &self,
+        statement: &UpdateStatement<'_>,
+    ) -> Result<Vec<crate::Row>, DatabaseError> {
+        Ok(update_and_get_rows(
+            &*self.connection.lock().await,
+            statement.table_name,
+            &statement.values,
+            statement.filters.as_deref(),
+            statement.limit,
+        )?)
+    }
+
+    async fn exec_update_first(
+        &self,
+        statement: &UpdateStatement<'_>,
+    ) -> Result<Option<crate::Row>, DatabaseError> {
+        Ok(update_and_get_row(
+            &*self.connection.lock().await,
+            statement.table_name,
+            &statement.values,
+            statement.filters.as_deref(),
+            statement.limit,
+        )?)
+    }
+
+    async fn exec_upsert(
+        &self,
+        statement: &UpsertStatement<'_>,
+    ) -> Result<Vec<crate::Row>, DatabaseError> {
+        Ok(upsert(
+            &*self.connection.lock().await,
+            statement.table_name,
+            &statement.values,
+            statement.filters.as_deref(),
+            statement.limit,
+        )?)
+    }
+
+    async fn exec_upsert_first(
+        &self,
+        statement: &UpsertStatement<'_>,
+    ) -> Result<crate::Row, DatabaseError> {
+        Ok(upsert_and_get_row(
+            &*self.connection.lock().await,
+            statement.table_name,
+            &statement.values,
+            statement.filters.as_deref(),
+            statement.limit,
+        )?)
+    }
+
+    async fn exec_upsert_multi(
+        &self,
+        statement: &UpsertMultiStatement<'_>,
+    ) -> Result<Vec<crate::Row>, DatabaseError> {
+        Ok(upsert_multi(
+            &*self.connection.lock().await,
+            statement.table_name,
+            statement
+                .unique
+                .as_ref()
+                .ok_or(RusqliteDatabaseError::MissingUnique)?,
+            &statement.values,
+        )?)
+    }
+
+    async fn exec_raw(&self, statement: &str) -> Result<(), DatabaseError> {
+        self.connection
+            .lock()
+            .await
+            .execute_batch(statement)
+            .map_err(RusqliteDatabaseError::Rusqlite)?;
+        Ok(())
+    }
+
+    #[allow(clippy::significant_drop_tightening)]
+    async fn begin_transaction(&self) -> Result<Box<dyn DatabaseTransaction>, DatabaseError> {
+        let connection = self.connection.clone().lock_owned().await;
+        connection
+            .execute_batch("BEGIN TRANSACTION")
+            .map_err(RusqliteDatabaseError::Rusqlite)?;
+
+        Ok(Box::new(RusqliteTransaction {
+            connection: Mutex::new(connection),
+            finished: false,
+        }))
+    }
+}
+
+#[async_trait]
+impl Database for RusqliteTransaction {
+    async fn query(&self, query: &SelectQuery<'_>) -> Result<Vec<crate::Row>, DatabaseError> {
+        Ok(select(&*self.connection.lock().await, query, query.limit)?)
+    }
+
+    async fn query_first(
+        &self,
+        query: &SelectQuery<'_>,
+    ) -> Result<Option<crate::Row>, DatabaseError> {
+        Ok(select(&*self.connection.lock().await, query, Some(1))?
+            .into_iter()
+            .next())
+    }
+
     async fn exec_delete(
         &self,
         statement: &DeleteStatement<'_>,
@@ -349,6 +607,81 @@ impl Database for RusqliteDatabase {
             &statement.values,
         )?)
     }
+
+    async fn exec_raw(&self, statement: &str) -> Result<(), DatabaseError> {
+        self.connection
+            .lock()
+            .await
+            .execute_batch(statement)
+            .map_err(RusqliteDatabaseError::Rusqlite)?;
+        Ok(())
+    }
+
+    async fn begin_transaction(&self) -> Result<Box<dyn DatabaseTransaction>, DatabaseError> {
+        Err(DatabaseError::AlreadyInTransaction)
+    }
+}
+
+#[async_trait]
+impl DatabaseTransaction for RusqliteTransaction {
+    async fn commit(mut self: Box<Self>) -> Result<(), DatabaseError> {
+        self.connection
+            .get_mut()
+            .execute_batch("COMMIT")
+            .map_err(RusqliteDatabaseError::Rusqlite)?;
+        self.finished = true;
+        Ok(())
+    }
+
+    async fn rollback(mut self: Box<Self>) -> Result<(), DatabaseError> {
+        self.connection
+            .get_mut()
+            .execute_batch("ROLLBACK")
+            .map_err(RusqliteDatabaseError::Rusqlite)?;
+        self.finished = true;
+        Ok(())
+    }
+
+    async fn savepoint(&self, name: &str) -> Result<(), DatabaseError> {
+        validate_savepoint_name(name)?;
+        self.connection
+            .lock()
+            .await
+            .execute_batch(&format!("SAVEPOINT {name}"))
+            .map_err(RusqliteDatabaseError::Rusqlite)?;
+        Ok(())
+    }
+
+    async fn release_savepoint(&self, name: &str) -> Result<(), DatabaseError> {
+        validate_savepoint_name(name)?;
+        self.connection
+            .lock()
+            .await
+            .execute_batch(&format!("RELEASE SAVEPOINT {name}"))
+            .map_err(RusqliteDatabaseError::Rusqlite)?;
+        Ok(())
+    }
+
+    async fn rollback_to_savepoint(&self, name: &str) -> Result<(), DatabaseError> {
+        validate_savepoint_name(name)?;
+        self.connection
+            .lock()
+            .await
+            .execute_batch(&format!("ROLLBACK TO SAVEPOINT {name}"))
+            .map_err(RusqliteDatabaseError::Rusqlite)?;
+        Ok(())
+    }
+}
+
+impl Drop for RusqliteTransaction {
+    fn drop(&mut self) {
+        if !self.finished {
+            log::debug!("Rolling back unfinished transaction");
+            if let Err(e) = self.connection.get_mut().execute_batch("ROLLBACK") {
+                log::error!("Failed to roll back transaction: {e:?}");
+            }
+        }
+    }
 }
 
 impl From<Value> for DatabaseValue {
@@ -545,6 +878,71 @@ fn build_sort_props(sorts: &[Sort]) -> Vec<String> {
     sorts.iter().map(Sort::to_sql).collect()
 }
 
+fn build_columns(columns: &[&str], aggregates: Option<&[(&str, Aggregate)]>) -> String {
+    columns
+        .iter()
+        .map(std::string::ToString::to_string)
+        .chain(
+            aggregates
+                .unwrap_or_default()
+                .iter()
+                .map(|(alias, aggregate)| format!("{} AS {}", aggregate.to_sql(), alias)),
+        )
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn build_group_by_clause(group_by: Option<&[Box<dyn Expression>]>) -> String {
+    group_by.map_or_else(String::new, |group_by| {
+        if group_by.is_empty() {
+            String::new()
+        } else {
+            format!(
+                "GROUP BY {}",
+                group_by
+                    .iter()
+                    .map(|x| x.to_sql())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        }
+    })
+}
+
+fn build_having_clause(having: Option<&[Box<dyn BooleanExpression>]>) -> String {
+    having.map_or_else(String::new, |having| {
+        if having.is_empty() {
+            String::new()
+        } else {
+            format!("HAVING {}", build_where_props(having).join(" AND "))
+        }
+    })
+}
+
+fn build_limit_clause(limit: Option<usize>, offset: Option<usize>) -> String {
+    match (limit, offset) {
+        (Some(limit), Some(offset)) => format!("LIMIT {limit} OFFSET {offset}"),
+        (Some(limit), None) => format!("LIMIT {limit}"),
+        (None, Some(offset)) => format!("LIMIT -1 OFFSET {offset}"),
+        (None, None) => String::new(),
+    }
+}
+
+fn build_select_query(query: &SelectQuery<'_>, limit: Option<usize>) -> String {
+    format!(
+        "SELECT {} {} FROM {} {} {} {} {} {} {}",
+        if query.distinct { "DISTINCT" } else { "" },
+        build_columns(query.columns, query.aggregates.as_deref()),
+        query.table_name,
+        build_join_clauses(query.joins.as_deref()),
+        build_where_clause(query.filters.as_deref()),
+        build_group_by_clause(query.group_by.as_deref()),
+        build_having_clause(query.having.as_deref()),
+        build_sort_clause(query.sorts.as_deref()),
+        build_limit_clause(limit, query.offset),
+    )
+}
+
 fn build_update_where_clause(
     filters: Option<&[Box<dyn BooleanExpression>]>,
     limit: Option<usize>,
@@ -763,32 +1161,29 @@ fn bexprs_to_values_opt(
     values.map(bexprs_to_values)
 }
 
-#[allow(clippy::too_many_arguments)]
 fn select(
     connection: &Connection,
-    table_name: &str,
-    distinct: bool,
-    columns: &[&str],
-    filters: Option<&[Box<dyn BooleanExpression>]>,
-    joins: Option<&[Join<'_>]>,
-    sort: Option<&[Sort]>,
+    query: &SelectQuery<'_>,
     limit: Option<usize>,
 ) -> Result<Vec<crate::Row>, RusqliteDatabaseError> {
-    let query = format!(
-        "SELECT {} {} FROM {table_name} {} {} {} {}",
-        if distinct { "DISTINCT" } else { "" },
-        columns.join(", "),
-        build_join_clauses(joins),
-        build_where_clause(filters),
-        build_sort_clause(sort),
-        limit.map_or_else(String::new, |limit| format!("LIMIT {limit}"))
-    );
-
-    log::trace!(
-        "Running select query: {query} with params: {:?}",
-        filters.map(|f| f.iter().filter_map(|x| x.params()).collect::<Vec<_>>())
-    );
-
+    let statement_query = build_select_query(query, limit);
+    let values = [
+        query
+            .filters
+            .as_deref()
+            .map(bexprs_to_values)
+            .unwrap_or_default(),
+        query
+            .having
+            .as_deref()
+            .map(bexprs_to_values)
+            .unwrap_or_default(),
+    ]
+    .concat();
+
+    log::trace!("Running select query: {statement_query} with params: {values:?}");
+
+    let query = statement_query;
     let mut statement = connection.prepare_cached(&query)?;
     let column_names = statement
         .column_names()
@@ -796,12 +1191,7 @@ fn select(
         .map(std::string::ToString::to_string)
         .collect::<Vec<_>>();
 
-    bind_values(
-        &mut statement,
-        bexprs_to_values_opt(filters).as_deref(),
-        false,
-        0,
-    )?;
+    bind_values(&mut statement, Some(&values), false, 0)?;
 
     to_rows(&column_names, statement.raw_query())
 }
@@ -1309,3 +1699,143 @@ impl Expression for RusqliteDatabaseValue {
         ExpressionType::DatabaseValue(self)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_log::test(tokio::test)]
+    async fn transactions_roll_back_and_commit() {
+        let connection = Connection::open_in_memory().unwrap();
+        connection
+            .execute_batch(
+                "CREATE TABLE transaction_test (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT)",
+            )
+            .unwrap();
+        let db = RusqliteDatabase::new(Arc::new(Mutex::new(connection)));
+
+        crate::test::test_transactions(&db).await;
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn query_features() {
+        let connection = Connection::open_in_memory().unwrap();
+        connection
+            .execute_batch(
+                "CREATE TABLE query_test (id INTEGER PRIMARY KEY AUTOINCREMENT, artist TEXT, duration INTEGER)",
+            )
+            .unwrap();
+        let db = RusqliteDatabase::new(Arc::new(Mutex::new(connection)));
+
+        crate::test::test_query_features(&db).await;
+    }
+
+    struct TempDb(std::path::PathBuf);
+
+    impl TempDb {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "moosicbox_database_{name}_{}.db",
+                std::process::id()
+            ));
+            let db = Self(path);
+            db.remove();
+            db
+        }
+
+        fn remove(&self) {
+            for suffix in ["", "-wal", "-shm"] {
+                let mut path = self.0.clone().into_os_string();
+                path.push(suffix);
+                let _ = std::fs::remove_file(path);
+            }
+        }
+    }
+
+    impl Drop for TempDb {
+        fn drop(&mut self) {
+            self.remove();
+        }
+    }
+
+    async fn names(db: &dyn Database) -> Vec<String> {
+        db.select("pool_test")
+            .columns(&["name"])
+            .execute(db)
+            .await
+            .unwrap()
+            .into_iter()
+            .filter_map(|row| {
+                row.get("name")
+                    .and_then(|x| x.as_str().map(ToString::to_string))
+            })
+            .collect()
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn pooled_reads_dont_wait_for_writes() {
+        let path = TempDb::new("pooled_reads");
+        let db = RusqliteDatabase::open(&path.0, &RusqlitePoolConfig::default()).unwrap();
+        db.exec_raw("CREATE TABLE pool_test (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT)")
+            .await
+            .unwrap();
+        db.insert("pool_test")
+            .value("name", "committed")
+            .execute(&db as &dyn Database)
+            .await
+            .unwrap();
+
+        let tx = db.begin_transaction().await.unwrap();
+        tx.insert("pool_test")
+            .value("name", "pending")
+            .execute(&*tx)
+            .await
+            .unwrap();
+
+        let read = tokio::time::timeout(Duration::from_secs(5), names(&db))
+            .await
+            .expect("Read waited for the open write transaction");
+        assert_eq!(read, vec!["committed".to_string()]);
+
+        tx.commit().await.unwrap();
+
+        assert_eq!(
+            names(&db).await,
+            vec!["committed".to_string(), "pending".to_string()]
+        );
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn lock_contention_maps_to_busy() {
+        let path = TempDb::new("busy");
+        let config = RusqlitePoolConfig {
+            read_connections: 1,
+            busy_timeout: Duration::from_millis(0),
+        };
+        let db = RusqliteDatabase::open(&path.0, &config).unwrap();
+        let other = RusqliteDatabase::open(&path.0, &config).unwrap();
+        db.exec_raw("CREATE TABLE pool_test (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT)")
+            .await
+            .unwrap();
+
+        let tx = db.begin_transaction().await.unwrap();
+        tx.insert("pool_test")
+            .value("name", "locked")
+            .execute(&*tx)
+            .await
+            .unwrap();
+
+        let result = other
+            .insert("pool_test")
+            .value("name", "blocked")
+            .execute(&other as &dyn Database)
+            .await;
+
+        assert!(
+            matches!(result, Err(DatabaseError::Busy)),
+            "Expected a busy error, got {result:?}"
+        );
+
+        tx.rollback().await.unwrap();
+    }
+}
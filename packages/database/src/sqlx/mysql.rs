@@ -4,16 +4,22 @@ use async_trait::async_trait;
 use futures::{Stream, StreamExt};
 use sqlx::{
     mysql::{MySqlArguments, MySqlRow, MySqlValueRef},
+    pool::PoolConnection,
     query::Query,
-    Column, Executor, MySql, MySqlPool, Row, Statement, TypeInfo, Value, ValueRef,
+    Column, Executor, MySql, MySqlConnection, MySqlPool, Row, Statement, Transaction, TypeInfo,
+    Value, ValueRef,
 };
 use thiserror::Error;
 use tokio::sync::Mutex;
 
 use crate::{
-    query::{BooleanExpression, Expression, ExpressionType, Join, Sort, SortDirection},
-    Database, DatabaseError, DatabaseValue, DeleteStatement, InsertStatement, SelectQuery,
-    UpdateStatement, UpsertMultiStatement, UpsertStatement,
+    query::{
+        Aggregate, AggregateFunction, BooleanExpression, Expression, ExpressionType, Join, Sort,
+        SortDirection,
+    },
+    validate_savepoint_name, Database, DatabaseError, DatabaseTransaction, DatabaseValue,
+    DeleteStatement, InsertStatement, SelectQuery, UpdateStatement, UpsertMultiStatement,
+    UpsertStatement,
 };
 
 trait ToSql {
@@ -123,58 +129,35 @@ impl<T: Expression + ?Sized> ToSql for T {
                     .collect::<Vec<_>>()
                     .join(",")
             ),
+            ExpressionType::Like(value) => {
+                if value.case_insensitive {
+                    format!(
+                        "(LOWER({}) LIKE LOWER({}))",
+                        value.left.to_sql(),
+                        value.pattern.to_sql()
+                    )
+                } else {
+                    format!("({} LIKE {})", value.left.to_sql(), value.pattern.to_sql())
+                }
+            }
+            ExpressionType::Aggregate(value) => {
+                let column = value
+                    .column
+                    .as_ref()
+                    .map_or_else(|| "*".to_string(), ToSql::to_sql);
+                let distinct = if value.distinct { "DISTINCT " } else { "" };
+
+                match value.function {
+                    AggregateFunction::Count => format!("COUNT({distinct}{column})"),
+                    AggregateFunction::Sum => format!("CAST(SUM({distinct}{column}) AS DOUBLE)"),
+                    AggregateFunction::Avg => format!("CAST(AVG({distinct}{column}) AS DOUBLE)"),
+                    AggregateFunction::Min => format!("MIN({distinct}{column})"),
+                    AggregateFunction::Max => format!("MAX({distinct}{column})"),
+                }
+            }
             ExpressionType::Literal(value) => value.value.to_string(),
             ExpressionType::Identifier(value) => value.value.clone(),
-            ExpressionType::SelectQuery(value) => {
-                let joins = value.joins.as_ref().map_or_else(String::new, |joins| {
-                    joins.iter().map(Join::to_sql).collect::<Vec<_>>().join(" ")
-                });
-
-                let where_clause = value.filters.as_ref().map_or_else(String::new, |filters| {
-                    if filters.is_empty() {
-                        String::new()
-                    } else {
-                        format!(
-                            "WHERE {}",
-                            filters
-                                .iter()
-                                .map(|x| format!("({})", x.to_sql()))
-                                .collect::<Vec<_>>()
-                                .join(" AND ")
-                        )
-                    }
-                });
-
-                let sort_clause = value.sorts.as_ref().map_or_else(String::new, |sorts| {
-                    if sorts.is_empty() {
-                        String::new()
-                    } else {
-                        format!(
-                            "ORDER BY {}",
-                            sorts
-                                .iter()
-                                .map(Sort::to_sql)
-                                .collect::<Vec<_>>()
-                                .join(", ")
-                        )
-                    }
-                });
-
-                let limit = value
-                    .limit
-                    .map_or_else(String::new, |limit| format!("LIMIT {limit}"));
-
-                format!(
-                    "SELECT {} {} FROM {} {} {} {} {}",
-                    if value.distinct { "DISTINCT" } else { "" },
-                    value.columns.join(", "),
-                    value.table_name,
-                    joins,
-                    where_clause,
-                    sort_clause,
-                    limit
-                )
-            }
+            ExpressionType::SelectQuery(value) => build_select_query(value, value.limit),
             ExpressionType::DatabaseValue(value) => match value {
                 DatabaseValue::Null
                 | DatabaseValue::BoolOpt(None)
@@ -205,6 +188,34 @@ impl MySqlSqlxDatabase {
     pub const fn new(connection: Arc<Mutex<MySqlPool>>) -> Self {
         Self { connection }
     }
+
+    /// # Errors
+    ///
+    /// Will return `Err` if cannot get a connection
+    pub async fn get_connection(&self) -> Result<PoolConnection<MySql>, SqlxDatabaseError> {
+        Ok(self.connection.lock().await.acquire().await?)
+    }
+}
+
+/// A transaction on its own connection from the pool. The transaction is
+/// rolled back if it is dropped without being committed.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug)]
+pub struct MySqlSqlxTransaction {
+    transaction: Mutex<Transaction<'static, MySql>>,
+}
+
+impl MySqlSqlxTransaction {
+    async fn execute(&self, statement: &str) -> Result<(), DatabaseError> {
+        self.transaction
+            .lock()
+            .await
+            .as_mut()
+            .execute(statement)
+            .await
+            .map_err(SqlxDatabaseError::Sqlx)?;
+        Ok(())
+    }
 }
 
 #[derive(Debug, Error)]
@@ -230,33 +241,176 @@ impl From<SqlxDatabaseError> for DatabaseError {
 #[async_trait]
 impl Database for MySqlSqlxDatabase {
     async fn query(&self, query: &SelectQuery<'_>) -> Result<Vec<crate::Row>, DatabaseError> {
-        Ok(select(
-            &*self.connection.lock().await,
-            query.table_name,
-            query.distinct,
-            query.columns,
-            query.filters.as_deref(),
-            query.joins.as_deref(),
-            query.sorts.as_deref(),
-            query.limit,
+        Ok(select(self.get_connection().await?.as_mut(), query, query.limit).await?)
+    }
+
+    async fn query_first(
+        &self,
+        query: &SelectQuery<'_>,
+    ) -> Result<Option<crate::Row>, DatabaseError> {
+        Ok(
+            select(self.get_connection().await?.as_mut(), query, Some(1))
+                .await?
+                .into_iter()
+                .next(),
+        )
+    }
+
+    async fn exec_delete(
+        &self,
+        statement: &DeleteStatement<'_>,
+    ) -> Result<Vec<crate::Row>, DatabaseError> {
+        Ok(delete(
+            self.get_connection().await?.as_mut(),
+            statement.table_name,
+            statement.filters.as_deref(),
+            statement.limit,
+        )
+        .await?)
+    }
+
+    async fn exec_delete_first(
+        &self,
+        statement: &DeleteStatement<'_>,
+    ) -> Result<Option<crate::Row>, DatabaseError> {
+        Ok(delete(
+            self.get_connection().await?.as_mut(),
+            statement.table_name,
+            statement.filters.as_deref(),
+            Some(1),
+        )
+        .await?
+        .into_iter()
+        .next())
+    }
+
+    async fn exec_insert(
+        &self,
+        statement: &InsertStatement<'_>,
+    ) -> Result<crate::Row, DatabaseError> {
+        Ok(insert_and_get_row(
+            self.get_connection().await?.as_mut(),
+            statement.table_name,
+            &statement.values,
+        )
+        .await?)
+    }
+
+    async fn exec_update(
+        &self,
+        statement: &UpdateStatement<'_>,
+    ) -> Result<Vec<crate::Row>, DatabaseError> {
+        Ok(update_and_get_rows(
+            self.get_connection().await?.as_mut(),
+            statement.table_name,
+            &statement.values,
+            statement.filters.as_deref(),
+            statement.limit,
         )
         .await?)
     }
 
+    async fn exec_update_first(
+        &self,
+        statement: &UpdateStatement<'_>,
+    ) -> Result<Option<crate::Row>, DatabaseError> {
+        Ok(update_and_get_row(
+            self.get_connection().await?.as_mut(),
+            statement.table_name,
+            &statement.values,
+            statement.filters.as_deref(),
+            statement.limit,
+        )
+        .await?)
+    }
+
+    async fn exec_upsert(
+        &self,
+        statement: &UpsertStatement<'_>,
+    ) -> Result<Vec<crate::Row>, DatabaseError> {
+        Ok(upsert(
+            self.get_connection().await?.as_mut(),
+            statement.table_name,
+            &statement.values,
+            statement.filters.as_deref(),
+            statement.limit,
+        )
+        .await?)
+    }
+
+    async fn exec_upsert_first(
+        &self,
+        statement: &UpsertStatement<'_>,
+    ) -> Result<crate::Row, DatabaseError> {
+        Ok(upsert_and_get_row(
+            self.get_connection().await?.as_mut(),
+            statement.table_name,
+            &statement.values,
+            statement.filters.as_deref(),
+            statement.limit,
+        )
+        .await?)
+    }
+
+    async fn exec_upsert_multi(
+        &self,
+        statement: &UpsertMultiStatement<'_>,
+    ) -> Result<Vec<crate::Row>, DatabaseError> {
+        let rows = {
+            upsert_multi(
+                self.get_connection().await?.as_mut(),
+                statement.table_name,
+                statement
+                    .unique
+                    .as_ref()
+                    .ok_or(SqlxDatabaseError::MissingUnique)?,
+                &statement.values,
+            )
+            .await?
+        };
+        Ok(rows)
+    }
+
+    async fn exec_raw(&self, statement: &str) -> Result<(), DatabaseError> {
+        self.get_connection()
+            .await?
+            .execute(statement)
+            .await
+            .map_err(SqlxDatabaseError::Sqlx)?;
+        Ok(())
+    }
+
+    async fn begin_transaction(&self) -> Result<Box<dyn DatabaseTransaction>, DatabaseError> {
+        let transaction = self
+            .connection
+            .lock()
+            .await
+            .begin()
+            .await
+            .map_err(SqlxDatabaseError::Sqlx)?;
+
+        Ok(Box::new(MySqlSqlxTransaction {
+            transaction: Mutex::new(transaction),
+        }))
+    }
+}
+
+#[async_trait]
+impl Database for MySqlSqlxTransaction {
+    async fn query(&self, query: &SelectQuery<'_>) -> Result<Vec<crate::Row>, DatabaseError> {
+        Ok(select(self.transaction.lock().await.as_mut(), query, query.limit).await?)
+    }
+
     async fn query_first(
         &self,
         query: &SelectQuery<'_>,
     ) -> Result<Option<crate::Row>, DatabaseError> {
-        Ok(find_row(
-            &*self.connection.lock().await,
-            query.table_name,
-            query.distinct,
-            query.columns,
-            query.filters.as_deref(),
-            query.joins.as_deref(),
-            query.sorts.as_deref(),
+        Ok(
+            select(self.transaction.lock().await.as_mut(), query, Some(1))
+                .await?
+                .into_iter()
+                .next(),
         )
-        .await?)
     }
 
     async fn exec_delete(
@@ -264,7 +418,7 @@ impl Database for MySqlSqlxDatabase {
         statement: &DeleteStatement<'_>,
     ) -> Result<Vec<crate::Row>, DatabaseError> {
         Ok(delete(
-            &*self.connection.lock().await,
+            self.transaction.lock().await.as_mut(),
             statement.table_name,
             statement.filters.as_deref(),
             statement.limit,
@@ -277,7 +431,7 @@ impl Database for MySqlSqlxDatabase {
         statement: &DeleteStatement<'_>,
     ) -> Result<Option<crate::Row>, DatabaseError> {
         Ok(delete(
-            &*self.connection.lock().await,
+            self.transaction.lock().await.as_mut(),
             statement.table_name,
             statement.filters.as_deref(),
             Some(1),
@@ -292,7 +446,7 @@ impl Database for MySqlSqlxDatabase {
         statement: &InsertStatement<'_>,
     ) -> Result<crate::Row, DatabaseError> {
         Ok(insert_and_get_row(
-            &*self.connection.lock().await,
+            self.transaction.lock().await.as_mut(),
             statement.table_name,
             &statement.values,
         )
@@ -304,7 +458,7 @@ impl Database for MySqlSqlxDatabase {
         statement: &UpdateStatement<'_>,
     ) -> Result<Vec<crate::Row>, DatabaseError> {
         Ok(update_and_get_rows(
-            &*self.connection.lock().await,
+            self.transaction.lock().await.as_mut(),
             statement.table_name,
             &statement.values,
             statement.filters.as_deref(),
@@ -318,7 +472,7 @@ impl Database for MySqlSqlxDatabase {
         statement: &UpdateStatement<'_>,
     ) -> Result<Option<crate::Row>, DatabaseError> {
         Ok(update_and_get_row(
-            &*self.connection.lock().await,
+            self.transaction.lock().await.as_mut(),
             statement.table_name,
             &statement.values,
             statement.filters.as_deref(),
@@ -332,7 +486,7 @@ impl Database for MySqlSqlxDatabase {
         statement: &UpsertStatement<'_>,
     ) -> Result<Vec<crate::Row>, DatabaseError> {
         Ok(upsert(
-            &*self.connection.lock().await,
+            self.transaction.lock().await.as_mut(),
             statement.table_name,
             &statement.values,
             statement.filters.as_deref(),
@@ -346,7 +500,7 @@ impl Database for MySqlSqlxDatabase {
         statement: &UpsertStatement<'_>,
     ) -> Result<crate::Row, DatabaseError> {
         Ok(upsert_and_get_row(
-            &*self.connection.lock().await,
+            self.transaction.lock().await.as_mut(),
             statement.table_name,
             &statement.values,
             statement.filters.as_deref(),
@@ -361,7 +515,7 @@ impl Database for MySqlSqlxDatabase {
     ) -> Result<Vec<crate::Row>, DatabaseError> {
         let rows = {
             upsert_multi(
-                &*self.connection.lock().await,
+                self.transaction.lock().await.as_mut(),
                 statement.table_name,
                 statement
                     .unique
@@ -373,6 +527,50 @@ impl Database for MySqlSqlxDatabase {
         };
         Ok(rows)
     }
+
+    async fn exec_raw(&self, statement: &str) -> Result<(), DatabaseError> {
+        self.execute(statement).await
+    }
+
+    async fn begin_transaction(&self) -> Result<Box<dyn DatabaseTransaction>, DatabaseError> {
+        Err(DatabaseError::AlreadyInTransaction)
+    }
+}
+
+#[async_trait]
+impl DatabaseTransaction for MySqlSqlxTransaction {
+    async fn commit(self: Box<Self>) -> Result<(), DatabaseError> {
+        self.transaction
+            .into_inner()
+            .commit()
+            .await
+            .map_err(SqlxDatabaseError::Sqlx)?;
+        Ok(())
+    }
+
+    async fn rollback(self: Box<Self>) -> Result<(), DatabaseError> {
+        self.transaction
+            .into_inner()
+            .rollback()
+            .await
+            .map_err(SqlxDatabaseError::Sqlx)?;
+        Ok(())
+    }
+
+    async fn savepoint(&self, name: &str) -> Result<(), DatabaseError> {
+        validate_savepoint_name(name)?;
+        self.execute(&format!("SAVEPOINT {name}")).await
+    }
+
+    async fn release_savepoint(&self, name: &str) -> Result<(), DatabaseError> {
+        validate_savepoint_name(name)?;
+        self.execute(&format!("RELEASE SAVEPOINT {name}")).await
+    }
+
+    async fn rollback_to_savepoint(&self, name: &str) -> Result<(), DatabaseError> {
+        validate_savepoint_name(name)?;
+        self.execute(&format!("ROLLBACK TO SAVEPOINT {name}")).await
+    }
 }
 
 fn column_value(value: &MySqlValueRef<'_>) -> Result<DatabaseValue, sqlx::Error> {
@@ -384,13 +582,13 @@ fn column_value(value: &MySqlValueRef<'_>) -> Result<DatabaseValue, sqlx::Error>
         "BOOL" => Ok(DatabaseValue::Bool(owned.try_decode()?)),
         "CHAR" | "SMALLINT" | "SMALLSERIAL" | "INT2" | "INT" | "SERIAL" | "INT4" | "BIGINT"
         | "BIGSERIAL" | "INT8" => Ok(DatabaseValue::Number(owned.try_decode()?)),
-        "REAL" | "FLOAT4" | "DOUBLE PRECISION" | "FLOAT8" => {
+        "REAL" | "FLOAT4" | "DOUBLE" | "DOUBLE PRECISION" | "FLOAT8" => {
             Ok(DatabaseValue::Real(owned.try_decode()?))
         }
         "VARCHAR" | "CHAR(N)" | "TEXT" | "NAME" | "CITEXT" => {
             Ok(DatabaseValue::String(owned.try_decode()?))
         }
-        "TIMESTAMP" => Ok(DatabaseValue::DateTime(owned.try_decode()?)),
+        "TIMESTAMP" | "DATETIME" => Ok(DatabaseValue::DateTime(owned.try_decode()?)),
         _ => Err(sqlx::Error::TypeNotFound {
             type_name: value.type_info().name().to_string(),
         }),
@@ -411,7 +609,7 @@ fn from_row(column_names: &[String], row: &MySqlRow) -> Result<crate::Row, SqlxD
 }
 
 async fn update_and_get_row(
-    connection: &MySqlPool,
+    connection: &mut MySqlConnection,
     table_name: &str,
     values: &[(&str, Box<dyn Expression>)],
     filters: Option<&[Box<dyn BooleanExpression>]>,
@@ -473,7 +671,7 @@ async fn update_and_get_row(
 }
 
 async fn update_and_get_rows(
-    connection: &MySqlPool,
+    connection: &mut MySqlConnection,
     table_name: &str,
     values: &[(&str, Box<dyn Expression>)],
     filters: Option<&[Box<dyn BooleanExpression>]>,
@@ -579,6 +777,71 @@ fn build_sort_props(sorts: &[Sort]) -> Vec<String> {
     sorts.iter().map(Sort::to_sql).collect()
 }
 
+fn build_columns(columns: &[&str], aggregates: Option<&[(&str, Aggregate)]>) -> String {
+    columns
+        .iter()
+        .map(std::string::ToString::to_string)
+        .chain(
+            aggregates
+                .unwrap_or_default()
+                .iter()
+                .map(|(alias, aggregate)| format!("{} AS {}", aggregate.to_sql(), alias)),
+        )
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn build_group_by_clause(group_by: Option<&[Box<dyn Expression>]>) -> String {
+    group_by.map_or_else(String::new, |group_by| {
+        if group_by.is_empty() {
+            String::new()
+        } else {
+            format!(
+                "GROUP BY {}",
+                group_by
+                    .iter()
+                    .map(|x| x.to_sql())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        }
+    })
+}
+
+fn build_having_clause(having: Option<&[Box<dyn BooleanExpression>]>) -> String {
+    having.map_or_else(String::new, |having| {
+        if having.is_empty() {
+            String::new()
+        } else {
+            format!("HAVING {}", build_where_props(having).join(" AND "))
+        }
+    })
+}
+
+fn build_limit_clause(limit: Option<usize>, offset: Option<usize>) -> String {
+    match (limit, offset) {
+        (Some(limit), Some(offset)) => format!("LIMIT {limit} OFFSET {offset}"),
+        (Some(limit), None) => format!("LIMIT {limit}"),
+        (None, Some(offset)) => format!("LIMIT 18446744073709551615 OFFSET {offset}"),
+        (None, None) => String::new(),
+    }
+}
+
+fn build_select_query(query: &SelectQuery<'_>, limit: Option<usize>) -> String {
+    format!(
+        "SELECT {} {} FROM {} {} {} {} {} {} {}",
+        if query.distinct { "DISTINCT" } else { "" },
+        build_columns(query.columns, query.aggregates.as_deref()),
+        query.table_name,
+        build_join_clauses(query.joins.as_deref()),
+        build_where_clause(query.filters.as_deref()),
+        build_group_by_clause(query.group_by.as_deref()),
+        build_having_clause(query.having.as_deref()),
+        build_sort_clause(query.sorts.as_deref()),
+        build_limit_clause(limit, query.offset),
+    )
+}
+
 fn build_update_where_clause(
     filters: Option<&[Box<dyn BooleanExpression>]>,
     limit: Option<usize>,
@@ -745,32 +1008,29 @@ fn bexprs_to_values_opt(
     values.map(bexprs_to_values)
 }
 
-#[allow(clippy::too_many_arguments)]
 async fn select(
-    connection: &MySqlPool,
-    table_name: &str,
-    distinct: bool,
-    columns: &[&str],
-    filters: Option<&[Box<dyn BooleanExpression>]>,
-    joins: Option<&[Join<'_>]>,
-    sort: Option<&[Sort]>,
+    connection: &mut MySqlConnection,
+    query: &SelectQuery<'_>,
     limit: Option<usize>,
 ) -> Result<Vec<crate::Row>, SqlxDatabaseError> {
-    let query = format!(
-        "SELECT {} {} FROM {table_name} {} {} {} {}",
-        if distinct { "DISTINCT" } else { "" },
-        columns.join(", "),
-        build_join_clauses(joins),
-        build_where_clause(filters),
-        build_sort_clause(sort),
-        limit.map_or_else(String::new, |limit| format!("LIMIT {limit}"))
-    );
-
-    log::trace!(
-        "Running select query: {query} with params: {:?}",
-        filters.map(|f| f.iter().filter_map(|x| x.params()).collect::<Vec<_>>())
-    );
-
+    let statement_query = build_select_query(query, limit);
+    let values = [
+        query
+            .filters
+            .as_deref()
+            .map(bexprs_to_values)
+            .unwrap_or_default(),
+        query
+            .having
+            .as_deref()
+            .map(bexprs_to_values)
+            .unwrap_or_default(),
+    ]
+    .concat();
+
+    log::trace!("Running select query: {statement_query} with params: {values:?}");
+
+    let query = statement_query;
     let statement = connection.prepare(&query).await?;
     let column_names = statement
         .columns()
@@ -778,14 +1038,13 @@ async fn select(
         .map(|x| x.name().to_string())
         .collect::<Vec<_>>();
 
-    let filters = bexprs_to_values_opt(filters);
-    let query = bind_values(statement.query(), filters.as_deref())?;
+    let query = bind_values(statement.query(), Some(&values))?;
 
     to_rows(&column_names, query.fetch(connection)).await
 }
 
 async fn delete(
-    connection: &MySqlPool,
+    connection: &mut MySqlConnection,
     table_name: &str,
     filters: Option<&[Box<dyn BooleanExpression>]>,
     limit: Option<usize>,
@@ -815,7 +1074,7 @@ async fn delete(
 }
 
 async fn find_row(
-    connection: &MySqlPool,
+    connection: &mut MySqlConnection,
     table_name: &str,
     distinct: bool,
     columns: &[&str],
@@ -858,7 +1117,7 @@ async fn find_row(
 }
 
 async fn insert_and_get_row(
-    connection: &MySqlPool,
+    connection: &mut MySqlConnection,
     table_name: &str,
     values: &[(&str, Box<dyn Expression>)],
 ) -> Result<crate::Row, SqlxDatabaseError> {
@@ -905,7 +1164,7 @@ async fn insert_and_get_row(
 ///
 /// Will return `Err` if the update multi execution failed.
 pub async fn update_multi(
-    connection: &MySqlPool,
+    connection: &mut MySqlConnection,
     table_name: &str,
     values: &[Vec<(&str, Box<dyn Expression>)>],
     filters: Option<Vec<Box<dyn BooleanExpression>>>,
@@ -953,7 +1212,7 @@ pub async fn update_multi(
 }
 
 async fn update_chunk(
-    connection: &MySqlPool,
+    connection: &mut MySqlConnection,
     table_name: &str,
     values: &[Vec<(&str, Box<dyn Expression>)>],
     filters: &Option<Vec<Box<dyn BooleanExpression>>>,
@@ -1048,7 +1307,7 @@ async fn update_chunk(
 ///
 /// Will return `Err` if the upsert multi execution failed.
 pub async fn upsert_multi(
-    connection: &MySqlPool,
+    connection: &mut MySqlConnection,
     table_name: &str,
     unique: &[Box<dyn Expression>],
     values: &[Vec<(&str, Box<dyn Expression>)>],
@@ -1084,7 +1343,7 @@ pub async fn upsert_multi(
 }
 
 async fn upsert_chunk(
-    connection: &MySqlPool,
+    connection: &mut MySqlConnection,
     table_name: &str,
     unique: &[Box<dyn Expression>],
     values: &[Vec<(&str, Box<dyn Expression>)>],
@@ -1158,7 +1417,7 @@ async fn upsert_chunk(
 }
 
 async fn upsert(
-    connection: &MySqlPool,
+    connection: &mut MySqlConnection,
     table_name: &str,
     values: &[(&str, Box<dyn Expression>)],
     filters: Option<&[Box<dyn BooleanExpression>]>,
@@ -1175,7 +1434,7 @@ async fn upsert(
 
 #[allow(unused)]
 async fn upsert_and_get_row(
-    connection: &MySqlPool,
+    connection: &mut MySqlConnection,
     table_name: &str,
     values: &[(&str, Box<dyn Expression>)],
     filters: Option<&[Box<dyn BooleanExpression>]>,
@@ -1232,3 +1491,44 @@ impl Expression for MySqlDatabaseValue {
         ExpressionType::DatabaseValue(self)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_log::test(tokio::test)]
+    #[ignore = "requires a mysql server at TEST_MYSQL_URL"]
+    async fn transactions_roll_back_and_commit() {
+        let url = std::env::var("TEST_MYSQL_URL").expect("Missing TEST_MYSQL_URL");
+        let pool = MySqlPool::connect(&url).await.unwrap();
+        pool.execute("DROP TABLE IF EXISTS transaction_test")
+            .await
+            .unwrap();
+        pool.execute(
+            "CREATE TABLE transaction_test (id BIGINT AUTO_INCREMENT PRIMARY KEY, name TEXT)",
+        )
+        .await
+        .unwrap();
+        let db = MySqlSqlxDatabase::new(Arc::new(Mutex::new(pool)));
+
+        crate::test::test_transactions(&db).await;
+    }
+
+    #[test_log::test(tokio::test)]
+    #[ignore = "requires a mysql server at TEST_MYSQL_URL"]
+    async fn query_features() {
+        let url = std::env::var("TEST_MYSQL_URL").expect("Missing TEST_MYSQL_URL");
+        let pool = MySqlPool::connect(&url).await.unwrap();
+        pool.execute("DROP TABLE IF EXISTS query_test")
+            .await
+            .unwrap();
+        pool.execute(
+            "CREATE TABLE query_test (id BIGINT AUTO_INCREMENT PRIMARY KEY, artist TEXT, duration BIGINT)",
+        )
+        .await
+        .unwrap();
+        let db = MySqlSqlxDatabase::new(Arc::new(Mutex::new(pool)));
+
+        crate::test::test_query_features(&db).await;
+    }
+}
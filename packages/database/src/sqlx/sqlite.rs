@@ -10,16 +10,20 @@ use sqlx::{
     pool::PoolConnection,
     query::Query,
     sqlite::{SqliteArguments, SqliteRow, SqliteValueRef},
-    Column, Executor, Row, Sqlite, SqliteConnection, SqlitePool, Statement, TypeInfo, Value,
-    ValueRef,
+    Column, Executor, Row, Sqlite, SqliteConnection, SqlitePool, Statement, Transaction, TypeInfo,
+    Value, ValueRef,
 };
 use thiserror::Error;
-use tokio::sync::Mutex;
+use tokio::sync::{Mutex, OwnedMutexGuard};
 
 use crate::{
-    query::{BooleanExpression, Expression, ExpressionType, Join, Sort, SortDirection},
-    Database, DatabaseError, DatabaseValue, DeleteStatement, InsertStatement, SelectQuery,
-    UpdateStatement, UpsertMultiStatement, UpsertStatement,
+    query::{
+        Aggregate, AggregateFunction, BooleanExpression, Expression, ExpressionType, Join, Sort,
+        SortDirection,
+    },
+    validate_savepoint_name, Database, DatabaseError, DatabaseTransaction, DatabaseValue,
+    DeleteStatement, InsertStatement, SelectQuery, UpdateStatement, UpsertMultiStatement,
+    UpsertStatement,
 };
 
 trait ToSql {
@@ -165,67 +169,33 @@ impl<T: Expression + ?Sized> ToSql for T {
                     .collect::<Vec<_>>()
                     .join(",")
             ),
-            ExpressionType::Literal(value) => value.value.to_string(),
-            ExpressionType::Identifier(value) => format_identifier(&value.value),
-            ExpressionType::SelectQuery(value) => {
-                let joins = value.joins.as_ref().map_or_else(String::new, |joins| {
-                    joins
-                        .iter()
-                        .map(|x| x.to_sql(index))
-                        .collect::<Vec<_>>()
-                        .join(" ")
-                });
-
-                let where_clause = value.filters.as_ref().map_or_else(String::new, |filters| {
-                    if filters.is_empty() {
-                        String::new()
-                    } else {
-                        format!(
-                            "WHERE {}",
-                            filters
-                                .iter()
-                                .map(|x| format!("({})", x.to_sql(index)))
-                                .collect::<Vec<_>>()
-                                .join(" AND ")
-                        )
-                    }
-                });
-
-                let sort_clause = value.sorts.as_ref().map_or_else(String::new, |sorts| {
-                    if sorts.is_empty() {
-                        String::new()
-                    } else {
-                        format!(
-                            "ORDER BY {}",
-                            sorts
-                                .iter()
-                                .map(|x| x.to_sql(index))
-                                .collect::<Vec<_>>()
-                                .join(", ")
-                        )
-                    }
-                });
-
-                let limit = value
-                    .limit
-                    .map_or_else(String::new, |limit| format!("LIMIT {limit}"));
-
+            // sqlite's `LIKE` already ignores the case of ASCII characters,
+            // and `LOWER` doesn't fold non-ASCII characters either
+            ExpressionType::Like(value) => {
                 format!(
-                    "SELECT {} {} FROM {} {} {} {} {}",
-                    if value.distinct { "DISTINCT" } else { "" },
-                    value
-                        .columns
-                        .iter()
-                        .map(|x| format_identifier(x))
-                        .collect::<Vec<_>>()
-                        .join(", "),
-                    value.table_name,
-                    joins,
-                    where_clause,
-                    sort_clause,
-                    limit
+                    "({} LIKE {})",
+                    value.left.to_sql(index),
+                    value.pattern.to_sql(index)
                 )
             }
+            ExpressionType::Aggregate(value) => {
+                let column = value
+                    .column
+                    .as_ref()
+                    .map_or_else(|| "*".to_string(), |x| x.to_sql(index));
+                let distinct = if value.distinct { "DISTINCT " } else { "" };
+
+                match value.function {
+                    AggregateFunction::Count => format!("COUNT({distinct}{column})"),
+                    AggregateFunction::Sum => format!("CAST(SUM({distinct}{column}) AS REAL)"),
+                    AggregateFunction::Avg => format!("CAST(AVG({distinct}{column}) AS REAL)"),
+                    AggregateFunction::Min => format!("MIN({distinct}{column})"),
+                    AggregateFunction::Max => format!("MAX({distinct}{column})"),
+                }
+            }
+            ExpressionType::Literal(value) => value.value.to_string(),
+            ExpressionType::Identifier(value) => format_identifier(&value.value),
+            ExpressionType::SelectQuery(value) => build_select_query(value, value.limit, index),
             ExpressionType::DatabaseValue(value) => match value {
                 DatabaseValue::Null
                 | DatabaseValue::BoolOpt(None)
@@ -282,6 +252,32 @@ impl SqliteSqlxDatabase {
     }
 }
 
+/// A transaction on its own connection from the pool.
+///
+/// The database's shared connection is held until the transaction is finished
+/// so that statements executed on the database in the meantime wait for it
+/// instead of failing on the locked database file. The transaction is rolled
+/// back if it is dropped without being committed.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug)]
+pub struct SqliteSqlxTransaction {
+    transaction: Mutex<Transaction<'static, Sqlite>>,
+    _connection: OwnedMutexGuard<PoolConnection<Sqlite>>,
+}
+
+impl SqliteSqlxTransaction {
+    async fn execute(&self, statement: &str) -> Result<(), DatabaseError> {
+        self.transaction
+            .lock()
+            .await
+            .as_mut()
+            .execute(statement)
+            .await
+            .map_err(SqlxDatabaseError::Sqlx)?;
+        Ok(())
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum SqlxDatabaseError {
     #[error(transparent)]
@@ -307,12 +303,7 @@ impl Database for SqliteSqlxDatabase {
     async fn query(&self, query: &SelectQuery<'_>) -> Result<Vec<crate::Row>, DatabaseError> {
         Ok(select(
             self.get_connection().await?.lock().await.as_mut(),
-            query.table_name,
-            query.distinct,
-            query.columns,
-            query.filters.as_deref(),
-            query.joins.as_deref(),
-            query.sorts.as_deref(),
+            query,
             query.limit,
         )
         .await?)
@@ -322,16 +313,14 @@ impl Database for SqliteSqlxDatabase {
         &self,
         query: &SelectQuery<'_>,
     ) -> Result<Option<crate::Row>, DatabaseError> {
-        Ok(find_row(
+        Ok(select(
             self.get_connection().await?.lock().await.as_mut(),
-            query.table_name,
-            query.distinct,
-            query.columns,
-            query.filters.as_deref(),
-            query.joins.as_deref(),
-            query.sorts.as_deref(),
+            query,
+            Some(1),
         )
-        .await?)
+        .await?
+        .into_iter()
+        .next())
     }
 
     async fn exec_delete(
@@ -448,6 +437,211 @@ impl Database for SqliteSqlxDatabase {
         };
         Ok(rows)
     }
+
+    async fn exec_raw(&self, statement: &str) -> Result<(), DatabaseError> {
+        self.get_connection()
+            .await?
+            .lock()
+            .await
+            .execute(statement)
+            .await
+            .map_err(SqlxDatabaseError::Sqlx)?;
+        Ok(())
+    }
+
+    async fn begin_transaction(&self) -> Result<Box<dyn DatabaseTransaction>, DatabaseError> {
+        let connection = self.get_connection().await?.lock_owned().await;
+        let transaction = self
+            .pool
+            .lock()
+            .await
+            .begin()
+            .await
+            .map_err(SqlxDatabaseError::Sqlx)?;
+
+        Ok(Box::new(SqliteSqlxTransaction {
+            transaction: Mutex::new(transaction),
+            _connection: connection,
+        }))
+    }
+}
+
+#[async_trait]
+impl Database for SqliteSqlxTransaction {
+    async fn query(&self, query: &SelectQuery<'_>) -> Result<Vec<crate::Row>, DatabaseError> {
+        Ok(select(self.transaction.lock().await.as_mut(), query, query.limit).await?)
+    }
+
+    async fn query_first(
+        &self,
+        query: &SelectQuery<'_>,
+    ) -> Result<Option<crate::Row>, DatabaseError> {
+        Ok(
+            select(self.transaction.lock().await.as_mut(), query, Some(1))
+                .await?
+                .into_iter()
+                .next(),
+        )
+    }
+
+    async fn exec_delete(
+        &self,
+        statement: &DeleteStatement<'_>,
+    ) -> Result<Vec<crate::Row>, DatabaseError> {
+        Ok(delete(
+            self.transaction.lock().await.as_mut(),
+            statement.table_name,
+            statement.filters.as_deref(),
+            statement.limit,
+        )
+        .await?)
+    }
+
+    async fn exec_delete_first(
+        &self,
+        statement: &DeleteStatement<'_>,
+    ) -> Result<Option<crate::Row>, DatabaseError> {
+        Ok(delete(
+            self.transaction.lock().await.as_mut(),
+            statement.table_name,
+            statement.filters.as_deref(),
+            Some(1),
+        )
+        .await?
+        .into_iter()
+        .next())
+    }
+
+    async fn exec_insert(
+        &self,
+        statement: &InsertStatement<'_>,
+    ) -> Result<crate::Row, DatabaseError> {
+        Ok(insert_and_get_row(
+            self.transaction.lock().await.as_mut(),
+            statement.table_name,
+            &statement.values,
+        )
+        .await?)
+    }
+
+    async fn exec_update(
+        &self,
+        statement: &UpdateStatement<'_>,
+    ) -> Result<Vec<crate::Row>, DatabaseError> {
+        Ok(update_and_get_rows(
+            self.transaction.lock().await.as_mut(),
+            statement.table_name,
+            &statement.values,
+            statement.filters.as_deref(),
+            statement.limit,
+        )
+        .await?)
+    }
+
+    async fn exec_update_first(
+        &self,
+        statement: &UpdateStatement<'_>,
+    ) -> Result<Option<crate::Row>, DatabaseError> {
+        Ok(update_and_get_row(
+            self.transaction.lock().await.as_mut(),
+            statement.table_name,
+            &statement.values,
+            statement.filters.as_deref(),
+            statement.limit,
+        )
+        .await?)
+    }
+
+    async fn exec_upsert(
+        &self,
+        statement: &UpsertStatement<'_>,
+    ) -> Result<Vec<crate::Row>, DatabaseError> {
+        Ok(upsert(
+            self.transaction.lock().await.as_mut(),
+            statement.table_name,
+            &statement.values,
+            statement.filters.as_deref(),
+            statement.limit,
+        )
+        .await?)
+    }
+
+    async fn exec_upsert_first(
+        &self,
+        statement: &UpsertStatement<'_>,
+    ) -> Result<crate::Row, DatabaseError> {
+        Ok(upsert_and_get_row(
+            self.transaction.lock().await.as_mut(),
+            statement.table_name,
+            &statement.values,
+            statement.filters.as_deref(),
+            statement.limit,
+        )
+        .await?)
+    }
+
+    async fn exec_upsert_multi(
+        &self,
+        statement: &UpsertMultiStatement<'_>,
+    ) -> Result<Vec<crate::Row>, DatabaseError> {
+        let rows = {
+            upsert_multi(
+                self.transaction.lock().await.as_mut(),
+                statement.table_name,
+                statement
+                    .unique
+                    .as_ref()
+                    .ok_or(SqlxDatabaseError::MissingUnique)?,
+                &statement.values,
+            )
+            .await?
+        };
+        Ok(rows)
+    }
+
+    async fn exec_raw(&self, statement: &str) -> Result<(), DatabaseError> {
+        self.execute(statement).await
+    }
+
+    async fn begin_transaction(&self) -> Result<Box<dyn DatabaseTransaction>, DatabaseError> {
+        Err(DatabaseError::AlreadyInTransaction)
+    }
+}
+
+#[async_trait]
+impl DatabaseTransaction for SqliteSqlxTransaction {
+    async fn commit(self: Box<Self>) -> Result<(), DatabaseError> {
+        self.transaction
+            .into_inner()
+            .commit()
+            .await
+            .map_err(SqlxDatabaseError::Sqlx)?;
+        Ok(())
+    }
+
+    async fn rollback(self: Box<Self>) -> Result<(), DatabaseError> {
+        self.transaction
+            .into_inner()
+            .rollback()
+            .await
+            .map_err(SqlxDatabaseError::Sqlx)?;
+        Ok(())
+    }
+
+    async fn savepoint(&self, name: &str) -> Result<(), DatabaseError> {
+        validate_savepoint_name(name)?;
+        self.execute(&format!("SAVEPOINT {name}")).await
+    }
+
+    async fn release_savepoint(&self, name: &str) -> Result<(), DatabaseError> {
+        validate_savepoint_name(name)?;
+        self.execute(&format!("RELEASE SAVEPOINT {name}")).await
+    }
+
+    async fn rollback_to_savepoint(&self, name: &str) -> Result<(), DatabaseError> {
+        validate_savepoint_name(name)?;
+        self.execute(&format!("ROLLBACK TO SAVEPOINT {name}")).await
+    }
 }
 
 /// # Errors
@@ -694,6 +888,81 @@ fn build_sort_props(sorts: &[Sort], index: &AtomicU16) -> Vec<String> {
     sorts.iter().map(|sort| sort.to_sql(index)).collect()
 }
 
+fn build_columns(
+    columns: &[&str],
+    aggregates: Option<&[(&str, Aggregate)]>,
+    index: &AtomicU16,
+) -> String {
+    columns
+        .iter()
+        .map(|x| format_identifier(x))
+        .chain(
+            aggregates
+                .unwrap_or_default()
+                .iter()
+                .map(|(alias, aggregate)| {
+                    format!(
+                        "{} AS {}",
+                        aggregate.to_sql(index),
+                        format_identifier(alias)
+                    )
+                }),
+        )
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn build_group_by_clause(group_by: Option<&[Box<dyn Expression>]>, index: &AtomicU16) -> String {
+    group_by.map_or_else(String::new, |group_by| {
+        if group_by.is_empty() {
+            String::new()
+        } else {
+            format!(
+                "GROUP BY {}",
+                group_by
+                    .iter()
+                    .map(|x| x.to_sql(index))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        }
+    })
+}
+
+fn build_having_clause(having: Option<&[Box<dyn BooleanExpression>]>, index: &AtomicU16) -> String {
+    having.map_or_else(String::new, |having| {
+        if having.is_empty() {
+            String::new()
+        } else {
+            format!("HAVING {}", build_where_props(having, index).join(" AND "))
+        }
+    })
+}
+
+fn build_limit_clause(limit: Option<usize>, offset: Option<usize>) -> String {
+    match (limit, offset) {
+        (Some(limit), Some(offset)) => format!("LIMIT {limit} OFFSET {offset}"),
+        (Some(limit), None) => format!("LIMIT {limit}"),
+        (None, Some(offset)) => format!("LIMIT -1 OFFSET {offset}"),
+        (None, None) => String::new(),
+    }
+}
+
+fn build_select_query(query: &SelectQuery<'_>, limit: Option<usize>, index: &AtomicU16) -> String {
+    format!(
+        "SELECT {} {} FROM {} {} {} {} {} {} {}",
+        if query.distinct { "DISTINCT" } else { "" },
+        build_columns(query.columns, query.aggregates.as_deref(), index),
+        query.table_name,
+        build_join_clauses(query.joins.as_deref()),
+        build_where_clause(query.filters.as_deref(), index),
+        build_group_by_clause(query.group_by.as_deref(), index),
+        build_having_clause(query.having.as_deref(), index),
+        build_sort_clause(query.sorts.as_deref(), index),
+        build_limit_clause(limit, query.offset),
+    )
+}
+
 fn build_update_where_clause(
     filters: Option<&[Box<dyn BooleanExpression>]>,
     limit: Option<usize>,
@@ -879,37 +1148,30 @@ fn bexprs_to_values_opt(
     values.map(bexprs_to_values)
 }
 
-#[allow(clippy::too_many_arguments)]
 async fn select(
     connection: &mut SqliteConnection,
-    table_name: &str,
-    distinct: bool,
-    columns: &[&str],
-    filters: Option<&[Box<dyn BooleanExpression>]>,
-    joins: Option<&[Join<'_>]>,
-    sort: Option<&[Sort]>,
+    query: &SelectQuery<'_>,
     limit: Option<usize>,
 ) -> Result<Vec<crate::Row>, SqlxDatabaseError> {
     let index = AtomicU16::new(0);
-    let query = format!(
-        "SELECT {} {} FROM {table_name} {} {} {} {}",
-        if distinct { "DISTINCT" } else { "" },
-        columns
-            .iter()
-            .map(|x| format_identifier(x))
-            .collect::<Vec<_>>()
-            .join(", "),
-        build_join_clauses(joins),
-        build_where_clause(filters, &index),
-        build_sort_clause(sort, &index),
-        limit.map_or_else(String::new, |limit| format!("LIMIT {limit}"))
-    );
-
-    log::trace!(
-        "Running select query: {query} with params: {:?}",
-        filters.map(|f| f.iter().filter_map(|x| x.params()).collect::<Vec<_>>())
-    );
-
+    let statement_query = build_select_query(query, limit, &index);
+    let values = [
+        query
+            .filters
+            .as_deref()
+            .map(bexprs_to_values)
+            .unwrap_or_default(),
+        query
+            .having
+            .as_deref()
+            .map(bexprs_to_values)
+            .unwrap_or_default(),
+    ]
+    .concat();
+
+    log::trace!("Running select query: {statement_query} with params: {values:?}");
+
+    let query = statement_query;
     let statement = connection.prepare(&query).await?;
     let column_names = statement
         .columns()
@@ -917,8 +1179,7 @@ async fn select(
         .map(|x| x.name().to_string())
         .collect::<Vec<_>>();
 
-    let filters = bexprs_to_values_opt(filters);
-    let query = bind_values(statement.query(), filters.as_deref())?;
+    let query = bind_values(statement.query(), Some(&values))?;
 
     to_rows(&column_names, query.fetch(connection)).await
 }
@@ -1414,3 +1675,57 @@ impl Expression for SqliteDatabaseValue {
         ExpressionType::DatabaseValue(self)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+
+    use super::*;
+
+    #[test_log::test(tokio::test)]
+    async fn transactions_roll_back_and_commit() {
+        let path = std::env::temp_dir().join(format!(
+            "moosicbox_database_transactions_{}.db",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+
+        let pool = SqlitePoolOptions::new()
+            .max_connections(5)
+            .connect_with(
+                SqliteConnectOptions::new()
+                    .filename(&path)
+                    .create_if_missing(true),
+            )
+            .await
+            .unwrap();
+        pool.execute(
+            "CREATE TABLE transaction_test (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT)",
+        )
+        .await
+        .unwrap();
+        let db = SqliteSqlxDatabase::new(Arc::new(Mutex::new(pool)));
+
+        crate::test::test_transactions(&db).await;
+
+        drop(db);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn query_features() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        pool.execute(
+            "CREATE TABLE query_test (id INTEGER PRIMARY KEY AUTOINCREMENT, artist TEXT, duration INTEGER)",
+        )
+        .await
+        .unwrap();
+        let db = SqliteSqlxDatabase::new(Arc::new(Mutex::new(pool)));
+
+        crate::test::test_query_features(&db).await;
+    }
+}
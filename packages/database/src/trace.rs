@@ -0,0 +1,762 @@
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    sync::{Arc, LazyLock, Mutex},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+
+use crate::{
+    query::{
+        AggregateFunction, BooleanExpression, DeleteStatement, Expression, ExpressionType,
+        InsertStatement, SelectQuery, SortDirection, UpdateStatement, UpsertMultiStatement,
+        UpsertStatement,
+    },
+    Database, DatabaseError, DatabaseTransaction, DatabaseValue, Row,
+};
+
+pub const DEFAULT_SLOW_QUERY_THRESHOLD: Duration = Duration::from_millis(100);
+
+/// The stats recorded by every [`TracedDatabase`] that isn't given its own
+/// [`QueryStats`].
+pub static QUERY_STATS: LazyLock<Arc<QueryStats>> =
+    LazyLock::new(|| Arc::new(QueryStats::default()));
+
+/// Aggregated counters for a single rendered statement.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "api", derive(serde::Serialize))]
+#[cfg_attr(feature = "api", serde(rename_all = "camelCase"))]
+pub struct StatementStats {
+    pub statement: String,
+    pub count: u64,
+    pub errors: u64,
+    pub slow: u64,
+    pub rows: u64,
+    pub params: u64,
+    pub total_duration_micros: u64,
+    pub max_duration_micros: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct QueryStats {
+    statements: Mutex<BTreeMap<String, StatementStats>>,
+}
+
+impl QueryStats {
+    fn record(
+        &self,
+        statement: &str,
+        params: usize,
+        rows: Option<usize>,
+        duration: Duration,
+        slow: bool,
+    ) {
+        let micros = u64::try_from(duration.as_micros()).unwrap_or(u64::MAX);
+        let mut statements = self.statements.lock().unwrap();
+        let stats = statements
+            .entry(statement.to_string())
+            .or_insert_with(|| StatementStats {
+                statement: statement.to_string(),
+                ..Default::default()
+            });
+
+        stats.count += 1;
+        stats.params += params as u64;
+        stats.total_duration_micros = stats.total_duration_micros.saturating_add(micros);
+        stats.max_duration_micros = stats.max_duration_micros.max(micros);
+        if slow {
+            stats.slow += 1;
+        }
+        match rows {
+            Some(rows) => stats.rows += rows as u64,
+            None => stats.errors += 1,
+        }
+        drop(statements);
+    }
+
+    /// Returns the counters for every statement recorded so far, the
+    /// statements that took the most time in total first.
+    ///
+    /// # Panics
+    ///
+    /// * If the stats lock is poisoned
+    #[must_use]
+    pub fn snapshot(&self) -> Vec<StatementStats> {
+        let mut stats = self
+            .statements
+            .lock()
+            .unwrap()
+            .values()
+            .cloned()
+            .collect::<Vec<_>>();
+        stats.sort_by_key(|x| std::cmp::Reverse(x.total_duration_micros));
+        stats
+    }
+
+    /// # Panics
+    ///
+    /// * If the stats lock is poisoned
+    pub fn reset(&self) {
+        self.statements.lock().unwrap().clear();
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Tracer {
+    stats: Arc<QueryStats>,
+    slow_query_threshold: Duration,
+}
+
+impl Tracer {
+    async fn trace<T>(
+        &self,
+        statement: String,
+        params: usize,
+        rows: impl FnOnce(&T) -> usize + Send,
+        future: impl Future<Output = Result<T, DatabaseError>> + Send,
+    ) -> Result<T, DatabaseError> {
+        let start = Instant::now();
+        let result = future.await;
+        let duration = start.elapsed();
+        let row_count = result.as_ref().ok().map(rows);
+        let slow = duration >= self.slow_query_threshold;
+
+        if slow {
+            log::warn!(
+                "Slow query ({duration:?}, {params} params, {} rows): {statement}",
+                row_count.map_or_else(|| "failed".to_string(), |x| x.to_string())
+            );
+        } else {
+            log::trace!("Query ({duration:?}, {params} params, {row_count:?} rows): {statement}");
+        }
+
+        self.stats
+            .record(&statement, params, row_count, duration, slow);
+
+        result
+    }
+}
+
+/// A [`Database`] that records the rendered SQL, bound parameter count,
+/// duration and row count of every statement it executes, and logs the
+/// statements that take longer than its slow query threshold.
+///
+/// The statements are rendered in a backend agnostic form with `?` in place
+/// of the bound values, so executions of the same statement are aggregated
+/// together.
+#[derive(Debug)]
+pub struct TracedDatabase {
+    inner: Box<dyn Database>,
+    tracer: Tracer,
+}
+
+impl TracedDatabase {
+    #[must_use]
+    pub fn new(inner: Box<dyn Database>) -> Self {
+        Self {
+            inner,
+            tracer: Tracer {
+                stats: QUERY_STATS.clone(),
+                slow_query_threshold: DEFAULT_SLOW_QUERY_THRESHOLD,
+            },
+        }
+    }
+
+    #[must_use]
+    pub const fn with_slow_query_threshold(mut self, threshold: Duration) -> Self {
+        self.tracer.slow_query_threshold = threshold;
+        self
+    }
+
+    #[must_use]
+    pub fn with_stats(mut self, stats: Arc<QueryStats>) -> Self {
+        self.tracer.stats = stats;
+        self
+    }
+}
+
+/// A transaction started on a [`TracedDatabase`]
+#[derive(Debug)]
+pub struct TracedTransaction {
+    inner: Box<dyn DatabaseTransaction>,
+    tracer: Tracer,
+}
+
+macro_rules! impl_traced_database {
+    ($type:ty) => {
+        #[async_trait]
+        impl Database for $type {
+            async fn query(&self, query: &SelectQuery<'_>) -> Result<Vec<Row>, DatabaseError> {
+                self.tracer
+                    .trace(
+                        render_select(query, query.limit),
+                        param_count(query),
+                        Vec::len,
+                        self.inner.query(query),
+                    )
+                    .await
+            }
+
+            async fn query_first(
+                &self,
+                query: &SelectQuery<'_>,
+            ) -> Result<Option<Row>, DatabaseError> {
+                self.tracer
+                    .trace(
+                        render_select(query, Some(1)),
+                        param_count(query),
+                        |x: &Option<Row>| usize::from(x.is_some()),
+                        self.inner.query_first(query),
+                    )
+                    .await
+            }
+
+            async fn exec_update(
+                &self,
+                statement: &UpdateStatement<'_>,
+            ) -> Result<Vec<Row>, DatabaseError> {
+                self.tracer
+                    .trace(
+                        render_update(statement, statement.limit),
+                        values_param_count(&statement.values)
+                            + filters_param_count(statement.filters.as_deref()),
+                        Vec::len,
+                        self.inner.exec_update(statement),
+                    )
+                    .await
+            }
+
+            async fn exec_update_first(
+                &self,
+                statement: &UpdateStatement<'_>,
+            ) -> Result<Option<Row>, DatabaseError> {
+                self.tracer
+                    .trace(
+                        render_update(statement, Some(1)),
+                        values_param_count(&statement.values)
+                            + filters_param_count(statement.filters.as_deref()),
+                        |x: &Option<Row>| usize::from(x.is_some()),
+                        self.inner.exec_update_first(statement),
+                    )
+                    .await
+            }
+
+            async fn exec_insert(
+                &self,
+                statement: &InsertStatement<'_>,
+            ) -> Result<Row, DatabaseError> {
+                self.tracer
+                    .trace(
+                        render_insert(statement.table_name, &statement.values),
+                        values_param_count(&statement.values),
+                        |_| 1,
+                        self.inner.exec_insert(statement),
+                    )
+                    .await
+            }
+
+            async fn exec_upsert(
+                &self,
+                statement: &UpsertStatement<'_>,
+            ) -> Result<Vec<Row>, DatabaseError> {
+                self.tracer
+                    .trace(
+                        render_upsert(statement, statement.limit),
+                        values_param_count(&statement.values)
+                            + filters_param_count(statement.filters.as_deref()),
+                        Vec::len,
+                        self.inner.exec_upsert(statement),
+                    )
+                    .await
+            }
+
+            async fn exec_upsert_first(
+                &self,
+                statement: &UpsertStatement<'_>,
+            ) -> Result<Row, DatabaseError> {
+                self.tracer
+                    .trace(
+                        render_upsert(statement, Some(1)),
+                        values_param_count(&statement.values)
+                            + filters_param_count(statement.filters.as_deref()),
+                        |_| 1,
+                        self.inner.exec_upsert_first(statement),
+                    )
+                    .await
+            }
+
+            async fn exec_upsert_multi(
+                &self,
+                statement: &UpsertMultiStatement<'_>,
+            ) -> Result<Vec<Row>, DatabaseError> {
+                self.tracer
+                    .trace(
+                        render_upsert_multi(statement),
+                        statement
+                            .values
+                            .iter()
+                            .map(|values| values_param_count(values))
+                            .sum(),
+                        Vec::len,
+                        self.inner.exec_upsert_multi(statement),
+                    )
+                    .await
+            }
+
+            async fn exec_delete(
+                &self,
+                statement: &DeleteStatement<'_>,
+            ) -> Result<Vec<Row>, DatabaseError> {
+                self.tracer
+                    .trace(
+                        render_delete(statement, statement.limit),
+                        filters_param_count(statement.filters.as_deref()),
+                        Vec::len,
+                        self.inner.exec_delete(statement),
+                    )
+                    .await
+            }
+
+            async fn exec_delete_first(
+                &self,
+                statement: &DeleteStatement<'_>,
+            ) -> Result<Option<Row>, DatabaseError> {
+                self.tracer
+                    .trace(
+                        render_delete(statement, Some(1)),
+                        filters_param_count(statement.filters.as_deref()),
+                        |x: &Option<Row>| usize::from(x.is_some()),
+                        self.inner.exec_delete_first(statement),
+                    )
+                    .await
+            }
+
+            async fn exec_raw(&self, statement: &str) -> Result<(), DatabaseError> {
+                self.tracer
+                    .trace(
+                        statement.to_string(),
+                        0,
+                        |()| 0,
+                        self.inner.exec_raw(statement),
+                    )
+                    .await
+            }
+
+            fn trigger_close(&self) -> Result<(), DatabaseError> {
+                self.inner.trigger_close()
+            }
+
+            async fn close(&self) -> Result<(), DatabaseError> {
+                self.inner.close().await
+            }
+
+            async fn begin_transaction(
+                &self,
+            ) -> Result<Box<dyn DatabaseTransaction>, DatabaseError> {
+                Ok(Box::new(TracedTransaction {
+                    inner: self.inner.begin_transaction().await?,
+                    tracer: self.tracer.clone(),
+                }))
+            }
+        }
+    };
+}
+
+impl_traced_database!(TracedDatabase);
+impl_traced_database!(TracedTransaction);
+
+#[async_trait]
+impl DatabaseTransaction for TracedTransaction {
+    async fn commit(self: Box<Self>) -> Result<(), DatabaseError> {
+        let tracer = self.tracer.clone();
+        tracer
+            .trace("COMMIT".to_string(), 0, |()| 0, self.inner.commit())
+            .await
+    }
+
+    async fn rollback(self: Box<Self>) -> Result<(), DatabaseError> {
+        let tracer = self.tracer.clone();
+        tracer
+            .trace("ROLLBACK".to_string(), 0, |()| 0, self.inner.rollback())
+            .await
+    }
+
+    async fn savepoint(&self, name: &str) -> Result<(), DatabaseError> {
+        self.inner.savepoint(name).await
+    }
+
+    async fn release_savepoint(&self, name: &str) -> Result<(), DatabaseError> {
+        self.inner.release_savepoint(name).await
+    }
+
+    async fn rollback_to_savepoint(&self, name: &str) -> Result<(), DatabaseError> {
+        self.inner.rollback_to_savepoint(name).await
+    }
+}
+
+fn param_count(expression: &dyn Expression) -> usize {
+    expression.params().map_or(0, |x| x.len())
+}
+
+fn values_param_count(values: &[(&str, Box<dyn Expression>)]) -> usize {
+    values.iter().map(|(_, value)| param_count(&**value)).sum()
+}
+
+fn filters_param_count(filters: Option<&[Box<dyn BooleanExpression>]>) -> usize {
+    filters
+        .unwrap_or_default()
+        .iter()
+        .map(|filter| param_count(&**filter))
+        .sum()
+}
+
+fn join(expressions: impl Iterator<Item = String>, separator: &str) -> String {
+    expressions.collect::<Vec<_>>().join(separator)
+}
+
+fn render_filters(filters: Option<&[Box<dyn BooleanExpression>]>, keyword: &str) -> String {
+    match filters {
+        Some(filters) if !filters.is_empty() => format!(
+            " {keyword} {}",
+            join(filters.iter().map(|x| render(&**x)), " AND ")
+        ),
+        _ => String::new(),
+    }
+}
+
+fn render_limit(limit: Option<usize>) -> String {
+    limit.map_or_else(String::new, |limit| format!(" LIMIT {limit}"))
+}
+
+fn render_values(values: &[(&str, Box<dyn Expression>)]) -> (String, String) {
+    (
+        join(values.iter().map(|(name, _)| (*name).to_string()), ", "),
+        join(values.iter().map(|(_, value)| render(&**value)), ", "),
+    )
+}
+
+fn render_select(query: &SelectQuery<'_>, limit: Option<usize>) -> String {
+    let columns = query.columns.iter().map(ToString::to_string).chain(
+        query
+            .aggregates
+            .as_deref()
+            .unwrap_or_default()
+            .iter()
+            .map(|(alias, aggregate)| format!("{} AS {alias}", render(aggregate))),
+    );
+
+    format!(
+        "SELECT {}{} FROM {}{}{}{}{}{}{}{}",
+        if query.distinct { "DISTINCT " } else { "" },
+        join(columns, ", "),
+        query.table_name,
+        query
+            .joins
+            .as_deref()
+            .unwrap_or_default()
+            .iter()
+            .map(|x| format!(" {}", render(x)))
+            .collect::<Vec<_>>()
+            .concat(),
+        render_filters(query.filters.as_deref(), "WHERE"),
+        query
+            .group_by
+            .as_deref()
+            .filter(|x| !x.is_empty())
+            .map_or_else(String::new, |x| format!(
+                " GROUP BY {}",
+                join(x.iter().map(|x| render(&**x)), ", ")
+            )),
+        render_filters(query.having.as_deref(), "HAVING"),
+        query
+            .sorts
+            .as_deref()
+            .filter(|x| !x.is_empty())
+            .map_or_else(String::new, |x| format!(
+                " ORDER BY {}",
+                join(x.iter().map(|x| render(x)), ", ")
+            )),
+        render_limit(limit),
+        query
+            .offset
+            .map_or_else(String::new, |offset| format!(" OFFSET {offset}")),
+    )
+}
+
+fn render_insert(table_name: &str, values: &[(&str, Box<dyn Expression>)]) -> String {
+    let (columns, values) = render_values(values);
+    format!("INSERT INTO {table_name} ({columns}) VALUES ({values})")
+}
+
+fn render_update(statement: &UpdateStatement<'_>, limit: Option<usize>) -> String {
+    format!(
+        "UPDATE {} SET {}{}{}",
+        statement.table_name,
+        join(
+            statement
+                .values
+                .iter()
+                .map(|(name, value)| format!("{name} = {}", render(&**value))),
+            ", "
+        ),
+        render_filters(statement.filters.as_deref(), "WHERE"),
+        render_limit(limit),
+    )
+}
+
+fn render_upsert(statement: &UpsertStatement<'_>, limit: Option<usize>) -> String {
+    let (columns, values) = render_values(&statement.values);
+    format!(
+        "UPSERT INTO {} ({columns}) VALUES ({values}){}{}",
+        statement.table_name,
+        render_filters(statement.filters.as_deref(), "WHERE"),
+        render_limit(limit),
+    )
+}
+
+fn render_upsert_multi(statement: &UpsertMultiStatement<'_>) -> String {
+    let columns = statement.values.first().map_or_else(String::new, |values| {
+        join(values.iter().map(|(name, _)| (*name).to_string()), ", ")
+    });
+    format!(
+        "UPSERT INTO {} ({columns}) VALUES {} ON CONFLICT ({})",
+        statement.table_name,
+        join(
+            statement
+                .values
+                .iter()
+                .map(|values| format!("({})", render_values(values).1)),
+            ", "
+        ),
+        join(
+            statement
+                .unique
+                .as_deref()
+                .unwrap_or_default()
+                .iter()
+                .map(|x| render(&**x)),
+            ", "
+        ),
+    )
+}
+
+fn render_delete(statement: &DeleteStatement<'_>, limit: Option<usize>) -> String {
+    format!(
+        "DELETE FROM {}{}{}",
+        statement.table_name,
+        render_filters(statement.filters.as_deref(), "WHERE"),
+        render_limit(limit),
+    )
+}
+
+fn render_comparison(left: &dyn Expression, operator: &str, right: &dyn Expression) -> String {
+    format!("({} {operator} {})", render(left), render(right))
+}
+
+fn render(expression: &dyn Expression) -> String {
+    match expression.expression_type() {
+        ExpressionType::Eq(value) => render_comparison(
+            &*value.left,
+            if value.right.is_null() { "IS" } else { "=" },
+            &*value.right,
+        ),
+        ExpressionType::NotEq(value) => render_comparison(
+            &*value.left,
+            if value.right.is_null() {
+                "IS NOT"
+            } else {
+                "!="
+            },
+            &*value.right,
+        ),
+        ExpressionType::Gt(value) => render_comparison(&*value.left, ">", &*value.right),
+        ExpressionType::Gte(value) => render_comparison(&*value.left, ">=", &*value.right),
+        ExpressionType::Lt(value) => render_comparison(&*value.left, "<", &*value.right),
+        ExpressionType::Lte(value) => render_comparison(&*value.left, "<=", &*value.right),
+        ExpressionType::Like(value) => render_comparison(
+            &*value.left,
+            if value.case_insensitive {
+                "ILIKE"
+            } else {
+                "LIKE"
+            },
+            &*value.pattern,
+        ),
+        ExpressionType::In(value) => {
+            format!("{} IN ({})", render(&*value.left), render(&*value.values))
+        }
+        ExpressionType::NotIn(value) => {
+            format!(
+                "{} NOT IN ({})",
+                render(&*value.left),
+                render(&*value.values)
+            )
+        }
+        ExpressionType::And(value) => format!(
+            "({})",
+            join(value.conditions.iter().map(|x| render(&**x)), " AND ")
+        ),
+        ExpressionType::Or(value) => format!(
+            "({})",
+            join(value.conditions.iter().map(|x| render(&**x)), " OR ")
+        ),
+        ExpressionType::Join(value) => format!(
+            "{}JOIN {} ON {}",
+            if value.left { "LEFT " } else { "" },
+            value.table_name,
+            value.on
+        ),
+        ExpressionType::Sort(value) => format!(
+            "{} {}",
+            render(&*value.expression),
+            match value.direction {
+                SortDirection::Asc => "ASC",
+                SortDirection::Desc => "DESC",
+            }
+        ),
+        ExpressionType::InList(value) => join(value.values.iter().map(|x| render(&**x)), ", "),
+        ExpressionType::Coalesce(value) => format!(
+            "COALESCE({})",
+            join(value.values.iter().map(|x| render(&**x)), ", ")
+        ),
+        ExpressionType::Aggregate(value) => format!(
+            "{}({}{})",
+            match value.function {
+                AggregateFunction::Count => "COUNT",
+                AggregateFunction::Sum => "SUM",
+                AggregateFunction::Min => "MIN",
+                AggregateFunction::Max => "MAX",
+                AggregateFunction::Avg => "AVG",
+            },
+            if value.distinct { "DISTINCT " } else { "" },
+            value.column.as_ref().map_or("*", |x| x.value.as_str()),
+        ),
+        ExpressionType::Literal(value) => value.value.clone(),
+        ExpressionType::Identifier(value) => value.value.clone(),
+        ExpressionType::SelectQuery(value) => render_select(value, value.limit),
+        ExpressionType::DatabaseValue(value) => match value {
+            DatabaseValue::Null
+            | DatabaseValue::BoolOpt(None)
+            | DatabaseValue::StringOpt(None)
+            | DatabaseValue::NumberOpt(None)
+            | DatabaseValue::UNumberOpt(None)
+            | DatabaseValue::RealOpt(None) => "NULL".to_string(),
+            DatabaseValue::Now => "NOW()".to_string(),
+            DatabaseValue::NowAdd(add) => format!("NOW() + {add}"),
+            _ => "?".to_string(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use pretty_assertions::assert_eq;
+
+    use crate::{
+        query::{count_all, where_in, FilterableQuery as _, SortDirection},
+        Database,
+    };
+
+    use super::*;
+
+    fn rendered(stats: &QueryStats) -> Vec<(String, u64, u64, u64)> {
+        let mut stats = stats
+            .snapshot()
+            .into_iter()
+            .map(|x| (x.statement, x.count, x.params, x.rows))
+            .collect::<Vec<_>>();
+        stats.sort();
+        stats
+    }
+
+    #[test]
+    fn renders_select_with_placeholders() {
+        let query = crate::query::select("tracks")
+            .columns(&["id", "title"])
+            .left_join("albums", "albums.id=tracks.album_id")
+            .where_eq("album_id", 3)
+            .where_not_eq("file", DatabaseValue::Null)
+            .filter(Box::new(where_in("source", vec!["LOCAL", "TIDAL"])))
+            .sort("number", SortDirection::Asc)
+            .limit(10)
+            .offset(20);
+
+        assert_eq!(
+            render_select(&query, query.limit),
+            "SELECT id, title FROM tracks LEFT JOIN albums ON albums.id=tracks.album_id \
+             WHERE (album_id = ?) AND (file IS NOT NULL) AND source IN (?, ?) \
+             ORDER BY number ASC LIMIT 10 OFFSET 20"
+        );
+        assert_eq!(param_count(&query), 3);
+    }
+
+    #[test]
+    fn renders_aggregates() {
+        let query = crate::query::select("tracks")
+            .columns(&["album_id"])
+            .aggregate("tracks", count_all())
+            .group_by("album_id")
+            .having(Box::new(crate::query::where_gt(count_all(), 1)));
+
+        assert_eq!(
+            render_select(&query, None),
+            "SELECT album_id, COUNT(*) AS tracks FROM tracks GROUP BY album_id HAVING (COUNT(*) > ?)"
+        );
+    }
+
+    #[cfg(feature = "sqlite-rusqlite")]
+    #[test_log::test(tokio::test)]
+    async fn records_statement_stats() {
+        let connection = ::rusqlite::Connection::open_in_memory().unwrap();
+        connection
+            .execute_batch(
+                "CREATE TABLE trace_test (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT)",
+            )
+            .unwrap();
+        let stats = Arc::new(QueryStats::default());
+        let db = TracedDatabase::new(Box::new(crate::rusqlite::RusqliteDatabase::new(Arc::new(
+            tokio::sync::Mutex::new(connection),
+        ))))
+        .with_stats(stats.clone());
+        let db: &dyn Database = &db;
+
+        for name in ["a", "b"] {
+            db.insert("trace_test")
+                .value("name", name)
+                .execute(db)
+                .await
+                .unwrap();
+        }
+        db.select("trace_test").execute(db).await.unwrap();
+        db.select("missing_table").execute(db).await.unwrap_err();
+
+        assert_eq!(
+            rendered(&stats),
+            vec![
+                (
+                    "INSERT INTO trace_test (name) VALUES (?)".to_string(),
+                    2,
+                    2,
+                    2
+                ),
+                ("SELECT * FROM missing_table".to_string(), 1, 0, 0),
+                ("SELECT * FROM trace_test".to_string(), 1, 0, 2),
+            ]
+        );
+        assert_eq!(
+            stats
+                .snapshot()
+                .iter()
+                .find(|x| x.statement == "SELECT * FROM missing_table")
+                .unwrap()
+                .errors,
+            1
+        );
+
+        stats.reset();
+        assert_eq!(rendered(&stats), vec![]);
+    }
+}
@@ -30,6 +30,9 @@ pub enum InitDbError {
     #[cfg(feature = "sqlite-rusqlite")]
     #[error(transparent)]
     InitSqlite(#[from] InitSqliteError),
+    #[cfg(feature = "mysql-sqlx")]
+    #[error(transparent)]
+    InitMySql(#[from] InitMySqlError),
     #[cfg(feature = "postgres")]
     #[error(transparent)]
     InitPostgres(#[from] InitPostgresError),
@@ -84,6 +87,11 @@ pub async fn init(
         return Ok(init_postgres_sqlx(creds.ok_or(InitDbError::CredentialsRequired)?).await?);
         #[cfg(not(feature = "postgres-sqlx"))]
         panic!("Invalid database features")
+    } else if cfg!(feature = "mysql-sqlx") {
+        #[cfg(feature = "mysql-sqlx")]
+        return Ok(init_mysql_sqlx(creds.ok_or(InitDbError::CredentialsRequired)?).await?);
+        #[cfg(not(feature = "mysql-sqlx"))]
+        panic!("Invalid database features")
     } else if cfg!(feature = "sqlite-rusqlite") {
         #[cfg(feature = "sqlite-rusqlite")]
         return Ok(init_sqlite(path)?);
@@ -103,20 +111,41 @@ pub async fn init(
 #[derive(Debug, Error)]
 pub enum InitSqliteError {
     #[error(transparent)]
-    Sqlite(#[from] ::rusqlite::Error),
+    Sqlite(#[from] moosicbox_database::rusqlite::RusqliteDatabaseError),
 }
 
+/// Opens the sqlite database with a writer connection and a pool of read
+/// connections.
+///
+/// The pool size and busy timeout can be set with the
+/// `SQLITE_READ_CONNECTIONS` and `SQLITE_BUSY_TIMEOUT_MS` environment
+/// variables.
+///
+/// # Errors
+///
+/// * If the database failed to be opened
 #[cfg(feature = "sqlite-rusqlite")]
 pub fn init_sqlite(db_location: &std::path::Path) -> Result<Box<dyn Database>, InitSqliteError> {
-    let library = ::rusqlite::Connection::open(db_location)?;
-    library
-        .busy_timeout(std::time::Duration::from_millis(10))
-        .expect("Failed to set busy timeout");
-    let library = std::sync::Arc::new(tokio::sync::Mutex::new(library));
-
-    Ok(Box::new(
-        moosicbox_database::rusqlite::RusqliteDatabase::new(library),
-    ))
+    use moosicbox_database::rusqlite::{RusqliteDatabase, RusqlitePoolConfig};
+
+    let mut config = RusqlitePoolConfig::default();
+
+    if let Some(read_connections) = std::env::var("SQLITE_READ_CONNECTIONS")
+        .ok()
+        .and_then(|x| x.parse::<usize>().ok())
+    {
+        config.read_connections = read_connections;
+    }
+    if let Some(busy_timeout) = std::env::var("SQLITE_BUSY_TIMEOUT_MS")
+        .ok()
+        .and_then(|x| x.parse::<u64>().ok())
+    {
+        config.busy_timeout = std::time::Duration::from_millis(busy_timeout);
+    }
+
+    log::debug!("init_sqlite: {config:?}");
+
+    Ok(Box::new(RusqliteDatabase::open(db_location, &config)?))
 }
 
 #[cfg(feature = "postgres")]
@@ -168,6 +197,41 @@ pub async fn init_sqlite_sqlx(
     ))))
 }
 
+#[cfg(feature = "mysql-sqlx")]
+#[derive(Debug, Error)]
+pub enum InitMySqlError {
+    #[error(transparent)]
+    MySqlSqlx(#[from] sqlx::Error),
+}
+
+#[cfg(feature = "mysql-sqlx")]
+#[allow(unused)]
+pub async fn init_mysql_sqlx(creds: Credentials) -> Result<Box<dyn Database>, InitMySqlError> {
+    use std::sync::Arc;
+
+    use moosicbox_database::sqlx::mysql::MySqlSqlxDatabase;
+    use sqlx::mysql::{MySqlConnectOptions, MySqlPoolOptions};
+
+    let connect_options = MySqlConnectOptions::new();
+    let mut connect_options = connect_options
+        .host(&creds.host)
+        .database(&creds.name)
+        .username(&creds.user);
+
+    if let Some(db_password) = &creds.password {
+        connect_options = connect_options.password(db_password);
+    }
+
+    let pool = MySqlPoolOptions::new()
+        .max_connections(5)
+        .connect_with(connect_options)
+        .await?;
+
+    Ok(Box::new(MySqlSqlxDatabase::new(Arc::new(
+        tokio::sync::Mutex::new(pool),
+    ))))
+}
+
 #[cfg(feature = "postgres")]
 #[derive(Debug, Error)]
 pub enum InitDatabaseError {
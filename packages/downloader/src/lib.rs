@@ -634,6 +634,7 @@ async fn download_track_inner(
         &track.id,
         source,
         AudioFormat::Source,
+        Default::default(),
         false,
         start,
         None,
@@ -0,0 +1,239 @@
+use std::sync::LazyLock;
+
+use actix_web::{
+    dev::{ServiceFactory, ServiceRequest},
+    error::{ErrorBadGateway, ErrorInternalServerError, ErrorNotFound},
+    route,
+    web::{self, Json},
+    Result, Scope,
+};
+use moosicbox_core::sqlite::models::Id;
+use moosicbox_database::profiles::LibraryDatabase;
+use serde::Deserialize;
+
+use crate::{
+    models::{AlbumMetadata, ApiEnrichmentCandidate},
+    musicbrainz::MusicBrainzClient,
+    ApplyCandidateError, LookupCandidatesError,
+};
+
+static CLIENT: LazyLock<MusicBrainzClient> = LazyLock::new(MusicBrainzClient::from_env);
+
+pub fn bind_services<
+    T: ServiceFactory<ServiceRequest, Config = (), Error = actix_web::Error, InitError = ()>,
+>(
+    scope: Scope<T>,
+) -> Scope<T> {
+    scope
+        .service(album_metadata_endpoint)
+        .service(lookup_album_candidates_endpoint)
+        .service(album_candidates_endpoint)
+        .service(dismiss_album_candidates_endpoint)
+        .service(apply_album_candidate_endpoint)
+}
+
+#[cfg(feature = "openapi")]
+#[derive(utoipa::OpenApi)]
+#[openapi(
+    tags((name = "Enrichment")),
+    paths(
+        album_metadata_endpoint,
+        lookup_album_candidates_endpoint,
+        album_candidates_endpoint,
+        dismiss_album_candidates_endpoint,
+        apply_album_candidate_endpoint,
+    ),
+    components(schemas(AlbumMetadata, ApiEnrichmentCandidate))
+)]
+pub struct Api;
+
+impl From<LookupCandidatesError> for actix_web::Error {
+    fn from(err: LookupCandidatesError) -> Self {
+        log::error!("{err:?}");
+        match err {
+            LookupCandidatesError::Db(_) => ErrorInternalServerError(err.to_string()),
+            LookupCandidatesError::MusicBrainz(_) => ErrorBadGateway(err.to_string()),
+        }
+    }
+}
+
+impl From<ApplyCandidateError> for actix_web::Error {
+    fn from(err: ApplyCandidateError) -> Self {
+        log::error!("{err:?}");
+        match err {
+            ApplyCandidateError::Db(_) => ErrorInternalServerError(err.to_string()),
+            ApplyCandidateError::CandidateNotFound(_) | ApplyCandidateError::AlbumNotFound(_) => {
+                ErrorNotFound(err.to_string())
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AlbumQuery {
+    album_id: u64,
+}
+
+#[cfg_attr(
+    feature = "openapi", utoipa::path(
+        tags = ["Enrichment"],
+        get,
+        path = "/albums/metadata",
+        description = "Get the MusicBrainz metadata stored on an album",
+        params(
+            ("albumId" = u64, Query, description = "The album ID"),
+        ),
+        responses(
+            (
+                status = 200,
+                description = "The album's metadata",
+                body = AlbumMetadata,
+            )
+        )
+    )
+)]
+#[route("/albums/metadata", method = "GET")]
+pub async fn album_metadata_endpoint(
+    query: web::Query<AlbumQuery>,
+    db: LibraryDatabase,
+) -> Result<Json<AlbumMetadata>> {
+    Ok(Json(
+        crate::db::get_album_metadata(&db, query.album_id)
+            .await
+            .map_err(ErrorInternalServerError)?
+            .ok_or_else(|| ErrorNotFound(format!("Album not found: {}", query.album_id)))?,
+    ))
+}
+
+#[cfg_attr(
+    feature = "openapi", utoipa::path(
+        tags = ["Enrichment"],
+        post,
+        path = "/albums/lookup",
+        description = "Look up the MusicBrainz releases that could match an album by its disc TOC, track durations and tags. The best scoring releases replace the album's pending candidates",
+        params(
+            ("albumId" = u64, Query, description = "The album ID"),
+        ),
+        responses(
+            (
+                status = 200,
+                description = "The candidates, best match first",
+                body = Vec<ApiEnrichmentCandidate>,
+            )
+        )
+    )
+)]
+#[route("/albums/lookup", method = "POST")]
+pub async fn lookup_album_candidates_endpoint(
+    query: web::Query<AlbumQuery>,
+    db: LibraryDatabase,
+) -> Result<Json<Vec<ApiEnrichmentCandidate>>> {
+    let album = moosicbox_library::db::get_album(&db, "id", &Id::Number(query.album_id))
+        .await
+        .map_err(ErrorInternalServerError)?
+        .ok_or_else(|| ErrorNotFound(format!("Album not found: {}", query.album_id)))?;
+
+    Ok(Json(
+        crate::lookup_candidates(&CLIENT, &db, &album)
+            .await?
+            .into_iter()
+            .map(Into::into)
+            .collect(),
+    ))
+}
+
+#[cfg_attr(
+    feature = "openapi", utoipa::path(
+        tags = ["Enrichment"],
+        get,
+        path = "/albums/candidates",
+        description = "Get the pending candidates of an album",
+        params(
+            ("albumId" = u64, Query, description = "The album ID"),
+        ),
+        responses(
+            (
+                status = 200,
+                description = "The candidates, best match first",
+                body = Vec<ApiEnrichmentCandidate>,
+            )
+        )
+    )
+)]
+#[route("/albums/candidates", method = "GET")]
+pub async fn album_candidates_endpoint(
+    query: web::Query<AlbumQuery>,
+    db: LibraryDatabase,
+) -> Result<Json<Vec<ApiEnrichmentCandidate>>> {
+    Ok(Json(
+        crate::db::get_candidates(&db, query.album_id)
+            .await
+            .map_err(ErrorInternalServerError)?
+            .into_iter()
+            .map(Into::into)
+            .collect(),
+    ))
+}
+
+#[cfg_attr(
+    feature = "openapi", utoipa::path(
+        tags = ["Enrichment"],
+        delete,
+        path = "/albums/candidates",
+        description = "Dismiss the pending candidates of an album without applying any of them",
+        params(
+            ("albumId" = u64, Query, description = "The album ID"),
+        ),
+        responses(
+            (
+                status = 200,
+                description = "The candidates were dismissed",
+                body = Value,
+            )
+        )
+    )
+)]
+#[route("/albums/candidates", method = "DELETE")]
+pub async fn dismiss_album_candidates_endpoint(
+    query: web::Query<AlbumQuery>,
+    db: LibraryDatabase,
+) -> Result<Json<serde_json::Value>> {
+    crate::db::delete_candidates(&db, query.album_id)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    Ok(Json(serde_json::json!({"success": true})))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApplyCandidateQuery {
+    candidate_id: u64,
+}
+
+#[cfg_attr(
+    feature = "openapi", utoipa::path(
+        tags = ["Enrichment"],
+        post,
+        path = "/albums/apply",
+        description = "Apply a reviewed candidate: store its MBIDs, release type, label, country and original release date on the album and clear the album's other candidates",
+        params(
+            ("candidateId" = u64, Query, description = "The candidate ID"),
+        ),
+        responses(
+            (
+                status = 200,
+                description = "The album's updated metadata",
+                body = AlbumMetadata,
+            )
+        )
+    )
+)]
+#[route("/albums/apply", method = "POST")]
+pub async fn apply_album_candidate_endpoint(
+    query: web::Query<ApplyCandidateQuery>,
+    db: LibraryDatabase,
+) -> Result<Json<AlbumMetadata>> {
+    Ok(Json(crate::apply_candidate(&db, query.candidate_id).await?))
+}
@@ -0,0 +1,167 @@
+use moosicbox_core::sqlite::db::DbError;
+use moosicbox_database::{
+    profiles::LibraryDatabase,
+    query::{FilterableQuery, SortDirection},
+    DatabaseValue,
+};
+use moosicbox_json_utils::{ParseError, ToValueType};
+
+use crate::{
+    models::{AlbumMetadata, EnrichmentCandidate},
+    musicbrainz::Release,
+};
+
+pub async fn get_candidates(
+    db: &LibraryDatabase,
+    album_id: u64,
+) -> Result<Vec<EnrichmentCandidate>, DbError> {
+    Ok(db
+        .select("album_enrichment_candidates")
+        .where_eq("album_id", album_id)
+        .sort("score", SortDirection::Desc)
+        .execute(db)
+        .await?
+        .to_value_type()?)
+}
+
+pub async fn get_candidate(
+    db: &LibraryDatabase,
+    candidate_id: u64,
+) -> Result<Option<EnrichmentCandidate>, DbError> {
+    Ok(db
+        .select("album_enrichment_candidates")
+        .where_eq("id", candidate_id)
+        .execute_first(db)
+        .await?
+        .as_ref()
+        .to_value_type()?)
+}
+
+pub async fn delete_candidates(db: &LibraryDatabase, album_id: u64) -> Result<(), DbError> {
+    db.delete("album_enrichment_candidates")
+        .where_eq("album_id", album_id)
+        .execute(db)
+        .await?;
+
+    Ok(())
+}
+
+/// Replaces the pending candidates of the album with the given scored
+/// releases.
+pub async fn set_candidates(
+    db: &LibraryDatabase,
+    album_id: u64,
+    releases: &[(Release, f64)],
+) -> Result<Vec<EnrichmentCandidate>, DbError> {
+    delete_candidates(db, album_id).await?;
+
+    for (release, score) in releases {
+        let json = serde_json::to_string(release)
+            .map_err(|e| ParseError::Parse(format!("release: {e:?}")))?;
+
+        db.insert("album_enrichment_candidates")
+            .value("album_id", album_id)
+            .value("release_id", release.id.as_str())
+            .value("score", *score)
+            .value("release", json)
+            .execute(db)
+            .await?;
+    }
+
+    get_candidates(db, album_id).await
+}
+
+pub async fn get_album_metadata(
+    db: &LibraryDatabase,
+    album_id: u64,
+) -> Result<Option<AlbumMetadata>, DbError> {
+    Ok(db
+        .select("albums")
+        .columns(&[
+            "id",
+            "musicbrainz_release_id",
+            "musicbrainz_release_group_id",
+            "release_type",
+            "label",
+            "country",
+            "original_release_date",
+        ])
+        .where_eq("id", album_id)
+        .execute_first(db)
+        .await?
+        .as_ref()
+        .to_value_type()?)
+}
+
+pub async fn set_album_metadata(
+    db: &LibraryDatabase,
+    metadata: &AlbumMetadata,
+    date_released: Option<&str>,
+) -> Result<(), DbError> {
+    let mut values = vec![
+        (
+            "musicbrainz_release_id",
+            DatabaseValue::StringOpt(metadata.musicbrainz_release_id.clone()),
+        ),
+        (
+            "musicbrainz_release_group_id",
+            DatabaseValue::StringOpt(metadata.musicbrainz_release_group_id.clone()),
+        ),
+        (
+            "release_type",
+            DatabaseValue::StringOpt(metadata.release_type.clone()),
+        ),
+        ("label", DatabaseValue::StringOpt(metadata.label.clone())),
+        (
+            "country",
+            DatabaseValue::StringOpt(metadata.country.clone()),
+        ),
+        (
+            "original_release_date",
+            DatabaseValue::StringOpt(metadata.original_release_date.clone()),
+        ),
+    ];
+
+    if let Some(date_released) = date_released {
+        values.push((
+            "date_released",
+            DatabaseValue::String(date_released.to_string()),
+        ));
+    }
+
+    db.update("albums")
+        .where_eq("id", metadata.album_id)
+        .values(values)
+        .execute(db)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn set_track_recording_id(
+    db: &LibraryDatabase,
+    track_id: u64,
+    recording_id: &str,
+) -> Result<(), DbError> {
+    db.update("tracks")
+        .where_eq("id", track_id)
+        .value("musicbrainz_recording_id", recording_id)
+        .execute(db)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn set_artist_musicbrainz_id(
+    db: &LibraryDatabase,
+    artist_id: u64,
+    musicbrainz_id: &str,
+) -> Result<(), DbError> {
+    db.update("artists")
+        .where_eq("id", artist_id)
+        .value("musicbrainz_id", musicbrainz_id)
+        .execute(db)
+        .await?;
+
+    Ok(())
+}
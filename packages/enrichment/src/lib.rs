@@ -0,0 +1,285 @@
+#![cfg_attr(feature = "fail-on-warnings", deny(warnings))]
+
+use std::collections::{HashMap, HashSet};
+
+use models::{AlbumMetadata, EnrichmentCandidate};
+use moosicbox_core::sqlite::{db::DbError, models::Id};
+use moosicbox_database::profiles::LibraryDatabase;
+use moosicbox_library::models::{LibraryAlbum, LibraryTrack};
+use musicbrainz::{escape_query_term, DiscToc, MusicBrainzClient, MusicBrainzError, Release};
+use score::{score_release, AlbumTags};
+use thiserror::Error;
+
+#[cfg(feature = "api")]
+pub mod api;
+
+pub mod db;
+pub mod models;
+pub mod musicbrainz;
+pub mod score;
+
+/// The number of search results to fetch the full release (with the
+/// tracklist) for.
+const MAX_SEARCH_RELEASES: u32 = 5;
+/// The number of candidates kept for review per album.
+const MAX_CANDIDATES: usize = 10;
+
+#[derive(Debug, Error)]
+pub enum LookupCandidatesError {
+    #[error(transparent)]
+    Db(#[from] DbError),
+    #[error(transparent)]
+    MusicBrainz(#[from] MusicBrainzError),
+}
+
+async fn get_album_tracks(
+    db: &LibraryDatabase,
+    album_id: u64,
+) -> Result<Vec<LibraryTrack>, DbError> {
+    let mut ids = HashSet::new();
+    let mut tracks = moosicbox_library::db::get_album_tracks(db, &Id::Number(album_id))
+        .await?
+        .into_iter()
+        .filter(|x| ids.insert(x.id))
+        .collect::<Vec<_>>();
+
+    tracks.sort_by_key(|x| x.number);
+
+    Ok(tracks)
+}
+
+/// A 404 from a lookup just means there is no match.
+fn not_found_as_empty(
+    result: Result<Vec<Release>, MusicBrainzError>,
+) -> Result<Vec<Release>, MusicBrainzError> {
+    match result {
+        Err(MusicBrainzError::Status(404)) => Ok(vec![]),
+        result => result,
+    }
+}
+
+/// Looks up the releases that could match the album and stores the best
+/// scoring ones as candidates for review, replacing any previous candidates.
+///
+/// Releases are looked up by the MusicBrainz release id already stored on
+/// the album, a fuzzy disc TOC built from the track durations and a search
+/// on the album's title and artist tags.
+pub async fn lookup_candidates(
+    client: &MusicBrainzClient,
+    db: &LibraryDatabase,
+    album: &LibraryAlbum,
+) -> Result<Vec<EnrichmentCandidate>, LookupCandidatesError> {
+    let tracks = get_album_tracks(db, album.id).await?;
+    let tags = AlbumTags {
+        title: album.title.clone(),
+        artist: album.artist.clone(),
+        durations: tracks.iter().map(|x| x.duration).collect(),
+    };
+
+    let mut releases: HashMap<String, Release> = HashMap::new();
+
+    if let Some(release_id) = db::get_album_metadata(db, album.id)
+        .await?
+        .and_then(|x| x.musicbrainz_release_id)
+    {
+        let release = client.get_release(&release_id).await?;
+        releases.insert(release.id.clone(), release);
+    }
+
+    if let Some(toc) = DiscToc::from_durations(&tags.durations) {
+        log::debug!("Looking up album_id={} by toc={toc}", album.id);
+
+        for release in not_found_as_empty(client.lookup_toc(&toc).await)? {
+            releases.entry(release.id.clone()).or_insert(release);
+        }
+    }
+
+    let query = format!(
+        "release:\"{}\" AND artist:\"{}\"",
+        escape_query_term(&album.title),
+        escape_query_term(&album.artist)
+    );
+
+    for result in client.search_releases(&query, MAX_SEARCH_RELEASES).await? {
+        if releases.contains_key(&result.id) {
+            continue;
+        }
+
+        let release = client.get_release(&result.id).await?;
+        releases.insert(release.id.clone(), release);
+    }
+
+    let mut scored = releases
+        .into_values()
+        .map(|release| {
+            let score = score_release(&tags, &release);
+            (release, score)
+        })
+        .collect::<Vec<_>>();
+
+    scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+    scored.truncate(MAX_CANDIDATES);
+
+    log::debug!(
+        "Found {} enrichment candidates for album_id={}",
+        scored.len(),
+        album.id
+    );
+
+    Ok(db::set_candidates(db, album.id, &scored).await?)
+}
+
+fn normalize(value: &str) -> String {
+    value.trim().to_lowercase()
+}
+
+/// Maps the library tracks to the recording ids of the release tracks. Tracks
+/// are matched by title first, then by their position on the release when
+/// the track numbers are unique.
+fn match_recordings(tracks: &[LibraryTrack], release: &Release) -> Vec<(u64, String)> {
+    let release_tracks = release.tracks().collect::<Vec<_>>();
+    let numbers_unique = tracks
+        .iter()
+        .map(|x| x.number)
+        .collect::<HashSet<_>>()
+        .len()
+        == tracks.len();
+
+    tracks
+        .iter()
+        .filter_map(|track| {
+            let title = normalize(&track.title);
+
+            release_tracks
+                .iter()
+                .find(|x| normalize(&x.title) == title)
+                .or_else(|| {
+                    numbers_unique
+                        .then(|| {
+                            (track.number as usize)
+                                .checked_sub(1)
+                                .and_then(|i| release_tracks.get(i))
+                        })
+                        .flatten()
+                })
+                .and_then(|x| x.recording.as_ref())
+                .map(|x| (track.id, x.id.clone()))
+        })
+        .collect()
+}
+
+#[derive(Debug, Error)]
+pub enum ApplyCandidateError {
+    #[error(transparent)]
+    Db(#[from] DbError),
+    #[error("Candidate not found: {0}")]
+    CandidateNotFound(u64),
+    #[error("Album not found: {0}")]
+    AlbumNotFound(u64),
+}
+
+/// Stores the metadata of the candidate release on its album, its tracks and
+/// its artist, and clears the album's pending candidates. The album's release
+/// date is only filled in if it doesn't have one yet.
+pub async fn apply_candidate(
+    db: &LibraryDatabase,
+    candidate_id: u64,
+) -> Result<AlbumMetadata, ApplyCandidateError> {
+    let candidate = db::get_candidate(db, candidate_id)
+        .await?
+        .ok_or(ApplyCandidateError::CandidateNotFound(candidate_id))?;
+
+    let album = moosicbox_library::db::get_album(db, "id", &Id::Number(candidate.album_id))
+        .await?
+        .ok_or(ApplyCandidateError::AlbumNotFound(candidate.album_id))?;
+
+    let release = &candidate.release;
+    let metadata = AlbumMetadata {
+        album_id: album.id,
+        musicbrainz_release_id: Some(release.id.clone()),
+        musicbrainz_release_group_id: release.release_group.as_ref().map(|x| x.id.clone()),
+        release_type: release.release_type().map(ToString::to_string),
+        label: release.label().map(ToString::to_string),
+        country: release.country.clone(),
+        original_release_date: release.original_release_date().map(ToString::to_string),
+    };
+
+    let date_released = album
+        .date_released
+        .is_none()
+        .then_some(metadata.original_release_date.as_deref())
+        .flatten();
+
+    db::set_album_metadata(db, &metadata, date_released).await?;
+
+    let tracks = get_album_tracks(db, album.id).await?;
+
+    for (track_id, recording_id) in match_recordings(&tracks, release) {
+        db::set_track_recording_id(db, track_id, &recording_id).await?;
+    }
+
+    if let [credit] = release.artist_credit.as_slice() {
+        if let Some(artist) = &credit.artist {
+            db::set_artist_musicbrainz_id(db, album.artist_id, &artist.id).await?;
+        }
+    }
+
+    db::delete_candidates(db, album.id).await?;
+
+    Ok(metadata)
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use crate::musicbrainz::{Medium, Recording, Track};
+
+    use super::*;
+
+    fn release_track(title: &str, recording_id: &str) -> Track {
+        Track {
+            title: title.to_string(),
+            recording: Some(Recording {
+                id: recording_id.to_string(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn library_track(id: u64, number: u32, title: &str) -> LibraryTrack {
+        LibraryTrack {
+            id,
+            number,
+            title: title.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn match_recordings_matches_by_title_then_position() {
+        let release = Release {
+            media: vec![Medium {
+                tracks: vec![
+                    release_track("Come Together", "a"),
+                    release_track("Something", "b"),
+                    release_track("Maxwell's Silver Hammer", "c"),
+                ],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let matches = match_recordings(
+            &[
+                library_track(10, 1, "come together"),
+                library_track(11, 2, "Track 02"),
+                library_track(12, 5, "Track 05"),
+            ],
+            &release,
+        );
+
+        assert_eq!(matches, vec![(10, "a".to_string()), (11, "b".to_string())]);
+    }
+}
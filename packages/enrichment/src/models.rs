@@ -0,0 +1,100 @@
+use moosicbox_database::Row;
+use moosicbox_json_utils::{database::ToValue as _, MissingValue, ParseError, ToValueType};
+use serde::{Deserialize, Serialize};
+
+use crate::musicbrainz::Release;
+
+/// A release that could match a library album, waiting to be reviewed.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct EnrichmentCandidate {
+    pub id: u64,
+    pub album_id: u64,
+    pub release_id: String,
+    pub score: f64,
+    pub release: Release,
+}
+
+impl MissingValue<EnrichmentCandidate> for &Row {}
+impl ToValueType<EnrichmentCandidate> for &Row {
+    fn to_value_type(self) -> Result<EnrichmentCandidate, ParseError> {
+        let release: String = self.to_value("release")?;
+
+        Ok(EnrichmentCandidate {
+            id: self.to_value("id")?,
+            album_id: self.to_value("album_id")?,
+            release_id: self.to_value("release_id")?,
+            score: self.to_value("score")?,
+            release: serde_json::from_str(&release)
+                .map_err(|e| ParseError::Parse(format!("release: {e:?}")))?,
+        })
+    }
+}
+
+/// The MusicBrainz metadata stored on an album.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AlbumMetadata {
+    pub album_id: u64,
+    pub musicbrainz_release_id: Option<String>,
+    pub musicbrainz_release_group_id: Option<String>,
+    pub release_type: Option<String>,
+    pub label: Option<String>,
+    pub country: Option<String>,
+    pub original_release_date: Option<String>,
+}
+
+impl MissingValue<AlbumMetadata> for &Row {}
+impl ToValueType<AlbumMetadata> for &Row {
+    fn to_value_type(self) -> Result<AlbumMetadata, ParseError> {
+        Ok(AlbumMetadata {
+            album_id: self.to_value("id")?,
+            musicbrainz_release_id: self.to_value("musicbrainz_release_id")?,
+            musicbrainz_release_group_id: self.to_value("musicbrainz_release_group_id")?,
+            release_type: self.to_value("release_type")?,
+            label: self.to_value("label")?,
+            country: self.to_value("country")?,
+            original_release_date: self.to_value("original_release_date")?,
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ApiEnrichmentCandidate {
+    pub candidate_id: u64,
+    pub album_id: u64,
+    pub release_id: String,
+    pub score: f64,
+    pub title: String,
+    pub artist: String,
+    pub date: Option<String>,
+    pub original_release_date: Option<String>,
+    pub country: Option<String>,
+    pub label: Option<String>,
+    pub release_type: Option<String>,
+    pub track_count: u32,
+}
+
+impl From<EnrichmentCandidate> for ApiEnrichmentCandidate {
+    fn from(value: EnrichmentCandidate) -> Self {
+        let release = &value.release;
+
+        Self {
+            candidate_id: value.id,
+            album_id: value.album_id,
+            release_id: value.release_id,
+            score: value.score,
+            title: release.title.clone(),
+            artist: release.artist(),
+            date: release.date.clone(),
+            original_release_date: release.original_release_date().map(ToString::to_string),
+            country: release.country.clone(),
+            label: release.label().map(ToString::to_string),
+            release_type: release.release_type().map(ToString::to_string),
+            track_count: release.track_count(),
+        }
+    }
+}
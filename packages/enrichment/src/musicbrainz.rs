@@ -0,0 +1,583 @@
+use std::{sync::LazyLock, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::{sync::Mutex, time::Instant};
+
+pub const DEFAULT_MUSICBRAINZ_API_URL: &str = "https://musicbrainz.org/ws/2";
+
+/// MusicBrainz requires every client to identify itself with a meaningful
+/// user agent, otherwise the requests get rate limited much more aggressively.
+static USER_AGENT: &str = concat!(
+    "MoosicBox/",
+    env!("CARGO_PKG_VERSION"),
+    " ( https://github.com/MoosicBox/MoosicBox )"
+);
+
+static RELEASE_INCLUDES: &str = "artist-credits+labels+recordings+release-groups";
+
+/// MusicBrainz allows an average of one request per second per client, and
+/// answers with a 503 to anything faster.
+const MIN_REQUEST_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How many times a request that got a 503 is retried before giving up.
+const MAX_RETRIES: u32 = 3;
+
+/// When the next request is allowed to be sent. Shared by every client since
+/// the rate limit applies to the whole server rather than to each lookup.
+static NEXT_REQUEST_AT: LazyLock<Mutex<Option<Instant>>> = LazyLock::new(|| Mutex::new(None));
+
+/// Waits until a request is allowed by the rate limit, and reserves the next
+/// slot for it. Requests made concurrently are sent one at a time.
+async fn throttle() {
+    let mut next_request_at = NEXT_REQUEST_AT.lock().await;
+
+    if let Some(at) = *next_request_at {
+        tokio::time::sleep_until(at).await;
+    }
+
+    *next_request_at = Some(Instant::now() + MIN_REQUEST_INTERVAL);
+}
+
+/// Holds off every request until at least `delay` from now.
+async fn back_off(delay: Duration) {
+    let mut next_request_at = NEXT_REQUEST_AT.lock().await;
+    let at = Instant::now() + delay;
+
+    *next_request_at = Some(next_request_at.map_or(at, |x| x.max(at)));
+}
+
+/// The delay before retrying a rate limited request: the `Retry-After` of the
+/// response if it has one, doubling from [`MIN_REQUEST_INTERVAL`] otherwise.
+fn retry_delay(retry_after: Option<&reqwest::header::HeaderValue>, attempt: u32) -> Duration {
+    retry_after
+        .and_then(|x| x.to_str().ok())
+        .and_then(|x| x.trim().parse::<u64>().ok())
+        .map_or_else(
+            || MIN_REQUEST_INTERVAL * 2_u32.pow(attempt),
+            Duration::from_secs,
+        )
+}
+
+#[derive(Debug, Error)]
+pub enum MusicBrainzError {
+    #[error(transparent)]
+    Reqwest(#[from] reqwest::Error),
+    #[error("Request failed with status {0}")]
+    Status(u16),
+}
+
+/// A minimal client for the MusicBrainz web service (or any service exposing
+/// a compatible `/ws/2` API).
+#[derive(Debug, Clone)]
+pub struct MusicBrainzClient {
+    base_url: String,
+    client: reqwest::Client,
+}
+
+impl Default for MusicBrainzClient {
+    fn default() -> Self {
+        Self::new(DEFAULT_MUSICBRAINZ_API_URL)
+    }
+}
+
+impl MusicBrainzClient {
+    #[must_use]
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            client: reqwest::Client::builder()
+                .user_agent(USER_AGENT)
+                .build()
+                .expect("Failed to build MusicBrainz client"),
+        }
+    }
+
+    /// Uses the `MUSICBRAINZ_API_URL` environment variable as the base url,
+    /// falling back to [`DEFAULT_MUSICBRAINZ_API_URL`].
+    #[must_use]
+    pub fn from_env() -> Self {
+        std::env::var("MUSICBRAINZ_API_URL")
+            .ok()
+            .filter(|x| !x.is_empty())
+            .map_or_else(Self::default, Self::new)
+    }
+
+    #[must_use]
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    async fn get<T: for<'de> Deserialize<'de>>(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+    ) -> Result<T, MusicBrainzError> {
+        let url = format!("{}{path}", self.base_url);
+
+        let mut attempt = 0;
+
+        let response = loop {
+            throttle().await;
+
+            log::debug!("Requesting MusicBrainz url={url} query={query:?} attempt={attempt}");
+
+            let response = self
+                .client
+                .get(&url)
+                .query(query)
+                .query(&[("fmt", "json")])
+                .send()
+                .await?;
+
+            if response.status() != reqwest::StatusCode::SERVICE_UNAVAILABLE
+                || attempt >= MAX_RETRIES
+            {
+                break response;
+            }
+
+            let delay = retry_delay(
+                response.headers().get(reqwest::header::RETRY_AFTER),
+                attempt,
+            );
+            attempt += 1;
+
+            log::debug!("MusicBrainz rate limited the request, retrying in {delay:?}");
+
+            back_off(delay).await;
+        };
+
+        let status = response.status();
+
+        if !status.is_success() {
+            return Err(MusicBrainzError::Status(status.as_u16()));
+        }
+
+        Ok(response.json().await?)
+    }
+
+    /// Fuzzy lookup of the releases matching the disc TOC.
+    pub async fn lookup_toc(&self, toc: &DiscToc) -> Result<Vec<Release>, MusicBrainzError> {
+        let response: ReleaseList = self
+            .get(
+                "/discid/-",
+                &[
+                    ("toc", &toc.to_string()),
+                    ("cdstubs", "no"),
+                    ("inc", RELEASE_INCLUDES),
+                ],
+            )
+            .await?;
+
+        Ok(response.releases)
+    }
+
+    /// Searches the releases with a Lucene query, e.g.
+    /// `release:"Abbey Road" AND artist:"The Beatles"`. The search results
+    /// don't include the tracklists, use [`Self::get_release`] for those.
+    pub async fn search_releases(
+        &self,
+        query: &str,
+        limit: u32,
+    ) -> Result<Vec<Release>, MusicBrainzError> {
+        let response: ReleaseList = self
+            .get(
+                "/release",
+                &[("query", query), ("limit", &limit.to_string())],
+            )
+            .await?;
+
+        Ok(response.releases)
+    }
+
+    pub async fn get_release(&self, id: &str) -> Result<Release, MusicBrainzError> {
+        self.get(&format!("/release/{id}"), &[("inc", RELEASE_INCLUDES)])
+            .await
+    }
+}
+
+/// Escapes the Lucene special characters in a search term.
+#[must_use]
+pub fn escape_query_term(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+
+    for c in value.chars() {
+        if matches!(
+            c,
+            '+' | '-'
+                | '&'
+                | '|'
+                | '!'
+                | '('
+                | ')'
+                | '{'
+                | '}'
+                | '['
+                | ']'
+                | '^'
+                | '"'
+                | '~'
+                | '*'
+                | '?'
+                | ':'
+                | '\\'
+                | '/'
+        ) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+/// The table of contents of an audio CD, in CD frames (1/75th of a second).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiscToc {
+    pub first_track: u32,
+    pub lead_out: u32,
+    pub offsets: Vec<u32>,
+}
+
+/// The lead-in of a CD. The first track starts 2 seconds into the disc.
+const LEAD_IN_FRAMES: u32 = 150;
+const FRAMES_PER_SECOND: f64 = 75.0;
+
+impl DiscToc {
+    /// Builds an approximate TOC from the track durations of a rip. Fuzzy
+    /// TOC lookups tolerate the small differences from the real disc caused
+    /// by gaps and rounding.
+    #[must_use]
+    pub fn from_durations(durations: &[f64]) -> Option<Self> {
+        if durations.is_empty() || durations.len() > 99 {
+            return None;
+        }
+
+        let mut offsets = Vec::with_capacity(durations.len());
+        let mut offset = LEAD_IN_FRAMES;
+
+        for duration in durations {
+            offsets.push(offset);
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            let frames = (duration.max(0.0) * FRAMES_PER_SECOND).round() as u32;
+            offset += frames;
+        }
+
+        Some(Self {
+            first_track: 1,
+            lead_out: offset,
+            offsets,
+        })
+    }
+
+    #[must_use]
+    pub fn last_track(&self) -> u32 {
+        self.first_track + self.offsets.len() as u32 - 1
+    }
+}
+
+impl std::fmt::Display for DiscToc {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {}",
+            self.first_track,
+            self.last_track(),
+            self.lead_out
+        )?;
+
+        for offset in &self.offsets {
+            write!(f, " {offset}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct ReleaseList {
+    #[serde(default)]
+    pub releases: Vec<Release>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct Release {
+    pub id: String,
+    pub title: String,
+    pub status: Option<String>,
+    pub date: Option<String>,
+    pub country: Option<String>,
+    #[serde(default)]
+    pub artist_credit: Vec<ArtistCredit>,
+    pub release_group: Option<ReleaseGroup>,
+    #[serde(default)]
+    pub label_info: Vec<LabelInfo>,
+    #[serde(default)]
+    pub media: Vec<Medium>,
+    /// The relevance of a search result (0-100). Only set on search results.
+    pub score: Option<u8>,
+}
+
+impl Release {
+    /// The full credited artist name, e.g. `Simon & Garfunkel`.
+    #[must_use]
+    pub fn artist(&self) -> String {
+        self.artist_credit
+            .iter()
+            .map(|x| format!("{}{}", x.name, x.joinphrase.as_deref().unwrap_or_default()))
+            .collect()
+    }
+
+    #[must_use]
+    pub fn label(&self) -> Option<&str> {
+        self.label_info
+            .iter()
+            .find_map(|x| x.label.as_ref().map(|x| x.name.as_str()))
+    }
+
+    #[must_use]
+    pub fn release_type(&self) -> Option<&str> {
+        self.release_group
+            .as_ref()
+            .and_then(|x| x.primary_type.as_deref())
+    }
+
+    #[must_use]
+    pub fn original_release_date(&self) -> Option<&str> {
+        self.release_group
+            .as_ref()
+            .and_then(|x| x.first_release_date.as_deref())
+            .filter(|x| !x.is_empty())
+            .or(self.date.as_deref())
+    }
+
+    #[must_use]
+    pub fn track_count(&self) -> u32 {
+        self.media
+            .iter()
+            .map(|x| x.track_count.unwrap_or(x.tracks.len() as u32))
+            .sum()
+    }
+
+    /// The tracks of all the media in order.
+    pub fn tracks(&self) -> impl Iterator<Item = &Track> {
+        self.media.iter().flat_map(|x| x.tracks.iter())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct ArtistCredit {
+    pub name: String,
+    pub joinphrase: Option<String>,
+    pub artist: Option<Artist>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct Artist {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct ReleaseGroup {
+    pub id: String,
+    pub primary_type: Option<String>,
+    pub first_release_date: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct LabelInfo {
+    pub catalog_number: Option<String>,
+    pub label: Option<Label>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct Label {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct Medium {
+    pub position: Option<u32>,
+    pub format: Option<String>,
+    pub track_count: Option<u32>,
+    #[serde(default)]
+    pub tracks: Vec<Track>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct Track {
+    pub id: String,
+    pub position: Option<u32>,
+    pub title: String,
+    /// The length of the track in milliseconds
+    pub length: Option<u64>,
+    pub recording: Option<Recording>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct Recording {
+    pub id: String,
+    pub title: String,
+    pub length: Option<u64>,
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        io::{Read as _, Write as _},
+        net::TcpListener,
+    };
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    /// Serves a single request with the given JSON body and returns the
+    /// received request line.
+    fn stand_in(body: &'static str) -> (String, std::thread::JoinHandle<String>) {
+        let (url, handle) = stand_in_responses(vec![("200 OK", body)]);
+
+        (
+            url,
+            std::thread::spawn(move || handle.join().unwrap().remove(0)),
+        )
+    }
+
+    /// Serves a request for each of the given statuses and JSON bodies, in
+    /// order, and returns the received request lines.
+    fn stand_in_responses(
+        responses: Vec<(&'static str, &'static str)>,
+    ) -> (String, std::thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/ws/2", listener.local_addr().unwrap());
+
+        let handle = std::thread::spawn(move || {
+            responses
+                .into_iter()
+                .map(|(status, body)| {
+                    let (mut stream, _) = listener.accept().unwrap();
+                    let mut buf = [0_u8; 4096];
+                    let len = stream.read(&mut buf).unwrap();
+                    let request = String::from_utf8_lossy(&buf[..len]).to_string();
+
+                    write!(
+                        stream,
+                        "HTTP/1.1 {status}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nretry-after: 0\r\nconnection: close\r\n\r\n{body}",
+                        body.len()
+                    )
+                    .unwrap();
+
+                    request.lines().next().unwrap_or_default().to_string()
+                })
+                .collect()
+        });
+
+        (url, handle)
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn client_searches_releases_on_the_configured_base_url() {
+        let (url, handle) = stand_in(
+            r#"{"count": 1, "offset": 0, "releases": [{"id": "a", "title": "Bookends", "score": 100}]}"#,
+        );
+
+        let releases = MusicBrainzClient::new(url)
+            .search_releases("release:Bookends", 5)
+            .await
+            .unwrap();
+        let request = handle.join().unwrap();
+
+        assert_eq!(releases.len(), 1);
+        assert_eq!(releases[0].title, "Bookends");
+        assert_eq!(releases[0].score, Some(100));
+        assert!(
+            request.starts_with("GET /ws/2/release?query=release%3ABookends&limit=5&fmt=json "),
+            "request={request}"
+        );
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn client_retries_rate_limited_requests() {
+        let (url, handle) = stand_in_responses(vec![
+            ("503 Service Unavailable", "{}"),
+            ("200 OK", r#"{"id": "a", "title": "Bookends"}"#),
+        ]);
+
+        let release = MusicBrainzClient::new(url).get_release("a").await.unwrap();
+        let requests = handle.join().unwrap();
+
+        assert_eq!(release.title, "Bookends");
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0], requests[1]);
+    }
+
+    #[test]
+    fn retry_delay_prefers_the_retry_after_header() {
+        let retry_after = reqwest::header::HeaderValue::from_static("5");
+
+        assert_eq!(retry_delay(Some(&retry_after), 0), Duration::from_secs(5));
+        assert_eq!(retry_delay(None, 0), MIN_REQUEST_INTERVAL);
+        assert_eq!(retry_delay(None, 2), MIN_REQUEST_INTERVAL * 4);
+    }
+
+    #[test]
+    fn disc_toc_is_built_from_the_track_durations() {
+        let toc = DiscToc::from_durations(&[180.0, 200.5, 60.0]).unwrap();
+
+        assert_eq!(toc.to_string(), "1 3 33188 150 13650 28688");
+    }
+
+    #[test]
+    fn escape_query_term_escapes_lucene_special_characters() {
+        assert_eq!(
+            escape_query_term("AC/DC: \"Live\""),
+            "AC\\/DC\\: \\\"Live\\\""
+        );
+    }
+
+    #[test]
+    fn release_is_parsed_from_the_web_service_json() {
+        let release: Release = serde_json::from_str(
+            r#"{
+                "id": "b84ee12a-09ef-421b-82de-0441a926375b",
+                "title": "Bookends",
+                "status": "Official",
+                "date": "1968-04-03",
+                "country": "US",
+                "artist-credit": [
+                    {"name": "Simon", "joinphrase": " & ", "artist": {"id": "a", "name": "Paul Simon"}},
+                    {"name": "Garfunkel", "joinphrase": "", "artist": {"id": "b", "name": "Art Garfunkel"}}
+                ],
+                "release-group": {
+                    "id": "c",
+                    "primary-type": "Album",
+                    "first-release-date": "1968-04-03"
+                },
+                "label-info": [{"catalog-number": "KCS 9529", "label": {"id": "d", "name": "Columbia"}}],
+                "media": [{
+                    "position": 1,
+                    "format": "12\" Vinyl",
+                    "track-count": 2,
+                    "tracks": [
+                        {"id": "e", "position": 1, "title": "Bookends Theme", "length": 32000, "recording": {"id": "f", "title": "Bookends Theme", "length": 32000}},
+                        {"id": "g", "position": 2, "title": "Save the Life of My Child", "length": 169000}
+                    ]
+                }]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(release.artist(), "Simon & Garfunkel");
+        assert_eq!(release.label(), Some("Columbia"));
+        assert_eq!(release.release_type(), Some("Album"));
+        assert_eq!(release.original_release_date(), Some("1968-04-03"));
+        assert_eq!(release.track_count(), 2);
+        assert_eq!(release.tracks().count(), 2);
+    }
+}
@@ -0,0 +1,218 @@
+use std::collections::HashSet;
+
+use crate::musicbrainz::Release;
+
+/// The tags of a library album used to score the candidate releases.
+#[derive(Debug, Clone, Default)]
+pub struct AlbumTags {
+    pub title: String,
+    pub artist: String,
+    /// The durations of the album's tracks in seconds, in track order
+    pub durations: Vec<f64>,
+}
+
+/// Track durations within this many seconds of the release's are considered
+/// a perfect match.
+const DURATION_TOLERANCE: f64 = 3.0;
+/// Track durations this many seconds or more off don't count as a match at
+/// all.
+const DURATION_MAX_DIFFERENCE: f64 = 30.0;
+
+const TITLE_WEIGHT: f64 = 0.3;
+const ARTIST_WEIGHT: f64 = 0.2;
+const TRACK_COUNT_WEIGHT: f64 = 0.2;
+const DURATIONS_WEIGHT: f64 = 0.3;
+
+fn tokens(value: &str) -> HashSet<String> {
+    value
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|x| !x.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// The Jaccard similarity of the words in both strings, from 0.0 to 1.0.
+#[must_use]
+pub fn text_similarity(a: &str, b: &str) -> f64 {
+    let a = tokens(a);
+    let b = tokens(b);
+
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+
+    let intersection = a.intersection(&b).count();
+    let union = a.union(&b).count();
+
+    intersection as f64 / union as f64
+}
+
+fn track_count_similarity(expected: usize, actual: u32) -> f64 {
+    if expected == 0 {
+        return 0.0;
+    }
+
+    let difference = (expected as f64 - f64::from(actual)).abs();
+
+    (1.0 - difference / expected as f64).max(0.0)
+}
+
+fn duration_similarity(expected: f64, actual: f64) -> f64 {
+    let difference = (expected - actual).abs();
+
+    if difference <= DURATION_TOLERANCE {
+        1.0
+    } else if difference >= DURATION_MAX_DIFFERENCE {
+        0.0
+    } else {
+        1.0 - (difference - DURATION_TOLERANCE) / (DURATION_MAX_DIFFERENCE - DURATION_TOLERANCE)
+    }
+}
+
+/// The average similarity of the track durations, or `None` if the release
+/// has no track lengths to compare against.
+fn durations_similarity(durations: &[f64], release: &Release) -> Option<f64> {
+    let lengths = release
+        .tracks()
+        .map(|x| x.length.map(|x| x as f64 / 1000.0))
+        .collect::<Vec<_>>();
+
+    if durations.is_empty() || lengths.iter().all(Option::is_none) {
+        return None;
+    }
+
+    let total = durations
+        .iter()
+        .enumerate()
+        .map(|(i, duration)| {
+            lengths
+                .get(i)
+                .copied()
+                .flatten()
+                .map_or(0.0, |length| duration_similarity(*duration, length))
+        })
+        .sum::<f64>();
+
+    Some(total / durations.len().max(lengths.len()) as f64)
+}
+
+/// Scores how likely the release is to be the album, from 0.0 to 1.0.
+///
+/// The title, artist, track count and track durations are compared. When
+/// the release doesn't include the track lengths (e.g. search results) the
+/// score is based on the other criteria only.
+#[must_use]
+pub fn score_release(album: &AlbumTags, release: &Release) -> f64 {
+    let mut components = vec![
+        (TITLE_WEIGHT, text_similarity(&album.title, &release.title)),
+        (
+            ARTIST_WEIGHT,
+            text_similarity(&album.artist, &release.artist()),
+        ),
+        (
+            TRACK_COUNT_WEIGHT,
+            track_count_similarity(album.durations.len(), release.track_count()),
+        ),
+    ];
+
+    if let Some(similarity) = durations_similarity(&album.durations, release) {
+        components.push((DURATIONS_WEIGHT, similarity));
+    }
+
+    let weights = components.iter().map(|(weight, _)| weight).sum::<f64>();
+
+    components
+        .iter()
+        .map(|(weight, similarity)| weight * similarity)
+        .sum::<f64>()
+        / weights
+}
+
+#[cfg(test)]
+mod test {
+    use crate::musicbrainz::{ArtistCredit, Medium, Track};
+
+    use super::*;
+
+    fn release(title: &str, artist: &str, lengths: &[Option<u64>]) -> Release {
+        Release {
+            id: title.to_string(),
+            title: title.to_string(),
+            artist_credit: vec![ArtistCredit {
+                name: artist.to_string(),
+                ..Default::default()
+            }],
+            media: vec![Medium {
+                track_count: Some(lengths.len() as u32),
+                tracks: lengths
+                    .iter()
+                    .map(|length| Track {
+                        length: *length,
+                        ..Default::default()
+                    })
+                    .collect(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn album(durations: &[f64]) -> AlbumTags {
+        AlbumTags {
+            title: "Abbey Road".to_string(),
+            artist: "The Beatles".to_string(),
+            durations: durations.to_vec(),
+        }
+    }
+
+    #[test]
+    fn score_release_is_perfect_for_an_exact_match() {
+        let score = score_release(
+            &album(&[259.0, 182.5]),
+            &release("Abbey Road", "The Beatles", &[Some(259_000), Some(184_000)]),
+        );
+
+        assert!((score - 1.0).abs() < f64::EPSILON, "score={score}");
+    }
+
+    #[test]
+    fn score_release_prefers_the_release_with_matching_durations() {
+        let album = album(&[259.0, 182.5]);
+        let matching = score_release(
+            &album,
+            &release("Abbey Road", "The Beatles", &[Some(259_000), Some(182_000)]),
+        );
+        let other = score_release(
+            &album,
+            &release("Abbey Road", "The Beatles", &[Some(300_000), Some(120_000)]),
+        );
+
+        assert!(matching > other, "matching={matching} other={other}");
+    }
+
+    #[test]
+    fn score_release_ignores_missing_track_lengths() {
+        let score = score_release(
+            &album(&[259.0, 182.5]),
+            &release("Abbey Road", "The Beatles", &[None, None]),
+        );
+
+        assert!((score - 1.0).abs() < f64::EPSILON, "score={score}");
+    }
+
+    #[test]
+    fn score_release_penalizes_different_track_counts() {
+        let score = score_release(
+            &album(&[259.0, 182.5]),
+            &release("Abbey Road", "The Beatles", &[None, None, None, None]),
+        );
+
+        assert!(score < 0.9, "score={score}");
+    }
+
+    #[test]
+    fn text_similarity_ignores_case_and_punctuation() {
+        assert!((text_similarity("Abbey Road", "abbey road!") - 1.0).abs() < f64::EPSILON);
+        assert!((text_similarity("Abbey Road", "Let It Be")).abs() < f64::EPSILON);
+    }
+}
@@ -3,9 +3,10 @@ use actix_web::{
     error::{ErrorBadRequest, ErrorInternalServerError, ErrorNotFound},
     route,
     web::{self, Json},
-    HttpRequest, HttpResponse, Result, Scope,
+    FromRequest as _, HttpRequest, HttpResponse, Result, Scope,
 };
 use futures::StreamExt;
+use moosicbox_audio_encoder::EncoderSettings;
 use moosicbox_core::{
     integer_range::{
         parse_id_ranges, parse_integer_ranges_to_ids, ParseIdsError, ParseIntegersError,
@@ -13,22 +14,49 @@ use moosicbox_core::{
     sqlite::models::{ApiSource, Id, IdType},
     types::AudioFormat,
 };
-use moosicbox_database::profiles::LibraryDatabase;
+use moosicbox_database::{
+    config::ConfigDatabase,
+    profiles::{api::ProfileName, LibraryDatabase},
+};
 use moosicbox_music_api::{
     ImageCoverSize, MusicApis, SourceToMusicApi as _, TrackAudioQuality, TrackSource,
 };
 use serde::Deserialize;
 use thiserror::Error;
 
+#[cfg(feature = "analysis")]
+use crate::files::analysis::{
+    get_album_analysis, get_or_init_analysis, AlbumAnalysis, AnalysisError,
+};
+#[cfg(feature = "archive")]
+use crate::files::archive::{get_album_archive, ArchiveError, ArchiveFormat, ArchivePlaylist};
+#[cfg(feature = "transcode-cache")]
+use crate::files::transcode_cache::{
+    self, PreTranscodeError, TranscodeCacheError, TranscodeCachePurge, TranscodeCacheStats,
+    TRANSCODE_CACHE,
+};
 use crate::files::{
     album::{get_album_cover, AlbumCoverError},
     artist::{get_artist_cover, ArtistCoverError},
+    quality_profile::{
+        delete_quality_profile, get_quality_profile, get_quality_profiles, upsert_quality_profile,
+        QualityProfile, QualityProfileError,
+    },
     track::{
         audio_format_to_content_type, get_or_init_track_visualization, get_silence_bytes,
         get_track_bytes, get_track_id_source, get_track_info, get_tracks_info,
-        track_source_to_content_type, GetSilenceBytesError, GetTrackBytesError, TrackInfo,
-        TrackInfoError, TrackSourceError,
+        track_source_to_content_type, validate_encoder_settings, GetSilenceBytesError,
+        GetTrackBytesError, TrackInfo, TrackInfoError, TrackSourceError,
     },
+    waveform::{
+        get_or_init_waveform, AudiowaveformJson, WaveformError, WaveformFormat,
+        BASE_SAMPLES_PER_PIXEL,
+    },
+};
+#[cfg(feature = "hls")]
+use crate::files::{
+    hls::{self, HlsError},
+    seekable::{seekable_bitrate, SeekableLayout},
 };
 
 pub fn bind_services<
@@ -36,17 +64,48 @@ pub fn bind_services<
 >(
     scope: Scope<T>,
 ) -> Scope<T> {
-    scope
+    let scope = scope
         .service(get_silence_endpoint)
         .service(track_endpoint)
         .service(track_visualization_endpoint)
+        .service(track_waveform_endpoint)
         .service(track_info_endpoint)
         .service(tracks_info_endpoint)
         .service(track_urls_endpoint)
         .service(artist_source_artwork_endpoint)
         .service(artist_cover_endpoint)
-        .service(album_source_artwork_endpoint)
+        .service(album_source_artwork_endpoint);
+
+    // Registered before the album artwork endpoint so `archive` and
+    // `analysis` aren't matched as image sizes
+    #[cfg(feature = "archive")]
+    let scope = scope.service(album_archive_endpoint);
+
+    #[cfg(feature = "analysis")]
+    let scope = scope.service(album_analysis_endpoint);
+
+    #[cfg(all(feature = "analysis", feature = "image"))]
+    let scope = scope.service(track_spectrogram_endpoint);
+
+    let scope = scope
         .service(album_artwork_endpoint)
+        .service(quality_profiles_endpoint)
+        .service(upsert_quality_profile_endpoint)
+        .service(delete_quality_profile_endpoint);
+
+    #[cfg(feature = "hls")]
+    let scope = scope
+        .service(track_hls_master_playlist_endpoint)
+        .service(track_hls_media_playlist_endpoint)
+        .service(track_hls_segment_endpoint);
+
+    #[cfg(feature = "transcode-cache")]
+    let scope = scope
+        .service(transcode_cache_stats_endpoint)
+        .service(purge_transcode_cache_endpoint)
+        .service(pre_transcode_endpoint);
+
+    scope
 }
 
 #[cfg(feature = "openapi")]
@@ -55,6 +114,7 @@ pub fn bind_services<
     tags((name = "Files")),
     paths(
         track_visualization_endpoint,
+        track_waveform_endpoint,
         get_silence_endpoint,
         track_endpoint,
         track_info_endpoint,
@@ -63,21 +123,124 @@ pub fn bind_services<
         artist_source_artwork_endpoint,
         album_artwork_endpoint,
         album_source_artwork_endpoint,
+        quality_profiles_endpoint,
+        upsert_quality_profile_endpoint,
+        delete_quality_profile_endpoint,
     ),
     components(schemas(
         GetTrackVisualizationQuery,
+        GetTrackWaveformQuery,
+        WaveformFormat,
+        AudiowaveformJson,
         GetTrackQuery,
         GetTrackInfoQuery,
         GetTracksInfoQuery,
         ArtistCoverQuery,
         AlbumCoverQuery,
+        DeleteQualityProfileQuery,
         TrackInfo,
+        QualityProfile,
         AudioFormat,
         ApiSource,
     ))
 )]
+struct FilesApi;
+
+#[cfg(all(feature = "openapi", feature = "hls"))]
+#[derive(utoipa::OpenApi)]
+#[openapi(
+    tags((name = "Files")),
+    paths(
+        track_hls_master_playlist_endpoint,
+        track_hls_media_playlist_endpoint,
+        track_hls_segment_endpoint,
+    ),
+    components(schemas(
+        GetTrackHlsQuery,
+        GetTrackHlsMediaQuery,
+        GetTrackHlsSegmentQuery,
+    ))
+)]
+struct HlsApi;
+
+#[cfg(all(feature = "openapi", feature = "archive"))]
+#[derive(utoipa::OpenApi)]
+#[openapi(
+    tags((name = "Files")),
+    paths(album_archive_endpoint),
+    components(schemas(AlbumArchiveQuery, ArchiveFormat, ArchivePlaylist))
+)]
+struct ArchiveApi;
+
+#[cfg(all(feature = "openapi", feature = "analysis"))]
+#[derive(utoipa::OpenApi)]
+#[openapi(
+    tags((name = "Files")),
+    paths(album_analysis_endpoint),
+    components(schemas(
+        AlbumAnalysisQuery,
+        AlbumAnalysis,
+        crate::files::analysis::AlbumTrackAnalysis,
+        crate::files::analysis::TrackAnalysis,
+        crate::files::analysis::AnalysisFlag,
+    ))
+)]
+struct AnalysisApi;
+
+#[cfg(all(feature = "openapi", feature = "analysis", feature = "image"))]
+#[derive(utoipa::OpenApi)]
+#[openapi(
+    tags((name = "Files")),
+    paths(track_spectrogram_endpoint),
+    components(schemas(GetTrackSpectrogramQuery))
+)]
+struct SpectrogramApi;
+
+#[cfg(all(feature = "openapi", feature = "transcode-cache"))]
+#[derive(utoipa::OpenApi)]
+#[openapi(
+    tags((name = "Files")),
+    paths(
+        transcode_cache_stats_endpoint,
+        purge_transcode_cache_endpoint,
+        pre_transcode_endpoint,
+    ),
+    components(schemas(
+        PreTranscodeQuery,
+        TranscodeCacheStats,
+        TranscodeCachePurge,
+    ))
+)]
+struct TranscodeCacheApi;
+
+#[cfg(feature = "openapi")]
 pub struct Api;
 
+#[cfg(feature = "openapi")]
+impl utoipa::OpenApi for Api {
+    fn openapi() -> utoipa::openapi::OpenApi {
+        #[allow(unused_mut)]
+        let mut api = <FilesApi as utoipa::OpenApi>::openapi();
+
+        #[cfg(feature = "hls")]
+        api.merge(<HlsApi as utoipa::OpenApi>::openapi());
+
+        #[cfg(feature = "archive")]
+        api.merge(<ArchiveApi as utoipa::OpenApi>::openapi());
+
+        #[cfg(feature = "analysis")]
+        api.merge(<AnalysisApi as utoipa::OpenApi>::openapi());
+
+        #[cfg(all(feature = "analysis", feature = "image"))]
+        api.merge(<SpectrogramApi as utoipa::OpenApi>::openapi());
+
+        #[cfg(feature = "transcode-cache")]
+        api.merge(<TranscodeCacheApi as utoipa::OpenApi>::openapi());
+
+        api
+    }
+}
+
 impl From<TrackSourceError> for actix_web::Error {
     fn from(e: TrackSourceError) -> Self {
         match e {
@@ -96,6 +259,21 @@ impl From<TrackInfoError> for actix_web::Error {
     }
 }
 
+#[cfg(feature = "hls")]
+impl From<HlsError> for actix_web::Error {
+    fn from(e: HlsError) -> Self {
+        match e {
+            HlsError::UnsupportedFormat(_) | HlsError::UnsupportedBitrate(_) => {
+                ErrorBadRequest(e.to_string())
+            }
+            HlsError::SegmentNotFound(_) => ErrorNotFound(e.to_string()),
+            HlsError::NoAudio | HlsError::Encode(_) | HlsError::Decode(_) => {
+                ErrorInternalServerError(e.to_string())
+            }
+        }
+    }
+}
+
 #[derive(Deserialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
 #[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
@@ -146,6 +324,109 @@ pub async fn track_visualization_endpoint(
     ))
 }
 
+impl From<WaveformError> for actix_web::Error {
+    fn from(e: WaveformError) -> Self {
+        match e {
+            WaveformError::InvalidWindow(..) | WaveformError::UnsupportedBits(_) => {
+                ErrorBadRequest(e.to_string())
+            }
+            WaveformError::IO(_)
+            | WaveformError::GetTrackBytes(_)
+            | WaveformError::Decode(_)
+            | WaveformError::NoAudio
+            | WaveformError::InvalidStore => ErrorInternalServerError(e.to_string()),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct GetTrackWaveformQuery {
+    pub track_id: u64,
+    pub source: Option<ApiSource>,
+    pub start: Option<f64>,
+    pub end: Option<f64>,
+    pub samples_per_pixel: Option<u32>,
+    pub pixels: Option<u32>,
+    pub split_channels: Option<bool>,
+    pub bits: Option<u8>,
+    pub format: Option<WaveformFormat>,
+}
+
+#[cfg_attr(
+    feature = "openapi", utoipa::path(
+        tags = ["Files"],
+        get,
+        path = "/track/waveform",
+        description = "Get the waveform peaks of a time window of the track in the audiowaveform JSON or binary format",
+        params(
+            ("trackId" = u64, Query, description = "The track ID"),
+            ("source" = Option<ApiSource>, Query, description = "The track API source"),
+            ("start" = Option<f64>, Query, description = "The start of the time window in seconds"),
+            ("end" = Option<f64>, Query, description = "The end of the time window in seconds. Defaults to the end of the track"),
+            ("samplesPerPixel" = Option<u32>, Query, description = "The number of samples per peak, rounded up to a multiple of 256"),
+            ("pixels" = Option<u32>, Query, description = "The number of peaks to fit the time window in. Overrides samplesPerPixel"),
+            ("splitChannels" = Option<bool>, Query, description = "Whether to return the peaks of each channel instead of the peaks of all channels"),
+            ("bits" = Option<u8>, Query, description = "The resolution of the peaks, 8 or 16 bits"),
+            ("format" = Option<WaveformFormat>, Query, description = "JSON or DAT, the audiowaveform binary format"),
+        ),
+        responses(
+            (
+                status = 200,
+                description = "The waveform peaks",
+                body = AudiowaveformJson,
+            )
+        )
+    )
+)]
+#[route("/track/waveform", method = "GET")]
+pub async fn track_waveform_endpoint(
+    query: web::Query<GetTrackWaveformQuery>,
+    music_apis: MusicApis,
+) -> Result<HttpResponse> {
+    let source = get_track_id_source(
+        music_apis,
+        &query.track_id.into(),
+        query.source.unwrap_or(ApiSource::Library),
+        Some(TrackAudioQuality::Low),
+    )
+    .await?;
+
+    let waveform = get_or_init_waveform(&source).await?;
+
+    let start = query.start.unwrap_or(0.0);
+    let samples_per_pixel = if let Some(pixels) = query.pixels.filter(|x| *x > 0) {
+        let end = query.end.unwrap_or_else(|| waveform.duration());
+        let samples = (end - start).max(0.0) * f64::from(waveform.sample_rate);
+        (samples / f64::from(pixels)).ceil() as u32
+    } else {
+        query.samples_per_pixel.unwrap_or(BASE_SAMPLES_PER_PIXEL)
+    };
+
+    let peaks = waveform.peaks(
+        start,
+        query.end,
+        samples_per_pixel,
+        query.split_channels.unwrap_or(false),
+    )?;
+    let bits = query.bits.unwrap_or(16);
+
+    log::debug!(
+        "track_waveform_endpoint: track_id={} samples_per_pixel={} length={}",
+        query.track_id,
+        peaks.samples_per_pixel,
+        peaks.len()
+    );
+
+    Ok(match query.format.unwrap_or_default() {
+        WaveformFormat::Json => HttpResponse::Ok().json(peaks.to_json(bits)?),
+        WaveformFormat::Dat => HttpResponse::Ok()
+            .content_type("application/octet-stream")
+            .body(peaks.to_dat(bits)?),
+    })
+}
+
 impl From<GetSilenceBytesError> for actix_web::Error {
     fn from(err: GetSilenceBytesError) -> Self {
         match err {
@@ -240,7 +521,9 @@ impl From<GetTrackBytesError> for actix_web::Error {
             | GetTrackBytesError::Track(_)
             | GetTrackBytesError::TrackInfo(_) => ErrorInternalServerError(err),
             GetTrackBytesError::NotFound => ErrorNotFound(err),
-            GetTrackBytesError::UnsupportedFormat => ErrorBadRequest(err),
+            GetTrackBytesError::Settings(_) | GetTrackBytesError::UnsupportedFormat => {
+                ErrorBadRequest(err)
+            }
         }
     }
 }
@@ -253,6 +536,110 @@ pub struct GetTrackQuery {
     pub format: Option<AudioFormat>,
     pub quality: Option<TrackAudioQuality>,
     pub source: Option<ApiSource>,
+    pub bitrate: Option<u32>,
+    pub vbr_quality: Option<u8>,
+    pub sample_rate: Option<u32>,
+    pub channels: Option<u8>,
+    pub quality_profile: Option<String>,
+    pub seek: Option<f64>,
+}
+
+impl GetTrackQuery {
+    const fn encoder_settings(&self) -> EncoderSettings {
+        EncoderSettings {
+            bitrate: self.bitrate,
+            vbr_quality: self.vbr_quality,
+            sample_rate: self.sample_rate,
+            channels: self.channels,
+        }
+    }
+}
+
+#[allow(unused)]
+fn range_header(req: &HttpRequest) -> Result<Option<crate::range::Range>> {
+    req.headers()
+        .get(actix_web::http::header::RANGE)
+        .and_then(|x| x.to_str().ok())
+        .map(|range| {
+            log::debug!("Got range request {:?}", range);
+
+            range
+                .strip_prefix("bytes=")
+                .map(|s| s.to_string())
+                .ok_or(ErrorBadRequest(format!("Invalid range: {range}")))
+        })
+        .transpose()?
+        .map(|range| {
+            crate::range::parse_range(&range)
+                .map_err(|e| ErrorBadRequest(format!("Invalid bytes range: {range} ({e:?})")))
+        })
+        .transpose()
+}
+
+/// Resolves the inclusive byte range of a body of `total` bytes, or `None`
+/// if the range can't be satisfied.
+#[allow(unused)]
+fn satisfiable_range(range: &crate::range::Range, total: u64) -> Option<(u64, u64)> {
+    let last = total.checked_sub(1)?;
+    let (start, end) = match (range.start, range.end) {
+        (Some(start), end) => (start as u64, end.map_or(last, |x| (x as u64).min(last))),
+        (None, Some(suffix)) => (total.saturating_sub(suffix as u64), last),
+        (None, None) => (0, last),
+    };
+
+    (start <= end).then_some((start, end))
+}
+
+#[allow(unused)]
+fn range_not_satisfiable(total: u64) -> HttpResponse {
+    HttpResponse::RangeNotSatisfiable()
+        .insert_header((
+            actix_web::http::header::CONTENT_RANGE,
+            format!("bytes */{total}"),
+        ))
+        .finish()
+}
+
+/// Resolves the output format and encoder settings of a request. The format
+/// and settings passed explicitly override the ones of the quality profile.
+async fn resolve_encoder_settings(
+    req: &HttpRequest,
+    quality_profile: Option<&str>,
+    format: Option<AudioFormat>,
+    settings: EncoderSettings,
+) -> Result<(Option<AudioFormat>, EncoderSettings)> {
+    let (format, settings) = if let Some(name) = quality_profile {
+        let profile = ProfileName::from_request_inner(req)?;
+        let db = ConfigDatabase::extract(req).await?;
+        let quality_profile = get_quality_profile(&db, &profile.0, name)
+            .await?
+            .ok_or_else(|| ErrorNotFound(format!("Quality profile '{name}' not found")))?;
+
+        // The bitrate and VBR quality are mutually exclusive, so an explicit
+        // rate control setting replaces both of the profile's
+        let (bitrate, vbr_quality) = if settings.bitrate.is_some() || settings.vbr_quality.is_some()
+        {
+            (settings.bitrate, settings.vbr_quality)
+        } else {
+            (quality_profile.bitrate, quality_profile.vbr_quality)
+        };
+
+        (
+            Some(format.unwrap_or(quality_profile.format)),
+            EncoderSettings {
+                bitrate,
+                vbr_quality,
+                sample_rate: settings.sample_rate.or(quality_profile.sample_rate),
+                channels: settings.channels.or(quality_profile.channels),
+            },
+        )
+    } else {
+        (format, settings)
+    };
+
+    validate_encoder_settings(format.unwrap_or_default(), &settings).map_err(ErrorBadRequest)?;
+
+    Ok((format, settings))
 }
 
 #[cfg_attr(
@@ -266,11 +653,25 @@ pub struct GetTrackQuery {
             ("format" = Option<AudioFormat>, Query, description = "The track format to return"),
             ("quality" = Option<TrackAudioQuality>, Query, description = "The quality to return"),
             ("source" = Option<ApiSource>, Query, description = "The track API source"),
+            ("bitrate" = Option<u32>, Query, description = "The constant bitrate in kbps to encode the track with"),
+            ("vbrQuality" = Option<u8>, Query, description = "The encoder specific VBR quality to encode the track with"),
+            ("sampleRate" = Option<u32>, Query, description = "The sample rate in Hz to encode the track with"),
+            ("channels" = Option<u8>, Query, description = "The number of channels to encode the track with"),
+            ("qualityProfile" = Option<String>, Query, description = "The name of the quality profile to encode the track with, e.g. 'mobile-data', 'wifi' or 'archive'"),
+            ("seek" = Option<f64>, Query, description = "The position in seconds to start the track from. Only supported when transcoding to AAC or MP3"),
         ),
         responses(
             (
                 status = 200,
                 description = "Track audio bytes",
+            ),
+            (
+                status = 206,
+                description = "The requested range of the track audio bytes",
+            ),
+            (
+                status = 416,
+                description = "The requested range is outside of the track audio bytes",
             )
         )
     )
@@ -298,33 +699,23 @@ pub async fn track_endpoint(
         query.source
     );
 
-    let content_type = query
-        .format
+    let (format, settings) = resolve_encoder_settings(
+        &req,
+        query.quality_profile.as_deref(),
+        query.format,
+        query.encoder_settings(),
+    )
+    .await?;
+
+    let content_type = format
         .as_ref()
         .and_then(audio_format_to_content_type)
         .or(track_source_to_content_type(&source));
 
-    let format = query.format.unwrap_or_default();
+    let format = format.unwrap_or_default();
 
     #[cfg(feature = "track-range")]
-    let range = req
-        .headers()
-        .get(actix_web::http::header::RANGE)
-        .and_then(|x| x.to_str().ok())
-        .map(|range| {
-            log::debug!("Got range request {:?}", range);
-
-            range
-                .strip_prefix("bytes=")
-                .map(|s| s.to_string())
-                .ok_or(ErrorBadRequest(format!("Invalid range: {range}")))
-        })
-        .transpose()?
-        .map(|range| {
-            crate::range::parse_range(&range)
-                .map_err(|e| ErrorBadRequest(format!("Invalid bytes range: {range} ({e:?})")))
-        })
-        .transpose()?;
+    let range = range_header(&req)?;
 
     #[cfg(not(feature = "track-range"))]
     let range: Option<crate::range::Range> = None;
@@ -358,6 +749,48 @@ pub async fn track_endpoint(
         }
     }
 
+    #[cfg(feature = "hls")]
+    {
+        // Byte ranges of a continuous transcode don't map to positions in the
+        // audio, so AAC and MP3 transcodes are served from the seekable layout
+        // instead, whether or not a range was requested, so that every
+        // response agrees on the size and bytes of the transcode
+        let transcoding = source.format() != format || !settings.is_default();
+        let bitrate = seekable_bitrate(format, &settings);
+
+        if let Some(seek) = query.seek {
+            let bitrate = bitrate.ok_or_else(|| ErrorBadRequest(SEEK_UNSUPPORTED))?;
+            return seek_track_response(
+                response,
+                &music_apis,
+                &query,
+                source,
+                format,
+                bitrate,
+                seek,
+            )
+            .await;
+        }
+
+        if let (Some(bitrate), true) = (bitrate, transcoding) {
+            return seekable_track_range_response(
+                response,
+                &music_apis,
+                &query,
+                source,
+                format,
+                bitrate,
+                range.as_ref(),
+            )
+            .await;
+        }
+    }
+
+    #[cfg(not(feature = "hls"))]
+    if query.seek.is_some() {
+        return Err(ErrorBadRequest(SEEK_UNSUPPORTED));
+    }
+
     log::debug!("{method} /track Fetching track bytes with range range={range:?}");
 
     let bytes = get_track_bytes(
@@ -367,6 +800,7 @@ pub async fn track_endpoint(
         &Id::Number(query.track_id),
         source,
         format,
+        settings,
         true,
         range.as_ref().and_then(|r| r.start.map(|x| x as u64)),
         range.as_ref().and_then(|r| r.end.map(|x| x as u64)),
@@ -440,34 +874,387 @@ pub async fn track_endpoint(
     }
 }
 
+const SEEK_UNSUPPORTED: &str = "Seeking is only supported when transcoding to AAC or MP3";
+
+#[cfg(feature = "hls")]
+async fn seekable_layout(
+    music_apis: &MusicApis,
+    query: &GetTrackQuery,
+    format: AudioFormat,
+    bitrate: u32,
+) -> Result<SeekableLayout> {
+    let duration = hls_track_duration(music_apis, query.track_id, query.source).await?;
+
+    Ok(SeekableLayout::new(format, bitrate, duration)?)
+}
+
+/// Responds with the seekable transcode of the track from the frame playing
+/// at `seek` seconds.
+#[cfg(feature = "hls")]
+async fn seek_track_response(
+    mut response: actix_web::HttpResponseBuilder,
+    music_apis: &MusicApis,
+    query: &GetTrackQuery,
+    source: TrackSource,
+    format: AudioFormat,
+    bitrate: u32,
+    seek: f64,
+) -> Result<HttpResponse> {
+    let layout = seekable_layout(music_apis, query, format, bitrate).await?;
+
+    if !seek.is_finite() || seek < 0.0 {
+        return Err(ErrorBadRequest(format!("Invalid seek position: {seek}")));
+    }
+
+    let (size, stream) = layout
+        .stream_from(source, seek)
+        .await
+        .map_err(|e| match e {
+            HlsError::SegmentNotFound(_) => {
+                ErrorBadRequest(format!("Seek position out of bounds: {seek}"))
+            }
+            e => e.into(),
+        })?;
+
+    log::debug!("Returning seekable stream from seek={seek} with size={size}");
+    Ok(response.body(actix_web::body::SizedStream::new(size, stream)))
+}
+
+/// Responds with the requested byte range of the seekable transcode of the
+/// track, or the whole transcode if no range was requested.
+#[cfg(feature = "hls")]
+async fn seekable_track_range_response(
+    mut response: actix_web::HttpResponseBuilder,
+    music_apis: &MusicApis,
+    query: &GetTrackQuery,
+    source: TrackSource,
+    format: AudioFormat,
+    bitrate: u32,
+    range: Option<&crate::range::Range>,
+) -> Result<HttpResponse> {
+    let layout = seekable_layout(music_apis, query, format, bitrate).await?;
+    let total = layout.size();
+
+    let (start, end) = if let Some(range) = range {
+        let Some((start, end)) = satisfiable_range(range, total) else {
+            log::debug!("Unsatisfiable range {range:?} of seekable stream with size={total}");
+            return Ok(range_not_satisfiable(total));
+        };
+
+        response.status(actix_web::http::StatusCode::PARTIAL_CONTENT);
+        response.insert_header((
+            actix_web::http::header::CONTENT_RANGE,
+            format!("bytes {start}-{end}/{total}"),
+        ));
+        (start, end)
+    } else {
+        (0, total.saturating_sub(1))
+    };
+
+    let size = (end + 1).min(total) - start;
+    let stream = layout.stream(source, start, end);
+
+    log::debug!("Returning seekable stream range {start}-{end} with size={size}");
+    Ok(response.body(actix_web::body::SizedStream::new(size, stream)))
+}
+
+#[cfg(feature = "hls")]
 #[derive(Deserialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
 #[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
-pub struct GetTrackInfoQuery {
-    pub track_id: Id,
+pub struct GetTrackHlsQuery {
+    pub track_id: u64,
+    pub format: Option<AudioFormat>,
+    pub quality: Option<TrackAudioQuality>,
+    pub source: Option<ApiSource>,
+    pub bitrates: Option<String>,
+}
+
+#[cfg(feature = "hls")]
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct GetTrackHlsMediaQuery {
+    pub track_id: u64,
+    pub format: Option<AudioFormat>,
+    pub quality: Option<TrackAudioQuality>,
+    pub source: Option<ApiSource>,
+    pub bitrate: u32,
+}
+
+#[cfg(feature = "hls")]
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct GetTrackHlsSegmentQuery {
+    pub track_id: u64,
+    pub format: Option<AudioFormat>,
+    pub quality: Option<TrackAudioQuality>,
     pub source: Option<ApiSource>,
+    pub bitrate: u32,
+    pub index: u64,
+}
+
+#[cfg(feature = "hls")]
+const fn hls_format(format: Option<AudioFormat>) -> AudioFormat {
+    match format {
+        Some(format) => format,
+        #[cfg(feature = "aac")]
+        None => AudioFormat::Aac,
+        #[cfg(all(not(feature = "aac"), feature = "mp3"))]
+        None => AudioFormat::Mp3,
+        #[cfg(all(not(feature = "aac"), not(feature = "mp3")))]
+        None => AudioFormat::Source,
+    }
+}
+
+/// Builds a playlist entry URI relative to the current request, keeping the
+/// query params of the request (e.g. auth) except for the `overridden` ones.
+#[cfg(feature = "hls")]
+fn hls_uri(
+    req: &HttpRequest,
+    path: &str,
+    overridden: &[&str],
+    params: &[(&str, String)],
+) -> String {
+    let query = req
+        .query_string()
+        .split('&')
+        .filter(|x| !x.is_empty())
+        .filter(|x| {
+            let key = x.split('=').next().unwrap_or_default();
+            !overridden.contains(&key)
+        })
+        .map(ToString::to_string)
+        .chain(params.iter().map(|(key, value)| format!("{key}={value}")))
+        .collect::<Vec<_>>()
+        .join("&");
+
+    format!("{path}?{query}")
+}
+
+#[cfg(feature = "hls")]
+async fn hls_track_duration(
+    music_apis: &MusicApis,
+    track_id: u64,
+    source: Option<ApiSource>,
+) -> Result<f64> {
+    let info = get_track_info(
+        &**music_apis
+            .get(source.unwrap_or(ApiSource::Library))
+            .map_err(|e| ErrorBadRequest(format!("Invalid source: {e:?}")))?,
+        &Id::Number(track_id),
+    )
+    .await?;
+
+    Ok(info.duration)
 }
 
+#[cfg(feature = "hls")]
 #[cfg_attr(
     feature = "openapi", utoipa::path(
         tags = ["Files"],
         get,
-        path = "/track/info",
-        description = "Get the track's metadata",
+        path = "/track/hls/master.m3u8",
+        description = "Get the HLS master playlist of the track with a variant for each bitrate",
         params(
-            ("trackId" = Id, Query, description = "The track ID"),
+            ("trackId" = u64, Query, description = "The track ID"),
+            ("format" = Option<AudioFormat>, Query, description = "The format of the segments"),
+            ("quality" = Option<TrackAudioQuality>, Query, description = "The quality of the source audio"),
             ("source" = Option<ApiSource>, Query, description = "The track API source"),
+            ("bitrates" = Option<String>, Query, description = "The comma-separated list of variant bitrates in kbps"),
         ),
         responses(
             (
                 status = 200,
-                description = "Track info",
-                body = TrackInfo,
+                description = "HLS master playlist",
             )
         )
     )
 )]
-#[route("/track/info", method = "GET")]
+#[route("/track/hls/master.m3u8", method = "GET")]
+pub async fn track_hls_master_playlist_endpoint(
+    req: HttpRequest,
+    query: web::Query<GetTrackHlsQuery>,
+) -> Result<HttpResponse> {
+    let format = hls_format(query.format);
+
+    let bitrates = query.bitrates.as_ref().map_or_else(
+        || Ok(hls::default_bitrates(format).to_vec()),
+        |bitrates| {
+            bitrates
+                .split(',')
+                .map(|x| {
+                    x.trim()
+                        .parse::<u32>()
+                        .map_err(|_| ErrorBadRequest(format!("Invalid bitrate '{x}'")))
+                })
+                .collect::<Result<Vec<_>>>()
+        },
+    )?;
+
+    log::debug!(
+        "GET /track/hls/master.m3u8 track_id={} format={format:?} bitrates={bitrates:?}",
+        query.track_id,
+    );
+
+    let playlist = hls::master_playlist(format, &bitrates, |bitrate| {
+        hls_uri(
+            &req,
+            "media.m3u8",
+            &["bitrates", "bitrate", "format"],
+            &[
+                ("format", format.to_string()),
+                ("bitrate", bitrate.to_string()),
+            ],
+        )
+    })?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/vnd.apple.mpegurl")
+        .body(playlist))
+}
+
+#[cfg(feature = "hls")]
+#[cfg_attr(
+    feature = "openapi", utoipa::path(
+        tags = ["Files"],
+        get,
+        path = "/track/hls/media.m3u8",
+        description = "Get the HLS media playlist of the track's segments at the bitrate",
+        params(
+            ("trackId" = u64, Query, description = "The track ID"),
+            ("format" = Option<AudioFormat>, Query, description = "The format of the segments"),
+            ("quality" = Option<TrackAudioQuality>, Query, description = "The quality of the source audio"),
+            ("source" = Option<ApiSource>, Query, description = "The track API source"),
+            ("bitrate" = u32, Query, description = "The bitrate of the segments in kbps"),
+        ),
+        responses(
+            (
+                status = 200,
+                description = "HLS media playlist",
+            )
+        )
+    )
+)]
+#[route("/track/hls/media.m3u8", method = "GET")]
+pub async fn track_hls_media_playlist_endpoint(
+    req: HttpRequest,
+    query: web::Query<GetTrackHlsMediaQuery>,
+    music_apis: MusicApis,
+) -> Result<HttpResponse> {
+    let format = hls_format(query.format);
+    hls::validate_variant(format, query.bitrate)?;
+
+    let duration = hls_track_duration(&music_apis, query.track_id, query.source).await?;
+
+    log::debug!(
+        "GET /track/hls/media.m3u8 track_id={} format={format:?} bitrate={} duration={duration}",
+        query.track_id,
+        query.bitrate,
+    );
+
+    let playlist = hls::media_playlist(format, duration, |index| {
+        hls_uri(
+            &req,
+            "segment",
+            &["index", "format"],
+            &[("format", format.to_string()), ("index", index.to_string())],
+        )
+    })?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/vnd.apple.mpegurl")
+        .body(playlist))
+}
+
+#[cfg(feature = "hls")]
+#[cfg_attr(
+    feature = "openapi", utoipa::path(
+        tags = ["Files"],
+        get,
+        path = "/track/hls/segment",
+        description = "Get an encoded HLS segment of the track",
+        params(
+            ("trackId" = u64, Query, description = "The track ID"),
+            ("format" = Option<AudioFormat>, Query, description = "The format of the segment"),
+            ("quality" = Option<TrackAudioQuality>, Query, description = "The quality of the source audio"),
+            ("source" = Option<ApiSource>, Query, description = "The track API source"),
+            ("bitrate" = u32, Query, description = "The bitrate of the segment in kbps"),
+            ("index" = u64, Query, description = "The index of the segment in the media playlist"),
+        ),
+        responses(
+            (
+                status = 200,
+                description = "HLS segment audio bytes",
+            )
+        )
+    )
+)]
+#[route("/track/hls/segment", method = "GET")]
+pub async fn track_hls_segment_endpoint(
+    query: web::Query<GetTrackHlsSegmentQuery>,
+    music_apis: MusicApis,
+) -> Result<HttpResponse> {
+    let format = hls_format(query.format);
+    hls::validate_variant(format, query.bitrate)?;
+
+    let source = get_track_id_source(
+        music_apis.clone(),
+        &query.track_id.into(),
+        query.source.unwrap_or(ApiSource::Library),
+        query.quality,
+    )
+    .await?;
+
+    let duration = hls_track_duration(&music_apis, query.track_id, query.source).await?;
+
+    log::debug!(
+        "GET /track/hls/segment track_id={} format={format:?} bitrate={} index={} source={source:?}",
+        query.track_id,
+        query.bitrate,
+        query.index,
+    );
+
+    let segment = hls::get_segment(source, format, query.bitrate, duration, query.index).await?;
+
+    let mut response = HttpResponse::Ok();
+
+    if let Some(content_type) = hls::segment_content_type(format) {
+        response.insert_header((actix_web::http::header::CONTENT_TYPE, content_type));
+    }
+
+    Ok(response.body(segment))
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct GetTrackInfoQuery {
+    pub track_id: Id,
+    pub source: Option<ApiSource>,
+}
+
+#[cfg_attr(
+    feature = "openapi", utoipa::path(
+        tags = ["Files"],
+        get,
+        path = "/track/info",
+        description = "Get the track's metadata",
+        params(
+            ("trackId" = Id, Query, description = "The track ID"),
+            ("source" = Option<ApiSource>, Query, description = "The track API source"),
+        ),
+        responses(
+            (
+                status = 200,
+                description = "Track info",
+                body = TrackInfo,
+            )
+        )
+    )
+)]
+#[route("/track/info", method = "GET")]
 pub async fn track_info_endpoint(
     query: web::Query<GetTrackInfoQuery>,
     music_apis: MusicApis,
@@ -619,6 +1406,284 @@ pub async fn track_urls_endpoint(
     Ok(Json(urls))
 }
 
+impl From<QualityProfileError> for actix_web::Error {
+    fn from(e: QualityProfileError) -> Self {
+        match e {
+            QualityProfileError::Database(_) | QualityProfileError::Parse(_) => {
+                ErrorInternalServerError(e.to_string())
+            }
+            QualityProfileError::Settings(_)
+            | QualityProfileError::InvalidFormat(_)
+            | QualityProfileError::InvalidName => ErrorBadRequest(e.to_string()),
+        }
+    }
+}
+
+#[cfg_attr(
+    feature = "openapi", utoipa::path(
+        tags = ["Files"],
+        get,
+        path = "/quality-profiles",
+        description = "Get the built-in quality profiles along with the ones configured for the profile",
+        params(),
+        responses(
+            (
+                status = 200,
+                description = "The quality profiles",
+                body = Vec<QualityProfile>,
+            )
+        )
+    )
+)]
+#[route("/quality-profiles", method = "GET")]
+pub async fn quality_profiles_endpoint(
+    profile: ProfileName,
+    db: ConfigDatabase,
+) -> Result<Json<Vec<QualityProfile>>> {
+    Ok(Json(get_quality_profiles(&db, &profile.0).await?))
+}
+
+#[cfg_attr(
+    feature = "openapi", utoipa::path(
+        tags = ["Files"],
+        post,
+        path = "/quality-profiles",
+        description = "Create or replace a quality profile for the profile. A quality profile with the name of a built-in one overrides it",
+        params(),
+        request_body = QualityProfile,
+        responses(
+            (
+                status = 200,
+                description = "The stored quality profile",
+                body = QualityProfile,
+            )
+        )
+    )
+)]
+#[route("/quality-profiles", method = "POST")]
+pub async fn upsert_quality_profile_endpoint(
+    quality_profile: Json<QualityProfile>,
+    profile: ProfileName,
+    db: ConfigDatabase,
+) -> Result<Json<QualityProfile>> {
+    Ok(Json(
+        upsert_quality_profile(&db, &profile.0, &quality_profile).await?,
+    ))
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DeleteQualityProfileQuery {
+    pub name: String,
+}
+
+#[cfg_attr(
+    feature = "openapi", utoipa::path(
+        tags = ["Files"],
+        delete,
+        path = "/quality-profiles",
+        description = "Delete a quality profile configured for the profile",
+        params(
+            ("name" = String, Query, description = "The name of the quality profile to delete"),
+        ),
+        responses(
+            (
+                status = 200,
+                description = "The deleted quality profile",
+                body = QualityProfile,
+            )
+        )
+    )
+)]
+#[route("/quality-profiles", method = "DELETE")]
+pub async fn delete_quality_profile_endpoint(
+    query: web::Query<DeleteQualityProfileQuery>,
+    profile: ProfileName,
+    db: ConfigDatabase,
+) -> Result<Json<QualityProfile>> {
+    delete_quality_profile(&db, &profile.0, &query.name)
+        .await?
+        .map(Json)
+        .ok_or_else(|| ErrorNotFound(format!("Quality profile '{}' not found", query.name)))
+}
+
+#[cfg(feature = "transcode-cache")]
+impl From<PreTranscodeError> for actix_web::Error {
+    fn from(e: PreTranscodeError) -> Self {
+        match e {
+            PreTranscodeError::TranscodeCache(TranscodeCacheError::NotInitialized) => {
+                actix_web::error::ErrorServiceUnavailable(e.to_string())
+            }
+            PreTranscodeError::MusicApis(_) => ErrorBadRequest(e.to_string()),
+            PreTranscodeError::TranscodeCache(_)
+            | PreTranscodeError::TrackSource(_)
+            | PreTranscodeError::GetTrackBytes(_) => ErrorInternalServerError(e.to_string()),
+        }
+    }
+}
+
+#[cfg(feature = "transcode-cache")]
+fn transcode_cache() -> Result<&'static transcode_cache::TranscodeCache> {
+    TRANSCODE_CACHE.get().ok_or_else(|| {
+        actix_web::error::ErrorServiceUnavailable(TranscodeCacheError::NotInitialized.to_string())
+    })
+}
+
+#[cfg(feature = "transcode-cache")]
+#[cfg_attr(
+    feature = "openapi", utoipa::path(
+        tags = ["Files"],
+        get,
+        path = "/transcode-cache/stats",
+        description = "Get the stats of the on-disk transcode cache",
+        params(),
+        responses(
+            (
+                status = 200,
+                description = "Transcode cache stats",
+                body = TranscodeCacheStats,
+            )
+        )
+    )
+)]
+#[route("/transcode-cache/stats", method = "GET")]
+pub async fn transcode_cache_stats_endpoint() -> Result<Json<TranscodeCacheStats>> {
+    Ok(Json(transcode_cache()?.stats()))
+}
+
+#[cfg(feature = "transcode-cache")]
+#[cfg_attr(
+    feature = "openapi", utoipa::path(
+        tags = ["Files"],
+        delete,
+        path = "/transcode-cache",
+        description = "Remove every transcoded track from the on-disk transcode cache",
+        params(),
+        responses(
+            (
+                status = 200,
+                description = "The entries that were removed",
+                body = TranscodeCachePurge,
+            )
+        )
+    )
+)]
+#[route("/transcode-cache", method = "DELETE")]
+pub async fn purge_transcode_cache_endpoint() -> Result<Json<TranscodeCachePurge>> {
+    Ok(Json(transcode_cache()?.purge()))
+}
+
+#[cfg(feature = "transcode-cache")]
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct PreTranscodeQuery {
+    pub track_ids: Option<String>,
+    pub favorites: Option<bool>,
+    pub format: AudioFormat,
+    pub quality: Option<TrackAudioQuality>,
+    pub source: Option<ApiSource>,
+    pub bitrate: Option<u32>,
+    pub vbr_quality: Option<u8>,
+    pub sample_rate: Option<u32>,
+    pub channels: Option<u8>,
+}
+
+#[cfg(feature = "transcode-cache")]
+#[cfg_attr(
+    feature = "openapi", utoipa::path(
+        tags = ["Files"],
+        post,
+        path = "/transcode-cache/pre-transcode",
+        description = "Transcode tracks into the on-disk transcode cache in the background",
+        params(
+            ("trackIds" = Option<String>, Query, description = "The comma-separated list of track IDs, e.g. the tracks of a playlist"),
+            ("favorites" = Option<bool>, Query, description = "Whether to transcode the tracks added to the API source's library"),
+            ("format" = AudioFormat, Query, description = "The format to transcode the tracks to"),
+            ("quality" = Option<TrackAudioQuality>, Query, description = "The quality of the source audio"),
+            ("source" = Option<ApiSource>, Query, description = "The tracks' API source"),
+            ("bitrate" = Option<u32>, Query, description = "The constant bitrate in kbps to encode the tracks with"),
+            ("vbrQuality" = Option<u8>, Query, description = "The encoder specific VBR quality to encode the tracks with"),
+            ("sampleRate" = Option<u32>, Query, description = "The sample rate in Hz to encode the tracks with"),
+            ("channels" = Option<u8>, Query, description = "The number of channels to encode the tracks with"),
+        ),
+        responses(
+            (
+                status = 200,
+                description = "The transcode cache stats including the queued tracks",
+                body = TranscodeCacheStats,
+            )
+        )
+    )
+)]
+#[route("/transcode-cache/pre-transcode", method = "POST")]
+pub async fn pre_transcode_endpoint(
+    query: web::Query<PreTranscodeQuery>,
+    music_apis: MusicApis,
+) -> Result<Json<TranscodeCacheStats>> {
+    let cache = transcode_cache()?;
+    let source = query.source.unwrap_or(ApiSource::Library);
+    let settings = EncoderSettings {
+        bitrate: query.bitrate,
+        vbr_quality: query.vbr_quality,
+        sample_rate: query.sample_rate,
+        channels: query.channels,
+    };
+    validate_encoder_settings(query.format, &settings).map_err(ErrorBadRequest)?;
+
+    let mut ids = vec![];
+
+    if let Some(track_ids) = &query.track_ids {
+        ids.extend(
+            parse_id_ranges(track_ids, source, IdType::Track).map_err(|e| match e {
+                ParseIdsError::ParseId(id) => {
+                    ErrorBadRequest(format!("Could not parse trackId '{id}'"))
+                }
+                ParseIdsError::UnmatchedRange(range) => {
+                    ErrorBadRequest(format!("Unmatched range '{range}'"))
+                }
+                ParseIdsError::RangeTooLarge(range) => {
+                    ErrorBadRequest(format!("Range too large '{range}'"))
+                }
+            })?,
+        );
+    }
+
+    if query.favorites == Some(true) {
+        let api = music_apis
+            .get(source)
+            .map_err(|e| ErrorBadRequest(format!("Invalid source: {e:?}")))?;
+
+        ids.extend(
+            transcode_cache::favorite_track_ids(&**api)
+                .await
+                .map_err(ErrorInternalServerError)?,
+        );
+    }
+
+    if ids.is_empty() {
+        return Err(ErrorBadRequest("No trackIds or favorites to transcode"));
+    }
+
+    log::debug!(
+        "POST /transcode-cache/pre-transcode format={} tracks={}",
+        query.format,
+        ids.len()
+    );
+
+    transcode_cache::pre_transcode(
+        music_apis,
+        source,
+        ids,
+        query.format,
+        settings,
+        query.quality,
+    )?;
+
+    Ok(Json(cache.stats()))
+}
+
 impl From<ArtistCoverError> for actix_web::Error {
     fn from(err: ArtistCoverError) -> Self {
         match err {
@@ -927,6 +1992,339 @@ pub async fn album_artwork_endpoint(
         .map_err(|e| ErrorInternalServerError(format!("Failed to resize image: {e:?}")))
 }
 
+#[cfg(feature = "archive")]
+impl From<ArchiveError> for actix_web::Error {
+    fn from(e: ArchiveError) -> Self {
+        match e {
+            ArchiveError::NoTracks => ErrorNotFound(e.to_string()),
+            ArchiveError::TooLarge | ArchiveError::NameTooLong(_) => ErrorBadRequest(e.to_string()),
+            ArchiveError::IO(_)
+            | ArchiveError::Tracks(_)
+            | ArchiveError::TrackSource(_)
+            | ArchiveError::GetTrackBytes(_) => ErrorInternalServerError(e.to_string()),
+        }
+    }
+}
+
+#[cfg(feature = "archive")]
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AlbumArchiveQuery {
+    pub source: Option<ApiSource>,
+    pub archive_format: Option<ArchiveFormat>,
+    pub format: Option<AudioFormat>,
+    pub quality: Option<TrackAudioQuality>,
+    pub bitrate: Option<u32>,
+    pub vbr_quality: Option<u8>,
+    pub sample_rate: Option<u32>,
+    pub channels: Option<u8>,
+    pub quality_profile: Option<String>,
+    pub playlist: Option<ArchivePlaylist>,
+}
+
+#[cfg(feature = "archive")]
+#[cfg_attr(
+    feature = "openapi", utoipa::path(
+        tags = ["Files"],
+        method(head, get),
+        path = "/albums/{albumId}/archive",
+        description = "Download the album's tracks and cover as a ZIP or tar archive. Byte ranges are supported when no tracks are transcoded",
+        params(
+            ("albumId" = u64, Path, description = "The Album ID"),
+            ("source" = Option<ApiSource>, Query, description = "The album source"),
+            ("archiveFormat" = Option<ArchiveFormat>, Query, description = "The archive format, ZIP by default"),
+            ("format" = Option<AudioFormat>, Query, description = "The format to transcode the tracks to. The tracks are archived in their source format by default"),
+            ("quality" = Option<TrackAudioQuality>, Query, description = "The quality of the source audio"),
+            ("bitrate" = Option<u32>, Query, description = "The constant bitrate in kbps to encode the tracks with"),
+            ("vbrQuality" = Option<u8>, Query, description = "The encoder specific VBR quality to encode the tracks with"),
+            ("sampleRate" = Option<u32>, Query, description = "The sample rate in Hz to encode the tracks with"),
+            ("channels" = Option<u8>, Query, description = "The number of channels to encode the tracks with"),
+            ("qualityProfile" = Option<String>, Query, description = "The name of the quality profile to encode the tracks with"),
+            ("playlist" = Option<ArchivePlaylist>, Query, description = "A playlist of the tracks to include in the archive"),
+        ),
+        responses(
+            (
+                status = 200,
+                description = "The album archive",
+            ),
+            (
+                status = 206,
+                description = "The requested range of the album archive",
+            ),
+            (
+                status = 416,
+                description = "The requested range is outside of the album archive",
+            )
+        )
+    )
+)]
+#[cfg(feature = "archive")]
+#[route("/albums/{albumId}/archive", method = "GET", method = "HEAD")]
+pub async fn album_archive_endpoint(
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<AlbumArchiveQuery>,
+    db: LibraryDatabase,
+    music_apis: MusicApis,
+) -> Result<HttpResponse> {
+    let album_id_string = path.into_inner();
+    let source = query.source.unwrap_or(ApiSource::Library);
+    let album_id = match source {
+        ApiSource::Library => album_id_string.parse::<u64>().map(Id::Number),
+        ApiSource::Tidal => album_id_string.parse::<u64>().map(Id::Number),
+        ApiSource::Qobuz => Ok(Id::String(album_id_string)),
+        ApiSource::Yt => Ok(Id::String(album_id_string)),
+    }
+    .map_err(|_e| ErrorBadRequest("Invalid album_id"))?;
+
+    let range = range_header(&req)?;
+
+    let (format, settings) = resolve_encoder_settings(
+        &req,
+        query.quality_profile.as_deref(),
+        query.format,
+        EncoderSettings {
+            bitrate: query.bitrate,
+            vbr_quality: query.vbr_quality,
+            sample_rate: query.sample_rate,
+            channels: query.channels,
+        },
+    )
+    .await?;
+
+    let api = music_apis
+        .get(source)
+        .map_err(|e| ErrorInternalServerError(format!("Failed to get music_api: {e:?}")))?;
+    let album = api
+        .album(&album_id)
+        .await
+        .map_err(|e| ErrorNotFound(format!("Failed to get album: {e:?}")))?
+        .ok_or_else(|| ErrorNotFound(format!("Album not found: {}", album_id.to_owned())))?;
+
+    let archive_format = query.archive_format.unwrap_or_default();
+
+    log::debug!(
+        "album_archive_endpoint: album_id={album_id} archive_format={archive_format:?} format={format:?} settings={settings:?}"
+    );
+
+    let archive = get_album_archive(
+        &**api,
+        &db,
+        &album,
+        archive_format,
+        format,
+        settings,
+        query.quality,
+        query.playlist,
+    )
+    .await?;
+
+    let mut response = HttpResponse::Ok();
+    response.insert_header((
+        actix_web::http::header::CONTENT_TYPE,
+        archive_format.content_type(),
+    ));
+    response.insert_header((
+        actix_web::http::header::CONTENT_DISPOSITION,
+        format!(
+            "attachment; filename=\"{} - {}.{}\"",
+            album.artist.replace(['"', '/', '\\'], "_"),
+            album.title.replace(['"', '/', '\\'], "_"),
+            archive_format.extension()
+        ),
+    ));
+
+    let Some(layout) = archive.layout().await? else {
+        log::debug!("Streaming transcoded album archive without a size");
+        response.insert_header((actix_web::http::header::ACCEPT_RANGES, "none"));
+        return Ok(response.streaming(archive.stream()));
+    };
+
+    let total = layout.size();
+    response.insert_header((actix_web::http::header::ACCEPT_RANGES, "bytes"));
+
+    let (start, end) = if let Some(range) = range {
+        let Some((start, end)) = satisfiable_range(&range, total) else {
+            log::debug!("Unsatisfiable range {range:?} of album archive with size={total}");
+            return Ok(range_not_satisfiable(total));
+        };
+
+        response.status(actix_web::http::StatusCode::PARTIAL_CONTENT);
+        response.insert_header((
+            actix_web::http::header::CONTENT_RANGE,
+            format!("bytes {start}-{end}/{total}"),
+        ));
+        (start, end)
+    } else {
+        (0, total - 1)
+    };
+
+    let size = end - start + 1;
+
+    log::debug!("Returning album archive range {start}-{end} with size={size}");
+    Ok(response.body(actix_web::body::SizedStream::new(
+        size,
+        layout.stream(start, end),
+    )))
+}
+
+#[cfg(feature = "analysis")]
+impl From<AnalysisError> for actix_web::Error {
+    fn from(e: AnalysisError) -> Self {
+        match e {
+            AnalysisError::InvalidDimensions(..) => ErrorBadRequest(e.to_string()),
+            AnalysisError::IO(_)
+            | AnalysisError::Json(_)
+            | AnalysisError::Tracks(_)
+            | AnalysisError::GetTrackBytes(_)
+            | AnalysisError::Decode(_)
+            | AnalysisError::NoAudio
+            | AnalysisError::InvalidStore
+            | AnalysisError::Image(_) => ErrorInternalServerError(e.to_string()),
+        }
+    }
+}
+
+#[cfg(feature = "analysis")]
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AlbumAnalysisQuery {
+    pub source: Option<ApiSource>,
+    pub quality: Option<TrackAudioQuality>,
+}
+
+#[cfg(feature = "analysis")]
+#[cfg_attr(
+    feature = "openapi", utoipa::path(
+        tags = ["Files"],
+        get,
+        path = "/albums/{albumId}/analysis",
+        description = "Get the spectral analysis report of every track of the album, flagging tracks that were likely transcoded from a lossy source or upsampled. Tracks are analyzed the first time they are requested",
+        params(
+            ("albumId" = u64, Path, description = "The Album ID"),
+            ("source" = Option<ApiSource>, Query, description = "The album source"),
+            ("quality" = Option<TrackAudioQuality>, Query, description = "The quality of the source audio to analyze"),
+        ),
+        responses(
+            (
+                status = 200,
+                description = "The album analysis report",
+                body = AlbumAnalysis,
+            )
+        )
+    )
+)]
+#[cfg(feature = "analysis")]
+#[route("/albums/{albumId}/analysis", method = "GET")]
+pub async fn album_analysis_endpoint(
+    path: web::Path<String>,
+    query: web::Query<AlbumAnalysisQuery>,
+    music_apis: MusicApis,
+) -> Result<Json<AlbumAnalysis>> {
+    let album_id_string = path.into_inner();
+    let source = query.source.unwrap_or(ApiSource::Library);
+    let album_id = match source {
+        ApiSource::Library => album_id_string.parse::<u64>().map(Id::Number),
+        ApiSource::Tidal => album_id_string.parse::<u64>().map(Id::Number),
+        ApiSource::Qobuz => Ok(Id::String(album_id_string)),
+        ApiSource::Yt => Ok(Id::String(album_id_string)),
+    }
+    .map_err(|_e| ErrorBadRequest("Invalid album_id"))?;
+
+    let api = music_apis
+        .get(source)
+        .map_err(|e| ErrorInternalServerError(format!("Failed to get music_api: {e:?}")))?;
+    let album = api
+        .album(&album_id)
+        .await
+        .map_err(|e| ErrorNotFound(format!("Failed to get album: {e:?}")))?
+        .ok_or_else(|| ErrorNotFound(format!("Album not found: {}", album_id.to_owned())))?;
+
+    log::debug!("album_analysis_endpoint: album_id={album_id}");
+
+    Ok(Json(
+        get_album_analysis(&**api, &album, query.quality).await?,
+    ))
+}
+
+#[cfg(all(feature = "analysis", feature = "image"))]
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct GetTrackSpectrogramQuery {
+    pub track_id: u64,
+    pub source: Option<ApiSource>,
+    pub quality: Option<TrackAudioQuality>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+}
+
+#[cfg(all(feature = "analysis", feature = "image"))]
+const MAX_SPECTROGRAM_DIMENSION: u32 = 4096;
+
+#[cfg(all(feature = "analysis", feature = "image"))]
+#[cfg_attr(
+    feature = "openapi", utoipa::path(
+        tags = ["Files"],
+        get,
+        path = "/track/spectrogram",
+        description = "Get the spectrogram of the track as a PNG, with time running left to right and frequency from 0 Hz to the Nyquist frequency bottom to top",
+        params(
+            ("trackId" = u64, Query, description = "The track ID"),
+            ("source" = Option<ApiSource>, Query, description = "The track API source"),
+            ("quality" = Option<TrackAudioQuality>, Query, description = "The quality of the source audio to analyze"),
+            ("width" = Option<u32>, Query, description = "The width of the image in pixels, 1024 by default"),
+            ("height" = Option<u32>, Query, description = "The height of the image in pixels, 512 by default"),
+        ),
+        responses(
+            (
+                status = 200,
+                description = "The spectrogram PNG",
+            )
+        )
+    )
+)]
+#[cfg(all(feature = "analysis", feature = "image"))]
+#[route("/track/spectrogram", method = "GET")]
+pub async fn track_spectrogram_endpoint(
+    query: web::Query<GetTrackSpectrogramQuery>,
+    music_apis: MusicApis,
+) -> Result<HttpResponse> {
+    let width = query.width.unwrap_or(1024);
+    let height = query.height.unwrap_or(512);
+
+    if width > MAX_SPECTROGRAM_DIMENSION || height > MAX_SPECTROGRAM_DIMENSION {
+        return Err(ErrorBadRequest(format!(
+            "Spectrogram dimensions can't exceed {MAX_SPECTROGRAM_DIMENSION}"
+        )));
+    }
+
+    let source = get_track_id_source(
+        music_apis,
+        &query.track_id.into(),
+        query.source.unwrap_or(ApiSource::Library),
+        query.quality,
+    )
+    .await?;
+
+    let analysis = get_or_init_analysis(&source).await?;
+
+    log::debug!(
+        "track_spectrogram_endpoint: track_id={} width={width} height={height}",
+        query.track_id,
+    );
+
+    let png = moosicbox_task::spawn_blocking("files: render spectrogram", move || {
+        analysis.spectrogram.to_png(width, height)
+    })
+    .await
+    .map_err(ErrorInternalServerError)??;
+
+    Ok(HttpResponse::Ok().content_type("image/png").body(png))
+}
+
 #[derive(Debug, Error)]
 pub enum ResizeImageError {
     #[error("Failed to read file with path: {0} ({1})")]
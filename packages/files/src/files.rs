@@ -1,11 +1,23 @@
 use std::str::FromStr as _;
 
 pub mod album;
+#[cfg(feature = "analysis")]
+pub mod analysis;
+#[cfg(feature = "archive")]
+pub mod archive;
 pub mod artist;
+#[cfg(feature = "hls")]
+pub mod hls;
+pub mod quality_profile;
+#[cfg(feature = "hls")]
+pub mod seekable;
 pub mod track;
 
 mod track_bytes_media_source;
 pub mod track_pool;
+#[cfg(feature = "transcode-cache")]
+pub mod transcode_cache;
+pub mod waveform;
 
 pub(crate) fn filename_from_path_str(path: &str) -> Option<String> {
     std::path::PathBuf::from_str(path).ok().and_then(|p| {
@@ -0,0 +1,933 @@
+//! Spectral analysis of tracks for detecting fake lossless files.
+//!
+//! A track is decoded once, the first time its analysis is requested, into a
+//! spectrogram of its mono mix over frames of [`FFT_SIZE`] samples, summarized
+//! in [`BANDS`] frequency bands. The effective frequency cutoff is estimated
+//! from the average spectrum as the highest band well above the noise floor.
+//! A sharp cutoff below the band limit of CD audio is the lowpass filter of a
+//! lossy encoder, and a cutoff at the band limit of a lower sample rate means
+//! the file was upsampled. The effective bit depth is the number of bits
+//! actually used by the decoded samples.
+//!
+//! Analyses are stored in the `analyses` cache directory along with their
+//! spectrogram, which can be rendered as an image.
+
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use moosicbox_audio_decoder::{decode_media_source_async, DecodeError};
+use moosicbox_audio_encoder::EncoderSettings;
+use moosicbox_core::{
+    sqlite::models::{Album, Id, TrackApiSource},
+    types::AudioFormat,
+};
+use moosicbox_music_api::{MusicApi, TrackAudioQuality, TrackSource, TracksError};
+use rustfft::{num_complex::Complex, Fft, FftPlanner};
+use serde::{Deserialize, Serialize};
+use symphonia::core::{
+    audio::{AudioBuffer, Signal as _},
+    io::MediaSourceStream,
+    probe::Hint,
+};
+use thiserror::Error;
+
+use super::{
+    track::{get_audio_bytes, get_track_source, GetTrackBytesError},
+    track_bytes_media_source::TrackBytesMediaSource,
+};
+use crate::sanitize_filename;
+
+/// The number of samples in each frame of the spectrogram.
+pub const FFT_SIZE: usize = 4096;
+
+/// The number of frequency bands the spectrum is summarized in, evenly spaced
+/// from 0 Hz to the Nyquist frequency.
+pub const BANDS: usize = 256;
+
+const BINS_PER_BAND: usize = FFT_SIZE / 2 / BANDS;
+
+/// Columns of the spectrogram are merged in pairs whenever it reaches this
+/// many, so long tracks take up as much space as short ones.
+const MAX_COLUMNS: usize = 1024;
+
+const MIN_DB: f32 = -150.0;
+
+/// How far above the noise floor a band must be to count as content.
+const FLOOR_MARGIN_DB: f32 = 20.0;
+
+/// How far the level must drop within [`CLIFF_HZ`] above the cutoff for it to
+/// be a lowpass filter rather than the natural rolloff of the recording.
+const CLIFF_DB: f32 = 20.0;
+const CLIFF_HZ: f32 = 1000.0;
+
+/// Lossy encoders lowpass their input below this frequency, while lossless CD
+/// audio extends up to its Nyquist frequency.
+const LOSSY_CUTOFF_HZ: f32 = 20_500.0;
+
+/// The sample rates upsampled files were most likely mastered in.
+const SOURCE_SAMPLE_RATES: [u32; 4] = [44_100, 48_000, 88_200, 96_000];
+
+/// The range of levels below the loudest band rendered in spectrogram images.
+const SPECTROGRAM_RANGE_DB: f32 = 120.0;
+
+const STORE_MAGIC: &[u8; 4] = b"MBSA";
+const STORE_VERSION: u8 = 1;
+
+#[derive(Debug, Error)]
+pub enum AnalysisError {
+    #[error(transparent)]
+    IO(#[from] std::io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Tracks(#[from] TracksError),
+    #[error(transparent)]
+    GetTrackBytes(#[from] Box<GetTrackBytesError>),
+    #[error(transparent)]
+    Decode(#[from] DecodeError),
+    #[error("No audio was decoded")]
+    NoAudio,
+    #[error("Invalid stored analysis")]
+    InvalidStore,
+    #[error("Invalid image dimensions: {0}x{1}")]
+    InvalidDimensions(u32, u32),
+    #[error("Failed to encode image: {0}")]
+    Image(String),
+}
+
+/// A sign that a track isn't what its format claims it is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum AnalysisFlag {
+    /// The spectrum has the sharp lowpass cutoff of a lossy encoder.
+    LossySource,
+    /// The spectrum stops at the band limit of a lower sample rate.
+    Upsampled,
+    /// The lowest bits of every sample are zero.
+    PaddedBitDepth,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TrackAnalysis {
+    pub sample_rate: u32,
+    pub channels: u8,
+    pub duration: f64,
+    /// The bit depth the track is encoded with, if it is lossless.
+    pub bit_depth: Option<u8>,
+    /// The number of bits used by the samples, counting from the most
+    /// significant bit to the lowest bit set in any sample.
+    pub effective_bit_depth: Option<u8>,
+    /// The average level in dB of the quietest band above 1 kHz.
+    pub noise_floor: f32,
+    /// The highest frequency in Hz with content above the noise floor.
+    pub cutoff_frequency: Option<f32>,
+    /// The drop in dB of the level over the kHz above the cutoff.
+    pub cutoff_drop: Option<f32>,
+    /// The sample rate the track was most likely upsampled from.
+    pub likely_source_sample_rate: Option<u32>,
+    pub flags: Vec<AnalysisFlag>,
+    /// The average level in dB of each frequency band.
+    pub spectrum: Vec<f32>,
+}
+
+/// The levels of the frequency bands of a track over time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spectrogram {
+    pub seconds_per_column: f64,
+    /// The level of each band of each column, column by column, from
+    /// -150 dB at 0 to 0 dB at 255.
+    pub levels: Vec<u8>,
+}
+
+impl Spectrogram {
+    #[must_use]
+    pub fn columns(&self) -> usize {
+        self.levels.len() / BANDS
+    }
+
+    /// Renders the spectrogram as 8-bit RGB pixels, row by row, with time
+    /// running left to right and frequency bottom to top.
+    #[must_use]
+    pub fn to_rgb(&self, width: u32, height: u32) -> Vec<u8> {
+        let columns = self.columns();
+        let top = self.levels.iter().copied().max().unwrap_or(0);
+        let top = level_to_db(top);
+
+        let mut pixels = Vec::with_capacity(width as usize * height as usize * 3);
+
+        for y in 0..height as usize {
+            let band = (height as usize - 1 - y) * BANDS / height as usize;
+
+            for x in 0..width as usize {
+                if columns == 0 {
+                    pixels.extend_from_slice(&[0, 0, 0]);
+                    continue;
+                }
+
+                let column = x * columns / width as usize;
+                let db = level_to_db(self.levels[column * BANDS + band]);
+                let intensity = (db - top + SPECTROGRAM_RANGE_DB) / SPECTROGRAM_RANGE_DB;
+                pixels.extend_from_slice(&heat_color(intensity));
+            }
+        }
+
+        pixels
+    }
+
+    /// Renders the spectrogram as a PNG.
+    ///
+    /// # Errors
+    ///
+    /// * If the dimensions are empty
+    /// * If the image failed to be encoded
+    #[cfg(feature = "image")]
+    pub fn to_png(&self, width: u32, height: u32) -> Result<bytes::Bytes, AnalysisError> {
+        if width == 0 || height == 0 {
+            return Err(AnalysisError::InvalidDimensions(width, height));
+        }
+
+        moosicbox_image::image::encode_rgb_png(width, height, self.to_rgb(width, height))
+            .map_err(|e| AnalysisError::Image(e.to_string()))
+    }
+}
+
+/// Maps an intensity from 0 to 1 through black, blue, purple, red, orange,
+/// yellow and white.
+fn heat_color(intensity: f32) -> [u8; 3] {
+    const STOPS: [[f32; 3]; 7] = [
+        [0.0, 0.0, 0.0],
+        [0.0, 0.0, 100.0],
+        [120.0, 0.0, 140.0],
+        [220.0, 30.0, 30.0],
+        [255.0, 140.0, 0.0],
+        [255.0, 230.0, 0.0],
+        [255.0, 255.0, 255.0],
+    ];
+
+    let position = intensity.clamp(0.0, 1.0) * (STOPS.len() - 1) as f32;
+    let index = (position.floor() as usize).min(STOPS.len() - 2);
+    let fraction = position - index as f32;
+    let (from, to) = (STOPS[index], STOPS[index + 1]);
+
+    [0, 1, 2].map(|i| (from[i] + (to[i] - from[i]) * fraction).round() as u8)
+}
+
+fn to_db(power: f32) -> f32 {
+    (10.0 * power.log10()).clamp(MIN_DB, 0.0)
+}
+
+fn db_to_level(db: f32) -> u8 {
+    ((db - MIN_DB) / -MIN_DB * 255.0).round() as u8
+}
+
+fn level_to_db(level: u8) -> f32 {
+    f32::from(level) / 255.0 * -MIN_DB + MIN_DB
+}
+
+/// A track's analysis and the spectrogram it was computed from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Analysis {
+    pub report: TrackAnalysis,
+    pub spectrogram: Spectrogram,
+}
+
+impl Analysis {
+    fn to_store(&self) -> Result<Vec<u8>, AnalysisError> {
+        let report = serde_json::to_vec(&self.report)?;
+
+        let mut data = Vec::with_capacity(17 + report.len() + self.spectrogram.levels.len());
+        data.extend_from_slice(STORE_MAGIC);
+        data.push(STORE_VERSION);
+        data.extend_from_slice(&(report.len() as u32).to_le_bytes());
+        data.extend_from_slice(&report);
+        data.extend_from_slice(&self.spectrogram.seconds_per_column.to_le_bytes());
+        data.extend_from_slice(&self.spectrogram.levels);
+
+        Ok(data)
+    }
+
+    fn from_store(data: &[u8]) -> Result<Self, AnalysisError> {
+        let header = STORE_MAGIC.len() + 5;
+
+        if data.len() < header
+            || &data[..STORE_MAGIC.len()] != STORE_MAGIC
+            || data[STORE_MAGIC.len()] != STORE_VERSION
+        {
+            return Err(AnalysisError::InvalidStore);
+        }
+
+        let length = u32::from_le_bytes(data[header - 4..header].try_into().unwrap()) as usize;
+        let data = &data[header..];
+
+        if data.len() < length + 8 || !(data.len() - length - 8).is_multiple_of(BANDS) {
+            return Err(AnalysisError::InvalidStore);
+        }
+
+        let report = serde_json::from_slice(&data[..length])?;
+        let seconds_per_column = f64::from_le_bytes(data[length..length + 8].try_into().unwrap());
+
+        Ok(Self {
+            report,
+            spectrogram: Spectrogram {
+                seconds_per_column,
+                levels: data[length + 8..].to_vec(),
+            },
+        })
+    }
+}
+
+/// Accumulates the spectrum and sample statistics of decoded audio.
+struct AnalysisBuilder {
+    sample_rate: u32,
+    channels: usize,
+    bit_depth: Option<u8>,
+    /// Whether every sample has been an integer at the bit depth.
+    exact: bool,
+    used_bits: u32,
+    samples: u64,
+    fft: Arc<dyn Fft<f32>>,
+    window: Vec<f32>,
+    frame: Vec<f32>,
+    buffer: Vec<Complex<f32>>,
+    frames: u64,
+    spectrum: Vec<f64>,
+    column: Vec<f64>,
+    column_frames: usize,
+    frames_per_column: usize,
+    columns: Vec<f32>,
+}
+
+impl AnalysisBuilder {
+    fn new(sample_rate: u32, channels: usize, bit_depth: Option<u32>) -> Self {
+        let window = (0..FFT_SIZE)
+            .map(|i| {
+                0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / (FFT_SIZE - 1) as f32).cos()
+            })
+            .collect();
+
+        Self {
+            sample_rate,
+            channels,
+            // Decoded samples are only exact integers at up to 24 bits
+            bit_depth: bit_depth.filter(|x| (1..=24).contains(x)).map(|x| x as u8),
+            exact: true,
+            used_bits: 0,
+            samples: 0,
+            fft: FftPlanner::new().plan_fft_forward(FFT_SIZE),
+            window,
+            frame: Vec::with_capacity(FFT_SIZE),
+            buffer: vec![Complex::default(); FFT_SIZE],
+            frames: 0,
+            spectrum: vec![0.0; BANDS],
+            column: vec![0.0; BANDS],
+            column_frames: 0,
+            frames_per_column: 1,
+            columns: vec![],
+        }
+    }
+
+    fn push(&mut self, channels: &[&[f32]]) {
+        let length = channels.iter().map(|x| x.len()).min().unwrap_or(0);
+        let scale = self.bit_depth.map(|x| (1_u32 << (x - 1)) as f32);
+
+        for i in 0..length {
+            let mut sum = 0.0;
+
+            for channel in channels {
+                let sample = channel[i];
+                sum += sample;
+
+                if let (Some(scale), true) = (scale, self.exact) {
+                    let value = sample * scale;
+                    let rounded = value.round();
+
+                    if (value - rounded).abs() > 1e-3 {
+                        self.exact = false;
+                    } else {
+                        self.used_bits |= rounded as i32 as u32;
+                    }
+                }
+            }
+
+            self.frame.push(sum / channels.len() as f32);
+
+            if self.frame.len() == FFT_SIZE {
+                self.push_frame();
+            }
+        }
+
+        self.samples += length as u64;
+    }
+
+    fn push_frame(&mut self) {
+        for (out, (sample, window)) in self
+            .buffer
+            .iter_mut()
+            .zip(self.frame.iter().zip(&self.window))
+        {
+            *out = Complex::new(sample * window, 0.0);
+        }
+        self.frame.clear();
+
+        self.fft.process(&mut self.buffer);
+
+        // A full scale sine at the center of a bin is at 0 dB
+        let scale = (FFT_SIZE as f32 / 4.0).powi(2);
+
+        for (band, bins) in self.buffer[..FFT_SIZE / 2]
+            .chunks(BINS_PER_BAND)
+            .enumerate()
+        {
+            let power = f64::from(bins.iter().map(Complex::norm_sqr).sum::<f32>() / scale);
+            self.spectrum[band] += power;
+            self.column[band] += power;
+        }
+
+        self.frames += 1;
+        self.column_frames += 1;
+
+        if self.column_frames == self.frames_per_column {
+            self.push_column();
+        }
+    }
+
+    fn push_column(&mut self) {
+        let frames = self.column_frames as f64;
+        self.columns
+            .extend(self.column.iter().map(|x| (x / frames) as f32));
+        self.column.fill(0.0);
+        self.column_frames = 0;
+
+        if self.columns.len() == MAX_COLUMNS * BANDS {
+            self.columns = self
+                .columns
+                .chunks(BANDS * 2)
+                .flat_map(|pair| {
+                    let (first, second) = pair.split_at(BANDS);
+                    first.iter().zip(second).map(|(a, b)| (a + b) / 2.0)
+                })
+                .collect();
+            self.frames_per_column *= 2;
+        }
+    }
+
+    fn finish(mut self) -> Result<Analysis, AnalysisError> {
+        // Tracks shorter than a frame are analyzed zero padded
+        if self.frames == 0 && !self.frame.is_empty() {
+            self.frame.resize(FFT_SIZE, 0.0);
+            self.push_frame();
+        }
+        if self.frames == 0 {
+            return Err(AnalysisError::NoAudio);
+        }
+
+        let seconds_per_column =
+            (self.frames_per_column * FFT_SIZE) as f64 / f64::from(self.sample_rate);
+
+        if self.column_frames > 0 {
+            self.push_column();
+        }
+
+        let frames = self.frames as f64;
+        let spectrum = self
+            .spectrum
+            .iter()
+            .map(|x| to_db((x / frames) as f32))
+            .collect::<Vec<_>>();
+
+        let band_width = self.sample_rate as f32 / 2.0 / BANDS as f32;
+        let (noise_floor, cutoff_band) = estimate_cutoff(&spectrum, band_width);
+        let cliff_bands = ((CLIFF_HZ / band_width).ceil() as usize).max(1);
+        let cutoff_drop = cutoff_band.map(|band| cutoff_drop(&spectrum, band, cliff_bands));
+        let cutoff_frequency = cutoff_band.map(|band| (band + 1) as f32 * band_width);
+
+        let mut flags = vec![];
+        let mut likely_source_sample_rate = None;
+
+        if let (Some(cutoff), Some(drop)) = (cutoff_frequency, cutoff_drop) {
+            let nyquist = self.sample_rate as f32 / 2.0;
+
+            if cutoff < LOSSY_CUTOFF_HZ && cutoff < nyquist - band_width && drop >= CLIFF_DB {
+                flags.push(AnalysisFlag::LossySource);
+            }
+
+            likely_source_sample_rate = SOURCE_SAMPLE_RATES
+                .into_iter()
+                .filter(|x| *x < self.sample_rate)
+                .find(|x| cutoff <= *x as f32 / 2.0 + band_width);
+
+            if likely_source_sample_rate.is_some() {
+                flags.push(AnalysisFlag::Upsampled);
+            }
+        }
+
+        let effective_bit_depth = self
+            .bit_depth
+            .filter(|_| self.exact && self.used_bits != 0)
+            .map(|x| x - self.used_bits.trailing_zeros().min(u32::from(x)) as u8);
+
+        if let (Some(bit_depth), Some(effective)) = (self.bit_depth, effective_bit_depth) {
+            if effective < bit_depth {
+                flags.push(AnalysisFlag::PaddedBitDepth);
+            }
+        }
+
+        Ok(Analysis {
+            report: TrackAnalysis {
+                sample_rate: self.sample_rate,
+                channels: self.channels as u8,
+                duration: self.samples as f64 / f64::from(self.sample_rate),
+                bit_depth: self.bit_depth,
+                effective_bit_depth,
+                noise_floor,
+                cutoff_frequency,
+                cutoff_drop,
+                likely_source_sample_rate,
+                flags,
+                spectrum,
+            },
+            spectrogram: Spectrogram {
+                seconds_per_column,
+                levels: self
+                    .columns
+                    .into_iter()
+                    .map(|x| db_to_level(to_db(x)))
+                    .collect(),
+            },
+        })
+    }
+}
+
+/// Returns the noise floor, the level of the quietest band above 1 kHz, and
+/// the highest band well above it. A spectrum that is flat up to the Nyquist
+/// frequency has no band above the floor, so its cutoff is the last band. A
+/// silent spectrum has no cutoff.
+fn estimate_cutoff(spectrum: &[f32], band_width: f32) -> (f32, Option<usize>) {
+    let first = ((1000.0 / band_width) as usize).min(spectrum.len() - 1);
+    let floor = spectrum[first..]
+        .iter()
+        .copied()
+        .fold(f32::INFINITY, f32::min);
+
+    if spectrum.iter().all(|x| *x <= MIN_DB + FLOOR_MARGIN_DB) {
+        return (floor, None);
+    }
+
+    (
+        floor,
+        Some(
+            spectrum
+                .iter()
+                .rposition(|x| *x > floor + FLOOR_MARGIN_DB)
+                .unwrap_or(spectrum.len() - 1),
+        ),
+    )
+}
+
+/// The difference between the average level of the bands up to the cutoff
+/// and the bands above it.
+fn cutoff_drop(spectrum: &[f32], cutoff: usize, bands: usize) -> f32 {
+    fn average(levels: &[f32]) -> Option<f32> {
+        if levels.is_empty() {
+            return None;
+        }
+        Some(levels.iter().sum::<f32>() / levels.len() as f32)
+    }
+
+    let below = average(&spectrum[(cutoff + 1).saturating_sub(bands)..=cutoff]);
+    let above = average(&spectrum[cutoff + 1..(cutoff + 1 + bands).min(spectrum.len())]);
+
+    match (below, above) {
+        (Some(below), Some(above)) => below - above,
+        _ => 0.0,
+    }
+}
+
+/// The path of the stored analysis of the track, if it has an ID to key it
+/// by.
+fn store_path(source: &TrackSource) -> Option<PathBuf> {
+    let (api_source, track_id) = match source {
+        TrackSource::LocalFilePath {
+            source, track_id, ..
+        }
+        | TrackSource::RemoteUrl {
+            source, track_id, ..
+        } => (source, track_id.as_ref()?),
+    };
+
+    Some(
+        moosicbox_config::make_cache_dir_path()?
+            .join("analyses")
+            .join(api_source.as_ref())
+            .join(format!(
+                "{}.analysis",
+                sanitize_filename(&track_id.to_string())
+            )),
+    )
+}
+
+async fn read_stored_analysis(path: &PathBuf, source: &TrackSource) -> Option<Analysis> {
+    let stored = tokio::fs::metadata(path).await.ok()?;
+
+    // Reanalyze local files changed since the analysis was stored
+    if let TrackSource::LocalFilePath { path, .. } = source {
+        let modified = tokio::fs::metadata(path).await.ok()?.modified().ok()?;
+        if stored.modified().ok()? < modified {
+            return None;
+        }
+    }
+
+    let data = tokio::fs::read(path).await.ok()?;
+
+    match Analysis::from_store(&data) {
+        Ok(analysis) => Some(analysis),
+        Err(e) => {
+            log::warn!("Ignoring stored analysis {path:?}: {e:?}");
+            None
+        }
+    }
+}
+
+/// Decodes the track and analyzes its spectrum and samples.
+///
+/// # Errors
+///
+/// * If the track failed to be fetched or decoded
+/// * If the track has no audio
+pub async fn analyze_track(source: &TrackSource) -> Result<Analysis, AnalysisError> {
+    let builder: Arc<Mutex<Option<AnalysisBuilder>>> = Arc::new(Mutex::new(None));
+    let inner_builder = builder.clone();
+
+    let bytes = get_audio_bytes(
+        source.clone(),
+        source.format(),
+        EncoderSettings::default(),
+        None,
+        None,
+        None,
+    )
+    .await
+    .map_err(Box::new)?;
+
+    let get_handler = move || {
+        Ok(
+            moosicbox_audio_decoder::AudioDecodeHandler::new().with_filter(Box::new(
+                move |decoded: &mut AudioBuffer<f32>, _packet, track| {
+                    let spec = decoded.spec();
+                    let mut builder = inner_builder.lock().unwrap();
+                    let builder = builder.get_or_insert_with(|| {
+                        AnalysisBuilder::new(
+                            spec.rate,
+                            spec.channels.count(),
+                            track.codec_params.bits_per_sample,
+                        )
+                    });
+                    let channels = (0..spec.channels.count())
+                        .map(|c| decoded.chan(c))
+                        .collect::<Vec<_>>();
+                    builder.push(&channels);
+                    Ok(())
+                },
+            )),
+        )
+    };
+
+    let hint = Hint::new();
+    let media_source = TrackBytesMediaSource::new(bytes);
+    let mss = MediaSourceStream::new(Box::new(media_source), Default::default());
+
+    decode_media_source_async(mss, &hint, get_handler, true, true, None, None).await?;
+
+    let builder = builder.lock().unwrap().take();
+    builder.ok_or(AnalysisError::NoAudio)?.finish()
+}
+
+/// Returns the stored analysis of the track, analyzing it and storing the
+/// analysis if it hasn't been yet.
+///
+/// # Errors
+///
+/// * If the track failed to be analyzed
+pub async fn get_or_init_analysis(source: &TrackSource) -> Result<Analysis, AnalysisError> {
+    let path = store_path(source);
+
+    if let Some(path) = &path {
+        if let Some(analysis) = read_stored_analysis(path, source).await {
+            return Ok(analysis);
+        }
+    }
+
+    log::debug!(
+        "Analyzing track track_id={:?} path={path:?}",
+        source.track_id()
+    );
+
+    let analysis = analyze_track(source).await?;
+
+    if let Some(path) = path {
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        // Write to a temporary file first so concurrent readers never see a
+        // partially written analysis
+        let temp = path.with_extension(format!("{}.tmp", std::process::id()));
+        tokio::fs::write(&temp, analysis.to_store()?).await?;
+        tokio::fs::rename(&temp, &path).await?;
+    }
+
+    Ok(analysis)
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AlbumTrackAnalysis {
+    pub track_id: Id,
+    pub number: u32,
+    pub title: String,
+    pub source: TrackApiSource,
+    pub format: Option<AudioFormat>,
+    pub analysis: Option<TrackAnalysis>,
+    /// Why the track couldn't be analyzed.
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AlbumAnalysis {
+    pub album_id: Id,
+    pub title: String,
+    pub artist: String,
+    /// Every flag raised for any of the tracks.
+    pub flags: Vec<AnalysisFlag>,
+    pub tracks: Vec<AlbumTrackAnalysis>,
+}
+
+/// Analyzes every track of every version of the album. Tracks that fail to
+/// be analyzed are reported with the error instead of failing the report.
+///
+/// # Errors
+///
+/// * If the album tracks failed to be fetched
+pub async fn get_album_analysis(
+    api: &dyn MusicApi,
+    album: &Album,
+    quality: Option<TrackAudioQuality>,
+) -> Result<AlbumAnalysis, AnalysisError> {
+    let tracks = api
+        .album_tracks(&album.id, None, None, None, None)
+        .await?
+        .with_rest_of_items_in_batches()
+        .await?;
+
+    let mut flags = vec![];
+    let mut analyses = Vec::with_capacity(tracks.len());
+
+    for track in &tracks {
+        let analysis = match get_track_source(api, track, quality).await {
+            Ok(source) => get_or_init_analysis(&source)
+                .await
+                .map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+
+        let (analysis, error) = match analysis {
+            Ok(analysis) => {
+                for flag in &analysis.report.flags {
+                    if !flags.contains(flag) {
+                        flags.push(*flag);
+                    }
+                }
+                (Some(analysis.report), None)
+            }
+            Err(e) => {
+                log::warn!("Failed to analyze track {}: {e}", track.id);
+                (None, Some(e))
+            }
+        };
+
+        analyses.push(AlbumTrackAnalysis {
+            track_id: track.id.clone(),
+            number: track.number,
+            title: track.title.clone(),
+            source: track.source,
+            format: track.format,
+            analysis,
+            error,
+        });
+    }
+
+    Ok(AlbumAnalysis {
+        album_id: album.id.clone(),
+        title: album.title.clone(),
+        artist: album.artist.clone(),
+        flags,
+        tracks: analyses,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    /// Deterministic white noise in the range [-amplitude, amplitude].
+    fn noise(length: usize, amplitude: f32) -> Vec<f32> {
+        let mut state = 0x1234_5678_u32;
+        (0..length)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                (state as f32 / u32::MAX as f32 * 2.0 - 1.0) * amplitude
+            })
+            .collect()
+    }
+
+    /// Removes everything above the cutoff frequency with two passes of a
+    /// windowed sinc lowpass filter.
+    fn lowpass(samples: &[f32], sample_rate: u32, cutoff: f32) -> Vec<f32> {
+        lowpass_pass(
+            &lowpass_pass(samples, sample_rate, cutoff),
+            sample_rate,
+            cutoff,
+        )
+    }
+
+    fn lowpass_pass(samples: &[f32], sample_rate: u32, cutoff: f32) -> Vec<f32> {
+        const TAPS: isize = 511;
+        let fc = cutoff / sample_rate as f32;
+        let kernel = (0..TAPS)
+            .map(|i| {
+                let n = (i - TAPS / 2) as f32;
+                let sinc = if n == 0.0 {
+                    2.0 * fc
+                } else {
+                    (2.0 * std::f32::consts::PI * fc * n).sin() / (std::f32::consts::PI * n)
+                };
+                let window = 0.42
+                    - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / (TAPS - 1) as f32).cos()
+                    + 0.08 * (4.0 * std::f32::consts::PI * i as f32 / (TAPS - 1) as f32).cos();
+                sinc * window
+            })
+            .collect::<Vec<_>>();
+
+        (0..samples.len())
+            .map(|i| {
+                kernel
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(k, x)| {
+                        let j = i as isize + k as isize - TAPS / 2;
+                        samples.get(usize::try_from(j).ok()?).map(|s| s * x)
+                    })
+                    .sum()
+            })
+            .collect()
+    }
+
+    fn quantize(samples: &[f32], bits: u8) -> Vec<f32> {
+        let scale = (1_u32 << (bits - 1)) as f32;
+        samples
+            .iter()
+            .map(|x| (x * scale).round() / scale)
+            .collect()
+    }
+
+    fn analyze(samples: &[f32], sample_rate: u32, bit_depth: Option<u32>) -> Analysis {
+        let mut builder = AnalysisBuilder::new(sample_rate, 2, bit_depth);
+        for chunk in samples.chunks(1152) {
+            builder.push(&[chunk, chunk]);
+        }
+        builder.finish().unwrap()
+    }
+
+    #[test_log::test]
+    fn full_band_noise_isnt_flagged() {
+        let samples = quantize(&noise(44_100 * 2, 0.5), 16);
+        let report = analyze(&samples, 44_100, Some(16)).report;
+
+        assert_eq!(report.flags, vec![]);
+        assert_eq!(report.effective_bit_depth, Some(16));
+        assert!(report.cutoff_frequency.unwrap() > 21_500.0);
+    }
+
+    #[test_log::test]
+    fn lowpassed_noise_is_flagged_as_lossy() {
+        let samples = lowpass(&noise(44_100, 0.5), 44_100, 16_000.0);
+        let report = analyze(&quantize(&samples, 16), 44_100, Some(16)).report;
+
+        assert_eq!(report.flags, vec![AnalysisFlag::LossySource]);
+        let cutoff = report.cutoff_frequency.unwrap();
+        assert!((15_500.0..17_000.0).contains(&cutoff), "cutoff={cutoff}");
+    }
+
+    #[test_log::test]
+    fn band_limited_hi_res_is_flagged_as_upsampled() {
+        let samples = lowpass(&noise(96_000, 0.5), 96_000, 21_000.0);
+        let report = analyze(&quantize(&samples, 24), 96_000, Some(24)).report;
+
+        assert_eq!(report.flags, vec![AnalysisFlag::Upsampled]);
+        assert_eq!(report.likely_source_sample_rate, Some(44_100));
+        assert_eq!(report.effective_bit_depth, Some(24));
+    }
+
+    #[test_log::test]
+    fn silence_has_no_cutoff() {
+        let report = analyze(&vec![0.0; FFT_SIZE * 2], 44_100, Some(16)).report;
+
+        assert_eq!(report.flags, vec![]);
+        assert_eq!(report.cutoff_frequency, None);
+        assert_eq!(report.effective_bit_depth, None);
+    }
+
+    #[test_log::test]
+    fn padded_bit_depth_is_flagged() {
+        let samples = quantize(&noise(44_100, 0.5), 16);
+        let report = analyze(&samples, 44_100, Some(24)).report;
+
+        assert_eq!(report.flags, vec![AnalysisFlag::PaddedBitDepth]);
+        assert_eq!(report.bit_depth, Some(24));
+        assert_eq!(report.effective_bit_depth, Some(16));
+    }
+
+    #[test_log::test]
+    fn spectrogram_columns_are_merged() {
+        let samples = noise(FFT_SIZE * (MAX_COLUMNS + 10), 0.5);
+        let spectrogram = analyze(&samples, 44_100, None).spectrogram;
+
+        assert_eq!(spectrogram.columns(), MAX_COLUMNS / 2 + 5);
+        assert_eq!(
+            spectrogram.seconds_per_column,
+            (2 * FFT_SIZE) as f64 / 44_100.0
+        );
+    }
+
+    #[test_log::test]
+    fn store_round_trips() {
+        let analysis = analyze(&noise(FFT_SIZE * 3, 0.5), 48_000, None);
+
+        assert_eq!(
+            Analysis::from_store(&analysis.to_store().unwrap()).unwrap(),
+            analysis
+        );
+        assert!(Analysis::from_store(b"MBSA").is_err());
+    }
+
+    #[test_log::test]
+    fn renders_rgb_pixels() {
+        let spectrogram = analyze(&noise(FFT_SIZE * 4, 0.5), 44_100, None).spectrogram;
+
+        assert_eq!(spectrogram.to_rgb(16, 8).len(), 16 * 8 * 3);
+        assert_eq!(heat_color(0.0), [0, 0, 0]);
+        assert_eq!(heat_color(1.0), [255, 255, 255]);
+    }
+}
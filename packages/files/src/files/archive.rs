@@ -0,0 +1,1042 @@
+//! Album archives for downloading a whole album at once.
+//!
+//! ZIP archives are written in store mode, without compression, since the
+//! audio is already compressed. When every entry is a file in its source
+//! format, the archive layout is computed up front so its size is known and
+//! any byte range of it can be streamed. Transcoded tracks are encoded one at
+//! a time while the archive streams, so those archives have no known size.
+//!
+//! ZIP entries and archives that don't fit the 32 bit sizes and offsets of
+//! the original ZIP format are written with the ZIP64 extensions.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{LazyLock, Mutex},
+    time::SystemTime,
+};
+
+use bytes::{Bytes, BytesMut};
+use futures::{stream, StreamExt as _, TryStreamExt as _};
+use moosicbox_audio_encoder::EncoderSettings;
+use moosicbox_core::{
+    sqlite::models::{Album, Track},
+    types::AudioFormat,
+};
+use moosicbox_database::profiles::LibraryDatabase;
+use moosicbox_music_api::{ImageCoverSize, MusicApi, TrackAudioQuality, TrackSource, TracksError};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::io::{AsyncReadExt as _, AsyncSeekExt as _};
+use tokio_util::codec::{BytesCodec, FramedRead};
+
+use super::{
+    album::get_album_cover,
+    track::{get_audio_bytes, get_track_source, BytesStream, GetTrackBytesError, TrackSourceError},
+};
+
+const TAR_BLOCK_SIZE: usize = 512;
+const TAR_NAME_LENGTH: usize = 100;
+const TAR_PREFIX_LENGTH: usize = 155;
+
+/// The date of the entries in the DOS format, 1980-01-01.
+const ZIP_DOS_DATE: u16 = (1 << 5) | 1;
+/// The general purpose flag for UTF-8 entry names.
+const ZIP_UTF8_FLAG: u16 = 1 << 11;
+const ZIP_VERSION: u16 = 10;
+/// The version needed to extract entries with ZIP64 extensions.
+const ZIP64_VERSION: u16 = 45;
+/// The value of the 32 bit sizes and offsets that are stored in the ZIP64
+/// extra field or end of central directory record instead.
+const ZIP64_MARKER: u32 = u32::MAX;
+/// The ID of the ZIP64 extended information extra field.
+const ZIP64_EXTRA_ID: u16 = 0x0001;
+
+/// The checksums of the files archived so far, with the modification time
+/// they were computed at, so the files of an album are only read once to
+/// compute its archive layout.
+static FILE_CRCS: LazyLock<Mutex<HashMap<PathBuf, (SystemTime, u32)>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum ArchiveFormat {
+    #[default]
+    Zip,
+    Tar,
+}
+
+impl ArchiveFormat {
+    #[must_use]
+    pub const fn content_type(self) -> &'static str {
+        match self {
+            Self::Zip => "application/zip",
+            Self::Tar => "application/x-tar",
+        }
+    }
+
+    #[must_use]
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::Zip => "zip",
+            Self::Tar => "tar",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum ArchivePlaylist {
+    M3u,
+    Cue,
+}
+
+#[derive(Debug, Error)]
+pub enum ArchiveError {
+    #[error(transparent)]
+    IO(#[from] std::io::Error),
+    #[error(transparent)]
+    Tracks(#[from] TracksError),
+    #[error(transparent)]
+    TrackSource(#[from] TrackSourceError),
+    #[error(transparent)]
+    GetTrackBytes(#[from] GetTrackBytesError),
+    #[error("Album has no tracks")]
+    NoTracks,
+    #[error("Entry name is too long: {0}")]
+    NameTooLong(String),
+    #[error("Entry is too large for the archive format")]
+    TooLarge,
+}
+
+#[derive(Debug, Clone)]
+pub enum ArchiveEntryData {
+    Bytes(Bytes),
+    File {
+        path: PathBuf,
+        size: u64,
+    },
+    /// A track that is encoded when the archive reaches it.
+    Track {
+        source: TrackSource,
+        format: AudioFormat,
+        settings: EncoderSettings,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct ArchiveEntry {
+    pub name: String,
+    pub data: ArchiveEntryData,
+}
+
+#[derive(Debug, Clone)]
+enum Part {
+    Bytes(Bytes),
+    File { path: PathBuf, size: u64 },
+}
+
+impl Part {
+    fn size(&self) -> u64 {
+        match self {
+            Self::Bytes(bytes) => bytes.len() as u64,
+            Self::File { size, .. } => *size,
+        }
+    }
+}
+
+struct ZipRecord {
+    name: String,
+    size: u64,
+    crc: u32,
+    offset: u64,
+}
+
+impl ZipRecord {
+    const fn is_zip64(&self) -> bool {
+        self.size >= ZIP64_MARKER as u64 || self.offset >= ZIP64_MARKER as u64
+    }
+}
+
+/// Writes the headers around the entry data.
+struct ArchiveWriter {
+    format: ArchiveFormat,
+    offset: u64,
+    records: Vec<ZipRecord>,
+}
+
+impl ArchiveWriter {
+    const fn new(format: ArchiveFormat) -> Self {
+        Self {
+            format,
+            offset: 0,
+            records: vec![],
+        }
+    }
+
+    /// Returns the header to write before the entry data and the padding to
+    /// write after it.
+    fn entry(&mut self, name: &str, size: u64, crc: u32) -> Result<(Bytes, Bytes), ArchiveError> {
+        let (header, padding) = match self.format {
+            ArchiveFormat::Zip => {
+                let record = ZipRecord {
+                    name: name.to_string(),
+                    size,
+                    crc,
+                    offset: self.offset,
+                };
+                let header = zip_local_header(&record)?;
+                self.records.push(record);
+                (header, Bytes::new())
+            }
+            ArchiveFormat::Tar => (
+                tar_header(name, size)?,
+                Bytes::from(vec![0; tar_padding(size)]),
+            ),
+        };
+
+        self.offset += header.len() as u64 + size + padding.len() as u64;
+
+        Ok((header, padding))
+    }
+
+    fn finish(self) -> Bytes {
+        match self.format {
+            ArchiveFormat::Zip => zip_central_directory(&self.records, self.offset),
+            ArchiveFormat::Tar => Bytes::from(vec![0; TAR_BLOCK_SIZE * 2]),
+        }
+    }
+}
+
+/// An archive whose size is known, so any byte range of it can be streamed.
+#[derive(Debug, Clone)]
+pub struct ArchiveLayout {
+    parts: Vec<Part>,
+}
+
+impl ArchiveLayout {
+    #[must_use]
+    pub fn size(&self) -> u64 {
+        self.parts.iter().map(Part::size).sum()
+    }
+
+    /// Streams the bytes from `start` to `end`, inclusive.
+    #[must_use]
+    pub fn stream(self, start: u64, end: u64) -> BytesStream {
+        let mut offset = 0;
+        let ranges = self
+            .parts
+            .into_iter()
+            .filter_map(|part| {
+                let size = part.size();
+                let part_start = offset;
+                offset += size;
+
+                if size == 0 || part_start > end || offset <= start {
+                    return None;
+                }
+
+                let from = start.saturating_sub(part_start);
+                let to = (end + 1 - part_start).min(size);
+                Some((part, from, to))
+            })
+            .collect::<Vec<_>>();
+
+        stream::iter(ranges)
+            .then(|(part, from, to)| async move {
+                match part {
+                    Part::Bytes(bytes) => {
+                        let bytes = bytes.slice(from as usize..to as usize);
+                        stream::once(async { Ok(bytes) }).boxed()
+                    }
+                    Part::File { path, .. } => match read_file_range(&path, from, to).await {
+                        Ok(stream) => stream,
+                        Err(e) => stream::once(async { Err(e) }).boxed(),
+                    },
+                }
+            })
+            .flatten()
+            .boxed()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Archive {
+    format: ArchiveFormat,
+    entries: Vec<ArchiveEntry>,
+}
+
+impl Archive {
+    #[must_use]
+    pub const fn new(format: ArchiveFormat, entries: Vec<ArchiveEntry>) -> Self {
+        Self { format, entries }
+    }
+
+    #[must_use]
+    pub const fn format(&self) -> ArchiveFormat {
+        self.format
+    }
+
+    /// Returns the layout of the archive, or `None` if it has tracks to be
+    /// transcoded, whose sizes aren't known until they're encoded.
+    ///
+    /// # Errors
+    ///
+    /// * If the checksum of a file failed to be computed
+    /// * If an entry doesn't fit in the archive format
+    pub async fn layout(&self) -> Result<Option<ArchiveLayout>, ArchiveError> {
+        if self
+            .entries
+            .iter()
+            .any(|x| matches!(x.data, ArchiveEntryData::Track { .. }))
+        {
+            return Ok(None);
+        }
+
+        let mut writer = ArchiveWriter::new(self.format);
+        let mut parts = vec![];
+
+        for entry in &self.entries {
+            let (part, crc) = match &entry.data {
+                ArchiveEntryData::Bytes(bytes) => (Part::Bytes(bytes.clone()), crc(bytes)),
+                ArchiveEntryData::File { path, size } => {
+                    // Only ZIP entries have a checksum
+                    let crc = match self.format {
+                        ArchiveFormat::Zip => cached_file_crc(path).await?,
+                        ArchiveFormat::Tar => 0,
+                    };
+                    (
+                        Part::File {
+                            path: path.clone(),
+                            size: *size,
+                        },
+                        crc,
+                    )
+                }
+                ArchiveEntryData::Track { .. } => unreachable!(),
+            };
+
+            let (header, padding) = writer.entry(&entry.name, part.size(), crc)?;
+            parts.extend([Part::Bytes(header), part, Part::Bytes(padding)]);
+        }
+
+        parts.push(Part::Bytes(writer.finish()));
+
+        Ok(Some(ArchiveLayout { parts }))
+    }
+
+    /// Streams the archive, encoding its tracks one at a time. Files are
+    /// streamed in chunks rather than read into memory.
+    #[must_use]
+    pub fn stream(self) -> BytesStream {
+        let writer = Some(ArchiveWriter::new(self.format));
+
+        stream::unfold(
+            (self.entries.into_iter(), writer),
+            |(mut entries, writer)| async move {
+                let mut writer = writer?;
+
+                let Some(entry) = entries.next() else {
+                    return Some((bytes_stream(Ok(writer.finish())), (entries, None)));
+                };
+
+                match write_entry(&mut writer, entry).await {
+                    Ok(stream) => Some((stream, (entries, Some(writer)))),
+                    Err(e) => Some((bytes_stream(Err(std::io::Error::other(e))), (entries, None))),
+                }
+            },
+        )
+        .flatten()
+        .boxed()
+    }
+}
+
+fn bytes_stream(bytes: Result<Bytes, std::io::Error>) -> BytesStream {
+    stream::once(async { bytes }).boxed()
+}
+
+/// Streams the entry's header, data and padding.
+async fn write_entry(
+    writer: &mut ArchiveWriter,
+    entry: ArchiveEntry,
+) -> Result<BytesStream, ArchiveError> {
+    let (data, size, crc) = match entry.data {
+        ArchiveEntryData::Bytes(bytes) => {
+            let (size, crc) = (bytes.len() as u64, crc(&bytes));
+            (bytes_stream(Ok(bytes)), size, crc)
+        }
+        ArchiveEntryData::File { path, size } => {
+            // Only ZIP entries have a checksum
+            let crc = match writer.format {
+                ArchiveFormat::Zip => cached_file_crc(&path).await?,
+                ArchiveFormat::Tar => 0,
+            };
+            (read_file_range(&path, 0, size).await?, size, crc)
+        }
+        ArchiveEntryData::Track {
+            source,
+            format,
+            settings,
+        } => {
+            log::debug!("Encoding archive entry {} as {format:?}", entry.name);
+            // The size and checksum of the encoded track are needed before
+            // its header is written, so it's encoded in memory
+            let bytes: Bytes = get_audio_bytes(source, format, settings, None, None, None)
+                .await?
+                .stream
+                .map(|x| x.and_then(|x| x))
+                .try_collect::<Vec<_>>()
+                .await?
+                .concat()
+                .into();
+            let (size, crc) = (bytes.len() as u64, crc(&bytes));
+            (bytes_stream(Ok(bytes)), size, crc)
+        }
+    };
+
+    let (header, padding) = writer.entry(&entry.name, size, crc)?;
+
+    Ok(bytes_stream(Ok(header))
+        .chain(data)
+        .chain(bytes_stream(Ok(padding)))
+        .boxed())
+}
+
+fn crc(bytes: &[u8]) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(bytes);
+    hasher.finalize()
+}
+
+/// The checksum of the file, computed again only if the file was modified
+/// since it was last computed.
+async fn cached_file_crc(path: &Path) -> Result<u32, std::io::Error> {
+    let modified = tokio::fs::metadata(path).await?.modified()?;
+
+    if let Some((cached_modified, crc)) = FILE_CRCS.lock().unwrap().get(path) {
+        if *cached_modified == modified {
+            return Ok(*crc);
+        }
+    }
+
+    let crc = file_crc(path).await?;
+
+    FILE_CRCS
+        .lock()
+        .unwrap()
+        .insert(path.to_path_buf(), (modified, crc));
+
+    Ok(crc)
+}
+
+async fn file_crc(path: &Path) -> Result<u32, std::io::Error> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut hasher = crc32fast::Hasher::new();
+    let mut buf = vec![0; 64 * 1024];
+
+    loop {
+        let read = file.read(&mut buf).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buf[..read]);
+    }
+
+    Ok(hasher.finalize())
+}
+
+async fn read_file_range(path: &Path, from: u64, to: u64) -> Result<BytesStream, std::io::Error> {
+    let mut file = tokio::fs::File::open(path).await?;
+    file.seek(std::io::SeekFrom::Start(from)).await?;
+
+    Ok(FramedRead::new(file.take(to - from), BytesCodec::new())
+        .map_ok(BytesMut::freeze)
+        .boxed())
+}
+
+fn zip_local_header(record: &ZipRecord) -> Result<Bytes, ArchiveError> {
+    let name_length = u16::try_from(record.name.len())
+        .map_err(|_| ArchiveError::NameTooLong(record.name.clone()))?;
+
+    // The local header only has the sizes, which are both moved to the ZIP64
+    // extra field if either doesn't fit
+    let (size, zip64) = zip32(record.size);
+    let (version, extra) = if zip64 {
+        let mut extra = zip64_extra_header(16);
+        extra.extend_from_slice(&record.size.to_le_bytes());
+        extra.extend_from_slice(&record.size.to_le_bytes());
+        (ZIP64_VERSION, extra)
+    } else {
+        (ZIP_VERSION, vec![])
+    };
+
+    let mut header = Vec::with_capacity(30 + record.name.len() + extra.len());
+    header.extend_from_slice(&0x0403_4b50_u32.to_le_bytes());
+    header.extend_from_slice(&version.to_le_bytes());
+    header.extend_from_slice(&ZIP_UTF8_FLAG.to_le_bytes());
+    // Stored, without compression
+    header.extend_from_slice(&0_u16.to_le_bytes());
+    header.extend_from_slice(&0_u16.to_le_bytes());
+    header.extend_from_slice(&ZIP_DOS_DATE.to_le_bytes());
+    header.extend_from_slice(&record.crc.to_le_bytes());
+    header.extend_from_slice(&size.to_le_bytes());
+    header.extend_from_slice(&size.to_le_bytes());
+    header.extend_from_slice(&name_length.to_le_bytes());
+    header.extend_from_slice(&(extra.len() as u16).to_le_bytes());
+    header.extend_from_slice(record.name.as_bytes());
+    header.extend_from_slice(&extra);
+
+    Ok(header.into())
+}
+
+fn zip64_extra_header(size: u16) -> Vec<u8> {
+    let mut extra = Vec::with_capacity(4 + usize::from(size));
+    extra.extend_from_slice(&ZIP64_EXTRA_ID.to_le_bytes());
+    extra.extend_from_slice(&size.to_le_bytes());
+    extra
+}
+
+/// Returns the 32 bit value to write in a header field and whether the
+/// value goes in the ZIP64 extra field instead.
+fn zip32(value: u64) -> (u32, bool) {
+    u32::try_from(value)
+        .ok()
+        .filter(|x| *x != ZIP64_MARKER)
+        .map_or((ZIP64_MARKER, true), |x| (x, false))
+}
+
+fn zip_central_directory(records: &[ZipRecord], offset: u64) -> Bytes {
+    let mut directory = vec![];
+    for record in records {
+        let (size, size_zip64) = zip32(record.size);
+        let (record_offset, offset_zip64) = zip32(record.offset);
+
+        // The ZIP64 extra field only has the values that don't fit, in the
+        // order of the uncompressed size, compressed size and offset
+        let mut values = vec![];
+        if size_zip64 {
+            values.extend([record.size, record.size]);
+        }
+        if offset_zip64 {
+            values.push(record.offset);
+        }
+        let extra = if values.is_empty() {
+            vec![]
+        } else {
+            let mut extra = zip64_extra_header(values.len() as u16 * 8);
+            for value in values {
+                extra.extend_from_slice(&value.to_le_bytes());
+            }
+            extra
+        };
+        let version = if record.is_zip64() {
+            ZIP64_VERSION
+        } else {
+            ZIP_VERSION
+        };
+
+        directory.extend_from_slice(&0x0201_4b50_u32.to_le_bytes());
+        directory.extend_from_slice(&version.to_le_bytes());
+        directory.extend_from_slice(&version.to_le_bytes());
+        directory.extend_from_slice(&ZIP_UTF8_FLAG.to_le_bytes());
+        directory.extend_from_slice(&0_u16.to_le_bytes());
+        directory.extend_from_slice(&0_u16.to_le_bytes());
+        directory.extend_from_slice(&ZIP_DOS_DATE.to_le_bytes());
+        directory.extend_from_slice(&record.crc.to_le_bytes());
+        directory.extend_from_slice(&size.to_le_bytes());
+        directory.extend_from_slice(&size.to_le_bytes());
+        directory.extend_from_slice(&(record.name.len() as u16).to_le_bytes());
+        directory.extend_from_slice(&(extra.len() as u16).to_le_bytes());
+        // The comment, disk number and attributes
+        directory.extend_from_slice(&[0; 10]);
+        directory.extend_from_slice(&record_offset.to_le_bytes());
+        directory.extend_from_slice(record.name.as_bytes());
+        directory.extend_from_slice(&extra);
+    }
+
+    let size = directory.len() as u64;
+    let count = records.len() as u64;
+    let count16 = u16::try_from(count).ok().filter(|x| *x != u16::MAX);
+    let (size32, size_zip64) = zip32(size);
+    let (offset32, offset_zip64) = zip32(offset);
+
+    if count16.is_none() || size_zip64 || offset_zip64 {
+        let zip64_offset = offset + size;
+
+        // The ZIP64 end of central directory record, without its signature
+        // and size counted in its size
+        directory.extend_from_slice(&0x0606_4b50_u32.to_le_bytes());
+        directory.extend_from_slice(&44_u64.to_le_bytes());
+        directory.extend_from_slice(&ZIP64_VERSION.to_le_bytes());
+        directory.extend_from_slice(&ZIP64_VERSION.to_le_bytes());
+        directory.extend_from_slice(&0_u32.to_le_bytes());
+        directory.extend_from_slice(&0_u32.to_le_bytes());
+        directory.extend_from_slice(&count.to_le_bytes());
+        directory.extend_from_slice(&count.to_le_bytes());
+        directory.extend_from_slice(&size.to_le_bytes());
+        directory.extend_from_slice(&offset.to_le_bytes());
+
+        // The ZIP64 end of central directory locator
+        directory.extend_from_slice(&0x0706_4b50_u32.to_le_bytes());
+        directory.extend_from_slice(&0_u32.to_le_bytes());
+        directory.extend_from_slice(&zip64_offset.to_le_bytes());
+        directory.extend_from_slice(&1_u32.to_le_bytes());
+    }
+
+    let count = count16.unwrap_or(u16::MAX);
+
+    directory.extend_from_slice(&0x0605_4b50_u32.to_le_bytes());
+    directory.extend_from_slice(&0_u16.to_le_bytes());
+    directory.extend_from_slice(&0_u16.to_le_bytes());
+    directory.extend_from_slice(&count.to_le_bytes());
+    directory.extend_from_slice(&count.to_le_bytes());
+    directory.extend_from_slice(&size32.to_le_bytes());
+    directory.extend_from_slice(&offset32.to_le_bytes());
+    directory.extend_from_slice(&0_u16.to_le_bytes());
+
+    directory.into()
+}
+
+fn tar_header(name: &str, size: u64) -> Result<Bytes, ArchiveError> {
+    let too_long = || ArchiveError::NameTooLong(name.to_string());
+
+    // Names over 100 bytes are split into the ustar prefix and name at a
+    // directory separator
+    let (prefix, name) = if name.len() <= TAR_NAME_LENGTH {
+        ("", name)
+    } else {
+        name.char_indices()
+            .filter(|(_, c)| *c == '/')
+            .map(|(i, _)| (&name[..i], &name[i + 1..]))
+            .find(|(prefix, name)| {
+                prefix.len() <= TAR_PREFIX_LENGTH && name.len() <= TAR_NAME_LENGTH
+            })
+            .ok_or_else(too_long)?
+    };
+
+    let mut header = [0; TAR_BLOCK_SIZE];
+    header[..name.len()].copy_from_slice(name.as_bytes());
+    header[100..108].copy_from_slice(b"0000644\0");
+    header[108..116].copy_from_slice(b"0000000\0");
+    header[116..124].copy_from_slice(b"0000000\0");
+    header[124..136].copy_from_slice(format!("{size:011o}\0").as_bytes());
+    header[136..148].copy_from_slice(b"00000000000\0");
+    header[156] = b'0';
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+    header[345..345 + prefix.len()].copy_from_slice(prefix.as_bytes());
+
+    if size >= 8 << 30 {
+        return Err(ArchiveError::TooLarge);
+    }
+
+    // The checksum is computed with its own field filled with spaces
+    header[148..156].fill(b' ');
+    let checksum = header.iter().map(|x| u32::from(*x)).sum::<u32>();
+    header[148..156].copy_from_slice(format!("{checksum:06o}\0 ").as_bytes());
+
+    Ok(Bytes::copy_from_slice(&header))
+}
+
+const fn tar_padding(size: u64) -> usize {
+    let remainder = (size % TAR_BLOCK_SIZE as u64) as usize;
+    if remainder == 0 {
+        0
+    } else {
+        TAR_BLOCK_SIZE - remainder
+    }
+}
+
+/// Replaces the characters that aren't allowed in file names on common
+/// filesystems and limits the name to `max` bytes.
+fn entry_name(name: &str, max: usize) -> String {
+    let mut cleaned = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect::<String>();
+
+    if cleaned.len() > max {
+        let mut end = max;
+        while !cleaned.is_char_boundary(end) {
+            end -= 1;
+        }
+        cleaned.truncate(end);
+    }
+
+    let cleaned = cleaned.trim().trim_end_matches('.');
+
+    if cleaned.is_empty() {
+        "_".to_string()
+    } else {
+        cleaned.to_string()
+    }
+}
+
+const fn transcode_extension(format: AudioFormat) -> &'static str {
+    match format {
+        #[cfg(feature = "aac")]
+        AudioFormat::Aac => "aac",
+        #[cfg(feature = "flac")]
+        AudioFormat::Flac => "flac",
+        #[cfg(feature = "mp3")]
+        AudioFormat::Mp3 => "mp3",
+        #[cfg(feature = "opus")]
+        AudioFormat::Opus => "opus",
+        AudioFormat::Source => "bin",
+    }
+}
+
+fn m3u_playlist(tracks: &[(&Track, String)]) -> String {
+    let mut playlist = "#EXTM3U\n".to_string();
+
+    for (track, file) in tracks {
+        playlist.push_str(&format!(
+            "#EXTINF:{},{} - {}\n{file}\n",
+            track.duration.round() as u64,
+            track.artist,
+            track.title
+        ));
+    }
+
+    playlist
+}
+
+fn cue_sheet(album: &Album, tracks: &[(&Track, String)]) -> String {
+    let quote = |x: &str| x.replace('"', "'");
+    let mut sheet = format!(
+        "PERFORMER \"{}\"\nTITLE \"{}\"\n",
+        quote(&album.artist),
+        quote(&album.title)
+    );
+
+    for (i, (track, file)) in tracks.iter().enumerate() {
+        let file_type = if file.ends_with(".mp3") {
+            "MP3"
+        } else {
+            "WAVE"
+        };
+        sheet.push_str(&format!(
+            "FILE \"{}\" {file_type}\n  TRACK {:02} AUDIO\n    TITLE \"{}\"\n    PERFORMER \"{}\"\n    INDEX 01 00:00:00\n",
+            quote(file),
+            i + 1,
+            quote(&track.title),
+            quote(&track.artist),
+        ));
+    }
+
+    sheet
+}
+
+/// Builds the archive of the album's tracks with its cover and, optionally,
+/// a playlist of the tracks.
+///
+/// The tracks are archived in their source format unless `format` is set, in
+/// which case they're transcoded with the encoder settings. Only the tracks
+/// of a single version of the album, the one of its first track, are
+/// archived.
+///
+/// # Errors
+///
+/// * If the album's tracks failed to be fetched
+/// * If the album has no tracks
+/// * If a track's source failed to be fetched
+#[allow(clippy::too_many_arguments)]
+pub async fn get_album_archive(
+    api: &dyn MusicApi,
+    db: &LibraryDatabase,
+    album: &Album,
+    archive_format: ArchiveFormat,
+    format: Option<AudioFormat>,
+    settings: EncoderSettings,
+    quality: Option<TrackAudioQuality>,
+    playlist: Option<ArchivePlaylist>,
+) -> Result<Archive, ArchiveError> {
+    let tracks = api
+        .album_tracks(&album.id, None, None, None, None)
+        .await?
+        .with_rest_of_items_in_batches()
+        .await?;
+
+    let version = tracks.first().ok_or(ArchiveError::NoTracks)?.source;
+    let tracks = tracks
+        .iter()
+        .filter(|x| x.source == version)
+        .collect::<Vec<_>>();
+
+    let directory = entry_name(&format!("{} - {}", album.artist, album.title), 100);
+    let mut entries = vec![];
+    let mut files = vec![];
+
+    for track in &tracks {
+        let source = get_track_source(api, track, quality).await?;
+        let transcode = format
+            .filter(|x| *x != AudioFormat::Source)
+            .filter(|x| *x != source.format() || !settings.is_default());
+
+        let (extension, data) = match (&source, transcode) {
+            // Virtual tracks are cut out of their file by re-encoding them
+            (TrackSource::LocalFilePath { path, .. }, None) if source.time_range().is_none() => {
+                let path = PathBuf::from(path);
+                let size = tokio::fs::metadata(&path).await?.len();
+                let extension = path
+                    .extension()
+                    .and_then(|x| x.to_str())
+                    .unwrap_or_else(|| transcode_extension(source.format()))
+                    .to_lowercase();
+                (extension, ArchiveEntryData::File { path, size })
+            }
+            _ => {
+                let format = transcode.unwrap_or(source.format());
+                (
+                    transcode_extension(format).to_string(),
+                    ArchiveEntryData::Track {
+                        source: source.clone(),
+                        format,
+                        settings: if transcode.is_some() {
+                            settings
+                        } else {
+                            EncoderSettings::default()
+                        },
+                    },
+                )
+            }
+        };
+
+        let mut file = format!(
+            "{:02} - {}.{extension}",
+            track.number,
+            entry_name(&track.title, 90)
+        );
+        if files.iter().any(|(_, x)| *x == file) {
+            file = format!(
+                "{:02} - {} ({}).{extension}",
+                track.number,
+                entry_name(&track.title, 80),
+                files.len() + 1
+            );
+        }
+
+        entries.push(ArchiveEntry {
+            name: format!("{directory}/{file}"),
+            data,
+        });
+        files.push((*track, file));
+    }
+
+    match get_album_cover(api, db, album, ImageCoverSize::Max).await {
+        Ok(cover) => {
+            let path = PathBuf::from(cover);
+            let extension = path
+                .extension()
+                .and_then(|x| x.to_str())
+                .unwrap_or("jpg")
+                .to_lowercase();
+            let size = tokio::fs::metadata(&path).await?.len();
+            entries.push(ArchiveEntry {
+                name: format!("{directory}/cover.{extension}"),
+                data: ArchiveEntryData::File { path, size },
+            });
+        }
+        Err(e) => {
+            log::debug!("No cover for album archive album_id={}: {e:?}", album.id);
+        }
+    }
+
+    if let Some(playlist) = playlist {
+        let (extension, contents) = match playlist {
+            ArchivePlaylist::M3u => ("m3u", m3u_playlist(&files)),
+            ArchivePlaylist::Cue => ("cue", cue_sheet(album, &files)),
+        };
+        entries.push(ArchiveEntry {
+            name: format!("{directory}/{}.{extension}", entry_name(&album.title, 90)),
+            data: ArchiveEntryData::Bytes(contents.into()),
+        });
+    }
+
+    Ok(Archive::new(archive_format, entries))
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn entries() -> Vec<ArchiveEntry> {
+        vec![
+            ArchiveEntry {
+                name: "Artist - Album/01 - One.flac".to_string(),
+                data: ArchiveEntryData::Bytes(Bytes::from(vec![1; 1000])),
+            },
+            ArchiveEntry {
+                name: "Artist - Album/album.m3u".to_string(),
+                data: ArchiveEntryData::Bytes(Bytes::from_static(b"#EXTM3U\n")),
+            },
+        ]
+    }
+
+    async fn collect(stream: BytesStream) -> Vec<u8> {
+        stream.try_collect::<Vec<_>>().await.unwrap().concat()
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn zip_layout_matches_the_streamed_archive() {
+        let archive = Archive::new(ArchiveFormat::Zip, entries());
+        let layout = archive.layout().await.unwrap().unwrap();
+        let size = layout.size();
+
+        let streamed = collect(archive.stream()).await;
+        let full = collect(layout.clone().stream(0, size - 1)).await;
+
+        assert_eq!(streamed.len() as u64, size);
+        assert_eq!(full, streamed);
+
+        // Local headers, data, central directory records and the end record
+        let name_lengths = 28 + 24;
+        assert_eq!(size, 30 * 2 + 1008 + 46 * 2 + name_lengths * 2 + 22);
+        assert_eq!(&full[..4], b"PK\x03\x04");
+        assert_eq!(&full[full.len() - 22..full.len() - 18], b"PK\x05\x06");
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn streams_file_entries_like_their_layout() {
+        let path = std::env::temp_dir().join(format!(
+            "moosicbox_archive_test_{}.flac",
+            std::process::id()
+        ));
+        let data = b"0123456789".repeat(20_000);
+        tokio::fs::write(&path, &data).await.unwrap();
+
+        let archive = Archive::new(
+            ArchiveFormat::Zip,
+            vec![ArchiveEntry {
+                name: "Artist - Album/01 - One.flac".to_string(),
+                data: ArchiveEntryData::File {
+                    path: path.clone(),
+                    size: data.len() as u64,
+                },
+            }],
+        );
+        let layout = archive.layout().await.unwrap().unwrap();
+        let streamed = collect(archive.stream()).await;
+        let full = collect(layout.clone().stream(0, layout.size() - 1)).await;
+
+        tokio::fs::remove_file(&path).await.unwrap();
+
+        assert_eq!(streamed, full);
+        assert_eq!(&streamed[14..18], &crc(&data).to_le_bytes());
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn file_crc_is_computed_again_when_the_file_is_modified() {
+        let path = std::env::temp_dir().join(format!(
+            "moosicbox_archive_crc_test_{}.flac",
+            std::process::id()
+        ));
+        tokio::fs::write(&path, b"one").await.unwrap();
+
+        assert_eq!(cached_file_crc(&path).await.unwrap(), crc(b"one"));
+
+        std::fs::write(&path, b"two").unwrap();
+        let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(modified + std::time::Duration::from_secs(1))
+            .unwrap();
+
+        assert_eq!(cached_file_crc(&path).await.unwrap(), crc(b"two"));
+
+        tokio::fs::remove_file(&path).await.unwrap();
+    }
+
+    #[test_log::test]
+    fn writes_zip64_records_for_large_entries() {
+        let mut writer = ArchiveWriter::new(ArchiveFormat::Zip);
+        let large = 5 << 30;
+
+        let (header, _) = writer.entry("large.flac", large, 1).unwrap();
+
+        // The sizes are moved to the ZIP64 extra field after the name
+        assert_eq!(&header[4..6], &ZIP64_VERSION.to_le_bytes());
+        assert_eq!(&header[18..26], &[0xff; 8]);
+        assert_eq!(&header[28..30], &20_u16.to_le_bytes());
+        assert_eq!(&header[40..44], &[1, 0, 16, 0]);
+        assert_eq!(&header[44..52], &large.to_le_bytes());
+        assert_eq!(&header[52..60], &large.to_le_bytes());
+
+        // The second entry starts past 4 GiB, so only its offset is in the
+        // central directory's ZIP64 extra field
+        let offset = writer.offset;
+        writer.entry("small.m3u", 8, 2).unwrap();
+
+        let directory = writer.finish();
+        let record = &directory[46 + 10 + 20..];
+        assert_eq!(&record[..4], b"PK\x01\x02");
+        assert_eq!(&record[20..28], &[8, 0, 0, 0, 8, 0, 0, 0]);
+        assert_eq!(&record[30..32], &12_u16.to_le_bytes());
+        assert_eq!(&record[42..46], &[0xff; 4]);
+        assert_eq!(&record[55..59], &[1, 0, 8, 0]);
+        assert_eq!(&record[59..67], &offset.to_le_bytes());
+
+        // The ZIP64 end record and its locator come before the end record,
+        // which points to them with its offset
+        let end = &directory[directory.len() - 22 - 20 - 56..];
+        assert_eq!(&end[..4], b"PK\x06\x06");
+        assert_eq!(&end[24..32], &2_u64.to_le_bytes());
+        assert_eq!(&end[56..60], b"PK\x06\x07");
+        assert_eq!(&end[64..72], &(offset + 39 + 8 + 76 + 67).to_le_bytes());
+        assert_eq!(&end[76..80], b"PK\x05\x06");
+        assert_eq!(&end[92..96], &[0xff; 4]);
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn layout_ranges_concatenate_to_the_archive() {
+        let archive = Archive::new(ArchiveFormat::Tar, entries());
+        let layout = archive.layout().await.unwrap().unwrap();
+        let full = collect(archive.stream()).await;
+
+        let mut ranges = vec![];
+        for start in (0..full.len() as u64).step_by(700) {
+            let end = (start + 699).min(full.len() as u64 - 1);
+            ranges.extend(collect(layout.clone().stream(start, end)).await);
+        }
+
+        assert_eq!(ranges, full);
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn tar_entries_are_block_aligned() {
+        let archive = Archive::new(ArchiveFormat::Tar, entries());
+        let tar = collect(archive.stream()).await;
+
+        // Each entry is a header block and its data padded to whole blocks,
+        // followed by two empty blocks
+        assert_eq!(tar.len(), 512 * (1 + 2) + 512 * (1 + 1) + 512 * 2);
+        assert_eq!(&tar[257..263], b"ustar\0");
+        assert_eq!(&tar[124..136], b"00000001750\0");
+    }
+
+    #[test_log::test]
+    fn tar_header_splits_long_names_into_the_prefix() {
+        let directory = "d".repeat(120);
+        let header = tar_header(&format!("{directory}/file.flac"), 1).unwrap();
+
+        assert_eq!(&header[..10], b"file.flac\0");
+        assert_eq!(&header[345..465], directory.as_bytes());
+    }
+
+    #[test_log::test]
+    fn entry_name_removes_separators_and_limits_the_length() {
+        assert_eq!(entry_name("AC/DC: Live?", 100), "AC_DC_ Live_");
+        assert_eq!(entry_name("Ünïcode", 4), "Ün");
+        assert_eq!(entry_name("...", 100), "_");
+    }
+}
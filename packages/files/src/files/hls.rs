@@ -0,0 +1,722 @@
+//! HTTP Live Streaming of transcoded tracks.
+//!
+//! A track is split into segments of roughly [`SEGMENT_DURATION`] seconds
+//! that are encoded on demand, so clients can seek to any segment without the
+//! server encoding the track from the start. Segment boundaries are aligned
+//! to the encoder's frames (1024 samples for AAC, 1152 for MP3 at 44.1kHz):
+//! every segment is encoded from a few frames before its start and cut at the
+//! same frame positions a continuous encode would have, so consecutive
+//! segments play back without gaps.
+//!
+//! The segments are packed audio (ADTS AAC or MPEG audio) starting with the
+//! ID3 `PRIV` timestamp tag HLS requires.
+
+use std::sync::{Arc, Mutex};
+
+use bytes::Bytes;
+use moosicbox_audio_decoder::{
+    decode_file_path_str_async, decode_media_source_async,
+    media_sources::remote_bytestream::RemoteByteStreamMediaSource, AudioDecode, AudioDecodeError,
+    AudioDecodeHandler, DecodeError,
+};
+use moosicbox_core::types::AudioFormat;
+use moosicbox_music_api::TrackSource;
+use moosicbox_stream_utils::remote_bytestream::RemoteByteStream;
+use symphonia::core::{
+    audio::{AudioBuffer, Channels, Signal, SignalSpec},
+    conv::IntoSample as _,
+    formats::{Packet, Track},
+    io::MediaSourceStream,
+    probe::Hint,
+};
+use thiserror::Error;
+use tokio_util::sync::CancellationToken;
+
+/// The target duration of each segment, in seconds.
+pub const SEGMENT_DURATION: f64 = 6.0;
+
+/// The bit rates, in kbps, of the variants offered for a format when none are
+/// requested.
+#[must_use]
+pub const fn default_bitrates(format: AudioFormat) -> &'static [u32] {
+    match format {
+        #[cfg(feature = "mp3")]
+        AudioFormat::Mp3 => &[320, 192, 128],
+        _ => &[256, 128, 64],
+    }
+}
+
+pub(crate) const SAMPLE_RATE: u32 = 44_100;
+
+/// The number of frames encoded before a segment so the encoder has settled
+/// by the segment's first frame.
+const PRE_ROLL_FRAMES: u64 = 4;
+
+/// The number of frames encoded after a segment so the encoder and resampler
+/// have emitted all of its frames.
+const POST_ROLL_FRAMES: u64 = 8;
+
+/// How long before the pre-roll the decoder seeks to, since it starts
+/// decoding at the first packet after the seek position.
+const SEEK_MARGIN: f64 = 0.5;
+
+/// MP3 bit rates below this make LAME lower the output sample rate.
+#[cfg(feature = "mp3")]
+const MIN_MP3_BITRATE: u32 = 112;
+
+#[derive(Debug, Error)]
+pub enum HlsError {
+    #[error("Unsupported format: {0:?}")]
+    UnsupportedFormat(AudioFormat),
+    #[error("Unsupported bitrate: {0}")]
+    UnsupportedBitrate(u32),
+    #[error("Segment not found: {0}")]
+    SegmentNotFound(u64),
+    #[error("No audio was decoded")]
+    NoAudio,
+    #[error("Failed to encode: {0}")]
+    Encode(String),
+    #[error(transparent)]
+    Decode(#[from] DecodeError),
+}
+
+/// The number of samples per channel in each encoded frame.
+pub(crate) const fn frame_samples(format: AudioFormat) -> Result<u64, HlsError> {
+    match format {
+        #[cfg(feature = "aac")]
+        AudioFormat::Aac => Ok(1024),
+        #[cfg(feature = "mp3")]
+        AudioFormat::Mp3 => Ok(1152),
+        _ => Err(HlsError::UnsupportedFormat(format)),
+    }
+}
+
+/// Returns the `Content-Type` of the segments of the given format.
+#[must_use]
+pub const fn segment_content_type(format: AudioFormat) -> Option<&'static str> {
+    match format {
+        #[cfg(feature = "aac")]
+        AudioFormat::Aac => Some("audio/aac"),
+        #[cfg(feature = "mp3")]
+        AudioFormat::Mp3 => Some("audio/mpeg"),
+        _ => None,
+    }
+}
+
+const fn codecs(format: AudioFormat) -> &'static str {
+    match format {
+        #[cfg(feature = "mp3")]
+        AudioFormat::Mp3 => "mp4a.40.34",
+        _ => "mp4a.40.2",
+    }
+}
+
+/// Checks that segments can be encoded in the format at the bit rate.
+///
+/// # Errors
+///
+/// * If the format can't be segmented
+/// * If the bit rate isn't supported for the format
+pub fn validate_variant(format: AudioFormat, bitrate: u32) -> Result<(), HlsError> {
+    frame_samples(format)?;
+
+    #[cfg(feature = "mp3")]
+    if format == AudioFormat::Mp3 && bitrate < MIN_MP3_BITRATE {
+        return Err(HlsError::UnsupportedBitrate(bitrate));
+    }
+
+    if bitrate == 0 || bitrate > 320 {
+        return Err(HlsError::UnsupportedBitrate(bitrate));
+    }
+
+    Ok(())
+}
+
+/// Returns the frame range of every segment of a track with the given
+/// duration.
+pub(crate) fn segment_frames(
+    format: AudioFormat,
+    duration: f64,
+) -> Result<Vec<(u64, u64)>, HlsError> {
+    let frame_samples = frame_samples(format)?;
+    let frames_per_second = f64::from(SAMPLE_RATE) / frame_samples as f64;
+
+    let total = (duration.max(0.0) * frames_per_second).ceil() as u64;
+
+    let mut segments = vec![];
+    let mut start = 0;
+
+    while start < total {
+        let end = (((segments.len() + 1) as f64 * SEGMENT_DURATION * frames_per_second).round()
+            as u64)
+            .min(total);
+        segments.push((start, end));
+        start = end;
+    }
+
+    Ok(segments)
+}
+
+pub(crate) fn frames_to_seconds(frames: u64, frame_samples: u64) -> f64 {
+    (frames * frame_samples) as f64 / f64::from(SAMPLE_RATE)
+}
+
+/// Renders the master playlist listing a variant for each bit rate.
+/// `media_playlist_uri` returns the URI of a variant's media playlist.
+///
+/// # Errors
+///
+/// * If any of the variants isn't supported
+pub fn master_playlist(
+    format: AudioFormat,
+    bitrates: &[u32],
+    media_playlist_uri: impl Fn(u32) -> String,
+) -> Result<String, HlsError> {
+    let mut playlist = "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-INDEPENDENT-SEGMENTS\n".to_string();
+
+    for bitrate in bitrates {
+        validate_variant(format, *bitrate)?;
+        playlist.push_str(&format!(
+            "#EXT-X-STREAM-INF:BANDWIDTH={},CODECS=\"{}\"\n{}\n",
+            // Leave room for the container overhead
+            bitrate * 1_050,
+            codecs(format),
+            media_playlist_uri(*bitrate),
+        ));
+    }
+
+    Ok(playlist)
+}
+
+/// Renders the media playlist of a track with the given duration.
+/// `segment_uri` returns the URI of the segment with the given index.
+///
+/// # Errors
+///
+/// * If the format can't be segmented
+pub fn media_playlist(
+    format: AudioFormat,
+    duration: f64,
+    segment_uri: impl Fn(u64) -> String,
+) -> Result<String, HlsError> {
+    let frame_samples = frame_samples(format)?;
+    let segments = segment_frames(format, duration)?;
+
+    let target_duration = segments
+        .iter()
+        .map(|(start, end)| frames_to_seconds(end - start, frame_samples))
+        .fold(SEGMENT_DURATION, f64::max)
+        .round();
+
+    let mut playlist = format!(
+        "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-PLAYLIST-TYPE:VOD\n#EXT-X-TARGETDURATION:{target_duration}\n#EXT-X-MEDIA-SEQUENCE:0\n"
+    );
+
+    for (index, (start, end)) in (0..).zip(&segments) {
+        playlist.push_str(&format!(
+            "#EXTINF:{:.5},\n{}\n",
+            frames_to_seconds(end - start, frame_samples),
+            segment_uri(index),
+        ));
+    }
+
+    playlist.push_str("#EXT-X-ENDLIST\n");
+
+    Ok(playlist)
+}
+
+/// Collects the decoded audio between two timestamps as interleaved stereo
+/// samples.
+#[derive(Default)]
+struct Window {
+    start: f64,
+    end: f64,
+    rate: Option<u32>,
+    samples: Vec<f32>,
+}
+
+#[derive(Clone)]
+struct WindowOutput(Arc<Mutex<Window>>);
+
+impl AudioDecode for WindowOutput {
+    fn decoded(
+        &mut self,
+        decoded: AudioBuffer<f32>,
+        packet: &Packet,
+        track: &Track,
+    ) -> Result<(), AudioDecodeError> {
+        let spec = *decoded.spec();
+        let rate = f64::from(spec.rate);
+        let frames = decoded.frames();
+
+        let packet_start = track.codec_params.time_base.map_or_else(
+            || packet.ts() as f64 / rate,
+            |time_base| {
+                let time = time_base.calc_time(packet.ts());
+                time.seconds as f64 + time.frac
+            },
+        );
+
+        let mut window = self.0.lock().unwrap();
+        window.rate.get_or_insert(spec.rate);
+
+        let to_frame =
+            |time: f64| (((time - packet_start) * rate).round().max(0.0) as usize).min(frames);
+        let first = to_frame(window.start);
+        let last = to_frame(window.end);
+
+        let left = decoded.chan(0);
+        let right = decoded.chan(usize::from(spec.channels.count() > 1));
+
+        for i in first..last {
+            window.samples.push(left[i]);
+            window.samples.push(right[i]);
+        }
+
+        let finished = packet_start + frames as f64 / rate >= window.end;
+        drop(window);
+
+        if finished {
+            return Err(AudioDecodeError::StreamEnd);
+        }
+
+        Ok(())
+    }
+}
+
+/// Decodes the audio between `start` and `end` seconds, resampled to 44.1kHz.
+async fn decode_window(source: TrackSource, start: f64, end: f64) -> Result<Vec<i16>, HlsError> {
+    // The window of a virtual track is relative to the start of the track
+    // within its file
+    let (start, end) = match source.time_range() {
+        Some((offset, track_end)) => (
+            start + offset,
+            track_end.map_or(end + offset, |track_end| track_end.min(end + offset)),
+        ),
+        None => (start, end),
+    };
+
+    let window = Arc::new(Mutex::new(Window {
+        start,
+        end,
+        ..Default::default()
+    }));
+
+    let get_handler = {
+        let output = WindowOutput(window.clone());
+        move || {
+            Ok(
+                AudioDecodeHandler::new().with_output(Box::new(move |_spec, _duration| {
+                    Ok(Box::new(output.clone()))
+                })),
+            )
+        }
+    };
+
+    let seek = Some(start - SEEK_MARGIN).filter(|x| *x > 0.0);
+
+    match source {
+        TrackSource::LocalFilePath { path, .. } => {
+            decode_file_path_str_async(&path, get_handler, true, false, None, seek).await?;
+        }
+        TrackSource::RemoteUrl { url, .. } => {
+            let source: RemoteByteStreamMediaSource =
+                RemoteByteStream::new(url, None, true, true, CancellationToken::new()).into();
+            decode_media_source_async(
+                MediaSourceStream::new(Box::new(source), Default::default()),
+                &Hint::new(),
+                get_handler,
+                true,
+                false,
+                None,
+                seek,
+            )
+            .await?;
+        }
+    }
+
+    let window = std::mem::take(&mut *window.lock().unwrap());
+    let rate = window.rate.ok_or(HlsError::NoAudio)?;
+
+    if rate == SAMPLE_RATE {
+        return Ok(window
+            .samples
+            .into_iter()
+            .map(|x| x.into_sample())
+            .collect());
+    }
+
+    log::debug!("decode_window: Resampling from {rate} to {SAMPLE_RATE}");
+
+    let spec = SignalSpec {
+        rate,
+        channels: Channels::FRONT_LEFT | Channels::FRONT_RIGHT,
+    };
+    let mut resampler =
+        moosicbox_resampler::Resampler::<i16>::new(spec, SAMPLE_RATE as usize, 1024);
+    let mut resampled = vec![];
+
+    let mut input = Some(moosicbox_resampler::to_audio_buffer(&window.samples, spec));
+    while let Some(samples) =
+        resampler.resample(input.take().unwrap_or_else(|| AudioBuffer::new(0, spec)))
+    {
+        resampled.extend_from_slice(samples);
+    }
+    if let Some(samples) = resampler.flush() {
+        resampled.extend_from_slice(samples);
+    }
+
+    Ok(resampled)
+}
+
+/// Encodes interleaved stereo 44.1kHz samples and splits the output into
+/// frames.
+#[allow(unused)]
+pub(crate) fn encode_frames(
+    format: AudioFormat,
+    bitrate: u32,
+    samples: &[i16],
+) -> Result<Vec<Bytes>, HlsError> {
+    let encoded: Bytes = match format {
+        #[cfg(feature = "aac")]
+        AudioFormat::Aac => {
+            use moosicbox_audio_encoder::aac::{encode_aac, encoder_aac_with_bit_rate, BitRate};
+
+            let encoder = encoder_aac_with_bit_rate(BitRate::Cbr(bitrate * 1000))
+                .map_err(|e| HlsError::Encode(format!("{e:?}")))?;
+            let mut encoded = vec![];
+            let mut output = [0u8; 2048];
+            let mut read = 0;
+
+            while read < samples.len() {
+                let end = std::cmp::min(read + 2048, samples.len());
+                let info = encode_aac(&encoder, &samples[read..end], &mut output)
+                    .map_err(|e| HlsError::Encode(format!("{e:?}")))?;
+                encoded.extend_from_slice(&output[..info.output_size]);
+                read += info.input_consumed;
+                if info.input_consumed == 0 && info.output_size == 0 {
+                    break;
+                }
+            }
+
+            encoded.into()
+        }
+        #[cfg(feature = "mp3")]
+        AudioFormat::Mp3 => {
+            use moosicbox_audio_encoder::mp3::{encode_mp3, encoder_mp3_with_bitrate};
+
+            let mut encoder = encoder_mp3_with_bitrate(bitrate)
+                .map_err(|e| HlsError::Encode(format!("{e:?}")))?;
+
+            encode_mp3(&mut encoder, samples)
+                .map_err(|e| HlsError::Encode(format!("{e:?}")))?
+                .0
+                .into()
+        }
+        _ => return Err(HlsError::UnsupportedFormat(format)),
+    };
+
+    #[allow(unreachable_code)]
+    split_frames(format, &encoded)
+}
+
+/// Splits an ADTS or MPEG audio stream into its frames.
+pub(crate) fn split_frames(format: AudioFormat, mut data: &[u8]) -> Result<Vec<Bytes>, HlsError> {
+    let mut frames = vec![];
+
+    while data.len() >= 7 {
+        if data[0] != 0xFF || data[1] & 0xE0 != 0xE0 {
+            return Err(HlsError::Encode("Invalid frame sync".to_string()));
+        }
+
+        let length = frame_length(format, data)?;
+        if length == 0 || length > data.len() {
+            break;
+        }
+
+        frames.push(Bytes::copy_from_slice(&data[..length]));
+        data = &data[length..];
+    }
+
+    Ok(frames)
+}
+
+#[allow(unused)]
+fn frame_length(format: AudioFormat, header: &[u8]) -> Result<usize, HlsError> {
+    match format {
+        #[cfg(feature = "aac")]
+        AudioFormat::Aac => Ok((usize::from(header[3] & 0x03) << 11)
+            | (usize::from(header[4]) << 3)
+            | (usize::from(header[5]) >> 5)),
+        #[cfg(feature = "mp3")]
+        AudioFormat::Mp3 => {
+            const BITRATES: [usize; 15] = [
+                0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320,
+            ];
+
+            let mpeg1_layer3 = (header[1] >> 3) & 0x03 == 0x03 && (header[1] >> 1) & 0x03 == 0x01;
+            let sample_rate_index = (header[2] >> 2) & 0x03;
+            if !mpeg1_layer3 || sample_rate_index != 0 {
+                return Err(HlsError::Encode(
+                    "Expected MPEG-1 Layer III frames at 44.1kHz".to_string(),
+                ));
+            }
+
+            let bitrate = BITRATES
+                .get(usize::from(header[2] >> 4))
+                .copied()
+                .ok_or_else(|| HlsError::Encode("Invalid bitrate".to_string()))?;
+            let padding = usize::from((header[2] >> 1) & 0x01);
+
+            Ok(144_000 * bitrate / SAMPLE_RATE as usize + padding)
+        }
+        _ => Err(HlsError::UnsupportedFormat(format)),
+    }
+}
+
+/// The ID3 tag with the `com.apple.streaming.transportStreamTimestamp` `PRIV`
+/// frame carrying the 90kHz timestamp of the segment's first sample.
+fn timestamp_tag(seconds: f64) -> Vec<u8> {
+    const OWNER: &[u8] = b"com.apple.streaming.transportStreamTimestamp\0";
+
+    let timestamp = ((seconds * 90_000.0).round() as u64) & 0x1_FFFF_FFFF;
+
+    let frame_size = OWNER.len() + 8;
+    let tag_size = 10 + frame_size;
+
+    let mut tag = Vec::with_capacity(10 + tag_size);
+    tag.extend_from_slice(b"ID3\x04\x00\x00");
+    tag.extend_from_slice(&syncsafe(tag_size));
+    tag.extend_from_slice(b"PRIV");
+    tag.extend_from_slice(&syncsafe(frame_size));
+    tag.extend_from_slice(&[0, 0]);
+    tag.extend_from_slice(OWNER);
+    tag.extend_from_slice(&timestamp.to_be_bytes());
+    tag
+}
+
+const fn syncsafe(size: usize) -> [u8; 4] {
+    [
+        ((size >> 21) & 0x7F) as u8,
+        ((size >> 14) & 0x7F) as u8,
+        ((size >> 7) & 0x7F) as u8,
+        (size & 0x7F) as u8,
+    ]
+}
+
+/// Encodes segment `index` of a track with the given duration.
+///
+/// # Errors
+///
+/// * If the variant isn't supported
+/// * If the segment doesn't exist
+/// * If the track failed to be decoded or encoded
+pub async fn get_segment(
+    source: TrackSource,
+    format: AudioFormat,
+    bitrate: u32,
+    duration: f64,
+    index: u64,
+) -> Result<Bytes, HlsError> {
+    let frames = encode_segment(source, format, bitrate, duration, index).await?;
+    let (start, _) = segment_frames(format, duration)?[index as usize];
+
+    let mut segment = timestamp_tag(frames_to_seconds(start, frame_samples(format)?));
+    for frame in frames {
+        segment.extend_from_slice(&frame);
+    }
+
+    Ok(segment.into())
+}
+
+/// Encodes the frames of segment `index` of a track with the given duration.
+///
+/// # Errors
+///
+/// * If the variant isn't supported
+/// * If the segment doesn't exist
+/// * If the track failed to be decoded or encoded
+pub(crate) async fn encode_segment(
+    source: TrackSource,
+    format: AudioFormat,
+    bitrate: u32,
+    duration: f64,
+    index: u64,
+) -> Result<Vec<Bytes>, HlsError> {
+    validate_variant(format, bitrate)?;
+
+    let frame_samples = frame_samples(format)?;
+    let (start, end) = usize::try_from(index)
+        .ok()
+        .and_then(|i| segment_frames(format, duration).ok()?.get(i).copied())
+        .ok_or(HlsError::SegmentNotFound(index))?;
+
+    let pre_roll = PRE_ROLL_FRAMES.min(start);
+    let window_start = start - pre_roll;
+    let window_frames = end - window_start + POST_ROLL_FRAMES;
+
+    log::debug!(
+        "get_segment: index={index} format={format:?} bitrate={bitrate} frames={start}..{end}"
+    );
+
+    let mut samples = decode_window(
+        source,
+        frames_to_seconds(window_start, frame_samples),
+        frames_to_seconds(window_start + window_frames, frame_samples),
+    )
+    .await?;
+
+    // Pad the end of the track with silence so the encoder emits its last
+    // frames
+    samples.resize((window_frames * frame_samples * 2) as usize, 0);
+
+    let frames = moosicbox_task::spawn_blocking("files: hls encode", move || {
+        encode_frames(format, bitrate, &samples)
+    })
+    .await
+    .map_err(DecodeError::from)??;
+
+    frames
+        .get(pre_roll as usize..(end - window_start) as usize)
+        .map(<[Bytes]>::to_vec)
+        .ok_or_else(|| HlsError::Encode(format!("Only {} frames were encoded", frames.len())))
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[cfg(feature = "aac")]
+    #[test]
+    fn segment_frames_are_aligned_to_the_frame_grid() {
+        let segments = segment_frames(AudioFormat::Aac, 14.0).unwrap();
+
+        // 6s is 258.4 frames of 1024 samples at 44.1kHz
+        assert_eq!(segments, vec![(0, 258), (258, 517), (517, 603)]);
+    }
+
+    #[cfg(feature = "aac")]
+    #[test]
+    fn renders_media_playlist() {
+        let playlist =
+            media_playlist(AudioFormat::Aac, 7.0, |i| format!("segment?index={i}")).unwrap();
+
+        assert_eq!(
+            playlist,
+            "#EXTM3U
+#EXT-X-VERSION:3
+#EXT-X-PLAYLIST-TYPE:VOD
+#EXT-X-TARGETDURATION:6
+#EXT-X-MEDIA-SEQUENCE:0
+#EXTINF:5.99075,
+segment?index=0
+#EXTINF:1.02168,
+segment?index=1
+#EXT-X-ENDLIST
+"
+        );
+    }
+
+    #[cfg(feature = "mp3")]
+    #[test]
+    fn renders_master_playlist() {
+        let playlist = master_playlist(AudioFormat::Mp3, &[320, 128], |bitrate| {
+            format!("media.m3u8?bitrate={bitrate}")
+        })
+        .unwrap();
+
+        assert_eq!(
+            playlist,
+            "#EXTM3U
+#EXT-X-VERSION:3
+#EXT-X-INDEPENDENT-SEGMENTS
+#EXT-X-STREAM-INF:BANDWIDTH=336000,CODECS=\"mp4a.40.34\"
+media.m3u8?bitrate=320
+#EXT-X-STREAM-INF:BANDWIDTH=134400,CODECS=\"mp4a.40.34\"
+media.m3u8?bitrate=128
+"
+        );
+
+        assert!(matches!(
+            master_playlist(AudioFormat::Mp3, &[96], |_| String::new()),
+            Err(HlsError::UnsupportedBitrate(96))
+        ));
+    }
+
+    #[test]
+    fn timestamp_tag_is_a_priv_frame() {
+        let tag = timestamp_tag(6.0);
+
+        assert_eq!(&tag[..10], b"ID3\x04\x00\x00\x00\x00\x00\x3F");
+        assert_eq!(&tag[10..14], b"PRIV");
+        assert_eq!(tag.len(), 10 + 63);
+        assert_eq!(&tag[tag.len() - 8..], &540_000_u64.to_be_bytes());
+    }
+
+    #[cfg(feature = "aac")]
+    #[test_log::test(tokio::test)]
+    async fn consecutive_segments_cover_the_track() {
+        let path = std::env::temp_dir().join(format!("moosicbox_hls_{}.wav", std::process::id()));
+        write_sine_wav(&path, 48_000, 14.0);
+        let source = TrackSource::LocalFilePath {
+            path: path.to_str().unwrap().to_string(),
+            format: AudioFormat::Source,
+            track_id: None,
+            source: moosicbox_core::sqlite::models::TrackApiSource::Local,
+            start_offset: None,
+            end_offset: None,
+        };
+
+        let mut frames = vec![];
+        for index in 0..3 {
+            let segment = get_segment(source.clone(), AudioFormat::Aac, 128, 14.0, index)
+                .await
+                .unwrap();
+            assert_eq!(&segment[10..14], b"PRIV");
+            frames.push(
+                split_frames(AudioFormat::Aac, &segment[73..])
+                    .unwrap()
+                    .len(),
+            );
+        }
+
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(frames, vec![258, 259, 86]);
+        assert!(matches!(
+            get_segment(source, AudioFormat::Aac, 128, 14.0, 3).await,
+            Err(HlsError::SegmentNotFound(3))
+        ));
+    }
+
+    #[cfg(feature = "aac")]
+    fn write_sine_wav(path: &std::path::Path, rate: u32, seconds: f64) {
+        let frames = (f64::from(rate) * seconds) as u32;
+        let data_size = frames * 4;
+
+        let mut wav = vec![];
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&(36 + data_size).to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        wav.extend_from_slice(&16_u32.to_le_bytes());
+        wav.extend_from_slice(&1_u16.to_le_bytes());
+        wav.extend_from_slice(&2_u16.to_le_bytes());
+        wav.extend_from_slice(&rate.to_le_bytes());
+        wav.extend_from_slice(&(rate * 4).to_le_bytes());
+        wav.extend_from_slice(&4_u16.to_le_bytes());
+        wav.extend_from_slice(&16_u16.to_le_bytes());
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&data_size.to_le_bytes());
+
+        for i in 0..frames {
+            let sample = ((f64::from(i) * 440.0 * std::f64::consts::TAU / f64::from(rate)).sin()
+                * 8_000.0) as i16;
+            wav.extend_from_slice(&sample.to_le_bytes());
+            wav.extend_from_slice(&sample.to_le_bytes());
+        }
+
+        std::fs::write(path, wav).unwrap();
+    }
+}
@@ -0,0 +1,293 @@
+//! Named sets of encoder settings that can be requested instead of passing
+//! each setting, e.g. `mobile-data` or `wifi`.
+//!
+//! The built-in profiles are always available. Profiles stored in the config
+//! database are scoped to a `MoosicBox` profile and take precedence over a
+//! built-in profile with the same name.
+
+use std::str::FromStr as _;
+
+use moosicbox_audio_encoder::{EncoderSettings, SettingsError};
+use moosicbox_core::types::AudioFormat;
+use moosicbox_database::{config::ConfigDatabase, query::*, DatabaseError};
+use moosicbox_json_utils::{database::FromRow, ParseError, ToValueType as _};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::track::validate_encoder_settings;
+
+pub const MOBILE_DATA: &str = "mobile-data";
+pub const WIFI: &str = "wifi";
+pub const ARCHIVE: &str = "archive";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct QualityProfile {
+    pub name: String,
+    pub format: AudioFormat,
+    pub bitrate: Option<u32>,
+    pub vbr_quality: Option<u8>,
+    pub sample_rate: Option<u32>,
+    pub channels: Option<u8>,
+}
+
+impl QualityProfile {
+    #[must_use]
+    pub const fn settings(&self) -> EncoderSettings {
+        EncoderSettings {
+            bitrate: self.bitrate,
+            vbr_quality: self.vbr_quality,
+            sample_rate: self.sample_rate,
+            channels: self.channels,
+        }
+    }
+}
+
+#[derive(Debug, Clone, FromRow)]
+struct QualityProfileRow {
+    name: String,
+    format: String,
+    bitrate: Option<u32>,
+    vbr_quality: Option<u8>,
+    sample_rate: Option<u32>,
+    channels: Option<u8>,
+}
+
+impl TryFrom<QualityProfileRow> for QualityProfile {
+    type Error = QualityProfileError;
+
+    fn try_from(value: QualityProfileRow) -> Result<Self, Self::Error> {
+        Ok(Self {
+            format: AudioFormat::from_str(&value.format)
+                .map_err(|_| QualityProfileError::InvalidFormat(value.format))?,
+            name: value.name,
+            bitrate: value.bitrate,
+            vbr_quality: value.vbr_quality,
+            sample_rate: value.sample_rate,
+            channels: value.channels,
+        })
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum QualityProfileError {
+    #[error(transparent)]
+    Database(#[from] DatabaseError),
+    #[error(transparent)]
+    Parse(#[from] ParseError),
+    #[error(transparent)]
+    Settings(#[from] SettingsError),
+    #[error("Invalid format: {0}")]
+    InvalidFormat(String),
+    #[error("Invalid quality profile name")]
+    InvalidName,
+}
+
+/// The quality profiles available without any configuration, for the formats
+/// enabled in this build.
+#[must_use]
+pub fn builtin_quality_profiles() -> Vec<QualityProfile> {
+    #[allow(unused_mut)]
+    let mut profiles = vec![];
+
+    #[cfg(feature = "aac")]
+    profiles.extend([
+        QualityProfile {
+            name: MOBILE_DATA.to_string(),
+            format: AudioFormat::Aac,
+            bitrate: Some(96),
+            vbr_quality: None,
+            sample_rate: None,
+            channels: None,
+        },
+        QualityProfile {
+            name: WIFI.to_string(),
+            format: AudioFormat::Aac,
+            bitrate: Some(256),
+            vbr_quality: None,
+            sample_rate: None,
+            channels: None,
+        },
+    ]);
+
+    #[cfg(feature = "flac")]
+    profiles.push(QualityProfile {
+        name: ARCHIVE.to_string(),
+        format: AudioFormat::Flac,
+        bitrate: None,
+        vbr_quality: None,
+        sample_rate: None,
+        channels: None,
+    });
+
+    profiles
+}
+
+/// Returns the built-in quality profiles merged with the ones stored for the
+/// profile.
+///
+/// # Errors
+///
+/// * If the stored quality profiles failed to be fetched
+pub async fn get_quality_profiles(
+    db: &ConfigDatabase,
+    profile: &str,
+) -> Result<Vec<QualityProfile>, QualityProfileError> {
+    let rows: Vec<QualityProfileRow> = db
+        .select("quality_profiles")
+        .where_eq("profile", profile)
+        .sort("name", SortDirection::Asc)
+        .execute(db)
+        .await?
+        .to_value_type()?;
+
+    let stored = rows
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect::<Result<Vec<QualityProfile>, _>>()?;
+
+    let mut profiles = builtin_quality_profiles()
+        .into_iter()
+        .map(|builtin| {
+            stored
+                .iter()
+                .find(|x| x.name == builtin.name)
+                .cloned()
+                .unwrap_or(builtin)
+        })
+        .collect::<Vec<_>>();
+
+    let custom = stored
+        .into_iter()
+        .filter(|x| !profiles.iter().any(|p| p.name == x.name))
+        .collect::<Vec<_>>();
+
+    profiles.extend(custom);
+
+    Ok(profiles)
+}
+
+/// Returns the quality profile with the name, preferring the one stored for
+/// the profile over the built-in one.
+///
+/// # Errors
+///
+/// * If the stored quality profile failed to be fetched
+pub async fn get_quality_profile(
+    db: &ConfigDatabase,
+    profile: &str,
+    name: &str,
+) -> Result<Option<QualityProfile>, QualityProfileError> {
+    let row: Option<QualityProfileRow> = db
+        .select("quality_profiles")
+        .where_eq("profile", profile)
+        .where_eq("name", name)
+        .execute_first(db)
+        .await?
+        .as_ref()
+        .to_value_type()?;
+
+    if let Some(row) = row {
+        return Ok(Some(row.try_into()?));
+    }
+
+    Ok(builtin_quality_profiles()
+        .into_iter()
+        .find(|x| x.name == name))
+}
+
+/// Stores the quality profile for the profile, replacing the one with the
+/// same name.
+///
+/// # Errors
+///
+/// * If the name is empty
+/// * If the format's encoder doesn't support the settings
+/// * If the quality profile failed to be stored
+pub async fn upsert_quality_profile(
+    db: &ConfigDatabase,
+    profile: &str,
+    quality_profile: &QualityProfile,
+) -> Result<QualityProfile, QualityProfileError> {
+    if quality_profile.name.trim().is_empty() {
+        return Err(QualityProfileError::InvalidName);
+    }
+
+    validate_encoder_settings(quality_profile.format, &quality_profile.settings())?;
+
+    let row: QualityProfileRow = db
+        .upsert("quality_profiles")
+        .where_eq("profile", profile)
+        .where_eq("name", quality_profile.name.as_str())
+        .value("profile", profile)
+        .value("name", quality_profile.name.as_str())
+        .value("format", quality_profile.format.as_ref())
+        .value("bitrate", quality_profile.bitrate)
+        .value("vbr_quality", quality_profile.vbr_quality)
+        .value("sample_rate", quality_profile.sample_rate)
+        .value("channels", quality_profile.channels)
+        .execute_first(db)
+        .await?
+        .to_value_type()?;
+
+    row.try_into()
+}
+
+/// Deletes the quality profile stored for the profile. A built-in quality
+/// profile with the same name becomes available again.
+///
+/// # Errors
+///
+/// * If the quality profile failed to be deleted
+pub async fn delete_quality_profile(
+    db: &ConfigDatabase,
+    profile: &str,
+    name: &str,
+) -> Result<Option<QualityProfile>, QualityProfileError> {
+    let rows: Vec<QualityProfileRow> = db
+        .delete("quality_profiles")
+        .where_eq("profile", profile)
+        .where_eq("name", name)
+        .execute(db)
+        .await?
+        .to_value_type()?;
+
+    rows.into_iter().next().map(TryInto::try_into).transpose()
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test_log::test]
+    fn builtin_quality_profiles_have_valid_settings() {
+        for profile in builtin_quality_profiles() {
+            assert_eq!(
+                validate_encoder_settings(profile.format, &profile.settings()),
+                Ok(()),
+                "{}",
+                profile.name
+            );
+        }
+    }
+
+    #[test_log::test]
+    fn row_with_invalid_format_is_rejected() {
+        let row = QualityProfileRow {
+            name: "custom".to_string(),
+            format: "WAV".to_string(),
+            bitrate: None,
+            vbr_quality: None,
+            sample_rate: None,
+            channels: None,
+        };
+
+        assert!(matches!(
+            QualityProfile::try_from(row),
+            Err(QualityProfileError::InvalidFormat(format)) if format == "WAV"
+        ));
+    }
+}
@@ -0,0 +1,594 @@
+//! Byte-range seekable transcodes.
+//!
+//! A regular transcode is a single continuous encode, so its size isn't known
+//! up front and a byte offset doesn't correspond to a position in the audio.
+//! The seekable layout instead splits the track into the HLS segments and
+//! gives every segment a fixed number of bytes, computed from the constant bit
+//! rate and the number of frames in the segment:
+//!
+//! * MP3 is encoded without the bit reservoir and every frame is padded to the
+//!   same length, so a segment is exactly its frames
+//! * AAC frame sizes vary with the encoder's bit reservoir, so the last ADTS
+//!   frame of a segment is padded with fill elements up to the segment's size
+//!
+//! A byte range maps to the segments it overlaps, which are encoded on demand,
+//! so every request for the same bytes gets the same, playable content.
+
+use bytes::{Bytes, BytesMut};
+use futures::{stream, StreamExt as _};
+use moosicbox_audio_encoder::EncoderSettings;
+use moosicbox_core::types::AudioFormat;
+use moosicbox_music_api::TrackSource;
+
+use super::{
+    hls::{
+        default_bitrates, encode_segment, frame_samples, frames_to_seconds, segment_frames,
+        validate_variant, HlsError, SAMPLE_RATE,
+    },
+    track::BytesStream,
+};
+
+/// The most an AAC segment can exceed its nominal size by: the size of the
+/// encoder's bit reservoir for a stereo stream.
+#[cfg(feature = "aac")]
+const AAC_SEGMENT_MARGIN: u64 = 6144 * 2 / 8;
+
+#[cfg(feature = "aac")]
+const ADTS_HEADER_LENGTH: usize = 7;
+
+/// Returns the bit rate of the seekable transcode of the format with the
+/// settings, or `None` if the transcode can't be seekable.
+#[must_use]
+pub fn seekable_bitrate(format: AudioFormat, settings: &EncoderSettings) -> Option<u32> {
+    if settings.vbr_quality.is_some()
+        || settings.sample_rate.is_some_and(|x| x != SAMPLE_RATE)
+        || settings.channels.is_some_and(|x| x != 2)
+    {
+        return None;
+    }
+
+    let bitrate = settings
+        .bitrate
+        .unwrap_or_else(|| default_bitrates(format)[0]);
+
+    validate_variant(format, bitrate).ok().map(|()| bitrate)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Segment {
+    frames: u64,
+    offset: u64,
+    size: u64,
+}
+
+/// The byte positions of the segments of a seekable transcode.
+#[derive(Debug, Clone)]
+pub struct SeekableLayout {
+    format: AudioFormat,
+    bitrate: u32,
+    duration: f64,
+    segments: Vec<Segment>,
+}
+
+impl SeekableLayout {
+    /// # Errors
+    ///
+    /// * If the variant isn't supported
+    pub fn new(format: AudioFormat, bitrate: u32, duration: f64) -> Result<Self, HlsError> {
+        validate_variant(format, bitrate)?;
+
+        let mut offset = 0;
+        let segments = segment_frames(format, duration)?
+            .into_iter()
+            .map(|(start, end)| {
+                let frames = end - start;
+                let size = segment_size(format, bitrate, frames)?;
+                let segment = Segment {
+                    frames,
+                    offset,
+                    size,
+                };
+                offset += size;
+                Ok(segment)
+            })
+            .collect::<Result<Vec<_>, HlsError>>()?;
+
+        Ok(Self {
+            format,
+            bitrate,
+            duration,
+            segments,
+        })
+    }
+
+    /// The size of the whole transcode in bytes.
+    #[must_use]
+    pub fn size(&self) -> u64 {
+        self.segments.last().map_or(0, |x| x.offset + x.size)
+    }
+
+    fn segment_at(&self, offset: u64) -> Option<usize> {
+        let index = self
+            .segments
+            .partition_point(|x| x.offset + x.size <= offset);
+        (index < self.segments.len()).then_some(index)
+    }
+
+    /// Encodes the segment and pads it to its size.
+    async fn encode(&self, source: TrackSource, index: usize) -> Result<Vec<Bytes>, HlsError> {
+        let segment = self.segments[index];
+        let mut frames = encode_segment(
+            source,
+            self.format,
+            self.bitrate,
+            self.duration,
+            index as u64,
+        )
+        .await?;
+
+        match self.format {
+            #[cfg(feature = "mp3")]
+            AudioFormat::Mp3 => {
+                let length = mp3_frame_length(self.bitrate);
+                frames = frames
+                    .iter()
+                    .map(|frame| pad_mp3_frame(frame, length))
+                    .collect::<Result<_, _>>()?;
+            }
+            #[cfg(feature = "aac")]
+            AudioFormat::Aac => {
+                let size = frames.iter().map(Bytes::len).sum::<usize>() as u64;
+                let padding = segment.size.checked_sub(size).ok_or_else(|| {
+                    HlsError::Encode(format!(
+                        "Segment {index} is {size} bytes, over its size of {}",
+                        segment.size
+                    ))
+                })?;
+                if let Some(last) = frames.last_mut() {
+                    *last = pad_adts_frame(last, last.len() + padding as usize)?;
+                }
+            }
+            _ => return Err(HlsError::UnsupportedFormat(self.format)),
+        }
+
+        let size = frames.iter().map(Bytes::len).sum::<usize>() as u64;
+        if size != segment.size {
+            return Err(HlsError::Encode(format!(
+                "Segment {index} is {size} bytes instead of {}",
+                segment.size
+            )));
+        }
+
+        Ok(frames)
+    }
+
+    /// Streams the bytes from `start` to `end`, inclusive.
+    #[must_use]
+    pub fn stream(self, source: TrackSource, start: u64, end: u64) -> BytesStream {
+        let end = end.min(self.size().saturating_sub(1));
+        let first = self.segment_at(start);
+
+        stream::unfold(first, move |index| {
+            let source = source.clone();
+            let layout = self.clone();
+            async move {
+                let index = index?;
+                let segment = layout.segments[index];
+                if start > end || segment.offset > end {
+                    return None;
+                }
+
+                let bytes = match layout.encode(source, index).await {
+                    Ok(frames) => {
+                        let bytes = frames.concat();
+                        let from = start.saturating_sub(segment.offset) as usize;
+                        let to = (end + 1 - segment.offset).min(segment.size) as usize;
+                        Ok(Bytes::from(bytes).slice(from..to))
+                    }
+                    Err(e) => Err(std::io::Error::other(e)),
+                };
+
+                let next = if bytes.is_ok() && index + 1 < layout.segments.len() {
+                    Some(index + 1)
+                } else {
+                    None
+                };
+
+                Some((bytes, next))
+            }
+        })
+        .boxed()
+    }
+
+    /// Streams the transcode from the frame playing at `seconds`. Returns the
+    /// number of bytes streamed along with the stream.
+    ///
+    /// # Errors
+    ///
+    /// * If `seconds` is past the end of the track
+    /// * If the segment at `seconds` failed to be encoded
+    pub async fn stream_from(
+        self,
+        source: TrackSource,
+        seconds: f64,
+    ) -> Result<(u64, BytesStream), HlsError> {
+        let frame_samples = frame_samples(self.format)?;
+        let frame = (seconds.max(0.0) * f64::from(SAMPLE_RATE) / frame_samples as f64) as u64;
+
+        let mut segment_start = 0;
+        let index = self
+            .segments
+            .iter()
+            .position(|x| {
+                segment_start += x.frames;
+                frame < segment_start
+            })
+            .ok_or(HlsError::SegmentNotFound(frame))?;
+        let segment = self.segments[index];
+        let skipped = frame - (segment_start - segment.frames);
+
+        log::debug!(
+            "stream_from: seconds={seconds} segment={index} starts {}s into it",
+            frames_to_seconds(skipped, frame_samples)
+        );
+
+        let frames = self.encode(source.clone(), index).await?;
+        let head = BytesMut::from_iter(frames.iter().skip(skipped as usize).flatten()).freeze();
+        let size = head.len() as u64 + self.size() - (segment.offset + segment.size);
+
+        let rest = if index + 1 < self.segments.len() {
+            let start = segment.offset + segment.size;
+            let end = self.size() - 1;
+            self.stream(source, start, end)
+        } else {
+            stream::empty().boxed()
+        };
+
+        Ok((size, stream::once(async { Ok(head) }).chain(rest).boxed()))
+    }
+}
+
+fn segment_size(format: AudioFormat, bitrate: u32, frames: u64) -> Result<u64, HlsError> {
+    Ok(match format {
+        #[cfg(feature = "aac")]
+        AudioFormat::Aac => {
+            (frames * u64::from(bitrate) * 128_000).div_ceil(u64::from(SAMPLE_RATE))
+                + AAC_SEGMENT_MARGIN
+        }
+        #[cfg(feature = "mp3")]
+        AudioFormat::Mp3 => frames * mp3_frame_length(bitrate) as u64,
+        _ => {
+            let _ = (bitrate, frames);
+            return Err(HlsError::UnsupportedFormat(format));
+        }
+    })
+}
+
+/// The length of a 44.1kHz MPEG-1 Layer III frame with its padding slot.
+#[cfg(feature = "mp3")]
+fn mp3_frame_length(bitrate: u32) -> usize {
+    let bytes = 144_000 * bitrate as usize;
+    let rate = SAMPLE_RATE as usize;
+    bytes / rate + usize::from(!bytes.is_multiple_of(rate))
+}
+
+/// Pads an MPEG audio frame to `length` by setting the padding bit and
+/// appending the padding slot, if the frame doesn't already have it.
+#[cfg(feature = "mp3")]
+fn pad_mp3_frame(frame: &[u8], length: usize) -> Result<Bytes, HlsError> {
+    if frame.len() == length {
+        return Ok(Bytes::copy_from_slice(frame));
+    }
+
+    let protected = frame[1] & 0x01 == 0;
+    let padded = frame[2] & 0x02 != 0;
+    if protected || padded || frame.len() + 1 != length {
+        return Err(HlsError::Encode(format!(
+            "Can't pad a {} byte MP3 frame to {length} bytes",
+            frame.len()
+        )));
+    }
+
+    let mut frame = frame.to_vec();
+    frame[2] |= 0x02;
+    frame.push(0);
+
+    Ok(frame.into())
+}
+
+/// Pads an ADTS frame to `length` by inserting fill elements before the END
+/// element of its raw data block.
+#[cfg(feature = "aac")]
+fn pad_adts_frame(frame: &[u8], length: usize) -> Result<Bytes, HlsError> {
+    const ID_FIL: u32 = 6;
+    const ID_END: u32 = 7;
+    const FILL_BYTE: u32 = 0xA5;
+
+    if frame.len() == length {
+        return Ok(Bytes::copy_from_slice(frame));
+    }
+
+    let invalid = |reason: &str| {
+        HlsError::Encode(format!(
+            "Can't pad a {} byte ADTS frame to {length} bytes: {reason}",
+            frame.len()
+        ))
+    };
+
+    if frame.len() <= ADTS_HEADER_LENGTH || length < frame.len() || length > 0x1FFF {
+        return Err(invalid("invalid length"));
+    }
+    if frame[1] & 0x01 == 0 || frame[6] & 0x03 != 0 {
+        return Err(invalid(
+            "only single raw data blocks without a CRC are supported",
+        ));
+    }
+
+    // The END element (0b111) is only followed by the zero bits of the byte
+    // alignment, so the last set bit is the end of the END element
+    let payload = &frame[ADTS_HEADER_LENGTH..];
+    let last_byte = payload
+        .iter()
+        .rposition(|x| *x != 0)
+        .ok_or_else(|| invalid("no END element"))?;
+    let last_bit = last_byte * 8 + 7 - payload[last_byte].trailing_zeros() as usize;
+    let end_element = last_bit
+        .checked_sub(2)
+        .filter(|x| (*x..=last_bit).all(|bit| payload[bit / 8] & (0x80 >> (bit % 8)) != 0))
+        .ok_or_else(|| invalid("no END element"))?;
+
+    let mut writer = BitWriter::default();
+    for bit in 0..end_element {
+        writer.write(u32::from(payload[bit / 8] >> (7 - bit % 8)) & 1, 1);
+    }
+
+    let mut available = (length - ADTS_HEADER_LENGTH) * 8 - end_element - 3;
+    while available >= 7 {
+        // Each fill element has a 3 bit ID, a 4 bit count, an 8 bit escape
+        // count for 15 bytes and over, and the count's bytes
+        let count = if available >= 15 + 8 * 15 {
+            ((available - 15) / 8).min(269)
+        } else {
+            ((available - 7) / 8).min(14)
+        };
+
+        writer.write(ID_FIL, 3);
+        if count >= 15 {
+            writer.write(15, 4);
+            writer.write((count - 14) as u32, 8);
+            available -= 15 + count * 8;
+        } else {
+            writer.write(count as u32, 4);
+            available -= 7 + count * 8;
+        }
+
+        if count > 0 {
+            // An EXT_FILL extension payload: the type and fill nibble, then
+            // the fill bytes
+            writer.write(0, 8);
+            for _ in 1..count {
+                writer.write(FILL_BYTE, 8);
+            }
+        }
+    }
+
+    writer.write(ID_END, 3);
+    let payload = writer.finish();
+
+    let mut padded = Vec::with_capacity(length);
+    padded.extend_from_slice(&frame[..ADTS_HEADER_LENGTH]);
+    padded.extend_from_slice(&payload);
+
+    if padded.len() != length {
+        return Err(invalid("unexpected padded length"));
+    }
+
+    // Set the frame length and a buffer fullness of 0x7FF, i.e. a variable
+    // bit rate, since the padding changes the decoder's buffer state
+    padded[3] = (padded[3] & 0xFC) | ((length >> 11) & 0x03) as u8;
+    padded[4] = ((length >> 3) & 0xFF) as u8;
+    padded[5] = (((length & 0x07) << 5) as u8) | 0x1F;
+    padded[6] = 0xFC | (padded[6] & 0x03);
+
+    Ok(padded.into())
+}
+
+#[cfg(feature = "aac")]
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    bits: usize,
+}
+
+#[cfg(feature = "aac")]
+impl BitWriter {
+    fn write(&mut self, value: u32, bits: usize) {
+        for i in (0..bits).rev() {
+            if self.bits.is_multiple_of(8) {
+                self.bytes.push(0);
+            }
+            if (value >> i) & 1 == 1 {
+                *self.bytes.last_mut().unwrap() |= 0x80 >> (self.bits % 8);
+            }
+            self.bits += 1;
+        }
+    }
+
+    fn finish(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use symphonia::core::{
+        audio::SampleBuffer, codecs::DecoderOptions, formats::FormatOptions, io::MediaSourceStream,
+        meta::MetadataOptions, probe::Hint,
+    };
+
+    use super::*;
+    use crate::files::hls::encode_frames;
+
+    /// Deterministic stereo noise, the hardest signal for the encoders to
+    /// keep to their bit rate.
+    fn noise(frames: usize) -> Vec<i16> {
+        let mut state = 0x1234_5678_u32;
+        (0..frames * 2)
+            .map(|_| {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (state >> 16) as i16 / 2
+            })
+            .collect()
+    }
+
+    fn decode(data: Vec<u8>, extension: &str) -> Vec<f32> {
+        let stream =
+            MediaSourceStream::new(Box::new(std::io::Cursor::new(data)), Default::default());
+        let mut hint = Hint::new();
+        hint.with_extension(extension);
+
+        let mut reader = symphonia::default::get_probe()
+            .format(
+                &hint,
+                stream,
+                &FormatOptions::default(),
+                &MetadataOptions::default(),
+            )
+            .unwrap()
+            .format;
+        let track = reader.default_track().unwrap();
+        let mut decoder = symphonia::default::get_codecs()
+            .make(&track.codec_params, &DecoderOptions::default())
+            .unwrap();
+
+        let mut samples = vec![];
+        while let Ok(packet) = reader.next_packet() {
+            let decoded = decoder.decode(&packet).unwrap();
+            let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, *decoded.spec());
+            buffer.copy_interleaved_ref(decoded);
+            samples.extend_from_slice(buffer.samples());
+        }
+
+        samples
+    }
+
+    #[cfg(feature = "aac")]
+    #[test_log::test]
+    fn padded_adts_frames_decode_identically() {
+        let frames = encode_frames(AudioFormat::Aac, 128, &noise(44_100)).unwrap();
+
+        let padded = frames
+            .iter()
+            .enumerate()
+            .map(|(i, frame)| {
+                let length = frame.len() + i * 37 % 400;
+                let padded = pad_adts_frame(frame, length).unwrap();
+                assert_eq!(padded.len(), length);
+                padded
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            decode(padded.concat(), "aac"),
+            decode(frames.concat(), "aac")
+        );
+    }
+
+    #[cfg(feature = "aac")]
+    #[test_log::test]
+    fn aac_segments_fit_their_size() {
+        let layout = SeekableLayout::new(AudioFormat::Aac, 256, 6.0).unwrap();
+        let segment = layout.segments[0];
+        let frames = encode_frames(AudioFormat::Aac, 256, &noise(44_100 * 7)).unwrap();
+
+        let size = frames[..segment.frames as usize]
+            .iter()
+            .map(Bytes::len)
+            .sum::<usize>() as u64;
+
+        assert!(size <= segment.size, "{size} > {}", segment.size);
+    }
+
+    #[cfg(feature = "mp3")]
+    #[test_log::test]
+    fn padded_mp3_frames_have_a_constant_length_and_decode_identically() {
+        let frames = encode_frames(AudioFormat::Mp3, 128, &noise(44_100)).unwrap();
+        let length = mp3_frame_length(128);
+
+        let padded = frames
+            .iter()
+            .map(|frame| pad_mp3_frame(frame, length).unwrap())
+            .collect::<Vec<_>>();
+
+        assert!(padded.iter().all(|frame| frame.len() == length));
+        assert_eq!(
+            decode(padded.concat(), "mp3"),
+            decode(frames.concat(), "mp3")
+        );
+    }
+
+    #[cfg(feature = "mp3")]
+    #[test_log::test]
+    fn layout_maps_offsets_to_segments() {
+        let layout = SeekableLayout::new(AudioFormat::Mp3, 320, 14.0).unwrap();
+        let frame = mp3_frame_length(320) as u64;
+
+        // 6s is 229.7 frames of 1152 samples at 44.1kHz
+        assert_eq!(
+            layout
+                .segments
+                .iter()
+                .map(|x| (x.frames, x.offset))
+                .collect::<Vec<_>>(),
+            vec![(230, 0), (229, 230 * frame), (77, 459 * frame)]
+        );
+        assert_eq!(layout.size(), 536 * frame);
+        assert_eq!(layout.segment_at(230 * frame - 1), Some(0));
+        assert_eq!(layout.segment_at(230 * frame), Some(1));
+        assert_eq!(layout.segment_at(layout.size() - 1), Some(2));
+        assert_eq!(layout.segment_at(layout.size()), None);
+    }
+
+    #[test_log::test]
+    fn only_constant_bitrate_stereo_transcodes_are_seekable() {
+        #[cfg(feature = "aac")]
+        assert_eq!(
+            seekable_bitrate(AudioFormat::Aac, &EncoderSettings::default()),
+            Some(256)
+        );
+        #[cfg(feature = "mp3")]
+        assert_eq!(
+            seekable_bitrate(
+                AudioFormat::Mp3,
+                &EncoderSettings {
+                    bitrate: Some(192),
+                    ..Default::default()
+                }
+            ),
+            Some(192)
+        );
+        #[cfg(feature = "mp3")]
+        assert_eq!(
+            seekable_bitrate(
+                AudioFormat::Mp3,
+                &EncoderSettings {
+                    vbr_quality: Some(2),
+                    ..Default::default()
+                }
+            ),
+            None
+        );
+        #[cfg(feature = "aac")]
+        assert_eq!(
+            seekable_bitrate(
+                AudioFormat::Aac,
+                &EncoderSettings {
+                    channels: Some(1),
+                    ..Default::default()
+                }
+            ),
+            None
+        );
+    }
+}
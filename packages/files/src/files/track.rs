@@ -9,8 +9,9 @@ use futures::prelude::*;
 use futures_core::Stream;
 use moosicbox_audio_decoder::{
     decode_file_path_str_async, decode_media_source_async,
-    media_sources::remote_bytestream::RemoteByteStreamMediaSource, DecodeError,
+    media_sources::remote_bytestream::RemoteByteStreamMediaSource, time_range_seek, DecodeError,
 };
+use moosicbox_audio_encoder::{EncoderSettings, SettingsError};
 use moosicbox_audio_output::{AudioOutputError, AudioWrite, Channels, SignalSpec};
 use moosicbox_core::{
     sqlite::{
@@ -168,6 +169,8 @@ pub enum GetTrackBytesError {
     TrackInfo(#[from] TrackInfoError),
     #[error(transparent)]
     Commander(#[from] CommanderError),
+    #[error(transparent)]
+    Settings(#[from] SettingsError),
     #[error("Track not found")]
     NotFound,
     #[error("Unsupported format")]
@@ -205,18 +208,24 @@ impl std::fmt::Debug for TrackBytes {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn get_track_bytes(
     api: &dyn MusicApi,
     track_id: &Id,
     source: TrackSource,
     format: AudioFormat,
+    settings: EncoderSettings,
     try_to_get_size: bool,
     start: Option<u64>,
     end: Option<u64>,
 ) -> Result<TrackBytes, GetTrackBytesError> {
-    log::debug!("get_track_bytes: Getting track bytes track_id={track_id} format={format:?} try_to_get_size={try_to_get_size} start={start:?} end={end:?}");
+    log::debug!("get_track_bytes: Getting track bytes track_id={track_id} format={format:?} settings={settings:?} try_to_get_size={try_to_get_size} start={start:?} end={end:?}");
+
+    validate_encoder_settings(format, &settings)?;
 
-    let size = if try_to_get_size {
+    // The stored track sizes are for the default encoder settings. Virtual tracks are encoded on
+    // the fly, so their size isn't known up front
+    let size = if try_to_get_size && settings.is_default() && source.time_range().is_none() {
         match get_or_init_track_size(api, track_id, &source, PlaybackQuality { format }).await {
             Ok(size) => Some(size),
             Err(err) => match err {
@@ -257,7 +266,33 @@ pub async fn get_track_bytes(
         _ => format,
     };
 
-    get_audio_bytes(source, format, size, start, end).await
+    get_audio_bytes(source, format, settings, size, start, end).await
+}
+
+/// Validates the encoder settings against the encoder of the format.
+///
+/// # Errors
+///
+/// * If the format doesn't support a setting
+pub fn validate_encoder_settings(
+    format: AudioFormat,
+    settings: &EncoderSettings,
+) -> Result<(), SettingsError> {
+    if settings.is_default() {
+        return Ok(());
+    }
+
+    match format {
+        #[cfg(feature = "aac")]
+        AudioFormat::Aac => moosicbox_audio_encoder::aac::validate_settings(settings),
+        #[cfg(feature = "flac")]
+        AudioFormat::Flac => moosicbox_audio_encoder::flac::validate_settings(settings),
+        #[cfg(feature = "mp3")]
+        AudioFormat::Mp3 => moosicbox_audio_encoder::mp3::validate_settings(settings),
+        #[cfg(feature = "opus")]
+        AudioFormat::Opus => moosicbox_audio_encoder::opus::validate_settings(settings),
+        AudioFormat::Source => Err(SettingsError::Unsupported),
+    }
 }
 
 #[derive(Debug, Error)]
@@ -337,13 +372,32 @@ pub async fn get_silence_bytes(
 pub async fn get_audio_bytes(
     source: TrackSource,
     format: AudioFormat,
+    settings: EncoderSettings,
     size: Option<u64>,
     start: Option<u64>,
     end: Option<u64>,
 ) -> Result<TrackBytes, GetTrackBytesError> {
-    log::debug!("Getting audio bytes format={format:?} size={size:?} start={start:?} end={end:?}");
+    log::debug!("Getting audio bytes format={format:?} settings={settings:?} size={size:?} start={start:?} end={end:?}");
+
+    // Virtual tracks can't be served straight from their file, so they are re-encoded to the
+    // format of the file instead
+    let format = match (format, source.time_range()) {
+        (AudioFormat::Source, Some(_)) => match source.format() {
+            AudioFormat::Source => return Err(GetTrackBytesError::UnsupportedFormat),
+            #[allow(unreachable_patterns)]
+            format => format,
+        },
+        _ => format,
+    };
 
-    get_or_fetch_track(&source, format, size, start, end, {
+    #[cfg(feature = "transcode-cache")]
+    if let Some(track_bytes) =
+        super::transcode_cache::get_cached_track_bytes(&source, format, &settings, start, end).await
+    {
+        return Ok(track_bytes);
+    }
+
+    get_or_fetch_track(&source, format, settings, size, start, end, {
         let source = source.clone();
         move |start, end, size| {
             let source = source.clone();
@@ -353,7 +407,9 @@ pub async fn get_audio_bytes(
                 let writer_id = writer.id;
                 #[allow(unused)]
                 let stream = writer.stream();
-                let same_format = source.format() == format;
+                let same_format = source.format() == format
+                    && settings.is_default()
+                    && source.time_range().is_none();
 
                 let track_bytes = if same_format {
                     match source {
@@ -375,6 +431,8 @@ pub async fn get_audio_bytes(
                                     Box::new(move |spec, duration| {
                                         Ok(Box::new(
                                             AacEncoder::with_writer(writer.clone())
+                                                .with_settings(&settings)
+                                                .map_err(encoder_settings_error)?
                                                 .open(spec, duration),
                                         ))
                                     }),
@@ -399,6 +457,8 @@ pub async fn get_audio_bytes(
                                     Box::new(move |spec, duration| {
                                         Ok(Box::new(
                                             Mp3Encoder::with_writer(writer.clone())
+                                                .with_settings(&settings)
+                                                .map_err(encoder_settings_error)?
                                                 .open(spec, duration),
                                         ))
                                     }),
@@ -411,6 +471,8 @@ pub async fn get_audio_bytes(
                                     Box::new(move |spec, duration| {
                                         Ok(Box::new(
                                             OpusEncoder::with_writer(writer.clone())
+                                                .with_settings(&settings)
+                                                .map_err(encoder_settings_error)?
                                                 .open(spec, duration),
                                         ))
                                     }),
@@ -422,7 +484,19 @@ pub async fn get_audio_bytes(
                         })
                     };
 
-                    match source {
+                    // Virtual tracks are cut out of their file
+                    let time_range = source.time_range();
+                    let get_handler = move || {
+                        let handler: moosicbox_audio_decoder::AudioDecodeHandler = get_handler()?;
+
+                        Ok(match time_range {
+                            Some((start, end)) => handler.with_time_range(start, end),
+                            None => handler,
+                        })
+                    };
+
+                    #[cfg_attr(not(feature = "transcode-cache"), allow(unused_variables))]
+                    let transcoded = match source {
                         TrackSource::LocalFilePath { ref path, .. } => {
                             if let Err(err) = decode_file_path_str_async(
                                 path,
@@ -430,11 +504,14 @@ pub async fn get_audio_bytes(
                                 true,
                                 true,
                                 None,
-                                None,
+                                time_range.and_then(|(start, _)| time_range_seek(start)),
                             )
                             .await
                             {
                                 log::error!("Failed to encode to aac: {err:?}");
+                                false
+                            } else {
+                                true
                             }
                         }
                         TrackSource::RemoteUrl { ref url, .. } => {
@@ -463,8 +540,22 @@ pub async fn get_audio_bytes(
                             .await
                             {
                                 log::error!("Failed to encode to aac: {err:?}");
+                                false
+                            } else {
+                                true
                             }
                         }
+                    };
+
+                    #[allow(unused_mut)]
+                    let mut stream = stream.boxed();
+
+                    // Only complete transcodes are cached
+                    #[cfg(feature = "transcode-cache")]
+                    if transcoded {
+                        stream = super::transcode_cache::cache_transcode(
+                            &source, format, &settings, stream,
+                        );
                     }
 
                     match source {
@@ -476,7 +567,7 @@ pub async fn get_audio_bytes(
                             #[allow(unreachable_patterns)]
                             _ => TrackBytes {
                                 id: writer_id,
-                                stream: StalledReadMonitor::new(stream.boxed()),
+                                stream: StalledReadMonitor::new(stream),
                                 size,
                                 original_size: size,
                                 format,
@@ -490,7 +581,7 @@ pub async fn get_audio_bytes(
                             #[allow(unreachable_patterns)]
                             _ => TrackBytes {
                                 id: writer_id,
-                                stream: StalledReadMonitor::new(stream.boxed()),
+                                stream: StalledReadMonitor::new(stream),
                                 size,
                                 original_size: size,
                                 format,
@@ -507,7 +598,12 @@ pub async fn get_audio_bytes(
     .await
 }
 
-async fn request_audio_bytes_from_file(
+#[cfg(any(feature = "aac", feature = "mp3", feature = "opus"))]
+fn encoder_settings_error(err: impl std::fmt::Debug) -> moosicbox_audio_decoder::AudioDecodeError {
+    std::io::Error::other(format!("Invalid encoder settings: {err:?}")).into()
+}
+
+pub(crate) async fn request_audio_bytes_from_file(
     path: String,
     format: AudioFormat,
     size: Option<u64>,
@@ -737,9 +833,16 @@ pub async fn get_or_init_track_visualization(
     let viz = Arc::new(RwLock::new(vec![]));
     let inner_viz = viz.clone();
 
-    let bytes = get_audio_bytes(source.clone(), source.format(), None, None, None)
-        .await
-        .map_err(Box::new)?;
+    let bytes = get_audio_bytes(
+        source.clone(),
+        source.format(),
+        EncoderSettings::default(),
+        None,
+        None,
+        None,
+    )
+    .await
+    .map_err(Box::new)?;
 
     let get_handler = move || {
         Ok(
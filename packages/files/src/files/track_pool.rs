@@ -11,6 +11,7 @@ use flume::{bounded, SendError, Sender};
 use futures::StreamExt;
 use futures_core::Future;
 use lazy_static::lazy_static;
+use moosicbox_audio_encoder::EncoderSettings;
 use moosicbox_core::types::AudioFormat;
 use moosicbox_music_api::TrackSource;
 use moosicbox_stream_utils::{stalled_monitor::StalledReadMonitor, ByteWriter};
@@ -65,6 +66,7 @@ pub enum Command {
         tx: Sender<TrackBytes>,
         source: TrackSource,
         output_format: AudioFormat,
+        settings: EncoderSettings,
         size: Option<u64>,
         start: Option<u64>,
         end: Option<u64>,
@@ -98,16 +100,18 @@ impl Context {
         Self::default()
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn fetch_track_bytes(
         &mut self,
         source: TrackSource,
         output_format: AudioFormat,
+        settings: &EncoderSettings,
         size: Option<u64>,
         start: Option<u64>,
         end: Option<u64>,
         fetch: FetchTrackBytesFunc,
     ) -> Result<TrackBytes, GetTrackBytesError> {
-        let key = track_key(&source, output_format);
+        let key = track_key(&source, output_format, settings);
         log::debug!("get_or_fetch_track key={key}");
 
         let semaphore = self
@@ -230,6 +234,7 @@ impl service::Processor for service::Service {
                 tx,
                 source,
                 output_format,
+                settings,
                 size,
                 start,
                 end,
@@ -238,7 +243,15 @@ impl service::Processor for service::Service {
                 tx.send_async(
                     ctx.write()
                         .await
-                        .fetch_track_bytes(source, output_format, size, start, end, fetch)
+                        .fetch_track_bytes(
+                            source,
+                            output_format,
+                            &settings,
+                            size,
+                            start,
+                            end,
+                            fetch,
+                        )
                         .await?,
                 )
                 .await?;
@@ -398,22 +411,32 @@ impl TrackBytesSource {
     }
 }
 
-pub fn track_key(source: &TrackSource, output_format: AudioFormat) -> String {
-    match source {
+pub fn track_key(
+    source: &TrackSource,
+    output_format: AudioFormat,
+    settings: &EncoderSettings,
+) -> String {
+    let key = match source {
         TrackSource::LocalFilePath {
             format,
             path,
             track_id,
             source,
+            start_offset,
+            end_offset,
         } => {
-            format!(
-                "local:{source}:{format}:{id}:{output_format}",
-                id = track_id
-                    .as_ref()
-                    .map(|x| format!("id:{x}"))
-                    .as_deref()
-                    .unwrap_or(path)
-            )
+            let id = track_id.as_ref().map_or_else(
+                || match (start_offset, end_offset) {
+                    (None, None) => path.to_string(),
+                    (start, end) => format!(
+                        "{path}:{}-{}",
+                        start.unwrap_or(0.0),
+                        end.map(|x| x.to_string()).unwrap_or_default()
+                    ),
+                },
+                |x| format!("id:{x}"),
+            );
+            format!("local:{source}:{format}:{id}:{output_format}")
         }
         TrackSource::RemoteUrl {
             format,
@@ -428,12 +451,34 @@ pub fn track_key(source: &TrackSource, output_format: AudioFormat) -> String {
                 .as_deref()
                 .unwrap_or(url)
         ),
+    };
+
+    if settings.is_default() {
+        return key;
     }
+
+    let EncoderSettings {
+        bitrate,
+        vbr_quality,
+        sample_rate,
+        channels,
+    } = settings;
+
+    [
+        ("br", bitrate.map(|x| x.to_string())),
+        ("q", vbr_quality.map(|x| x.to_string())),
+        ("sr", sample_rate.map(|x| x.to_string())),
+        ("ch", channels.map(|x| x.to_string())),
+    ]
+    .into_iter()
+    .filter_map(|(name, value)| value.map(|value| format!(":{name}={value}")))
+    .fold(key, |key, setting| key + &setting)
 }
 
 pub async fn get_or_fetch_track(
     source: &TrackSource,
     output_format: AudioFormat,
+    settings: EncoderSettings,
     size: Option<u64>,
     start: Option<u64>,
     end: Option<u64>,
@@ -463,6 +508,7 @@ pub async fn get_or_fetch_track(
             tx,
             source: source.clone(),
             output_format,
+            settings,
             size,
             start,
             end,
@@ -476,3 +522,84 @@ pub async fn get_or_fetch_track(
 
     Ok(bytes)
 }
+
+#[cfg(test)]
+mod test {
+    use moosicbox_core::sqlite::models::TrackApiSource;
+    use pretty_assertions::{assert_eq, assert_ne};
+
+    use super::*;
+
+    fn source() -> TrackSource {
+        TrackSource::LocalFilePath {
+            path: "/music/track.flac".to_string(),
+            format: AudioFormat::Source,
+            track_id: Some(1.into()),
+            source: TrackApiSource::Local,
+            start_offset: None,
+            end_offset: None,
+        }
+    }
+
+    #[test_log::test]
+    fn track_key_is_unchanged_for_default_settings() {
+        assert_eq!(
+            track_key(&source(), AudioFormat::Source, &EncoderSettings::default()),
+            "local:LOCAL:SOURCE:id:1:SOURCE"
+        );
+    }
+
+    #[test_log::test]
+    fn track_key_includes_the_encoder_settings() {
+        let settings = EncoderSettings {
+            bitrate: Some(96),
+            channels: Some(1),
+            ..Default::default()
+        };
+
+        let key = track_key(&source(), AudioFormat::Source, &settings);
+
+        assert_eq!(key, "local:LOCAL:SOURCE:id:1:SOURCE:br=96:ch=1");
+        assert_ne!(
+            key,
+            track_key(
+                &source(),
+                AudioFormat::Source,
+                &EncoderSettings {
+                    bitrate: Some(256),
+                    ..settings
+                }
+            )
+        );
+    }
+
+    #[test_log::test]
+    fn track_key_distinguishes_virtual_tracks_without_ids() {
+        let track = |start_offset, end_offset| TrackSource::LocalFilePath {
+            path: "/music/album.flac".to_string(),
+            format: AudioFormat::Source,
+            track_id: None,
+            source: TrackApiSource::Local,
+            start_offset,
+            end_offset,
+        };
+        let settings = EncoderSettings::default();
+
+        assert_eq!(
+            track_key(&track(None, None), AudioFormat::Source, &settings),
+            "local:LOCAL:SOURCE:/music/album.flac:SOURCE"
+        );
+        assert_eq!(
+            track_key(
+                &track(Some(0.0), Some(312.5)),
+                AudioFormat::Source,
+                &settings
+            ),
+            "local:LOCAL:SOURCE:/music/album.flac:0-312.5:SOURCE"
+        );
+        assert_eq!(
+            track_key(&track(Some(312.5), None), AudioFormat::Source, &settings),
+            "local:LOCAL:SOURCE:/music/album.flac:312.5-:SOURCE"
+        );
+    }
+}
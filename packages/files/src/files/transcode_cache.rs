@@ -0,0 +1,877 @@
+//! A disk-backed cache of transcoded tracks.
+//!
+//! Finished transcodes are written under the config cache dir, keyed by the
+//! [`track_key`] of the track source, output format and encoder settings, so
+//! they survive restarts. Every entry is a data file next to a metadata file
+//! holding its size and SHA-256 checksum, and the cache is kept under its size
+//! limit by evicting the least recently used entries.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufWriter, Read as _, Write as _},
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Mutex, OnceLock,
+    },
+    task::{Context, Poll},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use futures::{Stream, StreamExt as _};
+use moosicbox_audio_encoder::EncoderSettings;
+use moosicbox_core::{
+    sqlite::models::{ApiSource, Id},
+    types::AudioFormat,
+};
+use moosicbox_music_api::{
+    MusicApi, MusicApis, MusicApisError, SourceToMusicApi as _, TrackAudioQuality, TrackSource,
+    TracksError,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+use thiserror::Error;
+use tokio::sync::Semaphore;
+
+use super::{
+    filename_from_path_str,
+    track::{
+        get_track_bytes, get_track_id_source, request_audio_bytes_from_file, BytesStream,
+        BytesStreamItem, GetTrackBytesError, TrackBytes, TrackSourceError,
+    },
+    track_pool::track_key,
+};
+
+/// The default maximum size of the cache in bytes.
+pub const DEFAULT_MAX_SIZE: u64 = 10 * 1024 * 1024 * 1024;
+
+pub static TRANSCODE_CACHE: OnceLock<TranscodeCache> = OnceLock::new();
+
+/// Only one track is pre-transcoded at a time so the background jobs don't
+/// starve the playback transcodes.
+static PRE_TRANSCODE_PERMIT: Semaphore = Semaphore::const_new(1);
+
+static WRITER_ID: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Error)]
+pub enum TranscodeCacheError {
+    #[error(transparent)]
+    IO(#[from] std::io::Error),
+    #[error(transparent)]
+    Serde(#[from] serde_json::Error),
+    #[error("Failed to get the cache directory")]
+    NoCacheDir,
+    #[error("Transcode cache not initialized")]
+    NotInitialized,
+    #[error("Transcode cache already initialized")]
+    AlreadyInitialized,
+}
+
+/// Initializes the global [`TRANSCODE_CACHE`] in the `transcodes` directory
+/// of the config cache dir.
+///
+/// # Errors
+///
+/// * If the cache directory couldn't be created or read
+/// * If the cache was already initialized
+pub fn init(max_size: u64) -> Result<(), TranscodeCacheError> {
+    let dir = moosicbox_config::make_cache_dir_path()
+        .ok_or(TranscodeCacheError::NoCacheDir)?
+        .join("transcodes");
+
+    let cache = TranscodeCache::new(dir, max_size)?;
+
+    TRANSCODE_CACHE
+        .set(cache)
+        .map_err(|_| TranscodeCacheError::AlreadyInitialized)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EntryMetadata {
+    key: String,
+    format: AudioFormat,
+    size: u64,
+    sha256: String,
+}
+
+struct Entry {
+    metadata: EntryMetadata,
+    last_access: SystemTime,
+    /// Whether the checksum of the data file was verified by this process.
+    verified: bool,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TranscodeCacheStats {
+    pub entries: u64,
+    pub size: u64,
+    pub max_size: u64,
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    pub pending_transcodes: u64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TranscodeCachePurge {
+    pub entries: u64,
+    pub size: u64,
+}
+
+pub struct TranscodeCache {
+    dir: PathBuf,
+    max_size: u64,
+    entries: Mutex<HashMap<String, Entry>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+    pending_transcodes: AtomicU64,
+}
+
+fn entry_name(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+fn data_file_name(name: &str, format: AudioFormat) -> String {
+    format!("{name}.{}", format.as_ref().to_lowercase())
+}
+
+fn metadata_file_name(name: &str) -> String {
+    format!("{name}.json")
+}
+
+fn file_sha256(path: &Path) -> Result<String, std::io::Error> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; 64 * 1024];
+
+    loop {
+        let count = file.read(&mut buf)?;
+        if count == 0 {
+            break;
+        }
+        hasher.update(&buf[..count]);
+    }
+
+    Ok(hex::encode(hasher.finalize()))
+}
+
+fn remove_file(path: &Path) {
+    if let Err(e) = std::fs::remove_file(path) {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            log::error!("Failed to remove transcode cache file {path:?}: {e:?}");
+        }
+    }
+}
+
+impl TranscodeCache {
+    /// Opens the cache in `dir`, dropping the unfinished, orphaned and
+    /// truncated entries left behind by a previous run.
+    ///
+    /// # Errors
+    ///
+    /// * If the directory couldn't be created or read
+    pub fn new(dir: PathBuf, max_size: u64) -> Result<Self, TranscodeCacheError> {
+        std::fs::create_dir_all(&dir)?;
+
+        let mut data_files = HashMap::new();
+        let mut metadata_files = vec![];
+
+        for dir_entry in std::fs::read_dir(&dir)? {
+            let path = dir_entry?.path();
+
+            let Some(file_name) = path.file_name().and_then(|x| x.to_str()) else {
+                continue;
+            };
+
+            if file_name.ends_with(".part") {
+                log::debug!("Removing unfinished transcode {path:?}");
+                remove_file(&path);
+                continue;
+            }
+
+            let Some(stem) = path.file_stem().and_then(|x| x.to_str()) else {
+                continue;
+            };
+            let stem = stem.to_string();
+
+            if path.extension().is_some_and(|x| x == "json") {
+                metadata_files.push((stem, path));
+            } else {
+                data_files.insert(stem, path);
+            }
+        }
+
+        let mut entries = HashMap::new();
+
+        for (name, metadata_path) in metadata_files {
+            let entry = std::fs::read(&metadata_path)
+                .ok()
+                .and_then(|x| serde_json::from_slice::<EntryMetadata>(&x).ok())
+                .filter(|metadata| entry_name(&metadata.key) == name)
+                .and_then(|metadata| {
+                    let path = data_files.get(&name)?;
+                    let file_metadata = std::fs::metadata(path).ok()?;
+
+                    (file_metadata.len() == metadata.size
+                        && *path == dir.join(data_file_name(&name, metadata.format)))
+                    .then(|| Entry {
+                        metadata,
+                        last_access: file_metadata.modified().unwrap_or(UNIX_EPOCH),
+                        verified: false,
+                    })
+                });
+
+            if let Some(entry) = entry {
+                data_files.remove(&name);
+                entries.insert(name, entry);
+            } else {
+                log::debug!("Removing invalid transcode cache entry {metadata_path:?}");
+                remove_file(&metadata_path);
+            }
+        }
+
+        for path in data_files.into_values() {
+            log::debug!("Removing orphaned transcode {path:?}");
+            remove_file(&path);
+        }
+
+        log::debug!(
+            "Opened transcode cache in {dir:?} with {} entries",
+            entries.len()
+        );
+
+        let cache = Self {
+            dir,
+            max_size,
+            entries: Mutex::new(HashMap::new()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+            pending_transcodes: AtomicU64::new(0),
+        };
+
+        let evicted = cache.evict(&mut entries);
+        *cache.entries.lock().unwrap() = entries;
+        cache.remove_files(evicted);
+
+        Ok(cache)
+    }
+
+    #[must_use]
+    pub fn contains(&self, key: &str) -> bool {
+        self.entries.lock().unwrap().contains_key(&entry_name(key))
+    }
+
+    /// Returns the path and size of the cached transcode for the key. The
+    /// checksum of the entry is verified on its first hit, and corrupted
+    /// entries are removed.
+    pub async fn get(&self, key: &str) -> Option<(PathBuf, u64)> {
+        let name = entry_name(key);
+
+        let Some((format, size, sha256, verified)) =
+            self.entries.lock().unwrap().get(&name).map(|entry| {
+                (
+                    entry.metadata.format,
+                    entry.metadata.size,
+                    entry.metadata.sha256.clone(),
+                    entry.verified,
+                )
+            })
+        else {
+            self.misses.fetch_add(1, Ordering::SeqCst);
+            return None;
+        };
+
+        let path = self.dir.join(data_file_name(&name, format));
+
+        if !verified {
+            let verify_path = path.clone();
+            let valid =
+                moosicbox_task::spawn_blocking("files: transcode_cache verify", move || {
+                    file_sha256(&verify_path)
+                })
+                .await
+                .ok()
+                .and_then(Result::ok)
+                .is_some_and(|x| x == sha256);
+
+            if !valid {
+                log::warn!("Removing corrupted transcode cache entry for key={key}");
+                let removed = self.entries.lock().unwrap().remove(&name);
+                if let Some(entry) = removed {
+                    self.remove_files(vec![(name, entry.metadata.format)]);
+                }
+                self.misses.fetch_add(1, Ordering::SeqCst);
+                return None;
+            }
+        }
+
+        let now = SystemTime::now();
+
+        {
+            let mut entries = self.entries.lock().unwrap();
+            let Some(entry) = entries.get_mut(&name) else {
+                self.misses.fetch_add(1, Ordering::SeqCst);
+                return None;
+            };
+            entry.verified = true;
+            entry.last_access = now;
+        }
+
+        // Keep the access time on disk so the eviction order survives restarts
+        if let Err(e) = File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|file| file.set_modified(now))
+        {
+            log::debug!("Failed to update the access time of {path:?}: {e:?}");
+        }
+
+        self.hits.fetch_add(1, Ordering::SeqCst);
+
+        Some((path, size))
+    }
+
+    /// Starts writing a transcode for the key into the cache. The entry is
+    /// only added once [`CacheWriter::finish`] is called.
+    ///
+    /// # Errors
+    ///
+    /// * If the file couldn't be created
+    pub fn writer(
+        &self,
+        key: &str,
+        format: AudioFormat,
+    ) -> Result<CacheWriter<'_>, TranscodeCacheError> {
+        let name = entry_name(key);
+        let id = WRITER_ID.fetch_add(1, Ordering::SeqCst);
+        let part_path = self.dir.join(format!("{name}.{id}.part"));
+        let file = File::create(&part_path)?;
+
+        Ok(CacheWriter {
+            cache: self,
+            key: key.to_string(),
+            name,
+            format,
+            part_path,
+            writer: BufWriter::new(file),
+            hasher: Sha256::new(),
+            size: 0,
+        })
+    }
+
+    fn insert(
+        &self,
+        name: String,
+        part_path: &Path,
+        metadata: EntryMetadata,
+    ) -> Result<(), TranscodeCacheError> {
+        if metadata.size > self.max_size {
+            log::debug!(
+                "Not caching transcode for key={} larger than the cache: size={}",
+                metadata.key,
+                metadata.size
+            );
+            return Ok(());
+        }
+
+        std::fs::rename(
+            part_path,
+            self.dir.join(data_file_name(&name, metadata.format)),
+        )?;
+        std::fs::write(
+            self.dir.join(metadata_file_name(&name)),
+            serde_json::to_vec(&metadata)?,
+        )?;
+
+        log::debug!(
+            "Cached transcode for key={} size={}",
+            metadata.key,
+            metadata.size
+        );
+
+        let evicted = {
+            let mut entries = self.entries.lock().unwrap();
+            entries.insert(
+                name,
+                Entry {
+                    metadata,
+                    last_access: SystemTime::now(),
+                    verified: true,
+                },
+            );
+            self.evict(&mut entries)
+        };
+
+        self.remove_files(evicted);
+
+        Ok(())
+    }
+
+    /// Removes the least recently used entries until the cache fits in its
+    /// size limit, returning the entries that need their files removed.
+    fn evict(&self, entries: &mut HashMap<String, Entry>) -> Vec<(String, AudioFormat)> {
+        let mut size = entries.values().map(|x| x.metadata.size).sum::<u64>();
+        let mut evicted = vec![];
+
+        while size > self.max_size {
+            let Some(name) = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_access)
+                .map(|(name, _)| name.clone())
+            else {
+                break;
+            };
+
+            let entry = entries.remove(&name).unwrap();
+            log::debug!("Evicting transcode for key={}", entry.metadata.key);
+            size -= entry.metadata.size;
+            self.evictions.fetch_add(1, Ordering::SeqCst);
+            evicted.push((name, entry.metadata.format));
+        }
+
+        evicted
+    }
+
+    fn remove_files(&self, entries: Vec<(String, AudioFormat)>) {
+        for (name, format) in entries {
+            remove_file(&self.dir.join(data_file_name(&name, format)));
+            remove_file(&self.dir.join(metadata_file_name(&name)));
+        }
+    }
+
+    #[must_use]
+    pub fn stats(&self) -> TranscodeCacheStats {
+        let entries = self.entries.lock().unwrap();
+
+        TranscodeCacheStats {
+            entries: entries.len() as u64,
+            size: entries.values().map(|x| x.metadata.size).sum(),
+            max_size: self.max_size,
+            hits: self.hits.load(Ordering::SeqCst),
+            misses: self.misses.load(Ordering::SeqCst),
+            evictions: self.evictions.load(Ordering::SeqCst),
+            pending_transcodes: self.pending_transcodes.load(Ordering::SeqCst),
+        }
+    }
+
+    /// Removes every entry from the cache.
+    pub fn purge(&self) -> TranscodeCachePurge {
+        let entries = self.entries.lock().unwrap().drain().collect::<Vec<_>>();
+
+        let purge = TranscodeCachePurge {
+            entries: entries.len() as u64,
+            size: entries.iter().map(|(_, x)| x.metadata.size).sum(),
+        };
+
+        log::debug!("Purging transcode cache: {purge:?}");
+
+        self.remove_files(
+            entries
+                .into_iter()
+                .map(|(name, entry)| (name, entry.metadata.format))
+                .collect(),
+        );
+
+        purge
+    }
+}
+
+/// Writes a transcode into a temporary file of the cache. The file is removed
+/// when the writer is dropped without being finished.
+pub struct CacheWriter<'a> {
+    cache: &'a TranscodeCache,
+    key: String,
+    name: String,
+    format: AudioFormat,
+    part_path: PathBuf,
+    writer: BufWriter<File>,
+    hasher: Sha256,
+    size: u64,
+}
+
+impl CacheWriter<'_> {
+    /// # Errors
+    ///
+    /// * If the bytes failed to be written to the file
+    pub fn write(&mut self, bytes: &[u8]) -> Result<(), std::io::Error> {
+        self.writer.write_all(bytes)?;
+        self.hasher.update(bytes);
+        self.size += bytes.len() as u64;
+        Ok(())
+    }
+
+    /// Adds the written transcode to the cache, evicting the least recently
+    /// used entries if the cache is full.
+    ///
+    /// # Errors
+    ///
+    /// * If the file failed to be written or moved into the cache
+    pub fn finish(mut self) -> Result<(), TranscodeCacheError> {
+        self.writer.flush()?;
+        self.writer.get_ref().sync_all()?;
+
+        let metadata = EntryMetadata {
+            key: self.key.clone(),
+            format: self.format,
+            size: self.size,
+            sha256: hex::encode(std::mem::take(&mut self.hasher).finalize()),
+        };
+
+        self.cache
+            .insert(self.name.clone(), &self.part_path, metadata)
+    }
+}
+
+impl Drop for CacheWriter<'_> {
+    fn drop(&mut self) {
+        // The file was already moved into the cache if the writer finished
+        remove_file(&self.part_path);
+    }
+}
+
+/// Whether fetching the track source in the format with the encoder settings
+/// requires a transcode.
+fn transcodes(source: &TrackSource, format: AudioFormat, settings: &EncoderSettings) -> bool {
+    format != AudioFormat::Source && (source.format() != format || !settings.is_default())
+}
+
+/// Returns the cached transcode of the track source in the format, if any.
+pub(crate) async fn get_cached_track_bytes(
+    source: &TrackSource,
+    format: AudioFormat,
+    settings: &EncoderSettings,
+    start: Option<u64>,
+    end: Option<u64>,
+) -> Option<TrackBytes> {
+    let cache = TRANSCODE_CACHE.get()?;
+
+    if !transcodes(source, format, settings) {
+        return None;
+    }
+
+    let key = track_key(source, format, settings);
+    let (path, size) = cache.get(&key).await?;
+
+    log::debug!("get_cached_track_bytes: Using cached transcode for key={key} size={size}");
+
+    match request_audio_bytes_from_file(path.to_str()?.to_string(), format, None, start, end).await
+    {
+        Ok(mut bytes) => {
+            bytes.filename = match source {
+                TrackSource::LocalFilePath { path, .. } => filename_from_path_str(path),
+                TrackSource::RemoteUrl { .. } => None,
+            };
+            Some(bytes)
+        }
+        Err(e) => {
+            log::error!("Failed to read cached transcode for key={key}: {e:?}");
+            None
+        }
+    }
+}
+
+/// Wraps the stream of a transcode so it's added to the cache once the stream
+/// ends. Returns the stream unchanged if the cache isn't initialized.
+pub(crate) fn cache_transcode(
+    source: &TrackSource,
+    format: AudioFormat,
+    settings: &EncoderSettings,
+    stream: BytesStream,
+) -> BytesStream {
+    let Some(cache) = TRANSCODE_CACHE.get() else {
+        return stream;
+    };
+
+    let key = track_key(source, format, settings);
+
+    match cache.writer(&key, format) {
+        Ok(writer) => CachingStream {
+            inner: stream,
+            writer: Some(writer),
+        }
+        .boxed(),
+        Err(e) => {
+            log::error!("Failed to create transcode cache writer for key={key}: {e:?}");
+            stream
+        }
+    }
+}
+
+struct CachingStream {
+    inner: BytesStream,
+    writer: Option<CacheWriter<'static>>,
+}
+
+impl Stream for CachingStream {
+    type Item = BytesStreamItem;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let item = futures::ready!(self.inner.poll_next_unpin(cx));
+
+        match &item {
+            Some(Ok(bytes)) => {
+                if let Some(writer) = &mut self.writer {
+                    if let Err(e) = writer.write(bytes) {
+                        log::error!("Failed to write to the transcode cache: {e:?}");
+                        self.writer.take();
+                    }
+                }
+            }
+            Some(Err(_)) => {
+                self.writer.take();
+            }
+            None => {
+                if let Some(writer) = self.writer.take() {
+                    if let Err(e) = writer.finish() {
+                        log::error!("Failed to add transcode to the cache: {e:?}");
+                    }
+                }
+            }
+        }
+
+        Poll::Ready(item)
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum PreTranscodeError {
+    #[error(transparent)]
+    TranscodeCache(#[from] TranscodeCacheError),
+    #[error(transparent)]
+    TrackSource(#[from] TrackSourceError),
+    #[error(transparent)]
+    GetTrackBytes(#[from] GetTrackBytesError),
+    #[error(transparent)]
+    MusicApis(#[from] MusicApisError),
+}
+
+/// Returns the IDs of the tracks added to the API's library, i.e. the
+/// favorite tracks of the streaming services.
+///
+/// # Errors
+///
+/// * If the tracks failed to be fetched
+pub async fn favorite_track_ids(api: &dyn MusicApi) -> Result<Vec<Id>, TracksError> {
+    Ok(api
+        .tracks(None, None, None, None, None)
+        .await?
+        .with_rest_of_items_in_batches()
+        .await?
+        .into_iter()
+        .map(|track| track.id)
+        .collect())
+}
+
+/// Queues a background job transcoding the tracks into the cache. The tracks
+/// that are already cached are skipped.
+///
+/// # Errors
+///
+/// * If the cache isn't initialized
+pub fn pre_transcode(
+    music_apis: MusicApis,
+    source: ApiSource,
+    track_ids: Vec<Id>,
+    format: AudioFormat,
+    settings: EncoderSettings,
+    quality: Option<TrackAudioQuality>,
+) -> Result<(), PreTranscodeError> {
+    let cache = TRANSCODE_CACHE
+        .get()
+        .ok_or(TranscodeCacheError::NotInitialized)?;
+
+    cache
+        .pending_transcodes
+        .fetch_add(track_ids.len() as u64, Ordering::SeqCst);
+
+    moosicbox_task::spawn("files: transcode_cache pre_transcode", async move {
+        for track_id in track_ids {
+            if let Err(e) = pre_transcode_track(
+                cache,
+                &music_apis,
+                source,
+                &track_id,
+                format,
+                settings,
+                quality,
+            )
+            .await
+            {
+                log::error!("Failed to pre-transcode track_id={track_id}: {e:?}");
+            }
+            cache.pending_transcodes.fetch_sub(1, Ordering::SeqCst);
+        }
+    });
+
+    Ok(())
+}
+
+async fn pre_transcode_track(
+    cache: &TranscodeCache,
+    music_apis: &MusicApis,
+    source: ApiSource,
+    track_id: &Id,
+    format: AudioFormat,
+    settings: EncoderSettings,
+    quality: Option<TrackAudioQuality>,
+) -> Result<(), PreTranscodeError> {
+    let _permit = PRE_TRANSCODE_PERMIT
+        .acquire()
+        .await
+        .expect("Semaphore is never closed");
+
+    let track_source = get_track_id_source(music_apis.clone(), track_id, source, quality).await?;
+
+    if !transcodes(&track_source, format, &settings)
+        || cache.contains(&track_key(&track_source, format, &settings))
+    {
+        log::debug!("pre_transcode_track: Nothing to transcode for track_id={track_id}");
+        return Ok(());
+    }
+
+    log::debug!("pre_transcode_track: Transcoding track_id={track_id} format={format}");
+
+    let api = music_apis.get(source)?;
+    let bytes = get_track_bytes(
+        &**api,
+        track_id,
+        track_source,
+        format,
+        settings,
+        false,
+        None,
+        None,
+    )
+    .await?;
+
+    // Drain the stream so the transcode finishes and is added to the cache
+    bytes.stream.for_each(|_| async {}).await;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn cache_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "moosicbox_transcode_cache_{name}_{}",
+            std::process::id()
+        ));
+        std::fs::remove_dir_all(&dir).ok();
+        dir
+    }
+
+    fn write_entry(cache: &TranscodeCache, key: &str, bytes: &[u8]) {
+        let mut writer = cache.writer(key, AudioFormat::Source).unwrap();
+        writer.write(bytes).unwrap();
+        writer.finish().unwrap();
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn finished_writes_are_cached_across_restarts() {
+        let dir = cache_dir("restart");
+
+        let cache = TranscodeCache::new(dir.clone(), 1024).unwrap();
+        write_entry(&cache, "a", b"track a");
+        let mut writer = cache.writer("b", AudioFormat::Source).unwrap();
+        writer.write(b"unfinished").unwrap();
+        drop(writer);
+        drop(cache);
+
+        let cache = TranscodeCache::new(dir.clone(), 1024).unwrap();
+        let (path, size) = cache.get("a").await.unwrap();
+
+        assert_eq!(std::fs::read(path).unwrap(), b"track a");
+        assert_eq!(size, 7);
+        assert!(cache.get("b").await.is_none());
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 2);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn evicts_least_recently_used_entries() {
+        let dir = cache_dir("evict");
+
+        let cache = TranscodeCache::new(dir.clone(), 20).unwrap();
+        write_entry(&cache, "a", &[0; 8]);
+        write_entry(&cache, "b", &[1; 8]);
+        assert!(cache.get("a").await.is_some());
+        write_entry(&cache, "c", &[2; 8]);
+
+        assert!(cache.contains("a"));
+        assert!(!cache.contains("b"));
+        assert!(cache.contains("c"));
+        assert_eq!(
+            cache.stats(),
+            TranscodeCacheStats {
+                entries: 2,
+                size: 16,
+                max_size: 20,
+                hits: 1,
+                misses: 0,
+                evictions: 1,
+                pending_transcodes: 0,
+            }
+        );
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn removes_corrupted_entries() {
+        let dir = cache_dir("corrupted");
+
+        let cache = TranscodeCache::new(dir.clone(), 1024).unwrap();
+        write_entry(&cache, "a", b"track a");
+        drop(cache);
+
+        let (path, _) = TranscodeCache::new(dir.clone(), 1024)
+            .unwrap()
+            .get("a")
+            .await
+            .unwrap();
+        std::fs::write(path, b"track b").unwrap();
+
+        let cache = TranscodeCache::new(dir.clone(), 1024).unwrap();
+        assert!(cache.get("a").await.is_none());
+        assert!(!cache.contains("a"));
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn purge_removes_every_entry() {
+        let dir = cache_dir("purge");
+
+        let cache = TranscodeCache::new(dir.clone(), 1024).unwrap();
+        write_entry(&cache, "a", &[0; 8]);
+        write_entry(&cache, "b", &[1; 4]);
+
+        assert_eq!(
+            cache.purge(),
+            TranscodeCachePurge {
+                entries: 2,
+                size: 12
+            }
+        );
+        assert_eq!(cache.stats().entries, 0);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
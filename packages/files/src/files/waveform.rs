@@ -0,0 +1,749 @@
+//! Waveform peaks of tracks at several zoom levels.
+//!
+//! A track is decoded once, the first time its waveform is requested, into
+//! the min, max and RMS of every channel over blocks of
+//! [`BASE_SAMPLES_PER_PIXEL`] samples. Coarser zoom levels are merged from the
+//! base level and every level is stored in the `waveforms` cache directory.
+//! Requests read the coarsest stored level that evenly divides the requested
+//! resolution and merge it down for the requested time window.
+//!
+//! The peaks are returned in the JSON and binary (`.dat`) formats of
+//! [audiowaveform](https://github.com/bbc/audiowaveform), version 2.
+
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use moosicbox_audio_decoder::{decode_media_source_async, DecodeError};
+use moosicbox_audio_encoder::EncoderSettings;
+use moosicbox_music_api::TrackSource;
+use serde::{Deserialize, Serialize};
+use symphonia::core::{
+    audio::{AudioBuffer, Signal as _},
+    io::MediaSourceStream,
+    probe::Hint,
+};
+use thiserror::Error;
+
+use super::{
+    track::{get_audio_bytes, GetTrackBytesError},
+    track_bytes_media_source::TrackBytesMediaSource,
+};
+use crate::sanitize_filename;
+
+/// The number of samples per channel in each peak of the finest zoom level.
+pub const BASE_SAMPLES_PER_PIXEL: u32 = 256;
+
+/// The factor between the samples per pixel of consecutive zoom levels.
+const LEVEL_FACTOR: u32 = 4;
+const LEVELS: usize = 4;
+
+const STORE_MAGIC: &[u8; 4] = b"MBWF";
+const STORE_VERSION: u8 = 1;
+const AUDIOWAVEFORM_VERSION: u32 = 2;
+
+#[derive(Debug, Error)]
+pub enum WaveformError {
+    #[error(transparent)]
+    IO(#[from] std::io::Error),
+    #[error(transparent)]
+    GetTrackBytes(#[from] Box<GetTrackBytesError>),
+    #[error(transparent)]
+    Decode(#[from] DecodeError),
+    #[error("No audio was decoded")]
+    NoAudio,
+    #[error("Invalid stored waveform")]
+    InvalidStore,
+    #[error("Invalid time window: {0}s to {1}s")]
+    InvalidWindow(f64, f64),
+    #[error("Unsupported bits: {0}")]
+    UnsupportedBits(u8),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum WaveformFormat {
+    #[default]
+    Json,
+    Dat,
+}
+
+/// The min, max and RMS of a channel's samples over a pixel.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Peak {
+    pub min: i16,
+    pub max: i16,
+    pub rms: u16,
+}
+
+impl Peak {
+    /// Merges the peaks, each of the given number of samples.
+    fn merge(peaks: impl Iterator<Item = (Self, u64)>) -> Self {
+        let mut min = i16::MAX;
+        let mut max = i16::MIN;
+        let mut squares = 0.0;
+        let mut samples = 0;
+
+        for (peak, count) in peaks {
+            min = min.min(peak.min);
+            max = max.max(peak.max);
+            squares += f64::from(peak.rms).powi(2) * count as f64;
+            samples += count;
+        }
+
+        if samples == 0 {
+            return Self::default();
+        }
+
+        Self {
+            min,
+            max,
+            rms: (squares / samples as f64).sqrt().round() as u16,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct WaveformLevel {
+    samples_per_pixel: u32,
+    /// The peaks of every channel, interleaved.
+    peaks: Vec<Peak>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Waveform {
+    pub sample_rate: u32,
+    pub channels: u8,
+    /// The number of samples per channel.
+    pub samples: u64,
+    levels: Vec<WaveformLevel>,
+}
+
+/// The peaks of a time window of a [`Waveform`] at a single resolution.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WaveformPeaks {
+    pub sample_rate: u32,
+    pub samples_per_pixel: u32,
+    pub channels: u8,
+    /// The time of the first peak in seconds.
+    pub start: f64,
+    /// The peaks of every channel, interleaved.
+    pub peaks: Vec<Peak>,
+}
+
+/// The audiowaveform JSON format. `rms` and `start` aren't part of it, so
+/// audiowaveform clients ignore them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AudiowaveformJson {
+    pub version: u32,
+    pub channels: u8,
+    pub sample_rate: u32,
+    pub samples_per_pixel: u32,
+    pub bits: u8,
+    pub length: usize,
+    pub data: Vec<i16>,
+    pub rms: Vec<u16>,
+    pub start: f64,
+}
+
+impl WaveformPeaks {
+    /// The number of peaks per channel.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.peaks.len() / usize::from(self.channels.max(1))
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.peaks.is_empty()
+    }
+
+    /// # Errors
+    ///
+    /// * If `bits` isn't 8 or 16
+    pub fn to_json(&self, bits: u8) -> Result<AudiowaveformJson, WaveformError> {
+        let shift = bits_shift(bits)?;
+
+        Ok(AudiowaveformJson {
+            version: AUDIOWAVEFORM_VERSION,
+            channels: self.channels,
+            sample_rate: self.sample_rate,
+            samples_per_pixel: self.samples_per_pixel,
+            bits,
+            length: self.len(),
+            data: self
+                .peaks
+                .iter()
+                .flat_map(|x| [x.min >> shift, x.max >> shift])
+                .collect(),
+            rms: self.peaks.iter().map(|x| x.rms >> shift).collect(),
+            start: self.start,
+        })
+    }
+
+    /// Encodes the peaks in the audiowaveform binary format.
+    ///
+    /// # Errors
+    ///
+    /// * If `bits` isn't 8 or 16
+    pub fn to_dat(&self, bits: u8) -> Result<Vec<u8>, WaveformError> {
+        let shift = bits_shift(bits)?;
+
+        let mut dat = Vec::with_capacity(24 + self.peaks.len() * 4);
+        dat.extend_from_slice(&AUDIOWAVEFORM_VERSION.to_le_bytes());
+        // Bit 0 of the flags is set for 8 bit data
+        dat.extend_from_slice(&u32::from(bits == 8).to_le_bytes());
+        dat.extend_from_slice(&self.sample_rate.to_le_bytes());
+        dat.extend_from_slice(&self.samples_per_pixel.to_le_bytes());
+        dat.extend_from_slice(&(self.len() as u32).to_le_bytes());
+        dat.extend_from_slice(&u32::from(self.channels).to_le_bytes());
+
+        for peak in &self.peaks {
+            if bits == 8 {
+                dat.push((peak.min >> shift) as i8 as u8);
+                dat.push((peak.max >> shift) as i8 as u8);
+            } else {
+                dat.extend_from_slice(&peak.min.to_le_bytes());
+                dat.extend_from_slice(&peak.max.to_le_bytes());
+            }
+        }
+
+        Ok(dat)
+    }
+}
+
+const fn bits_shift(bits: u8) -> Result<u8, WaveformError> {
+    match bits {
+        8 => Ok(8),
+        16 => Ok(0),
+        _ => Err(WaveformError::UnsupportedBits(bits)),
+    }
+}
+
+impl Waveform {
+    /// The duration of the track in seconds.
+    #[must_use]
+    pub fn duration(&self) -> f64 {
+        self.samples as f64 / f64::from(self.sample_rate)
+    }
+
+    /// Returns the peaks from `start` to `end` seconds, or the end of the
+    /// track, with `samples_per_pixel` rounded up to a multiple of
+    /// [`BASE_SAMPLES_PER_PIXEL`]. The peaks are aligned to the resolution,
+    /// so the first peak may start before `start`.
+    ///
+    /// # Errors
+    ///
+    /// * If the time window is empty or outside of the track
+    pub fn peaks(
+        &self,
+        start: f64,
+        end: Option<f64>,
+        samples_per_pixel: u32,
+        split_channels: bool,
+    ) -> Result<WaveformPeaks, WaveformError> {
+        let end = end.unwrap_or_else(|| self.duration()).min(self.duration());
+        if !(start >= 0.0 && start < end) {
+            return Err(WaveformError::InvalidWindow(start, end));
+        }
+
+        let samples_per_pixel = samples_per_pixel
+            .max(1)
+            .div_ceil(BASE_SAMPLES_PER_PIXEL)
+            .saturating_mul(BASE_SAMPLES_PER_PIXEL);
+
+        // The coarsest stored level that evenly divides the resolution
+        let level = self
+            .levels
+            .iter()
+            .rev()
+            .find(|x| samples_per_pixel.is_multiple_of(x.samples_per_pixel))
+            .ok_or(WaveformError::InvalidStore)?;
+        let factor = u64::from(samples_per_pixel / level.samples_per_pixel);
+
+        let channels = usize::from(self.channels);
+        let level_len = (level.peaks.len() / channels) as u64;
+        let spp = u64::from(samples_per_pixel);
+        let first = (start * f64::from(self.sample_rate)) as u64 / spp;
+        let last = ((end * f64::from(self.sample_rate)).ceil() as u64).div_ceil(spp);
+
+        let mut peaks = vec![];
+        for point in first..last {
+            let from = point * factor;
+            let to = ((point + 1) * factor).min(level_len);
+            if from >= to {
+                break;
+            }
+
+            let level_peaks = |channel: usize| {
+                (from..to).map(move |x| {
+                    let samples = (self.samples - x * u64::from(level.samples_per_pixel))
+                        .min(u64::from(level.samples_per_pixel));
+                    (level.peaks[x as usize * channels + channel], samples)
+                })
+            };
+
+            if split_channels {
+                peaks.extend((0..channels).map(|c| Peak::merge(level_peaks(c))));
+            } else {
+                peaks.push(Peak::merge((0..channels).flat_map(level_peaks)));
+            }
+        }
+
+        Ok(WaveformPeaks {
+            sample_rate: self.sample_rate,
+            samples_per_pixel,
+            channels: if split_channels { self.channels } else { 1 },
+            start: (first * spp) as f64 / f64::from(self.sample_rate),
+            peaks,
+        })
+    }
+
+    fn to_store(&self) -> Vec<u8> {
+        let mut data = vec![];
+        data.extend_from_slice(STORE_MAGIC);
+        data.push(STORE_VERSION);
+        data.push(self.channels);
+        data.extend_from_slice(&self.sample_rate.to_le_bytes());
+        data.extend_from_slice(&self.samples.to_le_bytes());
+        data.push(self.levels.len() as u8);
+
+        for level in &self.levels {
+            data.extend_from_slice(&level.samples_per_pixel.to_le_bytes());
+            data.extend_from_slice(&(level.peaks.len() as u32).to_le_bytes());
+            for peak in &level.peaks {
+                data.extend_from_slice(&peak.min.to_le_bytes());
+                data.extend_from_slice(&peak.max.to_le_bytes());
+                data.extend_from_slice(&peak.rms.to_le_bytes());
+            }
+        }
+
+        data
+    }
+
+    fn from_store(mut data: &[u8]) -> Result<Self, WaveformError> {
+        fn take<'a>(data: &mut &'a [u8], len: usize) -> Result<&'a [u8], WaveformError> {
+            if data.len() < len {
+                return Err(WaveformError::InvalidStore);
+            }
+            let (taken, rest) = data.split_at(len);
+            *data = rest;
+            Ok(taken)
+        }
+        fn u16_le(data: &mut &[u8]) -> Result<u16, WaveformError> {
+            Ok(u16::from_le_bytes(take(data, 2)?.try_into().unwrap()))
+        }
+        fn u32_le(data: &mut &[u8]) -> Result<u32, WaveformError> {
+            Ok(u32::from_le_bytes(take(data, 4)?.try_into().unwrap()))
+        }
+
+        if take(&mut data, 4)? != STORE_MAGIC || take(&mut data, 1)?[0] != STORE_VERSION {
+            return Err(WaveformError::InvalidStore);
+        }
+
+        let channels = take(&mut data, 1)?[0];
+        let sample_rate = u32_le(&mut data)?;
+        let samples = u64::from_le_bytes(take(&mut data, 8)?.try_into().unwrap());
+        let level_count = take(&mut data, 1)?[0];
+
+        if channels == 0 || sample_rate == 0 || level_count == 0 {
+            return Err(WaveformError::InvalidStore);
+        }
+
+        let levels = (0..level_count)
+            .map(|_| {
+                let samples_per_pixel = u32_le(&mut data)?;
+                let len = u32_le(&mut data)?;
+                let peaks = (0..len)
+                    .map(|_| {
+                        Ok(Peak {
+                            min: u16_le(&mut data)? as i16,
+                            max: u16_le(&mut data)? as i16,
+                            rms: u16_le(&mut data)?,
+                        })
+                    })
+                    .collect::<Result<Vec<_>, WaveformError>>()?;
+                Ok(WaveformLevel {
+                    samples_per_pixel,
+                    peaks,
+                })
+            })
+            .collect::<Result<Vec<_>, WaveformError>>()?;
+
+        if !data.is_empty() {
+            return Err(WaveformError::InvalidStore);
+        }
+
+        Ok(Self {
+            sample_rate,
+            channels,
+            samples,
+            levels,
+        })
+    }
+}
+
+/// Accumulates decoded samples into the peaks of the base level.
+struct WaveformBuilder {
+    sample_rate: u32,
+    channels: usize,
+    samples: u64,
+    min: Vec<i16>,
+    max: Vec<i16>,
+    squares: Vec<f64>,
+    count: u32,
+    peaks: Vec<Peak>,
+}
+
+impl WaveformBuilder {
+    fn new(sample_rate: u32, channels: usize) -> Self {
+        Self {
+            sample_rate,
+            channels,
+            samples: 0,
+            min: vec![i16::MAX; channels],
+            max: vec![i16::MIN; channels],
+            squares: vec![0.0; channels],
+            count: 0,
+            peaks: vec![],
+        }
+    }
+
+    /// Adds the samples of each channel. The channels must have the same
+    /// number of samples.
+    fn push(&mut self, channels: &[&[f32]]) {
+        let frames = channels.first().map_or(0, |x| x.len());
+
+        for i in 0..frames {
+            for (c, samples) in channels.iter().enumerate().take(self.channels) {
+                let sample = (samples[i].clamp(-1.0, 1.0) * f32::from(i16::MAX)) as i16;
+                self.min[c] = self.min[c].min(sample);
+                self.max[c] = self.max[c].max(sample);
+                self.squares[c] += f64::from(sample).powi(2);
+            }
+
+            self.count += 1;
+            self.samples += 1;
+
+            if self.count == BASE_SAMPLES_PER_PIXEL {
+                self.flush();
+            }
+        }
+    }
+
+    fn flush(&mut self) {
+        for c in 0..self.channels {
+            self.peaks.push(Peak {
+                min: self.min[c],
+                max: self.max[c],
+                rms: (self.squares[c] / f64::from(self.count)).sqrt().round() as u16,
+            });
+        }
+
+        self.min.fill(i16::MAX);
+        self.max.fill(i16::MIN);
+        self.squares.fill(0.0);
+        self.count = 0;
+    }
+
+    fn finish(mut self) -> Result<Waveform, WaveformError> {
+        if self.count > 0 {
+            self.flush();
+        }
+
+        if self.samples == 0 || self.channels == 0 {
+            return Err(WaveformError::NoAudio);
+        }
+
+        let mut levels = vec![WaveformLevel {
+            samples_per_pixel: BASE_SAMPLES_PER_PIXEL,
+            peaks: self.peaks,
+        }];
+
+        for _ in 1..LEVELS {
+            let previous = levels.last().unwrap();
+            let samples_per_pixel = previous.samples_per_pixel * LEVEL_FACTOR;
+            let len = (previous.peaks.len() / self.channels) as u64;
+            let samples = self.samples;
+
+            let peaks = (0..len.div_ceil(u64::from(LEVEL_FACTOR)))
+                .flat_map(|point| {
+                    let from = point * u64::from(LEVEL_FACTOR);
+                    let to = (from + u64::from(LEVEL_FACTOR)).min(len);
+                    (0..self.channels).map(move |c| {
+                        Peak::merge((from..to).map(|x| {
+                            let spp = u64::from(previous.samples_per_pixel);
+                            (
+                                previous.peaks[x as usize * self.channels + c],
+                                (samples - x * spp).min(spp),
+                            )
+                        }))
+                    })
+                })
+                .collect();
+
+            levels.push(WaveformLevel {
+                samples_per_pixel,
+                peaks,
+            });
+        }
+
+        Ok(Waveform {
+            sample_rate: self.sample_rate,
+            channels: self.channels as u8,
+            samples: self.samples,
+            levels,
+        })
+    }
+}
+
+/// The path of the stored waveform of the track, if it has an ID to key it
+/// by.
+fn store_path(source: &TrackSource) -> Option<PathBuf> {
+    let (api_source, track_id) = match source {
+        TrackSource::LocalFilePath {
+            source, track_id, ..
+        }
+        | TrackSource::RemoteUrl {
+            source, track_id, ..
+        } => (source, track_id.as_ref()?),
+    };
+
+    Some(
+        moosicbox_config::make_cache_dir_path()?
+            .join("waveforms")
+            .join(api_source.as_ref())
+            .join(format!(
+                "{}.waveform",
+                sanitize_filename(&track_id.to_string())
+            )),
+    )
+}
+
+async fn read_stored_waveform(path: &PathBuf, source: &TrackSource) -> Option<Waveform> {
+    let stored = tokio::fs::metadata(path).await.ok()?;
+
+    // Recompute the waveform of local files changed since it was stored
+    if let TrackSource::LocalFilePath { path, .. } = source {
+        let modified = tokio::fs::metadata(path).await.ok()?.modified().ok()?;
+        if stored.modified().ok()? < modified {
+            return None;
+        }
+    }
+
+    let data = tokio::fs::read(path).await.ok()?;
+
+    match Waveform::from_store(&data) {
+        Ok(waveform) => Some(waveform),
+        Err(e) => {
+            log::warn!("Ignoring stored waveform {path:?}: {e:?}");
+            None
+        }
+    }
+}
+
+/// Decodes the track into its waveform.
+///
+/// # Errors
+///
+/// * If the track failed to be fetched or decoded
+/// * If the track has no audio
+pub async fn compute_waveform(source: &TrackSource) -> Result<Waveform, WaveformError> {
+    let builder: Arc<Mutex<Option<WaveformBuilder>>> = Arc::new(Mutex::new(None));
+    let inner_builder = builder.clone();
+
+    let bytes = get_audio_bytes(
+        source.clone(),
+        source.format(),
+        EncoderSettings::default(),
+        None,
+        None,
+        None,
+    )
+    .await
+    .map_err(Box::new)?;
+
+    let get_handler = move || {
+        Ok(
+            moosicbox_audio_decoder::AudioDecodeHandler::new().with_filter(Box::new(
+                move |decoded: &mut AudioBuffer<f32>, _packet, _track| {
+                    let spec = decoded.spec();
+                    let mut builder = inner_builder.lock().unwrap();
+                    let builder = builder.get_or_insert_with(|| {
+                        WaveformBuilder::new(spec.rate, spec.channels.count())
+                    });
+                    let channels = (0..spec.channels.count())
+                        .map(|c| decoded.chan(c))
+                        .collect::<Vec<_>>();
+                    builder.push(&channels);
+                    Ok(())
+                },
+            )),
+        )
+    };
+
+    let hint = Hint::new();
+    let media_source = TrackBytesMediaSource::new(bytes);
+    let mss = MediaSourceStream::new(Box::new(media_source), Default::default());
+
+    decode_media_source_async(mss, &hint, get_handler, true, true, None, None).await?;
+
+    let builder = builder.lock().unwrap().take();
+    builder.ok_or(WaveformError::NoAudio)?.finish()
+}
+
+/// Returns the stored waveform of the track, computing and storing it if it
+/// hasn't been yet.
+///
+/// # Errors
+///
+/// * If the waveform failed to be computed
+pub async fn get_or_init_waveform(source: &TrackSource) -> Result<Waveform, WaveformError> {
+    let path = store_path(source);
+
+    if let Some(path) = &path {
+        if let Some(waveform) = read_stored_waveform(path, source).await {
+            return Ok(waveform);
+        }
+    }
+
+    log::debug!(
+        "Computing waveform track_id={:?} path={path:?}",
+        source.track_id()
+    );
+
+    let waveform = compute_waveform(source).await?;
+
+    if let Some(path) = path {
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        // Write to a temporary file first so concurrent readers never see a
+        // partially written waveform
+        let temp = path.with_extension(format!("{}.tmp", std::process::id()));
+        tokio::fs::write(&temp, waveform.to_store()).await?;
+        tokio::fs::rename(&temp, &path).await?;
+    }
+
+    Ok(waveform)
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    /// A stereo waveform with a left channel of full scale peaks and a
+    /// silent right channel.
+    fn waveform(samples: usize) -> Waveform {
+        let left = (0..samples)
+            .map(|i| if i % 2 == 0 { 1.0 } else { -1.0 })
+            .collect::<Vec<_>>();
+        let right = vec![0.0; samples];
+
+        let mut builder = WaveformBuilder::new(44_100, 2);
+        // Push in uneven chunks to cross the pixel boundaries
+        for (l, r) in left.chunks(1000).zip(right.chunks(1000)) {
+            builder.push(&[l, r]);
+        }
+        builder.finish().unwrap()
+    }
+
+    #[test_log::test]
+    fn builds_every_zoom_level() {
+        let waveform = waveform(44_100);
+
+        assert_eq!(
+            waveform
+                .levels
+                .iter()
+                .map(|x| (x.samples_per_pixel, x.peaks.len()))
+                .collect::<Vec<_>>(),
+            // 44100 samples are 172.3 pixels of 256 samples, for 2 channels
+            vec![(256, 346), (1024, 88), (4096, 22), (16384, 6)]
+        );
+
+        let full = Peak {
+            min: -i16::MAX,
+            max: i16::MAX,
+            rms: i16::MAX as u16,
+        };
+        assert_eq!(waveform.levels[2].peaks[..2], [full, Peak::default()]);
+    }
+
+    #[test_log::test]
+    fn peaks_of_a_window_are_aligned_to_the_resolution() {
+        let waveform = waveform(44_100 * 2);
+
+        let peaks = waveform.peaks(0.5, Some(1.0), 2048, true).unwrap();
+
+        assert_eq!(peaks.samples_per_pixel, 2048);
+        assert_eq!(peaks.channels, 2);
+        // 0.5s is sample 22050, in the pixel starting at sample 20480
+        assert_eq!(peaks.start, 20_480.0 / 44_100.0);
+        assert_eq!(peaks.len(), 12);
+    }
+
+    #[test_log::test]
+    fn merged_channels_combine_the_rms() {
+        let waveform = waveform(44_100);
+
+        let peaks = waveform.peaks(0.0, None, 300, false).unwrap();
+
+        assert_eq!(peaks.samples_per_pixel, 512);
+        assert_eq!(peaks.channels, 1);
+        assert_eq!(
+            peaks.peaks[0],
+            Peak {
+                min: -i16::MAX,
+                max: i16::MAX,
+                // The RMS of a full scale and a silent channel
+                rms: (f64::from(i16::MAX) / 2_f64.sqrt()).round() as u16,
+            }
+        );
+    }
+
+    #[test_log::test]
+    fn rejects_windows_outside_of_the_track() {
+        let waveform = waveform(44_100);
+
+        assert!(matches!(
+            waveform.peaks(2.0, None, 256, false),
+            Err(WaveformError::InvalidWindow(..))
+        ));
+    }
+
+    #[test_log::test]
+    fn store_round_trips() {
+        let waveform = waveform(10_000);
+
+        assert_eq!(
+            Waveform::from_store(&waveform.to_store()).unwrap(),
+            waveform
+        );
+        assert!(Waveform::from_store(&waveform.to_store()[..100]).is_err());
+    }
+
+    #[test_log::test]
+    fn encodes_audiowaveform_dat() {
+        let peaks = waveform(512).peaks(0.0, None, 256, true).unwrap();
+
+        let dat = peaks.to_dat(8).unwrap();
+
+        assert_eq!(
+            dat,
+            [
+                vec![2, 0, 0, 0, 1, 0, 0, 0],
+                44_100_u32.to_le_bytes().to_vec(),
+                vec![0, 1, 0, 0, 2, 0, 0, 0, 2, 0, 0, 0],
+                vec![0x80, 0x7F, 0, 0, 0x80, 0x7F, 0, 0],
+            ]
+            .concat()
+        );
+    }
+}
@@ -1,5 +1,12 @@
+use std::io::Cursor;
+
 use bytes::Bytes;
-use image::{codecs::jpeg::JpegEncoder, imageops::FilterType};
+use image::{
+    codecs::jpeg::JpegEncoder,
+    error::{ParameterError, ParameterErrorKind},
+    imageops::FilterType,
+    ImageFormat, RgbImage,
+};
 use thiserror::Error;
 
 use crate::Encoding;
@@ -32,6 +39,22 @@ pub fn try_resize_local_file(
     }
 }
 
+/// Encodes raw 8-bit RGB pixels, row by row, as a PNG.
+pub fn encode_rgb_png(
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+) -> Result<Bytes, image::error::ImageError> {
+    let img = RgbImage::from_raw(width, height, pixels).ok_or_else(|| {
+        image::error::ImageError::Parameter(ParameterError::from_kind(
+            ParameterErrorKind::DimensionMismatch,
+        ))
+    })?;
+    let mut buffer = Cursor::new(Vec::new());
+    img.write_to(&mut buffer, ImageFormat::Png)?;
+    Ok(buffer.into_inner().into())
+}
+
 #[derive(Debug, Error)]
 pub enum ResizeImageError {
     #[error(transparent)]
@@ -0,0 +1,227 @@
+#![cfg_attr(feature = "fail-on-warnings", deny(warnings))]
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+
+//! Derive macros mapping `moosicbox_database::Row`s to structs and structs to
+//! insertion values. See `moosicbox_json_utils::database::FromRow` and
+//! `moosicbox_json_utils::database::ToDatabaseValues`.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    ext::IdentExt as _, parse_macro_input, punctuated::Punctuated, token::Comma, Data, DataStruct,
+    DeriveInput, Field, Fields, GenericArgument, LitStr, PathArguments, Type,
+};
+
+#[derive(Default)]
+struct FieldOptions {
+    rename: Option<String>,
+    prefix: Option<String>,
+    skip: bool,
+    skip_insert: bool,
+    default: bool,
+}
+
+impl FieldOptions {
+    fn parse(field: &Field) -> syn::Result<Self> {
+        let mut options = Self::default();
+
+        for attr in field.attrs.iter().filter(|x| x.path().is_ident("row")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    options.rename = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if meta.path.is_ident("prefix") {
+                    options.prefix = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if meta.path.is_ident("skip") {
+                    options.skip = true;
+                } else if meta.path.is_ident("skip_insert") {
+                    options.skip_insert = true;
+                } else if meta.path.is_ident("default") {
+                    options.default = true;
+                } else {
+                    return Err(meta.error("unsupported row attribute"));
+                }
+                Ok(())
+            })?;
+        }
+
+        Ok(options)
+    }
+
+    fn column(&self, field: &Field) -> String {
+        self.rename.clone().unwrap_or_else(|| {
+            field
+                .ident
+                .as_ref()
+                .expect("named field")
+                .unraw()
+                .to_string()
+        })
+    }
+}
+
+fn named_fields(input: &DeriveInput) -> syn::Result<&Punctuated<Field, Comma>> {
+    match &input.data {
+        Data::Struct(DataStruct {
+            fields: Fields::Named(fields),
+            ..
+        }) => Ok(&fields.named),
+        _ => Err(syn::Error::new_spanned(
+            &input.ident,
+            "only structs with named fields are supported",
+        )),
+    }
+}
+
+fn option_inner(ty: &Type) -> Option<&Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    if segment.ident != "Option" {
+        return None;
+    }
+    let PathArguments::AngleBracketed(arguments) = &segment.arguments else {
+        return None;
+    };
+    match arguments.args.first()? {
+        GenericArgument::Type(inner) => Some(inner),
+        _ => None,
+    }
+}
+
+/// Implements `FromRow` and `ToValueType<Self> for &Row`, reading each field
+/// from the column of the same name.
+///
+/// Field attributes:
+///
+/// * `#[row(rename = "column")]` reads the field from a differently named column
+/// * `#[row(default)]` uses `Default::default()` when the column is missing
+/// * `#[row(skip)]` doesn't read the field and uses `Default::default()`
+/// * `#[row(prefix = "artist_")]` reads a nested `FromRow` struct from the
+///   columns starting with the prefix, e.g. joined as `artists.title as
+///   artist_title`. An `Option` of a nested struct is `None` when all of its
+///   columns are missing or null.
+#[proc_macro_derive(FromRow, attributes(row))]
+pub fn derive_from_row(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    from_row(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn from_row(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let fields = named_fields(input)?
+        .iter()
+        .map(|field| {
+            let ident = &field.ident;
+            let ty = &field.ty;
+            let options = FieldOptions::parse(field)?;
+
+            let value = if options.skip {
+                quote!(::core::default::Default::default())
+            } else if let Some(nested) = &options.prefix {
+                let prefix = quote!(&::moosicbox_json_utils::database::prefixed_column(prefix, #nested));
+                option_inner(ty).map_or_else(
+                    || quote!(<#ty as ::moosicbox_json_utils::database::FromRow>::from_row_prefixed(row, #prefix)?),
+                    |inner| {
+                        quote! {
+                            if ::moosicbox_json_utils::database::has_prefixed_values(row, #prefix) {
+                                ::core::option::Option::Some(
+                                    <#inner as ::moosicbox_json_utils::database::FromRow>::from_row_prefixed(row, #prefix)?
+                                )
+                            } else {
+                                ::core::option::Option::None
+                            }
+                        }
+                    },
+                )
+            } else {
+                let column = options.column(field);
+                let column = quote!(&::moosicbox_json_utils::database::prefixed_column(prefix, #column));
+                let read = quote!(::moosicbox_json_utils::database::ToValue::to_value(row, #column)?);
+                if options.default {
+                    quote! {
+                        if row.get(#column).is_some() {
+                            #read
+                        } else {
+                            ::core::default::Default::default()
+                        }
+                    }
+                } else {
+                    read
+                }
+            };
+
+            Ok(quote!(#ident: #value))
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    Ok(quote! {
+        impl #impl_generics ::moosicbox_json_utils::database::FromRow for #name #ty_generics #where_clause {
+            fn from_row_prefixed(
+                row: &::moosicbox_database::Row,
+                prefix: &str,
+            ) -> ::core::result::Result<Self, ::moosicbox_json_utils::ParseError> {
+                ::core::result::Result::Ok(Self {
+                    #(#fields,)*
+                })
+            }
+        }
+
+        impl #impl_generics ::moosicbox_json_utils::ToValueType<#name #ty_generics> for &::moosicbox_database::Row #where_clause {
+            fn to_value_type(self) -> ::core::result::Result<#name #ty_generics, ::moosicbox_json_utils::ParseError> {
+                <#name #ty_generics as ::moosicbox_json_utils::database::FromRow>::from_row(self)
+            }
+        }
+    })
+}
+
+/// Implements `ToDatabaseValues`, converting each field with
+/// `DatabaseValue::from` into a `(column, value)` pair that can be passed to
+/// an insert or upsert's `values`.
+///
+/// Fields marked `#[row(skip)]`, `#[row(skip_insert)]` (e.g. generated ids) or
+/// `#[row(prefix = "...")]` are left out. `#[row(rename = "column")]` changes
+/// the column name.
+#[proc_macro_derive(ToDatabaseValues, attributes(row))]
+pub fn derive_to_database_values(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    to_database_values(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn to_database_values(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let mut values = vec![];
+
+    for field in named_fields(input)? {
+        let options = FieldOptions::parse(field)?;
+        if options.skip || options.skip_insert || options.prefix.is_some() {
+            continue;
+        }
+
+        let ident = &field.ident;
+        let column = options.column(field);
+        values.push(quote! {
+            (
+                #column,
+                ::moosicbox_database::DatabaseValue::from(::core::clone::Clone::clone(&self.#ident)),
+            )
+        });
+    }
+
+    Ok(quote! {
+        impl #impl_generics ::moosicbox_json_utils::database::ToDatabaseValues for #name #ty_generics #where_clause {
+            fn to_database_values(&self) -> ::std::vec::Vec<(&'static str, ::moosicbox_database::DatabaseValue)> {
+                ::std::vec![#(#values),*]
+            }
+        }
+    })
+}
@@ -1,9 +1,14 @@
+use std::borrow::Cow;
+
 use chrono::NaiveDateTime;
 use moosicbox_database::{DatabaseValue, Row};
 use thiserror::Error;
 
 use crate::{MissingValue, ParseError, ToValueType};
 
+#[cfg(feature = "derive")]
+pub use moosicbox_json_utils_derive::{FromRow, ToDatabaseValues};
+
 #[derive(Debug, Error)]
 pub enum DatabaseFetchError {
     #[error(transparent)]
@@ -12,6 +17,57 @@ pub enum DatabaseFetchError {
     Parse(#[from] ParseError),
 }
 
+/// A model that can be read from a [`Row`]. Usually derived, which also
+/// implements `ToValueType<Self> for &Row`.
+pub trait FromRow: Sized {
+    /// Reads the model from the columns of the row starting with `prefix`.
+    ///
+    /// # Errors
+    ///
+    /// * If a column failed to be converted to its field's type
+    fn from_row_prefixed(row: &Row, prefix: &str) -> Result<Self, ParseError>;
+
+    /// # Errors
+    ///
+    /// * If a column failed to be converted to its field's type
+    fn from_row(row: &Row) -> Result<Self, ParseError> {
+        Self::from_row_prefixed(row, "")
+    }
+}
+
+/// A model that can be converted to the `(column, value)` pairs to insert it
+/// with.
+pub trait ToDatabaseValues {
+    fn to_database_values(&self) -> Vec<(&'static str, DatabaseValue)>;
+}
+
+#[must_use]
+pub fn prefixed_column<'a>(prefix: &str, column: &'a str) -> Cow<'a, str> {
+    if prefix.is_empty() {
+        Cow::Borrowed(column)
+    } else {
+        Cow::Owned(format!("{prefix}{column}"))
+    }
+}
+
+/// Whether the row has any non-null column starting with `prefix`, e.g. to
+/// tell if a left join matched.
+#[must_use]
+pub fn has_prefixed_values(row: &Row, prefix: &str) -> bool {
+    row.columns.iter().any(|(column, value)| {
+        column.starts_with(prefix)
+            && !matches!(
+                value,
+                DatabaseValue::Null
+                    | DatabaseValue::BoolOpt(None)
+                    | DatabaseValue::StringOpt(None)
+                    | DatabaseValue::NumberOpt(None)
+                    | DatabaseValue::UNumberOpt(None)
+                    | DatabaseValue::RealOpt(None)
+            )
+    })
+}
+
 impl<'a> ToValueType<&'a str> for &'a DatabaseValue {
     fn to_value_type(self) -> Result<&'a str, ParseError> {
         match self {
@@ -570,4 +626,123 @@ mod tests {
             Some(ParseError::ConvertType("u64".into())),
         );
     }
+
+    #[cfg(feature = "derive")]
+    mod derive {
+        use pretty_assertions::assert_eq;
+
+        use crate::database::{FromRow, ToDatabaseValues};
+        use crate::ToValueType as _;
+
+        use super::*;
+
+        #[derive(Debug, Default, PartialEq, FromRow, ToDatabaseValues)]
+        struct Artist {
+            #[row(skip_insert)]
+            id: u64,
+            title: String,
+            cover: Option<String>,
+        }
+
+        #[derive(Debug, PartialEq, FromRow)]
+        struct Album {
+            id: u64,
+            #[row(rename = "album_title")]
+            title: String,
+            #[row(default)]
+            blur: bool,
+            #[row(skip)]
+            tracks: Vec<u64>,
+            #[row(prefix = "artist_")]
+            artist: Artist,
+            #[row(prefix = "track_artist_")]
+            track_artist: Option<Artist>,
+            r#type: String,
+        }
+
+        fn row(columns: &[(&str, DatabaseValue)]) -> Row {
+            Row {
+                columns: columns
+                    .iter()
+                    .map(|(name, value)| ((*name).to_string(), value.clone()))
+                    .collect(),
+            }
+        }
+
+        #[test]
+        fn maps_row_to_struct() {
+            let row = row(&[
+                ("id", DatabaseValue::Number(1)),
+                ("title", DatabaseValue::String("Artist".into())),
+                ("cover", DatabaseValue::Null),
+            ]);
+
+            assert_eq!(
+                (&row).to_value_type(),
+                Ok(Artist {
+                    id: 1,
+                    title: "Artist".into(),
+                    cover: None,
+                })
+            );
+        }
+
+        #[test]
+        fn maps_renamed_defaulted_and_prefixed_columns() {
+            let row = row(&[
+                ("id", DatabaseValue::Number(2)),
+                ("album_title", DatabaseValue::String("Album".into())),
+                ("artist_id", DatabaseValue::Number(1)),
+                ("artist_title", DatabaseValue::String("Artist".into())),
+                ("artist_cover", DatabaseValue::String("cover.jpg".into())),
+                ("track_artist_id", DatabaseValue::NumberOpt(None)),
+                ("track_artist_title", DatabaseValue::StringOpt(None)),
+                ("type", DatabaseValue::String("LP".into())),
+            ]);
+
+            assert_eq!(
+                Album::from_row(&row),
+                Ok(Album {
+                    id: 2,
+                    title: "Album".into(),
+                    blur: false,
+                    tracks: vec![],
+                    artist: Artist {
+                        id: 1,
+                        title: "Artist".into(),
+                        cover: Some("cover.jpg".into()),
+                    },
+                    track_artist: None,
+                    r#type: "LP".into(),
+                })
+            );
+        }
+
+        #[test]
+        fn reports_missing_columns() {
+            let row = row(&[("id", DatabaseValue::Number(1))]);
+
+            assert_eq!(
+                Artist::from_row(&row),
+                Err(ParseError::Parse("Missing value: 'title'".into()))
+            );
+        }
+
+        #[test]
+        fn maps_struct_to_database_values() {
+            let artist = Artist {
+                id: 1,
+                title: "Artist".into(),
+                cover: None,
+            };
+
+            assert_eq!(
+                artist.to_database_values(),
+                vec![
+                    ("title", DatabaseValue::String("Artist".into())),
+                    ("cover", DatabaseValue::Null),
+                ]
+            );
+        }
+    }
 }
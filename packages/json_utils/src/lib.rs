@@ -2,6 +2,9 @@
 
 use thiserror::Error;
 
+#[cfg(all(test, feature = "derive"))]
+extern crate self as moosicbox_json_utils;
+
 #[cfg(feature = "database")]
 pub mod database;
 
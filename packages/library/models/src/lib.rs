@@ -28,6 +28,9 @@ pub struct LibraryArtist {
     pub tidal_id: Option<u64>,
     pub qobuz_id: Option<u64>,
     pub yt_id: Option<u64>,
+    pub rating: Option<u8>,
+    pub loved: bool,
+    pub banned: bool,
 }
 
 impl From<LibraryArtist> for Artist {
@@ -62,6 +65,9 @@ impl ToValueType<LibraryArtist> for &moosicbox_database::Row {
             tidal_id: self.to_value("tidal_id")?,
             qobuz_id: self.to_value("qobuz_id")?,
             yt_id: self.to_value("yt_id")?,
+            rating: self.to_value("rating")?,
+            loved: self.to_value::<Option<bool>>("loved")?.unwrap_or_default(),
+            banned: self.to_value::<Option<bool>>("banned")?.unwrap_or_default(),
         })
     }
 }
@@ -78,6 +84,9 @@ impl moosicbox_core::sqlite::models::AsModelResult<LibraryArtist, ParseError>
             tidal_id: self.to_value("tidal_id")?,
             qobuz_id: self.to_value("qobuz_id")?,
             yt_id: self.to_value("yt_id")?,
+            rating: self.to_value("rating")?,
+            loved: self.to_value::<Option<bool>>("loved")?.unwrap_or_default(),
+            banned: self.to_value::<Option<bool>>("banned")?.unwrap_or_default(),
         })
     }
 }
@@ -99,6 +108,12 @@ pub struct ApiLibraryArtist {
     pub tidal_id: Option<u64>,
     pub qobuz_id: Option<u64>,
     pub yt_id: Option<u64>,
+    #[serde(default)]
+    pub rating: Option<u8>,
+    #[serde(default)]
+    pub loved: bool,
+    #[serde(default)]
+    pub banned: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
@@ -118,11 +133,15 @@ impl ToApi<ApiArtist> for LibraryArtist {
             tidal_id: self.tidal_id,
             qobuz_id: self.qobuz_id,
             yt_id: self.yt_id,
+            rating: self.rating,
+            loved: self.loved,
+            banned: self.banned,
         })
     }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct LibraryAlbum {
     pub id: u64,
     pub title: String,
@@ -141,6 +160,10 @@ pub struct LibraryAlbum {
     pub tidal_artist_id: Option<u64>,
     pub qobuz_artist_id: Option<u64>,
     pub yt_artist_id: Option<u64>,
+    pub compilation: bool,
+    pub rating: Option<u8>,
+    pub loved: bool,
+    pub banned: bool,
 }
 
 impl From<LibraryAlbum> for Album {
@@ -189,6 +212,10 @@ impl From<Album> for LibraryAlbum {
             tidal_artist_id: value.artist_sources.get(ApiSource::Tidal).map(Into::into),
             qobuz_artist_id: value.artist_sources.get(ApiSource::Qobuz).map(Into::into),
             yt_artist_id: value.artist_sources.get(ApiSource::Yt).map(Into::into),
+            compilation: false,
+            rating: None,
+            loved: false,
+            banned: false,
         }
     }
 }
@@ -223,6 +250,10 @@ impl ToValueType<LibraryAlbum> for &moosicbox_database::Row {
             tidal_artist_id: self.to_value("tidal_artist_id")?,
             qobuz_artist_id: self.to_value("qobuz_artist_id")?,
             yt_artist_id: self.to_value("yt_artist_id")?,
+            compilation: self.to_value("compilation").unwrap_or_default(),
+            rating: self.to_value("rating")?,
+            loved: self.to_value::<Option<bool>>("loved")?.unwrap_or_default(),
+            banned: self.to_value::<Option<bool>>("banned")?.unwrap_or_default(),
         })
     }
 }
@@ -250,6 +281,10 @@ impl moosicbox_core::sqlite::models::AsModelResult<LibraryAlbum, ParseError>
             tidal_artist_id: self.to_value("tidal_artist_id")?,
             qobuz_artist_id: self.to_value("qobuz_artist_id")?,
             yt_artist_id: self.to_value("yt_artist_id")?,
+            compilation: self.to_value("compilation").unwrap_or_default(),
+            rating: self.to_value("rating")?,
+            loved: self.to_value::<Option<bool>>("loved")?.unwrap_or_default(),
+            banned: self.to_value::<Option<bool>>("banned")?.unwrap_or_default(),
         })
     }
 }
@@ -422,6 +457,10 @@ impl moosicbox_core::sqlite::models::AsModelQuery<LibraryAlbum> for &moosicbox_d
             tidal_artist_id: self.to_value("tidal_artist_id")?,
             qobuz_artist_id: self.to_value("qobuz_artist_id")?,
             yt_artist_id: self.to_value("yt_artist_id")?,
+            compilation: self.to_value("compilation").unwrap_or_default(),
+            rating: self.to_value("rating")?,
+            loved: self.to_value::<Option<bool>>("loved")?.unwrap_or_default(),
+            banned: self.to_value::<Option<bool>>("banned")?.unwrap_or_default(),
         })
     }
 }
@@ -444,6 +483,7 @@ pub enum ApiAlbum {
 #[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
 #[serde(rename_all = "camelCase")]
 #[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[allow(clippy::struct_excessive_bools)]
 pub struct ApiLibraryAlbum {
     pub album_id: u64,
     pub title: String,
@@ -458,6 +498,14 @@ pub struct ApiLibraryAlbum {
     pub tidal_id: Option<u64>,
     pub qobuz_id: Option<String>,
     pub yt_id: Option<u64>,
+    #[serde(default)]
+    pub compilation: bool,
+    #[serde(default)]
+    pub rating: Option<u8>,
+    #[serde(default)]
+    pub loved: bool,
+    #[serde(default)]
+    pub banned: bool,
 }
 
 impl ToApi<ApiLibraryAlbum> for LibraryAlbum {
@@ -480,6 +528,10 @@ impl ToApi<ApiLibraryAlbum> for LibraryAlbum {
             tidal_id: self.tidal_id,
             qobuz_id: self.qobuz_id,
             yt_id: self.yt_id,
+            compilation: self.compilation,
+            rating: self.rating,
+            loved: self.loved,
+            banned: self.banned,
         }
     }
 }
@@ -503,7 +555,11 @@ pub struct LibraryTrack {
     pub date_added: Option<String>,
     pub artist: String,
     pub artist_id: u64,
+    pub track_artist: Option<String>,
+    pub track_artist_id: Option<u64>,
     pub file: Option<String>,
+    pub start_offset: Option<f64>,
+    pub end_offset: Option<f64>,
     pub artwork: Option<String>,
     pub blur: bool,
     pub bytes: u64,
@@ -517,6 +573,9 @@ pub struct LibraryTrack {
     pub qobuz_id: Option<u64>,
     pub tidal_id: Option<u64>,
     pub yt_id: Option<u64>,
+    pub rating: Option<u8>,
+    pub loved: bool,
+    pub banned: bool,
 }
 
 impl LibraryTrack {
@@ -543,9 +602,11 @@ impl From<LibraryTrack> for Track {
             album_id: value.album_id.into(),
             date_released: value.date_released,
             date_added: value.date_added,
-            artist: value.artist,
-            artist_id: value.artist_id.into(),
+            artist: value.track_artist.unwrap_or(value.artist),
+            artist_id: value.track_artist_id.unwrap_or(value.artist_id).into(),
             file: value.file,
+            start_offset: value.start_offset,
+            end_offset: value.end_offset,
             artwork: value.artwork,
             blur: value.blur,
             bytes: value.bytes,
@@ -586,7 +647,11 @@ impl ToValueType<LibraryTrack> for &moosicbox_database::Row {
             date_added: self.to_value("date_added").unwrap_or_default(),
             artist: self.to_value("artist").unwrap_or_default(),
             artist_id: self.to_value("artist_id").unwrap_or_default(),
+            track_artist: self.to_value("track_artist").unwrap_or_default(),
+            track_artist_id: self.to_value("track_artist_id").unwrap_or_default(),
             file: self.to_value("file")?,
+            start_offset: self.to_value("start_offset").unwrap_or_default(),
+            end_offset: self.to_value("end_offset").unwrap_or_default(),
             artwork: self.to_value("artwork").unwrap_or_default(),
             blur: self.to_value("blur").unwrap_or_default(),
             bytes: self.to_value("bytes").unwrap_or_default(),
@@ -608,6 +673,9 @@ impl ToValueType<LibraryTrack> for &moosicbox_database::Row {
             qobuz_id: self.to_value("qobuz_id")?,
             tidal_id: self.to_value("tidal_id")?,
             yt_id: self.to_value("yt_id")?,
+            rating: self.to_value("rating")?,
+            loved: self.to_value::<Option<bool>>("loved")?.unwrap_or_default(),
+            banned: self.to_value::<Option<bool>>("banned")?.unwrap_or_default(),
         })
     }
 }
@@ -628,7 +696,11 @@ impl moosicbox_core::sqlite::models::AsModelResult<LibraryTrack, ParseError>
             date_added: self.to_value("date_added").unwrap_or_default(),
             artist: self.to_value("artist").unwrap_or_default(),
             artist_id: self.to_value("artist_id").unwrap_or_default(),
+            track_artist: self.to_value("track_artist").unwrap_or_default(),
+            track_artist_id: self.to_value("track_artist_id").unwrap_or_default(),
             file: self.to_value("file")?,
+            start_offset: self.to_value("start_offset").unwrap_or_default(),
+            end_offset: self.to_value("end_offset").unwrap_or_default(),
             artwork: self.to_value("artwork").unwrap_or_default(),
             blur: self.to_value("blur").unwrap_or_default(),
             bytes: self.to_value("bytes").unwrap_or_default(),
@@ -650,6 +722,9 @@ impl moosicbox_core::sqlite::models::AsModelResult<LibraryTrack, ParseError>
             qobuz_id: self.to_value("qobuz_id")?,
             tidal_id: self.to_value("tidal_id")?,
             yt_id: self.to_value("yt_id")?,
+            rating: self.to_value("rating")?,
+            loved: self.to_value::<Option<bool>>("loved")?.unwrap_or_default(),
+            banned: self.to_value::<Option<bool>>("banned")?.unwrap_or_default(),
         })
     }
 }
@@ -664,6 +739,7 @@ impl AsId for LibraryTrack {
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 #[serde(tag = "type")]
+#[allow(clippy::large_enum_variant)]
 enum ApiTrackInner {
     Library(ApiLibraryTrack),
     Tidal(serde_json::Value),
@@ -673,6 +749,7 @@ enum ApiTrackInner {
 
 #[derive(Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[allow(clippy::large_enum_variant)]
 pub enum ApiTrack {
     Library {
         track_id: u64,
@@ -782,6 +859,7 @@ impl ApiTrack {
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
 #[serde(rename_all = "camelCase")]
 #[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[allow(clippy::struct_excessive_bools)]
 pub struct ApiLibraryTrack {
     pub track_id: u64,
     pub number: u32,
@@ -803,6 +881,20 @@ pub struct ApiLibraryTrack {
     pub sample_rate: Option<u32>,
     pub channels: Option<u8>,
     pub source: TrackApiSource,
+    #[serde(default)]
+    pub track_artist: Option<String>,
+    #[serde(default)]
+    pub track_artist_id: Option<u64>,
+    #[serde(default)]
+    pub rating: Option<u8>,
+    #[serde(default)]
+    pub loved: bool,
+    #[serde(default)]
+    pub banned: bool,
+    #[serde(default)]
+    pub start_offset: Option<f64>,
+    #[serde(default)]
+    pub end_offset: Option<f64>,
 }
 
 impl From<&ApiLibraryTrack> for LibraryTrack {
@@ -824,7 +916,11 @@ impl From<ApiLibraryTrack> for LibraryTrack {
             date_added: value.date_added,
             artist: value.artist,
             artist_id: value.artist_id,
+            track_artist: value.track_artist,
+            track_artist_id: value.track_artist_id,
             file: None,
+            start_offset: value.start_offset,
+            end_offset: value.end_offset,
             artwork: None,
             blur: value.blur,
             bytes: value.bytes,
@@ -838,6 +934,9 @@ impl From<ApiLibraryTrack> for LibraryTrack {
             qobuz_id: None,
             tidal_id: None,
             yt_id: None,
+            rating: value.rating,
+            loved: value.loved,
+            banned: value.banned,
         }
     }
 }
@@ -853,9 +952,11 @@ impl From<ApiLibraryTrack> for Track {
             album_id: value.album_id.into(),
             date_released: value.date_released,
             date_added: value.date_added,
-            artist: value.artist,
-            artist_id: value.artist_id.into(),
+            artist: value.track_artist.unwrap_or(value.artist),
+            artist_id: value.track_artist_id.unwrap_or(value.artist_id).into(),
             file: None,
+            start_offset: value.start_offset,
+            end_offset: value.end_offset,
             artwork: None,
             blur: value.blur,
             bytes: value.bytes,
@@ -897,6 +998,13 @@ impl ToApi<ApiTrack> for LibraryTrack {
                 sample_rate: self.sample_rate,
                 channels: self.channels,
                 source: self.source,
+                track_artist: self.track_artist.clone(),
+                track_artist_id: self.track_artist_id,
+                rating: self.rating,
+                loved: self.loved,
+                banned: self.banned,
+                start_offset: self.start_offset,
+                end_offset: self.end_offset,
             },
         }
     }
@@ -7,10 +7,11 @@ use actix_web::{
 };
 use moosicbox_core::{
     integer_range::parse_integer_ranges_to_ids,
-    sqlite::models::{AlbumSort, ToApi},
+    sqlite::models::{AlbumSort, ToApi, TrackApiSource},
+    types::AudioFormat,
 };
 use moosicbox_database::profiles::LibraryDatabase;
-use moosicbox_music_api::AlbumsRequest;
+use moosicbox_music_api::{AlbumFilters, AlbumsRequest};
 use moosicbox_paging::{Page, PagingRequest};
 use moosicbox_search::models::ApiSearchResultsResponse;
 use serde::{Deserialize, Serialize};
@@ -19,17 +20,20 @@ use strum_macros::{AsRefStr, EnumString};
 
 use crate::{
     add_favorite_album, add_favorite_artist, add_favorite_track, album, album_tracks, artist,
-    artist_albums, favorite_albums, favorite_artists, favorite_tracks, reindex_global_search_index,
-    remove_favorite_album, remove_favorite_artist, remove_favorite_track, search, track,
-    track_file_url, LibraryAddFavoriteAlbumError, LibraryAddFavoriteArtistError,
-    LibraryAddFavoriteTrackError, LibraryAlbum, LibraryAlbumError, LibraryAlbumOrder,
-    LibraryAlbumOrderDirection, LibraryAlbumTracksError, LibraryAlbumType, LibraryArtist,
-    LibraryArtistAlbumsError, LibraryArtistError, LibraryArtistOrder, LibraryArtistOrderDirection,
-    LibraryAudioQuality, LibraryFavoriteAlbumsError, LibraryFavoriteArtistsError,
-    LibraryFavoriteTracksError, LibraryRemoveFavoriteAlbumError, LibraryRemoveFavoriteArtistError,
-    LibraryRemoveFavoriteTrackError, LibrarySearchError, LibraryTrack, LibraryTrackError,
-    LibraryTrackFileUrlError, LibraryTrackOrder, LibraryTrackOrderDirection, ReindexError,
-    SearchType,
+    artist_albums, db::SetRating, duplicate_tracks, favorite_albums, favorite_artists,
+    favorite_tracks, reindex_global_search_index, remove_favorite_album, remove_favorite_artist,
+    remove_favorite_track, search, set_album_rating, set_artist_rating, set_track_rating, track,
+    track_file_url, DuplicateTrackGroup, LibraryAddFavoriteAlbumError,
+    LibraryAddFavoriteArtistError, LibraryAddFavoriteTrackError, LibraryAlbum, LibraryAlbumError,
+    LibraryAlbumOrder, LibraryAlbumOrderDirection, LibraryAlbumTracksError, LibraryAlbumType,
+    LibraryArtist, LibraryArtistAlbumsError, LibraryArtistError, LibraryArtistOrder,
+    LibraryArtistOrderDirection, LibraryAudioQuality, LibraryDuplicateTracksError,
+    LibraryFavoriteAlbumsError, LibraryFavoriteArtistsError, LibraryFavoriteTracksError,
+    LibraryRemoveFavoriteAlbumError, LibraryRemoveFavoriteArtistError,
+    LibraryRemoveFavoriteTrackError, LibrarySearchError, LibrarySetAlbumRatingError,
+    LibrarySetArtistRatingError, LibrarySetTrackRatingError, LibraryTrack, LibraryTrackError,
+    LibraryTrackFileUrlError, LibraryTrackFilters, LibraryTrackOrder, LibraryTrackOrderDirection,
+    ReindexError, SearchType,
 };
 
 pub fn bind_services<
@@ -48,6 +52,9 @@ pub fn bind_services<
         .service(favorite_tracks_endpoint)
         .service(add_favorite_track_endpoint)
         .service(remove_favorite_track_endpoint)
+        .service(set_track_rating_endpoint)
+        .service(set_album_rating_endpoint)
+        .service(set_artist_rating_endpoint)
         .service(artist_albums_endpoint)
         .service(album_tracks_endpoint)
         .service(album_endpoint)
@@ -55,6 +62,7 @@ pub fn bind_services<
         .service(track_endpoint)
         .service(search_endpoint)
         .service(reindex_endpoint)
+        .service(duplicate_tracks_endpoint)
 }
 
 #[cfg(feature = "openapi")]
@@ -72,6 +80,9 @@ pub fn bind_services<
         add_favorite_track_endpoint,
         remove_favorite_track_endpoint,
         favorite_tracks_endpoint,
+        set_track_rating_endpoint,
+        set_album_rating_endpoint,
+        set_artist_rating_endpoint,
         artist_albums_endpoint,
         album_tracks_endpoint,
         album_endpoint,
@@ -79,6 +90,7 @@ pub fn bind_services<
         track_endpoint,
         search_endpoint,
         reindex_endpoint,
+        duplicate_tracks_endpoint,
     ),
     components(schemas(
         LibraryTrackQuery,
@@ -102,6 +114,8 @@ pub fn bind_services<
         LibraryTrackOrderDirection,
         SearchType,
         LibraryAudioQuality,
+        ApiDuplicateTrackGroup,
+        ApiDuplicateTrack,
     ))
 )]
 pub struct Api;
@@ -124,6 +138,10 @@ impl ToApi<ApiAlbum> for LibraryAlbum {
             explicit: false,
             date_released: self.date_released,
             title: self.title,
+            compilation: self.compilation,
+            rating: self.rating,
+            loved: self.loved,
+            banned: self.banned,
         })
     }
 }
@@ -139,6 +157,10 @@ pub struct ApiLibraryAlbum {
     pub explicit: bool,
     pub date_released: Option<String>,
     pub title: String,
+    pub compilation: bool,
+    pub rating: Option<u8>,
+    pub loved: bool,
+    pub banned: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -156,12 +178,15 @@ impl ToApi<ApiTrack> for LibraryTrack {
             number: self.number,
             album: self.album,
             album_id: self.album_id,
-            artist: self.artist,
-            artist_id: self.artist_id,
+            artist: self.track_artist.unwrap_or(self.artist),
+            artist_id: self.track_artist_id.unwrap_or(self.artist_id),
             contains_cover: self.artwork.is_some(),
             duration: self.duration,
             explicit: false,
             title: self.title,
+            rating: self.rating,
+            loved: self.loved,
+            banned: self.banned,
         })
     }
 }
@@ -180,6 +205,9 @@ pub struct ApiLibraryTrack {
     pub duration: f64,
     pub explicit: bool,
     pub title: String,
+    pub rating: Option<u8>,
+    pub loved: bool,
+    pub banned: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -196,6 +224,9 @@ impl ToApi<ApiArtist> for LibraryArtist {
             id: self.id,
             contains_cover: self.cover.is_some(),
             title: self.title,
+            rating: self.rating,
+            loved: self.loved,
+            banned: self.banned,
         })
     }
 }
@@ -207,6 +238,9 @@ pub struct ApiLibraryArtist {
     pub id: u64,
     pub contains_cover: bool,
     pub title: String,
+    pub rating: Option<u8>,
+    pub loved: bool,
+    pub banned: bool,
 }
 
 impl From<LibraryTrackFileUrlError> for actix_web::Error {
@@ -274,6 +308,9 @@ pub struct LibraryFavoriteAlbumsQuery {
     limit: Option<u32>,
     order: Option<LibraryAlbumOrder>,
     order_direction: Option<LibraryAlbumOrderDirection>,
+    min_rating: Option<u8>,
+    loved: Option<bool>,
+    banned: Option<bool>,
 }
 
 #[cfg_attr(
@@ -287,6 +324,9 @@ pub struct LibraryFavoriteAlbumsQuery {
             ("limit" = Option<u32>, Query, description = "Page limit"),
             ("order" = Option<LibraryAlbumOrder>, Query, description = "Sort order"),
             ("orderDirection" = Option<LibraryAlbumOrderDirection>, Query, description = "Sort order direction"),
+            ("minRating" = Option<u8>, Query, description = "Minimum star rating (1-5) to filter by"),
+            ("loved" = Option<bool>, Query, description = "Loved flag to filter by"),
+            ("banned" = Option<bool>, Query, description = "Banned flag to filter by"),
         ),
         responses(
             (
@@ -315,6 +355,7 @@ pub async fn favorite_albums_endpoint(
                     }),
                     (Some(order), None) => Some(match order {
                         LibraryAlbumOrder::Date => AlbumSort::ReleaseDateDesc,
+                        LibraryAlbumOrder::Rating => AlbumSort::RatingDesc,
                     }),
                     (Some(order), Some(direction)) => Some(match (order, direction) {
                         (LibraryAlbumOrder::Date, LibraryAlbumOrderDirection::Asc) => {
@@ -323,9 +364,27 @@ pub async fn favorite_albums_endpoint(
                         (LibraryAlbumOrder::Date, LibraryAlbumOrderDirection::Desc) => {
                             AlbumSort::ReleaseDateDesc
                         }
+                        (LibraryAlbumOrder::Rating, LibraryAlbumOrderDirection::Asc) => {
+                            AlbumSort::RatingAsc
+                        }
+                        (LibraryAlbumOrder::Rating, LibraryAlbumOrderDirection::Desc) => {
+                            AlbumSort::RatingDesc
+                        }
                     }),
                 },
-                filters: None,
+                filters: if query.min_rating.is_some()
+                    || query.loved.is_some()
+                    || query.banned.is_some()
+                {
+                    Some(AlbumFilters {
+                        min_rating: query.min_rating,
+                        loved: query.loved,
+                        banned: query.banned,
+                        ..Default::default()
+                    })
+                } else {
+                    None
+                },
                 page: if query.offset.is_some() || query.limit.is_some() {
                     Some(PagingRequest {
                         offset: query.offset.unwrap_or(0),
@@ -671,6 +730,9 @@ pub struct LibraryFavoriteTracksQuery {
     limit: Option<u32>,
     order: Option<LibraryTrackOrder>,
     order_direction: Option<LibraryTrackOrderDirection>,
+    min_rating: Option<u8>,
+    loved: Option<bool>,
+    banned: Option<bool>,
 }
 
 #[cfg_attr(
@@ -685,6 +747,9 @@ pub struct LibraryFavoriteTracksQuery {
             ("limit" = Option<u32>, Query, description = "Page limit"),
             ("order" = Option<LibraryTrackOrder>, Query, description = "Sort order"),
             ("orderDirection" = Option<LibraryTrackOrderDirection>, Query, description = "Sort order direction"),
+            ("minRating" = Option<u8>, Query, description = "Minimum star rating (1-5) to filter by"),
+            ("loved" = Option<bool>, Query, description = "Loved flag to filter by"),
+            ("banned" = Option<bool>, Query, description = "Banned flag to filter by"),
         ),
         responses(
             (
@@ -715,6 +780,15 @@ pub async fn favorite_tracks_endpoint(
             query.limit,
             query.order,
             query.order_direction,
+            if query.min_rating.is_some() || query.loved.is_some() || query.banned.is_some() {
+                Some(LibraryTrackFilters {
+                    min_rating: query.min_rating,
+                    loved: query.loved,
+                    banned: query.banned,
+                })
+            } else {
+                None
+            },
         )
         .await?
         .to_api()
@@ -722,6 +796,189 @@ pub async fn favorite_tracks_endpoint(
     ))
 }
 
+impl From<LibrarySetTrackRatingError> for actix_web::Error {
+    fn from(err: LibrarySetTrackRatingError) -> Self {
+        log::error!("{err:?}");
+        match err {
+            LibrarySetTrackRatingError::InvalidRating(_) => ErrorBadRequest(err.to_string()),
+            LibrarySetTrackRatingError::Db(_) => ErrorInternalServerError(err.to_string()),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LibrarySetTrackRatingQuery {
+    track_id: u64,
+    rating: Option<u8>,
+    loved: Option<bool>,
+    banned: Option<bool>,
+}
+
+#[cfg_attr(
+    feature = "openapi", utoipa::path(
+        tags = ["Library"],
+        post,
+        path = "/ratings/tracks",
+        description = "Set the rating and love/ban flags of a track",
+        params(
+            ("trackId" = u64, Query, description = "The track ID"),
+            ("rating" = Option<u8>, Query, description = "The star rating (1-5), or 0 to clear the rating"),
+            ("loved" = Option<bool>, Query, description = "Whether the track is loved"),
+            ("banned" = Option<bool>, Query, description = "Whether the track is banned"),
+        ),
+        responses(
+            (
+                status = 200,
+                description = "Success message",
+                body = Value,
+            )
+        )
+    )
+)]
+#[route("/ratings/tracks", method = "POST")]
+pub async fn set_track_rating_endpoint(
+    query: web::Query<LibrarySetTrackRatingQuery>,
+    db: LibraryDatabase,
+) -> Result<Json<Value>> {
+    set_track_rating(
+        &db,
+        &query.track_id.into(),
+        SetRating {
+            rating: query.rating.map(Some),
+            loved: query.loved,
+            banned: query.banned,
+        },
+    )
+    .await?;
+
+    Ok(Json(serde_json::json!({
+        "success": true
+    })))
+}
+
+impl From<LibrarySetAlbumRatingError> for actix_web::Error {
+    fn from(err: LibrarySetAlbumRatingError) -> Self {
+        log::error!("{err:?}");
+        match err {
+            LibrarySetAlbumRatingError::InvalidRating(_) => ErrorBadRequest(err.to_string()),
+            LibrarySetAlbumRatingError::Db(_) => ErrorInternalServerError(err.to_string()),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LibrarySetAlbumRatingQuery {
+    album_id: u64,
+    rating: Option<u8>,
+    loved: Option<bool>,
+    banned: Option<bool>,
+}
+
+#[cfg_attr(
+    feature = "openapi", utoipa::path(
+        tags = ["Library"],
+        post,
+        path = "/ratings/albums",
+        description = "Set the rating and love/ban flags of an album",
+        params(
+            ("albumId" = u64, Query, description = "The album ID"),
+            ("rating" = Option<u8>, Query, description = "The star rating (1-5), or 0 to clear the rating"),
+            ("loved" = Option<bool>, Query, description = "Whether the album is loved"),
+            ("banned" = Option<bool>, Query, description = "Whether the album is banned"),
+        ),
+        responses(
+            (
+                status = 200,
+                description = "Success message",
+                body = Value,
+            )
+        )
+    )
+)]
+#[route("/ratings/albums", method = "POST")]
+pub async fn set_album_rating_endpoint(
+    query: web::Query<LibrarySetAlbumRatingQuery>,
+    db: LibraryDatabase,
+) -> Result<Json<Value>> {
+    set_album_rating(
+        &db,
+        &query.album_id.into(),
+        SetRating {
+            rating: query.rating.map(Some),
+            loved: query.loved,
+            banned: query.banned,
+        },
+    )
+    .await?;
+
+    Ok(Json(serde_json::json!({
+        "success": true
+    })))
+}
+
+impl From<LibrarySetArtistRatingError> for actix_web::Error {
+    fn from(err: LibrarySetArtistRatingError) -> Self {
+        log::error!("{err:?}");
+        match err {
+            LibrarySetArtistRatingError::InvalidRating(_) => ErrorBadRequest(err.to_string()),
+            LibrarySetArtistRatingError::Db(_) => ErrorInternalServerError(err.to_string()),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LibrarySetArtistRatingQuery {
+    artist_id: u64,
+    rating: Option<u8>,
+    loved: Option<bool>,
+    banned: Option<bool>,
+}
+
+#[cfg_attr(
+    feature = "openapi", utoipa::path(
+        tags = ["Library"],
+        post,
+        path = "/ratings/artists",
+        description = "Set the rating and love/ban flags of an artist",
+        params(
+            ("artistId" = u64, Query, description = "The artist ID"),
+            ("rating" = Option<u8>, Query, description = "The star rating (1-5), or 0 to clear the rating"),
+            ("loved" = Option<bool>, Query, description = "Whether the artist is loved"),
+            ("banned" = Option<bool>, Query, description = "Whether the artist is banned"),
+        ),
+        responses(
+            (
+                status = 200,
+                description = "Success message",
+                body = Value,
+            )
+        )
+    )
+)]
+#[route("/ratings/artists", method = "POST")]
+pub async fn set_artist_rating_endpoint(
+    query: web::Query<LibrarySetArtistRatingQuery>,
+    db: LibraryDatabase,
+) -> Result<Json<Value>> {
+    set_artist_rating(
+        &db,
+        &query.artist_id.into(),
+        SetRating {
+            rating: query.rating.map(Some),
+            loved: query.loved,
+            banned: query.banned,
+        },
+    )
+    .await?;
+
+    Ok(Json(serde_json::json!({
+        "success": true
+    })))
+}
+
 impl From<LibraryArtistAlbumsError> for actix_web::Error {
     fn from(err: LibraryArtistAlbumsError) -> Self {
         log::error!("{err:?}");
@@ -1072,3 +1329,117 @@ pub async fn reindex_endpoint(
 
     Ok(Json(serde_json::json!({"success": true})))
 }
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ApiDuplicateTrack {
+    pub id: u64,
+    pub title: String,
+    pub album: String,
+    pub album_id: u64,
+    pub artist: String,
+    pub artist_id: u64,
+    pub duration: f64,
+    pub file: Option<String>,
+    pub bytes: u64,
+    pub format: Option<AudioFormat>,
+    pub bit_depth: Option<u8>,
+    pub sample_rate: Option<u32>,
+    pub channels: Option<u8>,
+    pub source: TrackApiSource,
+}
+
+impl From<LibraryTrack> for ApiDuplicateTrack {
+    fn from(value: LibraryTrack) -> Self {
+        Self {
+            id: value.id,
+            title: value.title,
+            album: value.album,
+            album_id: value.album_id,
+            artist: value.artist,
+            artist_id: value.artist_id,
+            duration: value.duration,
+            file: value.file,
+            bytes: value.bytes,
+            format: value.format,
+            bit_depth: value.bit_depth,
+            sample_rate: value.sample_rate,
+            channels: value.channels,
+            source: value.source,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ApiDuplicateTrackGroup {
+    pub keep: ApiDuplicateTrack,
+    pub duplicates: Vec<ApiDuplicateTrack>,
+    pub similarity: f64,
+}
+
+impl From<DuplicateTrackGroup> for ApiDuplicateTrackGroup {
+    fn from(value: DuplicateTrackGroup) -> Self {
+        Self {
+            keep: value.keep.into(),
+            duplicates: value.duplicates.into_iter().map(Into::into).collect(),
+            similarity: value.similarity,
+        }
+    }
+}
+
+impl From<LibraryDuplicateTracksError> for actix_web::Error {
+    fn from(err: LibraryDuplicateTracksError) -> Self {
+        log::error!("{err:?}");
+        match err {
+            LibraryDuplicateTracksError::Db(_) => ErrorInternalServerError(err.to_string()),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryDuplicateTracksQuery {
+    min_similarity: Option<f64>,
+}
+
+#[cfg_attr(
+    feature = "openapi", utoipa::path(
+        tags = ["Library"],
+        get,
+        path = "/duplicates",
+        description = "Get the groups of tracks that are copies of the same recording, based on their acoustic fingerprints",
+        params(
+            ("minSimilarity" = Option<f64>, Query, description = "The minimum fingerprint similarity (0.0-1.0) for two tracks to be considered the same recording"),
+        ),
+        responses(
+            (
+                status = 200,
+                description = "The groups of duplicate tracks, with the suggested copy to keep",
+                body = Vec<ApiDuplicateTrackGroup>,
+            )
+        )
+    )
+)]
+#[route("/duplicates", method = "GET")]
+pub async fn duplicate_tracks_endpoint(
+    query: web::Query<LibraryDuplicateTracksQuery>,
+    db: LibraryDatabase,
+) -> Result<Json<Vec<ApiDuplicateTrackGroup>>> {
+    if query
+        .min_similarity
+        .is_some_and(|x| !(0.0..=1.0).contains(&x))
+    {
+        return Err(ErrorBadRequest("minSimilarity must be between 0.0 and 1.0"));
+    }
+
+    Ok(Json(
+        duplicate_tracks(&db, query.min_similarity)
+            .await?
+            .into_iter()
+            .map(Into::into)
+            .collect(),
+    ))
+}
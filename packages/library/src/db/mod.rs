@@ -10,13 +10,13 @@ use moosicbox_core::{
 use moosicbox_database::{
     boxed, profiles::LibraryDatabase, query::*, DatabaseError, DatabaseValue,
 };
-use moosicbox_json_utils::ToValueType;
+use moosicbox_json_utils::{database::ToValue as _, ToValueType};
 use thiserror::Error;
 
 pub mod models;
 
 use crate::{
-    db::models::LibraryConfig,
+    db::models::{LibraryConfig, TrackFingerprint},
     models::{LibraryAlbum, LibraryArtist, LibraryTrack},
 };
 
@@ -105,7 +105,18 @@ pub async fn get_library_access_token(
 }
 
 pub async fn get_artists(db: &LibraryDatabase) -> Result<Vec<LibraryArtist>, DbError> {
-    Ok(db.select("artists").execute(db).await?.to_value_type()?)
+    Ok(db
+        .select("artists")
+        .columns(&[
+            "artists.*",
+            "artist_ratings.rating",
+            "artist_ratings.loved",
+            "artist_ratings.banned",
+        ])
+        .left_join("artist_ratings", "artist_ratings.artist_id=artists.id")
+        .execute(db)
+        .await?
+        .to_value_type()?)
 }
 
 pub async fn get_albums(db: &LibraryDatabase) -> Result<Vec<LibraryAlbum>, DbError> {
@@ -122,9 +133,13 @@ pub async fn get_albums(db: &LibraryDatabase) -> Result<Vec<LibraryAlbum>, DbErr
             "artists.tidal_id as tidal_artist_id",
             "artists.qobuz_id as qobuz_artist_id",
             "tracks.source",
+            "album_ratings.rating",
+            "album_ratings.loved",
+            "album_ratings.banned",
         ])
         .left_join("tracks", "tracks.album_id=albums.id")
         .left_join("track_sizes", "track_sizes.track_id=tracks.id")
+        .left_join("album_ratings", "album_ratings.album_id=albums.id")
         .join("artists", "artists.id=albums.artist_id")
         .sort("albums.id", SortDirection::Desc)
         .where_or(boxed![
@@ -143,7 +158,14 @@ pub async fn get_artist(
 ) -> Result<Option<LibraryArtist>, DbError> {
     Ok(db
         .select("artists")
-        .where_eq(column.to_string(), id)
+        .columns(&[
+            "artists.*",
+            "artist_ratings.rating",
+            "artist_ratings.loved",
+            "artist_ratings.banned",
+        ])
+        .where_eq(format!("artists.{column}"), id)
+        .left_join("artist_ratings", "artist_ratings.artist_id=artists.id")
         .execute_first(db)
         .await?
         .as_ref()
@@ -235,9 +257,13 @@ pub async fn get_album(
             "artists.title as artist",
             "artists.tidal_id as tidal_artist_id",
             "artists.qobuz_id as qobuz_artist_id",
+            "album_ratings.rating",
+            "album_ratings.loved",
+            "album_ratings.banned",
         ])
         .where_eq(format!("albums.{column}"), id)
         .join("artists", "artists.id = albums.artist_id")
+        .left_join("album_ratings", "album_ratings.album_id=albums.id")
         .execute_first(db)
         .await?
         .as_ref()
@@ -260,6 +286,7 @@ pub async fn get_album_tracks(
             "artists.tidal_id as tidal_artist_id",
             "artists.qobuz_id as qobuz_artist_id",
             "artists.id as artist_id",
+            "track_artists.title as track_artist",
             "albums.artwork",
             "track_sizes.format",
             "track_sizes.bytes",
@@ -268,14 +295,22 @@ pub async fn get_album_tracks(
             "track_sizes.overall_bitrate",
             "track_sizes.sample_rate",
             "track_sizes.channels",
+            "track_ratings.rating",
+            "track_ratings.loved",
+            "track_ratings.banned",
         ])
         .where_eq("tracks.album_id", album_id)
         .join("albums", "albums.id=tracks.album_id")
         .join("artists", "artists.id=albums.artist_id")
+        .left_join(
+            "artists track_artists",
+            "track_artists.id=tracks.track_artist_id",
+        )
         .left_join(
             "track_sizes",
             "tracks.id=track_sizes.track_id AND track_sizes.format=tracks.format",
         )
+        .left_join("track_ratings", "track_ratings.track_id=tracks.id")
         .sort("number", SortDirection::Asc)
         .execute(db)
         .await?
@@ -299,9 +334,13 @@ pub async fn get_artist_albums(
             "artists.qobuz_id as qobuz_artist_id",
             "tracks.format",
             "tracks.source",
+            "album_ratings.rating",
+            "album_ratings.loved",
+            "album_ratings.banned",
         ])
         .left_join("tracks", "tracks.album_id=albums.id")
         .left_join("track_sizes", "track_sizes.track_id=tracks.id")
+        .left_join("album_ratings", "album_ratings.album_id=albums.id")
         .join("artists", "artists.id=albums.artist_id")
         .where_eq("albums.artist_id", artist_id)
         .sort("albums.id", SortDirection::Desc)
@@ -310,6 +349,56 @@ pub async fn get_artist_albums(
         .as_model_mapped()
 }
 
+/// Albums by other album artists (e.g. compilations) that contain tracks by
+/// the given artist.
+pub async fn get_artist_appears_on_albums(
+    db: &LibraryDatabase,
+    artist_id: &Id,
+) -> Result<Vec<LibraryAlbum>, DbError> {
+    let album_ids = db
+        .select("tracks")
+        .distinct()
+        .columns(&["album_id"])
+        .where_eq("track_artist_id", artist_id)
+        .execute(db)
+        .await?
+        .iter()
+        .map(|row| row.to_value("album_id"))
+        .collect::<Result<Vec<u64>, _>>()?;
+
+    if album_ids.is_empty() {
+        return Ok(vec![]);
+    }
+
+    db.select("albums")
+        .distinct()
+        .columns(&[
+            "albums.*",
+            "albums.id as album_id",
+            "track_sizes.bit_depth",
+            "track_sizes.sample_rate",
+            "track_sizes.channels",
+            "artists.title as artist",
+            "artists.tidal_id as tidal_artist_id",
+            "artists.qobuz_id as qobuz_artist_id",
+            "tracks.format",
+            "tracks.source",
+            "album_ratings.rating",
+            "album_ratings.loved",
+            "album_ratings.banned",
+        ])
+        .left_join("tracks", "tracks.album_id=albums.id")
+        .left_join("track_sizes", "track_sizes.track_id=tracks.id")
+        .left_join("album_ratings", "album_ratings.album_id=albums.id")
+        .join("artists", "artists.id=albums.artist_id")
+        .where_in("albums.id", album_ids)
+        .where_not_eq("albums.artist_id", artist_id)
+        .sort("albums.id", SortDirection::Desc)
+        .execute(db)
+        .await?
+        .as_model_mapped()
+}
+
 #[derive(Debug, Clone)]
 pub struct SetTrackSize {
     pub track_id: u64,
@@ -449,6 +538,7 @@ pub async fn get_tracks(
             "artists.tidal_id as tidal_artist_id",
             "artists.qobuz_id as qobuz_artist_id",
             "artists.id as artist_id",
+            "track_artists.title as track_artist",
             "albums.artwork",
             "track_sizes.format",
             "track_sizes.bytes",
@@ -457,14 +547,22 @@ pub async fn get_tracks(
             "track_sizes.overall_bitrate",
             "track_sizes.sample_rate",
             "track_sizes.channels",
+            "track_ratings.rating",
+            "track_ratings.loved",
+            "track_ratings.banned",
         ])
         .filter_if_some(ids.map(|ids| where_in("tracks.id", ids.to_vec())))
         .join("albums", "albums.id=tracks.album_id")
         .join("artists", "artists.id=albums.artist_id")
+        .left_join(
+            "artists track_artists",
+            "track_artists.id=tracks.track_artist_id",
+        )
         .left_join(
             "track_sizes",
             "tracks.id=track_sizes.track_id AND track_sizes.format=tracks.format",
         )
+        .left_join("track_ratings", "track_ratings.track_id=tracks.id")
         .execute(db)
         .await?
         .to_value_type()?)
@@ -632,6 +730,10 @@ pub async fn add_albums_and_get_albums(
                     ),
                     ("artwork", DatabaseValue::StringOpt(album.artwork)),
                     ("directory", DatabaseValue::StringOpt(album.directory)),
+                    (
+                        "compilation",
+                        DatabaseValue::Number(i64::from(album.compilation)),
+                    ),
                 ])
             })
             .collect(),
@@ -695,6 +797,18 @@ pub async fn add_tracks(
                     "source",
                     DatabaseValue::String(insert.track.source.as_ref().to_string()),
                 ),
+                (
+                    "track_artist_id",
+                    DatabaseValue::NumberOpt(insert.track.track_artist_id.map(|x| x as i64)),
+                ),
+                (
+                    "start_offset",
+                    DatabaseValue::RealOpt(insert.track.start_offset),
+                ),
+                (
+                    "end_offset",
+                    DatabaseValue::RealOpt(insert.track.end_offset),
+                ),
             ];
 
             if let Some(file) = &insert.file {
@@ -731,3 +845,212 @@ pub async fn add_tracks(
         .await?
         .to_value_type()?)
 }
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SetRating {
+    pub rating: Option<Option<u8>>,
+    pub loved: Option<bool>,
+    pub banned: Option<bool>,
+}
+
+impl SetRating {
+    fn to_values(self) -> Vec<(&'static str, DatabaseValue)> {
+        let mut values = vec![];
+
+        if let Some(rating) = self.rating {
+            values.push(("rating", DatabaseValue::NumberOpt(rating.map(|x| x as i64))));
+        }
+        if let Some(loved) = self.loved {
+            values.push(("loved", DatabaseValue::Number(loved as i64)));
+        }
+        if let Some(banned) = self.banned {
+            values.push(("banned", DatabaseValue::Number(banned as i64)));
+        }
+
+        values
+    }
+}
+
+async fn set_rating(
+    db: &LibraryDatabase,
+    table: &str,
+    column: &str,
+    id: u64,
+    value: SetRating,
+) -> Result<(), DbError> {
+    let values = value.to_values();
+
+    if values.is_empty() {
+        return Ok(());
+    }
+
+    db.upsert(table)
+        .where_eq(column, id)
+        .value(column, id)
+        .values(values)
+        .execute(db)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn set_track_rating(
+    db: &LibraryDatabase,
+    track_id: u64,
+    value: SetRating,
+) -> Result<(), DbError> {
+    set_rating(db, "track_ratings", "track_id", track_id, value).await
+}
+
+pub async fn set_album_rating(
+    db: &LibraryDatabase,
+    album_id: u64,
+    value: SetRating,
+) -> Result<(), DbError> {
+    set_rating(db, "album_ratings", "album_id", album_id, value).await
+}
+
+pub async fn set_artist_rating(
+    db: &LibraryDatabase,
+    artist_id: u64,
+    value: SetRating,
+) -> Result<(), DbError> {
+    set_rating(db, "artist_ratings", "artist_id", artist_id, value).await
+}
+
+/// Stores the star ratings read from the tracks' file tags. Only the
+/// `rating` column is touched so the loved/banned flags are preserved.
+pub async fn set_track_tag_ratings(
+    db: &LibraryDatabase,
+    ratings: &[(u64, u8)],
+) -> Result<(), DbError> {
+    if ratings.is_empty() {
+        return Ok(());
+    }
+
+    let values = ratings
+        .iter()
+        .map(|(track_id, rating)| {
+            vec![
+                ("track_id", DatabaseValue::Number(*track_id as i64)),
+                ("rating", DatabaseValue::Number(*rating as i64)),
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    db.upsert_multi("track_ratings")
+        .unique(boxed![identifier("track_id")])
+        .values(values)
+        .execute(db)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn get_banned_track_ids(
+    db: &LibraryDatabase,
+    track_ids: &[u64],
+) -> Result<Vec<u64>, DbError> {
+    if track_ids.is_empty() {
+        return Ok(vec![]);
+    }
+
+    Ok(db
+        .select("track_ratings")
+        .columns(&["track_id"])
+        .where_in("track_id", track_ids.to_vec())
+        .where_eq("banned", 1)
+        .execute(db)
+        .await?
+        .iter()
+        .map(|row| row.to_value("track_id"))
+        .collect::<Result<Vec<_>, _>>()?)
+}
+
+pub async fn set_track_fingerprint(
+    db: &LibraryDatabase,
+    track_id: u64,
+    fingerprint: &str,
+    duration: f64,
+) -> Result<(), DbError> {
+    db.upsert("track_fingerprints")
+        .where_eq("track_id", track_id)
+        .value("track_id", track_id)
+        .value("fingerprint", fingerprint)
+        .value("duration", duration)
+        .execute(db)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn get_track_fingerprints(
+    db: &LibraryDatabase,
+) -> Result<Vec<TrackFingerprint>, DbError> {
+    Ok(db
+        .select("track_fingerprints")
+        .execute(db)
+        .await?
+        .to_value_type()?)
+}
+
+#[cfg(test)]
+mod test {
+    use moosicbox_schema::fixtures;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test_log::test(tokio::test)]
+    async fn get_artists_returns_seeded_artists() {
+        let db = fixtures::library_db().await;
+        let library = fixtures::seed_library(&*db).await.unwrap();
+
+        let artists = get_artists(&db).await.unwrap();
+
+        assert_eq!(
+            artists.iter().map(|x| x.id).collect::<Vec<_>>(),
+            library.artists
+        );
+        assert_eq!(artists[1].title, "Fixture Ensemble");
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn get_album_tracks_returns_tracks_in_order() {
+        let db = fixtures::library_db().await;
+        let library = fixtures::seed_library(&*db).await.unwrap();
+
+        let tracks = get_album_tracks(&db, &library.albums[0].into())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            tracks
+                .iter()
+                .map(|x| (x.number, x.title.as_str(), x.artist.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (1, "Intro", "The Test Pattern"),
+                (2, "Signal", "The Test Pattern"),
+                (3, "Noise Floor", "The Test Pattern"),
+            ]
+        );
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn delete_track_removes_only_that_track() {
+        let db = fixtures::library_db().await;
+        let library = fixtures::seed_library(&*db).await.unwrap();
+
+        let deleted = delete_track(&db, library.tracks[0]).await.unwrap();
+
+        assert_eq!(deleted.map(|x| x.id), Some(library.tracks[0]));
+        assert_eq!(
+            get_album_tracks(&db, &library.albums[0].into())
+                .await
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+}
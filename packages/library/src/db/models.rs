@@ -59,3 +59,40 @@ impl AsId for LibraryConfig {
         DatabaseValue::Number(self.id as i64)
     }
 }
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct TrackFingerprint {
+    pub id: u64,
+    pub track_id: u64,
+    pub fingerprint: String,
+    pub duration: f64,
+    pub created: String,
+    pub updated: String,
+}
+
+impl MissingValue<TrackFingerprint> for &moosicbox_database::Row {}
+impl ToValueType<TrackFingerprint> for &Row {
+    fn to_value_type(self) -> Result<TrackFingerprint, ParseError> {
+        Ok(TrackFingerprint {
+            id: self.to_value("id")?,
+            track_id: self.to_value("track_id")?,
+            fingerprint: self.to_value("fingerprint")?,
+            duration: self.to_value("duration")?,
+            created: self.to_value("created")?,
+            updated: self.to_value("updated")?,
+        })
+    }
+}
+
+impl AsModelResult<TrackFingerprint, ParseError> for Row {
+    fn as_model(&self) -> Result<TrackFingerprint, ParseError> {
+        self.to_value_type()
+    }
+}
+
+impl AsId for TrackFingerprint {
+    fn as_id(&self) -> DatabaseValue {
+        DatabaseValue::Number(self.id as i64)
+    }
+}
@@ -0,0 +1,346 @@
+use std::{collections::VecDeque, sync::Arc};
+
+use rustfft::{num_complex::Complex, Fft, FftPlanner};
+use thiserror::Error;
+
+/// The sample rate the audio is resampled to before
+/// analysis. Only the 28 Hz - 3.5 kHz range is used for the chroma
+/// features, so there is no point in analyzing anything higher.
+pub const TARGET_SAMPLE_RATE: u32 = 11025;
+
+/// Only the beginning of each track is fingerprinted, which is more than
+/// enough to identify a recording and keeps the stored fingerprints small.
+pub const MAX_DURATION_SECS: u32 = 120;
+
+const FRAME_SIZE: usize = 4096;
+const FRAME_STEP: usize = FRAME_SIZE / 3;
+const MIN_FREQ: f32 = 28.0;
+const MAX_FREQ: f32 = 3520.0;
+const MAX_ALIGNMENT_OFFSET: isize = 80;
+
+#[derive(Debug, Error)]
+pub enum FingerprintError {
+    #[error("Invalid fingerprint: {0}")]
+    InvalidFingerprint(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fingerprint {
+    pub duration: f64,
+    pub data: Vec<u32>,
+}
+
+impl Fingerprint {
+    #[must_use]
+    pub fn encode(&self) -> String {
+        encode(&self.data)
+    }
+}
+
+#[must_use]
+pub fn encode(data: &[u32]) -> String {
+    data.iter().map(|x| format!("{x:08x}")).collect()
+}
+
+pub fn decode(value: &str) -> Result<Vec<u32>, FingerprintError> {
+    value
+        .as_bytes()
+        .chunks(8)
+        .map(|chunk| {
+            std::str::from_utf8(chunk)
+                .ok()
+                .filter(|x| x.len() == 8)
+                .and_then(|x| u32::from_str_radix(x, 16).ok())
+                .ok_or_else(|| FingerprintError::InvalidFingerprint(value.to_string()))
+        })
+        .collect()
+}
+
+/// Computes a chroma based acoustic fingerprint from mono PCM samples.
+///
+/// Each frame of audio is reduced to a 12 bin chroma vector (the energy of
+/// each pitch class), and each sub-fingerprint encodes the relations between
+/// neighbouring chroma bins and frames as 32 bits. Since only the pitch
+/// content is looked at, the same recording yields nearly identical
+/// fingerprints regardless of the format, bitrate or sample rate it was
+/// encoded in.
+pub struct Fingerprinter {
+    step: f64,
+    position: f64,
+    index: u64,
+    previous: f32,
+    filter: VecDeque<f32>,
+    filter_width: usize,
+    filter_sum: f32,
+    max_samples: usize,
+    consumed: usize,
+    buffer: Vec<f32>,
+    window: Vec<f32>,
+    fft: Arc<dyn Fft<f32>>,
+    bin_chroma: Vec<Option<usize>>,
+    chroma: Vec<[f32; 12]>,
+}
+
+impl Fingerprinter {
+    #[must_use]
+    pub fn new(sample_rate: u32) -> Self {
+        let step = f64::from(sample_rate) / f64::from(TARGET_SAMPLE_RATE);
+        let filter_width = (step.round() as usize).max(1);
+
+        let window = (0..FRAME_SIZE)
+            .map(|i| {
+                0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / (FRAME_SIZE - 1) as f32).cos()
+            })
+            .collect();
+
+        let bin_chroma = (0..FRAME_SIZE / 2)
+            .map(|i| {
+                let freq = i as f32 * TARGET_SAMPLE_RATE as f32 / FRAME_SIZE as f32;
+
+                if !(MIN_FREQ..=MAX_FREQ).contains(&freq) {
+                    return None;
+                }
+
+                let note = 12.0 * (freq / 440.0).log2() + 69.0;
+
+                Some((note.round() as i64).rem_euclid(12) as usize)
+            })
+            .collect();
+
+        Self {
+            step,
+            position: 0.0,
+            index: 0,
+            previous: 0.0,
+            filter: VecDeque::with_capacity(filter_width),
+            filter_width,
+            filter_sum: 0.0,
+            max_samples: (TARGET_SAMPLE_RATE * MAX_DURATION_SECS) as usize,
+            consumed: 0,
+            buffer: Vec::with_capacity(FRAME_SIZE),
+            window,
+            fft: FftPlanner::new().plan_fft_forward(FRAME_SIZE),
+            bin_chroma,
+            chroma: vec![],
+        }
+    }
+
+    /// Whether enough audio has been consumed and the rest of the input can
+    /// be skipped.
+    #[must_use]
+    pub fn is_full(&self) -> bool {
+        self.consumed >= self.max_samples
+    }
+
+    /// Consumes mono samples at the sample rate the `Fingerprinter` was
+    /// created with. The samples are low-pass filtered with a moving average
+    /// and linearly resampled to the [`TARGET_SAMPLE_RATE`].
+    pub fn consume(&mut self, samples: &[f32]) {
+        for sample in samples {
+            if self.is_full() {
+                break;
+            }
+
+            self.filter.push_back(*sample);
+            self.filter_sum += sample;
+            if self.filter.len() > self.filter_width {
+                self.filter_sum -= self.filter.pop_front().unwrap_or_default();
+            }
+            let filtered = self.filter_sum / self.filter.len() as f32;
+
+            while self.position <= self.index as f64 {
+                let fraction = (self.position - (self.index as f64 - 1.0)) as f32;
+                let value = self.previous + (filtered - self.previous) * fraction;
+                self.position += self.step;
+                self.push(value);
+            }
+
+            self.previous = filtered;
+            self.index += 1;
+        }
+    }
+
+    fn push(&mut self, value: f32) {
+        self.buffer.push(value);
+        self.consumed += 1;
+
+        if self.buffer.len() == FRAME_SIZE {
+            self.process_frame();
+            self.buffer.drain(..FRAME_STEP);
+        }
+    }
+
+    fn process_frame(&mut self) {
+        let mut frame = self
+            .buffer
+            .iter()
+            .zip(self.window.iter())
+            .map(|(x, w)| Complex::new(x * w, 0.0))
+            .collect::<Vec<_>>();
+
+        self.fft.process(&mut frame);
+
+        let mut chroma = [0.0_f32; 12];
+
+        for (bin, value) in frame.iter().take(FRAME_SIZE / 2).enumerate() {
+            if let Some(index) = self.bin_chroma[bin] {
+                chroma[index] += value.norm_sqr();
+            }
+        }
+
+        let norm = chroma.iter().map(|x| x * x).sum::<f32>().sqrt();
+
+        if norm > 0.01 {
+            for value in &mut chroma {
+                *value /= norm;
+            }
+        } else {
+            chroma = [0.0; 12];
+        }
+
+        self.chroma.push(chroma);
+    }
+
+    #[must_use]
+    pub fn finish(self) -> Fingerprint {
+        let duration = self.consumed as f64 / f64::from(TARGET_SAMPLE_RATE);
+
+        // Smooth the chroma features over time to make them less sensitive
+        // to slight differences in the frame alignment.
+        let smoothed = (0..self.chroma.len())
+            .map(|i| {
+                let from = i.saturating_sub(1);
+                let to = (i + 2).min(self.chroma.len());
+                let mut value = [0.0_f32; 12];
+
+                for chroma in &self.chroma[from..to] {
+                    for (v, c) in value.iter_mut().zip(chroma.iter()) {
+                        *v += c;
+                    }
+                }
+
+                value
+            })
+            .collect::<Vec<_>>();
+
+        let data = smoothed
+            .windows(2)
+            .map(|frames| sub_fingerprint(&frames[0], &frames[1]))
+            .collect();
+
+        Fingerprint { duration, data }
+    }
+}
+
+fn sub_fingerprint(previous: &[f32; 12], current: &[f32; 12]) -> u32 {
+    let mut bits = 0_u32;
+
+    for c in 0..12 {
+        let next = (c + 1) % 12;
+        let delta = (current[c] - current[next]) - (previous[c] - previous[next]);
+
+        if delta > 0.0 {
+            bits |= 1 << c;
+        }
+        if current[c] > previous[c] {
+            bits |= 1 << (12 + c);
+        }
+        if c < 8 && current[c] > current[(c + 7) % 12] {
+            bits |= 1 << (24 + c);
+        }
+    }
+
+    bits
+}
+
+/// Compares two fingerprints, returning the fraction of matching bits
+/// (0.0-1.0) at the best alignment of the two. Unrelated recordings score
+/// around 0.5.
+#[must_use]
+pub fn similarity(a: &[u32], b: &[u32]) -> f64 {
+    let min_overlap = (a.len().min(b.len()) / 2).max(1);
+    let mut best = 0.0;
+
+    for offset in -MAX_ALIGNMENT_OFFSET..=MAX_ALIGNMENT_OFFSET {
+        let (a, b) = if offset >= 0 {
+            (a.get(offset as usize..).unwrap_or_default(), b)
+        } else {
+            (a, b.get((-offset) as usize..).unwrap_or_default())
+        };
+
+        let overlap = a.len().min(b.len());
+
+        if overlap < min_overlap {
+            continue;
+        }
+
+        let errors = a
+            .iter()
+            .zip(b.iter())
+            .map(|(a, b)| (a ^ b).count_ones())
+            .sum::<u32>();
+
+        let score = 1.0 - f64::from(errors) / (32.0 * overlap as f64);
+
+        if score > best {
+            best = score;
+        }
+    }
+
+    best
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const MELODY: [f32; 6] = [261.63, 329.63, 392.0, 493.88, 440.0, 349.23];
+    const OTHER_MELODY: [f32; 6] = [293.66, 369.99, 277.18, 415.3, 311.13, 466.16];
+
+    fn tone_sequence(notes: &[f32], sample_rate: u32, seconds: f32) -> Vec<f32> {
+        let len = (sample_rate as f32 * seconds) as usize;
+        let note_len = sample_rate as usize / 2;
+
+        (0..len)
+            .map(|i| {
+                let freq = notes[(i / note_len) % notes.len()];
+                (2.0 * std::f32::consts::PI * freq * i as f32 / sample_rate as f32).sin() * 0.5
+            })
+            .collect()
+    }
+
+    fn fingerprint(sample_rate: u32, samples: &[f32]) -> Fingerprint {
+        let mut fingerprinter = Fingerprinter::new(sample_rate);
+        fingerprinter.consume(samples);
+        fingerprinter.finish()
+    }
+
+    #[test]
+    fn encode_and_decode_round_trips() {
+        let data = vec![0, 1, 0xdead_beef, u32::MAX];
+
+        assert_eq!(decode(&encode(&data)).unwrap(), data);
+    }
+
+    #[test]
+    fn decode_fails_on_invalid_length() {
+        assert!(decode("abc").is_err());
+    }
+
+    #[test]
+    fn same_recording_at_different_sample_rates_is_similar() {
+        let a = fingerprint(44100, &tone_sequence(&MELODY, 44100, 20.0));
+        let b = fingerprint(48000, &tone_sequence(&MELODY, 48000, 20.0));
+
+        assert!(!a.data.is_empty());
+        assert!(similarity(&a.data, &b.data) > 0.85);
+    }
+
+    #[test]
+    fn different_recordings_are_not_similar() {
+        let a = fingerprint(44100, &tone_sequence(&MELODY, 44100, 20.0));
+        let b = fingerprint(44100, &tone_sequence(&OTHER_MELODY, 44100, 20.0));
+
+        assert!(similarity(&a.data, &b.data) < crate::DEFAULT_DUPLICATE_MIN_SIMILARITY);
+    }
+}
@@ -2,6 +2,7 @@
 
 use std::{
     cmp::Ordering,
+    collections::HashMap,
     fs::File,
     sync::{Arc, LazyLock},
 };
@@ -43,7 +44,10 @@ pub mod api;
 
 pub mod cache;
 pub mod db;
+pub mod fingerprint;
 pub mod profiles;
+#[cfg(feature = "tags")]
+pub mod tags;
 
 pub mod models {
     pub use moosicbox_library_models::*;
@@ -154,6 +158,7 @@ pub async fn remove_favorite_artist(
 #[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub enum LibraryAlbumOrder {
     Date,
+    Rating,
 }
 
 #[derive(Debug, Serialize, Deserialize, EnumString, AsRefStr, PartialEq, Clone, Copy)]
@@ -220,6 +225,24 @@ pub fn filter_albums<'a>(
                 })
             })
         })
+        .filter(|album| {
+            !request.filters.as_ref().is_some_and(|x| {
+                x.min_rating
+                    .is_some_and(|min| !album.rating.is_some_and(|rating| rating >= min))
+            })
+        })
+        .filter(|album| {
+            !request
+                .filters
+                .as_ref()
+                .is_some_and(|x| x.loved.is_some_and(|loved| album.loved != loved))
+        })
+        .filter(|album| {
+            !request
+                .filters
+                .as_ref()
+                .is_some_and(|x| x.banned.is_some_and(|banned| album.banned != banned))
+        })
 }
 
 pub fn sort_albums<'a>(
@@ -268,12 +291,30 @@ pub fn sort_albums<'a>(
         }),
         Some(AlbumSort::DateAddedAsc) => albums.sort_by(|a, b| a.date_added.cmp(&b.date_added)),
         Some(AlbumSort::DateAddedDesc) => albums.sort_by(|b, a| a.date_added.cmp(&b.date_added)),
+        Some(AlbumSort::RatingAsc) => {
+            albums.sort_by(|a, b| compare_ratings(a.rating, b.rating, false))
+        }
+        Some(AlbumSort::RatingDesc) => {
+            albums.sort_by(|a, b| compare_ratings(a.rating, b.rating, true))
+        }
         None => (),
     }
 
     albums
 }
 
+/// Orders the rated items by their rating, keeping the unrated items at the
+/// end regardless of the sort direction.
+fn compare_ratings(a: Option<u8>, b: Option<u8>, descending: bool) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) if descending => b.cmp(&a),
+        (Some(a), Some(b)) => a.cmp(&b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum LibraryFavoriteAlbumsError {
     #[error("No user ID available")]
@@ -373,6 +414,7 @@ pub async fn remove_favorite_album(
 #[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub enum LibraryTrackOrder {
     Date,
+    Rating,
 }
 
 #[derive(Debug, Serialize, Deserialize, EnumString, AsRefStr, PartialEq, Clone, Copy)]
@@ -384,6 +426,54 @@ pub enum LibraryTrackOrderDirection {
     Desc,
 }
 
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LibraryTrackFilters {
+    pub min_rating: Option<u8>,
+    pub loved: Option<bool>,
+    pub banned: Option<bool>,
+}
+
+pub fn filter_tracks<'a>(
+    tracks: &'a [LibraryTrack],
+    filters: &'a LibraryTrackFilters,
+) -> impl Iterator<Item = &'a LibraryTrack> {
+    tracks
+        .iter()
+        .filter(|track| {
+            !filters
+                .min_rating
+                .is_some_and(|min| !track.rating.is_some_and(|rating| rating >= min))
+        })
+        .filter(|track| !filters.loved.is_some_and(|loved| track.loved != loved))
+        .filter(|track| !filters.banned.is_some_and(|banned| track.banned != banned))
+}
+
+pub fn sort_tracks(
+    tracks: &mut [LibraryTrack],
+    order: Option<LibraryTrackOrder>,
+    order_direction: Option<LibraryTrackOrderDirection>,
+) {
+    let Some(order) = order else {
+        return;
+    };
+    let direction = order_direction.unwrap_or(LibraryTrackOrderDirection::Desc);
+
+    match (order, direction) {
+        (LibraryTrackOrder::Date, LibraryTrackOrderDirection::Asc) => {
+            tracks.sort_by(|a, b| a.date_added.cmp(&b.date_added))
+        }
+        (LibraryTrackOrder::Date, LibraryTrackOrderDirection::Desc) => {
+            tracks.sort_by(|a, b| b.date_added.cmp(&a.date_added))
+        }
+        (LibraryTrackOrder::Rating, LibraryTrackOrderDirection::Asc) => {
+            tracks.sort_by(|a, b| compare_ratings(a.rating, b.rating, false))
+        }
+        (LibraryTrackOrder::Rating, LibraryTrackOrderDirection::Desc) => {
+            tracks.sort_by(|a, b| compare_ratings(a.rating, b.rating, true))
+        }
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum LibraryFavoriteTracksError {
     #[error("No user ID available")]
@@ -401,13 +491,20 @@ pub async fn favorite_tracks(
     track_ids: Option<&[Id]>,
     offset: Option<u32>,
     limit: Option<u32>,
-    _order: Option<LibraryTrackOrder>,
-    _order_direction: Option<LibraryTrackOrderDirection>,
+    order: Option<LibraryTrackOrder>,
+    order_direction: Option<LibraryTrackOrderDirection>,
+    filters: Option<LibraryTrackFilters>,
 ) -> PagingResult<LibraryTrack, LibraryFavoriteTracksError> {
     let offset = offset.unwrap_or(0);
     let limit = limit.unwrap_or(100);
 
     let items = db::get_tracks(db, track_ids).await?;
+    let mut items = if let Some(filters) = &filters {
+        filter_tracks(&items, filters).cloned().collect::<Vec<_>>()
+    } else {
+        items
+    };
+    sort_tracks(&mut items, order, order_direction);
     log::trace!("Received favorite tracks response: {items:?}");
 
     let total = items.len() as u32;
@@ -433,8 +530,9 @@ pub async fn favorite_tracks(
                         track_ids.as_deref(),
                         Some(offset),
                         Some(limit),
-                        _order,
-                        _order_direction,
+                        order,
+                        order_direction,
+                        filters,
                     )
                     .await
                 })
@@ -479,6 +577,95 @@ pub async fn remove_favorite_track(
     Ok(())
 }
 
+#[derive(Debug, Error)]
+pub enum LibrarySetTrackRatingError {
+    #[error("Invalid rating: {0}")]
+    InvalidRating(u8),
+    #[error(transparent)]
+    Db(#[from] DbError),
+}
+
+/// Treats a rating of `0` as clearing it, and returns the rating back as the
+/// error if it is out of the 1-5 range.
+fn validate_rating(value: db::SetRating) -> Result<db::SetRating, u8> {
+    let value = db::SetRating {
+        rating: value.rating.map(|x| x.filter(|x| *x > 0)),
+        ..value
+    };
+
+    match value.rating {
+        Some(Some(rating)) if rating > 5 => Err(rating),
+        _ => Ok(value),
+    }
+}
+
+/// Sets the rating and love/ban flags of a track. A `Some(0)` rating clears
+/// the track's rating. When the rating changes and the track is stored
+/// locally, the rating is also written to the file's tags.
+pub async fn set_track_rating(
+    db: &LibraryDatabase,
+    track_id: &Id,
+    value: db::SetRating,
+) -> Result<(), LibrarySetTrackRatingError> {
+    let value = validate_rating(value).map_err(LibrarySetTrackRatingError::InvalidRating)?;
+
+    let id = track_id.into();
+    db::set_track_rating(db, id, value).await?;
+
+    #[cfg(feature = "tags")]
+    if let Some(rating) = value.rating {
+        if let Some(file) = db::get_track(db, track_id).await?.and_then(|x| x.file) {
+            match tokio::task::spawn_blocking(move || tags::write_rating_to_path(file, rating))
+                .await
+            {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => {
+                    log::error!("Failed to write rating tag for track_id={id}: {e:?}")
+                }
+                Err(e) => log::error!("Failed to join rating tag task for track_id={id}: {e:?}"),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Error)]
+pub enum LibrarySetAlbumRatingError {
+    #[error("Invalid rating: {0}")]
+    InvalidRating(u8),
+    #[error(transparent)]
+    Db(#[from] DbError),
+}
+
+pub async fn set_album_rating(
+    db: &LibraryDatabase,
+    album_id: &Id,
+    value: db::SetRating,
+) -> Result<(), LibrarySetAlbumRatingError> {
+    let value = validate_rating(value).map_err(LibrarySetAlbumRatingError::InvalidRating)?;
+
+    Ok(db::set_album_rating(db, album_id.into(), value).await?)
+}
+
+#[derive(Debug, Error)]
+pub enum LibrarySetArtistRatingError {
+    #[error("Invalid rating: {0}")]
+    InvalidRating(u8),
+    #[error(transparent)]
+    Db(#[from] DbError),
+}
+
+pub async fn set_artist_rating(
+    db: &LibraryDatabase,
+    artist_id: &Id,
+    value: db::SetRating,
+) -> Result<(), LibrarySetArtistRatingError> {
+    let value = validate_rating(value).map_err(LibrarySetArtistRatingError::InvalidRating)?;
+
+    Ok(db::set_artist_rating(db, artist_id.into(), value).await?)
+}
+
 #[derive(Debug, Error)]
 pub enum LibraryArtistAlbumsError {
     #[error("Request failed: {0:?}")]
@@ -1143,6 +1330,7 @@ impl MusicApi for LibraryMusicApi {
             limit,
             order.map(|x| x.into()),
             order_direction.map(|x| x.into()),
+            None,
         )
         .await?
         .map(|x| x.into()))
@@ -1203,6 +1391,8 @@ impl MusicApi for LibraryMusicApi {
             format: track.format.unwrap_or(AudioFormat::Source),
             track_id: Some(track.id.to_owned()),
             source: track.source,
+            start_offset: track.start_offset,
+            end_offset: track.end_offset,
         }))
     }
 
@@ -1217,6 +1407,11 @@ impl MusicApi for LibraryMusicApi {
             track.id()
         );
 
+        // The size of a virtual track is only known once it has been encoded
+        if source.time_range().is_some() {
+            return Ok(None);
+        }
+
         if let Some(size) = db::get_track_size(&self.db, track.id(), &quality)
             .await
             .map_err(|e| TrackError::Other(Box::new(e)))?
@@ -1303,6 +1498,169 @@ impl MusicApi for LibraryMusicApi {
     }
 }
 
+/// The minimum fingerprint similarity for two tracks to be considered the
+/// same recording.
+pub const DEFAULT_DUPLICATE_MIN_SIMILARITY: f64 = 0.8;
+
+/// The maximum difference in duration, in seconds, between two copies of the
+/// same recording. Different rips can have slightly different amounts of
+/// silence at the start and end of a track.
+const DUPLICATE_MAX_DURATION_DIFFERENCE: f64 = 3.0;
+
+#[derive(Debug, Clone)]
+pub struct DuplicateTrackGroup {
+    /// The highest quality copy of the recording, which is the one to keep
+    pub keep: LibraryTrack,
+    /// The other copies of the recording, best quality first
+    pub duplicates: Vec<LibraryTrack>,
+    /// The lowest similarity between any two linked copies in the group
+    pub similarity: f64,
+}
+
+#[derive(Debug, Error)]
+pub enum LibraryDuplicateTracksError {
+    #[error(transparent)]
+    Db(#[from] DbError),
+}
+
+pub async fn duplicate_tracks(
+    db: &LibraryDatabase,
+    min_similarity: Option<f64>,
+) -> Result<Vec<DuplicateTrackGroup>, LibraryDuplicateTracksError> {
+    let mut fingerprints = db::get_track_fingerprints(db)
+        .await?
+        .into_iter()
+        .filter_map(|x| match fingerprint::decode(&x.fingerprint) {
+            Ok(data) => Some((x.track_id, data)),
+            Err(e) => {
+                log::warn!("Invalid fingerprint for track_id={}: {e:?}", x.track_id);
+                None
+            }
+        })
+        .collect::<HashMap<_, _>>();
+
+    let ids = fingerprints
+        .keys()
+        .map(|id| (*id).into())
+        .collect::<Vec<Id>>();
+
+    let tracks = db::get_tracks(db, Some(&ids))
+        .await?
+        .into_iter()
+        .filter_map(|track| fingerprints.remove(&track.id).map(|data| (track, data)))
+        .collect::<Vec<_>>();
+
+    Ok(group_duplicate_tracks(
+        tracks,
+        min_similarity.unwrap_or(DEFAULT_DUPLICATE_MIN_SIMILARITY),
+    ))
+}
+
+/// Groups the tracks whose fingerprints match into sets of copies of the same
+/// recording. Only tracks with a similar duration are compared.
+#[must_use]
+pub fn group_duplicate_tracks(
+    mut tracks: Vec<(LibraryTrack, Vec<u32>)>,
+    min_similarity: f64,
+) -> Vec<DuplicateTrackGroup> {
+    fn find(parents: &mut [usize], i: usize) -> usize {
+        let mut root = i;
+        while parents[root] != root {
+            root = parents[root];
+        }
+        parents[i] = root;
+        root
+    }
+
+    tracks.sort_by(|a, b| a.0.duration.total_cmp(&b.0.duration));
+
+    let mut parents = (0..tracks.len()).collect::<Vec<_>>();
+    let mut similarities = vec![1.0_f64; tracks.len()];
+
+    for i in 0..tracks.len() {
+        for j in (i + 1)..tracks.len() {
+            if tracks[j].0.duration - tracks[i].0.duration > DUPLICATE_MAX_DURATION_DIFFERENCE {
+                break;
+            }
+
+            let similarity = fingerprint::similarity(&tracks[i].1, &tracks[j].1);
+
+            if similarity < min_similarity {
+                continue;
+            }
+
+            let a = find(&mut parents, i);
+            let b = find(&mut parents, j);
+
+            if a != b {
+                parents[b] = a;
+                similarities[a] = similarities[a].min(similarities[b]);
+            }
+
+            similarities[a] = similarities[a].min(similarity);
+        }
+    }
+
+    let mut groups: HashMap<usize, Vec<LibraryTrack>> = HashMap::new();
+
+    for (i, (track, _)) in tracks.into_iter().enumerate() {
+        let root = find(&mut parents, i);
+        groups.entry(root).or_default().push(track);
+    }
+
+    let mut groups = groups
+        .into_iter()
+        .filter(|(_, tracks)| tracks.len() > 1)
+        .collect::<Vec<_>>();
+
+    // Roots are indices into the tracks sorted by duration, so this keeps the
+    // groups in a stable order
+    groups.sort_unstable_by_key(|(root, _)| *root);
+
+    groups
+        .into_iter()
+        .map(|(root, mut tracks)| {
+            sort_tracks_by_quality(&mut tracks);
+            let keep = tracks.remove(0);
+
+            DuplicateTrackGroup {
+                keep,
+                duplicates: tracks,
+                similarity: similarities[root],
+            }
+        })
+        .collect()
+}
+
+/// Sorts the tracks by the same quality ordering used for the album versions,
+/// falling back to the file size.
+fn sort_tracks_by_quality(tracks: &mut [LibraryTrack]) {
+    use moosicbox_core::sqlite::models::AlbumVersionQuality;
+
+    fn quality(track: &LibraryTrack) -> AlbumVersionQuality {
+        AlbumVersionQuality {
+            format: track.format,
+            bit_depth: track.bit_depth,
+            sample_rate: track.sample_rate,
+            channels: track.channels,
+            source: track.source,
+        }
+    }
+
+    let mut versions = tracks.iter().map(quality).collect::<Vec<_>>();
+    models::sort_album_versions(&mut versions);
+
+    let rank = |track: &LibraryTrack| {
+        let quality = quality(track);
+        versions
+            .iter()
+            .position(|x| *x == quality)
+            .unwrap_or(usize::MAX)
+    };
+
+    tracks.sort_by(|a, b| rank(a).cmp(&rank(b)).then_with(|| b.bytes.cmp(&a.bytes)));
+}
+
 #[derive(Debug, Error)]
 pub enum ReindexError {
     #[error(transparent)]
@@ -1476,6 +1834,9 @@ mod test {
                     artist_id: None,
                     tidal_artist_id: None,
                     qobuz_artist_id: None,
+                    min_rating: None,
+                    loved: None,
+                    banned: None,
                 }),
                 page: Some(PagingRequest {
                     offset: 0,
@@ -1527,6 +1888,9 @@ mod test {
                     artist_id: None,
                     tidal_artist_id: None,
                     qobuz_artist_id: None,
+                    min_rating: None,
+                    loved: None,
+                    banned: None,
                 }),
                 page: Some(PagingRequest {
                     offset: 0,
@@ -1578,6 +1942,9 @@ mod test {
                     artist_id: None,
                     tidal_artist_id: None,
                     qobuz_artist_id: None,
+                    min_rating: None,
+                    loved: None,
+                    banned: None,
                 }),
                 page: Some(PagingRequest {
                     offset: 0,
@@ -1629,6 +1996,9 @@ mod test {
                     artist_id: None,
                     tidal_artist_id: None,
                     qobuz_artist_id: None,
+                    min_rating: None,
+                    loved: None,
+                    banned: None,
                 }),
                 page: Some(PagingRequest {
                     offset: 0,
@@ -1680,6 +2050,9 @@ mod test {
                     artist_id: None,
                     tidal_artist_id: None,
                     qobuz_artist_id: None,
+                    min_rating: None,
+                    loved: None,
+                    banned: None,
                 }),
                 page: Some(PagingRequest {
                     offset: 0,
@@ -1731,6 +2104,9 @@ mod test {
                     artist_id: None,
                     tidal_artist_id: None,
                     qobuz_artist_id: None,
+                    min_rating: None,
+                    loved: None,
+                    banned: None,
                 }),
                 page: Some(PagingRequest {
                     offset: 0,
@@ -1782,6 +2158,9 @@ mod test {
                     artist_id: None,
                     tidal_artist_id: None,
                     qobuz_artist_id: None,
+                    min_rating: None,
+                    loved: None,
+                    banned: None,
                 }),
                 page: Some(PagingRequest {
                     offset: 0,
@@ -1833,6 +2212,9 @@ mod test {
                     artist_id: None,
                     tidal_artist_id: None,
                     qobuz_artist_id: None,
+                    min_rating: None,
+                    loved: None,
+                    banned: None,
                 }),
                 page: Some(PagingRequest {
                     offset: 0,
@@ -1884,6 +2266,9 @@ mod test {
                     artist_id: None,
                     tidal_artist_id: None,
                     qobuz_artist_id: None,
+                    min_rating: None,
+                    loved: None,
+                    banned: None,
                 }),
                 page: Some(PagingRequest {
                     offset: 0,
@@ -1895,4 +2280,173 @@ mod test {
         .collect::<Vec<_>>();
         assert_eq!(result, vec![bob, test]);
     }
+
+    #[test]
+    fn filter_albums_filters_albums_below_min_rating() {
+        let unrated = LibraryAlbum {
+            id: 1,
+            title: "unrated".to_string(),
+            source: AlbumSource::Local,
+            ..Default::default()
+        };
+        let low = LibraryAlbum {
+            id: 2,
+            title: "low".to_string(),
+            source: AlbumSource::Local,
+            rating: Some(2),
+            ..Default::default()
+        };
+        let high = LibraryAlbum {
+            id: 3,
+            title: "high".to_string(),
+            source: AlbumSource::Local,
+            rating: Some(4),
+            ..Default::default()
+        };
+        let albums = vec![unrated, low, high.clone()];
+        let result = filter_albums(
+            &albums,
+            &AlbumsRequest {
+                sources: None,
+                sort: None,
+                filters: Some(AlbumFilters {
+                    name: None,
+                    artist: None,
+                    search: None,
+                    artist_id: None,
+                    tidal_artist_id: None,
+                    qobuz_artist_id: None,
+                    min_rating: Some(3),
+                    loved: None,
+                    banned: None,
+                }),
+                page: None,
+            },
+        )
+        .cloned()
+        .collect::<Vec<_>>();
+        assert_eq!(result, vec![high]);
+    }
+
+    #[test]
+    fn sort_albums_by_rating_desc_puts_unrated_albums_last() {
+        let unrated = LibraryAlbum {
+            id: 1,
+            title: "unrated".to_string(),
+            source: AlbumSource::Local,
+            ..Default::default()
+        };
+        let low = LibraryAlbum {
+            id: 2,
+            title: "low".to_string(),
+            source: AlbumSource::Local,
+            rating: Some(2),
+            ..Default::default()
+        };
+        let high = LibraryAlbum {
+            id: 3,
+            title: "high".to_string(),
+            source: AlbumSource::Local,
+            rating: Some(5),
+            ..Default::default()
+        };
+        let request = AlbumsRequest {
+            sources: None,
+            sort: Some(AlbumSort::RatingDesc),
+            filters: None,
+            page: None,
+        };
+        let result = sort_albums(vec![&unrated, &low, &high], &request);
+        assert_eq!(result, vec![&high, &low, &unrated]);
+    }
+
+    #[test]
+    fn group_duplicate_tracks_keeps_the_highest_quality_copy() {
+        use moosicbox_core::types::AudioFormat;
+
+        let fingerprint = |seed: u32| {
+            (0..200_u32)
+                .scan(seed, |state, _| {
+                    *state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                    Some(*state)
+                })
+                .collect::<Vec<_>>()
+        };
+        let flac = LibraryTrack {
+            id: 1,
+            duration: 200.0,
+            format: Some(AudioFormat::Flac),
+            bit_depth: Some(24),
+            sample_rate: Some(96000),
+            source: TrackApiSource::Local,
+            ..Default::default()
+        };
+        let mp3 = LibraryTrack {
+            id: 2,
+            duration: 201.5,
+            format: Some(AudioFormat::Mp3),
+            sample_rate: Some(44100),
+            source: TrackApiSource::Local,
+            ..Default::default()
+        };
+        let other = LibraryTrack {
+            id: 3,
+            duration: 200.5,
+            format: Some(AudioFormat::Flac),
+            bit_depth: Some(16),
+            sample_rate: Some(44100),
+            source: TrackApiSource::Local,
+            ..Default::default()
+        };
+
+        let groups = group_duplicate_tracks(
+            vec![
+                (mp3.clone(), fingerprint(1)),
+                (other, fingerprint(2)),
+                (flac.clone(), fingerprint(1)),
+            ],
+            DEFAULT_DUPLICATE_MIN_SIMILARITY,
+        );
+
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].keep, flac);
+        assert_eq!(groups[0].duplicates, vec![mp3]);
+        assert_eq!(groups[0].similarity, 1.0);
+    }
+
+    #[test]
+    fn group_duplicate_tracks_returns_each_group_once_in_duration_order() {
+        let fingerprint = |seed: u32| (0..200_u32).map(|x| x ^ seed).collect::<Vec<_>>();
+        let track = |id: u64, duration: f64| LibraryTrack {
+            id,
+            duration,
+            ..Default::default()
+        };
+
+        let groups = group_duplicate_tracks(
+            vec![
+                (track(1, 300.0), fingerprint(0xffff_0000)),
+                (track(2, 100.0), fingerprint(0x0000_ffff)),
+                (track(3, 300.5), fingerprint(0xffff_0000)),
+                (track(4, 100.5), fingerprint(0x0000_ffff)),
+                (track(5, 200.0), fingerprint(0x00ff_ff00)),
+            ],
+            DEFAULT_DUPLICATE_MIN_SIMILARITY,
+        );
+
+        assert_eq!(
+            groups
+                .iter()
+                .map(|x| {
+                    let mut ids = std::iter::once(&x.keep)
+                        .chain(&x.duplicates)
+                        .map(|x| x.id)
+                        .collect::<Vec<_>>();
+                    ids.sort_unstable();
+                    ids
+                })
+                .collect::<Vec<_>>(),
+            vec![vec![2, 4], vec![1, 3]]
+        );
+    }
 }
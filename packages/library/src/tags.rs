@@ -0,0 +1,202 @@
+use std::path::Path;
+
+use moosicbox_lofty::{
+    id3::v2::Popularimeter, AudioFile as _, ItemKey, ItemValue, ParseOptions, Probe, Tag, TagItem,
+    TagType, TaggedFileExt as _,
+};
+use thiserror::Error;
+
+/// The email stored in the POPM frames written by MoosicBox.
+pub const POPM_EMAIL: &str = "MoosicBox";
+
+const FMPS_RATING_KEY: &str = "FMPS_RATING";
+
+#[derive(Debug, Error)]
+pub enum TagError {
+    #[error(transparent)]
+    Lofty(#[from] moosicbox_lofty::LoftyError),
+    #[error("Invalid rating: {0}")]
+    InvalidRating(u8),
+}
+
+/// Maps a POPM rating byte (1-255, 0 being unknown) to a 1-5 star rating
+/// using the same ranges as Windows Media Player and foobar2000.
+#[must_use]
+pub fn popm_to_stars(value: u8) -> Option<u8> {
+    match value {
+        0 => None,
+        1..=31 => Some(1),
+        32..=95 => Some(2),
+        96..=159 => Some(3),
+        160..=223 => Some(4),
+        224..=255 => Some(5),
+    }
+}
+
+#[must_use]
+pub fn stars_to_popm(stars: u8) -> u8 {
+    match stars {
+        0 => 0,
+        1 => 1,
+        2 => 64,
+        3 => 128,
+        4 => 196,
+        _ => 255,
+    }
+}
+
+fn percent_to_stars(value: f64) -> Option<u8> {
+    if value <= 0.0 {
+        return None;
+    }
+
+    Some(((value / 20.0).ceil() as u8).clamp(1, 5))
+}
+
+/// Reads a 1-5 star rating from a tag. ID3v2 POPM frames, textual
+/// `RATING`/`rate` values (0-100) and `FMPS_RATING` values (0.0-1.0) are
+/// supported, in that order of precedence.
+#[must_use]
+pub fn read_rating(tag: &Tag) -> Option<u8> {
+    if let Some(mut bytes) = tag.get_binary(&ItemKey::Popularimeter, false) {
+        if let Ok(popm) = Popularimeter::parse(&mut bytes) {
+            return popm_to_stars(popm.rating);
+        }
+    }
+
+    if let Some(value) = tag
+        .get_string(&ItemKey::Popularimeter)
+        .and_then(|x| x.trim().parse::<f64>().ok())
+    {
+        return percent_to_stars(value);
+    }
+
+    tag.get_string(&ItemKey::Unknown(FMPS_RATING_KEY.to_string()))
+        .and_then(|x| x.trim().parse::<f64>().ok())
+        .and_then(|x| percent_to_stars((x * 100.0).round()))
+}
+
+/// Whether the tag marks the track as part of a compilation (ID3v2 `TCMP`,
+/// Vorbis `COMPILATION` or MP4 `cpil`).
+#[must_use]
+pub fn read_compilation(tag: &Tag) -> bool {
+    tag.get_string(&ItemKey::FlagCompilation)
+        .map(str::trim)
+        .is_some_and(|x| x == "1" || x.eq_ignore_ascii_case("true"))
+}
+
+pub fn read_rating_from_path(path: impl AsRef<Path>) -> Result<Option<u8>, TagError> {
+    let file = Probe::open(path)?
+        .options(ParseOptions::new().read_picture(false))
+        .read()?;
+
+    Ok(file.tags().iter().find_map(read_rating))
+}
+
+/// Writes the star rating to the primary tag of the file at `path`. A
+/// `None` rating removes any existing rating from the tag.
+pub fn write_rating_to_path(path: impl AsRef<Path>, rating: Option<u8>) -> Result<(), TagError> {
+    if let Some(rating) = rating {
+        if rating > 5 {
+            return Err(TagError::InvalidRating(rating));
+        }
+    }
+
+    let path = path.as_ref();
+    let mut file = Probe::open(path)?
+        .options(ParseOptions::new().read_picture(false))
+        .read()?;
+
+    if file.primary_tag_mut().is_none() {
+        let tag_type = file.primary_tag_type();
+        file.insert_tag(Tag::new(tag_type));
+    }
+
+    write_rating(file.primary_tag_mut().unwrap(), rating);
+
+    file.save_to_path(path)?;
+
+    Ok(())
+}
+
+/// Replaces the rating of the tag. ID3v2 tags get a POPM frame, and other
+/// tags get both a textual `RATING` (0-100) and an `FMPS_RATING` (0.0-1.0).
+fn write_rating(tag: &mut Tag, rating: Option<u8>) {
+    let fmps_key = ItemKey::Unknown(FMPS_RATING_KEY.to_string());
+
+    tag.remove_key(&ItemKey::Popularimeter);
+    if tag.tag_type() != TagType::Id3v2 {
+        tag.remove_key(&fmps_key);
+    }
+
+    if let Some(rating) = rating.filter(|x| *x > 0) {
+        if tag.tag_type() == TagType::Id3v2 {
+            let popm = Popularimeter {
+                email: POPM_EMAIL.to_string(),
+                rating: stars_to_popm(rating),
+                counter: 0,
+            };
+            tag.insert(TagItem::new(
+                ItemKey::Popularimeter,
+                ItemValue::Binary(popm.as_bytes()),
+            ));
+        } else {
+            tag.insert_text(ItemKey::Popularimeter, (u32::from(rating) * 20).to_string());
+            // Unknown keys are only kept when inserted unchecked
+            tag.insert_unchecked(TagItem::new(
+                fmps_key,
+                ItemValue::Text(format!("{:.1}", f32::from(rating) / 5.0)),
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test_log::test]
+    fn popm_round_trips_every_star_rating() {
+        for stars in 1..=5 {
+            assert_eq!(popm_to_stars(stars_to_popm(stars)), Some(stars));
+        }
+
+        assert_eq!(popm_to_stars(stars_to_popm(0)), None);
+    }
+
+    #[test_log::test]
+    fn id3v2_ratings_round_trip_through_popm() {
+        let mut tag = Tag::new(TagType::Id3v2);
+
+        for stars in 1..=5 {
+            write_rating(&mut tag, Some(stars));
+
+            assert_eq!(read_rating(&tag), Some(stars));
+        }
+
+        write_rating(&mut tag, None);
+
+        assert_eq!(read_rating(&tag), None);
+    }
+
+    #[test_log::test]
+    fn vorbis_ratings_round_trip_through_fmps() {
+        let mut tag = Tag::new(TagType::VorbisComments);
+
+        for stars in 1..=5 {
+            write_rating(&mut tag, Some(stars));
+
+            assert_eq!(read_rating(&tag), Some(stars));
+
+            tag.remove_key(&ItemKey::Popularimeter);
+
+            assert_eq!(read_rating(&tag), Some(stars));
+        }
+
+        write_rating(&mut tag, Some(0));
+
+        assert_eq!(read_rating(&tag), None);
+    }
+}
@@ -14,10 +14,16 @@ pub enum RegisterServiceError {
     IO(#[from] std::io::Error),
 }
 
+/// The TXT record property with the base64 encoded public key that the
+/// server signs its tunnel E2E handshakes with. Clients pin it when they
+/// discover the server.
+pub const SIGNING_KEY_PROPERTY: &str = "signing_key";
+
 pub fn register_service(
     instance_name: &str,
     ip: &str,
     port: u16,
+    properties: &[(&str, &str)],
 ) -> Result<(), RegisterServiceError> {
     let mdns = ServiceDaemon::new()?;
     let service_type = "_moosicboxserver._tcp.local.";
@@ -30,7 +36,14 @@ pub fn register_service(
 
     log::debug!("register_service: Registering mdns service service_type={service_type} instance_name={instance_name} host_name={host_name} ip={ip} port={port}");
 
-    let service_info = ServiceInfo::new(service_type, instance_name, &host_name, ip, port, None)?;
+    let service_info = ServiceInfo::new(
+        service_type,
+        instance_name,
+        &host_name,
+        ip,
+        port,
+        properties,
+    )?;
 
     mdns.register(service_info)?;
 
@@ -11,6 +11,8 @@ pub struct MoosicBox {
     pub name: String,
     pub host: SocketAddr,
     pub dns: String,
+    /// The public key the server signs its tunnel E2E handshakes with
+    pub signing_key: Option<String>,
 }
 
 #[derive(Debug, Error)]
@@ -93,6 +95,9 @@ impl service::Processor for service::Service {
                                 name: info.get_hostname().to_string(),
                                 host: socket_addr,
                                 dns,
+                                signing_key: info
+                                    .get_property_val_str(crate::SIGNING_KEY_PROPERTY)
+                                    .map(ToString::to_string),
                             };
 
                             moosicbox_assert::die_or_propagate!(tx.send(server).await);
@@ -30,7 +30,7 @@ use thiserror::Error;
 use crate::library::{
     albums::{add_album, get_album_versions, refavorite_album, remove_album, ApiAlbumVersion},
     artists::{get_all_artists, ArtistFilters, ArtistsRequest},
-    get_album, get_artist, get_artist_albums, GetArtistError,
+    get_album, get_artist, get_artist_albums, get_artist_appears_on_albums, GetArtistError,
 };
 
 pub fn bind_services<
@@ -50,6 +50,7 @@ pub fn bind_services<
         .service(get_album_tracks_endpoint)
         .service(get_album_versions_endpoint)
         .service(get_artist_albums_endpoint)
+        .service(get_artist_appears_on_albums_endpoint)
 }
 
 #[cfg(feature = "openapi")]
@@ -63,6 +64,7 @@ pub fn bind_services<
         get_album_tracks_endpoint,
         get_album_versions_endpoint,
         get_artist_albums_endpoint,
+        get_artist_appears_on_albums_endpoint,
         get_artist_endpoint,
         get_album_endpoint,
         add_album_endpoint,
@@ -190,6 +192,9 @@ pub struct GetAlbumsQuery {
     artist_id: Option<i32>,
     tidal_artist_id: Option<u64>,
     qobuz_artist_id: Option<u64>,
+    min_rating: Option<u8>,
+    loved: Option<bool>,
+    banned: Option<bool>,
     offset: Option<u32>,
     limit: Option<u32>,
 }
@@ -209,6 +214,9 @@ pub struct GetAlbumsQuery {
             ("artistId" = Option<i32>, Query, description = "Artist ID to filter by"),
             ("tidalArtistId" = Option<i32>, Query, description = "Tidal artist ID to filter by"),
             ("qobuzArtistId" = Option<i32>, Query, description = "Qobuz artist ID to filter by"),
+            ("minRating" = Option<u8>, Query, description = "Minimum star rating (1-5) to filter by"),
+            ("loved" = Option<bool>, Query, description = "Loved flag to filter by"),
+            ("banned" = Option<bool>, Query, description = "Banned flag to filter by"),
             ("offset" = Option<u32>, Query, description = "Page offset"),
             ("limit" = Option<u32>, Query, description = "Page limit"),
         ),
@@ -264,6 +272,9 @@ pub async fn get_albums_endpoint(
             artist_id: query.artist_id.map(|x| x.into()),
             tidal_artist_id: query.tidal_artist_id.map(|x| x.into()),
             qobuz_artist_id: query.qobuz_artist_id.map(|x| x.into()),
+            min_rating: query.min_rating,
+            loved: query.loved,
+            banned: query.banned,
         }),
     };
 
@@ -445,6 +456,45 @@ pub async fn get_artist_albums_endpoint(
     ))
 }
 
+#[derive(Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct GetArtistAppearsOnAlbumsQuery {
+    artist_id: i32,
+}
+
+#[cfg_attr(
+    feature = "openapi", utoipa::path(
+        tags = ["Menu"],
+        get,
+        path = "/artist/appears-on",
+        description = "Get the albums by other artists (e.g. compilations) that the specified artist appears on",
+        params(
+            ("artistId" = String, Query, description = "Artist ID to fetch the albums for"),
+        ),
+        responses(
+            (
+                status = 200,
+                description = "The list of albums",
+                body = Vec<ApiAlbum>,
+            )
+        )
+    )
+)]
+#[get("/artist/appears-on")]
+pub async fn get_artist_appears_on_albums_endpoint(
+    query: web::Query<GetArtistAppearsOnAlbumsQuery>,
+    db: LibraryDatabase,
+) -> Result<Json<Vec<ApiAlbum>>> {
+    Ok(Json(
+        get_artist_appears_on_albums(&query.artist_id.into(), &db)
+            .await
+            .map_err(|_e| ErrorInternalServerError("Failed to fetch albums"))?
+            .iter()
+            .map(|t| t.to_api())
+            .collect(),
+    ))
+}
+
 impl From<GetArtistError> for actix_web::Error {
     fn from(e: GetArtistError) -> Self {
         match e {
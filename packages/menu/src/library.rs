@@ -276,3 +276,24 @@ pub async fn get_artist_albums(
     .into_artist_albums()
     .unwrap())
 }
+
+/// Albums by other album artists (e.g. compilations) that the artist
+/// appears on.
+pub async fn get_artist_appears_on_albums(
+    artist_id: &Id,
+    db: &LibraryDatabase,
+) -> Result<Arc<Vec<LibraryAlbum>>, GetArtistAlbumsError> {
+    let request = CacheRequest {
+        key: &format!("sqlite|local_artist_appears_on_albums|{artist_id}"),
+        expiration: Duration::from_secs(5 * 60),
+    };
+
+    Ok(get_or_set_to_cache(request, || async {
+        Ok::<CacheItemType, GetArtistAlbumsError>(CacheItemType::ArtistAlbums(Arc::new(
+            db::get_artist_appears_on_albums(db, artist_id).await?,
+        )))
+    })
+    .await?
+    .into_artist_albums()
+    .unwrap())
+}
@@ -77,6 +77,9 @@ pub struct AlbumFilters {
     pub artist_id: Option<Id>,
     pub tidal_artist_id: Option<Id>,
     pub qobuz_artist_id: Option<Id>,
+    pub min_rating: Option<u8>,
+    pub loved: Option<bool>,
+    pub banned: Option<bool>,
 }
 
 #[derive(Debug, Serialize, Deserialize, EnumString, AsRefStr, PartialEq, Clone, Copy)]
@@ -144,6 +147,12 @@ pub enum TrackSource {
         format: AudioFormat,
         track_id: Option<Id>,
         source: TrackApiSource,
+        /// The offset in seconds of the start of the track within the file, for virtual tracks
+        /// such as the tracks of a cue sheet album rip
+        start_offset: Option<f64>,
+        /// The offset in seconds of the end of the track within the file. Runs to the end of the
+        /// file if missing
+        end_offset: Option<f64>,
     },
     RemoteUrl {
         url: String,
@@ -167,6 +176,25 @@ impl TrackSource {
             TrackSource::RemoteUrl { track_id, .. } => track_id.as_ref(),
         }
     }
+
+    /// The `(start, end)` offsets in seconds of a virtual track within its file, if the track
+    /// doesn't span the whole file.
+    pub fn time_range(&self) -> Option<(f64, Option<f64>)> {
+        match self {
+            TrackSource::LocalFilePath {
+                start_offset,
+                end_offset,
+                ..
+            } => {
+                if start_offset.is_none() && end_offset.is_none() {
+                    None
+                } else {
+                    Some((start_offset.unwrap_or(0.0), *end_offset))
+                }
+            }
+            TrackSource::RemoteUrl { .. } => None,
+        }
+    }
 }
 
 #[derive(Debug, Default, Serialize, Deserialize, EnumString, AsRefStr, PartialEq, Clone, Copy)]
@@ -25,7 +25,7 @@ use moosicbox_core::{
     },
     types::{AudioFormat, PlaybackQuality},
 };
-use moosicbox_database::profiles::LibraryDatabase;
+use moosicbox_database::profiles::{LibraryDatabase, PROFILES};
 use moosicbox_json_utils::{serde_json::ToValue as _, ParseError};
 use moosicbox_music_api::MusicApi;
 use moosicbox_session::{
@@ -139,6 +139,7 @@ impl std::fmt::Debug for PlayableTrack {
         f.debug_struct("PlayableTrack")
             .field("track_id", &self.track_id)
             .field("source", &"{{source}}")
+            .field("time_range", &self.time_range)
             .finish()
     }
 }
@@ -219,6 +220,42 @@ pub struct PlaybackStatus {
     pub success: bool,
 }
 
+/// Finds the first position at or after `from` whose track isn't banned in
+/// the playback profile's library. Tracks from other sources are never
+/// skipped.
+async fn next_unbanned_position(playback: &Playback, from: u16) -> Option<u16> {
+    let is_library_track = |track: &Track| track.source == ApiSource::Library;
+    let remaining = playback.tracks.iter().enumerate().skip(from as usize);
+
+    let track_ids = remaining
+        .clone()
+        .filter(|(_, track)| is_library_track(track))
+        .filter_map(|(_, track)| match track.id {
+            Id::Number(id) => Some(id),
+            Id::String(_) => None,
+        })
+        .collect::<Vec<_>>();
+
+    let banned = match PROFILES.get(&playback.profile) {
+        Some(db) if !track_ids.is_empty() => {
+            moosicbox_library::db::get_banned_track_ids(&db, &track_ids)
+                .await
+                .unwrap_or_else(|e| {
+                    log::error!("Failed to get banned tracks: {e:?}");
+                    vec![]
+                })
+        }
+        _ => vec![],
+    };
+
+    remaining
+        .filter(|(_, track)| {
+            !(is_library_track(track) && matches!(track.id, Id::Number(id) if banned.contains(&id)))
+        })
+        .map(|(position, _)| position as u16)
+        .next()
+}
+
 #[derive(Debug, Clone)]
 pub struct Track {
     pub id: Id,
@@ -423,6 +460,9 @@ pub struct PlayableTrack {
     pub track_id: Id,
     pub source: Box<dyn MediaSource>,
     pub hint: Hint,
+    /// The `(start, end)` offsets in seconds of the track within the source, for virtual tracks
+    /// such as the tracks of a cue sheet album rip
+    pub time_range: Option<(f64, Option<f64>)>,
 }
 
 #[derive(Copy, Clone, Default, Deserialize, Serialize, Debug)]
@@ -800,13 +840,15 @@ impl PlaybackHandler {
                     break;
                 }
 
-                if ((playback.position + 1) as usize) >= playback.tracks.len() {
+                let Some(next_position) =
+                    next_unbanned_position(&playback, playback.position + 1).await
+                else {
                     log::debug!("Playback position at end of tracks. Breaking");
                     break;
-                }
+                };
 
                 let old = playback.clone();
-                playback.position += 1;
+                playback.position = next_position;
                 playback.progress = 0.0;
                 player.playback.write().unwrap().replace(playback.clone());
                 trigger_playback_event(&playback, &old);
@@ -907,16 +949,17 @@ impl PlaybackHandler {
                 .ok_or(PlayerError::NoPlayersPlaying)?
         };
 
-        if playback.position + 1 >= playback.tracks.len() as u16 {
+        let Some(next_position) = next_unbanned_position(&playback, playback.position + 1).await
+        else {
             return Err(PlayerError::PositionOutOfBounds(playback.position + 1));
-        }
+        };
 
         self.update_playback(
             true,
             Some(true),
             None,
             None,
-            Some(playback.position + 1),
+            Some(next_position),
             seek,
             None,
             None,
@@ -1342,7 +1385,15 @@ async fn track_to_playable_file(
         }
     }
 
+    let time_range = match (track.start_offset, track.end_offset) {
+        (None, None) => None,
+        (start, end) => Some((start.unwrap_or(0.0), end)),
+    };
+
+    // Virtual tracks are played straight from their file, since they are cut
+    // out of it by seeking into it
     let same_source = match quality.format {
+        _ if time_range.is_some() => true,
         AudioFormat::Source => true,
         #[allow(unreachable_patterns)]
         _ => match track.format {
@@ -1442,6 +1493,7 @@ async fn track_to_playable_file(
         track_id: track.id.to_owned(),
         source,
         hint,
+        time_range,
     })
 }
 
@@ -1510,6 +1562,7 @@ async fn track_id_to_playable_stream(
         track_id: track_id.to_owned(),
         source: Box::new(source),
         hint,
+        time_range: None,
     })
 }
 
@@ -1609,3 +1662,75 @@ pub fn send_playback_event(update: &UpdateSession, playback: &Playback) {
         listener(update, playback);
     }
 }
+
+#[cfg(test)]
+mod test {
+    use moosicbox_library::db::{set_track_rating, SetRating};
+    use moosicbox_schema::fixtures;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn new_playback(profile: &str, tracks: Vec<Track>) -> Playback {
+        Playback::new(
+            tracks,
+            None,
+            AtomicF64::new(1.0),
+            PlaybackQuality::default(),
+            1,
+            profile.to_string(),
+            None,
+        )
+    }
+
+    fn track(id: u64, source: ApiSource) -> Track {
+        Track {
+            id: Id::Number(id),
+            source,
+            data: None,
+        }
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn next_unbanned_position_skips_banned_library_tracks() {
+        let db = fixtures::library_db().await;
+        let library = fixtures::seed_library(&**db.database).await.unwrap();
+        let [first, second, third, ..] = library.tracks[..] else {
+            panic!("Expected at least three fixture tracks");
+        };
+
+        let banned = SetRating {
+            banned: Some(true),
+            ..SetRating::default()
+        };
+        set_track_rating(&db, first, banned).await.unwrap();
+        set_track_rating(&db, second, banned).await.unwrap();
+
+        let profile = "next_unbanned_position_skips_banned_library_tracks";
+        PROFILES.add(profile.to_string(), db.database.clone());
+
+        let playback = new_playback(
+            profile,
+            vec![
+                track(first, ApiSource::Library),
+                track(second, ApiSource::Library),
+                track(third, ApiSource::Library),
+            ],
+        );
+
+        assert_eq!(next_unbanned_position(&playback, 0).await, Some(2));
+        assert_eq!(next_unbanned_position(&playback, 3).await, None);
+
+        let playback = new_playback(
+            profile,
+            vec![
+                track(first, ApiSource::Library),
+                track(first, ApiSource::Tidal),
+            ],
+        );
+
+        assert_eq!(next_unbanned_position(&playback, 0).await, Some(1));
+
+        PROFILES.remove(profile);
+    }
+}
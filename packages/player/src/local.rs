@@ -2,7 +2,7 @@ use std::sync::{atomic::AtomicBool, Arc, Mutex, RwLock};
 
 use async_trait::async_trait;
 use flume::Receiver;
-use moosicbox_audio_decoder::{AudioDecodeError, AudioDecodeHandler};
+use moosicbox_audio_decoder::{time_range_seek, AudioDecodeError, AudioDecodeHandler};
 use moosicbox_audio_output::{AudioOutput, AudioOutputFactory};
 use moosicbox_core::sqlite::models::{ToApi as _, TrackApiSource};
 use moosicbox_session::models::UpdateSession;
@@ -88,6 +88,17 @@ impl Player for LocalPlayer {
         .await?;
         let mss = MediaSourceStream::new(playable_track.source, Default::default());
 
+        // Virtual tracks are played by seeking into their file and stopping at
+        // the end of the track, with the progress relative to its start
+        let time_range = playable_track
+            .time_range
+            .map(|(start, end)| (start + seek.unwrap_or_default(), end));
+        let start_offset = playable_track.time_range.map_or(0.0, |(start, _)| start);
+        let seek = match time_range {
+            Some((start, _)) => time_range_seek(start),
+            None => seek,
+        };
+
         let active_playback = self.playback.clone();
         let sent_playback_start_event = AtomicBool::new(false);
 
@@ -106,7 +117,7 @@ impl Player for LocalPlayer {
                         if let Some(tb) = track.codec_params.time_base {
                             let ts = packet.ts();
                             let t = tb.calc_time(ts);
-                            let secs = f64::from(t.seconds as u32) + t.frac;
+                            let secs = (f64::from(t.seconds as u32) + t.frac - start_offset).max(0.0);
 
                             let mut binding = active_playback.write().unwrap();
                             if let Some(playback) = binding.as_mut() {
@@ -164,6 +175,10 @@ impl Player for LocalPlayer {
                 }))
                 .with_cancellation_token(playback.abort.clone());
 
+            if let Some((start, end)) = time_range {
+                audio_decode_handler = audio_decode_handler.with_time_range(start, end);
+            }
+
             moosicbox_assert::assert_or_err!(
                 audio_decode_handler.contains_outputs_to_open(),
                 crate::symphonia::PlaybackError::NoAudioOutputs,
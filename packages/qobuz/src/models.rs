@@ -414,6 +414,8 @@ impl From<QobuzTrack> for Track {
             artist: value.artist,
             artist_id: value.artist_id.into(),
             file: None,
+            start_offset: None,
+            end_offset: None,
             artwork,
             blur: false,
             bytes: 0,
@@ -27,6 +27,11 @@ pub fn bind_services<
         .service(enable_scan_origin_endpoint)
         .service(disable_scan_origin_endpoint);
 
+    #[cfg(feature = "fingerprint")]
+    let scope = scope
+        .service(run_fingerprint_endpoint)
+        .service(start_fingerprint_endpoint);
+
     #[cfg(feature = "local")]
     let scope = scope
         .service(get_scan_origins_endpoint)
@@ -45,6 +50,8 @@ pub fn bind_services<
     paths(
         run_scan_endpoint,
         start_scan_endpoint,
+        run_fingerprint_endpoint,
+        start_fingerprint_endpoint,
         run_scan_path_endpoint,
         get_scan_origins_endpoint,
         enable_scan_origin_endpoint,
@@ -164,6 +171,67 @@ pub async fn start_scan_endpoint(
     Ok(Json(serde_json::json!({"success": true})))
 }
 
+#[cfg(feature = "fingerprint")]
+#[cfg_attr(
+    feature = "openapi", utoipa::path(
+        tags = ["Scan"],
+        post,
+        path = "/run-fingerprint",
+        description = "Compute the acoustic fingerprints of the local tracks that haven't been fingerprinted yet",
+        params(),
+        responses(
+            (
+                status = 200,
+                description = "The number of tracks that were fingerprinted",
+                body = Value,
+            )
+        )
+    )
+)]
+#[post("/run-fingerprint")]
+pub async fn run_fingerprint_endpoint(
+    db: LibraryDatabase,
+    _: NonTunnelRequestAuthorized,
+) -> Result<Json<Value>> {
+    let count = crate::run_fingerprint(&db)
+        .await
+        .map_err(|e| ErrorInternalServerError(format!("Failed to fingerprint: {e:?}")))?;
+
+    Ok(Json(serde_json::json!({"success": true, "count": count})))
+}
+
+#[cfg(feature = "fingerprint")]
+#[cfg_attr(
+    feature = "openapi", utoipa::path(
+        tags = ["Scan"],
+        post,
+        path = "/start-fingerprint",
+        description = "Start computing the acoustic fingerprints of the local tracks that haven't been fingerprinted yet",
+        params(),
+        responses(
+            (
+                status = 200,
+                description = "The fingerprinting has successfully started",
+                body = Value,
+            )
+        )
+    )
+)]
+#[post("/start-fingerprint")]
+pub async fn start_fingerprint_endpoint(
+    db: LibraryDatabase,
+    _: NonTunnelRequestAuthorized,
+) -> Result<Json<Value>> {
+    moosicbox_task::spawn("scan: fingerprint", async move {
+        crate::run_fingerprint(&db).await.map_err(|e| {
+            log::error!("Fingerprint error: {e:?}");
+            e
+        })
+    });
+
+    Ok(Json(serde_json::json!({"success": true})))
+}
+
 #[cfg(feature = "local")]
 #[derive(Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
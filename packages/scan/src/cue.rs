@@ -0,0 +1,327 @@
+//! Cue sheet parsing for single-file album rips.
+//!
+//! A cue sheet describes the tracks within one or more audio files by their
+//! `INDEX 01` start positions, in `mm:ss:ff` where there are 75 frames per
+//! second. Each track runs until the start of the next track in the same
+//! file, and the last track runs until the end of the file.
+//!
+//! Only the commands needed to split a file into tracks are parsed. Unknown
+//! commands and malformed lines are ignored.
+
+use std::path::Path;
+
+/// The number of cue frames per second.
+const FRAMES_PER_SECOND: f64 = 75.0;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CueSheet {
+    pub title: Option<String>,
+    pub performer: Option<String>,
+    pub date: Option<String>,
+    pub files: Vec<CueFile>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CueFile {
+    /// The name of the file, as written in the cue sheet
+    pub name: String,
+    pub tracks: Vec<CueTrack>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CueTrack {
+    pub number: u32,
+    pub title: Option<String>,
+    pub performer: Option<String>,
+    /// The offset in seconds of the start of the track within its file
+    pub start: f64,
+    /// The offset in seconds of the end of the track within its file, or
+    /// `None` if the track runs until the end of the file
+    pub end: Option<f64>,
+}
+
+impl CueSheet {
+    /// Returns the tracks of the file at `path`. Files are matched by their
+    /// name, ignoring case, and then by their stem since cue sheets often
+    /// still reference the file they were ripped to (e.g. a `.wav`) after it
+    /// was converted.
+    #[must_use]
+    pub fn tracks_for_file(&self, path: &Path) -> Option<&[CueTrack]> {
+        let name = path.file_name()?.to_str()?;
+        let stem = path.file_stem()?.to_str()?;
+
+        self.files
+            .iter()
+            .find(|file| file_name(&file.name).eq_ignore_ascii_case(name))
+            .or_else(|| {
+                self.files.iter().find(|file| {
+                    Path::new(file_name(&file.name))
+                        .file_stem()
+                        .and_then(|x| x.to_str())
+                        .is_some_and(|x| x.eq_ignore_ascii_case(stem))
+                })
+            })
+            .map(|file| file.tracks.as_slice())
+    }
+}
+
+/// The file name of a `FILE` reference, which can be a relative path with
+/// either separator.
+fn file_name(name: &str) -> &str {
+    name.rsplit(['/', '\\']).next().unwrap_or(name)
+}
+
+/// Parses the cue sheet. Tracks without an `INDEX 01` are skipped.
+#[must_use]
+pub fn parse(contents: &str) -> CueSheet {
+    let mut sheet = CueSheet::default();
+    let mut track: Option<CueTrack> = None;
+    let mut start: Option<f64> = None;
+
+    let finish_track = |sheet: &mut CueSheet, track: Option<CueTrack>, start: Option<f64>| {
+        let (Some(track), Some(start), Some(file)) = (track, start, sheet.files.last_mut()) else {
+            return;
+        };
+        file.tracks.push(CueTrack { start, ..track });
+    };
+
+    for line in contents.trim_start_matches('\u{feff}').lines() {
+        let line = line.trim();
+        let (command, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let rest = rest.trim();
+
+        match command.to_uppercase().as_str() {
+            "FILE" => {
+                finish_track(&mut sheet, track.take(), start.take());
+                sheet.files.push(CueFile {
+                    name: file_argument(rest),
+                    tracks: vec![],
+                });
+            }
+            "TRACK" => {
+                finish_track(&mut sheet, track.take(), start.take());
+                let mut args = rest.split_whitespace();
+                let number = args.next().and_then(|x| x.parse::<u32>().ok());
+                let is_audio = args.next().is_none_or(|x| x.eq_ignore_ascii_case("AUDIO"));
+                if let (Some(number), true) = (number, is_audio) {
+                    track = Some(CueTrack {
+                        number,
+                        ..Default::default()
+                    });
+                }
+            }
+            "TITLE" => match &mut track {
+                Some(track) => track.title = Some(unquote(rest)),
+                None => sheet.title = Some(unquote(rest)),
+            },
+            "PERFORMER" => match &mut track {
+                Some(track) => track.performer = Some(unquote(rest)),
+                None => sheet.performer = Some(unquote(rest)),
+            },
+            "INDEX" => {
+                let mut args = rest.split_whitespace();
+                let index = args.next().and_then(|x| x.parse::<u32>().ok());
+                if track.is_some() && index == Some(1) {
+                    start = args.next().and_then(parse_time);
+                }
+            }
+            "REM" => {
+                let (key, value) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                if track.is_none() && key.eq_ignore_ascii_case("DATE") {
+                    sheet.date = Some(unquote(value.trim()));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    finish_track(&mut sheet, track.take(), start.take());
+
+    for file in &mut sheet.files {
+        file.tracks.sort_by(|a, b| a.start.total_cmp(&b.start));
+        let starts = file
+            .tracks
+            .iter()
+            .skip(1)
+            .map(|x| x.start)
+            .collect::<Vec<_>>();
+        for (track, end) in file.tracks.iter_mut().zip(starts) {
+            track.end = Some(end);
+        }
+    }
+
+    sheet
+}
+
+/// Parses a `mm:ss:ff` time to seconds.
+fn parse_time(value: &str) -> Option<f64> {
+    let mut parts = value.split(':').map(|x| x.parse::<u32>().ok());
+    let (Some(Some(minutes)), Some(Some(seconds)), Some(Some(frames)), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return None;
+    };
+
+    Some(f64::from(minutes * 60 + seconds) + f64::from(frames) / FRAMES_PER_SECOND)
+}
+
+/// The file name of a `FILE "name" TYPE` command.
+fn file_argument(value: &str) -> String {
+    if value.starts_with('"') {
+        return unquote(value);
+    }
+
+    // Unquoted names can't contain spaces, but some tools write them anyway,
+    // so only the trailing file type is dropped
+    match value.rsplit_once(char::is_whitespace) {
+        Some((name, _)) => name.trim().to_string(),
+        None => value.to_string(),
+    }
+}
+
+/// The value of a possibly quoted argument.
+fn unquote(value: &str) -> String {
+    value
+        .strip_prefix('"')
+        .and_then(|x| x.split_once('"'))
+        .map_or(value, |(x, _)| x)
+        .to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    const SHEET: &str = "\u{feff}REM GENRE Classical
+REM DATE 1995
+PERFORMER \"Berliner Philharmoniker\"
+TITLE \"Symphony No. 9\"
+FILE \"Symphony No. 9.wav\" WAVE
+  TRACK 01 AUDIO
+    TITLE \"I. Allegro ma non troppo\"
+    INDEX 01 00:00:00
+  TRACK 02 AUDIO
+    TITLE \"II. Molto vivace\"
+    PERFORMER \"Herbert von Karajan\"
+    INDEX 00 15:20:30
+    INDEX 01 15:22:45
+  TRACK 03 AUDIO
+    TITLE \"III. Adagio molto e cantabile\"
+    INDEX 01 26:01:00
+";
+
+    #[test_log::test]
+    fn parses_the_album_and_tracks() {
+        let sheet = parse(SHEET);
+
+        assert_eq!(sheet.title.as_deref(), Some("Symphony No. 9"));
+        assert_eq!(sheet.performer.as_deref(), Some("Berliner Philharmoniker"));
+        assert_eq!(sheet.date.as_deref(), Some("1995"));
+        assert_eq!(
+            sheet.files,
+            vec![CueFile {
+                name: "Symphony No. 9.wav".to_string(),
+                tracks: vec![
+                    CueTrack {
+                        number: 1,
+                        title: Some("I. Allegro ma non troppo".to_string()),
+                        performer: None,
+                        start: 0.0,
+                        end: Some(922.6),
+                    },
+                    CueTrack {
+                        number: 2,
+                        title: Some("II. Molto vivace".to_string()),
+                        performer: Some("Herbert von Karajan".to_string()),
+                        start: 922.6,
+                        end: Some(1561.0),
+                    },
+                    CueTrack {
+                        number: 3,
+                        title: Some("III. Adagio molto e cantabile".to_string()),
+                        performer: None,
+                        start: 1561.0,
+                        end: None,
+                    },
+                ],
+            }]
+        );
+    }
+
+    #[test_log::test]
+    fn ends_tracks_at_the_next_track_of_the_same_file() {
+        let sheet = parse(
+            "FILE disc1.flac WAVE
+TRACK 1 AUDIO
+INDEX 01 00:00:00
+TRACK 2 AUDIO
+INDEX 01 01:00:00
+FILE disc2.flac WAVE
+TRACK 3 AUDIO
+INDEX 01 00:00:00
+",
+        );
+
+        let ends = sheet
+            .files
+            .iter()
+            .map(|file| file.tracks.iter().map(|x| x.end).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+
+        assert_eq!(ends, vec![vec![Some(60.0), None], vec![None]]);
+    }
+
+    #[test_log::test]
+    fn skips_data_tracks_and_tracks_without_a_start() {
+        let sheet = parse(
+            "FILE \"album.flac\" WAVE
+TRACK 01 MODE1/2352
+INDEX 01 00:00:00
+TRACK 02 AUDIO
+INDEX 00 00:10:00
+TRACK 03 AUDIO
+INDEX 01 bad
+TRACK 04 AUDIO
+INDEX 01 00:20:00
+",
+        );
+
+        let numbers = sheet.files[0]
+            .tracks
+            .iter()
+            .map(|x| x.number)
+            .collect::<Vec<_>>();
+
+        assert_eq!(numbers, vec![4]);
+    }
+
+    #[test_log::test]
+    fn matches_files_by_name_then_stem() {
+        let sheet = parse(SHEET);
+
+        assert!(sheet
+            .tracks_for_file(Path::new("/music/Karajan/symphony no. 9.WAV"))
+            .is_some());
+        assert_eq!(
+            sheet
+                .tracks_for_file(Path::new("/music/Karajan/Symphony No. 9.flac"))
+                .map(<[CueTrack]>::len),
+            Some(3)
+        );
+        assert_eq!(
+            sheet.tracks_for_file(Path::new("/music/Karajan/Symphony No. 5.flac")),
+            None
+        );
+    }
+
+    #[test_log::test]
+    fn parses_cue_times() {
+        assert_eq!(parse_time("00:00:00"), Some(0.0));
+        assert_eq!(parse_time("01:02:15"), Some(62.2));
+        assert_eq!(parse_time("74:59:74"), Some(4499.0 + 74.0 / 75.0));
+        assert_eq!(parse_time("01:02"), None);
+        assert_eq!(parse_time("01:02:03:04"), None);
+    }
+}
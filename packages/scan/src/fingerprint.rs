@@ -0,0 +1,177 @@
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+};
+
+use moosicbox_audio_decoder::{
+    decode_file_path_str, time_range_seek, AudioDecode, AudioDecodeError, AudioDecodeHandler,
+    DecodeError,
+};
+use moosicbox_core::sqlite::{db::DbError, models::TrackApiSource};
+use moosicbox_database::profiles::LibraryDatabase;
+use moosicbox_library::{
+    db::{get_track_fingerprints, get_tracks, set_track_fingerprint},
+    fingerprint::{Fingerprint, Fingerprinter},
+};
+use symphonia::core::{
+    audio::{AudioBuffer, Signal as _},
+    formats::{Packet, Track},
+};
+use thiserror::Error;
+use tokio::task::JoinError;
+use tokio_util::sync::CancellationToken;
+
+#[derive(Debug, Error)]
+pub enum FingerprintError {
+    #[error(transparent)]
+    Db(#[from] DbError),
+    #[error(transparent)]
+    Decode(#[from] DecodeError),
+    #[error(transparent)]
+    Join(#[from] JoinError),
+    #[error("No audio was decoded")]
+    NoAudio,
+}
+
+struct FingerprintOutput {
+    fingerprinter: Arc<Mutex<Option<Fingerprinter>>>,
+}
+
+impl AudioDecode for FingerprintOutput {
+    fn decoded(
+        &mut self,
+        decoded: AudioBuffer<f32>,
+        _packet: &Packet,
+        _track: &Track,
+    ) -> Result<(), AudioDecodeError> {
+        let mut binding = self.fingerprinter.lock().unwrap();
+        let Some(fingerprinter) = binding.as_mut() else {
+            return Ok(());
+        };
+
+        let channels = decoded.spec().channels.count();
+
+        if channels == 0 {
+            return Ok(());
+        }
+
+        let mono = (0..decoded.frames())
+            .map(|i| (0..channels).map(|c| decoded.chan(c)[i]).sum::<f32>() / channels as f32)
+            .collect::<Vec<_>>();
+
+        fingerprinter.consume(&mono);
+
+        if fingerprinter.is_full() {
+            // Stops the decoder, the rest of the file doesn't need to be decoded
+            return Err(AudioDecodeError::StreamEnd);
+        }
+
+        Ok(())
+    }
+}
+
+/// Fingerprints the audio of the file at `path`, limited to the `(start, end)`
+/// seconds of `time_range` for virtual tracks within the file.
+pub fn fingerprint_file(
+    path: &str,
+    time_range: Option<(f64, Option<f64>)>,
+) -> Result<Fingerprint, FingerprintError> {
+    let fingerprinter = Arc::new(Mutex::new(None));
+
+    let mut handler = AudioDecodeHandler::new().with_output(Box::new({
+        let fingerprinter = fingerprinter.clone();
+        move |spec, _duration| {
+            fingerprinter
+                .lock()
+                .unwrap()
+                .replace(Fingerprinter::new(spec.rate));
+
+            Ok(Box::new(FingerprintOutput {
+                fingerprinter: fingerprinter.clone(),
+            }))
+        }
+    }));
+
+    let seek = match time_range {
+        Some((start, end)) => {
+            handler = handler.with_time_range(start, end);
+            time_range_seek(start)
+        }
+        None => None,
+    };
+
+    decode_file_path_str(path, &mut handler, true, false, None, seek)?;
+
+    let fingerprinter = fingerprinter
+        .lock()
+        .unwrap()
+        .take()
+        .ok_or(FingerprintError::NoAudio)?;
+
+    Ok(fingerprinter.finish())
+}
+
+/// Computes the acoustic fingerprints of all the local tracks that haven't
+/// been fingerprinted yet. Returns the number of tracks that were
+/// fingerprinted.
+pub async fn fingerprint_tracks(
+    db: &LibraryDatabase,
+    token: CancellationToken,
+) -> Result<usize, FingerprintError> {
+    let fingerprinted = get_track_fingerprints(db)
+        .await?
+        .into_iter()
+        .map(|x| x.track_id)
+        .collect::<HashSet<_>>();
+
+    let tracks = get_tracks(db, None)
+        .await?
+        .into_iter()
+        .filter(|track| track.source == TrackApiSource::Local)
+        .filter(|track| !fingerprinted.contains(&track.id))
+        .filter_map(|track| {
+            let time_range = match (track.start_offset, track.end_offset) {
+                (None, None) => None,
+                (start, end) => Some((start.unwrap_or(0.0), end)),
+            };
+            track.file.map(|file| (track.id, file, time_range))
+        })
+        .collect::<Vec<_>>();
+
+    log::debug!("fingerprint_tracks: {} tracks to fingerprint", tracks.len());
+
+    let mut count = 0;
+
+    for (track_id, file, time_range) in tracks {
+        if token.is_cancelled() {
+            log::debug!("fingerprint_tracks: cancelled");
+            break;
+        }
+
+        log::trace!("fingerprint_tracks: fingerprinting track_id={track_id} file={file}");
+
+        let result = moosicbox_task::spawn_blocking("scan: fingerprint track", {
+            let file = file.clone();
+            move || fingerprint_file(&file, time_range)
+        })
+        .await?;
+
+        match result {
+            Ok(fingerprint) if !fingerprint.data.is_empty() => {
+                set_track_fingerprint(db, track_id, &fingerprint.encode(), fingerprint.duration)
+                    .await?;
+                count += 1;
+            }
+            Ok(_) => {
+                log::debug!("fingerprint_tracks: track_id={track_id} file={file} is too short to fingerprint");
+            }
+            Err(e) => {
+                log::warn!("fingerprint_tracks: Failed to fingerprint track_id={track_id} file={file}: {e:?}");
+            }
+        }
+    }
+
+    log::debug!("fingerprint_tracks: fingerprinted {count} tracks");
+
+    Ok(count)
+}
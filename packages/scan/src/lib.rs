@@ -21,6 +21,10 @@ use tokio_util::sync::CancellationToken;
 #[cfg(feature = "api")]
 pub mod api;
 #[cfg(feature = "local")]
+pub mod cue;
+#[cfg(feature = "fingerprint")]
+pub mod fingerprint;
+#[cfg(feature = "local")]
 pub mod local;
 
 pub mod db;
@@ -351,6 +355,11 @@ pub async fn run_scan(
     Ok(())
 }
 
+#[cfg(feature = "fingerprint")]
+pub async fn run_fingerprint(db: &LibraryDatabase) -> Result<usize, fingerprint::FingerprintError> {
+    fingerprint::fingerprint_tracks(db, CANCELLATION_TOKEN.clone()).await
+}
+
 #[cfg(feature = "local")]
 pub async fn get_scan_paths(db: &LibraryDatabase) -> Result<Vec<String>, DbError> {
     let locations = db::get_scan_locations_for_origin(db, ScanOrigin::Local).await?;
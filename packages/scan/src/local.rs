@@ -10,7 +10,7 @@ use moosicbox_core::{
 };
 use moosicbox_database::profiles::LibraryDatabase;
 use moosicbox_files::{sanitize_filename, search_for_cover};
-use moosicbox_lofty::{AudioFile, ParseOptions};
+use moosicbox_lofty::{AudioFile, ItemKey, ParseOptions, TaggedFileExt as _};
 use regex::Regex;
 use std::{
     fs::Metadata,
@@ -28,7 +28,8 @@ use tokio::{
 use tokio_util::sync::CancellationToken;
 
 use crate::{
-    output::{ScanOutput, UpdateDatabaseError},
+    cue,
+    output::{is_various_artists, ScanOutput, UpdateDatabaseError, VARIOUS_ARTISTS},
     Scanner, CACHE_DIR,
 };
 
@@ -115,6 +116,8 @@ pub async fn scan_items(
 
     log::info!("Finished initial scan");
 
+    output.write().await.group_compilations().await;
+
     {
         let output = output.read().await;
         output.update_database(db).await?;
@@ -175,12 +178,16 @@ fn scan_track(
             duration,
             album,
             album_artist,
+            track_artist,
+            compilation,
             date_released,
             audio_bitrate,
             overall_bitrate,
             sample_rate,
             bit_depth,
             channels,
+            rating,
+            cue_tracks,
         ) = moosicbox_task::spawn_blocking("scan: scan_track", move || {
             let extension = path
                 .extension()
@@ -199,6 +206,7 @@ fn scan_track(
                 .options(ParseOptions::new().read_picture(false))
                 .read()
                 .expect("ERROR: Failed to read file!");
+            let cue = find_cue_sheet(&path, moosicbox_lofty_tag.tags());
 
             let duration = if path.clone().to_str().unwrap().ends_with(".mp3") {
                 mp3_duration::from_path(path.as_path())
@@ -256,13 +264,15 @@ fn scan_track(
             let album = tag
                 .as_ref()
                 .and_then(|tag| tag.album_title())
-                .unwrap_or(&album_dir_name)
-                .to_string();
+                .map(ToString::to_string)
+                .or_else(|| cue.as_ref().and_then(|(sheet, _)| sheet.title.clone()))
+                .unwrap_or_else(|| album_dir_name.clone());
             let artist_name = tag
                 .as_ref()
                 .and_then(|tag| tag.artist().or(tag.album_artist()))
-                .unwrap_or(&artist_dir_name)
-                .to_string();
+                .map(ToString::to_string)
+                .or_else(|| cue.as_ref().and_then(|(sheet, _)| sheet.performer.clone()))
+                .unwrap_or_else(|| artist_dir_name.clone());
             let album_artist = tag
                 .as_ref()
                 .and_then(|tag| tag.album_artist())
@@ -271,13 +281,23 @@ fn scan_track(
             let date_released = tag
                 .as_ref()
                 .and_then(|tag| tag.date())
-                .map(|date| date.to_string());
+                .map(|date| date.to_string())
+                .or_else(|| cue.as_ref().and_then(|(sheet, _)| sheet.date.clone()));
 
             let audio_bitrate = moosicbox_lofty_tag.properties().audio_bitrate();
             let overall_bitrate = moosicbox_lofty_tag.properties().overall_bitrate();
             let sample_rate = moosicbox_lofty_tag.properties().sample_rate();
             let bit_depth = moosicbox_lofty_tag.properties().bit_depth();
             let channels = moosicbox_lofty_tag.properties().channels();
+            let rating = moosicbox_lofty_tag
+                .tags()
+                .iter()
+                .find_map(moosicbox_library::tags::read_rating);
+            let compilation = moosicbox_lofty_tag
+                .tags()
+                .iter()
+                .any(moosicbox_library::tags::read_compilation)
+                || is_various_artists(&album_artist);
 
             log::debug!("====== {} ======", path.clone().to_str().unwrap());
             log::debug!("title: {}", title);
@@ -290,21 +310,32 @@ fn scan_track(
             log::debug!("sample_rate: {:?}", sample_rate);
             log::debug!("bit_depth: {:?}", bit_depth);
             log::debug!("channels: {:?}", channels);
+            log::debug!("rating: {:?}", rating);
             log::debug!("album title: {}", album);
             log::debug!("artist directory name: {}", artist_dir_name);
             log::debug!("album directory name: {}", album_dir_name);
             log::debug!("artist: {}", artist_name.clone());
             log::debug!("album_artist: {}", album_artist.clone());
+            log::debug!("compilation: {}", compilation);
             log::debug!("date_released: {:?}", date_released);
+            log::debug!(
+                "cue tracks: {:?}",
+                cue.as_ref().map(|(_, tracks)| tracks.len())
+            );
             log::debug!(
                 "contains cover: {:?}",
                 tag.as_ref().is_some_and(|tag| tag.album_cover().is_some())
             );
 
-            let album_artist = match MULTI_ARTIST_PATTERN.find(album_artist.as_str()) {
-                Some(comma) => album_artist[..comma.start() + 1].to_string(),
-                None => album_artist,
+            let album_artist = if compilation {
+                VARIOUS_ARTISTS.to_string()
+            } else {
+                match MULTI_ARTIST_PATTERN.find(album_artist.as_str()) {
+                    Some(comma) => album_artist[..comma.start() + 1].to_string(),
+                    None => album_artist,
+                }
             };
+            let track_artist = Some(artist_name).filter(|x| *x != album_artist);
 
             Ok::<_, ScanError>((
                 path.to_path_buf(),
@@ -317,12 +348,16 @@ fn scan_track(
                 duration,
                 album,
                 album_artist,
+                track_artist,
+                compilation,
                 date_released,
                 audio_bitrate,
                 overall_bitrate,
                 sample_rate,
                 bit_depth,
                 channels,
+                rating,
+                cue.map(|(_, tracks)| tracks).unwrap_or_default(),
             ))
         })
         .await??;
@@ -338,6 +373,22 @@ fn scan_track(
 
         scanner.on_scanned_track().await;
 
+        if let Some(track_artist) = &track_artist {
+            output
+                .add_artist(track_artist, &None, ApiSource::Library)
+                .await;
+        }
+
+        for performer in cue_tracks
+            .iter()
+            .filter_map(|x| x.performer.as_ref())
+            .filter(|x| **x != album_artist)
+        {
+            output
+                .add_artist(performer, &None, ApiSource::Library)
+                .await;
+        }
+
         let artist = output
             .add_artist(&album_artist, &None, ApiSource::Library)
             .await;
@@ -352,6 +403,9 @@ fn scan_track(
             )
             .await;
         let mut album = album.write().await;
+        if compilation {
+            album.compilation = true;
+        }
         let save_path = CACHE_DIR
             .join("local")
             .join(sanitize_filename(&artist.name))
@@ -401,24 +455,82 @@ fn scan_track(
             }
         }
 
-        let _ = album
-            .add_track(
-                &Some(path.to_str().unwrap()),
-                number as u32,
-                &title,
-                duration,
-                &Some(bytes),
-                format,
-                &bit_depth,
-                &audio_bitrate,
-                &overall_bitrate,
-                &sample_rate,
-                &channels,
-                TrackApiSource::Local,
-                &None,
-                ApiSource::Library,
-            )
-            .await;
+        if cue_tracks.is_empty() {
+            let track = album
+                .add_track(
+                    &Some(path.to_str().unwrap()),
+                    number as u32,
+                    &title,
+                    duration,
+                    &Some(bytes),
+                    format,
+                    &bit_depth,
+                    &audio_bitrate,
+                    &overall_bitrate,
+                    &sample_rate,
+                    &channels,
+                    TrackApiSource::Local,
+                    &None,
+                    ApiSource::Library,
+                )
+                .await;
+
+            let mut track = track.write().await;
+            if rating.is_some() {
+                track.rating = rating;
+            }
+            track.artist = track_artist;
+
+            return Ok(());
+        }
+
+        // Each track of the cue sheet is a virtual track of the file, played
+        // by seeking into it
+        for cue_track in cue_tracks {
+            let start = cue_track.start.min(duration);
+            let end = cue_track.end.map_or(duration, |end| end.min(duration));
+            let track_duration = (end - start).max(0.0);
+            let track_bytes = if duration > 0.0 {
+                (bytes as f64 * track_duration / duration).round() as u64
+            } else {
+                0
+            };
+            let title = cue_track
+                .title
+                .clone()
+                .unwrap_or_else(|| format!("Track {}", cue_track.number));
+            let track_artist = cue_track
+                .performer
+                .filter(|x| *x != album_artist)
+                .or_else(|| track_artist.clone());
+
+            let track = album
+                .add_track(
+                    &Some(path.to_str().unwrap()),
+                    cue_track.number,
+                    &title,
+                    track_duration,
+                    &Some(track_bytes),
+                    format,
+                    &bit_depth,
+                    &audio_bitrate,
+                    &overall_bitrate,
+                    &sample_rate,
+                    &channels,
+                    TrackApiSource::Local,
+                    &None,
+                    ApiSource::Library,
+                )
+                .await;
+
+            let mut track = track.write().await;
+            if rating.is_some() {
+                track.rating = rating;
+            }
+            track.artist = track_artist;
+            track.start_offset = Some(cue_track.start);
+            track.end_offset = cue_track.end;
+        }
 
         Ok(())
     })
@@ -492,6 +604,66 @@ fn scan_artist_cover(
     })
 }
 
+const CUESHEET_TAG_KEY: &str = "CUESHEET";
+
+/// Finds the cue sheet describing the tracks within the file at `path`,
+/// either embedded in its `CUESHEET` tag or in a `.cue` file next to it.
+/// Returns the cue sheet with the tracks of the file, if it contains more
+/// than one.
+fn find_cue_sheet(
+    path: &Path,
+    tags: &[moosicbox_lofty::Tag],
+) -> Option<(cue::CueSheet, Vec<cue::CueTrack>)> {
+    let stem = path.file_stem().and_then(|x| x.to_str())?;
+
+    let embedded = tags
+        .iter()
+        .find_map(|tag| tag.get_string(&ItemKey::Unknown(CUESHEET_TAG_KEY.to_string())))
+        .map(|contents| (true, cue::parse(contents)));
+
+    let siblings = std::fs::read_dir(path.parent()?)
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|x| {
+            x.extension()
+                .and_then(|x| x.to_str())
+                .is_some_and(|x| x.eq_ignore_ascii_case("cue"))
+        })
+        .filter_map(|cue_path| {
+            let contents = std::fs::read(&cue_path)
+                .map_err(|e| log::warn!("Failed to read cue sheet {cue_path:?}: {e:?}"))
+                .ok()?;
+            let same_stem = cue_path
+                .file_stem()
+                .and_then(|x| x.to_str())
+                .is_some_and(|x| x.eq_ignore_ascii_case(stem));
+            Some((same_stem, cue::parse(&String::from_utf8_lossy(&contents))))
+        });
+
+    embedded
+        .into_iter()
+        .chain(siblings)
+        .find_map(|(owned, sheet)| {
+            // Embedded sheets and sheets named after the file describe the file
+            // even if they reference it by another name
+            let tracks = sheet
+                .tracks_for_file(path)
+                .or(match sheet.files.as_slice() {
+                    [file] if owned => Some(file.tracks.as_slice()),
+                    _ => None,
+                })?;
+
+            if tracks.len() < 2 {
+                return None;
+            }
+
+            let tracks = tracks.to_vec();
+            Some((sheet, tracks))
+        })
+}
+
 static MUSIC_FILE_PATTERN: LazyLock<Regex> =
     LazyLock::new(|| Regex::new(r".+\.(flac|m4a|mp3|opus)").unwrap());
 static MULTI_ARTIST_PATTERN: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\S,\S").unwrap());
@@ -17,7 +17,7 @@ use moosicbox_files::FetchAndSaveBytesFromRemoteUrlError;
 use moosicbox_library::{
     db::{
         self, add_album_maps_and_get_albums, add_artist_maps_and_get_artists, add_tracks,
-        set_track_sizes, InsertTrack, SetTrackSize,
+        set_track_sizes, set_track_tag_ratings, InsertTrack, SetTrackSize,
     },
     models::{LibraryAlbum, LibraryArtist, LibraryTrack},
 };
@@ -30,6 +30,20 @@ use tokio::{sync::RwLock, task::JoinError};
 
 use crate::CACHE_DIR;
 
+/// The album artist that compilation albums are grouped under.
+pub const VARIOUS_ARTISTS: &str = "Various Artists";
+
+/// Whether the album artist name is one of the common spellings of
+/// "Various Artists".
+pub fn is_various_artists(name: &str) -> bool {
+    let name = name.trim().to_lowercase();
+
+    matches!(
+        name.as_str(),
+        "various artists" | "various" | "va" | "v.a." | "v/a"
+    )
+}
+
 static IMAGE_CLIENT: LazyLock<reqwest::Client> = LazyLock::new(reqwest::Client::new);
 
 async fn search_for_cover(
@@ -76,6 +90,14 @@ pub struct ScanTrack {
     pub source: TrackApiSource,
     pub id: Option<Id>,
     pub api_source: ApiSource,
+    pub rating: Option<u8>,
+    /// The track artist, when it differs from the album artist.
+    pub artist: Option<String>,
+    /// The offset in seconds of the start of a virtual track within its file,
+    /// e.g. a track of a cue sheet album rip.
+    pub start_offset: Option<f64>,
+    /// The offset in seconds of the end of a virtual track within its file.
+    pub end_offset: Option<f64>,
 }
 
 impl ScanTrack {
@@ -112,6 +134,10 @@ impl ScanTrack {
             source,
             id: id.cloned(),
             api_source,
+            rating: None,
+            artist: None,
+            start_offset: None,
+            end_offset: None,
         }
     }
 }
@@ -127,6 +153,7 @@ pub struct ScanAlbum {
     pub tracks: Arc<RwLock<Vec<Arc<RwLock<ScanTrack>>>>>,
     pub id: Option<Id>,
     pub api_source: ApiSource,
+    pub compilation: bool,
 }
 
 impl ScanAlbum {
@@ -149,6 +176,7 @@ impl ScanAlbum {
             tracks: Arc::new(RwLock::new(Vec::new())),
             id: id.cloned(),
             api_source,
+            compilation: false,
         }
     }
 
@@ -179,9 +207,11 @@ impl ScanAlbum {
                 let is_match = if t.path.is_none() && path.is_none() {
                     t.number == number && t.name == name && t.source == source
                 } else {
-                    t.path
-                        .as_ref()
-                        .is_some_and(|p| path.is_some_and(|new_p| p == new_p))
+                    // The virtual tracks of a cue sheet album rip share a path
+                    t.number == number
+                        && t.path
+                            .as_ref()
+                            .is_some_and(|p| path.is_some_and(|new_p| p == new_p))
                 };
                 if is_match {
                     maybe_track.replace(entry.clone());
@@ -257,6 +287,10 @@ impl ScanAlbum {
             ),
             ("artwork", DatabaseValue::StringOpt(self.cover)),
             ("directory", DatabaseValue::StringOpt(self.directory)),
+            (
+                "compilation",
+                DatabaseValue::Number(i64::from(self.compilation)),
+            ),
         ]);
         if let Some(id) = &self.id {
             match self.api_source {
@@ -285,6 +319,10 @@ impl ScanAlbum {
             ),
             ("artwork", DatabaseValue::StringOpt(self.cover)),
             ("directory", DatabaseValue::StringOpt(self.directory)),
+            (
+                "compilation",
+                DatabaseValue::Number(i64::from(self.compilation)),
+            ),
         ]);
         if let Some(id) = &self.id {
             match self.api_source {
@@ -508,6 +546,91 @@ impl ScanOutput {
         }
     }
 
+    /// Merges albums that were split across multiple album artists but live
+    /// in the same directory (e.g. a compilation without album artist tags)
+    /// into a single [`VARIOUS_ARTISTS`] compilation album. The original
+    /// album artist is kept as the track artist of the moved tracks.
+    pub async fn group_compilations(&mut self) {
+        let mut albums_by_directory = HashMap::<_, Vec<_>>::new();
+
+        for artist in self.artists.read().await.iter() {
+            for album in artist.read().await.albums.read().await.iter() {
+                let key = {
+                    let album = album.read().await;
+                    if album.api_source != ApiSource::Library {
+                        continue;
+                    }
+                    let Some(directory) = album.directory.clone() else {
+                        continue;
+                    };
+                    (directory, album.name.clone())
+                };
+                albums_by_directory
+                    .entry(key)
+                    .or_default()
+                    .push((artist.clone(), album.clone()));
+            }
+        }
+
+        for ((directory, name), entries) in albums_by_directory {
+            if entries.len() < 2 {
+                continue;
+            }
+
+            log::debug!(
+                "Grouping {} albums in {directory} under {VARIOUS_ARTISTS}",
+                entries.len()
+            );
+
+            let date_released = entries[0].1.read().await.date_released.clone();
+            let various_artists = self
+                .add_artist(VARIOUS_ARTISTS, &None, ApiSource::Library)
+                .await;
+            let compilation = various_artists
+                .write()
+                .await
+                .add_album(
+                    &name,
+                    &date_released,
+                    Some(&directory),
+                    &None,
+                    ApiSource::Library,
+                )
+                .await;
+
+            for (artist, album) in entries {
+                if Arc::ptr_eq(&album, &compilation) {
+                    continue;
+                }
+
+                let artist_name = {
+                    let artist = artist.read().await;
+                    let mut albums = artist.albums.write().await;
+                    albums.retain(|x| !Arc::ptr_eq(x, &album));
+                    artist.name.clone()
+                };
+
+                let album = album.read().await;
+                let mut compilation = compilation.write().await;
+
+                for track in album.tracks.read().await.iter() {
+                    track
+                        .write()
+                        .await
+                        .artist
+                        .get_or_insert_with(|| artist_name.clone());
+                    compilation.tracks.write().await.push(track.clone());
+                }
+
+                if compilation.cover.is_none() {
+                    compilation.cover.clone_from(&album.cover);
+                }
+            }
+
+            compilation.write().await.compilation = true;
+        }
+    }
+
     #[allow(unused)]
     pub async fn update_database(
         &self,
@@ -597,6 +720,12 @@ impl ScanOutput {
             )));
         }
 
+        let artist_ids = artists
+            .iter()
+            .zip(db_artists.iter())
+            .map(|(artist, db)| (artist.name.as_str(), db.id))
+            .collect::<HashMap<_, _>>();
+
         let db_albums_start = std::time::SystemTime::now();
 
         let existing_album_ids = db
@@ -677,6 +806,12 @@ impl ScanOutput {
                             duration: track.duration,
                             format: Some(track.format),
                             source: track.source,
+                            track_artist_id: track
+                                .artist
+                                .as_ref()
+                                .and_then(|name| artist_ids.get(name.as_str()).copied()),
+                            start_offset: track.start_offset,
+                            end_offset: track.end_offset,
                             ..Default::default()
                         },
                     }
@@ -728,6 +863,14 @@ impl ScanOutput {
 
         set_track_sizes(db, &track_sizes).await?;
 
+        let track_ratings = tracks
+            .iter()
+            .zip(db_tracks.iter())
+            .filter_map(|(track, db_track)| track.rating.map(|rating| (db_track.id, rating)))
+            .collect::<Vec<_>>();
+
+        set_track_tag_ratings(db, &track_ratings).await?;
+
         let db_track_sizes_end = std::time::SystemTime::now();
         log::info!(
             "Finished db track_sizes update for scan in {}ms",
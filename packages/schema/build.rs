@@ -1,3 +1,70 @@
+use std::{fmt::Write as _, path::Path};
+
+const SCHEMAS: &[&str] = &["config", "library"];
+const BACKENDS: &[(&str, &str)] = &[
+    ("mysql", "MYSQL"),
+    ("postgres", "POSTGRES"),
+    ("sqlite", "SQLITE"),
+];
+
 fn main() {
     println!("cargo:rerun-if-changed=migrations");
+
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let out_dir = std::env::var("OUT_DIR").unwrap();
+    let mut code = String::new();
+
+    for schema in SCHEMAS {
+        for (backend, prefix) in BACKENDS {
+            let dir = Path::new(&manifest_dir)
+                .join("migrations")
+                .join("server")
+                .join(schema)
+                .join(backend);
+
+            let mut names = std::fs::read_dir(&dir)
+                .unwrap_or_else(|e| panic!("Failed to read {}: {e:?}", dir.display()))
+                .map(|entry| entry.unwrap())
+                .filter(|entry| entry.path().join("up.sql").is_file())
+                .map(|entry| entry.file_name().to_str().unwrap().to_string())
+                .collect::<Vec<_>>();
+            names.sort();
+
+            writeln!(code, "#[cfg(feature = \"{backend}\")]").unwrap();
+            writeln!(
+                code,
+                "pub const {prefix}_{}_MIGRATIONS: &[Migration] = &[",
+                schema.to_uppercase()
+            )
+            .unwrap();
+
+            for name in names {
+                // Matches the version diesel records for the migration so
+                // databases migrated by diesel aren't migrated again.
+                let version = name.split('_').next().unwrap().replace('-', "");
+                assert!(
+                    !version.is_empty() && version.chars().all(|c| c.is_ascii_digit()),
+                    "Invalid migration name: {name}"
+                );
+                let path = dir.join(&name);
+                let run_in_transaction = std::fs::read_to_string(path.join("metadata.toml"))
+                    .map_or(true, |metadata| {
+                        !metadata
+                            .lines()
+                            .any(|line| line.replace(' ', "") == "run_in_transaction=false")
+                    });
+
+                writeln!(
+                    code,
+                    "    Migration {{ version: {version:?}, name: {name:?}, up: include_str!({:?}), run_in_transaction: {run_in_transaction} }},",
+                    path.join("up.sql").to_str().unwrap(),
+                )
+                .unwrap();
+            }
+
+            writeln!(code, "];").unwrap();
+        }
+    }
+
+    std::fs::write(Path::new(&out_dir).join("migrations.rs"), code).unwrap();
 }
@@ -0,0 +1,8 @@
+DROP TABLE magic_tokens;
+DROP TABLE client_access_tokens;
+DROP TABLE audio_zone_players;
+DROP TABLE audio_zones;
+DROP TABLE players;
+DROP TABLE connections;
+DROP TABLE profiles;
+DROP TABLE identity;
@@ -0,0 +1,61 @@
+CREATE TABLE IF NOT EXISTS identity (
+    id VARCHAR(128) PRIMARY KEY NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS profiles (
+    id BIGINT PRIMARY KEY NOT NULL AUTO_INCREMENT,
+    name VARCHAR(128) NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS connections (
+    id VARCHAR(128) PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL,
+    created DATETIME NOT NULL DEFAULT NOW(),
+    updated DATETIME NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS players (
+    id BIGINT PRIMARY KEY NOT NULL AUTO_INCREMENT,
+    connection_id VARCHAR(128) NOT NULL,
+    name TEXT NOT NULL,
+    audio_output_id VARCHAR(128) NOT NULL,
+    playing BIGINT NOT NULL DEFAULT 0,
+    created DATETIME NOT NULL DEFAULT NOW(),
+    updated DATETIME NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS audio_zones (
+    id BIGINT PRIMARY KEY NOT NULL AUTO_INCREMENT,
+    name TEXT NOT NULL,
+    created DATETIME NOT NULL DEFAULT NOW(),
+    updated DATETIME NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS audio_zone_players (
+    audio_zone_id BIGINT NOT NULL,
+    player_id BIGINT NOT NULL,
+    created DATETIME NOT NULL DEFAULT NOW(),
+    updated DATETIME NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX ux_audio_zone_players_props ON audio_zone_players(
+    audio_zone_id,
+    player_id
+);
+
+CREATE TABLE IF NOT EXISTS client_access_tokens (
+    token VARCHAR(64) PRIMARY KEY NOT NULL,
+    client_id VARCHAR(64) NOT NULL,
+    expires DATETIME DEFAULT NULL,
+    created DATETIME NOT NULL DEFAULT NOW(),
+    updated DATETIME NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS magic_tokens (
+    magic_token VARCHAR(64) PRIMARY KEY NOT NULL,
+    client_id VARCHAR(64) NOT NULL,
+    access_token VARCHAR(64) NOT NULL,
+    expires DATETIME DEFAULT NULL,
+    created DATETIME NOT NULL DEFAULT NOW(),
+    updated DATETIME NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1 @@
+DROP TABLE quality_profiles;
@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS quality_profiles (
+    id BIGINT PRIMARY KEY NOT NULL AUTO_INCREMENT,
+    profile VARCHAR(255) NOT NULL,
+    name VARCHAR(255) NOT NULL,
+    format VARCHAR(16) NOT NULL,
+    bitrate BIGINT DEFAULT NULL,
+    vbr_quality BIGINT DEFAULT NULL,
+    sample_rate BIGINT DEFAULT NULL,
+    channels BIGINT DEFAULT NULL,
+    created DATETIME NOT NULL DEFAULT NOW(),
+    updated DATETIME NOT NULL DEFAULT NOW()
+);
+CREATE UNIQUE INDEX ux_quality_profiles ON quality_profiles(profile, name);
@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS quality_profiles (
+    "id" BIGSERIAL PRIMARY KEY NOT NULL,
+    "profile" TEXT NOT NULL,
+    "name" TEXT NOT NULL,
+    "format" TEXT NOT NULL,
+    "bitrate" BIGINT DEFAULT NULL,
+    "vbr_quality" BIGINT DEFAULT NULL,
+    "sample_rate" BIGINT DEFAULT NULL,
+    "channels" BIGINT DEFAULT NULL,
+    created TIMESTAMP NOT NULL DEFAULT NOW(),
+    updated TIMESTAMP NOT NULL DEFAULT NOW()
+);
+CREATE UNIQUE INDEX ux_quality_profiles ON quality_profiles("profile", "name");
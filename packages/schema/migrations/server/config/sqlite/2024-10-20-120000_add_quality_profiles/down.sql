@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS ux_quality_profiles;
+DROP TABLE quality_profiles;
@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS quality_profiles (
+    `id` INTEGER PRIMARY KEY NOT NULL,
+    `profile` TEXT NOT NULL,
+    `name` TEXT NOT NULL,
+    `format` TEXT NOT NULL,
+    `bitrate` INTEGER DEFAULT NULL,
+    `vbr_quality` INTEGER DEFAULT NULL,
+    `sample_rate` INTEGER DEFAULT NULL,
+    `channels` INTEGER DEFAULT NULL,
+    `created` TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%f', 'now')),
+    `updated` TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%f', 'now'))
+);
+CREATE UNIQUE INDEX ux_quality_profiles ON quality_profiles(`profile`, `name`);
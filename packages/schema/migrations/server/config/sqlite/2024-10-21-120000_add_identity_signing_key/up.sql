@@ -0,0 +1 @@
+ALTER TABLE identity ADD COLUMN signing_key TEXT DEFAULT NULL;
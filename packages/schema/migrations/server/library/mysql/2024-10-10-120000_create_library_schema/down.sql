@@ -0,0 +1,22 @@
+DROP TABLE yt_config;
+DROP TABLE qobuz_bundle_secrets;
+DROP TABLE qobuz_bundles;
+DROP TABLE qobuz_config;
+DROP TABLE tidal_config;
+DROP TABLE download_tasks;
+DROP TABLE download_locations;
+DROP TABLE scan_locations;
+DROP TABLE audio_zone_sessions;
+DROP TABLE active_players;
+DROP TABLE sessions;
+DROP TABLE session_playlist_tracks;
+DROP TABLE session_playlists;
+DROP TABLE album_enrichment_candidates;
+DROP TABLE track_ratings;
+DROP TABLE album_ratings;
+DROP TABLE artist_ratings;
+DROP TABLE track_fingerprints;
+DROP TABLE track_sizes;
+DROP TABLE tracks;
+DROP TABLE albums;
+DROP TABLE artists;
@@ -0,0 +1,304 @@
+CREATE TABLE IF NOT EXISTS artists (
+    id BIGINT PRIMARY KEY NOT NULL AUTO_INCREMENT,
+    title TEXT NOT NULL,
+    cover TEXT DEFAULT NULL,
+    tidal_id BIGINT DEFAULT NULL,
+    qobuz_id BIGINT DEFAULT NULL,
+    musicbrainz_id TEXT DEFAULT NULL
+);
+
+CREATE TABLE IF NOT EXISTS albums (
+    id BIGINT PRIMARY KEY NOT NULL AUTO_INCREMENT,
+    artist_id BIGINT NOT NULL,
+    title TEXT NOT NULL,
+    date_released TEXT DEFAULT NULL,
+    date_added DATETIME NOT NULL DEFAULT NOW(),
+    artwork TEXT DEFAULT NULL,
+    directory TEXT DEFAULT NULL,
+    blur BIGINT NOT NULL DEFAULT 0,
+    tidal_id BIGINT DEFAULT NULL,
+    qobuz_id VARCHAR(64) DEFAULT NULL,
+    compilation BIGINT NOT NULL DEFAULT 0,
+    musicbrainz_release_id TEXT DEFAULT NULL,
+    musicbrainz_release_group_id TEXT DEFAULT NULL,
+    release_type TEXT DEFAULT NULL,
+    label TEXT DEFAULT NULL,
+    country TEXT DEFAULT NULL,
+    original_release_date TEXT DEFAULT NULL,
+    FOREIGN KEY (artist_id) REFERENCES artists(id)
+);
+
+-- TEXT columns can't be part of a unique index, so the unique indexes over
+-- them are on a hash of the values instead.
+CREATE UNIQUE INDEX ux_albums ON albums(
+    (CAST(SHA2(CONCAT_WS('|', artist_id, title), 256) AS CHAR(64)))
+);
+
+CREATE TABLE IF NOT EXISTS tracks (
+    id BIGINT PRIMARY KEY NOT NULL AUTO_INCREMENT,
+    album_id BIGINT NOT NULL,
+    number BIGINT NOT NULL,
+    title TEXT NOT NULL,
+    duration DOUBLE NOT NULL,
+    file TEXT DEFAULT NULL,
+    format VARCHAR(64) NOT NULL DEFAULT 'SOURCE',
+    source VARCHAR(64) NOT NULL DEFAULT 'LOCAL',
+    tidal_id BIGINT DEFAULT NULL,
+    qobuz_id BIGINT DEFAULT NULL,
+    track_artist_id BIGINT DEFAULT NULL,
+    musicbrainz_recording_id TEXT DEFAULT NULL,
+    FOREIGN KEY (album_id) REFERENCES albums(id)
+);
+
+CREATE UNIQUE INDEX ux_tracks_file ON tracks(
+    (CAST(SHA2(CONCAT_WS(
+        '|',
+        COALESCE(file, ''),
+        album_id,
+        title,
+        duration,
+        number,
+        COALESCE(format, ''),
+        source,
+        COALESCE(tidal_id, 0),
+        COALESCE(qobuz_id, 0)
+    ), 256) AS CHAR(64)))
+);
+
+CREATE TABLE IF NOT EXISTS track_sizes (
+    id BIGINT PRIMARY KEY NOT NULL AUTO_INCREMENT,
+    track_id BIGINT NOT NULL,
+    format VARCHAR(64) NOT NULL,
+    bytes BIGINT DEFAULT NULL,
+    audio_bitrate BIGINT DEFAULT NULL,
+    overall_bitrate BIGINT DEFAULT NULL,
+    bit_depth BIGINT DEFAULT NULL,
+    sample_rate BIGINT DEFAULT NULL,
+    channels BIGINT DEFAULT NULL,
+    created DATETIME NOT NULL DEFAULT NOW(),
+    updated DATETIME NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX ux_track_sizes_props ON track_sizes(
+    track_id,
+    format,
+    (COALESCE(audio_bitrate, 0)),
+    (COALESCE(overall_bitrate, 0)),
+    (COALESCE(bit_depth, 0)),
+    (COALESCE(sample_rate, 0)),
+    (COALESCE(channels, 0))
+);
+
+CREATE TABLE IF NOT EXISTS track_fingerprints (
+    id BIGINT PRIMARY KEY NOT NULL AUTO_INCREMENT,
+    track_id BIGINT NOT NULL,
+    fingerprint TEXT NOT NULL,
+    duration DOUBLE NOT NULL,
+    created DATETIME NOT NULL DEFAULT NOW(),
+    updated DATETIME NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX ux_track_fingerprints ON track_fingerprints(track_id);
+
+CREATE TABLE IF NOT EXISTS artist_ratings (
+    id BIGINT PRIMARY KEY NOT NULL AUTO_INCREMENT,
+    artist_id BIGINT NOT NULL,
+    rating BIGINT DEFAULT NULL,
+    loved BIGINT NOT NULL DEFAULT 0,
+    banned BIGINT NOT NULL DEFAULT 0,
+    created DATETIME NOT NULL DEFAULT NOW(),
+    updated DATETIME NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX ux_artist_ratings ON artist_ratings(artist_id);
+
+CREATE TABLE IF NOT EXISTS album_ratings (
+    id BIGINT PRIMARY KEY NOT NULL AUTO_INCREMENT,
+    album_id BIGINT NOT NULL,
+    rating BIGINT DEFAULT NULL,
+    loved BIGINT NOT NULL DEFAULT 0,
+    banned BIGINT NOT NULL DEFAULT 0,
+    created DATETIME NOT NULL DEFAULT NOW(),
+    updated DATETIME NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX ux_album_ratings ON album_ratings(album_id);
+
+CREATE TABLE IF NOT EXISTS track_ratings (
+    id BIGINT PRIMARY KEY NOT NULL AUTO_INCREMENT,
+    track_id BIGINT NOT NULL,
+    rating BIGINT DEFAULT NULL,
+    loved BIGINT NOT NULL DEFAULT 0,
+    banned BIGINT NOT NULL DEFAULT 0,
+    created DATETIME NOT NULL DEFAULT NOW(),
+    updated DATETIME NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX ux_track_ratings ON track_ratings(track_id);
+
+CREATE TABLE IF NOT EXISTS album_enrichment_candidates (
+    id BIGINT PRIMARY KEY NOT NULL AUTO_INCREMENT,
+    album_id BIGINT NOT NULL,
+    release_id TEXT NOT NULL,
+    score DOUBLE NOT NULL,
+    `release` TEXT NOT NULL,
+    created DATETIME NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX ix_album_enrichment_candidates_album_id ON album_enrichment_candidates(album_id);
+
+CREATE TABLE IF NOT EXISTS session_playlists (
+    id BIGINT PRIMARY KEY NOT NULL AUTO_INCREMENT
+);
+
+CREATE TABLE IF NOT EXISTS session_playlist_tracks (
+    id BIGINT PRIMARY KEY NOT NULL AUTO_INCREMENT,
+    session_playlist_id BIGINT NOT NULL,
+    `type` VARCHAR(64) NOT NULL DEFAULT 'LIBRARY',
+    track_id VARCHAR(64) DEFAULT NULL,
+    data TEXT DEFAULT NULL
+);
+
+CREATE TABLE IF NOT EXISTS sessions (
+    id BIGINT PRIMARY KEY NOT NULL AUTO_INCREMENT,
+    session_playlist_id BIGINT NOT NULL,
+    name TEXT NOT NULL,
+    active BIGINT NOT NULL DEFAULT 0,
+    playing BIGINT NOT NULL DEFAULT 0,
+    `position` BIGINT DEFAULT NULL,
+    seek BIGINT DEFAULT NULL,
+    volume DOUBLE NOT NULL DEFAULT 1,
+    audio_zone_id BIGINT DEFAULT NULL,
+    playback_target VARCHAR(128) DEFAULT NULL,
+    connection_id VARCHAR(128) DEFAULT NULL,
+    output_id VARCHAR(128) DEFAULT NULL,
+    FOREIGN KEY (session_playlist_id) REFERENCES session_playlists(id)
+);
+
+CREATE TABLE IF NOT EXISTS active_players (
+    id BIGINT PRIMARY KEY NOT NULL AUTO_INCREMENT,
+    session_id BIGINT NOT NULL,
+    player_id BIGINT NOT NULL,
+    created DATETIME NOT NULL DEFAULT NOW(),
+    updated DATETIME NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS audio_zone_sessions (
+    audio_zone_id BIGINT NOT NULL,
+    session_id BIGINT NOT NULL,
+    created DATETIME NOT NULL DEFAULT NOW(),
+    updated DATETIME NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX ux_audio_zone_sessions_props ON audio_zone_sessions(
+    audio_zone_id,
+    session_id
+);
+
+CREATE TABLE IF NOT EXISTS scan_locations (
+    id BIGINT PRIMARY KEY NOT NULL AUTO_INCREMENT,
+    origin VARCHAR(128) NOT NULL,
+    path TEXT DEFAULT NULL,
+    created DATETIME NOT NULL DEFAULT NOW(),
+    updated DATETIME NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX ux_scan_locations ON scan_locations(
+    (CAST(SHA2(CONCAT_WS('|', origin, COALESCE(path, '')), 256) AS CHAR(64)))
+);
+
+CREATE TABLE IF NOT EXISTS download_locations (
+    id BIGINT PRIMARY KEY NOT NULL AUTO_INCREMENT,
+    path TEXT DEFAULT NULL,
+    created DATETIME NOT NULL DEFAULT NOW(),
+    updated DATETIME NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX ux_download_locations ON download_locations(
+    (CAST(SHA2(COALESCE(path, ''), 256) AS CHAR(64)))
+);
+
+CREATE TABLE IF NOT EXISTS download_tasks (
+    id BIGINT PRIMARY KEY NOT NULL AUTO_INCREMENT,
+    state VARCHAR(32) NOT NULL DEFAULT 'PENDING',
+    `type` VARCHAR(32) NOT NULL,
+    track_id VARCHAR(64) DEFAULT NULL,
+    album_id VARCHAR(64) DEFAULT NULL,
+    artist_id VARCHAR(64) DEFAULT NULL,
+    source VARCHAR(32) DEFAULT NULL,
+    quality VARCHAR(32) DEFAULT NULL,
+    file_path VARCHAR(1024) NOT NULL,
+    artist VARCHAR(256) DEFAULT NULL,
+    album VARCHAR(256) DEFAULT NULL,
+    track VARCHAR(256) DEFAULT NULL,
+    contains_cover BIGINT NOT NULL DEFAULT 0,
+    total_bytes BIGINT DEFAULT NULL,
+    created DATETIME NOT NULL DEFAULT NOW(),
+    updated DATETIME NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX ux_download_tasks ON download_tasks(
+    (CAST(SHA2(CONCAT_WS(
+        '|',
+        `type`,
+        COALESCE(track_id, ''),
+        COALESCE(album_id, ''),
+        COALESCE(source, ''),
+        COALESCE(quality, ''),
+        file_path,
+        COALESCE(total_bytes, 0)
+    ), 256) AS CHAR(64)))
+);
+
+CREATE TABLE IF NOT EXISTS tidal_config (
+    id BIGINT PRIMARY KEY NOT NULL AUTO_INCREMENT,
+    access_token TEXT NOT NULL,
+    refresh_token TEXT NOT NULL,
+    client_id VARCHAR(64) NOT NULL DEFAULT '',
+    client_name VARCHAR(128) NOT NULL,
+    expires_in BIGINT NOT NULL,
+    issued_at BIGINT NOT NULL DEFAULT (UNIX_TIMESTAMP() * 1000),
+    scope VARCHAR(128) NOT NULL,
+    token_type VARCHAR(128) NOT NULL,
+    `user` TEXT NOT NULL,
+    user_id BIGINT NOT NULL,
+    created DATETIME NOT NULL DEFAULT NOW(),
+    updated DATETIME NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS qobuz_config (
+    id BIGINT PRIMARY KEY NOT NULL AUTO_INCREMENT,
+    access_token VARCHAR(256) NOT NULL,
+    user_id BIGINT NOT NULL,
+    user_email VARCHAR(256) NOT NULL,
+    user_public_id VARCHAR(256) NOT NULL,
+    created DATETIME NOT NULL DEFAULT NOW(),
+    updated DATETIME NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS qobuz_bundles (
+    id BIGINT PRIMARY KEY NOT NULL AUTO_INCREMENT,
+    bundle_version VARCHAR(32) NOT NULL,
+    app_id VARCHAR(32) NOT NULL,
+    created DATETIME NOT NULL DEFAULT NOW(),
+    updated DATETIME NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS qobuz_bundle_secrets (
+    id BIGINT PRIMARY KEY NOT NULL AUTO_INCREMENT,
+    qobuz_bundle_id BIGINT NOT NULL,
+    timezone VARCHAR(32) NOT NULL,
+    secret VARCHAR(32) NOT NULL,
+    created DATETIME NOT NULL DEFAULT NOW(),
+    updated DATETIME NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS yt_config (
+    id BIGINT PRIMARY KEY NOT NULL AUTO_INCREMENT,
+    access_token VARCHAR(256) NOT NULL,
+    user_id BIGINT NOT NULL,
+    user_email VARCHAR(256) NOT NULL,
+    user_public_id VARCHAR(256) NOT NULL,
+    created DATETIME NOT NULL DEFAULT NOW(),
+    updated DATETIME NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,2 @@
+ALTER TABLE tracks ADD COLUMN start_offset DOUBLE DEFAULT NULL;
+ALTER TABLE tracks ADD COLUMN end_offset DOUBLE DEFAULT NULL;
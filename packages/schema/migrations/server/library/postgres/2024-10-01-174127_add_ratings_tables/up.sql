@@ -0,0 +1,32 @@
+CREATE TABLE IF NOT EXISTS track_ratings (
+    "id" BIGSERIAL PRIMARY KEY NOT NULL,
+    "track_id" BIGINT NOT NULL,
+    "rating" BIGINT DEFAULT NULL,
+    "loved" BIGINT NOT NULL DEFAULT 0,
+    "banned" BIGINT NOT NULL DEFAULT 0,
+    created TIMESTAMP NOT NULL DEFAULT NOW(),
+    updated TIMESTAMP NOT NULL DEFAULT NOW()
+);
+CREATE UNIQUE INDEX ux_track_ratings ON track_ratings("track_id");
+
+CREATE TABLE IF NOT EXISTS album_ratings (
+    "id" BIGSERIAL PRIMARY KEY NOT NULL,
+    "album_id" BIGINT NOT NULL,
+    "rating" BIGINT DEFAULT NULL,
+    "loved" BIGINT NOT NULL DEFAULT 0,
+    "banned" BIGINT NOT NULL DEFAULT 0,
+    created TIMESTAMP NOT NULL DEFAULT NOW(),
+    updated TIMESTAMP NOT NULL DEFAULT NOW()
+);
+CREATE UNIQUE INDEX ux_album_ratings ON album_ratings("album_id");
+
+CREATE TABLE IF NOT EXISTS artist_ratings (
+    "id" BIGSERIAL PRIMARY KEY NOT NULL,
+    "artist_id" BIGINT NOT NULL,
+    "rating" BIGINT DEFAULT NULL,
+    "loved" BIGINT NOT NULL DEFAULT 0,
+    "banned" BIGINT NOT NULL DEFAULT 0,
+    created TIMESTAMP NOT NULL DEFAULT NOW(),
+    updated TIMESTAMP NOT NULL DEFAULT NOW()
+);
+CREATE UNIQUE INDEX ux_artist_ratings ON artist_ratings("artist_id");
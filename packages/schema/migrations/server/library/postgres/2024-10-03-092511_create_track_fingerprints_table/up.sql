@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS track_fingerprints (
+    "id" BIGSERIAL PRIMARY KEY NOT NULL,
+    "track_id" BIGINT NOT NULL,
+    "fingerprint" TEXT NOT NULL,
+    "duration" DOUBLE PRECISION NOT NULL,
+    created TIMESTAMP NOT NULL DEFAULT NOW(),
+    updated TIMESTAMP NOT NULL DEFAULT NOW()
+);
+CREATE UNIQUE INDEX ux_track_fingerprints ON track_fingerprints("track_id");
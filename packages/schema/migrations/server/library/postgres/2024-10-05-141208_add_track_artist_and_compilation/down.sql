@@ -0,0 +1,2 @@
+ALTER TABLE albums DROP COLUMN compilation;
+ALTER TABLE tracks DROP COLUMN track_artist_id;
@@ -0,0 +1,2 @@
+ALTER TABLE tracks ADD COLUMN track_artist_id BIGINT DEFAULT NULL;
+ALTER TABLE albums ADD COLUMN compilation BIGINT NOT NULL DEFAULT 0;
@@ -0,0 +1,10 @@
+DROP TABLE album_enrichment_candidates;
+
+ALTER TABLE tracks DROP COLUMN musicbrainz_recording_id;
+ALTER TABLE albums DROP COLUMN original_release_date;
+ALTER TABLE albums DROP COLUMN country;
+ALTER TABLE albums DROP COLUMN label;
+ALTER TABLE albums DROP COLUMN release_type;
+ALTER TABLE albums DROP COLUMN musicbrainz_release_group_id;
+ALTER TABLE albums DROP COLUMN musicbrainz_release_id;
+ALTER TABLE artists DROP COLUMN musicbrainz_id;
@@ -0,0 +1,18 @@
+ALTER TABLE artists ADD COLUMN musicbrainz_id TEXT DEFAULT NULL;
+ALTER TABLE albums ADD COLUMN musicbrainz_release_id TEXT DEFAULT NULL;
+ALTER TABLE albums ADD COLUMN musicbrainz_release_group_id TEXT DEFAULT NULL;
+ALTER TABLE albums ADD COLUMN release_type TEXT DEFAULT NULL;
+ALTER TABLE albums ADD COLUMN label TEXT DEFAULT NULL;
+ALTER TABLE albums ADD COLUMN country TEXT DEFAULT NULL;
+ALTER TABLE albums ADD COLUMN original_release_date TEXT DEFAULT NULL;
+ALTER TABLE tracks ADD COLUMN musicbrainz_recording_id TEXT DEFAULT NULL;
+
+CREATE TABLE IF NOT EXISTS album_enrichment_candidates (
+    "id" BIGSERIAL PRIMARY KEY NOT NULL,
+    "album_id" BIGINT NOT NULL,
+    "release_id" TEXT NOT NULL,
+    "score" DOUBLE PRECISION NOT NULL,
+    "release" TEXT NOT NULL,
+    created TIMESTAMP NOT NULL DEFAULT NOW()
+);
+CREATE INDEX ix_album_enrichment_candidates_album_id ON album_enrichment_candidates("album_id");
@@ -0,0 +1,8 @@
+DROP INDEX IF EXISTS ux_artist_ratings;
+DROP TABLE artist_ratings;
+
+DROP INDEX IF EXISTS ux_album_ratings;
+DROP TABLE album_ratings;
+
+DROP INDEX IF EXISTS ux_track_ratings;
+DROP TABLE track_ratings;
@@ -0,0 +1,32 @@
+CREATE TABLE IF NOT EXISTS track_ratings (
+    `id` INTEGER PRIMARY KEY NOT NULL,
+    `track_id` INTEGER NOT NULL,
+    `rating` INTEGER DEFAULT NULL,
+    `loved` INTEGER NOT NULL DEFAULT 0,
+    `banned` INTEGER NOT NULL DEFAULT 0,
+    `created` TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%f', 'now')),
+    `updated` TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%f', 'now'))
+);
+CREATE UNIQUE INDEX ux_track_ratings ON track_ratings(`track_id`);
+
+CREATE TABLE IF NOT EXISTS album_ratings (
+    `id` INTEGER PRIMARY KEY NOT NULL,
+    `album_id` INTEGER NOT NULL,
+    `rating` INTEGER DEFAULT NULL,
+    `loved` INTEGER NOT NULL DEFAULT 0,
+    `banned` INTEGER NOT NULL DEFAULT 0,
+    `created` TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%f', 'now')),
+    `updated` TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%f', 'now'))
+);
+CREATE UNIQUE INDEX ux_album_ratings ON album_ratings(`album_id`);
+
+CREATE TABLE IF NOT EXISTS artist_ratings (
+    `id` INTEGER PRIMARY KEY NOT NULL,
+    `artist_id` INTEGER NOT NULL,
+    `rating` INTEGER DEFAULT NULL,
+    `loved` INTEGER NOT NULL DEFAULT 0,
+    `banned` INTEGER NOT NULL DEFAULT 0,
+    `created` TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%f', 'now')),
+    `updated` TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%f', 'now'))
+);
+CREATE UNIQUE INDEX ux_artist_ratings ON artist_ratings(`artist_id`);
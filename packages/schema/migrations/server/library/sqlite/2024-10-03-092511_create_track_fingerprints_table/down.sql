@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS ux_track_fingerprints;
+DROP TABLE track_fingerprints;
@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS track_fingerprints (
+    `id` INTEGER PRIMARY KEY NOT NULL,
+    `track_id` INTEGER NOT NULL,
+    `fingerprint` TEXT NOT NULL,
+    `duration` REAL NOT NULL,
+    `created` TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%f', 'now')),
+    `updated` TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%f', 'now'))
+);
+CREATE UNIQUE INDEX ux_track_fingerprints ON track_fingerprints(`track_id`);
@@ -0,0 +1,2 @@
+ALTER TABLE tracks ADD COLUMN track_artist_id INTEGER DEFAULT NULL;
+ALTER TABLE albums ADD COLUMN compilation INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,2 @@
+ALTER TABLE tracks DROP COLUMN end_offset;
+ALTER TABLE tracks DROP COLUMN start_offset;
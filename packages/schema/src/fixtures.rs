@@ -0,0 +1,195 @@
+//! In-memory databases with the sqlite migrations applied, along with helpers
+//! to fill them with library data, for hermetic tests of database code.
+//!
+//! ```rust,ignore
+//! let db = moosicbox_schema::fixtures::library_db().await;
+//! let library = moosicbox_schema::fixtures::seed_library(&db).await.unwrap();
+//!
+//! assert_eq!(get_album_tracks(&db, &library.albums[0].into()).await?.len(), 3);
+//! ```
+
+use std::sync::Arc;
+
+use moosicbox_database::{
+    config::ConfigDatabase, profiles::LibraryDatabase, rusqlite::RusqliteDatabase, Database,
+    DatabaseError, Row,
+};
+use tokio::sync::Mutex;
+
+use crate::{run_migrations, SQLITE_CONFIG_MIGRATIONS, SQLITE_LIBRARY_MIGRATIONS};
+
+fn in_memory_db() -> Box<dyn Database> {
+    let connection =
+        rusqlite::Connection::open_in_memory().expect("Failed to open in-memory database");
+    Box::new(RusqliteDatabase::new(Arc::new(Mutex::new(connection))))
+}
+
+/// Creates an in-memory config database with all the sqlite config migrations
+/// applied.
+///
+/// # Panics
+///
+/// * If the database failed to be created or migrated
+pub async fn config_db() -> ConfigDatabase {
+    let db = in_memory_db();
+    run_migrations(&*db, SQLITE_CONFIG_MIGRATIONS)
+        .await
+        .expect("Failed to migrate config database");
+    ConfigDatabase::from(Arc::new(db))
+}
+
+/// Creates an in-memory library database with all the sqlite library
+/// migrations applied.
+///
+/// # Panics
+///
+/// * If the database failed to be created or migrated
+pub async fn library_db() -> LibraryDatabase {
+    let db = in_memory_db();
+    run_migrations(&*db, SQLITE_LIBRARY_MIGRATIONS)
+        .await
+        .expect("Failed to migrate library database");
+    LibraryDatabase::from(Arc::new(db))
+}
+
+fn row_id(row: &Row) -> Result<u64, DatabaseError> {
+    row.id()
+        .and_then(|x| u64::try_from(x).ok())
+        .ok_or(DatabaseError::NoRow)
+}
+
+/// Inserts an artist and returns its id.
+///
+/// # Errors
+///
+/// * If the artist failed to be inserted
+pub async fn insert_artist(db: &dyn Database, title: &str) -> Result<u64, DatabaseError> {
+    row_id(
+        &db.insert("artists")
+            .value("title", title)
+            .execute(db)
+            .await?,
+    )
+}
+
+/// Inserts an album by the given artist and returns its id.
+///
+/// # Errors
+///
+/// * If the album failed to be inserted
+pub async fn insert_album(
+    db: &dyn Database,
+    artist_id: u64,
+    title: &str,
+    date_released: Option<&str>,
+) -> Result<u64, DatabaseError> {
+    row_id(
+        &db.insert("albums")
+            .value("artist_id", artist_id)
+            .value("title", title)
+            .value("date_released", date_released)
+            .execute(db)
+            .await?,
+    )
+}
+
+/// Inserts a local track on the given album and returns its id.
+///
+/// # Errors
+///
+/// * If the track failed to be inserted
+pub async fn insert_track(
+    db: &dyn Database,
+    album_id: u64,
+    number: u32,
+    title: &str,
+    duration: f64,
+) -> Result<u64, DatabaseError> {
+    row_id(
+        &db.insert("tracks")
+            .value("album_id", album_id)
+            .value("number", number)
+            .value("title", title)
+            .value("duration", duration)
+            .value(
+                "file",
+                format!("/music/{album_id}/{number:02} {title}.flac"),
+            )
+            .value("format", "FLAC")
+            .execute(db)
+            .await?,
+    )
+}
+
+/// The ids of the rows inserted by [`seed_library`], in insertion order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SeededLibrary {
+    pub artists: Vec<u64>,
+    pub albums: Vec<u64>,
+    pub tracks: Vec<u64>,
+}
+
+/// `(artist, [(album, date_released, [(track, duration)])])`
+type LibraryFixture = (
+    &'static str,
+    &'static [(&'static str, &'static str, &'static [(&'static str, f64)])],
+);
+
+const LIBRARY: &[LibraryFixture] = &[
+    (
+        "The Test Pattern",
+        &[
+            (
+                "Calibration",
+                "2019-04-12",
+                &[("Intro", 61.5), ("Signal", 215.0), ("Noise Floor", 187.25)],
+            ),
+            (
+                "Second Pass",
+                "2021-09-03",
+                &[("Warm Up", 142.0), ("Feedback", 263.75)],
+            ),
+        ],
+    ),
+    (
+        "Fixture Ensemble",
+        &[(
+            "Known Values",
+            "2023-01-20",
+            &[
+                ("One", 201.0),
+                ("Two", 198.5),
+                ("Three", 305.0),
+                ("Four", 177.0),
+            ],
+        )],
+    ),
+];
+
+/// Fills the library database with a small, fixed library of 2 artists, 3
+/// albums and 9 tracks.
+///
+/// # Errors
+///
+/// * If any of the rows failed to be inserted
+pub async fn seed_library(db: &dyn Database) -> Result<SeededLibrary, DatabaseError> {
+    let mut seeded = SeededLibrary::default();
+
+    for (artist, albums) in LIBRARY {
+        let artist_id = insert_artist(db, artist).await?;
+        seeded.artists.push(artist_id);
+
+        for (album, date_released, tracks) in *albums {
+            let album_id = insert_album(db, artist_id, album, Some(date_released)).await?;
+            seeded.albums.push(album_id);
+
+            for (number, (title, duration)) in (1..).zip(*tracks) {
+                seeded
+                    .tracks
+                    .push(insert_track(db, album_id, number, title, *duration).await?);
+            }
+        }
+    }
+
+    Ok(seeded)
+}
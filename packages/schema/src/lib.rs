@@ -1,77 +1,266 @@
 #![cfg_attr(feature = "fail-on-warnings", deny(warnings))]
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
 
+#[cfg(feature = "test")]
+pub mod fixtures;
+
+use std::collections::BTreeSet;
+
+use moosicbox_database::{query::max, Database, DatabaseError};
 use thiserror::Error;
 
-#[cfg(feature = "postgres")]
-pub const POSTGRES_CONFIG_MIGRATIONS: diesel_migrations::EmbeddedMigrations =
-    diesel_migrations::embed_migrations!("migrations/server/config/postgres");
+/// The table applied migration versions are tracked in. This is the same
+/// table diesel tracks migrations in, so databases that were migrated with
+/// diesel continue from where they left off.
+pub const MIGRATIONS_TABLE: &str = "__diesel_schema_migrations";
 
-#[cfg(feature = "sqlite")]
-pub const SQLITE_CONFIG_MIGRATIONS: diesel_migrations::EmbeddedMigrations =
-    diesel_migrations::embed_migrations!("migrations/server/config/sqlite");
+const CREATE_MIGRATIONS_TABLE: &str = "CREATE TABLE IF NOT EXISTS __diesel_schema_migrations (
+    version VARCHAR(50) PRIMARY KEY NOT NULL,
+    run_on TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+)";
 
-#[cfg(feature = "postgres")]
-pub const POSTGRES_LIBRARY_MIGRATIONS: diesel_migrations::EmbeddedMigrations =
-    diesel_migrations::embed_migrations!("migrations/server/library/postgres");
+#[derive(Debug, Clone, Copy)]
+pub struct Migration {
+    /// The migration's timestamp with the separators removed, e.g.
+    /// `20231013195407` for `2023-10-13-195407_create_artists`
+    pub version: &'static str,
+    pub name: &'static str,
+    pub up: &'static str,
+    pub run_in_transaction: bool,
+}
 
-#[cfg(feature = "sqlite")]
-pub const SQLITE_LIBRARY_MIGRATIONS: diesel_migrations::EmbeddedMigrations =
-    diesel_migrations::embed_migrations!("migrations/server/library/sqlite");
+include!(concat!(env!("OUT_DIR"), "/migrations.rs"));
 
 #[derive(Debug, Error)]
 pub enum MigrateError {
-    #[error("Diesel migration error: {0:?}")]
-    DieselMigration(Box<dyn std::error::Error + Send + Sync>),
+    #[error(transparent)]
+    Database(#[from] DatabaseError),
+    #[error("No migrations for the enabled database features")]
+    NoMigrations,
 }
 
-#[cfg(any(feature = "postgres", feature = "sqlite"))]
-pub fn migrate_config(database_url: &str) -> Result<(), MigrateError> {
-    use diesel::Connection as _;
-    use diesel_migrations::MigrationHarness as _;
+#[cfg(any(feature = "mysql", feature = "postgres", feature = "sqlite"))]
+const fn config_migrations() -> &'static [Migration] {
+    #[cfg(feature = "postgres")]
+    return POSTGRES_CONFIG_MIGRATIONS;
+    #[cfg(all(not(feature = "postgres"), feature = "mysql"))]
+    return MYSQL_CONFIG_MIGRATIONS;
+    #[cfg(all(not(feature = "postgres"), not(feature = "mysql")))]
+    return SQLITE_CONFIG_MIGRATIONS;
+}
 
+#[cfg(any(feature = "mysql", feature = "postgres", feature = "sqlite"))]
+const fn library_migrations() -> &'static [Migration] {
     #[cfg(feature = "postgres")]
-    {
-        log::debug!("migrate_config: running postgres migrations");
-        let mut conn = diesel::PgConnection::establish(database_url).unwrap();
-        conn.run_pending_migrations(POSTGRES_CONFIG_MIGRATIONS)
-            .map_err(MigrateError::DieselMigration)?;
-        log::debug!("migrate_config: finished running postgres migrations");
+    return POSTGRES_LIBRARY_MIGRATIONS;
+    #[cfg(all(not(feature = "postgres"), feature = "mysql"))]
+    return MYSQL_LIBRARY_MIGRATIONS;
+    #[cfg(all(not(feature = "postgres"), not(feature = "mysql")))]
+    return SQLITE_LIBRARY_MIGRATIONS;
+}
+
+#[cfg(not(any(feature = "mysql", feature = "postgres", feature = "sqlite")))]
+const fn config_migrations() -> &'static [Migration] {
+    &[]
+}
+
+#[cfg(not(any(feature = "mysql", feature = "postgres", feature = "sqlite")))]
+const fn library_migrations() -> &'static [Migration] {
+    &[]
+}
+
+/// Runs the pending config schema migrations for the enabled database backend.
+///
+/// When multiple backends are enabled, the postgres migrations take precedence
+/// over the mysql ones, which take precedence over the sqlite ones.
+///
+/// # Errors
+///
+/// * If no database backend feature is enabled
+/// * If a migration failed to run
+pub async fn migrate_config(db: &dyn Database) -> Result<(), MigrateError> {
+    log::debug!("migrate_config: running migrations");
+    run_migrations(db, config_migrations()).await?;
+    log::debug!("migrate_config: finished running migrations");
+    Ok(())
+}
+
+/// Runs the pending library schema migrations for the enabled database
+/// backend.
+///
+/// # Errors
+///
+/// * If no database backend feature is enabled
+/// * If a migration failed to run
+pub async fn migrate_library(db: &dyn Database) -> Result<(), MigrateError> {
+    log::debug!("migrate_library: running migrations");
+    run_migrations(db, library_migrations()).await?;
+    log::debug!("migrate_library: finished running migrations");
+    Ok(())
+}
+
+/// Runs the given migrations, in order, that haven't been applied yet.
+///
+/// Each migration is applied in its own transaction along with the record of
+/// it being applied, unless it opts out of running in a transaction.
+///
+/// # Errors
+///
+/// * If `migrations` is empty
+/// * If a migration failed to run
+pub async fn run_migrations(
+    db: &dyn Database,
+    migrations: &[Migration],
+) -> Result<(), MigrateError> {
+    if migrations.is_empty() {
+        return Err(MigrateError::NoMigrations);
     }
 
-    #[cfg(feature = "sqlite")]
+    db.exec_raw(CREATE_MIGRATIONS_TABLE).await?;
+
+    let applied = db
+        .select(MIGRATIONS_TABLE)
+        .columns(&["version"])
+        .execute(db)
+        .await?
+        .into_iter()
+        .filter_map(|row| {
+            row.get("version")
+                .and_then(|x| x.as_str().map(ToString::to_string))
+        })
+        .collect::<BTreeSet<_>>();
+
+    for migration in migrations
+        .iter()
+        .filter(|migration| !applied.contains(migration.version))
     {
-        log::debug!("migrate_config: running sqlite migrations");
-        let mut conn = diesel::SqliteConnection::establish(database_url).unwrap();
-        conn.run_pending_migrations(SQLITE_CONFIG_MIGRATIONS)
-            .map_err(MigrateError::DieselMigration)?;
-        log::debug!("migrate_config: finished running sqlite migrations");
+        log::debug!("run_migrations: running migration {}", migration.name);
+
+        // The version is validated to only contain digits at build time
+        let record = format!(
+            "INSERT INTO {MIGRATIONS_TABLE} (version) VALUES ('{}')",
+            migration.version
+        );
+
+        if migration.run_in_transaction {
+            let tx = db.begin_transaction().await?;
+            tx.exec_raw(migration.up).await?;
+            tx.exec_raw(&record).await?;
+            tx.commit().await?;
+        } else {
+            db.exec_raw(migration.up).await?;
+            db.exec_raw(&record).await?;
+        }
     }
 
     Ok(())
 }
 
-#[cfg(any(feature = "postgres", feature = "sqlite"))]
-pub fn migrate_library(database_url: &str) -> Result<(), MigrateError> {
-    use diesel::Connection as _;
-    use diesel_migrations::MigrationHarness as _;
+/// Returns the version of the most recently applied migration, or `None` if
+/// no migrations have been applied.
+///
+/// # Errors
+///
+/// * If the migrations table failed to be queried
+pub async fn get_schema_version(db: &dyn Database) -> Result<Option<String>, MigrateError> {
+    Ok(db
+        .select(MIGRATIONS_TABLE)
+        .aggregate("version", max("version"))
+        .execute_first(db)
+        .await?
+        .and_then(|row| {
+            row.get("version")
+                .and_then(|x| x.as_str().map(ToString::to_string))
+        }))
+}
 
-    #[cfg(feature = "postgres")]
-    {
-        log::debug!("migrate_library: running postgres migrations");
-        let mut conn = diesel::PgConnection::establish(database_url).unwrap();
-        conn.run_pending_migrations(POSTGRES_LIBRARY_MIGRATIONS)
-            .map_err(MigrateError::DieselMigration)?;
-        log::debug!("migrate_library: finished running postgres migrations");
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use moosicbox_database::{rusqlite::RusqliteDatabase, Database};
+    use pretty_assertions::assert_eq;
+    use tokio::sync::Mutex;
+
+    use super::*;
+
+    fn new_db() -> RusqliteDatabase {
+        let connection = rusqlite::Connection::open_in_memory().unwrap();
+        RusqliteDatabase::new(Arc::new(Mutex::new(connection)))
     }
 
-    #[cfg(feature = "sqlite")]
-    {
-        log::debug!("migrate_library: running sqlite migrations");
-        let mut conn = diesel::SqliteConnection::establish(database_url).unwrap();
-        conn.run_pending_migrations(SQLITE_LIBRARY_MIGRATIONS)
-            .map_err(MigrateError::DieselMigration)?;
-        log::debug!("migrate_library: finished running sqlite migrations");
+    async fn applied_versions(db: &dyn Database) -> Vec<String> {
+        db.select(MIGRATIONS_TABLE)
+            .columns(&["version"])
+            .execute(db)
+            .await
+            .unwrap()
+            .into_iter()
+            .filter_map(|row| {
+                row.get("version")
+                    .unwrap()
+                    .as_str()
+                    .map(ToString::to_string)
+            })
+            .collect()
     }
 
-    Ok(())
+    #[test_log::test(tokio::test)]
+    async fn runs_sqlite_config_migrations_once() {
+        let db = new_db();
+
+        migrate_config(&db).await.unwrap();
+        migrate_config(&db).await.unwrap();
+
+        let mut versions = applied_versions(&db).await;
+        versions.sort();
+        assert_eq!(
+            versions,
+            SQLITE_CONFIG_MIGRATIONS
+                .iter()
+                .map(|x| x.version.to_string())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            get_schema_version(&db).await.unwrap().as_deref(),
+            SQLITE_CONFIG_MIGRATIONS.last().map(|x| x.version)
+        );
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn runs_sqlite_library_migrations() {
+        let db = new_db();
+
+        migrate_library(&db).await.unwrap();
+
+        assert_eq!(
+            get_schema_version(&db).await.unwrap().as_deref(),
+            SQLITE_LIBRARY_MIGRATIONS.last().map(|x| x.version)
+        );
+        db.select("tracks")
+            .execute(&db as &dyn Database)
+            .await
+            .unwrap();
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn skips_migrations_applied_by_diesel() {
+        let db = new_db();
+        let first = &SQLITE_LIBRARY_MIGRATIONS[0];
+        db.exec_raw(CREATE_MIGRATIONS_TABLE).await.unwrap();
+        db.exec_raw(first.up).await.unwrap();
+        db.exec_raw(&format!(
+            "INSERT INTO {MIGRATIONS_TABLE} (version) VALUES ('{}')",
+            first.version
+        ))
+        .await
+        .unwrap();
+
+        migrate_library(&db).await.unwrap();
+
+        assert_eq!(
+            applied_versions(&db).await.len(),
+            SQLITE_LIBRARY_MIGRATIONS.len()
+        );
+    }
 }
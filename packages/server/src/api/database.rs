@@ -0,0 +1,37 @@
+//! The statement counters recorded by the
+//! [`TracedDatabase`](moosicbox_database::trace::TracedDatabase)s of the
+//! server. They are bound here instead of in `moosicbox_database` so that they
+//! can be limited to requests that don't come through the tunnel.
+
+use actix_web::{
+    dev::{ServiceFactory, ServiceRequest},
+    route,
+    web::Json,
+    Result, Scope,
+};
+use moosicbox_auth::NonTunnelRequestAuthorized;
+use moosicbox_database::trace::{StatementStats, QUERY_STATS};
+use serde_json::{json, Value};
+
+pub fn bind_services<
+    T: ServiceFactory<ServiceRequest, Config = (), Error = actix_web::Error, InitError = ()>,
+>(
+    scope: Scope<T>,
+) -> Scope<T> {
+    scope
+        .service(query_stats_endpoint)
+        .service(reset_query_stats_endpoint)
+}
+
+#[route("/query-stats", method = "GET")]
+pub async fn query_stats_endpoint(
+    _: NonTunnelRequestAuthorized,
+) -> Result<Json<Vec<StatementStats>>> {
+    Ok(Json(QUERY_STATS.snapshot()))
+}
+
+#[route("/query-stats", method = "DELETE")]
+pub async fn reset_query_stats_endpoint(_: NonTunnelRequestAuthorized) -> Result<Json<Value>> {
+    QUERY_STATS.reset();
+    Ok(Json(json!({"success": true})))
+}
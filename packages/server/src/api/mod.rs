@@ -6,16 +6,33 @@ use actix_web::{
 };
 use actix_web::{route, HttpResponse};
 use log::info;
-use moosicbox_database::profiles::api::ProfileName;
+use moosicbox_database::{config::ConfigDatabase, profiles::api::ProfileName};
 use serde_json::{json, Value};
 
+#[cfg(feature = "database-api")]
+pub mod database;
 #[cfg(feature = "openapi")]
 pub mod openapi;
 
 #[route("/health", method = "GET")]
-pub async fn health_endpoint() -> Result<Json<Value>> {
+pub async fn health_endpoint(
+    #[allow(unused)] config_database: ConfigDatabase,
+) -> Result<Json<Value>> {
+    #[cfg(any(feature = "mysql", feature = "postgres", feature = "sqlite"))]
+    let schema_version = moosicbox_schema::get_schema_version(&*config_database)
+        .await
+        .map_err(|e| {
+            actix_web::error::ErrorInternalServerError(format!(
+                "Failed to get schema version: {e:?}"
+            ))
+        })?;
+    #[cfg(not(any(feature = "mysql", feature = "postgres", feature = "sqlite")))]
+    let schema_version: Option<String> = None;
+
     info!("Healthy");
-    Ok(Json(json!({"healthy": true})))
+    Ok(Json(
+        json!({"healthy": true, "schemaVersion": schema_version}),
+    ))
 }
 
 #[allow(clippy::future_not_send)]
@@ -38,6 +38,8 @@ pub fn init() -> OpenApi {
     );
     #[cfg(feature = "auth-api")]
     let api = nest_api(api, "/auth", moosicbox_auth::api::Api::openapi());
+    #[cfg(feature = "backup-api")]
+    let api = nest_api(api, "/backup", moosicbox_backup::api::Api::openapi());
     #[cfg(feature = "downloader-api")]
     let api = nest_api(
         api,
@@ -46,6 +48,12 @@ pub fn init() -> OpenApi {
     );
     #[cfg(feature = "config-api")]
     let api = nest_api(api, "/config", moosicbox_config::api::Api::openapi());
+    #[cfg(feature = "enrichment-api")]
+    let api = nest_api(
+        api,
+        "/enrichment",
+        moosicbox_enrichment::api::Api::openapi(),
+    );
     #[cfg(feature = "files-api")]
     let api = nest_api(api, "/files", moosicbox_files::api::Api::openapi());
     #[cfg(feature = "library-api")]
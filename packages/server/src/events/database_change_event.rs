@@ -0,0 +1,65 @@
+use moosicbox_database::{changes::DATABASE_CHANGES, config::ConfigDatabase};
+use tokio::sync::broadcast::error::{RecvError, TryRecvError};
+
+use crate::{CANCELLATION_TOKEN, WS_SERVER_HANDLE};
+
+/// Pushes the data affected by every change published to the
+/// [`DATABASE_CHANGES`] feed to the websocket clients. Changes published
+/// while a broadcast is in progress are sent together in the next one. If
+/// the feed lagged and changes were missed, a full resync is sent instead.
+pub fn init(config_db: &ConfigDatabase) {
+    let config_db = config_db.to_owned();
+    let mut receiver = DATABASE_CHANGES.subscribe();
+
+    moosicbox_task::spawn("server: database changes", async move {
+        loop {
+            let change = tokio::select! {
+                () = CANCELLATION_TOKEN.cancelled() => break,
+                change = receiver.recv() => change,
+            };
+
+            let mut lagged = false;
+            let mut changes = match change {
+                Ok(change) => vec![change],
+                Err(RecvError::Lagged(count)) => {
+                    log::warn!("database_change_event: Missed {count} database changes");
+                    lagged = true;
+                    vec![]
+                }
+                Err(RecvError::Closed) => break,
+            };
+
+            loop {
+                match receiver.try_recv() {
+                    Ok(change) => changes.push(change),
+                    Err(TryRecvError::Lagged(count)) => {
+                        log::warn!("database_change_event: Missed {count} database changes");
+                        lagged = true;
+                    }
+                    Err(TryRecvError::Empty | TryRecvError::Closed) => break,
+                }
+            }
+
+            log::debug!(
+                "database_change_event: {} changes (lagged={lagged})",
+                changes.len()
+            );
+
+            let Some(handle) = WS_SERVER_HANDLE.read().await.clone() else {
+                continue;
+            };
+
+            // Missed changes can't be sent, so the clients are told to reload
+            // everything instead, which covers the received changes too
+            let result = if lagged {
+                moosicbox_ws::broadcast_database_resync(&config_db, &handle).await
+            } else {
+                moosicbox_ws::broadcast_database_changes(&config_db, &handle, &changes).await
+            };
+
+            if let Err(e) = result {
+                log::error!("Failed to broadcast database changes: {e:?}");
+            }
+        }
+    });
+}
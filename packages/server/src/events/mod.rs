@@ -1,4 +1,4 @@
-pub mod audio_zone_event;
+pub mod database_change_event;
 #[cfg(feature = "downloader")]
 pub mod download_event;
 #[cfg(feature = "player")]
@@ -6,4 +6,3 @@ pub mod playback_event;
 pub mod profiles_event;
 #[cfg(feature = "scan")]
 pub mod scan_event;
-pub mod session_event;
@@ -37,6 +37,43 @@ static CONFIG_DB: LazyLock<std::sync::RwLock<Option<ConfigDatabase>>> =
 
 static SERVER_ID: std::sync::OnceLock<String> = std::sync::OnceLock::new();
 
+/// Wraps the database in a [`TracedDatabase`] when `QUERY_TRACING=1` is set,
+/// logging the statements slower than `SLOW_QUERY_THRESHOLD_MS` milliseconds.
+///
+/// [`TracedDatabase`]: moosicbox_database::trace::TracedDatabase
+fn trace_database(db: Box<dyn Database>) -> Box<dyn Database> {
+    if std::env::var("QUERY_TRACING").as_deref() != Ok("1") {
+        return db;
+    }
+
+    let mut db = moosicbox_database::trace::TracedDatabase::new(db);
+
+    if let Some(threshold) = std::env::var("SLOW_QUERY_THRESHOLD_MS")
+        .ok()
+        .and_then(|x| x.parse::<u64>().ok())
+    {
+        db = db.with_slow_query_threshold(std::time::Duration::from_millis(threshold));
+    }
+
+    Box::new(db)
+}
+
+/// Wraps the database in [`trace_database`] and a [`ChangeFeedDatabase`]
+/// publishing its changes to the [`DATABASE_CHANGES`] feed, which the server
+/// pushes to the websocket clients. The changes of a library database are
+/// tagged with its `profile` so they only reach that profile's clients.
+///
+/// [`ChangeFeedDatabase`]: moosicbox_database::changes::ChangeFeedDatabase
+/// [`DATABASE_CHANGES`]: moosicbox_database::changes::DATABASE_CHANGES
+pub(crate) fn wrap_database(db: Box<dyn Database>, profile: Option<&str>) -> Box<dyn Database> {
+    let db = moosicbox_database::changes::ChangeFeedDatabase::new(trace_database(db));
+
+    match profile {
+        Some(profile) => Box::new(db.with_profile(profile)),
+        None => Box::new(db),
+    }
+}
+
 #[allow(clippy::too_many_lines)]
 #[allow(clippy::missing_panics_doc)]
 #[allow(clippy::missing_errors_doc)]
@@ -50,16 +87,7 @@ pub async fn run(
     on_startup: impl FnOnce() + Send,
 ) -> std::io::Result<()> {
     #[cfg(all(not(feature = "postgres"), feature = "sqlite"))]
-    let config_db_path = {
-        let path = db::make_config_db_path(app_type).expect("Failed to get DB config path");
-
-        let path_str = path.to_str().expect("Failed to get DB path_str");
-        if let Err(e) = moosicbox_schema::migrate_config(path_str) {
-            moosicbox_assert::die_or_panic!("Failed to migrate database: {e:?}");
-        };
-
-        path
-    };
+    let config_db_path = db::make_config_db_path(app_type).expect("Failed to get DB config path");
 
     let config_db = moosicbox_database_connection::init(
         #[cfg(all(not(feature = "postgres"), feature = "sqlite"))]
@@ -69,7 +97,12 @@ pub async fn run(
     .await
     .expect("Failed to initialize database");
 
-    let config_database: Arc<Box<dyn Database>> = Arc::new(config_db);
+    #[cfg(any(feature = "mysql", feature = "postgres", feature = "sqlite"))]
+    if let Err(e) = moosicbox_schema::migrate_config(&*config_db).await {
+        moosicbox_assert::die_or_panic!("Failed to migrate database: {e:?}");
+    };
+
+    let config_database: Arc<Box<dyn Database>> = Arc::new(wrap_database(config_db, None));
     let config_database = ConfigDatabase {
         database: config_database,
     };
@@ -110,8 +143,7 @@ pub async fn run(
     #[cfg(feature = "scan")]
     events::scan_event::init().await;
 
-    events::audio_zone_event::init(&config_database).await;
-    events::session_event::init().await;
+    events::database_change_event::init(&config_database);
 
     #[cfg(feature = "tunnel")]
     let (tunnel_host, tunnel_join_handle, tunnel_handle) =
@@ -140,6 +172,20 @@ pub async fn run(
         (handle, join_handle)
     };
 
+    {
+        let max_size = std::env::var("TRANSCODE_CACHE_MAX_SIZE_MB")
+            .ok()
+            .and_then(|x| x.parse::<u64>().ok())
+            .map_or(
+                moosicbox_files::files::transcode_cache::DEFAULT_MAX_SIZE,
+                |x| x * 1024 * 1024,
+            );
+
+        if let Err(e) = moosicbox_files::files::transcode_cache::init(max_size) {
+            log::error!("Failed to initialize the transcode cache: {e:?}");
+        }
+    }
+
     #[cfg(feature = "upnp")]
     let (upnp_service_handle, join_upnp_service) = if upnp_players {
         let upnp_service =
@@ -284,14 +330,25 @@ pub async fn run(
             #[cfg(feature = "auth-api")]
             let app = app.service(moosicbox_auth::api::bind_services(web::scope("/auth")));
 
+            #[cfg(feature = "backup-api")]
+            let app = app.service(moosicbox_backup::api::bind_services(web::scope("/backup")));
+
             #[cfg(feature = "config-api")]
             let app = app.service(moosicbox_config::api::bind_services(web::scope("/config")));
 
+            #[cfg(feature = "database-api")]
+            let app = app.service(api::database::bind_services(web::scope("/database")));
+
             #[cfg(feature = "downloader-api")]
             let app = app.service(moosicbox_downloader::api::bind_services(web::scope(
                 "/downloader",
             )));
 
+            #[cfg(feature = "enrichment-api")]
+            let app = app.service(moosicbox_enrichment::api::bind_services(web::scope(
+                "/enrichment",
+            )));
+
             #[cfg(feature = "files-api")]
             let app = app.service(moosicbox_files::api::bind_services(web::scope("/files")));
 
@@ -419,9 +476,24 @@ pub async fn run(
         |x| x.to_string(),
     );
 
-    if let Err(e) =
-        moosicbox_mdns::register_service(SERVER_ID.get().expect("No SERVER_ID"), &ip, service_port)
-    {
+    // Clients pin the signing key when they discover the server, so it's
+    // only advertised on the local network
+    #[cfg(feature = "e2e")]
+    let signing_key = moosicbox_tunnel_sender::e2e::get_or_init_signing_key(&config_database)
+        .await
+        .expect("Failed to get or init E2E signing key")
+        .public_key();
+    #[cfg(feature = "e2e")]
+    let properties = [(moosicbox_mdns::SIGNING_KEY_PROPERTY, signing_key.as_str())];
+    #[cfg(not(feature = "e2e"))]
+    let properties = [];
+
+    if let Err(e) = moosicbox_mdns::register_service(
+        SERVER_ID.get().expect("No SERVER_ID"),
+        &ip,
+        service_port,
+        &properties,
+    ) {
         moosicbox_assert::die_or_error!("Failed to register mdns service: {e:?}");
     }
 
@@ -292,11 +292,5 @@ pub async fn register_server_player(
         handle.add_player_action(player.id, handle_server_playback_update);
     }
 
-    for profile in PROFILES.names() {
-        if let Some(db) = PROFILES.get(&profile) {
-            moosicbox_ws::broadcast_sessions(&db, &handle, &context, true).await?;
-        }
-    }
-
     Ok(())
 }
@@ -288,11 +288,5 @@ pub async fn register_upnp_player(
         }
     }
 
-    for profile in PROFILES.names() {
-        if let Some(db) = PROFILES.get(&profile) {
-            moosicbox_ws::broadcast_sessions(&db, &handle, &context, true).await?;
-        }
-    }
-
     Ok(())
 }
@@ -73,6 +73,12 @@ pub async fn setup_tunnel(
 
             tunnel = tunnel.with_cancellation_token(CANCELLATION_TOKEN.clone());
 
+            #[cfg(feature = "e2e")]
+            {
+                tunnel = tunnel
+                    .with_require_e2e(std::env::var("TUNNEL_REQUIRE_E2E").as_deref() == Ok("1"));
+            }
+
             Ok((
                 Some(host),
                 Some(moosicbox_task::spawn("server: tunnel", async move {
@@ -94,6 +100,7 @@ pub async fn setup_tunnel(
                                                     request.path,
                                                     request.query,
                                                     request.payload,
+                                                    request.streaming_body,
                                                     request.headers,
                                                     request.profile,
                                                     request.encoding,
@@ -131,13 +138,40 @@ pub async fn setup_tunnel(
                                             log::debug!("Aborting request {}", request.request_id);
                                             tunnel.abort_request(request.request_id);
                                         }
+                                        TunnelRequest::Handshake(handshake) => {
+                                            tunnel.handshake(&handshake);
+                                        }
+                                        TunnelRequest::WindowUpdate(update) => {
+                                            tunnel.update_window(&update);
+                                        }
+                                        #[cfg(feature = "e2e")]
+                                        TunnelRequest::Encrypted(request) => {
+                                            if let Err(err) = tunnel
+                                                .encrypted_tunnel_request(service_port, request)
+                                                .await
+                                            {
+                                                log::error!(
+                                                    "Encrypted tunnel request failed: {err:?}"
+                                                );
+                                            }
+                                        }
+                                        #[cfg(not(feature = "e2e"))]
+                                        TunnelRequest::Encrypted(request) => {
+                                            log::warn!(
+                                                "Ignoring encrypted request {}: E2E encryption is not enabled",
+                                                request.request_id
+                                            );
+                                        }
                                     }
                                     Ok::<_, String>(())
                                 });
                             }
-                            TunnelMessage::Binary(_) => {
-                                unimplemented!("Binary TunnelMessage is not implemented")
-                            }
+                            TunnelMessage::Binary(bytes) => match bytes.try_into() {
+                                Ok(body) => tunnel.request_body(body),
+                                Err(err) => {
+                                    log::error!("Dropping malformed request body packet: {err:?}");
+                                }
+                            },
                             TunnelMessage::Ping(_) | TunnelMessage::Pong(_) => {}
                             TunnelMessage::Close => {
                                 log::info!("Tunnel connection was closed");
@@ -42,6 +42,14 @@ impl WebsocketSender for WsServer {
         Ok(())
     }
 
+    async fn send_all_profile(&self, profile: &str, data: &str) -> Result<(), WebsocketSendError> {
+        self.send_profile_message(profile, data.to_string());
+        for sender in &self.senders {
+            sender.send_all_profile(profile, data).await?;
+        }
+        Ok(())
+    }
+
     async fn send_all_except(
         &self,
         connection_id: &str,
@@ -97,6 +105,12 @@ pub enum Command {
         res_tx: OneshotAsyncSender<()>,
     },
 
+    BroadcastProfile {
+        profile: String,
+        msg: Msg,
+        res_tx: OneshotAsyncSender<()>,
+    },
+
     BroadcastExcept {
         msg: Msg,
         conn: ConnId,
@@ -225,6 +239,18 @@ impl WsServer {
         }
     }
 
+    /// Send message to every connection using the profile.
+    fn send_profile_message(&self, profile: &str, msg: impl Into<String>) {
+        let msg = msg.into();
+
+        for connection in self.connections.values() {
+            if connection.profile == profile {
+                // errors if client disconnected abruptly and hasn't been timed-out yet
+                let _ = connection.sender.send(msg.clone());
+            }
+        }
+    }
+
     /// Send message directly to the user.
     fn send_message_to(&self, id: ConnId, msg: impl Into<String>) {
         if let Some(Connection { sender, .. }) = self.connections.get(&id) {
@@ -321,6 +347,7 @@ impl WsServer {
         Ok(())
     }
 
+    #[allow(clippy::too_many_lines)]
     async fn process_command(ctx: Arc<RwLock<Self>>, cmd: Command) -> io::Result<()> {
         let cmd_str = cmd.to_string();
 
@@ -384,6 +411,20 @@ impl WsServer {
                 let _ = res_tx.send(()).await;
             }
 
+            Command::BroadcastProfile {
+                profile,
+                msg,
+                res_tx,
+            } => {
+                let response = ctx.read().await.send_all_profile(&profile, &msg).await;
+                if let Err(error) = response {
+                    moosicbox_assert::die_or_error!(
+                        "Failed to broadcast message to profile {profile} {msg:?}: {error:?}",
+                    );
+                }
+                let _ = res_tx.send(()).await;
+            }
+
             Command::BroadcastExcept { msg, conn, res_tx } => {
                 let response = ctx
                     .read()
@@ -474,6 +515,16 @@ impl WebsocketSender for WsServerHandle {
         Ok(())
     }
 
+    async fn send_all_profile(&self, profile: &str, data: &str) -> Result<(), WebsocketSendError> {
+        if log::log_enabled!(log::Level::Trace) {
+            log::trace!("Broadcasting message to profile {profile}: {data}");
+        } else {
+            log::debug!("Broadcasting message to profile {profile}");
+        }
+        self.broadcast_profile(profile, data.to_string()).await;
+        Ok(())
+    }
+
     async fn send_all_except(
         &self,
         connection_id: &str,
@@ -579,6 +630,33 @@ impl WsServerHandle {
         });
     }
 
+    pub async fn broadcast_profile(&self, profile: &str, msg: impl Into<String> + Send) {
+        log::trace!("Sending BroadcastProfile command");
+        let (res_tx, res_rx) = kanal::oneshot_async();
+
+        moosicbox_task::spawn("ws server broadcast_profile", {
+            let cmd_tx = self.cmd_tx.clone();
+            let profile = profile.to_string();
+            let msg = msg.into();
+            async move {
+                if let Err(e) = cmd_tx
+                    .send_async(Command::BroadcastProfile {
+                        profile,
+                        msg,
+                        res_tx,
+                    })
+                    .await
+                {
+                    moosicbox_assert::die_or_error!("Failed to send command: {e:?}");
+                }
+            }
+        });
+
+        res_rx.recv().await.unwrap_or_else(|e| {
+            moosicbox_assert::die_or_error!("Failed to recv response from ws server: {e:?}");
+        });
+    }
+
     pub async fn broadcast_except(&self, conn: ConnId, msg: impl Into<String> + Send) {
         log::trace!("Sending BroadcastExcept command");
         let (res_tx, res_rx) = kanal::oneshot_async();
@@ -448,3 +448,79 @@ pub async fn delete_session_playlist_tracks_by_track_id(
         .await?
         .to_value_type()?)
 }
+
+#[cfg(test)]
+mod test {
+    use moosicbox_schema::fixtures;
+    use pretty_assertions::assert_eq;
+
+    use crate::models::CreateSessionPlaylist;
+
+    use super::*;
+
+    #[test_log::test(tokio::test)]
+    async fn create_session_adds_playlist_tracks() {
+        let db = fixtures::library_db().await;
+        let library = fixtures::seed_library(&*db).await.unwrap();
+
+        let session = create_session(
+            &db,
+            &CreateSession {
+                name: "Test Session".to_string(),
+                audio_zone_id: None,
+                playlist: CreateSessionPlaylist {
+                    tracks: library.tracks[..3].to_vec(),
+                },
+            },
+        )
+        .await
+        .unwrap();
+
+        let tracks = get_session_playlist_tracks(&db, session.playlist.id)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            tracks.into_iter().map(|x| x.id).collect::<Vec<_>>(),
+            library.tracks[..3]
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            get_session(&db, session.id).await.unwrap().map(|x| x.name),
+            Some("Test Session".to_string())
+        );
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn get_sessions_returns_all_sessions() {
+        let db = fixtures::library_db().await;
+        let library = fixtures::seed_library(&*db).await.unwrap();
+
+        for (name, tracks) in [("First", &library.tracks[..2]), ("Second", &[][..])] {
+            create_session(
+                &db,
+                &CreateSession {
+                    name: name.to_string(),
+                    audio_zone_id: None,
+                    playlist: CreateSessionPlaylist {
+                        tracks: tracks.to_vec(),
+                    },
+                },
+            )
+            .await
+            .unwrap();
+        }
+
+        assert_eq!(
+            get_sessions(&db)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|x| (x.name, x.playlist.tracks.len()))
+                .collect::<Vec<_>>(),
+            vec![("First".to_string(), 2), ("Second".to_string(), 0)]
+        );
+    }
+}
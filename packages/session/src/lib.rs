@@ -14,9 +14,6 @@ pub mod models;
 #[cfg(feature = "api")]
 pub mod api;
 
-#[cfg(feature = "events")]
-pub mod events;
-
 pub async fn get_session_playlist_tracks(
     db: &LibraryDatabase,
     session_playlist_id: u64,
@@ -115,15 +112,6 @@ pub async fn create_player(
 ) -> Result<Player, DbError> {
     let result = crate::db::create_player(db, connection_id, player).await?;
 
-    #[cfg(feature = "events")]
-    {
-        moosicbox_task::spawn("create_player updated_events", async move {
-            if let Err(e) = crate::events::trigger_players_updated_event().await {
-                moosicbox_assert::die_or_error!("Failed to trigger event: {e:?}");
-            }
-        });
-    }
-
     Ok(result)
 }
 
@@ -138,30 +126,12 @@ pub async fn create_players(
         results.push(crate::db::create_player(db, connection_id, player).await?);
     }
 
-    #[cfg(feature = "events")]
-    {
-        moosicbox_task::spawn("create_players updated_events", async move {
-            if let Err(e) = crate::events::trigger_players_updated_event().await {
-                moosicbox_assert::die_or_error!("Failed to trigger event: {e:?}");
-            }
-        });
-    }
-
     Ok(results)
 }
 
 pub async fn delete_player(db: &ConfigDatabase, player_id: u64) -> Result<(), DbError> {
     crate::db::delete_player(db, player_id).await?;
 
-    #[cfg(feature = "events")]
-    {
-        moosicbox_task::spawn("delete_player updated_events", async move {
-            if let Err(e) = crate::events::trigger_players_updated_event().await {
-                moosicbox_assert::die_or_error!("Failed to trigger event: {e:?}");
-            }
-        });
-    }
-
     Ok(())
 }
 
@@ -11,7 +11,7 @@ use moosicbox_core::{
 };
 use moosicbox_database::{config::ConfigDatabase, AsId, Database, DatabaseValue};
 use moosicbox_json_utils::{
-    database::{DatabaseFetchError, ToValue as _},
+    database::{DatabaseFetchError, FromRow, ToValue as _},
     ParseError, ToValueType,
 };
 use moosicbox_library::{
@@ -490,22 +490,14 @@ impl ToApi<ApiSession> for Session {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default, FromRow)]
 #[serde(rename_all = "camelCase")]
 pub struct SessionPlaylist {
     pub id: u64,
+    #[row(skip)]
     pub tracks: Vec<ApiTrack>,
 }
 
-impl ToValueType<SessionPlaylist> for &moosicbox_database::Row {
-    fn to_value_type(self) -> Result<SessionPlaylist, ParseError> {
-        Ok(SessionPlaylist {
-            id: self.to_value("id")?,
-            ..Default::default()
-        })
-    }
-}
-
 #[derive(Debug)]
 pub struct SessionPlaylistTracks(Vec<SessionPlaylistTrack>);
 
@@ -568,9 +560,10 @@ impl AsId for SessionPlaylist {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, FromRow)]
 #[serde(rename_all = "camelCase")]
 pub struct SessionPlaylistTrack {
+    #[row(rename = "track_id")]
     pub id: String,
     pub r#type: ApiSource,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -680,23 +673,9 @@ impl ToApi<ApiTrack> for SessionPlaylistTrack {
     }
 }
 
-impl ToValueType<SessionPlaylistTrack> for &moosicbox_database::Row {
-    fn to_value_type(self) -> Result<SessionPlaylistTrack, ParseError> {
-        Ok(SessionPlaylistTrack {
-            id: self.to_value("track_id")?,
-            r#type: self.to_value("type")?,
-            data: self.to_value("data")?,
-        })
-    }
-}
-
 impl AsModelResult<SessionPlaylistTrack, ParseError> for &moosicbox_database::Row {
     fn as_model(&self) -> Result<SessionPlaylistTrack, ParseError> {
-        Ok(SessionPlaylistTrack {
-            id: self.to_value("track_id")?,
-            r#type: self.to_value("type")?,
-            data: self.to_value("data")?,
-        })
+        SessionPlaylistTrack::from_row(self)
     }
 }
 
@@ -745,13 +724,14 @@ pub struct RegisterConnection {
     pub players: Vec<RegisterPlayer>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default, FromRow)]
 #[serde(rename_all = "camelCase")]
 pub struct Connection {
     pub id: String,
     pub name: String,
     pub created: String,
     pub updated: String,
+    #[row(skip)]
     pub players: Vec<Player>,
 }
 
@@ -770,18 +750,6 @@ impl AsModelQuery<Connection> for &moosicbox_database::Row {
     }
 }
 
-impl ToValueType<Connection> for &moosicbox_database::Row {
-    fn to_value_type(self) -> Result<Connection, ParseError> {
-        Ok(Connection {
-            id: self.to_value("id")?,
-            name: self.to_value("name")?,
-            created: self.to_value("created")?,
-            updated: self.to_value("updated")?,
-            ..Default::default()
-        })
-    }
-}
-
 impl AsId for Connection {
     fn as_id(&self) -> DatabaseValue {
         DatabaseValue::String(self.id.clone())
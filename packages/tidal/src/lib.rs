@@ -875,6 +875,8 @@ impl From<AlbumSort> for TidalAlbumOrderDirection {
             AlbumSort::ReleaseDateDesc => TidalAlbumOrderDirection::Desc,
             AlbumSort::DateAddedAsc => TidalAlbumOrderDirection::Asc,
             AlbumSort::DateAddedDesc => TidalAlbumOrderDirection::Desc,
+            AlbumSort::RatingAsc => TidalAlbumOrderDirection::Asc,
+            AlbumSort::RatingDesc => TidalAlbumOrderDirection::Desc,
         }
     }
 }
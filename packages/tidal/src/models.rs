@@ -434,6 +434,8 @@ impl From<TidalTrack> for Track {
             artist: value.artist,
             artist_id: value.artist_id.into(),
             file: None,
+            start_offset: None,
+            end_offset: None,
             artwork: value.artist_cover,
             blur: false,
             bytes: 0,
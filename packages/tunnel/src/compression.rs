@@ -0,0 +1,187 @@
+//! Compression of tunnel packets between senders and the tunnel server.
+//!
+//! The tunnel server advertises the compressions it can decompress in the
+//! [`TUNNEL_COMPRESSION_HEADER`] of each [`TunnelHttpRequest`], and in its
+//! [`TunnelHandshake`] for websocket messages. A sender that compresses a
+//! response sets the same header in the response headers of the first packet
+//! to the compression it chose, and then compresses the body of every packet
+//! independently. The tunnel server decompresses the packets before they are
+//! sent on to the client.
+//!
+//! Only text-like content types are compressed. Audio, images and other
+//! binary content are usually compressed already.
+//!
+//! [`TunnelHttpRequest`]: crate::TunnelHttpRequest
+//! [`TunnelHandshake`]: crate::TunnelHandshake
+
+use std::io::{Read as _, Write as _};
+
+use crate::TunnelCompression;
+
+/// The compressions supported by this build, in order of preference.
+pub const SUPPORTED_COMPRESSIONS: [TunnelCompression; 2] =
+    [TunnelCompression::Zstd, TunnelCompression::Gzip];
+
+/// The request header value advertising [`SUPPORTED_COMPRESSIONS`].
+#[must_use]
+pub fn supported_compressions_header() -> String {
+    SUPPORTED_COMPRESSIONS
+        .iter()
+        .map(AsRef::as_ref)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The first supported compression of a comma separated list of
+/// compressions.
+#[must_use]
+pub fn negotiate(accepted: &str) -> Option<TunnelCompression> {
+    accepted
+        .split(',')
+        .filter_map(|x| x.trim().parse::<TunnelCompression>().ok())
+        .find(|x| SUPPORTED_COMPRESSIONS.contains(x))
+}
+
+/// Whether a response with the content type is worth compressing.
+#[must_use]
+pub fn is_compressible_content_type(content_type: &str) -> bool {
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+
+    mime.starts_with("text/")
+        || mime.ends_with("+json")
+        || mime.ends_with("+xml")
+        || matches!(
+            mime.as_str(),
+            "application/json"
+                | "application/javascript"
+                | "application/xml"
+                | "application/x-mpegurl"
+                | "application/vnd.apple.mpegurl"
+        )
+}
+
+/// Compresses the bytes.
+///
+/// # Errors
+///
+/// * If the bytes fail to compress
+pub fn compress(compression: TunnelCompression, bytes: &[u8]) -> std::io::Result<Vec<u8>> {
+    match compression {
+        TunnelCompression::Zstd => zstd::stream::encode_all(bytes, 0),
+        TunnelCompression::Gzip => {
+            let mut encoder =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(bytes)?;
+            encoder.finish()
+        }
+    }
+}
+
+/// Decompresses bytes compressed with [`compress`].
+///
+/// # Errors
+///
+/// * If the bytes are not valid for the compression
+pub fn decompress(compression: TunnelCompression, bytes: &[u8]) -> std::io::Result<Vec<u8>> {
+    match compression {
+        TunnelCompression::Zstd => zstd::stream::decode_all(bytes),
+        TunnelCompression::Gzip => {
+            let mut decoded = vec![];
+            flate2::read::GzDecoder::new(bytes).read_to_end(&mut decoded)?;
+            Ok(decoded)
+        }
+    }
+}
+
+/// Compresses the body of a binary response packet, leaving the routing
+/// header, and the status and headers of the first packet, uncompressed.
+///
+/// # Errors
+///
+/// * If the packet is malformed or fails to compress
+pub fn compress_packet(compression: TunnelCompression, packet: &[u8]) -> std::io::Result<Vec<u8>> {
+    let offset = packet_body_offset(packet).ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, "Invalid tunnel packet")
+    })?;
+
+    let body = compress(compression, &packet[offset..])?;
+
+    Ok([&packet[..offset], &body].concat())
+}
+
+fn packet_body_offset(packet: &[u8]) -> Option<usize> {
+    let packet_id = u32::from_be_bytes(packet.get(8..12)?.try_into().ok()?);
+
+    if packet_id != 1 {
+        return (packet.len() >= 13).then_some(13);
+    }
+
+    let headers_len = u32::from_be_bytes(packet.get(15..19)?.try_into().ok()?) as usize;
+    let offset = 19 + headers_len;
+
+    (packet.len() >= offset).then_some(offset)
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::Bytes;
+    use pretty_assertions::assert_eq;
+
+    use crate::TunnelResponse;
+
+    use super::*;
+
+    #[test_log::test]
+    fn negotiates_the_first_supported_compression() {
+        assert_eq!(negotiate("br, gzip, zstd"), Some(TunnelCompression::Gzip));
+        assert_eq!(negotiate(" ZSTD "), Some(TunnelCompression::Zstd));
+        assert_eq!(negotiate("br"), None);
+        assert_eq!(negotiate(""), None);
+        assert_eq!(
+            negotiate(&supported_compressions_header()),
+            Some(TunnelCompression::Zstd)
+        );
+    }
+
+    #[test_log::test]
+    fn only_compresses_text_content_types() {
+        assert!(is_compressible_content_type("application/json"));
+        assert!(is_compressible_content_type("text/html; charset=utf-8"));
+        assert!(is_compressible_content_type("application/ld+json"));
+        assert!(!is_compressible_content_type("audio/flac"));
+        assert!(!is_compressible_content_type("image/webp"));
+        assert!(!is_compressible_content_type("application/zip"));
+    }
+
+    #[test_log::test]
+    fn compresses_the_body_of_packets() {
+        let headers = br#"{"content-type":"application/json"}"#;
+        let body = b"[{\"id\":1},{\"id\":1},{\"id\":1},{\"id\":1},{\"id\":1}]".repeat(20);
+        let packet = [
+            5_usize.to_be_bytes().as_slice(),
+            &1_u32.to_be_bytes(),
+            &[0],
+            &200_u16.to_be_bytes(),
+            &(headers.len() as u32).to_be_bytes(),
+            headers,
+            &body,
+        ]
+        .concat();
+
+        for compression in SUPPORTED_COMPRESSIONS {
+            let compressed = compress_packet(compression, &packet).unwrap();
+
+            assert!(compressed.len() < packet.len());
+
+            let response = TunnelResponse::from(Bytes::from(compressed));
+
+            assert_eq!(response.status, Some(200));
+            assert_eq!(decompress(compression, &response.bytes).unwrap(), body);
+        }
+    }
+}
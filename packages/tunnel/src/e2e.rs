@@ -0,0 +1,761 @@
+//! End-to-end encryption of tunneled HTTP requests and responses.
+//!
+//! The tunnel server relays every request and response between a client and
+//! the MoosicBox server that it is connected to. With E2E encryption, the
+//! client and the MoosicBox server negotiate a session through the tunnel and
+//! the tunnel server only sees opaque frames that it routes by client id.
+//!
+//! A session is negotiated with a `POST` to [`E2E_HANDSHAKE_PATH`] that
+//! contains the client's ephemeral X25519 public key. The MoosicBox server
+//! responds with its own ephemeral public key, signed along with the client's
+//! key by its long-term Ed25519 [`E2eSigningKey`], and both sides derive a
+//! ChaCha20-Poly1305 key per direction with HKDF-SHA256.
+//!
+//! Clients pin the public signing key of the server when they pair with it on
+//! their local network, where the server advertises it, and verify the
+//! signature against the pinned key before deriving the session keys. A
+//! tunnel server that substitutes its own ephemeral key in the handshake
+//! can't sign it, so it can't read or forge the requests and responses of the
+//! session. This relies on the key being pinned from the real server: a
+//! client that pairs through a compromised local network, or that skips the
+//! verification, isn't protected. The tunnel server still sees the client
+//! ids, request ids, packet sizes and timing of every request.
+//!
+//! The `id` of every [`E2eHttpRequest`] must increase within its session.
+//! The MoosicBox server rejects ids it already handled, and ids that fall too
+//! far behind the highest one, so a tunnel server can't replay a captured
+//! request. MoosicBox servers that only want to be reached through E2E
+//! sessions can also refuse plaintext requests from the tunnel entirely.
+//!
+//! Sealed frames are a random nonce followed by the ciphertext and its tag.
+
+use std::sync::Mutex;
+
+use base64::{engine::general_purpose, Engine as _};
+use bytes::Bytes;
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN},
+    agreement::{self, EphemeralPrivateKey, UnparsedPublicKey, X25519},
+    hkdf::{Prk, Salt, HKDF_SHA256},
+    rand::{SecureRandom as _, SystemRandom},
+    signature::{self, Ed25519KeyPair, KeyPair as _, ED25519},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::{Method, TunnelResponse, TUNNEL_RESPONSE_ENCRYPTED_FLAG};
+
+/// The tunnel path of the handshake request that negotiates a session.
+pub const E2E_HANDSHAKE_PATH: &str = "tunnel/e2e/handshake";
+
+static E2E_HKDF_INFO: &[u8] = b"moosicbox-tunnel-e2e";
+static E2E_CONFIRMATION: &[u8] = b"moosicbox-tunnel-e2e-confirmation";
+static E2E_SIGNATURE_CONTEXT: &[u8] = b"moosicbox-tunnel-e2e-handshake";
+
+/// The size of the request id, packet id and flags that prefix a binary
+/// response packet.
+const PACKET_HEADER_LEN: usize = 13;
+
+/// How far behind the highest request id of a session a request id is still
+/// accepted, for requests that are handled out of order.
+const REPLAY_WINDOW: usize = 64;
+
+#[derive(Debug, Error)]
+pub enum E2eError {
+    #[error("Invalid key")]
+    InvalidKey,
+    #[error("Failed to encrypt")]
+    Encrypt,
+    #[error("Failed to decrypt")]
+    Decrypt,
+    #[error("Invalid frame")]
+    InvalidFrame,
+    #[error("Invalid session confirmation")]
+    InvalidConfirmation,
+    #[error("Invalid handshake signature")]
+    InvalidSignature,
+    #[error("Replayed request {0}")]
+    ReplayedRequest(usize),
+    #[error(transparent)]
+    Base64(#[from] base64::DecodeError),
+    #[error(transparent)]
+    SerdeJson(#[from] serde_json::Error),
+}
+
+/// Which end of the session the keys are derived for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum E2eRole {
+    Client,
+    Server,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct E2eHandshakeRequest {
+    /// The base64 encoded ephemeral public key of the client
+    pub public_key: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct E2eHandshakeResponse {
+    pub session_id: String,
+    /// The base64 encoded ephemeral public key of the MoosicBox server
+    pub public_key: String,
+    /// The base64 encoded signature of both ephemeral public keys by the
+    /// [`E2eSigningKey`] of the MoosicBox server
+    pub signature: String,
+    /// A frame sealed with the session keys that the client verifies with
+    /// [`E2eSession::verify_confirmation`] before sending any requests
+    pub confirmation: String,
+}
+
+/// An HTTP request sealed by the client into a
+/// [`TunnelEncryptedRequest`](crate::TunnelEncryptedRequest).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct E2eHttpRequest {
+    /// Chosen by the client to bind the response frames to this request.
+    /// Must increase with every request of the session
+    pub id: usize,
+    pub method: Method,
+    pub path: String,
+    pub query: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payload: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub headers: Option<Value>,
+    pub profile: Option<String>,
+}
+
+/// The long-term Ed25519 key the MoosicBox server signs its handshakes with.
+pub struct E2eSigningKey {
+    key_pair: Ed25519KeyPair,
+    pkcs8: String,
+}
+
+impl std::fmt::Debug for E2eSigningKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("E2eSigningKey")
+            .field("public_key", &self.public_key())
+            .finish_non_exhaustive()
+    }
+}
+
+impl E2eSigningKey {
+    /// Generates a new signing key.
+    ///
+    /// # Errors
+    ///
+    /// * If the key fails to generate
+    pub fn generate() -> Result<Self, E2eError> {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new())
+            .map_err(|_| E2eError::InvalidKey)?;
+
+        Self::from_pkcs8(&general_purpose::STANDARD.encode(pkcs8.as_ref()))
+    }
+
+    /// Loads a signing key stored with [`pkcs8`](Self::pkcs8).
+    ///
+    /// # Errors
+    ///
+    /// * If the key is not a valid base64 encoded PKCS#8 Ed25519 key
+    pub fn from_pkcs8(pkcs8: &str) -> Result<Self, E2eError> {
+        let key_pair = Ed25519KeyPair::from_pkcs8(&general_purpose::STANDARD.decode(pkcs8)?)
+            .map_err(|_| E2eError::InvalidKey)?;
+
+        Ok(Self {
+            key_pair,
+            pkcs8: pkcs8.to_string(),
+        })
+    }
+
+    /// The base64 encoded PKCS#8 document to store the key in.
+    #[must_use]
+    pub fn pkcs8(&self) -> &str {
+        &self.pkcs8
+    }
+
+    /// The base64 encoded public key that clients pin.
+    #[must_use]
+    pub fn public_key(&self) -> String {
+        general_purpose::STANDARD.encode(self.key_pair.public_key().as_ref())
+    }
+}
+
+/// The message the server signs in a handshake, binding its ephemeral key to
+/// the client's and to the server identity.
+fn signed_handshake(
+    server_identity: &str,
+    client_public_key: &[u8],
+    server_public_key: &[u8],
+) -> Vec<u8> {
+    [
+        E2E_SIGNATURE_CONTEXT,
+        server_identity.as_bytes(),
+        client_public_key,
+        server_public_key,
+    ]
+    .concat()
+}
+
+/// One side of a handshake in progress.
+pub struct E2eHandshake {
+    private_key: EphemeralPrivateKey,
+    public_key: Vec<u8>,
+}
+
+impl E2eHandshake {
+    /// Generates a new ephemeral key pair.
+    ///
+    /// # Errors
+    ///
+    /// * If the key pair fails to generate
+    pub fn new() -> Result<Self, E2eError> {
+        let rng = SystemRandom::new();
+        let private_key =
+            EphemeralPrivateKey::generate(&X25519, &rng).map_err(|_| E2eError::InvalidKey)?;
+        let public_key = private_key
+            .compute_public_key()
+            .map_err(|_| E2eError::InvalidKey)?
+            .as_ref()
+            .to_vec();
+
+        Ok(Self {
+            private_key,
+            public_key,
+        })
+    }
+
+    /// The base64 encoded public key to send to the peer.
+    #[must_use]
+    pub fn public_key(&self) -> String {
+        general_purpose::STANDARD.encode(&self.public_key)
+    }
+
+    /// Derives the server's session keys from the client's base64 encoded
+    /// public key, and signs the handshake with the server's signing key.
+    /// Returns the session and the base64 encoded signature for the
+    /// [`E2eHandshakeResponse`].
+    ///
+    /// # Errors
+    ///
+    /// * If the client public key is invalid
+    pub fn finish_server(
+        self,
+        server_identity: &str,
+        signing_key: &E2eSigningKey,
+        client_public_key: &str,
+    ) -> Result<(E2eSession, String), E2eError> {
+        let client_public_key = general_purpose::STANDARD.decode(client_public_key)?;
+        let signature = signing_key.key_pair.sign(&signed_handshake(
+            server_identity,
+            &client_public_key,
+            &self.public_key,
+        ));
+        let session = self.finish(E2eRole::Server, server_identity, &client_public_key)?;
+
+        Ok((
+            session,
+            general_purpose::STANDARD.encode(signature.as_ref()),
+        ))
+    }
+
+    /// Verifies the server's signature of the handshake with the pinned
+    /// public signing key of the server, and only then derives the client's
+    /// session keys from the server's base64 encoded public key.
+    ///
+    /// # Errors
+    ///
+    /// * If the handshake was not signed by the pinned signing key
+    /// * If the server public key is invalid
+    pub fn finish_client(
+        self,
+        server_identity: &str,
+        server_signing_key: &str,
+        server_public_key: &str,
+        signature: &str,
+    ) -> Result<E2eSession, E2eError> {
+        let server_signing_key = general_purpose::STANDARD.decode(server_signing_key)?;
+        let server_public_key = general_purpose::STANDARD.decode(server_public_key)?;
+        let signature = general_purpose::STANDARD.decode(signature)?;
+
+        signature::UnparsedPublicKey::new(&ED25519, &server_signing_key)
+            .verify(
+                &signed_handshake(server_identity, &self.public_key, &server_public_key),
+                &signature,
+            )
+            .map_err(|_| E2eError::InvalidSignature)?;
+
+        self.finish(E2eRole::Client, server_identity, &server_public_key)
+    }
+
+    fn finish(
+        self,
+        role: E2eRole,
+        server_identity: &str,
+        peer_public_key: &[u8],
+    ) -> Result<E2eSession, E2eError> {
+        let (client_public_key, server_public_key) = match role {
+            E2eRole::Client => (self.public_key.as_slice(), peer_public_key),
+            E2eRole::Server => (peer_public_key, self.public_key.as_slice()),
+        };
+
+        let (client_key, server_key) = agreement::agree_ephemeral(
+            self.private_key,
+            &UnparsedPublicKey::new(&X25519, peer_public_key),
+            |secret| {
+                let prk = Salt::new(HKDF_SHA256, server_identity.as_bytes()).extract(secret);
+                Ok::<_, E2eError>((
+                    derive_key(&prk, b"client", client_public_key, server_public_key)?,
+                    derive_key(&prk, b"server", client_public_key, server_public_key)?,
+                ))
+            },
+        )
+        .map_err(|_| E2eError::InvalidKey)??;
+
+        let (seal_key, open_key) = match role {
+            E2eRole::Client => (client_key, server_key),
+            E2eRole::Server => (server_key, client_key),
+        };
+
+        Ok(E2eSession {
+            seal_key,
+            open_key,
+            rng: SystemRandom::new(),
+            request_ids: Mutex::new(ReplayWindow::default()),
+        })
+    }
+}
+
+fn derive_key(
+    prk: &Prk,
+    label: &[u8],
+    client_public_key: &[u8],
+    server_public_key: &[u8],
+) -> Result<LessSafeKey, E2eError> {
+    let info = [E2E_HKDF_INFO, label, client_public_key, server_public_key];
+    let okm = prk
+        .expand(&info, &CHACHA20_POLY1305)
+        .map_err(|_| E2eError::InvalidKey)?;
+
+    Ok(LessSafeKey::new(UnboundKey::from(okm)))
+}
+
+/// The request ids a session has handled, as the highest id and a bitmap of
+/// the [`REPLAY_WINDOW`] ids up to it.
+#[derive(Default)]
+struct ReplayWindow {
+    highest: Option<usize>,
+    seen: u64,
+}
+
+impl ReplayWindow {
+    /// Records the id, returning `false` if it was already handled or is too
+    /// old to tell.
+    fn check(&mut self, id: usize) -> bool {
+        let Some(highest) = self.highest else {
+            self.highest = Some(id);
+            self.seen = 1;
+            return true;
+        };
+
+        if id > highest {
+            let shift = id - highest;
+            self.seen = if shift >= REPLAY_WINDOW {
+                0
+            } else {
+                self.seen << shift
+            };
+            self.seen |= 1;
+            self.highest = Some(id);
+            return true;
+        }
+
+        let offset = highest - id;
+        if offset >= REPLAY_WINDOW {
+            return false;
+        }
+
+        let bit = 1 << offset;
+        if self.seen & bit != 0 {
+            return false;
+        }
+        self.seen |= bit;
+
+        true
+    }
+}
+
+/// The negotiated keys of one end of a session.
+pub struct E2eSession {
+    seal_key: LessSafeKey,
+    open_key: LessSafeKey,
+    rng: SystemRandom,
+    /// The ids of the requests opened with [`E2eSession::open_request`]
+    request_ids: Mutex<ReplayWindow>,
+}
+
+impl std::fmt::Debug for E2eSession {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("E2eSession").finish_non_exhaustive()
+    }
+}
+
+impl E2eSession {
+    /// Seals `plaintext` into a frame for the peer.
+    ///
+    /// # Errors
+    ///
+    /// * If the plaintext fails to encrypt
+    pub fn seal(&self, aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, E2eError> {
+        let mut nonce = [0_u8; NONCE_LEN];
+        self.rng.fill(&mut nonce).map_err(|_| E2eError::Encrypt)?;
+
+        let mut in_out = plaintext.to_vec();
+        self.seal_key
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(aad),
+                &mut in_out,
+            )
+            .map_err(|_| E2eError::Encrypt)?;
+
+        Ok([nonce.as_slice(), &in_out].concat())
+    }
+
+    /// Opens a frame sealed by the peer.
+    ///
+    /// # Errors
+    ///
+    /// * If the frame was not sealed by the peer with the same `aad`
+    pub fn open(&self, aad: &[u8], frame: &[u8]) -> Result<Vec<u8>, E2eError> {
+        if frame.len() < NONCE_LEN {
+            return Err(E2eError::InvalidFrame);
+        }
+
+        let (nonce, ciphertext) = frame.split_at(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|_| E2eError::InvalidFrame)?;
+        let mut in_out = ciphertext.to_vec();
+        let len = self
+            .open_key
+            .open_in_place(nonce, Aad::from(aad), &mut in_out)
+            .map_err(|_| E2eError::Decrypt)?
+            .len();
+        in_out.truncate(len);
+
+        Ok(in_out)
+    }
+
+    /// The base64 encoded confirmation for [`E2eHandshakeResponse`].
+    ///
+    /// # Errors
+    ///
+    /// * If the confirmation fails to encrypt
+    pub fn confirmation(&self, session_id: &str) -> Result<String, E2eError> {
+        Ok(general_purpose::STANDARD.encode(self.seal(session_id.as_bytes(), E2E_CONFIRMATION)?))
+    }
+
+    /// Verifies the peer derived the same session keys.
+    ///
+    /// # Errors
+    ///
+    /// * If the confirmation was not sealed with the same session keys
+    pub fn verify_confirmation(
+        &self,
+        session_id: &str,
+        confirmation: &str,
+    ) -> Result<(), E2eError> {
+        let confirmation = general_purpose::STANDARD.decode(confirmation)?;
+        match self.open(session_id.as_bytes(), &confirmation) {
+            Ok(x) if x == E2E_CONFIRMATION => Ok(()),
+            _ => Err(E2eError::InvalidConfirmation),
+        }
+    }
+
+    /// Seals a request into the base64 encoded payload of a
+    /// [`TunnelEncryptedRequest`](crate::TunnelEncryptedRequest).
+    ///
+    /// # Errors
+    ///
+    /// * If the request fails to serialize or encrypt
+    pub fn seal_request(
+        &self,
+        session_id: &str,
+        request: &E2eHttpRequest,
+    ) -> Result<String, E2eError> {
+        let bytes = serde_json::to_vec(request)?;
+        Ok(general_purpose::STANDARD.encode(self.seal(session_id.as_bytes(), &bytes)?))
+    }
+
+    /// Opens the base64 encoded payload of a
+    /// [`TunnelEncryptedRequest`](crate::TunnelEncryptedRequest). Each request
+    /// id of the session is only opened once.
+    ///
+    /// # Errors
+    ///
+    /// * If the payload was not sealed by the peer for this session
+    /// * If the request id was already opened, or is too far behind the
+    ///   highest one opened
+    pub fn open_request(
+        &self,
+        session_id: &str,
+        payload: &str,
+    ) -> Result<E2eHttpRequest, E2eError> {
+        let frame = general_purpose::STANDARD.decode(payload)?;
+        let bytes = self.open(session_id.as_bytes(), &frame)?;
+        let request: E2eHttpRequest = serde_json::from_slice(&bytes)?;
+
+        if !self.request_ids.lock().unwrap().check(request.id) {
+            return Err(E2eError::ReplayedRequest(request.id));
+        }
+
+        Ok(request)
+    }
+
+    /// Seals everything after the request id of a binary response packet,
+    /// leaving the request id, packet id and flags in plaintext for the tunnel
+    /// server to route the packet with. The packet id and last flag are
+    /// sealed as well so the client can verify the order and completeness of
+    /// the response.
+    ///
+    /// # Errors
+    ///
+    /// * If the packet is too short or fails to encrypt
+    pub fn seal_response_packet(&self, id: usize, packet: &[u8]) -> Result<Vec<u8>, E2eError> {
+        if packet.len() < PACKET_HEADER_LEN {
+            return Err(E2eError::InvalidFrame);
+        }
+
+        let sealed = self.seal(&id.to_be_bytes(), &packet[8..])?;
+        let mut bytes = Vec::with_capacity(PACKET_HEADER_LEN + sealed.len());
+        bytes.extend_from_slice(&packet[..PACKET_HEADER_LEN]);
+        bytes[PACKET_HEADER_LEN - 1] |= TUNNEL_RESPONSE_ENCRYPTED_FLAG;
+        bytes.extend_from_slice(&sealed);
+
+        Ok(bytes)
+    }
+
+    /// Opens a frame of an encrypted response stream. The `request_id` of the
+    /// response is the `id` of the [`E2eHttpRequest`].
+    ///
+    /// # Errors
+    ///
+    /// * If the frame was not sealed by the peer for the request `id`
+    pub fn open_response_frame(&self, id: usize, frame: &[u8]) -> Result<TunnelResponse, E2eError> {
+        let bytes = self.open(&id.to_be_bytes(), frame)?;
+        if bytes.len() < PACKET_HEADER_LEN - 8 {
+            return Err(E2eError::InvalidFrame);
+        }
+
+        Ok(Bytes::from([id.to_be_bytes().as_slice(), &bytes].concat()).into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    use super::*;
+
+    fn handshake(
+        client_identity: &str,
+        pinned_key: &E2eSigningKey,
+        server_identity: &str,
+        signing_key: &E2eSigningKey,
+    ) -> Result<(E2eSession, E2eSession), E2eError> {
+        let client = E2eHandshake::new().unwrap();
+        let server = E2eHandshake::new().unwrap();
+        let client_public_key = client.public_key();
+        let server_public_key = server.public_key();
+
+        let (server, signature) = server
+            .finish_server(server_identity, signing_key, &client_public_key)
+            .unwrap();
+        let client = client.finish_client(
+            client_identity,
+            &pinned_key.public_key(),
+            &server_public_key,
+            &signature,
+        )?;
+
+        Ok((client, server))
+    }
+
+    fn sessions() -> (E2eSession, E2eSession) {
+        let signing_key = E2eSigningKey::generate().unwrap();
+
+        handshake("identity", &signing_key, "identity", &signing_key).unwrap()
+    }
+
+    #[test_log::test]
+    fn seals_requests_and_responses_between_the_client_and_server() {
+        let (client, server) = sessions();
+
+        client
+            .verify_confirmation("session", &server.confirmation("session").unwrap())
+            .unwrap();
+
+        let payload = client
+            .seal_request(
+                "session",
+                &E2eHttpRequest {
+                    id: 7,
+                    method: Method::Get,
+                    path: "menu/albums".to_string(),
+                    query: json!({"offset": 0}),
+                    payload: None,
+                    headers: None,
+                    profile: Some("master".to_string()),
+                },
+            )
+            .unwrap();
+        let request = server.open_request("session", &payload).unwrap();
+
+        assert_eq!(request.id, 7);
+        assert_eq!(request.path, "menu/albums");
+        assert_eq!(request.profile.as_deref(), Some("master"));
+
+        let headers = br#"{"content-type":"application/json"}"#;
+        let packet = [
+            123_usize.to_be_bytes().as_slice(),
+            &1_u32.to_be_bytes(),
+            &[1],
+            &200_u16.to_be_bytes(),
+            &(headers.len() as u32).to_be_bytes(),
+            headers,
+            b"[]",
+        ]
+        .concat();
+        let sealed = server.seal_response_packet(request.id, &packet).unwrap();
+
+        assert_eq!(sealed[..12], packet[..12]);
+        assert_eq!(sealed[12], 1 | TUNNEL_RESPONSE_ENCRYPTED_FLAG);
+
+        let relayed = TunnelResponse::from(Bytes::from(sealed));
+
+        assert_eq!(relayed.request_id, 123);
+        assert_eq!(relayed.status, None);
+
+        let response = client
+            .open_response_frame(request.id, &relayed.bytes)
+            .unwrap();
+
+        assert_eq!(response.request_id, 7);
+        assert_eq!(response.packet_id, 1);
+        assert!(response.last);
+        assert_eq!(response.status, Some(200));
+        assert_eq!(response.bytes, Bytes::from_static(b"[]"));
+    }
+
+    #[test_log::test]
+    fn rejects_handshakes_not_signed_by_the_pinned_key() {
+        let pinned_key = E2eSigningKey::generate().unwrap();
+        let other_key = E2eSigningKey::generate().unwrap();
+
+        assert!(matches!(
+            handshake("identity", &pinned_key, "identity", &other_key),
+            Err(E2eError::InvalidSignature)
+        ));
+    }
+
+    #[test_log::test]
+    fn rejects_handshakes_with_a_different_server_identity() {
+        let signing_key = E2eSigningKey::generate().unwrap();
+
+        assert!(matches!(
+            handshake("identity", &signing_key, "other", &signing_key),
+            Err(E2eError::InvalidSignature)
+        ));
+    }
+
+    #[test_log::test]
+    fn rejects_handshakes_with_a_substituted_server_key() {
+        let signing_key = E2eSigningKey::generate().unwrap();
+        let client = E2eHandshake::new().unwrap();
+        let server = E2eHandshake::new().unwrap();
+
+        let (_, signature) = server
+            .finish_server("identity", &signing_key, &client.public_key())
+            .unwrap();
+
+        // A relay that swaps in its own ephemeral key can't sign it
+        let relay = E2eHandshake::new().unwrap();
+
+        assert!(matches!(
+            client.finish_client(
+                "identity",
+                &signing_key.public_key(),
+                &relay.public_key(),
+                &signature
+            ),
+            Err(E2eError::InvalidSignature)
+        ));
+    }
+
+    #[test_log::test]
+    fn loads_stored_signing_keys() {
+        let signing_key = E2eSigningKey::generate().unwrap();
+        let loaded = E2eSigningKey::from_pkcs8(signing_key.pkcs8()).unwrap();
+
+        assert_eq!(loaded.public_key(), signing_key.public_key());
+    }
+
+    #[test_log::test]
+    fn rejects_tampered_and_misrouted_frames() {
+        let (client, server) = sessions();
+
+        let mut frame = server.seal(&1_usize.to_be_bytes(), b"data").unwrap();
+
+        assert!(client.open(&2_usize.to_be_bytes(), &frame).is_err());
+
+        let last = frame.len() - 1;
+        frame[last] ^= 1;
+
+        assert!(client.open(&1_usize.to_be_bytes(), &frame).is_err());
+    }
+
+    fn request(id: usize) -> E2eHttpRequest {
+        E2eHttpRequest {
+            id,
+            method: Method::Delete,
+            path: "session".to_string(),
+            query: json!({"sessionId": 1}),
+            payload: None,
+            headers: None,
+            profile: None,
+        }
+    }
+
+    #[test_log::test]
+    fn rejects_replayed_requests() {
+        let (client, server) = sessions();
+
+        let payload = client.seal_request("session", &request(1)).unwrap();
+
+        assert!(server.open_request("session", &payload).is_ok());
+        assert!(matches!(
+            server.open_request("session", &payload),
+            Err(E2eError::ReplayedRequest(1))
+        ));
+    }
+
+    #[test_log::test]
+    fn accepts_requests_opened_out_of_order_within_the_window() {
+        let (client, server) = sessions();
+
+        let open = |id| {
+            let payload = client.seal_request("session", &request(id)).unwrap();
+            server.open_request("session", &payload).map(|x| x.id)
+        };
+
+        assert_eq!(open(3).unwrap(), 3);
+        assert_eq!(open(1).unwrap(), 1);
+        assert_eq!(open(2).unwrap(), 2);
+        assert!(open(2).is_err());
+        assert_eq!(open(3 + REPLAY_WINDOW).unwrap(), 3 + REPLAY_WINDOW);
+        assert!(open(3).is_err());
+        assert_eq!(open(4).unwrap(), 4);
+    }
+}
@@ -3,14 +3,19 @@
 use std::{collections::HashMap, fmt::Display, task::Poll, time::SystemTime};
 
 use bytes::Bytes;
-use futures_util::{Future, Stream};
+use futures_util::{Future, Stream, StreamExt as _};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use strum_macros::EnumString;
+use strum_macros::{AsRefStr, EnumString};
 use thiserror::Error;
 use tokio::sync::mpsc::UnboundedReceiver;
 use tokio_util::sync::CancellationToken;
 
+#[cfg(feature = "compression")]
+pub mod compression;
+#[cfg(feature = "e2e")]
+pub mod e2e;
+
 #[cfg(feature = "base64")]
 static BASE64_TUNNEL_RESPONSE_PREFIX: &str = "TUNNEL_RESPONSE:";
 
@@ -31,6 +36,53 @@ pub struct TunnelWsResponse {
     pub exclude_connection_ids: Option<Vec<usize>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub to_connection_ids: Option<Vec<usize>>,
+    /// Only send to the client connections using this library profile
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+}
+
+/// The version of the tunnel protocol. Senders advertise it when connecting,
+/// and tunnel servers that support it respond with a [`TunnelHandshake`].
+/// Senders that don't advertise a version speak version 1, which has no flow
+/// control.
+pub const TUNNEL_PROTOCOL_VERSION: u32 = 3;
+
+/// The first protocol version with per-request flow control.
+pub const FLOW_CONTROL_PROTOCOL_VERSION: u32 = 2;
+
+/// The first protocol version that can receive request bodies as
+/// [`TunnelRequestBody`] packets.
+pub const REQUEST_BODY_PROTOCOL_VERSION: u32 = 3;
+
+/// Set in the flags byte of a binary [`TunnelResponse`] packet, alongside the
+/// last packet flag, when the rest of the packet is an end-to-end encrypted
+/// frame that only the client can open.
+pub const TUNNEL_RESPONSE_ENCRYPTED_FLAG: u8 = 0b10;
+
+/// Advertises the compressions a tunnel server can decompress in the headers
+/// of a [`TunnelHttpRequest`], and names the compression a sender chose in the
+/// headers of the response.
+pub const TUNNEL_COMPRESSION_HEADER: &str = "moosicbox-tunnel-compression";
+
+#[derive(Debug, Serialize, Deserialize, EnumString, AsRefStr, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+#[repr(u8)]
+pub enum TunnelCompression {
+    Zstd = 1,
+    Gzip = 2,
+}
+
+impl TryFrom<u8> for TunnelCompression {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(Self::Zstd),
+            2 => Ok(Self::Gzip),
+            _ => Err(value),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -77,6 +129,9 @@ pub enum TunnelRequest {
     Http(TunnelHttpRequest),
     Ws(TunnelWsRequest),
     Abort(TunnelAbortRequest),
+    Encrypted(TunnelEncryptedRequest),
+    Handshake(TunnelHandshake),
+    WindowUpdate(TunnelWindowUpdate),
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -91,6 +146,10 @@ pub struct TunnelHttpRequest {
     pub headers: Option<Value>,
     pub encoding: TunnelEncoding,
     pub profile: Option<String>,
+    /// The request body follows in binary [`TunnelRequestBody`] packets
+    /// instead of the `payload`
+    #[serde(default)]
+    pub streaming_body: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -108,13 +167,167 @@ pub struct TunnelAbortRequest {
     pub request_id: usize,
 }
 
+/// Sent by the tunnel server to a sender that advertised a protocol version
+/// when it connected.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TunnelHandshake {
+    pub protocol_version: u32,
+    /// The number of response packets a sender can send for each request
+    /// before waiting for a [`TunnelWindowUpdate`]
+    pub window: u32,
+    /// The compressions the tunnel server can decompress websocket messages
+    /// with, in order of preference
+    #[serde(default)]
+    pub compression: Vec<TunnelCompression>,
+}
+
+/// Grants a sender `credits` more response packets for the request, sent by
+/// the tunnel server as the client consumes the response.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TunnelWindowUpdate {
+    pub request_id: usize,
+    pub credits: u32,
+}
+
+/// A [`TunnelHttpRequest`] sealed by the client for the MoosicBox server. The
+/// tunnel server only routes it by client id and streams the encrypted
+/// response frames back to the client.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TunnelEncryptedRequest {
+    pub request_id: usize,
+    pub session_id: String,
+    /// The base64 encoded sealed request
+    pub payload: String,
+}
+
+/// Control messages sent by a sender to the tunnel server as JSON text
+/// messages, alongside [`TunnelWsResponse`]s.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+#[serde(tag = "type")]
+pub enum TunnelSenderMessage {
+    /// Grants the tunnel server `credits` more [`TunnelRequestBody`] packets
+    /// for the request
+    RequestBodyWindowUpdate(TunnelWindowUpdate),
+}
+
+/// A packet of the body of a [`TunnelHttpRequest`], sent by the tunnel server
+/// to the sender as a binary message framed like a [`TunnelResponse`] packet:
+/// the request id, the packet id, the flags, and then the bytes.
+///
+/// The tunnel server only sends a packet for each credit granted by the sender
+/// with a [`TunnelSenderMessage::RequestBodyWindowUpdate`], starting with none.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TunnelRequestBody {
+    pub request_id: usize,
+    pub packet_id: u32,
+    pub last: bool,
+    /// Set on the last packet if the tunnel server failed to read the rest of
+    /// the body from the client
+    pub failed: bool,
+    pub bytes: Bytes,
+}
+
+/// Set in the flags byte of a [`TunnelRequestBody`] packet, alongside the
+/// last packet flag, when the body ended with an error.
+pub const TUNNEL_REQUEST_BODY_FAILED_FLAG: u8 = 0b10;
+
+/// The size of the request id, packet id and flags of a packet.
+const PACKET_HEADER_SIZE: usize = 13;
+
+#[derive(Debug, Error)]
+pub enum TunnelRequestBodyError {
+    #[error("Request body packet is too short ({0} bytes)")]
+    TooShort(usize),
+}
+
+impl TryFrom<Bytes> for TunnelRequestBody {
+    type Error = TunnelRequestBodyError;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        if bytes.len() < PACKET_HEADER_SIZE {
+            return Err(TunnelRequestBodyError::TooShort(bytes.len()));
+        }
+
+        let request_id = usize::from_be_bytes(bytes[..8].try_into().unwrap());
+        let packet_id = u32::from_be_bytes(bytes[8..12].try_into().unwrap());
+        let last = bytes[12] & 1 == 1;
+        let failed = bytes[12] & TUNNEL_REQUEST_BODY_FAILED_FLAG != 0;
+
+        Ok(TunnelRequestBody {
+            request_id,
+            packet_id,
+            last,
+            failed,
+            bytes: bytes.slice(PACKET_HEADER_SIZE..),
+        })
+    }
+}
+
+impl From<TunnelRequestBody> for Bytes {
+    fn from(body: TunnelRequestBody) -> Self {
+        [
+            body.request_id.to_be_bytes().as_slice(),
+            &body.packet_id.to_be_bytes(),
+            &[u8::from(body.last)
+                | if body.failed {
+                    TUNNEL_REQUEST_BODY_FAILED_FLAG
+                } else {
+                    0
+                }],
+            &body.bytes,
+        ]
+        .concat()
+        .into()
+    }
+}
+
+/// Splits a request body into [`TunnelRequestBody`] packets, ending with an
+/// empty last packet. If reading the body fails, the last packet is marked as
+/// [`failed`](TunnelRequestBody::failed) so that the truncated body isn't
+/// mistaken for a complete one.
+pub fn request_body_packets<E: std::fmt::Debug>(
+    request_id: usize,
+    body: impl Stream<Item = Result<Bytes, E>> + Unpin,
+) -> impl Stream<Item = TunnelRequestBody> {
+    futures_util::stream::unfold(
+        (body, 0_u32, false),
+        move |(mut body, packet_id, done)| async move {
+            if done {
+                return None;
+            }
+
+            let (bytes, last, failed) = match body.next().await {
+                Some(Ok(bytes)) => (bytes, false, false),
+                Some(Err(err)) => {
+                    log::error!("Failed to read request body for request_id={request_id}: {err:?}");
+                    (Bytes::new(), true, true)
+                }
+                None => (Bytes::new(), true, false),
+            };
+
+            let packet_id = packet_id + 1;
+            let packet = TunnelRequestBody {
+                request_id,
+                packet_id,
+                last,
+                failed,
+                bytes,
+            };
+
+            Some((packet, (body, packet_id, last)))
+        },
+    )
+}
+
 impl From<Bytes> for TunnelResponse {
     fn from(bytes: Bytes) -> Self {
         let mut data = bytes.slice(13..);
         let request_id = usize::from_be_bytes(bytes[..8].try_into().unwrap());
         let packet_id = u32::from_be_bytes(bytes[8..12].try_into().unwrap());
-        let last = u8::from_be_bytes(bytes[12..13].try_into().unwrap()) == 1;
-        let (status, headers) = if packet_id == 1 {
+        let flags = u8::from_be_bytes(bytes[12..13].try_into().unwrap());
+        let last = flags & 1 == 1;
+        let (status, headers) = if packet_id == 1 && flags & TUNNEL_RESPONSE_ENCRYPTED_FLAG == 0 {
             let status = u16::from_be_bytes(data[..2].try_into().unwrap());
             data = data.slice(2..);
             let len = u32::from_be_bytes(data[..4].try_into().unwrap()) as usize;
@@ -232,6 +445,8 @@ pub enum TunnelStreamError {
     Aborted,
     #[error("TunnelStream end of stream")]
     EndOfStream,
+    #[error("TunnelStream failed to decompress packet: {0:?}")]
+    Decompress(std::io::Error),
 }
 
 pub struct TunnelStream<'a, F: Future<Output = Result<(), Box<dyn std::error::Error>>>> {
@@ -452,3 +667,68 @@ impl<F: Future<Output = Result<(), Box<dyn std::error::Error>>>> Stream for Tunn
         self.poll_next(cx)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use futures_util::StreamExt as _;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn packet(packet_id: u32, last: bool, failed: bool, bytes: &'static [u8]) -> TunnelRequestBody {
+        TunnelRequestBody {
+            request_id: 1,
+            packet_id,
+            last,
+            failed,
+            bytes: Bytes::from_static(bytes),
+        }
+    }
+
+    #[test_log::test]
+    fn frames_the_failed_flag_of_request_body_packets() {
+        let body = packet(2, true, true, b"");
+        let bytes = Bytes::from(body.clone());
+
+        assert_eq!(bytes[12], 1 | TUNNEL_REQUEST_BODY_FAILED_FLAG);
+        assert_eq!(TunnelRequestBody::try_from(bytes).unwrap(), body);
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn ends_request_bodies_with_an_empty_last_packet() {
+        let body = futures_util::stream::iter([
+            Ok::<_, std::io::Error>(Bytes::from_static(b"a")),
+            Ok(Bytes::from_static(b"b")),
+        ]);
+
+        let packets = request_body_packets(1, body).collect::<Vec<_>>().await;
+
+        assert_eq!(
+            packets,
+            vec![
+                packet(1, false, false, b"a"),
+                packet(2, false, false, b"b"),
+                packet(3, true, false, b""),
+            ]
+        );
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn fails_request_bodies_that_fail_to_read_partway_through() {
+        let body = futures_util::stream::iter([
+            Ok(Bytes::from_static(b"a")),
+            Err(std::io::Error::new(
+                std::io::ErrorKind::ConnectionReset,
+                "client disconnected",
+            )),
+            Ok(Bytes::from_static(b"b")),
+        ]);
+
+        let packets = request_body_packets(1, body).collect::<Vec<_>>().await;
+
+        assert_eq!(
+            packets,
+            vec![packet(1, false, false, b"a"), packet(2, true, true, b"")]
+        );
+    }
+}
@@ -0,0 +1,55 @@
+//! The long-term key the MoosicBox server signs its E2E handshakes with.
+//!
+//! The key is generated the first time it's needed and stored in the config
+//! database, so clients that pinned its public key keep trusting the server
+//! across restarts.
+
+use std::sync::Arc;
+
+use moosicbox_config::db::GetOrInitServerIdentityError;
+use moosicbox_database::{config::ConfigDatabase, DatabaseError};
+use moosicbox_tunnel::e2e::{E2eError, E2eSigningKey};
+use thiserror::Error;
+use tokio::sync::OnceCell;
+
+static SIGNING_KEY: OnceCell<Arc<E2eSigningKey>> = OnceCell::const_new();
+
+#[derive(Debug, Error)]
+pub enum SigningKeyError {
+    #[error(transparent)]
+    Database(#[from] DatabaseError),
+    #[error(transparent)]
+    ServerIdentity(#[from] GetOrInitServerIdentityError),
+    #[error(transparent)]
+    E2e(#[from] E2eError),
+}
+
+/// Returns the server's signing key, generating and storing it if the server
+/// doesn't have one yet.
+///
+/// # Errors
+///
+/// * If the key failed to be read or stored
+/// * If the stored key is invalid
+pub async fn get_or_init_signing_key(
+    db: &ConfigDatabase,
+) -> Result<Arc<E2eSigningKey>, SigningKeyError> {
+    SIGNING_KEY
+        .get_or_try_init(|| async {
+            if let Some(pkcs8) = moosicbox_config::get_server_signing_key(db).await? {
+                return Ok(Arc::new(E2eSigningKey::from_pkcs8(&pkcs8)?));
+            }
+
+            let signing_key = E2eSigningKey::generate()?;
+            moosicbox_config::set_server_signing_key(db, signing_key.pkcs8()).await?;
+
+            log::info!(
+                "Generated E2E signing key public_key={}",
+                signing_key.public_key()
+            );
+
+            Ok(Arc::new(signing_key))
+        })
+        .await
+        .cloned()
+}
@@ -0,0 +1,239 @@
+//! Per-request flow control and fair scheduling of tunnel response packets.
+//!
+//! Tunnel servers that speak [`TUNNEL_PROTOCOL_VERSION`] 2 or later grant each
+//! request a window of response packets in their
+//! [`TunnelHandshake`](moosicbox_tunnel::TunnelHandshake), and give credits
+//! back with a [`TunnelWindowUpdate`](moosicbox_tunnel::TunnelWindowUpdate) as
+//! the client consumes them. Until the handshake is received, packets are sent
+//! without flow control like version 1 tunnel servers expect.
+//!
+//! [`TUNNEL_PROTOCOL_VERSION`]: moosicbox_tunnel::TUNNEL_PROTOCOL_VERSION
+
+use std::{
+    collections::HashMap,
+    sync::{Mutex, RwLock},
+};
+
+use tokio::sync::Notify;
+use tokio_util::sync::CancellationToken;
+
+#[derive(Default)]
+pub struct FlowControl {
+    window: RwLock<Option<u32>>,
+    credits: Mutex<HashMap<usize, u32>>,
+    notify: Notify,
+}
+
+impl FlowControl {
+    /// Enables flow control with `window` packet credits per request.
+    pub fn enable(&self, window: u32) {
+        log::debug!("Enabling flow control with window={window}");
+        *self.window.write().unwrap() = Some(window);
+        self.credits.lock().unwrap().clear();
+        self.notify.notify_waiters();
+    }
+
+    /// Disables flow control until the next handshake, e.g. when the tunnel
+    /// connection is re-established.
+    pub fn disable(&self) {
+        *self.window.write().unwrap() = None;
+        self.credits.lock().unwrap().clear();
+        self.notify.notify_waiters();
+    }
+
+    /// Waits for a credit to send one packet for the request. Returns `false`
+    /// if the request was aborted while waiting.
+    pub async fn acquire(
+        &self,
+        request_id: usize,
+        abort_token: Option<&CancellationToken>,
+    ) -> bool {
+        loop {
+            let notified = self.notify.notified();
+
+            {
+                let Some(window) = *self.window.read().unwrap() else {
+                    return true;
+                };
+                let mut credits = self.credits.lock().unwrap();
+                let credits = credits.entry(request_id).or_insert(window);
+                if *credits > 0 {
+                    *credits -= 1;
+                    return true;
+                }
+            }
+
+            log::trace!("Waiting for window update for request_id={request_id}");
+
+            match abort_token {
+                Some(abort_token) => {
+                    tokio::select! {
+                        () = notified => {}
+                        () = abort_token.cancelled() => return false,
+                    }
+                }
+                None => notified.await,
+            }
+        }
+    }
+
+    /// Grants the request `credits` more packets.
+    pub fn grant(&self, request_id: usize, credits: u32) {
+        if let Some(existing) = self.credits.lock().unwrap().get_mut(&request_id) {
+            *existing = existing.saturating_add(credits);
+        }
+        self.notify.notify_waiters();
+    }
+
+    pub fn remove(&self, request_id: usize) {
+        self.credits.lock().unwrap().remove(&request_id);
+    }
+}
+
+/// Prioritizes the packets of the requests with the fewest bytes waiting to be
+/// sent, so concurrent requests take turns on the tunnel connection instead of
+/// one large response starving the others.
+#[derive(Default)]
+pub struct FairScheduler {
+    queued_bytes: Mutex<HashMap<usize, usize>>,
+}
+
+impl FairScheduler {
+    pub fn enqueue(&self, request_id: usize, len: usize) {
+        *self
+            .queued_bytes
+            .lock()
+            .unwrap()
+            .entry(request_id)
+            .or_default() += len;
+    }
+
+    pub fn dequeue(&self, request_id: usize, len: usize) {
+        let mut queued_bytes = self.queued_bytes.lock().unwrap();
+        if let Some(queued) = queued_bytes.get_mut(&request_id) {
+            *queued = queued.saturating_sub(len);
+            if *queued == 0 {
+                queued_bytes.remove(&request_id);
+            }
+        }
+    }
+
+    /// Forgets the queued bytes of an aborted request, whose queued packets
+    /// are dropped instead of sent.
+    pub fn remove(&self, request_id: usize) {
+        self.queued_bytes.lock().unwrap().remove(&request_id);
+    }
+
+    /// Forgets the queued bytes of every request, e.g. when the tunnel
+    /// connection closes and its queued packets are dropped.
+    pub fn clear(&self) {
+        self.queued_bytes.lock().unwrap().clear();
+    }
+
+    /// The priority of the next packet of the request, higher being sent
+    /// first.
+    pub fn priority(&self, request_id: usize) -> usize {
+        usize::MAX
+            - self
+                .queued_bytes
+                .lock()
+                .unwrap()
+                .get(&request_id)
+                .copied()
+                .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{sync::Arc, time::Duration};
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test_log::test(tokio::test)]
+    async fn sends_without_credits_until_enabled() {
+        let flow_control = FlowControl::default();
+
+        for _ in 0..100 {
+            assert!(flow_control.acquire(1, None).await);
+        }
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn waits_for_a_window_update_when_out_of_credits() {
+        let flow_control = Arc::new(FlowControl::default());
+        flow_control.enable(2);
+
+        assert!(flow_control.acquire(1, None).await);
+        assert!(flow_control.acquire(1, None).await);
+
+        let waiting = tokio::spawn({
+            let flow_control = flow_control.clone();
+            async move { flow_control.acquire(1, None).await }
+        });
+
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(!waiting.is_finished());
+
+        // Other requests have their own window
+        assert!(flow_control.acquire(2, None).await);
+
+        flow_control.grant(1, 1);
+
+        assert!(waiting.await.unwrap());
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn stops_waiting_when_the_request_is_aborted() {
+        let flow_control = FlowControl::default();
+        flow_control.enable(1);
+        let abort_token = CancellationToken::new();
+
+        assert!(flow_control.acquire(1, Some(&abort_token)).await);
+
+        abort_token.cancel();
+
+        assert!(!flow_control.acquire(1, Some(&abort_token)).await);
+    }
+
+    #[test_log::test]
+    fn prioritizes_requests_with_fewer_queued_bytes() {
+        let scheduler = FairScheduler::default();
+
+        scheduler.enqueue(1, 1000);
+        scheduler.enqueue(1, 1000);
+        scheduler.enqueue(2, 100);
+
+        assert!(scheduler.priority(2) > scheduler.priority(1));
+
+        scheduler.dequeue(1, 1000);
+        scheduler.dequeue(1, 1000);
+
+        assert_eq!(scheduler.priority(1), usize::MAX);
+        assert!(scheduler.priority(1) > scheduler.priority(2));
+    }
+
+    #[test_log::test]
+    fn forgets_the_queued_bytes_of_removed_requests() {
+        let scheduler = FairScheduler::default();
+
+        scheduler.enqueue(1, 1000);
+        scheduler.enqueue(2, 100);
+        scheduler.remove(1);
+
+        assert_eq!(scheduler.priority(1), usize::MAX);
+
+        // Packets of the removed request that are still queued don't affect
+        // the request's priority when they're dropped
+        scheduler.dequeue(1, 1000);
+        scheduler.enqueue(1, 10);
+
+        assert_eq!(scheduler.priority(1), usize::MAX - 10);
+
+        scheduler.clear();
+
+        assert!(scheduler.queued_bytes.lock().unwrap().is_empty());
+    }
+}
@@ -9,6 +9,10 @@ use serde_aux::prelude::*;
 use thiserror::Error;
 use tokio_tungstenite::tungstenite::protocol::frame::Frame;
 
+#[cfg(feature = "e2e")]
+pub mod e2e;
+pub mod flow_control;
+pub mod request_body;
 pub mod sender;
 pub mod websocket_sender;
 
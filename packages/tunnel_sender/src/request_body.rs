@@ -0,0 +1,329 @@
+//! Request bodies streamed from the tunnel server in
+//! [`TunnelRequestBody`] packets.
+//!
+//! The tunnel server only sends the packets that the sender granted credits
+//! for, starting with [`REQUEST_BODY_WINDOW`] credits once the body is
+//! [`open`](RequestBodies::open)ed, and more as the request consumes the body.
+//! Packets can still be processed out of order, so they are buffered until
+//! the body stream yields them in packet order.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Mutex,
+};
+
+use bytes::Bytes;
+use futures_util::Stream;
+use moosicbox_tunnel::TunnelRequestBody;
+use tokio::sync::mpsc::{channel, error::TrySendError, Receiver, Sender};
+
+/// The number of request body packets the tunnel server can send for each
+/// request before waiting for more credits.
+pub const REQUEST_BODY_WINDOW: u32 = 16;
+
+struct RequestBody {
+    tx: Sender<TunnelRequestBody>,
+    rx: Option<Receiver<TunnelRequestBody>>,
+}
+
+impl RequestBody {
+    fn new() -> Self {
+        let (tx, rx) = channel(REQUEST_BODY_WINDOW as usize);
+        Self { tx, rx: Some(rx) }
+    }
+}
+
+#[derive(Default)]
+pub struct RequestBodies {
+    bodies: Mutex<HashMap<usize, RequestBody>>,
+}
+
+impl RequestBodies {
+    /// Expects a streamed body for the request. The tunnel server should be
+    /// granted the [`REQUEST_BODY_WINDOW`] once the body is opened.
+    pub fn open(&self, request_id: usize) {
+        self.bodies
+            .lock()
+            .unwrap()
+            .entry(request_id)
+            .or_insert_with(RequestBody::new);
+    }
+
+    /// Buffers a packet of an opened body. Packets of requests that were never
+    /// opened or were already removed are dropped.
+    pub fn push(&self, body: TunnelRequestBody) {
+        log::trace!(
+            "Received request body packet request_id={} packet_id={} last={} (size {})",
+            body.request_id,
+            body.packet_id,
+            body.last,
+            body.bytes.len()
+        );
+
+        let request_id = body.request_id;
+        let mut bodies = self.bodies.lock().unwrap();
+
+        let Some(entry) = bodies.get(&request_id) else {
+            log::debug!("Dropping request body packet of unknown request_id={request_id}");
+            return;
+        };
+
+        match entry.tx.try_send(body) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                log::error!(
+                    "Request body of request_id={request_id} exceeded its window; dropping the body"
+                );
+                bodies.remove(&request_id);
+            }
+            Err(TrySendError::Closed(_)) => {
+                log::debug!("Request body stream dropped for request_id={request_id}");
+            }
+        }
+    }
+
+    /// Takes the body stream of a request that was [`open`](Self::open)ed.
+    /// The stream ends after the last packet, or with an error if the tunnel
+    /// server failed to read the body or the request is
+    /// [`remove`](Self::remove)d first. `grant` is called with the credits
+    /// to give back to the tunnel server as the body is consumed.
+    pub fn take(
+        &self,
+        request_id: usize,
+        grant: impl Fn(u32) + Send + 'static,
+    ) -> Option<impl Stream<Item = Result<Bytes, std::io::Error>>> {
+        let rx = self
+            .bodies
+            .lock()
+            .unwrap()
+            .get_mut(&request_id)?
+            .rx
+            .take()?;
+
+        Some(ordered_body(rx, grant))
+    }
+
+    /// Forgets the body of a finished request. Packets of the body that are
+    /// still on their way are dropped.
+    pub fn remove(&self, request_id: usize) {
+        self.bodies.lock().unwrap().remove(&request_id);
+    }
+}
+
+struct OrderedBody<F> {
+    rx: Receiver<TunnelRequestBody>,
+    pending: BTreeMap<u32, TunnelRequestBody>,
+    next: u32,
+    consumed: u32,
+    done: bool,
+    grant: F,
+}
+
+fn ordered_body(
+    rx: Receiver<TunnelRequestBody>,
+    grant: impl Fn(u32) + Send + 'static,
+) -> impl Stream<Item = Result<Bytes, std::io::Error>> {
+    let body = OrderedBody {
+        rx,
+        pending: BTreeMap::new(),
+        next: 1,
+        consumed: 0,
+        done: false,
+        grant,
+    };
+
+    futures_util::stream::unfold(body, |mut body| async move {
+        if body.done {
+            return None;
+        }
+
+        loop {
+            if let Some(packet) = body.pending.remove(&body.next) {
+                let TunnelRequestBody {
+                    last,
+                    failed,
+                    bytes,
+                    ..
+                } = packet;
+                body.next += 1;
+                body.done = last;
+
+                if failed {
+                    let err = std::io::Error::new(
+                        std::io::ErrorKind::UnexpectedEof,
+                        "Tunnel server failed to read the request body",
+                    );
+                    return Some((Err(err), body));
+                }
+
+                if !last {
+                    body.consumed += 1;
+                    if body.consumed >= (REQUEST_BODY_WINDOW / 2).max(1) {
+                        (body.grant)(body.consumed);
+                        body.consumed = 0;
+                    }
+                }
+
+                return Some((Ok(bytes), body));
+            }
+
+            let Some(packet) = body.rx.recv().await else {
+                body.done = true;
+                let err = std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    "Request body ended before its last packet",
+                );
+                return Some((Err(err), body));
+            };
+            body.pending.insert(packet.packet_id, packet);
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use futures_util::StreamExt as _;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn packet(packet_id: u32, last: bool, bytes: &'static [u8]) -> TunnelRequestBody {
+        TunnelRequestBody {
+            request_id: 1,
+            packet_id,
+            last,
+            failed: false,
+            bytes: Bytes::from_static(bytes),
+        }
+    }
+
+    #[test_log::test]
+    fn frames_packets_like_response_packets() {
+        let body = packet(3, true, b"body");
+        let bytes = Bytes::from(body.clone());
+
+        assert_eq!(bytes.len(), 13 + 4);
+        assert_eq!(TunnelRequestBody::try_from(bytes).unwrap(), body);
+    }
+
+    #[test_log::test]
+    fn rejects_truncated_packets() {
+        let bytes = Bytes::from(packet(1, false, b""));
+
+        assert!(TunnelRequestBody::try_from(bytes.slice(..12)).is_err());
+        assert!(TunnelRequestBody::try_from(Bytes::new()).is_err());
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn streams_packets_in_order_until_the_last() {
+        let bodies = RequestBodies::default();
+        bodies.open(1);
+        bodies.push(packet(2, false, b"b"));
+        bodies.push(packet(3, true, b"c"));
+        bodies.push(packet(1, false, b"a"));
+        bodies.push(packet(4, false, b"ignored"));
+
+        let body = bodies.take(1, |_| {}).unwrap();
+        let chunks = body.map(|x| x.unwrap()).collect::<Vec<_>>().await;
+
+        assert_eq!(chunks, vec!["a", "b", "c"]);
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn fails_the_stream_when_the_body_failed_to_upload() {
+        let bodies = RequestBodies::default();
+        bodies.open(1);
+        bodies.push(packet(1, false, b"a"));
+        bodies.push(TunnelRequestBody {
+            failed: true,
+            ..packet(2, true, b"")
+        });
+
+        let mut body = bodies.take(1, |_| {}).unwrap().boxed();
+
+        assert_eq!(body.next().await.unwrap().unwrap(), "a");
+        assert!(body.next().await.unwrap().is_err());
+        assert!(body.next().await.is_none());
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn drops_packets_of_unknown_requests() {
+        let bodies = RequestBodies::default();
+        bodies.push(packet(1, false, b"a"));
+
+        assert!(bodies.bodies.lock().unwrap().is_empty());
+        assert!(bodies.take(1, |_| {}).is_none());
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn ends_the_stream_when_the_request_is_removed() {
+        let bodies = RequestBodies::default();
+        bodies.open(1);
+        bodies.push(packet(1, false, b"a"));
+
+        let mut body = bodies.take(1, |_| {}).unwrap().boxed();
+        bodies.remove(1);
+
+        assert_eq!(body.next().await.unwrap().unwrap(), "a");
+        assert!(body.next().await.unwrap().is_err());
+        assert!(body.next().await.is_none());
+
+        bodies.push(packet(2, true, b"b"));
+
+        assert!(bodies.bodies.lock().unwrap().is_empty());
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn grants_credits_as_the_body_is_consumed() {
+        let bodies = RequestBodies::default();
+        bodies.open(1);
+
+        for packet_id in 1..=REQUEST_BODY_WINDOW {
+            bodies.push(packet(packet_id, false, b"a"));
+        }
+
+        let granted = Arc::new(Mutex::new(vec![]));
+        let mut body = bodies
+            .take(1, {
+                let granted = granted.clone();
+                move |credits| granted.lock().unwrap().push(credits)
+            })
+            .unwrap()
+            .boxed();
+
+        for _ in 0..REQUEST_BODY_WINDOW {
+            body.next().await.unwrap().unwrap();
+        }
+
+        assert_eq!(
+            *granted.lock().unwrap(),
+            vec![REQUEST_BODY_WINDOW / 2, REQUEST_BODY_WINDOW / 2]
+        );
+    }
+
+    #[test_log::test]
+    fn drops_bodies_that_exceed_their_window() {
+        let bodies = RequestBodies::default();
+        bodies.open(1);
+
+        for packet_id in 1..=REQUEST_BODY_WINDOW + 1 {
+            bodies.push(packet(packet_id, false, b"a"));
+        }
+
+        assert!(bodies.bodies.lock().unwrap().is_empty());
+    }
+
+    #[test_log::test]
+    fn only_takes_opened_bodies_once() {
+        let bodies = RequestBodies::default();
+
+        assert!(bodies.take(1, |_| {}).is_none());
+
+        bodies.open(1);
+
+        assert!(bodies.take(1, |_| {}).is_some());
+        assert!(bodies.take(1, |_| {}).is_none());
+    }
+}
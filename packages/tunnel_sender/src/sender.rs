@@ -1,4 +1,6 @@
 use std::collections::HashMap;
+#[cfg(feature = "e2e")]
+use std::collections::VecDeque;
 use std::fs::File;
 use std::io::Cursor;
 use std::sync::{Arc, LazyLock, RwLock};
@@ -30,7 +32,17 @@ use moosicbox_music_api::{SourceToMusicApi as _, TrackSource};
 use moosicbox_player::symphonia::play_media_source_async;
 use moosicbox_stream_utils::remote_bytestream::RemoteByteStream;
 use moosicbox_stream_utils::ByteWriter;
-use moosicbox_tunnel::{Method, TunnelEncoding, TunnelWsResponse};
+#[cfg(feature = "compression")]
+use moosicbox_tunnel::{compression::SUPPORTED_COMPRESSIONS, TunnelCompression};
+#[cfg(feature = "e2e")]
+use moosicbox_tunnel::{
+    e2e::{E2eHandshake, E2eHandshakeRequest, E2eHandshakeResponse, E2eSession},
+    TunnelEncryptedRequest,
+};
+use moosicbox_tunnel::{
+    Method, TunnelEncoding, TunnelHandshake, TunnelRequestBody, TunnelSenderMessage,
+    TunnelWindowUpdate, TunnelWsResponse, TUNNEL_PROTOCOL_VERSION,
+};
 use moosicbox_ws::{PlayerAction, WebsocketContext, WebsocketSendError, WebsocketSender};
 use rand::{thread_rng, Rng as _};
 use regex::Regex;
@@ -52,6 +64,8 @@ use super::{
     GetTrackInfoQuery, GetTrackQuery, SendBytesError, SendMessageError, TunnelMessage,
     TunnelRequestError,
 };
+use crate::flow_control::{FairScheduler, FlowControl};
+use crate::request_body::{RequestBodies, REQUEST_BODY_WINDOW};
 use crate::websocket_sender::TunnelWebsocketSender;
 
 #[derive(Debug, Error)]
@@ -98,6 +112,7 @@ impl WebsocketSender for TunnelSenderHandle {
                     message: data.into(),
                     exclude_connection_ids: None,
                     to_connection_ids: Some(vec![conn_id.parse::<usize>()?]),
+                    profile: None,
                 }))
                 .map_err(|e| WebsocketSendError::Unknown(e.to_string()))?;
         }
@@ -111,6 +126,25 @@ impl WebsocketSender for TunnelSenderHandle {
                     message: data.into(),
                     exclude_connection_ids: None,
                     to_connection_ids: None,
+                    profile: None,
+                }))
+                .map_err(|e| WebsocketSendError::Unknown(e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    async fn send_all_profile(
+        &self,
+        profile: &str,
+        data: &str,
+    ) -> Result<(), moosicbox_ws::WebsocketSendError> {
+        if let Some(sender) = self.sender.read().unwrap().as_ref() {
+            sender
+                .send(TunnelResponseMessage::Ws(TunnelResponseWs {
+                    message: data.into(),
+                    exclude_connection_ids: None,
+                    to_connection_ids: None,
+                    profile: Some(profile.to_string()),
                 }))
                 .map_err(|e| WebsocketSendError::Unknown(e.to_string()))?;
         }
@@ -128,6 +162,7 @@ impl WebsocketSender for TunnelSenderHandle {
                     message: data.into(),
                     exclude_connection_ids: Some(vec![conn_id.parse::<usize>()?]),
                     to_connection_ids: None,
+                    profile: None,
                 }))
                 .map_err(|e| WebsocketSendError::Unknown(e.to_string()))?;
         }
@@ -147,6 +182,7 @@ impl WebsocketSender for TunnelSenderHandle {
 pub enum TunnelResponseMessage {
     Packet(TunnelResponsePacket),
     Ws(TunnelResponseWs),
+    Control(TunnelSenderMessage),
     Ping,
 }
 
@@ -163,8 +199,14 @@ pub struct TunnelResponseWs {
     pub message: Message,
     pub exclude_connection_ids: Option<Vec<usize>>,
     pub to_connection_ids: Option<Vec<usize>>,
+    pub profile: Option<String>,
 }
 
+#[cfg(feature = "e2e")]
+type E2eSessionEntry = (String, Arc<E2eSession>);
+#[cfg(feature = "e2e")]
+type E2eSessionRequest = (Arc<E2eSession>, usize);
+
 #[derive(Clone)]
 pub struct TunnelSender {
     id: usize,
@@ -177,6 +219,27 @@ pub struct TunnelSender {
     abort_request_tokens: Arc<RwLock<HashMap<usize, CancellationToken>>>,
     player_actions: Arc<RwLock<Vec<(u64, PlayerAction)>>>,
     config_db: ConfigDatabase,
+    flow_control: Arc<FlowControl>,
+    scheduler: Arc<FairScheduler>,
+    request_bodies: Arc<RequestBodies>,
+    /// The compression the tunnel server accepts for each in-flight request
+    #[cfg(feature = "compression")]
+    request_compressions: Arc<RwLock<HashMap<usize, TunnelCompression>>>,
+    /// The compression of the in-flight responses being compressed
+    #[cfg(feature = "compression")]
+    compressed_requests: Arc<RwLock<HashMap<usize, TunnelCompression>>>,
+    /// The compression the tunnel server accepts for websocket messages
+    #[cfg(feature = "compression")]
+    ws_compression: Arc<RwLock<Option<TunnelCompression>>>,
+    #[cfg(feature = "e2e")]
+    e2e_sessions: Arc<RwLock<VecDeque<E2eSessionEntry>>>,
+    /// The session and client request id of the in-flight E2E requests
+    #[cfg(feature = "e2e")]
+    e2e_requests: Arc<RwLock<HashMap<usize, E2eSessionRequest>>>,
+    /// Refuse the plaintext requests of the tunnel server, other than E2E
+    /// handshakes
+    #[cfg(feature = "e2e")]
+    require_e2e: bool,
 }
 
 static BINARY_REQUEST_BUFFER_OFFSET: LazyLock<usize> = LazyLock::new(|| {
@@ -185,6 +248,15 @@ static BINARY_REQUEST_BUFFER_OFFSET: LazyLock<usize> = LazyLock::new(|| {
     std::mem::size_of::<u8>() // last
 });
 
+/// The number of E2E sessions kept before the oldest are dropped. Clients
+/// handshake again when their session is no longer known.
+#[cfg(feature = "e2e")]
+static MAX_E2E_SESSIONS: usize = 256;
+
+/// Websocket messages smaller than this are not worth compressing.
+#[cfg(feature = "compression")]
+static MIN_COMPRESSED_WS_MESSAGE_SIZE: usize = 1024;
+
 static DEFAULT_WS_MAX_PACKET_SIZE: usize = 1024 * 64;
 static WS_MAX_PACKET_SIZE: usize =
     default_env_usize!("WS_MAX_PACKET_SIZE", DEFAULT_WS_MAX_PACKET_SIZE);
@@ -219,6 +291,21 @@ impl TunnelSender {
                 abort_request_tokens: Arc::new(RwLock::new(HashMap::new())),
                 player_actions,
                 config_db,
+                flow_control: Arc::new(FlowControl::default()),
+                scheduler: Arc::new(FairScheduler::default()),
+                request_bodies: Arc::new(RequestBodies::default()),
+                #[cfg(feature = "compression")]
+                request_compressions: Arc::new(RwLock::new(HashMap::new())),
+                #[cfg(feature = "compression")]
+                compressed_requests: Arc::new(RwLock::new(HashMap::new())),
+                #[cfg(feature = "compression")]
+                ws_compression: Arc::new(RwLock::new(None)),
+                #[cfg(feature = "e2e")]
+                e2e_sessions: Arc::new(RwLock::new(VecDeque::new())),
+                #[cfg(feature = "e2e")]
+                e2e_requests: Arc::new(RwLock::new(HashMap::new())),
+                #[cfg(feature = "e2e")]
+                require_e2e: false,
             },
             handle,
         )
@@ -229,6 +316,14 @@ impl TunnelSender {
         self
     }
 
+    /// Only handles requests sealed in an E2E session, so that the tunnel
+    /// server can't make requests of its own.
+    #[cfg(feature = "e2e")]
+    pub fn with_require_e2e(mut self, require_e2e: bool) -> Self {
+        self.require_e2e = require_e2e;
+        self
+    }
+
     pub fn add_player_action(self, id: u64, action: PlayerAction) -> Self {
         self.player_actions.write().unwrap().push((id, action));
         self
@@ -278,6 +373,10 @@ impl TunnelSender {
         let sender_arc = self.sender.clone();
         let abort_request_tokens = self.abort_request_tokens.clone();
         let cancellation_token = self.cancellation_token.clone();
+        let flow_control = self.flow_control.clone();
+        let scheduler = self.scheduler.clone();
+        #[cfg(feature = "compression")]
+        let ws_compression = self.ws_compression.clone();
 
         moosicbox_task::spawn("tunnel_sender", async move {
             let mut just_retried = false;
@@ -314,23 +413,39 @@ impl TunnelSender {
             loop {
                 let close_token = CancellationToken::new();
 
+                // Flow control and websocket message compression are enabled
+                // again by the tunnel server's handshake
+                flow_control.disable();
+                // The packets queued for the previous connection were dropped
+                // with it
+                scheduler.clear();
+                #[cfg(feature = "compression")]
+                ws_compression.write().unwrap().take();
+
                 let (txf, rxf) = moosicbox_channel_utils::futures_channel::unbounded();
-                let txf = txf.with_priority(|message: &TunnelResponseMessage| match message {
-                    TunnelResponseMessage::Packet(packet) => {
-                        log::debug!(
-                            "determining priority for packet: packet_id={} len={}",
-                            packet.packet_id,
-                            packet.message.len()
-                        );
-                        usize::MAX - packet.message.len()
-                    }
-                    TunnelResponseMessage::Ws(ws) => {
-                        log::debug!("determining priority for ws: len={}", ws.message.len());
-                        usize::MAX - ws.message.len()
-                    }
-                    TunnelResponseMessage::Ping => {
-                        log::debug!("determining priority for ping");
-                        usize::MAX
+                let txf = txf.with_priority({
+                    let scheduler = scheduler.clone();
+                    move |message: &TunnelResponseMessage| match message {
+                        TunnelResponseMessage::Packet(packet) => {
+                            log::debug!(
+                                "determining priority for packet: packet_id={} len={}",
+                                packet.packet_id,
+                                packet.message.len()
+                            );
+                            scheduler.priority(packet.request_id)
+                        }
+                        TunnelResponseMessage::Ws(ws) => {
+                            log::debug!("determining priority for ws: len={}", ws.message.len());
+                            usize::MAX - ws.message.len()
+                        }
+                        TunnelResponseMessage::Control(_) => {
+                            log::debug!("determining priority for control message");
+                            usize::MAX
+                        }
+                        TunnelResponseMessage::Ping => {
+                            log::debug!("determining priority for ping");
+                            usize::MAX
+                        }
                     }
                 });
 
@@ -340,7 +455,7 @@ impl TunnelSender {
 
                 match select!(
                     resp = connect_async(
-                        format!("{url}?clientId={client_id}&sender=true&signature={token}"),
+                        format!("{url}?clientId={client_id}&sender=true&signature={token}&protocolVersion={TUNNEL_PROTOCOL_VERSION}"),
                     ) => resp,
                     _ = cancellation_token.cancelled() => {
                         log::debug!("Cancelling connect");
@@ -358,6 +473,11 @@ impl TunnelSender {
                         let (write, read) = ws_stream.split();
 
                         let ws_writer = rxf
+                                .inspect(|message| {
+                                    if let TunnelResponseMessage::Packet(packet) = message {
+                                        scheduler.dequeue(packet.request_id, packet.message.len());
+                                    }
+                                })
                                 .filter(|message| {
                                     match message {
                                         TunnelResponseMessage::Packet(packet) => {
@@ -372,6 +492,7 @@ impl TunnelSender {
                                             }
                                         },
                                         TunnelResponseMessage::Ws(_ws) => {}
+                                        TunnelResponseMessage::Control(_) => {}
                                         TunnelResponseMessage::Ping => {}
                                     }
 
@@ -412,7 +533,17 @@ impl TunnelSender {
                                                         body: value,
                                                         exclude_connection_ids: ws.exclude_connection_ids,
                                                         to_connection_ids: ws.to_connection_ids,
-                                                    }).map(Message::Text)
+                                                        profile: ws.profile,
+                                                    }).map(|text| {
+                                                        #[cfg(feature = "compression")]
+                                                        {
+                                                            Self::compress_ws_message(text, *ws_compression.read().unwrap())
+                                                        }
+                                                        #[cfg(not(feature = "compression"))]
+                                                        {
+                                                            Message::Text(text)
+                                                        }
+                                                    })
                                                 }).map_err(|e| {
                                                     log::error!("Serde error occurred: {e:?}");
                                                     tokio_tungstenite::tungstenite::Error::AlreadyClosed
@@ -421,6 +552,15 @@ impl TunnelSender {
                                                 Ok(ws.message)
                                             }
                                         },
+                                        TunnelResponseMessage::Control(message) => {
+                                            log::trace!("Sending control message {message:?}");
+                                            serde_json::to_string(&message)
+                                                .map(Message::Text)
+                                                .map_err(|e| {
+                                                    log::error!("Serde error occurred: {e:?}");
+                                                    tokio_tungstenite::tungstenite::Error::AlreadyClosed
+                                                })
+                                        }
                                         TunnelResponseMessage::Ping => {
                                             log::trace!("Sending ping");
                                             Ok(Message::Ping(vec![]))
@@ -529,7 +669,15 @@ impl TunnelSender {
         packet_id: u32,
         bytes: impl Into<Vec<u8>>,
     ) -> Result<(), SendBytesError> {
+        let bytes = bytes.into();
+        #[cfg(feature = "compression")]
+        let bytes = self.compress_packet(request_id, bytes)?;
+        #[cfg(feature = "e2e")]
+        let bytes = self.seal_e2e_packet(request_id, bytes)?;
+
         if let Some(sender) = self.sender.read().unwrap().as_ref() {
+            let len = bytes.len();
+            self.scheduler.enqueue(request_id, len);
             sender
                 .send(TunnelResponseMessage::Packet(TunnelResponsePacket {
                     request_id,
@@ -537,9 +685,12 @@ impl TunnelSender {
                     broadcast: true,
                     except_id: None,
                     only_id: None,
-                    message: Message::Binary(bytes.into()),
+                    message: Message::Binary(bytes),
                 }))
-                .map_err(|err| SendBytesError::Unknown(format!("Failed to send_bytes: {err:?}")))?;
+                .map_err(|err| {
+                    self.scheduler.dequeue(request_id, len);
+                    SendBytesError::Unknown(format!("Failed to send_bytes: {err:?}"))
+                })?;
         } else {
             return Err(SendBytesError::Unknown(
                 "Failed to get sender for send_bytes".into(),
@@ -549,6 +700,152 @@ impl TunnelSender {
         Ok(())
     }
 
+    /// Compresses the packet if its response is being compressed.
+    #[cfg(feature = "compression")]
+    fn compress_packet(
+        &self,
+        request_id: usize,
+        bytes: Vec<u8>,
+    ) -> Result<Vec<u8>, SendBytesError> {
+        let Some(compression) = self
+            .compressed_requests
+            .read()
+            .unwrap()
+            .get(&request_id)
+            .copied()
+        else {
+            return Ok(bytes);
+        };
+
+        moosicbox_tunnel::compression::compress_packet(compression, &bytes)
+            .map_err(|err| SendBytesError::Unknown(format!("Failed to compress packet: {err:?}")))
+    }
+
+    /// Sends the websocket message as a compressed binary message if the
+    /// tunnel server accepts them and it makes the message smaller.
+    #[cfg(feature = "compression")]
+    fn compress_ws_message(text: String, compression: Option<TunnelCompression>) -> Message {
+        let Some(compression) = compression else {
+            return Message::Text(text);
+        };
+        if text.len() < MIN_COMPRESSED_WS_MESSAGE_SIZE {
+            return Message::Text(text);
+        }
+
+        match moosicbox_tunnel::compression::compress(compression, text.as_bytes()) {
+            Ok(compressed) if compressed.len() + 9 < text.len() => {
+                log::trace!(
+                    "Compressed ws message from {} to {} bytes",
+                    text.len(),
+                    compressed.len()
+                );
+                Message::Binary(
+                    [
+                        0_usize.to_be_bytes().as_slice(),
+                        &[compression as u8],
+                        &compressed,
+                    ]
+                    .concat(),
+                )
+            }
+            Ok(_) => Message::Text(text),
+            Err(err) => {
+                log::error!("Failed to compress ws message: {err:?}");
+                Message::Text(text)
+            }
+        }
+    }
+
+    /// Takes the compressions the tunnel server accepts out of the request
+    /// headers, remembering the preferred one for the response.
+    #[cfg(feature = "compression")]
+    fn negotiate_compression(&self, request_id: usize, headers: Option<Value>) -> Option<Value> {
+        let mut headers = headers?;
+
+        let compression = headers
+            .as_object_mut()
+            .and_then(|x| x.remove(moosicbox_tunnel::TUNNEL_COMPRESSION_HEADER))
+            .and_then(|x| {
+                x.as_str()
+                    .and_then(moosicbox_tunnel::compression::negotiate)
+            });
+
+        if let Some(compression) = compression {
+            self.request_compressions
+                .write()
+                .unwrap()
+                .insert(request_id, compression);
+        }
+
+        Some(headers)
+    }
+
+    /// Compresses the response if the tunnel server accepts a compression and
+    /// the content is worth compressing, marking it in the response headers.
+    #[cfg(feature = "compression")]
+    fn compress_response(
+        &self,
+        request_id: usize,
+        mut headers: HashMap<String, String>,
+        encoding: &TunnelEncoding,
+        ranges: bool,
+    ) -> HashMap<String, String> {
+        let Some(compression) = self
+            .request_compressions
+            .read()
+            .unwrap()
+            .get(&request_id)
+            .copied()
+        else {
+            return headers;
+        };
+
+        #[cfg(feature = "e2e")]
+        if self.e2e_requests.read().unwrap().contains_key(&request_id) {
+            return headers;
+        }
+
+        if *encoding != TunnelEncoding::Binary
+            || ranges
+            || headers.contains_key("content-encoding")
+            || !headers.get("content-type").is_some_and(|content_type| {
+                moosicbox_tunnel::compression::is_compressible_content_type(content_type)
+            })
+        {
+            return headers;
+        }
+
+        log::debug!("Compressing response for request_id={request_id} with {compression:?}");
+
+        headers.insert(
+            moosicbox_tunnel::TUNNEL_COMPRESSION_HEADER.to_string(),
+            compression.as_ref().to_string(),
+        );
+        self.compressed_requests
+            .write()
+            .unwrap()
+            .insert(request_id, compression);
+
+        headers
+    }
+
+    /// Seals the packet if it is a response to an E2E encrypted request.
+    #[cfg(feature = "e2e")]
+    fn seal_e2e_packet(
+        &self,
+        request_id: usize,
+        bytes: Vec<u8>,
+    ) -> Result<Vec<u8>, SendBytesError> {
+        let Some((session, id)) = self.e2e_requests.read().unwrap().get(&request_id).cloned()
+        else {
+            return Ok(bytes);
+        };
+
+        session
+            .seal_response_packet(id, &bytes)
+            .map_err(|err| SendBytesError::Unknown(format!("Failed to seal packet: {err:?}")))
+    }
+
     pub fn send_message(
         &self,
         request_id: usize,
@@ -556,6 +853,9 @@ impl TunnelSender {
         message: impl Into<String>,
     ) -> Result<(), SendMessageError> {
         if let Some(sender) = self.sender.read().unwrap().as_ref() {
+            let message = message.into();
+            let len = message.len();
+            self.scheduler.enqueue(request_id, len);
             sender
                 .send(TunnelResponseMessage::Packet(TunnelResponsePacket {
                     request_id,
@@ -563,9 +863,10 @@ impl TunnelSender {
                     broadcast: true,
                     except_id: None,
                     only_id: None,
-                    message: Message::Text(message.into()),
+                    message: Message::Text(message),
                 }))
                 .map_err(|err| {
+                    self.scheduler.dequeue(request_id, len);
                     SendMessageError::Unknown(format!("Failed to send_message: {err:?}"))
                 })?;
         } else {
@@ -577,7 +878,106 @@ impl TunnelSender {
         Ok(())
     }
 
-    fn send(
+    /// Waits for the tunnel server to grant the request a packet credit.
+    /// Returns `false` if the request was aborted while waiting.
+    async fn acquire_packet_credit(&self, request_id: usize) -> bool {
+        let abort_token = self
+            .abort_request_tokens
+            .read()
+            .unwrap()
+            .get(&request_id)
+            .cloned();
+
+        self.flow_control
+            .acquire(request_id, abort_token.as_ref())
+            .await
+    }
+
+    async fn send_packet_bytes(
+        &self,
+        request_id: usize,
+        packet_id: u32,
+        bytes: impl Into<Vec<u8>>,
+    ) -> Result<(), SendBytesError> {
+        if !self.acquire_packet_credit(request_id).await {
+            log::debug!("Not sending packet from aborted request request_id={request_id} packet_id={packet_id}");
+            return Ok(());
+        }
+
+        self.send_bytes(request_id, packet_id, bytes)
+    }
+
+    #[cfg(feature = "base64")]
+    async fn send_packet_message(
+        &self,
+        request_id: usize,
+        packet_id: u32,
+        message: impl Into<String>,
+    ) -> Result<(), SendMessageError> {
+        if !self.acquire_packet_credit(request_id).await {
+            log::debug!("Not sending packet from aborted request request_id={request_id} packet_id={packet_id}");
+            return Ok(());
+        }
+
+        self.send_message(request_id, packet_id, message)
+    }
+
+    /// Enables flow control as negotiated by the tunnel server.
+    pub fn handshake(&self, handshake: &TunnelHandshake) {
+        log::debug!(
+            "Received tunnel handshake protocol_version={} window={}",
+            handshake.protocol_version,
+            handshake.window
+        );
+        if handshake.protocol_version >= moosicbox_tunnel::FLOW_CONTROL_PROTOCOL_VERSION {
+            self.flow_control.enable(handshake.window);
+        }
+
+        #[cfg(feature = "compression")]
+        {
+            let compression = handshake
+                .compression
+                .iter()
+                .find(|x| SUPPORTED_COMPRESSIONS.contains(x))
+                .copied();
+            log::debug!("Using ws message compression={compression:?}");
+            *self.ws_compression.write().unwrap() = compression;
+        }
+    }
+
+    /// Receives a packet of a request body streamed by the tunnel server.
+    pub fn request_body(&self, body: TunnelRequestBody) {
+        self.request_bodies.push(body);
+    }
+
+    /// Grants the tunnel server `credits` more packets of the request body.
+    fn grant_request_body_credits(&self, request_id: usize, credits: u32) {
+        log::trace!("Granting request_id={request_id} {credits} request body credits");
+
+        let message = TunnelSenderMessage::RequestBodyWindowUpdate(TunnelWindowUpdate {
+            request_id,
+            credits,
+        });
+
+        if let Some(sender) = self.sender.read().unwrap().as_ref() {
+            if let Err(err) = sender.send(TunnelResponseMessage::Control(message)) {
+                log::error!(
+                    "Failed to grant request body credits for request_id={request_id}: {err:?}"
+                );
+            }
+        }
+    }
+
+    pub fn update_window(&self, update: &TunnelWindowUpdate) {
+        log::trace!(
+            "Received window update request_id={} credits={}",
+            update.request_id,
+            update.credits
+        );
+        self.flow_control.grant(update.request_id, update.credits);
+    }
+
+    async fn send(
         &self,
         request_id: usize,
         status: u16,
@@ -585,10 +985,13 @@ impl TunnelSender {
         reader: impl std::io::Read,
         encoding: TunnelEncoding,
     ) -> Result<(), TunnelRequestError> {
+        #[cfg(feature = "compression")]
+        let headers = self.compress_response(request_id, headers, &encoding, false);
+
         match encoding {
-            TunnelEncoding::Binary => self.send_binary(request_id, status, headers, reader),
+            TunnelEncoding::Binary => self.send_binary(request_id, status, headers, reader).await,
             #[cfg(feature = "base64")]
-            TunnelEncoding::Base64 => self.send_base64(request_id, status, headers, reader),
+            TunnelEncoding::Base64 => self.send_base64(request_id, status, headers, reader).await,
         }
     }
 
@@ -601,6 +1004,9 @@ impl TunnelSender {
         stream: impl Stream<Item = Result<Bytes, E>> + std::marker::Unpin,
         encoding: TunnelEncoding,
     ) -> Result<(), TunnelRequestError> {
+        #[cfg(feature = "compression")]
+        let headers = self.compress_response(request_id, headers, &encoding, ranges.is_some());
+
         match encoding {
             TunnelEncoding::Binary => {
                 self.send_binary_stream(request_id, status, headers, ranges, stream)
@@ -773,15 +1179,18 @@ impl TunnelSender {
                         buf[*BINARY_REQUEST_BUFFER_OFFSET - 1] = 1;
                     }
 
-                    if let Err(err) = self.send_bytes(
-                        request_id,
-                        packet_id,
-                        [
-                            &headers_bytes[..header_offset],
-                            &buf[header_offset + start..header_offset + end],
-                        ]
-                        .concat(),
-                    ) {
+                    if let Err(err) = self
+                        .send_packet_bytes(
+                            request_id,
+                            packet_id,
+                            [
+                                &headers_bytes[..header_offset],
+                                &buf[header_offset + start..header_offset + end],
+                            ]
+                            .concat(),
+                        )
+                        .await
+                    {
                         log::error!("Failed to send bytes: {err:?}");
                         return Ok(());
                     }
@@ -793,7 +1202,7 @@ impl TunnelSender {
                 }
             } else {
                 let bytes = &buf[..offset];
-                if let Err(err) = self.send_bytes(request_id, packet_id, bytes) {
+                if let Err(err) = self.send_packet_bytes(request_id, packet_id, bytes).await {
                     log::error!("Failed to send bytes: {err:?}");
                     break;
                 }
@@ -809,7 +1218,7 @@ impl TunnelSender {
             && !range.end.is_some_and(|end| end < packet_start)
     }
 
-    fn send_binary(
+    async fn send_binary(
         &self,
         request_id: usize,
         status: u16,
@@ -851,7 +1260,7 @@ impl TunnelSender {
             }
 
             let bytes = &buf[..(read + offset)];
-            if let Err(err) = self.send_bytes(request_id, packet_id, bytes) {
+            if let Err(err) = self.send_packet_bytes(request_id, packet_id, bytes).await {
                 log::error!("Failed to send bytes: {err:?}");
                 break;
             }
@@ -889,7 +1298,7 @@ impl TunnelSender {
     }
 
     #[cfg(feature = "base64")]
-    fn send_base64(
+    async fn send_base64(
         &self,
         request_id: usize,
         status: u16,
@@ -940,7 +1349,8 @@ impl TunnelSender {
                     let end = min(base64.len(), buf_size - prefix.len());
                     let data = &base64[..end];
                     overflow_buf.push_str(&base64[end..]);
-                    self.send_message(request_id, packet_id, format!("{prefix}{data}"))
+                    self.send_packet_message(request_id, packet_id, format!("{prefix}{data}"))
+                        .await
                         .map_err(wrap_to_500)?;
 
                     if size == 0 {
@@ -952,13 +1362,19 @@ impl TunnelSender {
                             overflow_buf.push_str(&base64[end..]);
                             packet_id += 1;
                             let prefix = format!("{request_id}|{packet_id}|");
-                            self.send_message(request_id, packet_id, format!("{prefix}{data}"))
-                                .map_err(wrap_to_500)?;
+                            self.send_packet_message(
+                                request_id,
+                                packet_id,
+                                format!("{prefix}{data}"),
+                            )
+                            .await
+                            .map_err(wrap_to_500)?;
                         }
 
                         packet_id += 1;
                         let prefix = format!("{request_id}|{packet_id}|");
-                        self.send_message(request_id, packet_id, prefix)
+                        self.send_packet_message(request_id, packet_id, prefix)
+                            .await
                             .map_err(wrap_to_500)?;
                         break Ok(());
                     }
@@ -1039,12 +1455,14 @@ impl TunnelSender {
 
             let end = min(buf.len(), buf_size - prefix.len());
             let data = &buf[..end];
-            self.send_message(request_id, packet_id, format!("{prefix}{data}"))
+            self.send_packet_message(request_id, packet_id, format!("{prefix}{data}"))
+                .await
                 .map_err(wrap_to_500)?;
 
             if buf.is_empty() {
                 let prefix = format!("{request_id}|{packet_id}|");
-                self.send_message(request_id, packet_id, prefix)
+                self.send_packet_message(request_id, packet_id, prefix)
+                    .await
                     .map_err(wrap_to_500)?;
                 break Ok(());
             }
@@ -1105,8 +1523,16 @@ impl TunnelSender {
         profile: Option<String>,
         encoding: TunnelEncoding,
     ) -> Result<(), TunnelRequestError> {
+        let body = self
+            .request_bodies
+            .take(request_id, {
+                let sender = self.clone();
+                move |credits| sender.grant_request_body_credits(request_id, credits)
+            })
+            .map(reqwest::Body::wrap_stream);
+
         let response = self
-            .http_request(url, method, payload, headers, profile, true)
+            .http_request(url, method, payload, body, headers, profile, true)
             .await?;
 
         let headers = response
@@ -1126,11 +1552,13 @@ impl TunnelSender {
         .await
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn http_request(
         &self,
         url: &str,
         method: Method,
         payload: Option<Value>,
+        body: Option<reqwest::Body>,
         headers: Option<Value>,
         profile: Option<String>,
         user_agent_header: bool,
@@ -1161,15 +1589,92 @@ impl TunnelSender {
             builder = builder.header("user-agent", "MOOSICBOX_TUNNEL");
         }
 
-        if let Some(body) = payload {
+        if let Some(body) = body {
+            builder = builder.body(body);
+        } else if let Some(body) = payload {
             builder = builder.json(&body);
         }
 
         builder.send().await
     }
 
+    /// Handles a request from the tunnel server. If `streaming_body` is set,
+    /// the request body follows in [`TunnelRequestBody`] packets passed to
+    /// [`request_body`](Self::request_body).
     #[allow(clippy::too_many_arguments)]
     pub async fn tunnel_request(
+        &self,
+        service_port: u16,
+        request_id: usize,
+        method: Method,
+        path: String,
+        query: Value,
+        payload: Option<Value>,
+        streaming_body: bool,
+        headers: Option<Value>,
+        profile: Option<String>,
+        encoding: TunnelEncoding,
+    ) -> Result<(), TunnelRequestError> {
+        #[cfg(feature = "e2e")]
+        if self.require_e2e
+            && !self.e2e_requests.read().unwrap().contains_key(&request_id)
+            && path.to_lowercase() != moosicbox_tunnel::e2e::E2E_HANDSHAKE_PATH
+        {
+            log::warn!("Refusing plaintext request_id={request_id} path={path}: E2E encryption is required");
+            let mut headers = HashMap::new();
+            headers.insert("content-type".to_string(), "text/plain".to_string());
+            return self
+                .send(
+                    request_id,
+                    403,
+                    headers,
+                    Cursor::new(b"E2E encryption is required"),
+                    encoding,
+                )
+                .await;
+        }
+
+        if streaming_body {
+            self.request_bodies.open(request_id);
+            self.grant_request_body_credits(request_id, REQUEST_BODY_WINDOW);
+        }
+
+        #[cfg(feature = "compression")]
+        let headers = self.negotiate_compression(request_id, headers);
+
+        let response = self
+            .handle_tunnel_request(
+                service_port,
+                request_id,
+                method,
+                path,
+                query,
+                payload,
+                headers,
+                profile,
+                encoding,
+            )
+            .await;
+
+        self.flow_control.remove(request_id);
+        self.request_bodies.remove(request_id);
+        #[cfg(feature = "compression")]
+        {
+            self.request_compressions
+                .write()
+                .unwrap()
+                .remove(&request_id);
+            self.compressed_requests
+                .write()
+                .unwrap()
+                .remove(&request_id);
+        }
+
+        response
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn handle_tunnel_request(
         &self,
         service_port: u16,
         request_id: usize,
@@ -1196,6 +1701,11 @@ impl TunnelSender {
             .and_then(|x| moosicbox_music_api::profiles::PROFILES.get(x));
 
         match path.to_lowercase().as_str() {
+            #[cfg(feature = "e2e")]
+            moosicbox_tunnel::e2e::E2E_HANDSHAKE_PATH => match method {
+                Method::Post => self.e2e_handshake(request_id, payload, encoding).await,
+                _ => Err(TunnelRequestError::UnsupportedMethod),
+            },
             "files/track" => {
                 match method {
                     Method::Get => {
@@ -1291,7 +1801,8 @@ impl TunnelSender {
                                             response_headers,
                                             File::open(path)?,
                                             encoding,
-                                        )?;
+                                        )
+                                        .await?;
                                     }
                                 }
                             }
@@ -1452,7 +1963,8 @@ impl TunnelSender {
                     if let Ok(track_info) = get_track_info(&**api, &query.track_id.into()).await {
                         let mut bytes: Vec<u8> = Vec::new();
                         serde_json::to_writer(&mut bytes, &track_info)?;
-                        self.send(request_id, 200, headers, Cursor::new(bytes), encoding)?;
+                        self.send(request_id, 200, headers, Cursor::new(bytes), encoding)
+                            .await?;
                     }
 
                     Ok(())
@@ -1582,7 +2094,8 @@ impl TunnelSender {
                                 "cache-control".to_string(),
                                 format!("max-age={}", 86400u32 * 14),
                             );
-                            self.send(request_id, 200, headers, Cursor::new(resized), encoding)?;
+                            self.send(request_id, 200, headers, Cursor::new(resized), encoding)
+                                .await?;
 
                             Ok(())
                         }
@@ -1608,6 +2121,113 @@ impl TunnelSender {
         }
     }
 
+    #[cfg(feature = "e2e")]
+    async fn e2e_handshake(
+        &self,
+        request_id: usize,
+        payload: Option<Value>,
+        encoding: TunnelEncoding,
+    ) -> Result<(), TunnelRequestError> {
+        let request = serde_json::from_value::<E2eHandshakeRequest>(
+            payload.ok_or(TunnelRequestError::BadRequest("Missing handshake".into()))?,
+        )
+        .map_err(|e| TunnelRequestError::BadRequest(e.to_string()))?;
+
+        let server_identity = moosicbox_config::get_or_init_server_identity(&self.config_db)
+            .await
+            .map_err(wrap_to_500)?;
+
+        let signing_key = crate::e2e::get_or_init_signing_key(&self.config_db)
+            .await
+            .map_err(wrap_to_500)?;
+
+        let handshake = E2eHandshake::new().map_err(wrap_to_500)?;
+        let public_key = handshake.public_key();
+        let (session, signature) = handshake
+            .finish_server(&server_identity, &signing_key, &request.public_key)
+            .map_err(|e| TunnelRequestError::BadRequest(format!("Invalid handshake: {e:?}")))?;
+
+        let session_id = format!("{:016x}", thread_rng().gen::<u64>());
+        let confirmation = session.confirmation(&session_id).map_err(wrap_to_500)?;
+
+        {
+            let mut sessions = self.e2e_sessions.write().unwrap();
+            if sessions.len() >= MAX_E2E_SESSIONS {
+                sessions.pop_front();
+            }
+            sessions.push_back((session_id.clone(), Arc::new(session)));
+        }
+
+        log::debug!("Started E2E session {session_id}");
+
+        let mut headers = HashMap::new();
+        headers.insert("content-type".to_string(), "application/json".to_string());
+
+        let bytes = serde_json::to_vec(&E2eHandshakeResponse {
+            session_id,
+            public_key,
+            signature,
+            confirmation,
+        })?;
+        self.send(request_id, 200, headers, Cursor::new(bytes), encoding)
+            .await
+    }
+
+    /// Opens an E2E encrypted request and handles it like a
+    /// [`tunnel_request`](Self::tunnel_request), sealing every response packet
+    /// for the client.
+    #[cfg(feature = "e2e")]
+    pub async fn encrypted_tunnel_request(
+        &self,
+        service_port: u16,
+        request: TunnelEncryptedRequest,
+    ) -> Result<(), TunnelRequestError> {
+        let session = self
+            .e2e_sessions
+            .read()
+            .unwrap()
+            .iter()
+            .find(|(id, _)| *id == request.session_id)
+            .map(|(_, session)| session.clone())
+            .ok_or_else(|| {
+                TunnelRequestError::BadRequest(format!(
+                    "Unknown E2E session '{}'",
+                    request.session_id
+                ))
+            })?;
+
+        let http_request = session
+            .open_request(&request.session_id, &request.payload)
+            .map_err(|e| TunnelRequestError::BadRequest(format!("Invalid E2E request: {e:?}")))?;
+
+        self.e2e_requests
+            .write()
+            .unwrap()
+            .insert(request.request_id, (session, http_request.id));
+
+        let response = self
+            .tunnel_request(
+                service_port,
+                request.request_id,
+                http_request.method,
+                http_request.path,
+                http_request.query,
+                http_request.payload,
+                false,
+                http_request.headers,
+                http_request.profile,
+                TunnelEncoding::Binary,
+            )
+            .await;
+
+        self.e2e_requests
+            .write()
+            .unwrap()
+            .remove(&request.request_id);
+
+        response
+    }
+
     pub async fn ws_request(
         &self,
         conn_id: usize,
@@ -1641,5 +2261,6 @@ impl TunnelSender {
         if let Some(token) = self.abort_request_tokens.read().unwrap().get(&request_id) {
             token.cancel();
         }
+        self.scheduler.remove(request_id);
     }
 }
@@ -80,6 +80,11 @@ where
         Ok(())
     }
 
+    async fn send_all_profile(&self, profile: &str, data: &str) -> Result<(), WebsocketSendError> {
+        // The root sender forwards profile broadcasts to the tunnel itself
+        self.root_sender.send_all_profile(profile, data).await
+    }
+
     async fn send_all_except(
         &self,
         connection_id: &str,
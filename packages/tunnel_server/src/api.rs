@@ -1,22 +1,25 @@
 use actix_web::error::{
-    ErrorBadRequest, ErrorFailedDependency, ErrorInternalServerError, ErrorUnauthorized,
+    ErrorBadRequest, ErrorFailedDependency, ErrorInternalServerError, ErrorPayloadTooLarge,
+    ErrorUnauthorized,
 };
 use actix_web::http::{header, StatusCode};
 use actix_web::web::{self, Json};
 use actix_web::{route, HttpResponse};
 use actix_web::{HttpRequest, Result};
 use bytes::Bytes;
-use futures_util::StreamExt;
+use futures_util::{Stream, StreamExt};
 use log::{debug, info};
 use moosicbox_database::profiles::api::ProfileNameUnverified;
 use moosicbox_tunnel::{
-    Method, TunnelEncoding, TunnelHttpRequest, TunnelRequest, TunnelResponse, TunnelStream,
+    request_body_packets, Method, TunnelEncoding, TunnelEncryptedRequest, TunnelHttpRequest,
+    TunnelRequest, TunnelResponse, TunnelStream, TunnelStreamError, REQUEST_BODY_PROTOCOL_VERSION,
 };
 use qstring::QString;
 use rand::{thread_rng, Rng as _};
 use serde::Deserialize;
 use serde_json::{json, Value};
 use std::collections::HashMap;
+use std::pin::pin;
 use std::str::FromStr;
 use thiserror::Error;
 use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
@@ -30,8 +33,12 @@ use crate::auth::{
 use crate::db::{
     insert_client_access_token, insert_magic_token, insert_signature_token, select_magic_token,
 };
+use crate::ws::request_body::REQUEST_BODY_WINDOWS;
 use crate::ws::server::service::{Commander, CommanderError};
-use crate::ws::server::{get_connection_id, ConnectionIdError, RequestHeaders};
+use crate::ws::server::{
+    get_connection_id, get_protocol_version, ConnectionIdError, RequestHeaders,
+};
+use crate::ws::ConnId;
 use crate::WS_SERVER_HANDLE;
 
 #[route("/health", method = "GET")]
@@ -40,6 +47,12 @@ pub async fn health_endpoint() -> Result<Json<Value>> {
     Ok(Json(json!({"healthy": true})))
 }
 
+#[cfg(feature = "compression")]
+#[route("/stats/compression", method = "GET")]
+pub async fn compression_stats_endpoint(_: GeneralHeaderAuthorized) -> Result<Json<Value>> {
+    Ok(Json(serde_json::to_value(crate::compression::stats())?))
+}
+
 #[derive(Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct AuthMagicTokenRequest {
@@ -62,6 +75,7 @@ pub async fn auth_get_magic_token_endpoint(
             json!({"magicToken": token}),
             None,
             None,
+            None,
             profile.map(|x| x.0),
         )
         .await
@@ -174,12 +188,62 @@ pub async fn album_cover_endpoint(
     method = "HEAD"
 )]
 pub async fn tunnel_endpoint(
-    body: Option<Bytes>,
+    body: web::Payload,
     req: HttpRequest,
     profile: Option<ProfileNameUnverified>,
     _: ClientHeaderAuthorized,
 ) -> Result<HttpResponse> {
-    proxy_request(body, req, profile.map(|x| x.0)).await
+    proxy_streaming_request(body, req, profile.map(|x| x.0)).await
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct E2eRequest {
+    client_id: String,
+    session_id: String,
+}
+
+/// Relays an end-to-end encrypted request to the client's MoosicBox server.
+/// The body is the base64 encoded sealed request, and the response streams
+/// each sealed response frame prefixed by its length as a big-endian `u32`.
+#[route("/tunnel/e2e", method = "POST")]
+pub async fn e2e_endpoint(
+    body: String,
+    query: web::Query<E2eRequest>,
+    _: ClientHeaderAuthorized,
+) -> Result<HttpResponse> {
+    let request_id = thread_rng().gen::<usize>();
+    let abort_token = CancellationToken::new();
+
+    debug!(
+        "Starting e2e request {request_id} session_id={} (size {})",
+        query.session_id,
+        body.len()
+    );
+
+    let (_headers_rx, rx) = request(
+        &query.client_id,
+        request_id,
+        TunnelRequest::Encrypted(TunnelEncryptedRequest {
+            request_id,
+            session_id: query.session_id.clone(),
+            payload: body,
+        }),
+        abort_token.clone(),
+    )?;
+
+    let tunnel_stream = response_stream(request_id, rx, abort_token)
+        .await
+        .map(|frame| {
+            frame.map(|frame| {
+                let len = u32::try_from(frame.len()).unwrap_or(u32::MAX);
+                Bytes::from([len.to_be_bytes().as_slice(), &frame].concat())
+            })
+        });
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/octet-stream")
+        .streaming(tunnel_stream))
 }
 
 #[allow(dead_code)]
@@ -188,7 +252,7 @@ enum ResponseType {
     Body,
 }
 
-fn get_headers_for_request(req: &HttpRequest) -> Option<Value> {
+fn get_headers_for_request(req: &HttpRequest, streaming_body: bool) -> Option<Value> {
     let mut headers = HashMap::<String, String>::new();
 
     for (key, value) in req.headers().iter() {
@@ -196,10 +260,19 @@ fn get_headers_for_request(req: &HttpRequest) -> Option<Value> {
             header::ACCEPT | header::RANGE => {
                 headers.insert(key.to_string(), value.to_str().unwrap().to_string());
             }
+            header::CONTENT_TYPE if streaming_body => {
+                headers.insert(key.to_string(), value.to_str().unwrap().to_string());
+            }
             _ => {}
         }
     }
 
+    #[cfg(feature = "compression")]
+    headers.insert(
+        moosicbox_tunnel::TUNNEL_COMPRESSION_HEADER.to_string(),
+        moosicbox_tunnel::compression::supported_compressions_header(),
+    );
+
     if headers.is_empty() {
         None
     } else {
@@ -207,11 +280,14 @@ fn get_headers_for_request(req: &HttpRequest) -> Option<Value> {
     }
 }
 
-async fn proxy_request(
-    body: Option<Bytes>,
-    req: HttpRequest,
-    profile: Option<String>,
-) -> Result<HttpResponse> {
+struct ProxyRequest {
+    client_id: String,
+    method: Method,
+    path: String,
+    query: Value,
+}
+
+fn parse_proxy_request(req: &HttpRequest) -> Result<ProxyRequest> {
     let method = Method::from_str(&req.method().to_string().to_uppercase()).map_err(|e| {
         ErrorBadRequest(format!(
             "Failed to parse method: '{:?}': {e:?}",
@@ -227,42 +303,155 @@ async fn proxy_request(
         .ok_or(ErrorBadRequest("Missing clientId query param"))?;
     let query = serde_json::to_value(query).unwrap();
 
+    Ok(ProxyRequest {
+        client_id,
+        method,
+        path: path.to_string(),
+        query,
+    })
+}
+
+async fn proxy_request(
+    body: Option<Bytes>,
+    req: HttpRequest,
+    profile: Option<String>,
+) -> Result<HttpResponse> {
+    let request = parse_proxy_request(&req)?;
+
     let body = body
         .filter(|bytes| !bytes.is_empty())
         .map(|bytes| serde_json::from_slice(&bytes))
         .transpose()?;
 
-    let headers = get_headers_for_request(&req);
+    let headers = get_headers_for_request(&req, false);
+
+    handle_request(
+        &request.client_id,
+        &request.method,
+        &request.path,
+        request.query,
+        body,
+        None,
+        headers,
+        profile,
+    )
+    .await
+}
+
+/// The largest request body sent as a JSON `payload` to senders that don't
+/// support request body packets, the default limit of a [`Bytes`] extractor.
+const MAX_PAYLOAD_REQUEST_BODY_SIZE: usize = 256 * 1024;
+
+/// Proxies the request with its body streamed to the sender in
+/// [`TunnelRequestBody`] packets. Senders that don't support request body
+/// packets get the body as a JSON `payload` like [`proxy_request`].
+///
+/// [`TunnelRequestBody`]: moosicbox_tunnel::TunnelRequestBody
+async fn proxy_streaming_request(
+    body: web::Payload,
+    req: HttpRequest,
+    profile: Option<String>,
+) -> Result<HttpResponse> {
+    let request = parse_proxy_request(&req)?;
+
+    if !has_body(&req) || !supports_request_body(&request.client_id).await {
+        let body = body
+            .to_bytes_limited(MAX_PAYLOAD_REQUEST_BODY_SIZE)
+            .await
+            .map_err(|_| ErrorPayloadTooLarge("Request body is too large"))??;
+        return proxy_request(Some(body), req, profile).await;
+    }
+
+    let headers = get_headers_for_request(&req, true);
 
-    handle_request(&client_id, &method, path, query, body, headers, profile).await
+    handle_request(
+        &request.client_id,
+        &request.method,
+        &request.path,
+        request.query,
+        None,
+        Some(body),
+        headers,
+        profile,
+    )
+    .await
+}
+
+fn has_body(req: &HttpRequest) -> bool {
+    req.headers().contains_key(header::TRANSFER_ENCODING)
+        || req
+            .headers()
+            .get(header::CONTENT_LENGTH)
+            .and_then(|x| x.to_str().ok())
+            .and_then(|x| x.parse::<u64>().ok())
+            .is_some_and(|x| x > 0)
 }
 
+async fn supports_request_body(client_id: &str) -> bool {
+    get_connection_id(client_id)
+        .await
+        .is_ok_and(|conn_id| get_protocol_version(conn_id) >= REQUEST_BODY_PROTOCOL_VERSION)
+}
+
+#[allow(clippy::too_many_arguments)]
 async fn handle_request(
     client_id: &str,
     method: &Method,
     path: &str,
     query: Value,
     payload: Option<Value>,
+    body: Option<web::Payload>,
     headers: Option<Value>,
     profile: Option<String>,
 ) -> Result<HttpResponse> {
     let request_id = thread_rng().gen::<usize>();
     let abort_token = CancellationToken::new();
+    let streaming_body = body.is_some();
+
+    // The window is opened before the request is sent so that no credits
+    // granted by the sender are missed
+    let body = match body {
+        Some(body) => {
+            let conn_id = get_connection_id(client_id).await.map_err(|err| {
+                log::error!("Failed to get connection id for request_id={request_id} client_id={client_id}: {err:?}");
+                ErrorFailedDependency("Client with ID is not connected")
+            })?;
+            REQUEST_BODY_WINDOWS.open(conn_id, request_id);
+            Some((conn_id, body))
+        }
+        None => None,
+    };
 
     debug!("Starting ws request for {request_id} method={method} path={path} query={query:?} headers={headers:?} profile={profile:?} (id {request_id})");
 
     let (headers_rx, rx) = request(
         client_id,
         request_id,
-        method,
-        path,
-        query,
-        payload,
-        headers,
-        profile,
+        TunnelRequest::Http(TunnelHttpRequest {
+            request_id,
+            method: method.clone(),
+            path: path.to_string(),
+            query,
+            payload,
+            headers,
+            encoding: TunnelEncoding::Binary,
+            profile,
+            streaming_body,
+        }),
         abort_token.clone(),
     )?;
 
+    if let Some((conn_id, body)) = body {
+        let abort_token = abort_token.clone();
+        moosicbox_task::spawn_local("tunnel_server_request_body", async move {
+            let response = send_request_body(conn_id, request_id, body, abort_token).await;
+            REQUEST_BODY_WINDOWS.remove(request_id);
+            if let Err(err) = response {
+                log::error!("Failed to send request body for request_id={request_id}: {err:?}");
+            }
+        });
+    }
+
     let mut builder = HttpResponse::Ok();
 
     let headers = match headers_rx.await {
@@ -284,21 +473,33 @@ async fn handle_request(
         ))
     })?);
 
-    for (key, value) in &headers.headers {
+    #[allow(unused_mut)]
+    let mut headers = headers.headers;
+
+    #[cfg(feature = "compression")]
+    let compression = headers
+        .remove(moosicbox_tunnel::TUNNEL_COMPRESSION_HEADER)
+        .and_then(|x| x.parse::<moosicbox_tunnel::TunnelCompression>().ok());
+
+    for (key, value) in &headers {
         builder.insert_header((key.clone(), value.clone()));
     }
 
-    let tunnel_stream = TunnelStream::new(request_id, rx, abort_token, &|request_id| async move {
-        debug!("Request {request_id} ended");
-        WS_SERVER_HANDLE
-            .read()
-            .await
-            .as_ref()
-            .unwrap()
-            .send_command_async(crate::ws::server::Command::RequestEnd { request_id })
-            .await?;
-        Ok(())
-    });
+    let tunnel_stream = response_stream(request_id, rx, abort_token).await;
+
+    #[cfg(feature = "compression")]
+    let tunnel_stream = {
+        if let Some(compression) = compression {
+            crate::compression::record_response(compression);
+        }
+        tunnel_stream.map(move |packet| match (packet, compression) {
+            (Ok(bytes), Some(compression)) => {
+                crate::compression::decompress_packet(compression, &bytes)
+                    .map_err(TunnelStreamError::Decompress)
+            }
+            (packet, _) => packet,
+        })
+    };
 
     match response_type {
         ResponseType::Stream => Ok(builder.streaming(tunnel_stream)),
@@ -316,6 +517,37 @@ async fn handle_request(
     }
 }
 
+/// Streams the response packets of the request, telling the ws server as the
+/// client consumes each one so that it can grant the sender more credits.
+async fn response_stream(
+    request_id: usize,
+    rx: UnboundedReceiver<TunnelResponse>,
+    abort_token: CancellationToken,
+) -> impl Stream<Item = Result<Bytes, TunnelStreamError>> {
+    let ws_server = WS_SERVER_HANDLE.read().await.as_ref().unwrap().clone();
+
+    TunnelStream::new(request_id, rx, abort_token, &|request_id| async move {
+        debug!("Request {request_id} ended");
+        WS_SERVER_HANDLE
+            .read()
+            .await
+            .as_ref()
+            .unwrap()
+            .send_command_async(crate::ws::server::Command::RequestEnd { request_id })
+            .await?;
+        Ok(())
+    })
+    .inspect(move |packet| {
+        if packet.is_ok() {
+            if let Err(err) =
+                ws_server.send_command(crate::ws::server::Command::RequestConsumed { request_id })
+            {
+                log::error!("Failed to send RequestConsumed for request_id={request_id}: {err:?}");
+            }
+        }
+    })
+}
+
 #[derive(Error, Debug)]
 pub enum RequestError {
     #[error(transparent)]
@@ -324,16 +556,63 @@ pub enum RequestError {
     Commander(#[from] CommanderError),
 }
 
-#[allow(clippy::too_many_arguments)]
+/// Sends the request body to the sender in [`TunnelRequestBody`] packets as it
+/// is received from the client, ending with an empty last packet that is
+/// marked as failed if the client's upload failed. Each packet waits for a
+/// credit from the sender, so the client's upload is only read as fast as the
+/// sender consumes it.
+///
+/// [`TunnelRequestBody`]: moosicbox_tunnel::TunnelRequestBody
+async fn send_request_body(
+    conn_id: ConnId,
+    request_id: usize,
+    body: web::Payload,
+    abort_token: CancellationToken,
+) -> Result<(), RequestError> {
+    let ws_server = WS_SERVER_HANDLE.read().await.as_ref().unwrap().clone();
+    let mut packets = pin!(request_body_packets(request_id, body));
+
+    loop {
+        let packet = tokio::select! {
+            packet = packets.next() => packet,
+            () = abort_token.cancelled() => {
+                debug!("Request {request_id} aborted while sending body");
+                return Ok(());
+            }
+        };
+
+        let Some(packet) = packet else {
+            break;
+        };
+
+        if !REQUEST_BODY_WINDOWS.acquire(request_id, &abort_token).await {
+            debug!("Request {request_id} ended while sending body");
+            return Ok(());
+        }
+
+        log::trace!(
+            "Sending request body packet request_id={request_id} packet_id={} last={} failed={} (size {})",
+            packet.packet_id,
+            packet.last,
+            packet.failed,
+            packet.bytes.len()
+        );
+
+        ws_server
+            .send_command_async(crate::ws::server::Command::Message {
+                msg: Bytes::from(packet).into(),
+                conn: conn_id,
+            })
+            .await?;
+    }
+
+    Ok(())
+}
+
 fn request(
     client_id: &str,
     request_id: usize,
-    method: &Method,
-    path: &str,
-    query: Value,
-    payload: Option<Value>,
-    headers: Option<Value>,
-    profile: Option<String>,
+    body: TunnelRequest,
     abort_token: CancellationToken,
 ) -> Result<(
     oneshot::Receiver<RequestHeaders>,
@@ -343,8 +622,6 @@ fn request(
     let (tx, rx) = unbounded_channel();
 
     let client_id = client_id.to_string();
-    let method = method.clone();
-    let path = path.to_string();
     let abort_token = abort_token.clone();
 
     moosicbox_task::spawn("tunnel_server_request", async move {
@@ -373,18 +650,7 @@ fn request(
         debug!("Sending server request {request_id} to {conn_id}");
         ws_server
             .send_command_async(crate::ws::server::Command::Message {
-                msg: serde_json::to_value(TunnelRequest::Http(TunnelHttpRequest {
-                    request_id,
-                    method: method.clone(),
-                    path: path.to_string(),
-                    query,
-                    payload,
-                    headers,
-                    encoding: TunnelEncoding::Binary,
-                    profile,
-                }))
-                .unwrap()
-                .to_string(),
+                msg: serde_json::to_value(body).unwrap().to_string().into(),
                 conn: conn_id,
             })
             .await?;
@@ -0,0 +1,92 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use bytes::Bytes;
+use moosicbox_tunnel::{compression::decompress, TunnelCompression};
+use serde::Serialize;
+
+static COMPRESSED_RESPONSES: AtomicU64 = AtomicU64::new(0);
+static COMPRESSED_WS_MESSAGES: AtomicU64 = AtomicU64::new(0);
+static COMPRESSED_BYTES: AtomicU64 = AtomicU64::new(0);
+static DECOMPRESSED_BYTES: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompressionStats {
+    pub compressed_responses: u64,
+    pub compressed_ws_messages: u64,
+    /// The number of compressed bytes received from senders
+    pub compressed_bytes: u64,
+    /// The number of bytes the compressed bytes decompressed to
+    pub decompressed_bytes: u64,
+    /// The number of bytes compression saved on sender connections
+    pub saved_bytes: u64,
+}
+
+#[must_use]
+pub fn stats() -> CompressionStats {
+    let compressed_bytes = COMPRESSED_BYTES.load(Ordering::Relaxed);
+    let decompressed_bytes = DECOMPRESSED_BYTES.load(Ordering::Relaxed);
+
+    CompressionStats {
+        compressed_responses: COMPRESSED_RESPONSES.load(Ordering::Relaxed),
+        compressed_ws_messages: COMPRESSED_WS_MESSAGES.load(Ordering::Relaxed),
+        compressed_bytes,
+        decompressed_bytes,
+        saved_bytes: decompressed_bytes.saturating_sub(compressed_bytes),
+    }
+}
+
+pub fn record_response(compression: TunnelCompression) {
+    log::debug!("Received response compressed with {compression:?}");
+    COMPRESSED_RESPONSES.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Decompresses a packet of a compressed response.
+///
+/// # Errors
+///
+/// * If the packet is not valid for the compression
+pub fn decompress_packet(compression: TunnelCompression, bytes: &[u8]) -> std::io::Result<Bytes> {
+    let decompressed = decompress(compression, bytes)?;
+    record_bytes(bytes.len(), decompressed.len());
+    Ok(decompressed.into())
+}
+
+/// Decompresses a compressed websocket message from a sender. The message is
+/// a request id of `0`, the [`TunnelCompression`] byte, and then the
+/// compressed JSON message.
+///
+/// Returns `None` if the bytes aren't a compressed websocket message.
+///
+/// # Errors
+///
+/// * If the message is not valid for the compression
+pub fn decompress_ws_message(bytes: &[u8]) -> Option<std::io::Result<String>> {
+    if bytes.len() < 9 || bytes[..8] != 0_usize.to_be_bytes() {
+        return None;
+    }
+
+    let compression = match TunnelCompression::try_from(bytes[8]) {
+        Ok(compression) => compression,
+        Err(value) => {
+            return Some(Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Invalid compression {value}"),
+            )))
+        }
+    };
+
+    Some(
+        decompress(compression, &bytes[9..]).and_then(|decompressed| {
+            COMPRESSED_WS_MESSAGES.fetch_add(1, Ordering::Relaxed);
+            record_bytes(bytes.len() - 9, decompressed.len());
+            String::from_utf8(decompressed)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+        }),
+    )
+}
+
+fn record_bytes(compressed: usize, decompressed: usize) {
+    COMPRESSED_BYTES.fetch_add(compressed as u64, Ordering::Relaxed);
+    DECOMPRESSED_BYTES.fetch_add(decompressed as u64, Ordering::Relaxed);
+}
@@ -2,6 +2,8 @@
 
 mod api;
 mod auth;
+#[cfg(feature = "compression")]
+mod compression;
 mod db;
 mod ws;
 
@@ -93,7 +95,7 @@ fn main() -> Result<(), std::io::Error> {
                 .supports_credentials()
                 .max_age(3600);
 
-            App::new()
+            let app = App::new()
                 .wrap(cors)
                 .wrap(moosicbox_middleware::api_logger::ApiLogger::default())
                 .wrap(middleware::Compress::default())
@@ -106,8 +108,12 @@ fn main() -> Result<(), std::io::Error> {
                 .service(api::auth_validate_signature_token_endpoint)
                 .service(api::track_endpoint)
                 .service(api::artist_cover_endpoint)
-                .service(api::album_cover_endpoint)
-                .service(api::tunnel_endpoint)
+                .service(api::album_cover_endpoint);
+
+            #[cfg(feature = "compression")]
+            let app = app.service(api::compression_stats_endpoint);
+
+            app.service(api::e2e_endpoint).service(api::tunnel_endpoint)
         };
 
         let mut http_server = actix_web::HttpServer::new(app);
@@ -1,9 +1,37 @@
+use bytes::Bytes;
+
 pub mod api;
 pub mod handler;
+pub mod request_body;
 pub mod server;
 
 /// Connection ID.
 pub type ConnId = usize;
 
 /// Message sent to a room/client.
-pub type Msg = String;
+#[derive(Debug, Clone)]
+pub enum Msg {
+    Text(String),
+    Binary(Bytes),
+}
+
+impl Msg {
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Text(text) => text.len(),
+            Self::Binary(bytes) => bytes.len(),
+        }
+    }
+}
+
+impl From<String> for Msg {
+    fn from(value: String) -> Self {
+        Self::Text(value)
+    }
+}
+
+impl From<Bytes> for Msg {
+    fn from(value: Bytes) -> Self {
+        Self::Binary(value)
+    }
+}
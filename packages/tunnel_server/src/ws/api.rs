@@ -15,6 +15,9 @@ use serde::Deserialize;
 pub struct ConnectRequest {
     client_id: String,
     sender: Option<bool>,
+    /// The tunnel protocol version of the sender. Senders that predate the
+    /// handshake don't send one and speak version 1
+    protocol_version: Option<u32>,
 }
 
 #[get("/ws")]
@@ -37,6 +40,7 @@ pub async fn websocket(
             msg_stream,
             query.client_id.clone(),
             query.sender.unwrap_or(false),
+            query.protocol_version.unwrap_or(1),
             profile.map(|x| x.0),
         ),
     );
@@ -5,10 +5,12 @@ use futures_util::{
     future::{select, Either},
     StreamExt as _,
 };
-use moosicbox_tunnel::TunnelWsResponse;
+use moosicbox_tunnel::{TunnelSenderMessage, TunnelWsResponse};
 use tokio::{pin, sync::mpsc, time::interval};
 
+use super::request_body::REQUEST_BODY_WINDOWS;
 use super::server::service::CommanderError;
+use super::{ConnId, Msg};
 
 /// How often heartbeat pings are sent
 const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
@@ -24,6 +26,7 @@ pub async fn handle_ws(
     mut msg_stream: actix_ws::MessageStream,
     client_id: String,
     sender: bool,
+    protocol_version: u32,
     profile: Option<String>,
 ) -> Result<(), CommanderError> {
     log::info!("Connected");
@@ -34,7 +37,15 @@ pub async fn handle_ws(
     let (conn_tx, mut conn_rx) = mpsc::unbounded_channel();
 
     // unwrap: ws server is not dropped before the HTTP server
-    let conn_id = ws_server.connect(&client_id, sender, conn_tx).await?;
+    let conn_id = ws_server
+        .connect(
+            &client_id,
+            sender,
+            protocol_version,
+            profile.clone(),
+            conn_tx,
+        )
+        .await?;
 
     log::info!("Connection id: {conn_id}");
 
@@ -79,25 +90,7 @@ pub async fn handle_ws(
 
                     if !finished {
                         if sender {
-                            if let Ok(response) = serde_json::from_str::<TunnelWsResponse>(text) {
-                                if response.request_id == 0 {
-                                    log::debug!("Propagating ws message {text}");
-                                    if let Err(err) = ws_server.ws_message(response).await {
-                                        log::error!(
-                                            "Failed to propagate ws message from tunnel_server: {err:?}"
-                                        );
-                                    }
-                                } else {
-                                    log::debug!("Propagating ws response");
-                                    if let Err(err) = ws_server.ws_response(response).await {
-                                        log::error!(
-                                            "Failed to propagate ws response from tunnel_server: {err:?}"
-                                        );
-                                    }
-                                }
-                            } else {
-                                log::error!("Invalid TunnelWsResponse: {text}");
-                            }
+                            propagate_sender_message(&ws_server, conn_id, text).await;
                         } else if let Err(err) = ws_server
                             .ws_request(conn_id, &client_id, profile.clone(), text)
                             .await
@@ -112,6 +105,21 @@ pub async fn handle_ws(
                 Message::Binary(bytes) => {
                     last_heartbeat = Instant::now();
 
+                    #[cfg(feature = "compression")]
+                    if sender {
+                        match crate::compression::decompress_ws_message(&bytes) {
+                            Some(Ok(text)) => {
+                                propagate_sender_message(&ws_server, conn_id, &text).await;
+                                continue;
+                            }
+                            Some(Err(err)) => {
+                                log::error!("Failed to decompress ws message: {err:?}");
+                                continue;
+                            }
+                            None => {}
+                        }
+                    }
+
                     ws_server.response(conn_id, bytes.into()).await;
                 }
 
@@ -135,11 +143,18 @@ pub async fn handle_ws(
             }
 
             // ws messages received from other room participants
-            Either::Left((Either::Right((Some(ws_msg), _)), _)) => {
-                if let Err(err) = session.text(ws_msg).await {
-                    log::error!("Failed to send text message to conn_id='{conn_id}' client_id='{client_id}': {err:?}");
+            Either::Left((Either::Right((Some(ws_msg), _)), _)) => match ws_msg {
+                Msg::Text(text) => {
+                    if let Err(err) = session.text(text).await {
+                        log::error!("Failed to send text message to conn_id='{conn_id}' client_id='{client_id}': {err:?}");
+                    }
                 }
-            }
+                Msg::Binary(bytes) => {
+                    if let Err(err) = session.binary(bytes).await {
+                        log::error!("Failed to send binary message to conn_id='{conn_id}' client_id='{client_id}': {err:?}");
+                    }
+                }
+            },
 
             // all connection's message senders were dropped
             Either::Left((Either::Right((None, _)), _)) => unreachable!(
@@ -171,3 +186,33 @@ pub async fn handle_ws(
 
     Ok(())
 }
+
+/// Propagates a [`TunnelWsResponse`] from a sender to the clients it's for,
+/// or handles a [`TunnelSenderMessage`] meant for the tunnel server.
+async fn propagate_sender_message(
+    ws_server: &super::server::service::Handle,
+    conn_id: ConnId,
+    text: &str,
+) {
+    if let Ok(message) = serde_json::from_str::<TunnelSenderMessage>(text) {
+        match message {
+            TunnelSenderMessage::RequestBodyWindowUpdate(update) => {
+                REQUEST_BODY_WINDOWS.grant(conn_id, update.request_id, update.credits);
+            }
+        }
+    } else if let Ok(response) = serde_json::from_str::<TunnelWsResponse>(text) {
+        if response.request_id == 0 {
+            log::debug!("Propagating ws message {text}");
+            if let Err(err) = ws_server.ws_message(response).await {
+                log::error!("Failed to propagate ws message from tunnel_server: {err:?}");
+            }
+        } else {
+            log::debug!("Propagating ws response");
+            if let Err(err) = ws_server.ws_response(response).await {
+                log::error!("Failed to propagate ws response from tunnel_server: {err:?}");
+            }
+        }
+    } else {
+        log::error!("Invalid TunnelWsResponse: {text}");
+    }
+}
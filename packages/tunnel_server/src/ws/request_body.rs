@@ -0,0 +1,108 @@
+//! Flow control of request bodies streamed to senders in
+//! [`TunnelRequestBody`](moosicbox_tunnel::TunnelRequestBody) packets.
+//!
+//! A request body starts without credits. The sender grants them with a
+//! [`TunnelSenderMessage::RequestBodyWindowUpdate`] once it is ready for the
+//! body, and again as the request consumes it, so a slow sender holds back
+//! the client's upload instead of the tunnel server buffering it.
+//!
+//! [`TunnelSenderMessage::RequestBodyWindowUpdate`]: moosicbox_tunnel::TunnelSenderMessage::RequestBodyWindowUpdate
+
+use std::{
+    collections::HashMap,
+    sync::{LazyLock, Mutex},
+};
+
+use tokio::sync::Notify;
+use tokio_util::sync::CancellationToken;
+
+use super::ConnId;
+
+pub static REQUEST_BODY_WINDOWS: LazyLock<RequestBodyWindows> =
+    LazyLock::new(RequestBodyWindows::default);
+
+struct RequestBodyWindow {
+    conn_id: ConnId,
+    credits: u32,
+}
+
+#[derive(Default)]
+pub struct RequestBodyWindows {
+    windows: Mutex<HashMap<usize, RequestBodyWindow>>,
+    notify: Notify,
+}
+
+impl RequestBodyWindows {
+    /// Starts the body of a request sent to the sender connection, without
+    /// credits.
+    pub fn open(&self, conn_id: ConnId, request_id: usize) {
+        self.windows.lock().unwrap().insert(
+            request_id,
+            RequestBodyWindow {
+                conn_id,
+                credits: 0,
+            },
+        );
+    }
+
+    /// Grants the request body `credits` more packets. Credits from a
+    /// connection other than the one the request was sent to are ignored.
+    pub fn grant(&self, conn_id: ConnId, request_id: usize, credits: u32) {
+        let mut windows = self.windows.lock().unwrap();
+
+        match windows.get_mut(&request_id) {
+            Some(window) if window.conn_id == conn_id => {
+                window.credits = window.credits.saturating_add(credits);
+                drop(windows);
+                self.notify.notify_waiters();
+            }
+            _ => {
+                log::debug!(
+                    "Ignoring request body credits for unknown request_id={request_id} from conn_id={conn_id}"
+                );
+            }
+        }
+    }
+
+    /// Waits for a credit to send one packet of the request body. Returns
+    /// `false` if the request was aborted or removed while waiting.
+    pub async fn acquire(&self, request_id: usize, abort_token: &CancellationToken) -> bool {
+        loop {
+            let notified = self.notify.notified();
+
+            {
+                let mut windows = self.windows.lock().unwrap();
+                let Some(window) = windows.get_mut(&request_id) else {
+                    return false;
+                };
+                if window.credits > 0 {
+                    window.credits -= 1;
+                    return true;
+                }
+            }
+
+            log::trace!("Waiting for request body credits for request_id={request_id}");
+
+            tokio::select! {
+                () = notified => {}
+                () = abort_token.cancelled() => return false,
+            }
+        }
+    }
+
+    pub fn remove(&self, request_id: usize) {
+        if self.windows.lock().unwrap().remove(&request_id).is_some() {
+            self.notify.notify_waiters();
+        }
+    }
+
+    /// Removes the request bodies sent to a sender connection that
+    /// disconnected.
+    pub fn remove_connection(&self, conn_id: ConnId) {
+        self.windows
+            .lock()
+            .unwrap()
+            .retain(|_, window| window.conn_id != conn_id);
+        self.notify.notify_waiters();
+    }
+}
@@ -1,5 +1,5 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fmt,
     sync::{
         atomic::{AtomicUsize, Ordering},
@@ -7,8 +7,10 @@ use std::{
     },
 };
 
+use moosicbox_env_utils::default_env_u32;
 use moosicbox_tunnel::{
-    TunnelAbortRequest, TunnelRequest, TunnelResponse, TunnelWsRequest, TunnelWsResponse,
+    TunnelAbortRequest, TunnelHandshake, TunnelRequest, TunnelResponse, TunnelWindowUpdate,
+    TunnelWsRequest, TunnelWsResponse, FLOW_CONTROL_PROTOCOL_VERSION, TUNNEL_PROTOCOL_VERSION,
 };
 use moosicbox_tunnel_server::CANCELLATION_TOKEN;
 use rand::{thread_rng, Rng as _};
@@ -23,10 +25,14 @@ use tokio::sync::{
 use tokio_util::sync::CancellationToken;
 
 use crate::db::{delete_connection, select_connection, upsert_connection, DatabaseError};
-use crate::ws::{ConnId, Msg};
+use crate::ws::{request_body::REQUEST_BODY_WINDOWS, ConnId, Msg};
 
 use self::service::{Commander, CommanderError};
 
+/// The number of response packets a sender can send for each request before
+/// the client has consumed them.
+static REQUEST_WINDOW: u32 = default_env_u32!("TUNNEL_REQUEST_WINDOW", 16);
+
 /// A command received by the [`WsServer`].
 #[derive(Debug, AsRefStr)]
 pub enum Command {
@@ -35,6 +41,8 @@ pub enum Command {
         res_tx: oneshot::Sender<ConnId>,
         client_id: String,
         sender: bool,
+        protocol_version: u32,
+        profile: Option<String>,
     },
 
     Disconnect {
@@ -52,6 +60,11 @@ pub enum Command {
         request_id: usize,
     },
 
+    /// The client consumed a response packet of the request
+    RequestConsumed {
+        request_id: usize,
+    },
+
     Response {
         response: TunnelResponse,
         conn_id: ConnId,
@@ -109,9 +122,13 @@ impl service::Processor for service::Service {
                 conn_tx,
                 res_tx,
                 sender,
+                protocol_version,
+                profile,
             } => {
                 let mut binding = ctx.write().await;
-                let response = binding.connect(client_id, sender, conn_tx).await;
+                let response = binding
+                    .connect(client_id, sender, protocol_version, profile, conn_tx)
+                    .await;
                 drop(binding);
                 match response {
                     Ok(id) => {
@@ -152,7 +169,43 @@ impl service::Processor for service::Service {
                 ctx.senders.remove(&request_id);
                 ctx.headers_senders.remove(&request_id);
                 ctx.abort_request_tokens.remove(&request_id);
+                ctx.request_windows.remove(&request_id);
                 drop(ctx);
+                REQUEST_BODY_WINDOWS.remove(request_id);
+            }
+
+            Command::RequestConsumed { request_id } => {
+                let mut binding = ctx.write().await;
+                let update = binding
+                    .request_windows
+                    .get_mut(&request_id)
+                    .and_then(|window| {
+                        window.consumed += 1;
+                        (window.consumed >= (REQUEST_WINDOW / 2).max(1)).then(|| {
+                            let credits = window.consumed;
+                            window.consumed = 0;
+                            (window.conn_id, credits)
+                        })
+                    });
+                drop(binding);
+
+                if let Some((conn_id, credits)) = update {
+                    log::trace!(
+                        "process_command: Granting request_id={request_id} {credits} credits"
+                    );
+                    let body = TunnelRequest::WindowUpdate(TunnelWindowUpdate {
+                        request_id,
+                        credits,
+                    });
+                    let binding = ctx.read().await;
+                    let response = binding
+                        .send_message_to(conn_id, serde_json::to_string(&body).unwrap())
+                        .await;
+                    drop(binding);
+                    if let Err(error) = response {
+                        log::error!("Failed to send window update to {conn_id}: {error:?}");
+                    }
+                }
             }
 
             Command::Response { response, conn_id } => {
@@ -199,6 +252,22 @@ impl service::Processor for service::Service {
 
                 let sender = ctx.read().await.senders.get(&request_id).cloned();
 
+                if sender.is_some() {
+                    let mut binding = ctx.write().await;
+                    if response.last {
+                        binding.request_windows.remove(&request_id);
+                    } else if binding.flow_controlled_connections.contains(&conn_id) {
+                        binding
+                            .request_windows
+                            .entry(request_id)
+                            .or_insert(RequestWindow {
+                                conn_id,
+                                consumed: 0,
+                            });
+                    }
+                    drop(binding);
+                }
+
                 if let Some(sender) = sender {
                     let packet_id = response.packet_id;
                     let last = response.last;
@@ -275,7 +344,11 @@ impl service::Processor for service::Service {
                 } else if let Some(exclude_connection_ids) = message.exclude_connection_ids {
                     let binding = ctx.read().await;
                     let response = binding
-                        .broadcast_except(&exclude_connection_ids, message.body.to_string())
+                        .broadcast_except(
+                            &exclude_connection_ids,
+                            message.profile.as_deref(),
+                            message.body.to_string(),
+                        )
                         .await;
                     drop(binding);
                     if let Err(error) = response {
@@ -283,7 +356,9 @@ impl service::Processor for service::Service {
                     }
                 } else {
                     let binding = ctx.read().await;
-                    let response = binding.broadcast(message.body.to_string()).await;
+                    let response = binding
+                        .broadcast(message.profile.as_deref(), message.body.to_string())
+                        .await;
                     drop(binding);
                     if let Err(error) = response {
                         log::error!("Failed to broadcast WsMessage: {error:?}");
@@ -311,7 +386,7 @@ impl service::Processor for service::Service {
 
             Command::Message { conn, msg } => {
                 let binding = ctx.read().await;
-                let response = binding.send_message_to(conn, &msg).await;
+                let response = binding.send_message_to(conn, msg.clone()).await;
                 drop(binding);
                 if let Err(error) = response {
                     log::error!("Failed to send message to {conn}: {msg:?}: {error:?}");
@@ -322,6 +397,14 @@ impl service::Processor for service::Service {
     }
 }
 
+/// The response packets of a flow controlled request that the client
+/// consumed since the sender was last granted credits.
+#[derive(Debug)]
+struct RequestWindow {
+    conn_id: ConnId,
+    consumed: u32,
+}
+
 #[derive(Debug)]
 pub struct RequestHeaders {
     pub status: u16,
@@ -338,9 +421,14 @@ pub struct WsServer {
     /// Map of connection IDs to their message receivers.
     sessions: HashMap<ConnId, mpsc::UnboundedSender<Msg>>,
     clients: HashMap<ConnId, mpsc::UnboundedSender<Msg>>,
+    /// The library profile each client connection was opened with
+    client_profiles: HashMap<ConnId, String>,
     senders: HashMap<usize, UnboundedSender<TunnelResponse>>,
     headers_senders: HashMap<usize, oneshot::Sender<RequestHeaders>>,
     abort_request_tokens: HashMap<usize, CancellationToken>,
+    /// Sender connections that speak a protocol version with flow control
+    flow_controlled_connections: HashSet<ConnId>,
+    request_windows: HashMap<usize, RequestWindow>,
 
     /// Tracks total number of historical connections established.
     visitor_count: Arc<AtomicUsize>,
@@ -366,7 +454,7 @@ pub enum WebsocketMessageError {
     #[error("Session {0} not connected")]
     NoSession(ConnId),
     #[error(transparent)]
-    WebsocketSend(#[from] SendError<String>),
+    WebsocketSend(#[from] SendError<Msg>),
 }
 
 impl WsServer {
@@ -374,9 +462,12 @@ impl WsServer {
         Self {
             sessions: HashMap::new(),
             clients: HashMap::new(),
+            client_profiles: HashMap::new(),
             senders: HashMap::new(),
             headers_senders: HashMap::new(),
             abort_request_tokens: HashMap::new(),
+            flow_controlled_connections: HashSet::new(),
+            request_windows: HashMap::new(),
             visitor_count: Arc::new(AtomicUsize::new(0)),
             ws_requests: HashMap::new(),
         }
@@ -402,7 +493,7 @@ impl WsServer {
     async fn send_message_to(
         &self,
         id: ConnId,
-        msg: impl Into<String>,
+        msg: impl Into<Msg>,
     ) -> Result<(), WebsocketMessageError> {
         if let Some(session) = self.sessions.get(&id) {
             let message = msg.into();
@@ -414,29 +505,48 @@ impl WsServer {
         }
     }
 
-    /// Send message directly to the user.
-    async fn broadcast(&self, msg: impl Into<String>) -> Result<(), WebsocketMessageError> {
-        log::debug!("Broadcasting message");
+    /// Whether the client connection uses the profile. Every client matches
+    /// when there is no profile.
+    fn client_has_profile(&self, id: ConnId, profile: Option<&str>) -> bool {
+        profile.is_none_or(|profile| {
+            self.client_profiles
+                .get(&id)
+                .is_some_and(|client_profile| client_profile == profile)
+        })
+    }
+
+    /// Send message to every client, or only the clients using `profile`.
+    async fn broadcast(
+        &self,
+        profile: Option<&str>,
+        msg: impl Into<Msg>,
+    ) -> Result<(), WebsocketMessageError> {
+        log::debug!("Broadcasting message profile={profile:?}");
         let message = msg.into();
 
-        for session in self.clients.values() {
+        for (id, session) in &self.clients {
+            if !self.client_has_profile(*id, profile) {
+                continue;
+            }
             // errors if client disconnected abruptly and hasn't been timed-out yet
             session.send(message.clone())?
         }
         Ok(())
     }
 
-    /// Send message directly to the user.
+    /// Send message to every client except `ids`, or only the clients using
+    /// `profile`.
     async fn broadcast_except(
         &self,
         ids: &[ConnId],
-        msg: impl Into<String>,
+        profile: Option<&str>,
+        msg: impl Into<Msg>,
     ) -> Result<(), WebsocketMessageError> {
-        log::debug!("Broadcasting message except {ids:?}");
+        log::debug!("Broadcasting message except {ids:?} profile={profile:?}");
         let message = msg.into();
 
         for (id, session) in &self.clients {
-            if ids.iter().any(|exclude| *exclude == *id) {
+            if ids.iter().any(|exclude| *exclude == *id) || !self.client_has_profile(*id, profile) {
                 continue;
             }
             // errors if client disconnected abruptly and hasn't been timed-out yet
@@ -450,6 +560,8 @@ impl WsServer {
         &mut self,
         client_id: String,
         sender: bool,
+        protocol_version: u32,
+        profile: Option<String>,
         tx: mpsc::UnboundedSender<Msg>,
     ) -> Result<ConnId, DatabaseError> {
         // register session with random connection ID
@@ -460,12 +572,34 @@ impl WsServer {
         self.sessions.insert(id, tx.clone());
 
         if sender {
-            log::info!("connect: Adding sender connection client_id={client_id} conn_id={id}");
+            log::info!("connect: Adding sender connection client_id={client_id} conn_id={id} protocol_version={protocol_version}");
             upsert_connection(&client_id, &id.to_string()).await?;
             CACHE_CONNECTIONS_MAP.write().unwrap().insert(client_id, id);
+            CACHE_PROTOCOL_VERSIONS
+                .write()
+                .unwrap()
+                .insert(id, protocol_version);
+
+            if protocol_version >= FLOW_CONTROL_PROTOCOL_VERSION {
+                self.flow_controlled_connections.insert(id);
+                let body = TunnelRequest::Handshake(TunnelHandshake {
+                    protocol_version: protocol_version.min(TUNNEL_PROTOCOL_VERSION),
+                    window: REQUEST_WINDOW,
+                    #[cfg(feature = "compression")]
+                    compression: moosicbox_tunnel::compression::SUPPORTED_COMPRESSIONS.to_vec(),
+                    #[cfg(not(feature = "compression"))]
+                    compression: vec![],
+                });
+                if let Err(err) = tx.send(serde_json::to_string(&body).unwrap().into()) {
+                    log::error!("connect: Failed to send handshake to conn_id={id}: {err:?}");
+                }
+            }
         } else {
-            log::info!("connect: Adding client connection client_id={client_id} conn_id={id}");
+            log::info!("connect: Adding client connection client_id={client_id} conn_id={id} profile={profile:?}");
             self.clients.insert(id, tx.clone());
+            if let Some(profile) = profile {
+                self.client_profiles.insert(id, profile);
+            }
         }
 
         let count = self.visitor_count.fetch_add(1, Ordering::SeqCst) + 1;
@@ -500,6 +634,12 @@ impl WsServer {
                 }
             });
 
+        CACHE_PROTOCOL_VERSIONS.write().unwrap().remove(&conn_id);
+        self.flow_controlled_connections.remove(&conn_id);
+        self.request_windows
+            .retain(|_, window| window.conn_id != conn_id);
+        REQUEST_BODY_WINDOWS.remove_connection(conn_id);
+
         // remove sender
         if self.sessions.remove(&conn_id).is_some() {
             log::debug!("disconnect: Removed client session conn_id={conn_id}");
@@ -507,6 +647,7 @@ impl WsServer {
         if self.clients.remove(&conn_id).is_some() {
             log::info!("disconnect: Removed client connection conn_id={conn_id}");
         }
+        self.client_profiles.remove(&conn_id);
 
         Ok(())
     }
@@ -531,13 +672,18 @@ pub enum ConnectionIdError {
 static CACHE_CONNECTIONS_MAP: LazyLock<std::sync::RwLock<HashMap<String, usize>>> =
     LazyLock::new(|| std::sync::RwLock::new(HashMap::new()));
 
+static CACHE_PROTOCOL_VERSIONS: LazyLock<std::sync::RwLock<HashMap<ConnId, u32>>> =
+    LazyLock::new(|| std::sync::RwLock::new(HashMap::new()));
+
 impl service::Handle {
     /// Register client message sender and obtain connection ID.
     pub async fn connect(
         &self,
         client_id: &str,
         sender: bool,
-        conn_tx: mpsc::UnboundedSender<String>,
+        protocol_version: u32,
+        profile: Option<String>,
+        conn_tx: mpsc::UnboundedSender<Msg>,
     ) -> Result<ConnId, CommanderError> {
         let (res_tx, res_rx) = oneshot::channel();
         // unwrap: ws server should not have been dropped
@@ -546,6 +692,8 @@ impl service::Handle {
             res_tx,
             client_id: client_id.to_string(),
             sender,
+            protocol_version,
+            profile,
         })
         .await?;
 
@@ -629,3 +777,15 @@ pub async fn get_connection_id(client_id: &str) -> Result<usize, ConnectionIdErr
         Ok(conn_id)
     }
 }
+
+/// The protocol version the sender connection advertised when it connected.
+/// Connections to other tunnel server instances are assumed to speak version
+/// 1.
+pub fn get_protocol_version(conn_id: ConnId) -> u32 {
+    CACHE_PROTOCOL_VERSIONS
+        .read()
+        .unwrap()
+        .get(&conn_id)
+        .copied()
+        .unwrap_or(1)
+}
@@ -1,4 +1,8 @@
 use moosicbox_audio_zone::models::{ApiAudioZoneWithSession, CreateAudioZone};
+use moosicbox_database::{
+    changes::{ChangeOperation, DatabaseChange},
+    DatabaseValue,
+};
 use moosicbox_session::models::{
     ApiConnection, ApiPlaybackTarget, ApiSession, ApiUpdateSession, CreateSession, DeleteSession,
     RegisterConnection, RegisterPlayer, UpdateSession,
@@ -42,6 +46,7 @@ pub enum OutboundPayload {
     ScanEvent(ScanEventPayload),
     Connections(ConnectionsPayload),
     SetSeek(SetSeekPayload),
+    DatabaseChanges(DatabaseChangesPayload),
 }
 
 impl std::fmt::Display for OutboundPayload {
@@ -152,3 +157,78 @@ pub struct SetSeek {
     pub playback_target: ApiPlaybackTarget,
     pub seek: u64,
 }
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DatabaseChangesPayload {
+    pub payload: ApiDatabaseChanges,
+}
+
+/// The changes made to the database tables of a profile (or to the shared
+/// config tables when `profile` is `None`) since the last payload.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiDatabaseChanges {
+    pub profile: Option<String>,
+    /// Some changes were missed, so any data derived from the database
+    /// should be fetched again.
+    pub resync: bool,
+    pub changes: Vec<ApiDatabaseChange>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiDatabaseChange {
+    pub table: String,
+    pub operation: ApiChangeOperation,
+    pub keys: Vec<Value>,
+}
+
+impl From<&DatabaseChange> for ApiDatabaseChange {
+    fn from(value: &DatabaseChange) -> Self {
+        Self {
+            table: value.table.clone(),
+            operation: value.operation.into(),
+            keys: value.keys.iter().map(database_value_to_json).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ApiChangeOperation {
+    Insert,
+    Update,
+    Upsert,
+    Delete,
+}
+
+impl From<ChangeOperation> for ApiChangeOperation {
+    fn from(value: ChangeOperation) -> Self {
+        match value {
+            ChangeOperation::Insert => Self::Insert,
+            ChangeOperation::Update => Self::Update,
+            ChangeOperation::Upsert => Self::Upsert,
+            ChangeOperation::Delete => Self::Delete,
+        }
+    }
+}
+
+fn database_value_to_json(value: &DatabaseValue) -> Value {
+    match value {
+        DatabaseValue::String(x) | DatabaseValue::StringOpt(Some(x)) => Value::from(x.as_str()),
+        DatabaseValue::Bool(x) | DatabaseValue::BoolOpt(Some(x)) => Value::from(*x),
+        DatabaseValue::Number(x) | DatabaseValue::NumberOpt(Some(x)) => Value::from(*x),
+        DatabaseValue::UNumber(x) | DatabaseValue::UNumberOpt(Some(x)) => Value::from(*x),
+        DatabaseValue::Real(x) | DatabaseValue::RealOpt(Some(x)) => Value::from(*x),
+        DatabaseValue::DateTime(x) => Value::from(x.to_string()),
+        DatabaseValue::Null
+        | DatabaseValue::StringOpt(None)
+        | DatabaseValue::BoolOpt(None)
+        | DatabaseValue::NumberOpt(None)
+        | DatabaseValue::UNumberOpt(None)
+        | DatabaseValue::RealOpt(None)
+        | DatabaseValue::NowAdd(_)
+        | DatabaseValue::Now => Value::Null,
+    }
+}
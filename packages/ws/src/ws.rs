@@ -1,6 +1,6 @@
 use core::fmt;
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, BTreeSet, HashMap},
     future::Future,
     num::ParseIntError,
     pin::Pin,
@@ -12,6 +12,7 @@ use log::{debug, info, trace};
 use moosicbox_audio_zone::models::CreateAudioZone;
 use moosicbox_core::sqlite::{db::DbError, models::ToApi as _};
 use moosicbox_database::{
+    changes::DatabaseChange,
     config::ConfigDatabase,
     profiles::{LibraryDatabase, PROFILES},
 };
@@ -28,9 +29,9 @@ use serde_json::Value;
 use thiserror::Error;
 
 use crate::models::{
-    AudioZoneWithSessionsPayload, ConnectionIdPayload, ConnectionsPayload, DownloadEventPayload,
-    InboundPayload, OutboundPayload, ScanEventPayload, SessionUpdatedPayload, SessionsPayload,
-    SetSeekPayload,
+    ApiDatabaseChanges, AudioZoneWithSessionsPayload, ConnectionIdPayload, ConnectionsPayload,
+    DatabaseChangesPayload, DownloadEventPayload, InboundPayload, OutboundPayload,
+    ScanEventPayload, SessionUpdatedPayload, SessionsPayload, SetSeekPayload,
 };
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -73,6 +74,8 @@ pub struct WebsocketConnectionData {
 pub trait WebsocketSender: Send + Sync {
     async fn send(&self, connection_id: &str, data: &str) -> Result<(), WebsocketSendError>;
     async fn send_all(&self, data: &str) -> Result<(), WebsocketSendError>;
+    /// Sends to every connection using the library profile.
+    async fn send_all_profile(&self, profile: &str, data: &str) -> Result<(), WebsocketSendError>;
     async fn send_all_except(
         &self,
         connection_id: &str,
@@ -136,15 +139,6 @@ pub async fn disconnect(
         .await
         .map_err(|_e| WebsocketDisconnectError::Unknown)?;
 
-    sender
-        .send_all(
-            &get_connections(db)
-                .await
-                .map_err(|_e| WebsocketDisconnectError::Unknown)?,
-        )
-        .await
-        .map_err(|_e| WebsocketDisconnectError::Unknown)?;
-
     info!("Disconnected {}", context.connection_id);
 
     Ok(Response {
@@ -217,9 +211,6 @@ pub async fn message(
         }
         InboundPayload::RegisterConnection(payload) => {
             register_connection(config_db, sender, context, &payload.payload).await?;
-
-            sender.send_all(&get_connections(config_db).await?).await?;
-
             Ok(())
         }
         InboundPayload::RegisterPlayers(payload) => {
@@ -228,28 +219,15 @@ pub async fn message(
                 .map_err(|e| WebsocketMessageError::Unknown {
                     message: e.to_string(),
                 })?;
-
-            broadcast_connections(config_db, sender)
-                .await
-                .map_err(|e| WebsocketMessageError::Unknown {
-                    message: e.to_string(),
-                })?;
-
             Ok(())
         }
         InboundPayload::CreateAudioZone(payload) => {
-            let db = db.ok_or(WebsocketMessageError::MissingProfile)?;
-            create_audio_zone(config_db, &db, sender, context, &payload.payload).await?;
-
-            sender
-                .send_all_except(&context.connection_id, &get_connections(config_db).await?)
-                .await?;
-
+            create_audio_zone(config_db, &payload.payload).await?;
             Ok(())
         }
         InboundPayload::CreateSession(payload) => {
             let db = db.ok_or(WebsocketMessageError::MissingProfile)?;
-            create_session(&db, sender, context, &payload.payload).await?;
+            create_session(&db, &payload.payload).await?;
             Ok(())
         }
         InboundPayload::UpdateSession(payload) => {
@@ -259,7 +237,7 @@ pub async fn message(
         }
         InboundPayload::DeleteSession(payload) => {
             let db = db.ok_or(WebsocketMessageError::MissingProfile)?;
-            delete_session(&db, sender, context, &payload.payload).await?;
+            delete_session(&db, &payload.payload).await?;
             Ok(())
         }
         InboundPayload::Ping(_) => {
@@ -317,11 +295,7 @@ pub async fn broadcast_audio_zones(
     ))?
     .to_string();
 
-    if send_all {
-        sender.send_all(&audio_zones_json).await
-    } else {
-        sender.send(&context.connection_id, &audio_zones_json).await
-    }
+    send_to_profile(sender, context, send_all, &audio_zones_json).await
 }
 
 pub async fn broadcast_sessions(
@@ -343,21 +317,31 @@ pub async fn broadcast_sessions(
     }))?
     .to_string();
 
-    if send_all {
-        sender.send_all(&sessions_json).await
-    } else {
-        sender.send(&context.connection_id, &sessions_json).await
-    }
+    send_to_profile(sender, context, send_all, &sessions_json).await
 }
 
-async fn create_session(
-    db: &LibraryDatabase,
+/// Sends the data to every connection of the context's profile, or only to
+/// the context's connection.
+async fn send_to_profile(
     sender: &impl WebsocketSender,
     context: &WebsocketContext,
-    payload: &CreateSession,
+    send_all: bool,
+    data: &str,
 ) -> Result<(), WebsocketSendError> {
+    if !send_all {
+        return sender.send(&context.connection_id, data).await;
+    }
+
+    let profile = context
+        .profile
+        .as_ref()
+        .ok_or_else(|| WebsocketSendError::Unknown("Missing profile".into()))?;
+
+    sender.send_all_profile(profile, data).await
+}
+
+async fn create_session(db: &LibraryDatabase, payload: &CreateSession) -> Result<(), DbError> {
     moosicbox_session::create_session(db, payload).await?;
-    broadcast_sessions(db, sender, context, true).await?;
     Ok(())
 }
 
@@ -425,15 +409,198 @@ pub async fn broadcast_connections(
     Ok(())
 }
 
-async fn create_audio_zone(
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum ChangeBroadcast {
+    Sessions { profile: String },
+    AudioZones { profile: String },
+    Connections,
+}
+
+/// The payloads that need to be re-sent after a change, on top of the change
+/// itself. Changes to the shared config tables affect the payloads of every
+/// profile.
+fn database_change_broadcasts(
+    change: &DatabaseChange,
+    profiles: &[String],
+) -> Vec<ChangeBroadcast> {
+    let profiles = change
+        .profile
+        .as_ref()
+        .map_or(profiles, std::slice::from_ref);
+    let sessions = || {
+        profiles.iter().map(|profile| ChangeBroadcast::Sessions {
+            profile: profile.clone(),
+        })
+    };
+    let audio_zones = || {
+        profiles.iter().map(|profile| ChangeBroadcast::AudioZones {
+            profile: profile.clone(),
+        })
+    };
+
+    match change.table.as_str() {
+        "sessions" | "session_playlists" | "session_playlist_tracks" | "active_players" => {
+            sessions().chain(audio_zones()).collect()
+        }
+        "audio_zones" | "audio_zone_players" | "audio_zone_sessions" => audio_zones().collect(),
+        "players" => sessions()
+            .chain(audio_zones())
+            .chain(std::iter::once(ChangeBroadcast::Connections))
+            .collect(),
+        "connections" => vec![ChangeBroadcast::Connections],
+        _ => vec![],
+    }
+}
+
+/// Sends the changes to the connections of their profile, or to every
+/// connection for changes to the shared config tables.
+async fn send_database_changes(
+    sender: &impl WebsocketSender,
+    changes: ApiDatabaseChanges,
+) -> Result<(), WebsocketSendError> {
+    let profile = changes.profile.clone();
+    let changes_json =
+        serde_json::to_value(OutboundPayload::DatabaseChanges(DatabaseChangesPayload {
+            payload: changes,
+        }))?
+        .to_string();
+
+    match profile {
+        Some(profile) => sender.send_all_profile(&profile, &changes_json).await,
+        None => sender.send_all(&changes_json).await,
+    }
+}
+
+/// The database of the profile, and a context to send its data to every
+/// connection of the profile with.
+fn profile_broadcast_context(profile: &str) -> Option<(LibraryDatabase, WebsocketContext)> {
+    let Some(db) = PROFILES.get(profile) else {
+        log::debug!("profile_broadcast_context: No database for profile '{profile}'");
+        return None;
+    };
+
+    let context = WebsocketContext {
+        connection_id: "self".to_string(),
+        profile: Some(profile.to_string()),
+        ..Default::default()
+    };
+
+    Some((db, context))
+}
+
+async fn send_change_broadcast(
     config_db: &ConfigDatabase,
-    db: &LibraryDatabase,
     sender: &impl WebsocketSender,
-    context: &WebsocketContext,
+    broadcast: &ChangeBroadcast,
+) -> Result<(), WebsocketSendError> {
+    match broadcast {
+        ChangeBroadcast::Sessions { profile } => {
+            if let Some((db, context)) = profile_broadcast_context(profile) {
+                broadcast_sessions(&db, sender, &context, true).await?;
+            }
+        }
+        ChangeBroadcast::AudioZones { profile } => {
+            if let Some((db, context)) = profile_broadcast_context(profile) {
+                broadcast_audio_zones(config_db, &db, sender, &context, true).await?;
+            }
+        }
+        ChangeBroadcast::Connections => broadcast_connections(config_db, sender).await?,
+    }
+
+    Ok(())
+}
+
+/// Sends the `changes` to the clients in a [`OutboundPayload::DatabaseChanges`]
+/// along with the current state of the sessions, audio zones and connections
+/// they affect, e.g. the sessions after a `session_playlist_tracks` insert.
+/// Clients only receive the changes and data of their own profile. Each
+/// payload is sent at most once, however many of the changes affect it.
+///
+/// # Errors
+///
+/// * If the updated data failed to be fetched or sent
+pub async fn broadcast_database_changes(
+    config_db: &ConfigDatabase,
+    sender: &impl WebsocketSender,
+    changes: &[DatabaseChange],
+) -> Result<(), WebsocketSendError> {
+    let mut changes_by_profile = BTreeMap::<_, Vec<_>>::new();
+
+    for change in changes {
+        changes_by_profile
+            .entry(change.profile.clone())
+            .or_default()
+            .push(change.into());
+    }
+
+    for (profile, changes) in changes_by_profile {
+        send_database_changes(
+            sender,
+            ApiDatabaseChanges {
+                profile,
+                resync: false,
+                changes,
+            },
+        )
+        .await?;
+    }
+
+    let profiles = PROFILES.names();
+    let broadcasts = changes
+        .iter()
+        .flat_map(|change| database_change_broadcasts(change, &profiles))
+        .collect::<BTreeSet<_>>();
+
+    debug!("broadcast_database_changes: broadcasts={broadcasts:?}");
+
+    for broadcast in &broadcasts {
+        send_change_broadcast(config_db, sender, broadcast).await?;
+    }
+
+    Ok(())
+}
+
+/// Tells every client to fetch its data again, and sends the current
+/// sessions, audio zones and connections. Used when database changes were
+/// missed, so the clients can't be told exactly what changed.
+///
+/// # Errors
+///
+/// * If the data failed to be fetched or sent
+pub async fn broadcast_database_resync(
+    config_db: &ConfigDatabase,
+    sender: &impl WebsocketSender,
+) -> Result<(), WebsocketSendError> {
+    send_database_changes(
+        sender,
+        ApiDatabaseChanges {
+            resync: true,
+            ..Default::default()
+        },
+    )
+    .await?;
+
+    for profile in PROFILES.names() {
+        let broadcasts = [
+            ChangeBroadcast::Sessions {
+                profile: profile.clone(),
+            },
+            ChangeBroadcast::AudioZones { profile },
+        ];
+
+        for broadcast in &broadcasts {
+            send_change_broadcast(config_db, sender, broadcast).await?;
+        }
+    }
+
+    broadcast_connections(config_db, sender).await
+}
+
+async fn create_audio_zone(
+    config_db: &ConfigDatabase,
     payload: &CreateAudioZone,
 ) -> Result<(), WebsocketMessageError> {
     moosicbox_audio_zone::create_audio_zone(config_db, payload).await?;
-    broadcast_sessions(db, sender, context, true).await?;
     Ok(())
 }
 
@@ -603,16 +770,8 @@ pub async fn update_session(
     Ok(())
 }
 
-async fn delete_session(
-    db: &LibraryDatabase,
-    sender: &impl WebsocketSender,
-    context: &WebsocketContext,
-    payload: &DeleteSession,
-) -> Result<(), WebsocketSendError> {
+async fn delete_session(db: &LibraryDatabase, payload: &DeleteSession) -> Result<(), DbError> {
     moosicbox_session::delete_session(db, payload.session_id).await?;
-
-    broadcast_sessions(db, sender, context, true).await?;
-
     Ok(())
 }
 
@@ -983,6 +983,8 @@ impl From<AlbumSort> for YtAlbumOrderDirection {
             AlbumSort::ReleaseDateDesc => YtAlbumOrderDirection::Desc,
             AlbumSort::DateAddedAsc => YtAlbumOrderDirection::Asc,
             AlbumSort::DateAddedDesc => YtAlbumOrderDirection::Desc,
+            AlbumSort::RatingAsc => YtAlbumOrderDirection::Asc,
+            AlbumSort::RatingDesc => YtAlbumOrderDirection::Desc,
         }
     }
 }
@@ -361,6 +361,8 @@ impl From<YtTrack> for Track {
             artist: value.artist,
             artist_id: value.artist_id.into(),
             file: None,
+            start_offset: None,
+            end_offset: None,
             artwork: value.album_cover,
             blur: false,
             bytes: 0,